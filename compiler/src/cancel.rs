@@ -0,0 +1,92 @@
+//! Cooperative cancellation of a compilation.
+//!
+//! A server embedding the compiler hands a [`CancellationToken`] to the
+//! pipeline and cancels it from another thread, or gives it a deadline. The
+//! expensive phases (topological sort, handler body construction, IR
+//! validation, codegen emission) call [`CancellationToken::check`] as they
+//! go and stop with an X001 error tagged with the phase they were in.
+//!
+//! wasm32 has neither threads nor a clock, so the wasm entry points use a
+//! step budget instead: every check spends one step, and the compilation is
+//! cancelled when the budget runs out.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::error::{CompilerError, Phase, Severity};
+
+/// Shared cancellation flag. Clones share the flag (and step budget), so
+/// cancelling any clone cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+    /// Checks left before the token cancels itself.
+    steps_left: Option<Arc<AtomicUsize>>,
+}
+
+impl CancellationToken {
+    /// A token that is only cancelled by [`cancel`](Self::cancel).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that also cancels itself once `deadline` has passed. Not for
+    /// wasm32, where reading the clock panics.
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// A token that also cancels itself after `steps` checks.
+    pub fn with_step_budget(steps: usize) -> Self {
+        Self {
+            steps_left: Some(Arc::new(AtomicUsize::new(steps))),
+            ..Self::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// X001 in `phase` if the token is cancelled, its deadline has passed or
+    /// its step budget is spent.
+    pub fn check(&self, phase: Phase) -> Result<(), Vec<CompilerError>> {
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        let exhausted = self.steps_left.as_ref().is_some_and(|steps| {
+            steps
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_err()
+        });
+        if expired || exhausted {
+            self.cancel();
+        }
+        if !self.is_cancelled() {
+            return Ok(());
+        }
+        Err(vec![CompilerError {
+            code: "X001".into(),
+            severity: Severity::Error,
+            phase,
+            message: "Compilation cancelled".into(),
+            node_id: None,
+            related: vec![],
+            path: vec![],
+        }])
+    }
+}
+
+/// [`CancellationToken::check`] for an optional token.
+pub fn check(cancel: Option<&CancellationToken>, phase: Phase) -> Result<(), Vec<CompilerError>> {
+    cancel.map_or(Ok(()), |cancel| cancel.check(phase))
+}
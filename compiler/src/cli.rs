@@ -0,0 +1,180 @@
+//! The `sixflow-compile` command line tool (feature `cli`): run the pipeline
+//! on a workflow JSON file from a terminal.
+//!
+//! ```text
+//! sixflow-compile compile <input.json> -o <dir>   write the CRE project, list its files
+//! sixflow-compile check <input.json>              parse, validate, lower, IR validate
+//! sixflow-compile ir <input.json>                 print the IR as `ir::io` JSON
+//! sixflow-compile diff <old.json> <new.json>      show how the generated files differ
+//! ```
+//!
+//! Diagnostics go to stderr, one per line as [`CompilerError`]'s `Display`
+//! renders them. The exit code is 0 on success, 1 when the workflow has
+//! errors (or, for `diff`, the outputs differ) and 2 for a usage or I/O
+//! problem.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::codegen::{self, CodegenOutput, DiffLine, FileDiff};
+use crate::compile;
+use crate::error::{CompilerError, Severity, format_json_path};
+use crate::ir::{self, WorkflowIR};
+
+const USAGE: &str = "usage:
+  sixflow-compile compile <input.json> -o <dir>
+  sixflow-compile check <input.json>
+  sixflow-compile ir <input.json>
+  sixflow-compile diff <old.json> <new.json>";
+
+/// Run the tool with `args` (without the program name), writing results to
+/// `out` and diagnostics to `err`. Returns the process exit code.
+pub fn run(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    match run_command(args, out, err) {
+        Ok(code) => code,
+        Err(message) => {
+            let _ = writeln!(err, "{}", message);
+            2
+        }
+    }
+}
+
+fn run_command(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> Result<i32, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["compile", input, "-o", dir] | ["compile", "-o", dir, input] => {
+            compile_to_dir(input, Path::new(dir), out, err)
+        }
+        ["check", input] => {
+            let (_, warnings) = match checked_ir(&read(input)?) {
+                Ok(checked) => checked,
+                Err(errors) => return Ok(report_errors(&errors, err)),
+            };
+            write_diagnostics(&warnings, err)?;
+            writeln!(out, "{}: ok", input).map_err(io_error)?;
+            Ok(0)
+        }
+        ["ir", input] => {
+            let (ir, warnings) = match checked_ir(&read(input)?) {
+                Ok(checked) => checked,
+                Err(errors) => return Ok(report_errors(&errors, err)),
+            };
+            write_diagnostics(&warnings, err)?;
+            writeln!(out, "{}", ir::io::to_json(&ir)).map_err(io_error)?;
+            Ok(0)
+        }
+        ["diff", old, new] => {
+            let mut outputs = Vec::new();
+            for input in [old, new] {
+                match checked_ir(&read(input)?) {
+                    Ok((ir, _)) => outputs.push(codegen::codegen(&ir)),
+                    Err(errors) => return Ok(report_errors(&errors, err)),
+                }
+            }
+            let diffs = outputs[0].diff(&outputs[1]);
+            write_diff(&diffs, out).map_err(io_error)?;
+            Ok(if diffs.is_empty() { 0 } else { 1 })
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn compile_to_dir(
+    input: &str,
+    dir: &Path,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> Result<i32, String> {
+    let compiled = match compile::compile(&read(input)?, None) {
+        Ok(compiled) => compiled,
+        Err(errors) => return Ok(report_errors(&errors, err)),
+    };
+    write_diagnostics(&compiled.warnings, err)?;
+    write_output(&compiled.output, dir)
+        .map_err(|e| format!("cannot write to {}: {}", dir.display(), e))?;
+    for file in &compiled.output.files {
+        writeln!(out, "{:>8}  {}", file.content.len(), file.path).map_err(io_error)?;
+    }
+    writeln!(
+        out,
+        "{} files written to {}",
+        compiled.output.files.len(),
+        dir.display()
+    )
+    .map_err(io_error)?;
+    Ok(0)
+}
+
+/// Write every generated file under `dir`, creating directories as needed.
+pub fn write_output(output: &CodegenOutput, dir: &Path) -> std::io::Result<()> {
+    for file in &output.files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, file.content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Everything [`compile::compile`] does before codegen: lowering with its
+/// warnings, then IR validation with errors pointing at their node.
+fn checked_ir(json: &str) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    let (workflow, ir, warnings) = compile::lower_json(json, None)?;
+    let errors = ir::validate_ir(&ir);
+    if !errors.is_empty() {
+        return Err(errors
+            .into_iter()
+            .map(|e| CompilerError::from(e).locate_node(&workflow))
+            .collect());
+    }
+    Ok((ir, warnings))
+}
+
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))
+}
+
+fn io_error(e: std::io::Error) -> String {
+    e.to_string()
+}
+
+fn report_errors(errors: &[CompilerError], err: &mut dyn Write) -> i32 {
+    let _ = write_diagnostics(errors, err);
+    1
+}
+
+fn write_diagnostics(diagnostics: &[CompilerError], err: &mut dyn Write) -> Result<(), String> {
+    for d in diagnostics {
+        let severity = match d.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        writeln!(err, "{}: {}", severity, d).map_err(io_error)?;
+        if !d.path.is_empty() {
+            writeln!(err, "  at {}", format_json_path(&d.path)).map_err(io_error)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_diff(diffs: &[FileDiff], out: &mut dyn Write) -> std::io::Result<()> {
+    for diff in diffs {
+        match diff {
+            FileDiff::Added { path } => writeln!(out, "added {}", path)?,
+            FileDiff::Removed { path } => writeln!(out, "removed {}", path)?,
+            FileDiff::Modified { path, lines } => {
+                writeln!(out, "modified {}", path)?;
+                for line in lines {
+                    match line {
+                        DiffLine::Unchanged(_) => {}
+                        DiffLine::Added(text) => writeln!(out, "+ {}", text)?,
+                        DiffLine::Removed(text) => writeln!(out, "- {}", text)?,
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
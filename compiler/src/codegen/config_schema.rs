@@ -1,12 +1,26 @@
 //! Emit `configSchema` Zod object and `type Config`.
+//!
+//! A field flagged `optional_in_config` is only read inside a branch arm, so
+//! its schema entry is nullable and `Config` types it as `T | null |
+//! undefined`. [`resolve_optional_config_refs`] gives every read of it a
+//! non-null assertion, so it still type-checks where a `T` is required.
+
+use std::borrow::Cow;
 
 use super::writer::CodeWriter;
 use crate::ir::types::*;
+use crate::ir::validate::visit_operation_value_exprs_mut;
+
+/// Name of the emitted config type; augmented fetch configs extend it.
+pub const CONFIG_TYPE: &str = "Config";
 
 /// Emit the configSchema and Config type.
 pub fn emit_config_schema(fields: &[ConfigField], w: &mut CodeWriter) {
     if fields.is_empty() {
-        w.line("type Config = Record<string, never>;");
+        w.line_fmt(format_args!(
+            "type {} = Record<string, never>;",
+            CONFIG_TYPE
+        ));
         return;
     }
 
@@ -26,21 +40,98 @@ pub fn emit_config_schema(fields: &[ConfigField], w: &mut CodeWriter) {
                 zod_call,
                 format_default(default, &field.zod_type)
             )
+        } else if field.optional_in_config {
+            // config.json carries `null` for these until the user fills them in.
+            format!("{}.nullable().optional()", zod_call)
         } else {
             zod_call
         };
 
-        w.line(&format!("{}: {},", field.name, with_default));
+        if field.optional_in_config && field.default_value.is_none() && !w.is_compact() {
+            w.line_fmt(format_args!(
+                "{}: {}, // only used inside a branch; may be left null",
+                field.name, with_default
+            ));
+        } else {
+            w.line_fmt(format_args!("{}: {},", field.name, with_default));
+        }
     }
     w.dedent();
     w.line("});");
     w.blank();
-    w.line("type Config = z.infer<typeof configSchema>;");
+    w.line_fmt(format_args!(
+        "type {} = z.infer<typeof configSchema>;",
+        CONFIG_TYPE
+    ));
+}
+
+/// Read config fields flagged `optional_in_config` as
+/// `runtime.config.<field>!`, on a copy of the IR. Fetch functions receive
+/// the asserted value through their augmented config. IRs without such
+/// fields are used as-is.
+pub fn resolve_optional_config_refs(ir: &WorkflowIR) -> Cow<'_, WorkflowIR> {
+    let optional: Vec<&str> = ir
+        .config_schema
+        .iter()
+        .filter(|f| f.optional_in_config && f.default_value.is_none())
+        .map(|f| f.name.as_str())
+        .collect();
+    if optional.is_empty() {
+        return Cow::Borrowed(ir);
+    }
+
+    let mut resolved = ir.clone();
+    rewrite_block(&mut resolved.handler_body, &optional);
+    Cow::Owned(resolved)
+}
+
+fn rewrite_block(block: &mut Block, optional: &[&str]) {
+    for step in &mut block.steps {
+        visit_operation_value_exprs_mut(&mut step.operation, &mut |expr| {
+            rewrite_expr(expr, optional)
+        });
+        if let Operation::Branch(branch) = &mut step.operation {
+            rewrite_block(&mut branch.true_branch, optional);
+            rewrite_block(&mut branch.false_branch, optional);
+        }
+    }
+}
+
+fn rewrite_expr(expr: &mut ValueExpr, optional: &[&str]) {
+    match expr {
+        ValueExpr::ConfigRef { field } => {
+            if optional.contains(&field.as_str()) {
+                *expr = ValueExpr::raw(format!("runtime.config.{}!", field));
+            }
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    rewrite_expr(value, optional);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                rewrite_expr(value, optional);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
 }
 
 fn format_default(value: &str, zod_type: &ZodType) -> String {
     match zod_type {
-        ZodType::String => format!("\"{}\"", value.replace('"', "\\\"")),
+        // A JSON string literal is also a valid TypeScript string literal.
+        ZodType::String => serde_json::Value::String(value.to_string()).to_string(),
         ZodType::Number => value.to_string(),
         ZodType::Boolean => value.to_string(),
         ZodType::Raw(_) => value.to_string(),
@@ -59,12 +150,16 @@ mod tests {
                 zod_type: ZodType::String,
                 default_value: Some("0 */10 * * * *".into()),
                 description: None,
+                referenced_by: vec![],
+                optional_in_config: false,
             },
             ConfigField {
                 name: "walletAddress".into(),
                 zod_type: ZodType::String,
                 default_value: None,
                 description: None,
+                referenced_by: vec![],
+                optional_in_config: false,
             },
         ];
 
@@ -85,4 +180,24 @@ mod tests {
         let out = w.finish();
         assert!(out.contains("type Config = Record<string, never>;"));
     }
+
+    #[test]
+    fn branch_only_field_is_optional() {
+        let fields = vec![ConfigField {
+            name: "slackWebhook".into(),
+            zod_type: ZodType::String,
+            default_value: None,
+            description: None,
+            referenced_by: vec!["notify".into()],
+            optional_in_config: true,
+        }];
+
+        let mut w = CodeWriter::new();
+        emit_config_schema(&fields, &mut w);
+        let out = w.finish();
+
+        assert!(out.contains(
+            "slackWebhook: z.string().nullable().optional(), // only used inside a branch; may be left null"
+        ));
+    }
 }
@@ -0,0 +1,103 @@
+//! Point references to destructured step outputs at their local names.
+//!
+//! A step with `destructure_fields` declares one const per field, prefixed
+//! with its variable name (`step_decode_1_from`), and no `step_decode_1`
+//! object. A binding such as `decode-1.from` is rewritten to that local before
+//! emission; a reference to the whole output rebuilds the object. The rewrite
+//! runs on a copy of the IR after validation, so scope checks still see the
+//! original bindings. IRs without destructuring are used as-is.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::value_expr::emit_field_access;
+use crate::ir::types::*;
+use crate::ir::validate::visit_operation_value_exprs_mut;
+
+pub fn resolve_destructured_refs(ir: &WorkflowIR) -> Cow<'_, WorkflowIR> {
+    let mut outputs = HashMap::new();
+    collect_destructured(&ir.handler_body, &mut outputs);
+    if outputs.is_empty() {
+        return Cow::Borrowed(ir);
+    }
+
+    let mut resolved = ir.clone();
+    rewrite_block(&mut resolved.handler_body, &outputs);
+    Cow::Owned(resolved)
+}
+
+fn collect_destructured(block: &Block, outputs: &mut HashMap<String, OutputBinding>) {
+    for step in &block.steps {
+        if let Some(out) = &step.output
+            && out.destructure_fields.is_some()
+        {
+            outputs.insert(step.id.clone(), out.clone());
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            collect_destructured(&branch.true_branch, outputs);
+            collect_destructured(&branch.false_branch, outputs);
+        }
+    }
+}
+
+fn rewrite_block(block: &mut Block, outputs: &HashMap<String, OutputBinding>) {
+    for step in &mut block.steps {
+        visit_operation_value_exprs_mut(&mut step.operation, &mut |expr| {
+            rewrite_expr(expr, outputs)
+        });
+        if let Operation::Branch(branch) = &mut step.operation {
+            rewrite_block(&mut branch.true_branch, outputs);
+            rewrite_block(&mut branch.false_branch, outputs);
+        }
+    }
+}
+
+fn rewrite_expr(expr: &mut ValueExpr, outputs: &HashMap<String, OutputBinding>) {
+    match expr {
+        ValueExpr::Binding(binding) => {
+            if let Some(out) = outputs.get(&binding.step_id)
+                && let Some(local) = local_expr(out, &binding.field_path)
+            {
+                *expr = ValueExpr::raw(local);
+            }
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    rewrite_expr(value, outputs);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                rewrite_expr(value, outputs);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
+/// `from.x` → `step_decode_1_from.x`, `items.0` → `step_decode_1_items[0]`;
+/// `None` when the path does not start with a destructured field.
+fn local_expr(out: &OutputBinding, field_path: &str) -> Option<String> {
+    if field_path.is_empty() {
+        return Some(out.destructured_object());
+    }
+    let head_end = field_path.find(['.', '[']).unwrap_or(field_path.len());
+    let (head, rest) = field_path.split_at(head_end);
+    out.destructured_vars()
+        .into_iter()
+        .find(|(field, _)| *field == head)
+        .map(|(_, local)| match rest.strip_prefix('.') {
+            Some(rest) => format!("{local}{}", emit_field_access(rest)),
+            None => format!("{local}{rest}"),
+        })
+}
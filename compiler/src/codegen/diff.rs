@@ -0,0 +1,331 @@
+//! File- and line-level diff between two codegen outputs, for the editor's
+//! "what changed" view.
+
+use std::collections::{HashMap, HashSet};
+
+use super::CodegenOutput;
+use super::handler::ARM_LABEL_PREFIX;
+use super::operations::AUTO_RETURN_MARKER;
+
+/// How one generated file differs between two outputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDiff {
+    /// Only present in the new output.
+    Added { path: String },
+    /// Only present in the old output.
+    Removed { path: String },
+    /// Present in both with different content.
+    Modified { path: String, lines: Vec<DiffLine> },
+}
+
+impl FileDiff {
+    pub fn path(&self) -> &str {
+        match self {
+            FileDiff::Added { path }
+            | FileDiff::Removed { path }
+            | FileDiff::Modified { path, .. } => path,
+        }
+    }
+}
+
+/// One line of a modified file, in new-file order with removals interleaved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+}
+
+impl CodegenOutput {
+    /// Compare `self` (old) with `other` (new). Files in `self` come first in
+    /// their original order, then files that only exist in `other`.
+    /// Identical files are omitted, and Returns synthesized by lowering are
+    /// ignored: they follow from the graph shape, not from a user edit.
+    pub fn diff(&self, other: &CodegenOutput) -> Vec<FileDiff> {
+        let new_files: HashMap<&str, &str> = other
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), &*f.content))
+            .collect();
+        let old_paths: HashSet<&str> = self.files.iter().map(|f| f.path.as_str()).collect();
+
+        let mut diffs = Vec::new();
+        for file in &self.files {
+            match new_files.get(file.path.as_str()) {
+                None => diffs.push(FileDiff::Removed {
+                    path: file.path.clone(),
+                }),
+                Some(new_content) => {
+                    let lines = diff_lines(&file.content, new_content);
+                    if lines.iter().any(|l| !matches!(l, DiffLine::Unchanged(_))) {
+                        diffs.push(FileDiff::Modified {
+                            path: file.path.clone(),
+                            lines,
+                        });
+                    }
+                }
+            }
+        }
+        for file in &other.files {
+            if !old_paths.contains(file.path.as_str()) {
+                diffs.push(FileDiff::Added {
+                    path: file.path.clone(),
+                });
+            }
+        }
+        diffs
+    }
+}
+
+/// Line diff, skipping auto-return lines. Branch-arm label comments that
+/// appear once in each file are matched first, so a step renamed inside the
+/// "KYC approved" arm is reported there, even when pairing lines by position
+/// would line it up with the other arm. The lines between matched labels
+/// are diffed on their own.
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = significant_lines(old);
+    let new = significant_lines(new);
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in matched_arm_labels(&old, &new) {
+        lines.extend(diff_segment(&old[i..anchor_i], &new[j..anchor_j]));
+        lines.push(DiffLine::Unchanged(old[anchor_i].to_string()));
+        (i, j) = (anchor_i + 1, anchor_j + 1);
+    }
+    lines.extend(diff_segment(&old[i..], &new[j..]));
+    lines
+}
+
+/// Positions of arm label lines that occur exactly once in both files,
+/// keeping the longest run that is in the same order in both.
+fn matched_arm_labels(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    let unique_labels = |lines: &[&str]| {
+        let mut seen: HashMap<String, Option<usize>> = HashMap::new();
+        for (at, line) in lines.iter().enumerate() {
+            if line
+                .trim_start()
+                .starts_with(&format!("// {}", ARM_LABEL_PREFIX))
+            {
+                seen.entry(line.to_string())
+                    .and_modify(|at| *at = None)
+                    .or_insert(Some(at));
+            }
+        }
+        seen
+    };
+    let new_labels = unique_labels(new);
+    let mut pairs: Vec<(usize, usize)> = unique_labels(old)
+        .into_iter()
+        .filter_map(|(line, at)| Some((at?, new_labels.get(&line).copied()??)))
+        .collect();
+    pairs.sort();
+
+    // Longest chain increasing in both positions; there are few labels.
+    let mut best: Vec<Vec<(usize, usize)>> = Vec::with_capacity(pairs.len());
+    for (k, &pair) in pairs.iter().enumerate() {
+        let mut chain = (0..k)
+            .filter(|&prev| pairs[prev].1 < pair.1)
+            .map(|prev| best[prev].clone())
+            .max_by_key(Vec::len)
+            .unwrap_or_default();
+        chain.push(pair);
+        best.push(chain);
+    }
+    best.into_iter().max_by_key(Vec::len).unwrap_or_default()
+}
+
+/// Most differences [`middle_snake`] looks through before giving up on a
+/// segment and reporting it as wholly removed and re-added. Keeps the diff of
+/// two unrelated large files from taking quadratic time.
+const MAX_SNAKE_DIFFERENCES: usize = 2_000;
+
+/// Shortest line diff (Myers, in linear space). Within a changed run the
+/// removed lines come before the added ones.
+fn diff_segment(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    conquer(old, new, &mut lines);
+    for run in lines.split_mut(|l| matches!(l, DiffLine::Unchanged(_))) {
+        run.sort_by_key(|l| matches!(l, DiffLine::Added(_)));
+    }
+    lines
+}
+
+/// Diff `old` against `new`, splitting both at the middle of a shortest edit
+/// script. The shared prefix and suffix are peeled off first, so small edits
+/// to large files stay cheap.
+fn conquer(old: &[&str], new: &[&str], lines: &mut Vec<DiffLine>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let unchanged = |line: &&str| DiffLine::Unchanged(line.to_string());
+    lines.extend(old[..prefix].iter().map(unchanged));
+    match middle_snake(old_mid, new_mid) {
+        Some((x, y)) => {
+            conquer(&old_mid[..x], &new_mid[..y], lines);
+            conquer(&old_mid[x..], &new_mid[y..], lines);
+        }
+        None => {
+            lines.extend(old_mid.iter().map(|l| DiffLine::Removed(l.to_string())));
+            lines.extend(new_mid.iter().map(|l| DiffLine::Added(l.to_string())));
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(unchanged));
+}
+
+/// Where to split `old` and `new` so that diffing the halves separately
+/// still gives a shortest edit script: the start of the middle snake, found
+/// by searching from both ends until the two searches meet. `None` when
+/// either side is empty or after [`MAX_SNAKE_DIFFERENCES`]. Expects the
+/// common prefix and suffix to be peeled off, so the split is never at
+/// either end.
+fn middle_snake(old: &[&str], new: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    if n == 0 || m == 0 {
+        return None;
+    }
+    let max_d = ((n + m + 1) / 2 + 1).min(MAX_SNAKE_DIFFERENCES as isize);
+    // Furthest x reached on each diagonal k = x - y, for paths from the start
+    // (`forward`) and from the end (`backward`, counting back from `n`).
+    let offset = max_d + 1;
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+
+    for d in 0..max_d {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            if odd && (k - delta).abs() < d && x + backward[at(delta - k)] >= n {
+                return Some((x0 as usize, y0 as usize));
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[at(delta - k)] >= n {
+                return Some(((n - x) as usize, (m - y) as usize));
+            }
+        }
+    }
+    None
+}
+
+fn significant_lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter(|l| !l.ends_with(AUTO_RETURN_MARKER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_diff_marks_replaced_line() {
+        let lines = diff_lines("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(
+            lines,
+            vec![
+                DiffLine::Unchanged("a".into()),
+                DiffLine::Removed("b".into()),
+                DiffLine::Added("B".into()),
+                DiffLine::Unchanged("c".into()),
+                DiffLine::Added("d".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_return_lines_are_ignored() {
+        let old = format!("a\nreturn \"x\"; {AUTO_RETURN_MARKER}\n");
+        let new = format!("a\nreturn \"y\"; {AUTO_RETURN_MARKER}\n");
+        assert_eq!(
+            diff_lines(&old, &new),
+            vec![DiffLine::Unchanged("a".into())]
+        );
+    }
+
+    #[test]
+    fn arm_labels_are_matched_before_other_lines() {
+        let old = "if (ok) {\n  // ── Arm: Approved ──\n  a();\n  b();\n  d();\n} else {\n  // ── Arm: Rejected ──\n  c();\n}\n";
+        let new = "if (ok) {\n  // ── Arm: Approved ──\n  c();\n} else {\n  // ── Arm: Rejected ──\n  a();\n  b();\n  d();\n}\n";
+        let lines = diff_lines(old, new);
+        // Pairing by position alone would keep a(), b() and d() and report the
+        // else and the Rejected label as moved instead.
+        let rejected = lines
+            .iter()
+            .position(|l| *l == DiffLine::Unchanged("  // ── Arm: Rejected ──".into()))
+            .expect("the Rejected label is matched");
+        assert!(lines.contains(&DiffLine::Unchanged("} else {".into())));
+        let removed_a = lines
+            .iter()
+            .position(|l| *l == DiffLine::Removed("  a();".into()))
+            .unwrap();
+        let added_a = lines
+            .iter()
+            .position(|l| *l == DiffLine::Added("  a();".into()))
+            .unwrap();
+        assert!(removed_a < rejected && rejected < added_a, "{:?}", lines);
+    }
+
+    #[test]
+    fn shortest_diff_of_interleaved_edits() {
+        let old = "a\nb\nc\nd\ne\nf\n";
+        let new = "x\nb\nc\ny\ne\nz\n";
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                DiffLine::Removed("a".into()),
+                DiffLine::Added("x".into()),
+                DiffLine::Unchanged("b".into()),
+                DiffLine::Unchanged("c".into()),
+                DiffLine::Removed("d".into()),
+                DiffLine::Added("y".into()),
+                DiffLine::Unchanged("e".into()),
+                DiffLine::Removed("f".into()),
+                DiffLine::Added("z".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelated_large_files_diff_quickly() {
+        // Nothing in common: the search gives up and the whole file is
+        // replaced, instead of filling a 50k x 50k table.
+        let old: String = (0..50_000).map(|i| format!("old {i}\n")).collect();
+        let new: String = (0..50_000).map(|i| format!("new {i}\n")).collect();
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.len(), 100_000);
+        assert!(matches!(&lines[0], DiffLine::Removed(l) if l == "old 0"));
+        assert!(matches!(&lines[50_000], DiffLine::Added(l) if l == "new 0"));
+    }
+}
@@ -5,10 +5,16 @@
 //!
 //! **Scope challenge:** Fetch functions only receive `(sendRequester, config)`.
 //! Handler-scoped data (trigger data, step bindings, secrets) must be passed through
-//! an augmented config object. See `DynamicRef` and `FetchContext`.
+//! an augmented config object. See `DynamicRef` and `FetchContext`. A
+//! `SecretRef` in the URL, a header or the body is fetched in the handler and
+//! passed the same way, so the secret never appears in the generated source.
 
 use std::collections::HashMap;
 
+use serde::Serialize;
+
+use super::config_schema::CONFIG_TYPE;
+use super::names::{self, Names};
 use super::value_expr::{emit_value_expr, emit_value_expr_init};
 use super::writer::CodeWriter;
 use crate::ir::types::*;
@@ -21,6 +27,9 @@ use crate::ir::types::*;
 pub struct FetchFnInfo {
     pub fn_name: String,
     pub step_id: String,
+    /// What the default helper names are built from: the step id, or with
+    /// content naming the operation's [`names::content_key`].
+    pub name_key: String,
     pub kind: FetchFnKind,
 }
 
@@ -30,7 +39,7 @@ pub enum FetchFnKind {
 }
 
 /// A handler-scoped value that must be passed through the augmented config.
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct DynamicRef {
     /// Key in the augmented config, e.g. `_dyn0`.
     pub config_key: String,
@@ -38,48 +47,75 @@ pub struct DynamicRef {
     pub handler_expr: ValueExpr,
 }
 
-/// Context for a single fetch function: dynamic refs + auth info.
+/// Context for a single fetch function: its names, dynamic refs + auth info.
+#[derive(Serialize)]
 pub struct FetchContext {
+    /// Name of the emitted fetch function.
+    pub fn_name: String,
+    /// Name of the handler-side augmented config, when the step builds one.
+    pub cfg_var: Option<String>,
+    /// Type of `cfg_var`, also the fetch function's config parameter type.
+    pub cfg_type: Option<String>,
     pub dynamic_refs: Vec<DynamicRef>,
     pub has_auth: bool,
     /// For AI calls: the secret name to fetch the API key from.
     pub ai_api_key_secret: Option<String>,
+    /// Handler-level variable of a secret hoisted by `secrets`; when set, the
+    /// step reuses it instead of calling `getSecret` itself.
+    pub shared_secret_var: Option<String>,
 }
 
 // =============================================================================
 // COLLECT
 // =============================================================================
 
-/// Collect all HttpRequest and AiCall steps from the IR (including inside branches).
-pub fn collect_fetch_fns(block: &Block) -> Vec<FetchFnInfo> {
+/// Collect all HttpRequest and AiCall steps from the IR (including inside
+/// branches). With `content_named`, helpers are named after the operation
+/// rather than the step; a repeated name gets a `_2`, `_3`, ... suffix.
+pub fn collect_fetch_fns(block: &Block, content_named: bool) -> Vec<FetchFnInfo> {
     let mut fns = Vec::new();
-    collect_from_block(block, &mut fns);
+    collect_from_block(block, content_named, &mut HashMap::new(), &mut fns);
     fns
 }
 
-fn collect_from_block(block: &Block, fns: &mut Vec<FetchFnInfo>) {
+fn collect_from_block(
+    block: &Block,
+    content_named: bool,
+    seen: &mut HashMap<String, usize>,
+    fns: &mut Vec<FetchFnInfo>,
+) {
     for step in &block.steps {
-        match &step.operation {
-            Operation::HttpRequest(op) => {
-                fns.push(FetchFnInfo {
-                    fn_name: format!("fetch_{}", step.id.replace('-', "_")),
-                    step_id: step.id.clone(),
-                    kind: FetchFnKind::Http(op.clone()),
-                });
-            }
-            Operation::AiCall(op) => {
-                fns.push(FetchFnInfo {
-                    fn_name: format!("fetch_{}", step.id.replace('-', "_")),
-                    step_id: step.id.clone(),
-                    kind: FetchFnKind::Ai(op.clone()),
-                });
-            }
+        let kind = match &step.operation {
+            Operation::HttpRequest(op) => FetchFnKind::Http(op.clone()),
+            Operation::AiCall(op) => FetchFnKind::Ai(op.clone()),
             Operation::Branch(branch) => {
-                collect_from_block(&branch.true_branch, fns);
-                collect_from_block(&branch.false_branch, fns);
+                collect_from_block(&branch.true_branch, content_named, seen, fns);
+                collect_from_block(&branch.false_branch, content_named, seen, fns);
+                continue;
             }
-            _ => {}
-        }
+            _ => continue,
+        };
+        let name_key = if content_named {
+            let content_key = match &kind {
+                FetchFnKind::Http(op) => names::content_key(op),
+                FetchFnKind::Ai(op) => names::content_key(op),
+            };
+            let count = seen.entry(content_key.clone()).or_default();
+            *count += 1;
+            if *count == 1 {
+                content_key
+            } else {
+                format!("{}_{}", content_key, count)
+            }
+        } else {
+            step.id.clone()
+        };
+        fns.push(FetchFnInfo {
+            fn_name: names::fetch_fn_name(&name_key),
+            step_id: step.id.clone(),
+            name_key,
+            kind,
+        });
     }
 }
 
@@ -100,12 +136,19 @@ pub fn build_fetch_context(op: &HttpRequestOp) -> FetchContext {
     }
     if let Some(ref body) = op.body {
         scan_expr(&body.data, &mut refs, &mut seen, &mut counter);
+        for (_, v) in &body.fields {
+            scan_expr(v, &mut refs, &mut seen, &mut counter);
+        }
     }
 
     FetchContext {
+        fn_name: String::new(),
+        cfg_var: None,
+        cfg_type: None,
         dynamic_refs: refs,
         has_auth: op.authentication.is_some(),
         ai_api_key_secret: None,
+        shared_secret_var: None,
     }
 }
 
@@ -118,7 +161,11 @@ fn scan_expr(
     match expr {
         // RawExpr is used for handler-scoped bare variable refs (e.g. EVM log trigger args).
         // They must be passed through the augmented config just like Binding/TriggerDataRef.
-        ValueExpr::Binding(_) | ValueExpr::TriggerDataRef { .. } | ValueExpr::RawExpr { .. } => {
+        // Secrets can only be read in the handler, through `runtime`.
+        ValueExpr::Binding(_)
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {
             let key_str = emit_value_expr(expr);
             if !seen.contains_key(&key_str) {
                 let config_key = format!("_dyn{}", *counter);
@@ -137,6 +184,15 @@ fn scan_expr(
                 }
             }
         }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                scan_expr(value, refs, seen, counter);
+            }
+        }
         _ => {} // Literal, ConfigRef are fine in fetch scope
     }
 }
@@ -144,7 +200,10 @@ fn scan_expr(
 /// Substitute handler-scoped refs in a ValueExpr with `config._dynN` references.
 fn subst_expr(expr: &ValueExpr, mapping: &HashMap<String, String>) -> ValueExpr {
     match expr {
-        ValueExpr::Binding(_) | ValueExpr::TriggerDataRef { .. } | ValueExpr::RawExpr { .. } => {
+        ValueExpr::Binding(_)
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {
             let key = emit_value_expr(expr);
             if let Some(config_key) = mapping.get(&key) {
                 ValueExpr::RawExpr {
@@ -166,6 +225,15 @@ fn subst_expr(expr: &ValueExpr, mapping: &HashMap<String, String>) -> ValueExpr
                 .collect();
             ValueExpr::Template { parts: new_parts }
         }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => ValueExpr::conditional(
+            subst_expr(cond, mapping),
+            subst_expr(then, mapping),
+            subst_expr(otherwise, mapping),
+        ),
         _ => expr.clone(),
     }
 }
@@ -183,9 +251,13 @@ pub fn build_ai_fetch_context(op: &AiCallOp) -> FetchContext {
     scan_expr(&op.user_prompt, &mut refs, &mut seen, &mut counter);
 
     FetchContext {
+        fn_name: String::new(),
+        cfg_var: None,
+        cfg_type: None,
         dynamic_refs: refs,
         has_auth: true,
         ai_api_key_secret: Some(op.api_key_secret.clone()),
+        shared_secret_var: None,
     }
 }
 
@@ -201,44 +273,155 @@ fn build_subst_map(ctx: &FetchContext) -> HashMap<String, String> {
 // EMIT
 // =============================================================================
 
-/// Emit all top-level fetch functions. Returns a map from step_id → FetchContext.
+/// Emit all top-level fetch functions, named by `names`. `output_types` maps
+/// step ids to their output's TypeScript type, for typing augmented configs.
+/// Returns a map from step_id → FetchContext.
 pub fn emit_fetch_fns(
     fetch_fns: &[FetchFnInfo],
+    output_types: &HashMap<&str, &str>,
+    names: &mut Names,
     w: &mut CodeWriter,
 ) -> HashMap<String, FetchContext> {
     let mut contexts = HashMap::new();
-    for f in fetch_fns {
+    for (i, f) in fetch_fns.iter().enumerate() {
+        if w.cancelled() {
+            break;
+        }
+        let mut ctx = match &f.kind {
+            FetchFnKind::Http(op) => build_fetch_context(op),
+            FetchFnKind::Ai(op) => build_ai_fetch_context(op),
+        };
+        ctx.fn_name = names.fetch_fn(i, &f.fn_name);
+        // HTTP steps pass their auth token through the config; AI steps pass
+        // the API key as a separate argument.
+        let needs_cfg = match &f.kind {
+            FetchFnKind::Http(_) => !ctx.dynamic_refs.is_empty() || ctx.has_auth,
+            FetchFnKind::Ai(_) => !ctx.dynamic_refs.is_empty(),
+        };
+        let config_type = if needs_cfg {
+            ctx.cfg_var = Some(names.fetch_cfg(i, &names::fetch_cfg_name(&f.name_key)));
+            let type_name = names.fetch_cfg_type(i, &names::fetch_cfg_type_name(&f.name_key));
+            let string_slots = match &f.kind {
+                FetchFnKind::Http(op) => http_string_slots(op),
+                FetchFnKind::Ai(op) => vec![&op.base_url, &op.model],
+            };
+            let string_slots: Vec<String> = string_slots.into_iter().map(emit_value_expr).collect();
+            let auth = matches!(f.kind, FetchFnKind::Http(_)) && ctx.has_auth;
+            emit_config_type(&type_name, &ctx, auth, &string_slots, output_types, w);
+            ctx.cfg_type = Some(type_name.clone());
+            type_name
+        } else {
+            CONFIG_TYPE.to_string()
+        };
         match &f.kind {
-            FetchFnKind::Http(op) => {
-                let ctx = build_fetch_context(op);
-                emit_http_fetch_fn(&f.fn_name, op, &ctx, w);
-                contexts.insert(f.step_id.clone(), ctx);
-            }
-            FetchFnKind::Ai(op) => {
-                let ctx = build_ai_fetch_context(op);
-                emit_ai_fetch_fn(&f.fn_name, op, &ctx, w);
-                contexts.insert(f.step_id.clone(), ctx);
-            }
+            FetchFnKind::Http(op) => emit_http_fetch_fn(&ctx.fn_name, &config_type, op, &ctx, w),
+            FetchFnKind::Ai(op) => emit_ai_fetch_fn(&ctx.fn_name, &config_type, op, &ctx, w),
         }
+        contexts.insert(f.step_id.clone(), ctx);
         w.blank();
     }
     contexts
 }
 
-fn emit_http_fetch_fn(fn_name: &str, op: &HttpRequestOp, ctx: &FetchContext, w: &mut CodeWriter) {
-    let method = match op.method {
-        HttpMethod::Get => "GET",
-        HttpMethod::Post => "POST",
-        HttpMethod::Put => "PUT",
-        HttpMethod::Delete => "DELETE",
-        HttpMethod::Patch => "PATCH",
-        HttpMethod::Head => "HEAD",
+/// Emit the type of a fetch function's augmented config:
+/// `type Fetch_http_1_Config = Config & { _authToken: string; _dyn0: number; };`
+///
+/// A dynamic ref takes the type of the step output it reads, when that type
+/// spells the field out. Otherwise it is `string` if the ref is used on its
+/// own where a string is required (`string_slots`, as emitted), and
+/// `unknown` if not.
+fn emit_config_type(
+    type_name: &str,
+    ctx: &FetchContext,
+    auth: bool,
+    string_slots: &[String],
+    output_types: &HashMap<&str, &str>,
+    w: &mut CodeWriter,
+) {
+    w.block_open_fmt(format_args!("type {} = {} &", type_name, CONFIG_TYPE));
+    if auth {
+        w.line("_authToken: string;");
+    }
+    for dyn_ref in &ctx.dynamic_refs {
+        let ts_type = dynamic_ref_type(&dyn_ref.handler_expr, output_types).unwrap_or_else(|| {
+            if string_slots.contains(&emit_value_expr(&dyn_ref.handler_expr)) {
+                "string".to_string()
+            } else {
+                "unknown".to_string()
+            }
+        });
+        w.line_fmt(format_args!("{}: {};", dyn_ref.config_key, ts_type));
+    }
+    w.block_close_semi();
+    w.blank();
+}
+
+/// HTTP request parts that must be strings, other than template literals.
+fn http_string_slots(op: &HttpRequestOp) -> Vec<&ValueExpr> {
+    let mut slots = vec![&op.url];
+    slots.extend(op.headers.iter().map(|(_, v)| v));
+    if let Some(body) = &op.body
+        && !matches!(body.content_type, HttpContentType::Json)
+    {
+        slots.push(&body.data);
+    }
+    slots
+}
+
+/// Type of a handler-scoped value, when statically known and not `any`.
+fn dynamic_ref_type(expr: &ValueExpr, output_types: &HashMap<&str, &str>) -> Option<String> {
+    let ValueExpr::Binding(b) = expr else {
+        return None;
     };
+    let mut ts_type = output_types.get(b.step_id.as_str())?.trim();
+    for segment in b.field_path.split('.').filter(|s| !s.is_empty()) {
+        if segment.bytes().all(|c| c.is_ascii_digit()) {
+            ts_type = ts_type.strip_suffix("[]")?.trim();
+            continue;
+        }
+        let (name, indexes) = segment.split_once('[').unwrap_or((segment, ""));
+        ts_type = object_member_type(ts_type, name)?;
+        for _ in indexes.matches(']') {
+            ts_type = ts_type.strip_suffix("[]")?.trim();
+        }
+    }
+    (ts_type != "any").then(|| ts_type.to_string())
+}
 
-    let needs_any = !ctx.dynamic_refs.is_empty() || ctx.has_auth;
-    let config_type = if needs_any { "any" } else { "Config" };
+/// `number` for `statusCode` in `{ statusCode: number; body: any }`.
+fn object_member_type<'a>(ts_type: &'a str, name: &str) -> Option<&'a str> {
+    let inner = ts_type.strip_prefix('{')?.strip_suffix('}')?;
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut members = Vec::new();
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' | '[' | '(' | '<' => depth += 1,
+            '}' | ']' | ')' | '>' => depth = depth.saturating_sub(1),
+            ';' | ',' if depth == 0 => {
+                members.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    members.push(&inner[start..]);
+    members.into_iter().find_map(|member| {
+        let (key, ts_type) = member.split_once(':')?;
+        (key.trim() == name).then(|| ts_type.trim())
+    })
+}
 
-    w.block_open(&format!(
+fn emit_http_fetch_fn(
+    fn_name: &str,
+    config_type: &str,
+    op: &HttpRequestOp,
+    ctx: &FetchContext,
+    w: &mut CodeWriter,
+) {
+    let method = op.method.as_str();
+
+    w.block_open_fmt(format_args!(
         "const {} = (sendRequester: HTTPSendRequester, config: {}) =>",
         fn_name, config_type
     ));
@@ -248,17 +431,45 @@ fn emit_http_fetch_fn(fn_name: &str, op: &HttpRequestOp, ctx: &FetchContext, w:
     // Build request object
     w.block_open("const req =");
     let url_expr = subst_expr(&op.url, &subst);
-    w.line(&format!("url: {},", emit_value_expr_init(&url_expr)));
-    w.line(&format!("method: \"{}\" as const,", method));
+    w.line_fmt(format_args!("url: {},", emit_value_expr_init(&url_expr)));
+    w.line_fmt(format_args!("method: \"{}\" as const,", method));
 
-    // Headers (user-defined + auth)
-    let has_user_headers = !op.headers.is_empty();
+    // Headers (user-defined + auth). Statically empty headers are dropped and
+    // ones that may resolve to "" are spread in only when non-empty.
+    let user_headers: Vec<(&str, ValueExpr, HeaderPresence)> = op
+        .headers
+        .iter()
+        .map(|(key, value)| {
+            let v = subst_expr(value, &subst);
+            let presence = header_presence(&v);
+            (key.as_str(), v, presence)
+        })
+        .filter(|(_, _, presence)| *presence != HeaderPresence::Never)
+        .collect();
     let has_auth = op.authentication.is_some();
-    if has_user_headers || has_auth {
+    let default_content_type = op
+        .body
+        .as_ref()
+        .filter(|_| {
+            !op.headers
+                .iter()
+                .any(|(key, _)| key.eq_ignore_ascii_case("content-type"))
+        })
+        .map(|body| body.content_type.mime_type());
+    if !user_headers.is_empty() || has_auth || default_content_type.is_some() {
         w.block_open("headers:");
-        for (key, value) in &op.headers {
-            let v = subst_expr(value, &subst);
-            w.line(&format!("\"{}\": {},", key, emit_value_expr_init(&v)));
+        if let Some(mime_type) = default_content_type {
+            w.line_fmt(format_args!("\"Content-Type\": \"{}\",", mime_type));
+        }
+        for (key, v, presence) in &user_headers {
+            let value = emit_value_expr_init(v);
+            if *presence == HeaderPresence::IfNonEmpty {
+                w.line_fmt(format_args!(
+                    "...({value} ? {{ \"{key}\": {value} }} : {{}}),"
+                ));
+            } else {
+                w.line_fmt(format_args!("\"{}\": {},", key, value));
+            }
         }
         if has_auth {
             w.line("\"Authorization\": `Bearer ${config._authToken}`,");
@@ -267,28 +478,46 @@ fn emit_http_fetch_fn(fn_name: &str, op: &HttpRequestOp, ctx: &FetchContext, w:
         w.line("},");
     }
 
-    // Body
+    // Body: the CRE request takes base64 bytes, whatever the content type.
     if let Some(ref body) = op.body {
-        let data_expr = subst_expr(&body.data, &subst);
-        let data_str = emit_value_expr_init(&data_expr);
-        match body.content_type {
-            HttpContentType::Json => {
-                w.line(&format!(
-                    "body: Buffer.from(new TextEncoder().encode(JSON.stringify({}))).toString(\"base64\"),",
-                    data_str
-                ));
-            }
-            _ => {
-                w.line(&format!("body: {},", data_str));
+        let text = match body.content_type {
+            HttpContentType::Json => format!(
+                "JSON.stringify({})",
+                emit_value_expr_init(&subst_expr(&body.data, &subst))
+            ),
+            HttpContentType::FormUrlEncoded if !body.fields.is_empty() => {
+                let fields: Vec<String> = body
+                    .fields
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            emit_value_expr_init(&ValueExpr::string(key.as_str())),
+                            form_value(&subst_expr(value, &subst))
+                        )
+                    })
+                    .collect();
+                format!(
+                    "new URLSearchParams({{ {} }}).toString()",
+                    fields.join(", ")
+                )
             }
-        }
+            // A pre-built form string or a raw body is sent as written.
+            _ => emit_value_expr_init(&subst_expr(&body.data, &subst)),
+        };
+        w.line_fmt(format_args!(
+            "body: Buffer.from(new TextEncoder().encode({})).toString(\"base64\"),",
+            text
+        ));
     }
 
-    // Cache settings
-    if let Some(max_age) = op.cache_max_age_seconds {
+    // Cache settings (GET/HEAD only; lowering warns with W004 for other methods)
+    if let Some(max_age) = op.cache_max_age_seconds
+        && op.method.is_cacheable()
+    {
         w.block_open("cacheSettings:");
         w.line("store: true,");
-        w.line(&format!("maxAge: {{ seconds: {}n }},", max_age));
+        w.line_fmt(format_args!("maxAge: {{ seconds: {}n }},", max_age));
         w.dedent();
         w.line("},");
     }
@@ -309,31 +538,82 @@ fn emit_http_fetch_fn(fn_name: &str, op: &HttpRequestOp, ctx: &FetchContext, w:
         w.blank();
     }
 
-    // Return based on response format
-    match op.response_format {
-        HttpResponseFormat::Json => {
-            w.line(
-                "return { statusCode: resp.statusCode, body: JSON.parse(Buffer.from(resp.body, \"base64\").toString(\"utf-8\")), headers: resp.headers };",
-            );
+    // `resp.body` is raw bytes; decode it into the shape `body_ts_type` promises.
+    w.line_fmt(format_args!(
+        "return {{ statusCode: resp.statusCode, body: {}, headers: resp.headers }};",
+        decode_response_body(&op.response_format)
+    ));
+
+    w.block_close_semi();
+}
+
+/// A form field value as a string, which `URLSearchParams` requires.
+fn form_value(value: &ValueExpr) -> String {
+    match value {
+        ValueExpr::Literal(LiteralValue::String { .. }) | ValueExpr::Template { .. } => {
+            emit_value_expr_init(value)
         }
-        HttpResponseFormat::Text => {
-            w.line("return { statusCode: resp.statusCode, body: new TextDecoder().decode(resp.body), headers: resp.headers };");
+        _ => format!("String({})", emit_value_expr_init(value)),
+    }
+}
+
+/// Whether a header is sent, judged from its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeaderPresence {
+    Always,
+    /// Empty string literal: sending `Name: ""` can break auth, so it is dropped.
+    Never,
+    /// Resolved at runtime and possibly empty.
+    IfNonEmpty,
+}
+
+fn header_presence(value: &ValueExpr) -> HeaderPresence {
+    match value {
+        ValueExpr::Literal(LiteralValue::String { value }) if value.is_empty() => {
+            HeaderPresence::Never
         }
-        HttpResponseFormat::Binary => {
-            w.line(
-                "return { statusCode: resp.statusCode, body: resp.body, headers: resp.headers };",
-            );
+        ValueExpr::Literal(_) => HeaderPresence::Always,
+        // A template with any literal text can never be empty.
+        ValueExpr::Template { parts } => {
+            let has_text = parts
+                .iter()
+                .any(|p| matches!(p, TemplatePart::Lit { value } if !value.is_empty()));
+            let has_expr = parts.iter().any(|p| matches!(p, TemplatePart::Expr { .. }));
+            match (has_text, has_expr) {
+                (true, _) => HeaderPresence::Always,
+                (false, true) => HeaderPresence::IfNonEmpty,
+                (false, false) => HeaderPresence::Never,
+            }
         }
+        ValueExpr::Binding(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. }
+        | ValueExpr::Conditional { .. } => HeaderPresence::IfNonEmpty,
     }
+}
 
-    w.block_close_semi();
+/// TypeScript expression turning `resp.body` (bytes) into the step's `body`.
+fn decode_response_body(format: &HttpResponseFormat) -> &'static str {
+    match format {
+        HttpResponseFormat::Json => "JSON.parse(Buffer.from(resp.body).toString(\"utf-8\"))",
+        HttpResponseFormat::Text => "Buffer.from(resp.body).toString(\"utf-8\")",
+        HttpResponseFormat::Binary => "Buffer.from(resp.body).toString(\"base64\")",
+    }
 }
 
-fn emit_ai_fetch_fn(fn_name: &str, op: &AiCallOp, ctx: &FetchContext, w: &mut CodeWriter) {
+fn emit_ai_fetch_fn(
+    fn_name: &str,
+    config_type: &str,
+    op: &AiCallOp,
+    ctx: &FetchContext,
+    w: &mut CodeWriter,
+) {
     // AI fetch functions receive apiKey as a third parameter (passed from handler)
-    w.block_open(&format!(
-        "const {} = (sendRequester: HTTPSendRequester, config: any, apiKey: string) =>",
-        fn_name
+    w.block_open_fmt(format_args!(
+        "const {} = (sendRequester: HTTPSendRequester, config: {}, apiKey: string) =>",
+        fn_name, config_type
     ));
 
     let subst = build_subst_map(ctx);
@@ -343,13 +623,13 @@ fn emit_ai_fetch_fn(fn_name: &str, op: &AiCallOp, ctx: &FetchContext, w: &mut Co
     let system_prompt = emit_value_expr_init(&subst_expr(&op.system_prompt, &subst));
     let user_prompt = emit_value_expr_init(&subst_expr(&op.user_prompt, &subst));
 
-    let provider = op.provider.as_str();
-
     // Build request body — provider-specific format
-    match provider {
-        "google" => emit_google_body(w, &model, &system_prompt, &user_prompt, op),
-        "anthropic" => emit_anthropic_body(w, &model, &system_prompt, &user_prompt, op),
-        _ => emit_openai_body(w, &model, &system_prompt, &user_prompt, op), // OpenAI-compatible default
+    match op.provider {
+        AiProvider::Google => emit_google_body(w, &model, &system_prompt, &user_prompt, op),
+        AiProvider::Anthropic => emit_anthropic_body(w, &model, &system_prompt, &user_prompt, op),
+        AiProvider::OpenAi | AiProvider::OpenAiCompatible { .. } => {
+            emit_openai_body(w, &model, &system_prompt, &user_prompt, op)
+        }
     }
 
     w.line("const bodyBytes = new TextEncoder().encode(JSON.stringify(body));");
@@ -357,23 +637,18 @@ fn emit_ai_fetch_fn(fn_name: &str, op: &AiCallOp, ctx: &FetchContext, w: &mut Co
 
     // Build request object with provider-specific auth headers
     w.block_open("const req =");
-    w.line(&format!("url: {},", base_url));
+    w.line_fmt(format_args!("url: {},", base_url));
     w.line("method: \"POST\" as const,");
     w.line("body: Buffer.from(bodyBytes).toString(\"base64\"),");
     w.block_open("headers:");
     w.line("\"Content-Type\": \"application/json\",");
-    match provider {
-        "google" => {
-            w.line("\"x-goog-api-key\": apiKey,");
-        }
-        "anthropic" => {
-            w.line("\"x-api-key\": apiKey,");
-            w.line("\"anthropic-version\": \"2023-06-01\",");
-        }
-        _ => {
-            // OpenAI-compatible
-            w.line("\"Authorization\": `Bearer ${apiKey}`,");
-        }
+    for (name, value) in op.provider.auth_headers() {
+        let value = match value {
+            AiHeaderValue::ApiKey => "apiKey".to_string(),
+            AiHeaderValue::BearerApiKey => "`Bearer ${apiKey}`".to_string(),
+            AiHeaderValue::Fixed(v) => format!("\"{}\"", v),
+        };
+        w.line_fmt(format_args!("\"{}\": {},", name, value));
     }
     w.dedent();
     w.line("},");
@@ -387,7 +662,14 @@ fn emit_ai_fetch_fn(fn_name: &str, op: &AiCallOp, ctx: &FetchContext, w: &mut Co
     w.line("throw new Error(`AI call failed with status: ${resp.statusCode}`);");
     w.block_close();
     w.blank();
-    w.line("return JSON.parse(Buffer.from(resp.body, \"base64\").toString(\"utf-8\"));");
+    w.comment_fmt(format_args!(
+        "Generated text is at .{}",
+        op.provider.response_text_path()
+    ));
+    w.line_fmt(format_args!(
+        "return {};",
+        decode_response_body(&HttpResponseFormat::Json)
+    ));
 
     w.block_close_semi();
 }
@@ -400,21 +682,24 @@ fn emit_openai_body(
     op: &AiCallOp,
 ) {
     w.block_open("const body =");
-    w.line(&format!("model: {},", model));
+    w.line_fmt(format_args!("model: {},", model));
     w.line("messages: [");
     w.indent();
-    w.line(&format!(
+    w.line_fmt(format_args!(
         "{{ role: \"system\", content: {} }},",
         system_prompt
     ));
-    w.line(&format!("{{ role: \"user\", content: {} }},", user_prompt));
+    w.line_fmt(format_args!(
+        "{{ role: \"user\", content: {} }},",
+        user_prompt
+    ));
     w.dedent();
     w.line("],");
     if let Some(temp) = op.temperature {
-        w.line(&format!("temperature: {},", temp));
+        w.line_fmt(format_args!("temperature: {},", temp));
     }
     if let Some(max) = op.max_tokens {
-        w.line(&format!("max_tokens: {},", max));
+        w.line_fmt(format_args!("max_tokens: {},", max));
     }
     w.dedent();
     w.line("};");
@@ -429,18 +714,21 @@ fn emit_anthropic_body(
     op: &AiCallOp,
 ) {
     w.block_open("const body =");
-    w.line(&format!("model: {},", model));
-    w.line(&format!("system: {},", system_prompt));
+    w.line_fmt(format_args!("model: {},", model));
+    w.line_fmt(format_args!("system: {},", system_prompt));
     w.line("messages: [");
     w.indent();
-    w.line(&format!("{{ role: \"user\", content: {} }},", user_prompt));
+    w.line_fmt(format_args!(
+        "{{ role: \"user\", content: {} }},",
+        user_prompt
+    ));
     w.dedent();
     w.line("],");
     if let Some(temp) = op.temperature {
-        w.line(&format!("temperature: {},", temp));
+        w.line_fmt(format_args!("temperature: {},", temp));
     }
     if let Some(max) = op.max_tokens {
-        w.line(&format!("max_tokens: {},", max));
+        w.line_fmt(format_args!("max_tokens: {},", max));
     }
     w.dedent();
     w.line("};");
@@ -457,13 +745,13 @@ fn emit_google_body(
     w.block_open("const body =");
     // Google uses system_instruction for system prompts
     w.block_open("system_instruction:");
-    w.line(&format!("parts: [{{ text: {} }}],", system_prompt));
+    w.line_fmt(format_args!("parts: [{{ text: {} }}],", system_prompt));
     w.dedent();
     w.line("},");
     // Google uses contents array with parts
     w.line("contents: [");
     w.indent();
-    w.line(&format!(
+    w.line_fmt(format_args!(
         "{{ role: \"user\", parts: [{{ text: {} }}] }},",
         user_prompt
     ));
@@ -474,10 +762,10 @@ fn emit_google_body(
     if has_config {
         w.block_open("generationConfig:");
         if let Some(temp) = op.temperature {
-            w.line(&format!("temperature: {},", temp));
+            w.line_fmt(format_args!("temperature: {},", temp));
         }
         if let Some(max) = op.max_tokens {
-            w.line(&format!("maxOutputTokens: {},", max));
+            w.line_fmt(format_args!("maxOutputTokens: {},", max));
         }
         w.dedent();
         w.line("},");
@@ -517,19 +805,25 @@ mod tests {
                                 timeout_ms: None,
                                 expected_status_codes: vec![],
                                 response_format: HttpResponseFormat::Json,
+                                follow_redirects: true,
+                                ignore_ssl: false,
                                 consensus: ConsensusStrategy::Identical,
                             }),
                             output: None,
+                            notes: None,
                         }],
                     },
                     false_branch: Block { steps: vec![] },
+                    true_label: None,
+                    false_label: None,
                     reconverge_at: None,
                 }),
                 output: None,
+                notes: None,
             }],
         };
 
-        let fns = collect_fetch_fns(&block);
+        let fns = collect_fetch_fns(&block, false);
         assert_eq!(fns.len(), 1);
         assert_eq!(fns[0].fn_name, "fetch_http_inner");
     }
@@ -1,11 +1,17 @@
 //! Generate supporting project files: config.json, secrets.yaml, workflow.yaml,
-//! project.yaml, package.json, tsconfig.json, .env.
+//! project.yaml, package.json, tsconfig.json, .env, .gitignore, audit.json,
+//! writes.md, manifest.json.
 //! SYNC NOTE: Keep trigger/operation-based generation logic here aligned with
 //! IR changes that come from `shared/model/node.ts` + lowering updates.
 
 use std::collections::HashSet;
 
+use serde::Serialize;
+
+use super::{CodegenOptions, GeneratedFile};
+use crate::ir::analysis::{Calldata, describe_writes};
 use crate::ir::types::*;
+use crate::ir::validate::collect_secret_refs_from_step;
 
 /// Generate `config.json` content.
 pub fn gen_config_json(ir: &WorkflowIR) -> String {
@@ -13,10 +19,11 @@ pub fn gen_config_json(ir: &WorkflowIR) -> String {
     for field in &ir.config_schema {
         let value = match &field.default_value {
             Some(v) => match &field.zod_type {
-                ZodType::String => format!("\"{}\"", v.replace('"', "\\\"")),
+                ZodType::String => serde_json::Value::String(v.clone()).to_string(),
                 ZodType::Number | ZodType::Boolean => v.clone(),
                 ZodType::Raw(_) => v.clone(),
             },
+            None if field.optional_in_config => "null".to_string(),
             None => match &field.zod_type {
                 ZodType::String => "\"\"".to_string(),
                 ZodType::Number => "0".to_string(),
@@ -29,15 +36,27 @@ pub fn gen_config_json(ir: &WorkflowIR) -> String {
     format!("{{\n{}\n}}\n", entries.join(",\n"))
 }
 
-/// Generate `secrets.yaml` content.
+/// Generate `secrets.yaml` content. CRE's `secretsNames` has no
+/// per-environment sections, so each secret maps to its variable for the
+/// environment the bundle targets (see [`Environment::of`]).
 pub fn gen_secrets_yaml(ir: &WorkflowIR) -> String {
-    if ir.required_secrets.is_empty() {
-        return "secretsNames: {}\n".to_string();
-    }
+    let environment = Environment::of(&ir.metadata);
+    render_secrets_yaml(
+        ir.required_secrets
+            .iter()
+            .map(|secret| (secret.name.as_str(), secret.env_variable_for(environment))),
+    )
+}
+
+/// `secrets.yaml` mapping each `(secret name, environment variable)`.
+pub fn render_secrets_yaml<'a>(secrets: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
     let mut lines = vec!["secretsNames:".to_string()];
-    for secret in &ir.required_secrets {
-        lines.push(format!("  {}:", secret.name));
-        lines.push(format!("    - {}", secret.env_variable));
+    for (name, env_variable) in secrets {
+        lines.push(format!("  {}:", name));
+        lines.push(format!("    - {}", env_variable));
+    }
+    if lines.len() == 1 {
+        return "secretsNames: {}\n".to_string();
     }
     lines.push(String::new());
     lines.join("\n")
@@ -65,28 +84,78 @@ production-settings:
     )
 }
 
-/// Generate `project.yaml` content.
+/// Generate `project.yaml` content: an RPC list for each target in
+/// `workflow.yaml`. Both get the same user RPCs and chains; only the
+/// fallback RPC, used when the workflow names no chain, follows the
+/// environment (Sepolia for staging, Ethereum mainnet for production).
 pub fn gen_project_yaml(ir: &WorkflowIR) -> String {
-    let mut rpc_lines = String::new();
+    render_project_yaml(|environment| project_rpcs(ir, environment))
+}
+
+/// One `rpcs` entry of `project.yaml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectRpc {
+    pub chain_name: String,
+    pub url: String,
+    pub source: RpcSource,
+}
+
+/// Where a [`ProjectRpc`] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcSource {
+    /// Listed in `globalConfig.rpcs`.
+    User,
+    /// A chain the workflow uses, with a placeholder public RPC.
+    Detected,
+    /// The environment's default chain, since the workflow names none.
+    Fallback,
+}
+
+/// `project.yaml` with the `rpcs` of each environment.
+pub fn render_project_yaml(rpcs: impl Fn(Environment) -> Vec<ProjectRpc>) -> String {
+    Environment::ALL
+        .iter()
+        .map(|&environment| {
+            let rpc_lines: String = rpcs(environment)
+                .iter()
+                .map(|rpc| {
+                    format!(
+                        "    - chain-name: {}\n      url: {}\n",
+                        rpc.chain_name, rpc.url
+                    )
+                })
+                .collect();
+            format!("{}-settings:\n  rpcs:\n{}", environment.as_str(), rpc_lines)
+        })
+        .collect()
+}
+
+/// The RPCs `project.yaml` lists for `environment`, one per chain.
+pub fn project_rpcs(ir: &WorkflowIR, environment: Environment) -> Vec<ProjectRpc> {
+    let mut rpcs = Vec::new();
     let mut seen_chains = HashSet::new();
+    let mut push = |chain_name: &str, url: &str, source: RpcSource| {
+        if seen_chains.insert(chain_name.to_string()) {
+            rpcs.push(ProjectRpc {
+                chain_name: chain_name.to_string(),
+                url: url.to_string(),
+                source,
+            });
+        }
+    };
 
     // 1. User-defined RPCs take priority
     for rpc in &ir.user_rpcs {
-        seen_chains.insert(rpc.chain_name.clone());
-        rpc_lines.push_str(&format!(
-            "    - chain-name: {}\n      url: {}\n",
-            rpc.chain_name, rpc.url,
-        ));
+        push(&rpc.chain_name, &rpc.url, RpcSource::User);
     }
 
     // 2. Auto-detected EVM chains (skip if user already provided)
     for chain in &ir.evm_chains {
-        if seen_chains.insert(chain.chain_selector_name.clone()) {
-            rpc_lines.push_str(&format!(
-                "    - chain-name: {}\n      url: https://0xrpc.io/sep\n",
-                chain.chain_selector_name
-            ));
-        }
+        push(
+            &chain.chain_selector_name,
+            "https://0xrpc.io/sep",
+            RpcSource::Detected,
+        );
     }
 
     // 3. EVM log trigger chain
@@ -95,49 +164,59 @@ pub fn gen_project_yaml(ir: &WorkflowIR) -> String {
             .evm_client_binding
             .replace("evmClient_", "")
             .replace('_', "-");
-        if seen_chains.insert(trigger_chain.clone()) {
-            rpc_lines.push_str(&format!(
-                "    - chain-name: {}\n      url: https://0xrpc.io/sep\n",
-                trigger_chain
-            ));
-        }
+        push(&trigger_chain, "https://0xrpc.io/sep", RpcSource::Detected);
     }
 
     // 4. Fallback: at least one RPC required
-    if rpc_lines.is_empty() {
-        let chain_name =
-            ir.metadata
-                .default_chain_selector
-                .as_deref()
-                .unwrap_or(if ir.metadata.is_testnet {
-                    "ethereum-testnet-sepolia"
-                } else {
-                    "ethereum-mainnet"
-                });
-        let url = if ir.metadata.is_testnet {
-            "https://0xrpc.io/sep"
-        } else {
-            "https://0xrpc.io/eth"
+    if rpcs.is_empty() {
+        let (default_chain, url) = match environment {
+            Environment::Staging => ("ethereum-testnet-sepolia", "https://0xrpc.io/sep"),
+            Environment::Production => ("ethereum-mainnet", "https://0xrpc.io/eth"),
         };
-        rpc_lines.push_str(&format!(
-            "    - chain-name: {}\n      url: {}\n",
-            chain_name, url,
-        ));
+        let chain_name = ir
+            .metadata
+            .default_chain_selector
+            .as_deref()
+            .unwrap_or(default_chain);
+        rpcs.push(ProjectRpc {
+            chain_name: chain_name.to_string(),
+            url: url.to_string(),
+            source: RpcSource::Fallback,
+        });
     }
 
-    format!("staging-settings:\n  rpcs:\n{}", rpc_lines)
+    rpcs
 }
 
+/// `@chainlink/cre-sdk` version range used unless `CodegenOptions::sdk_version`
+/// says otherwise.
+pub const DEFAULT_SDK_VERSION: &str = "^1.0.9";
+
 /// Generate `package.json` content.
-pub fn gen_package_json(ir: &WorkflowIR) -> String {
-    let name = &ir.metadata.id;
-    let mut deps = vec![("@chainlink/cre-sdk", "^1.0.9"), ("zod", "^3.24")];
+pub fn gen_package_json(ir: &WorkflowIR, options: &CodegenOptions) -> String {
+    render_package_json(&ir.metadata.id, &package_dependencies(ir, options), options)
+}
+
+/// The `dependencies` of the workflow's `package.json`, as `(package, version range)`.
+pub fn package_dependencies<'a>(
+    ir: &WorkflowIR,
+    options: &'a CodegenOptions,
+) -> Vec<(&'static str, &'a str)> {
+    let sdk_version = options
+        .sdk_version
+        .as_deref()
+        .unwrap_or(DEFAULT_SDK_VERSION);
+    let mut deps = vec![("@chainlink/cre-sdk", sdk_version), ("zod", "^3.24")];
 
     // Check if viem is needed
     if needs_viem(ir) {
         deps.push(("viem", "^2.0"));
     }
+    deps
+}
 
+/// `package.json` named `name` with `deps` as its dependencies.
+pub fn render_package_json(name: &str, deps: &[(&str, &str)], options: &CodegenOptions) -> String {
     let dep_entries: Vec<String> = deps
         .iter()
         .map(|(k, v)| format!("    \"{}\": \"{}\"", k, v))
@@ -152,7 +231,7 @@ pub fn gen_package_json(ir: &WorkflowIR) -> String {
   "main": "dist/main.js",
   "private": true,
   "scripts": {{
-    "postinstall": "bun x cre-setup"
+    "postinstall": "{exec} cre-setup"
   }},
   "dependencies": {{
 {deps}
@@ -162,6 +241,7 @@ pub fn gen_package_json(ir: &WorkflowIR) -> String {
   }}
 }}
 "#,
+        exec = options.package_manager.exec(),
         deps = dep_entries.join(",\n"),
         dev_deps = dev_dep_entries.join(",\n")
     )
@@ -198,13 +278,282 @@ pub fn gen_dot_env(ir: &WorkflowIR) -> String {
     } else {
         lines.push(String::new());
         for secret in &ir.required_secrets {
-            lines.push(format!("{}=<{}>", secret.env_variable, secret.name));
+            if secret.optional_in_config {
+                lines.push(format!(
+                    "# Optional: only used inside a branch ({})",
+                    secret.referenced_by.join(", ")
+                ));
+            }
+            // One variable per environment, each labeled with its environment
+            if !secret.varies_by_environment() {
+                let env_variable = secret.env_variable_for(Environment::Staging);
+                lines.push(format!("{}=<{}>", env_variable, secret.name));
+                continue;
+            }
+            for environment in Environment::ALL {
+                lines.push(format!("# {} ({})", secret.name, environment.as_str()));
+                lines.push(format!(
+                    "{}=<{}>",
+                    secret.env_variable_for(environment),
+                    secret.name
+                ));
+            }
         }
     }
     lines.push(String::new());
     lines.join("\n")
 }
 
+/// Generate `.gitignore` content. Keeps `.env` (which holds real secret values
+/// once filled in) and build output out of version control.
+pub fn gen_gitignore() -> String {
+    ["node_modules/", "dist/", ".env", "*.log", ""].join("\n")
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Audit<'a> {
+    workflow_id: &'a str,
+    workflow_name: &'a str,
+    workflow_version: &'a str,
+    description: Option<&'a str>,
+    steps: Vec<AuditStep<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuditStep<'a> {
+    id: &'a str,
+    source_node_ids: &'a [String],
+    label: &'a str,
+    operation: &'static str,
+    notes: Option<&'a str>,
+    secrets: Vec<String>,
+    chains: Vec<&'a str>,
+    /// Label of the innermost labelled branch arm the step runs in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arm: Option<&'a str>,
+}
+
+/// Generate `names.json` for a compact bundle: each shortened helper name
+/// mapped to the name it has in a default bundle, e.g. `"_f1": "fetch_http_1"`.
+pub fn gen_names_json(renamed: &[(String, String)]) -> String {
+    let names: std::collections::BTreeMap<&str, &str> = renamed
+        .iter()
+        .map(|(short, original)| (short.as_str(), original.as_str()))
+        .collect();
+    let mut content =
+        serde_json::to_string_pretty(&names).expect("names serialization cannot fail");
+    content.push('\n');
+    content
+}
+
+/// Generate `audit.json`: every step (branch arms included, in handler order)
+/// with its source nodes, notes, and the secrets and chains it touches, so a
+/// deployed bundle can be reviewed against the annotated workflow.
+pub fn gen_audit_json(ir: &WorkflowIR) -> String {
+    let mut steps = Vec::new();
+    collect_audit_steps(ir, &ir.handler_body, None, &mut steps);
+    let audit = Audit {
+        workflow_id: &ir.metadata.id,
+        workflow_name: &ir.metadata.name,
+        workflow_version: &ir.metadata.version,
+        description: ir.metadata.description.as_deref(),
+        steps,
+    };
+    let mut content =
+        serde_json::to_string_pretty(&audit).expect("audit serialization cannot fail");
+    content.push('\n');
+    content
+}
+
+fn collect_audit_steps<'a>(
+    ir: &'a WorkflowIR,
+    block: &'a Block,
+    arm: Option<&'a str>,
+    out: &mut Vec<AuditStep<'a>>,
+) {
+    for step in &block.steps {
+        let binding = match &step.operation {
+            Operation::EvmRead(op) => Some(&op.evm_client_binding),
+            Operation::EvmWrite(op) => Some(&op.evm_client_binding),
+            _ => None,
+        };
+        out.push(AuditStep {
+            id: &step.id,
+            source_node_ids: &step.source_node_ids,
+            label: &step.label,
+            operation: step.operation.kind(),
+            notes: step.notes.as_deref(),
+            // A branch's arm steps are listed on their own.
+            secrets: match &step.operation {
+                Operation::Branch(_) => Vec::new(),
+                _ => collect_secret_refs_from_step(step),
+            },
+            chains: ir
+                .evm_chains
+                .iter()
+                .filter(|chain| Some(&chain.binding_name) == binding)
+                .map(|chain| chain.chain_selector_name.as_str())
+                .collect(),
+            arm,
+        });
+        if let Operation::Branch(branch) = &step.operation {
+            let true_arm = branch.true_label.as_deref().or(arm);
+            let false_arm = branch.false_label.as_deref().or(arm);
+            collect_audit_steps(ir, &branch.true_branch, true_arm, out);
+            collect_audit_steps(ir, &branch.false_branch, false_arm, out);
+        }
+    }
+}
+
+/// Generate `writes.md`: what every EvmWrite calls (chain, target, function
+/// and argument sources, gas, value), traced back through its AbiEncode step.
+/// None when the workflow writes nothing.
+pub fn gen_writes_md(ir: &WorkflowIR) -> Option<String> {
+    let writes = describe_writes(ir);
+    if writes.is_empty() {
+        return None;
+    }
+    let mut md = format!("# EVM writes: {}\n", ir.metadata.name);
+    for write in &writes {
+        md.push_str(&format!("\n## {} — {}\n\n", write.step_id, write.label));
+        md.push_str(&format!(
+            "- Chain: {}\n",
+            write.chain.as_deref().map_or("unknown".into(), code)
+        ));
+        md.push_str(&format!("- Target: {}\n", code(&write.receiver)));
+        match &write.calldata {
+            Calldata::Encoded {
+                encode_step_id,
+                signature,
+                args,
+                ..
+            } => {
+                let call = write.calldata.call().unwrap_or_default();
+                md.push_str(&format!("- Call: {}\n", code(&call)));
+                md.push_str(&format!("- Signature: {}\n", code(signature)));
+                md.push_str(&format!("- Encoded by: {}\n", code(encode_step_id)));
+                if !args.is_empty() {
+                    md.push_str("- Arguments:\n");
+                }
+                for arg in args {
+                    let source = arg.source.as_deref().map_or("unmapped".into(), code);
+                    md.push_str(&format!(
+                        "  - {} ({}): {}\n",
+                        code(&arg.name),
+                        arg.abi_type,
+                        source
+                    ));
+                }
+            }
+            Calldata::Opaque { expr } => {
+                md.push_str(&format!("- Call: opaque calldata {}\n", code(expr)));
+            }
+        }
+        md.push_str(&format!("- Gas limit: {}\n", code(&write.gas_limit)));
+        md.push_str(&format!(
+            "- Value: {}\n",
+            write.value_wei.as_deref().map_or("none".into(), code)
+        ));
+    }
+    Some(md)
+}
+
+/// Inline Markdown code span; template values contain backticks themselves.
+fn code(text: &str) -> String {
+    if text.contains('`') {
+        format!("`` {} ``", text)
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+pub const MANIFEST_PATH: &str = "manifest.json";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    compiler_version: &'static str,
+    workflow_id: &'a str,
+    workflow_version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generated_at: Option<&'a str>,
+    files: Vec<ManifestEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    path: &'a str,
+    sha256: &'a str,
+}
+
+/// Generate `manifest.json` from the `(path, sha256)` of every file generated
+/// before it, so the deploy tool can verify bundle integrity. Must be called
+/// after all other files are generated; the manifest does not list itself.
+/// `generatedAt` is only written when `generated_at` is given.
+pub fn gen_manifest(
+    ir: &WorkflowIR,
+    hashes: &[(String, String)],
+    generated_at: Option<&str>,
+) -> GeneratedFile {
+    let manifest = Manifest {
+        compiler_version: env!("CARGO_PKG_VERSION"),
+        workflow_id: &ir.metadata.id,
+        workflow_version: &ir.metadata.version,
+        generated_at,
+        files: hashes
+            .iter()
+            .filter(|(path, _)| path != MANIFEST_PATH)
+            .map(|(path, sha256)| ManifestEntry { path, sha256 })
+            .collect(),
+    };
+    let mut content =
+        serde_json::to_string_pretty(&manifest).expect("manifest serialization cannot fail");
+    content.push('\n');
+    GeneratedFile::new(MANIFEST_PATH, content)
+}
+
+/// Current UTC time in RFC 3339 form, for callers that stamp the manifest.
+#[cfg(target_arch = "wasm32")]
+pub fn current_timestamp() -> String {
+    // `SystemTime::now()` panics on wasm32-unknown-unknown; ask the JS host.
+    js_sys::Date::new_0().to_iso_string().into()
+}
+
+/// Current UTC time in RFC 3339 form, for callers that stamp the manifest.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn current_timestamp() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format_unix_timestamp(secs)
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn format_unix_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant), valid for any date after 1970.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
 fn needs_viem(ir: &WorkflowIR) -> bool {
     has_viem_ops(&ir.handler_body) || matches!(&ir.trigger, TriggerDef::EvmLog(_))
 }
@@ -232,6 +581,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*/5 * * * *"),
@@ -243,12 +593,16 @@ mod tests {
                     zod_type: ZodType::String,
                     default_value: Some("*/5 * * * *".into()),
                     description: None,
+                    referenced_by: vec![],
+                    optional_in_config: false,
                 },
                 ConfigField {
                     name: "threshold".into(),
                     zod_type: ZodType::Number,
                     default_value: None,
                     description: None,
+                    referenced_by: vec![],
+                    optional_in_config: false,
                 },
             ],
             required_secrets: vec![],
@@ -272,6 +626,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*"),
@@ -281,6 +636,10 @@ mod tests {
             required_secrets: vec![SecretDeclaration {
                 name: "API_KEY".into(),
                 env_variable: "API_KEY_VAR".into(),
+                env_variable_staging: None,
+                env_variable_production: None,
+                referenced_by: vec![],
+                optional_in_config: false,
             }],
             evm_chains: vec![],
             user_rpcs: vec![],
@@ -303,6 +662,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*"),
@@ -313,10 +673,18 @@ mod tests {
                 SecretDeclaration {
                     name: "API_KEY".into(),
                     env_variable: "API_KEY_VAR".into(),
+                    env_variable_staging: None,
+                    env_variable_production: None,
+                    referenced_by: vec![],
+                    optional_in_config: false,
                 },
                 SecretDeclaration {
                     name: "DB_PASSWORD".into(),
                     env_variable: "DB_PASSWORD_VAR".into(),
+                    env_variable_staging: None,
+                    env_variable_production: None,
+                    referenced_by: vec![],
+                    optional_in_config: false,
                 },
             ],
             evm_chains: vec![],
@@ -339,6 +707,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*"),
@@ -366,6 +735,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*/5 * * * *"),
@@ -394,6 +764,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: Some("base-testnet-sepolia".into()),
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*/5 * * * *"),
@@ -421,6 +792,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: false,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*/5 * * * *"),
@@ -448,6 +820,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*"),
@@ -468,11 +841,12 @@ mod tests {
                         data_mappings: vec![],
                     }),
                     output: None,
+                    notes: None,
                 }],
             },
         };
 
-        let pkg = gen_package_json(&ir);
+        let pkg = gen_package_json(&ir, &CodegenOptions::default());
         assert!(pkg.contains("viem"));
     }
 
@@ -489,6 +863,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::string("*/5 * * * *"),
@@ -564,4 +939,59 @@ mod tests {
         assert!(yaml.contains("ethereum-testnet-sepolia"));
         assert!(yaml.contains("https://0xrpc.io/sep"));
     }
+
+    #[test]
+    fn project_yaml_has_staging_and_production_rpcs() {
+        let yaml = gen_project_yaml(&project_yaml_test_ir(vec![], vec![]));
+        let (staging, production) = yaml.split_once("production-settings:").unwrap();
+        assert!(staging.starts_with("staging-settings:\n  rpcs:\n"));
+        assert!(staging.contains("ethereum-testnet-sepolia"));
+        assert!(staging.contains("https://0xrpc.io/sep"));
+        assert!(production.contains("ethereum-mainnet"));
+        assert!(production.contains("https://0xrpc.io/eth"));
+        assert!(!production.contains("https://0xrpc.io/sep"));
+
+        // User RPCs apply to both targets.
+        let yaml = gen_project_yaml(&project_yaml_test_ir(
+            vec![RpcEntry {
+                chain_name: "polygon-mainnet".into(),
+                url: "https://polygon-rpc.example.com".into(),
+            }],
+            vec![],
+        ));
+        assert_eq!(yaml.matches("https://polygon-rpc.example.com").count(), 2);
+        assert!(!yaml.contains("0xrpc.io"));
+    }
+
+    #[test]
+    fn config_json_branch_only_field_is_null() {
+        let mut ir = project_yaml_test_ir(vec![], vec![]);
+        ir.config_schema.push(ConfigField {
+            name: "slackWebhook".into(),
+            zod_type: ZodType::String,
+            default_value: None,
+            description: None,
+            referenced_by: vec!["notify".into()],
+            optional_in_config: true,
+        });
+
+        let json = gen_config_json(&ir);
+        assert!(json.contains("\"slackWebhook\": null"));
+    }
+
+    #[test]
+    fn gitignore_excludes_env_and_build_output() {
+        let gitignore = gen_gitignore();
+        let lines: Vec<&str> = gitignore.lines().collect();
+        assert!(lines.contains(&".env"));
+        assert!(lines.contains(&"node_modules/"));
+        assert!(lines.contains(&"dist/"));
+        assert!(lines.contains(&"*.log"));
+    }
+
+    #[test]
+    fn unix_timestamp_formats_as_rfc3339() {
+        assert_eq!(format_unix_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_unix_timestamp(1_709_210_096), "2024-02-29T12:34:56Z");
+    }
 }
@@ -6,11 +6,20 @@
 use std::collections::HashMap;
 
 use super::fetch_fns::FetchContext;
+use super::http_payload;
+use super::kv;
 use super::operations;
-use super::value_expr::emit_condition;
+use super::secrets::{self, SharedSecret};
+use super::snippets::SnippetCache;
+use super::value_expr::{emit_condition, emit_value_expr};
 use super::writer::CodeWriter;
+use crate::ir::returns;
 use crate::ir::types::*;
 
+/// Start of the comment naming a labelled branch arm:
+/// `// ── Arm: KYC approved ──`. The diff view anchors on these lines.
+pub const ARM_LABEL_PREFIX: &str = "── Arm: ";
+
 fn solidity_type_to_ts(sol_type: &str) -> &'static str {
     if sol_type.starts_with("uint") || sol_type.starts_with("int") {
         "bigint"
@@ -25,7 +34,7 @@ fn emit_evm_log_event_decode(trigger: &TriggerDef, w: &mut CodeWriter) {
     let TriggerDef::EvmLog(evm_trigger) = trigger else { return };
 
     // Always declare log metadata (present on every EVMLog)
-    w.line("// EVM log metadata");
+    w.comment("EVM log metadata");
     w.line("const blockNumber: bigint = log.blockNumber;");
     w.line("const logIndex: number = log.index;");
     w.line("const transactionHash: string = bytesToHex(log.blockHash);");
@@ -36,7 +45,7 @@ fn emit_evm_log_event_decode(trigger: &TriggerDef, w: &mut CodeWriter) {
     let Some(inputs) = abi_val.get("inputs").and_then(|v| v.as_array()) else { return };
     if inputs.is_empty() { return; }
 
-    w.line("// Decode event args from EVM log");
+    w.comment("Decode event args from EVM log");
     w.line("const topics = log.topics.map(t => bytesToHex(t)) as [`0x${string}`, ...`0x${string}`[]];");
     w.line("const data = bytesToHex(log.data);");
     w.line("const decodedLog = decodeEventLog({ abi: eventAbi, data, topics });");
@@ -46,7 +55,7 @@ fn emit_evm_log_event_decode(trigger: &TriggerDef, w: &mut CodeWriter) {
         let name = input.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
         let sol_type = input.get("type").and_then(|v| v.as_str()).unwrap_or("unknown");
         let ts_type = solidity_type_to_ts(sol_type);
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {}: {} = decodedLog.args.{} as {};",
             name, ts_type, name, ts_type
         ));
@@ -73,15 +82,32 @@ pub fn emit_evm_log_module_consts(ir: &WorkflowIR, w: &mut CodeWriter) {
     }).collect();
 
     let human_readable = format!("event {}({})", event_name, params.join(", "));
-    w.line(&format!("const eventAbi = parseAbi([\"{}\"]);" , human_readable));
-    w.line(&format!("const eventSignature = \"{}\";", evm_trigger.event_signature));
+    w.line_fmt(format_args!(
+        "const eventAbi = parseAbi([\"{}\"]);",
+        human_readable
+    ));
+    w.line_fmt(format_args!(
+        "const eventSignature = \"{}\";",
+        evm_trigger.event_signature
+    ));
     w.blank();
 }
 
-/// Emit the handler function signature and body.
+/// Emit the handler function signature and body. With `snippets`, leaf
+/// steps reuse code generated by an earlier compilation. With
+/// `label_errors`, the body runs in a `try` that prefixes errors thrown by
+/// capability calls with the step's id and label. With `decode_payload`, the HTTP
+/// trigger payload is decoded into `_payload` first. `return_type` is the
+/// handler's annotation (see [`infer_return_type`]).
+#[allow(clippy::too_many_arguments)]
 pub fn emit_handler(
     ir: &WorkflowIR,
+    return_type: &str,
     fetch_contexts: &HashMap<String, FetchContext>,
+    shared_secrets: &[SharedSecret],
+    decode_payload: bool,
+    label_errors: bool,
+    snippets: Option<&mut SnippetCache>,
     w: &mut CodeWriter,
 ) {
     let (handler_name, trigger_type, trigger_param) = match &ir.trigger_param {
@@ -92,14 +118,14 @@ pub fn emit_handler(
     };
 
     if trigger_param.is_empty() {
-        w.block_open(&format!(
-            "const {} = (runtime: Runtime<Config>): string =>",
-            handler_name
+        w.block_open_fmt(format_args!(
+            "const {} = (runtime: Runtime<Config>): {} =>",
+            handler_name, return_type
         ));
     } else {
-        w.block_open(&format!(
-            "const {} = (runtime: Runtime<Config>, {}: {}): string =>",
-            handler_name, trigger_param, trigger_type
+        w.block_open_fmt(format_args!(
+            "const {} = (runtime: Runtime<Config>, {}: {}): {} =>",
+            handler_name, trigger_param, trigger_type, return_type
         ));
     }
 
@@ -114,29 +140,81 @@ pub fn emit_handler(
     // Decode EVM log event args if trigger has a non-empty event ABI
     emit_evm_log_event_decode(&ir.trigger, w);
 
+    if decode_payload {
+        http_payload::emit_payload_decode(w);
+    }
+
+    // `__currentStep` names the capability call in flight; anything else
+    // throwing (an Error node, user code) leaves it empty and is rethrown as is
+    let label_errors = label_errors
+        && ir
+            .steps_iter()
+            .any(|step| step.operation.is_capability_call());
+    if label_errors {
+        w.line("let __currentStep = \"\";");
+        w.block_open("try");
+    }
+
+    // Fetch secrets shared by several steps once
+    secrets::emit_shared_secrets(shared_secrets, w);
+
     // Emit the block
-    emit_block(&ir.handler_body, fetch_contexts, w);
+    emit_block(&ir.handler_body, fetch_contexts, snippets, label_errors, w);
+
+    if label_errors {
+        w.dedent();
+        w.line("} catch (e) {");
+        w.indent();
+        w.line("if (!__currentStep) throw e;");
+        w.line("throw new Error(\"[\" + __currentStep + \"] \" + (e instanceof Error ? e.message : String(e)), { cause: e });");
+        w.block_close();
+    }
 
     w.block_close_semi();
 }
 
+/// Infer the handler's return type annotation from every `Return` in the body
+/// (see [`returns::return_types`]). A failed early-return filter returns its
+/// message string. With no constraining returns the handler keeps its
+/// documented `string` type; otherwise the annotation is the union of the
+/// inferred types, e.g. `string | object`.
+pub fn infer_return_type(ir: &WorkflowIR) -> String {
+    let mut found = returns::return_types(ir);
+    if returns::has_early_return_filter(&ir.handler_body) && !found.contains(&"string") {
+        found.insert(0, "string");
+    }
+    if found.is_empty() {
+        return "string".to_string();
+    }
+    found.join(" | ")
+}
+
 fn emit_capability_instantiations(ir: &WorkflowIR, w: &mut CodeWriter) {
     // HTTP client (if any HttpRequest or AiCall steps)
     if has_http_steps(&ir.handler_body) {
         w.line("const httpClient = new cre.capabilities.HTTPClient();");
     }
 
-    // EVM clients — emit for ALL chains (trigger chain is also needed if handler reads/writes on it)
-    for chain in &ir.evm_chains {
-        w.line(&format!(
-            "const {} = new cre.capabilities.EVMClient(getNetwork({{ chainFamily: \"evm\", chainSelectorName: \"{}\", isTestnet: {} }})!.chainSelector.selector);",
-            chain.binding_name,
-            chain.chain_selector_name,
-            ir.metadata.is_testnet,
-        ));
+    if kv::uses_kv(&ir.handler_body) {
+        kv::emit_kv_client(w);
+    }
+
+    // EVM clients — the trigger chain's client is constructed at module scope
+    // (see `trigger::emit_trigger_chain_client`), and reads/writes on that
+    // chain reuse it
+    for chain in ir.evm_chains.iter().filter(|c| !c.used_for_trigger) {
+        emit_evm_client(chain, ir.metadata.is_testnet, w);
     }
 }
 
+/// `const evmClient_<chain> = new cre.capabilities.EVMClient(...);`
+pub(super) fn emit_evm_client(chain: &EvmChainUsage, is_testnet: bool, w: &mut CodeWriter) {
+    w.line_fmt(format_args!(
+        "const {} = new cre.capabilities.EVMClient(getNetwork({{ chainFamily: \"evm\", chainSelectorName: \"{}\", isTestnet: {} }})!.chainSelector.selector);",
+        chain.binding_name, chain.chain_selector_name, is_testnet,
+    ));
+}
+
 fn has_http_steps(block: &Block) -> bool {
     block.steps.iter().any(|s| match &s.operation {
         Operation::HttpRequest(_) | Operation::AiCall(_) => true,
@@ -149,21 +227,68 @@ fn has_http_steps(block: &Block) -> bool {
 pub fn emit_block(
     block: &Block,
     fetch_contexts: &HashMap<String, FetchContext>,
+    snippets: Option<&mut SnippetCache>,
+    label_errors: bool,
+    w: &mut CodeWriter,
+) {
+    emit_steps(
+        &block.steps,
+        fetch_contexts,
+        snippets,
+        label_errors,
+        None,
+        w,
+    );
+}
+
+/// Emit `steps`, then `arm_result` (the assignment handing a branch arm's
+/// result to its merge) if the steps do not end the handler. Branch arms go
+/// through here as well, so nesting only adds CodeWriter indentation.
+fn emit_steps(
+    steps: &[Step],
+    fetch_contexts: &HashMap<String, FetchContext>,
+    mut snippets: Option<&mut SnippetCache>,
+    label_errors: bool,
+    arm_result: Option<&str>,
     w: &mut CodeWriter,
 ) {
-    let steps = &block.steps;
     let mut i = 0;
 
     while i < steps.len() {
+        if w.cancelled() {
+            return;
+        }
         let step = &steps[i];
+        // A branch's merge is emitted with the branch, which carries the notes.
+        if let Some(notes) = &step.notes
+            && !matches!(&step.operation, Operation::Merge(m) if m.branch_step_id.is_some())
+        {
+            emit_notes(notes, w);
+        }
 
         match &step.operation {
             Operation::Branch(branch) => {
-                emit_branch(step, branch, fetch_contexts, w);
-                // If there's a reconverge_at, skip the next Merge step
-                if branch.reconverge_at.is_some() && i + 1 < steps.len() {
-                    if let Operation::Merge(_) = &steps[i + 1].operation {
-                        i += 1; // skip the Merge
+                // A reconverging branch is followed by its Merge
+                let merge = steps.get(i + 1).filter(|next| {
+                    branch.reconverge_at.as_deref() == Some(next.id.as_str())
+                        && matches!(next.operation, Operation::Merge(_))
+                });
+                emit_branch(
+                    step,
+                    branch,
+                    merge,
+                    fetch_contexts,
+                    snippets.as_deref_mut(),
+                    label_errors,
+                    w,
+                );
+                if let Some(merge) = merge {
+                    i += 1;
+                    if let Some(out) = &merge.output {
+                        w.line_fmt(format_args!(
+                            "runtime.log(`[{}] ${{__stringify({})}}`);",
+                            merge.label, out.variable_name,
+                        ));
                     }
                 }
             }
@@ -175,53 +300,55 @@ pub fn emit_block(
                     FilterNonMatchBehavior::Skip => {
                         // Wrap remaining steps in if (condition) { ... }
                         let cond = emit_condition(&filter.conditions, &filter.combine_with);
-                        w.block_open(&format!("if ({})", cond));
+                        w.block_open_fmt(format_args!("if ({})", cond));
                         // Emit all remaining steps in this block inside the if
-                        let remaining = Block {
-                            steps: steps[i + 1..].to_vec(),
-                        };
-                        emit_block(&remaining, fetch_contexts, w);
+                        emit_steps(
+                            &steps[i + 1..],
+                            fetch_contexts,
+                            snippets,
+                            label_errors,
+                            arm_result,
+                            w,
+                        );
                         w.block_close();
                         return; // We've consumed all remaining steps
                     }
                 }
             }
-            Operation::Merge(_) => {
-                // Standalone Merge (shouldn't happen if Branch handled it, but be safe)
-                // Skip — already handled by Branch emitter
-            }
-            Operation::HttpRequest(op) => {
-                operations::emit_http_request(step, op, fetch_contexts, w);
-            }
-            Operation::EvmRead(op) => {
-                operations::emit_evm_read(step, op, w);
-            }
-            Operation::EvmWrite(op) => {
-                operations::emit_evm_write(step, op, w);
-            }
-            Operation::CodeNode(op) => {
-                operations::emit_code_node(step, op, w);
+            Operation::Merge(merge) if merge.branch_step_id.is_none() => {
+                operations::emit_standalone_merge(step, merge, w);
             }
-            Operation::AbiEncode(op) => {
-                operations::emit_abi_encode(step, op, w);
-            }
-            Operation::AbiDecode(op) => {
-                operations::emit_abi_decode(step, op, w);
-            }
-            Operation::AiCall(op) => {
-                operations::emit_ai_call(step, op, fetch_contexts, w);
-            }
-            Operation::ErrorThrow(op) => {
-                operations::emit_error_throw(step, op, w);
+            Operation::Merge(_) => {
+                // A Branch's merge is emitted with the Branch; skip a stray one
             }
-            Operation::Return(op) => {
-                operations::emit_return(step, op, w);
+            _ => {
+                let labeled = label_errors && step.operation.is_capability_call();
+                if labeled {
+                    let context = format!(
+                        "step:{} {}",
+                        step.id,
+                        serde_json::Value::from(step.label.as_str())
+                    );
+                    w.line_fmt(format_args!(
+                        "__currentStep = {};",
+                        serde_json::Value::from(context)
+                    ));
+                }
+                match snippets.as_deref_mut() {
+                    Some(cache) => cache.emit(step, fetch_contexts.get(&step.id), w, |w| {
+                        emit_leaf_step(step, fetch_contexts, w)
+                    }),
+                    None => emit_leaf_step(step, fetch_contexts, w),
+                }
+                if labeled {
+                    w.line("__currentStep = \"\";");
+                }
             }
         }
 
         // Auto-log output of every step
         if let Some(ref out) = step.output {
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "runtime.log(`[{}] ${{__stringify({})}}`);",
                 step.label, out.variable_name,
             ));
@@ -229,37 +356,184 @@ pub fn emit_block(
 
         i += 1;
     }
+
+    if let Some(assignment) = arm_result
+        && !ends_handler(steps)
+    {
+        w.line(assignment);
+    }
+}
+
+/// Whether the last of `steps` returns or throws.
+fn ends_handler(steps: &[Step]) -> bool {
+    matches!(
+        steps.last().map(|step| &step.operation),
+        Some(Operation::Return(_) | Operation::ErrorThrow(_))
+    )
+}
+
+/// Emit the step map that ends `main.ts`: one
+/// `// <step id> | <variable> | <source node ids> | <label>` line per step,
+/// in handler order with branch arms inline, so tooling can trace generated
+/// code back to the visual nodes. Steps without an output show `-` as their
+/// variable. Left out in compact mode.
+pub fn emit_step_map(body: &Block, w: &mut CodeWriter) {
+    if w.is_compact() {
+        return;
+    }
+    w.blank();
+    w.comment("Step map: step id | variable | source node ids | label");
+    emit_step_map_entries(body, w);
+}
+
+fn emit_step_map_entries(block: &Block, w: &mut CodeWriter) {
+    for step in &block.steps {
+        let variable = step
+            .output
+            .as_ref()
+            .map_or("-", |out| out.variable_name.as_str());
+        w.comment_fmt(format_args!(
+            "{} | {} | {} | {}",
+            step.id,
+            variable,
+            step.source_node_ids.join(", "),
+            step.label.replace(['\r', '\n'], " ")
+        ));
+        if let Operation::Branch(branch) = &step.operation {
+            emit_step_map_entries(&branch.true_branch, w);
+            emit_step_map_entries(&branch.false_branch, w);
+        }
+    }
+}
+
+/// Emit a node's `settings.notes` as a doc comment. `*/` inside the notes is
+/// broken up so it cannot close the comment early. Notes are left out in
+/// compact mode.
+fn emit_notes(notes: &str, w: &mut CodeWriter) {
+    if w.is_compact() {
+        return;
+    }
+    let notes = notes.replace("*/", "*\\/");
+    let mut lines = notes.lines();
+    match (lines.next(), lines.next()) {
+        (Some(line), None) => w.line_fmt(format_args!("/** {} */", line)),
+        _ => {
+            w.line("/**");
+            for line in notes.lines() {
+                w.line(format!(" * {}", line).trim_end());
+            }
+            w.line(" */");
+        }
+    }
+}
+
+/// Emit a step that does not wrap other steps.
+fn emit_leaf_step(step: &Step, fetch_contexts: &HashMap<String, FetchContext>, w: &mut CodeWriter) {
+    match &step.operation {
+        Operation::HttpRequest(op) => {
+            operations::emit_http_request(step, op, fetch_contexts, w);
+        }
+        Operation::EvmRead(op) => {
+            operations::emit_evm_read(step, op, w);
+        }
+        Operation::EvmWrite(op) => {
+            operations::emit_evm_write(step, op, w);
+        }
+        Operation::KvRead(op) => {
+            kv::emit_kv_read(step, op, w);
+        }
+        Operation::KvWrite(op) => {
+            kv::emit_kv_write(step, op, w);
+        }
+        Operation::CodeNode(op) => {
+            operations::emit_code_node(step, op, w);
+        }
+        Operation::AbiEncode(op) => {
+            operations::emit_abi_encode(step, op, w);
+        }
+        Operation::AbiDecode(op) => {
+            operations::emit_abi_decode(step, op, w);
+        }
+        Operation::Compute(op) => {
+            operations::emit_compute(step, op, w);
+        }
+        Operation::AiCall(op) => {
+            operations::emit_ai_call(step, op, fetch_contexts, w);
+        }
+        Operation::ErrorThrow(op) => {
+            operations::emit_error_throw(step, op, w);
+        }
+        Operation::Return(op) => {
+            operations::emit_return(step, op, w);
+        }
+        Operation::Branch(_) | Operation::Filter(_) | Operation::Merge(_) => {
+            unreachable!("emit_block emits steps that wrap other steps")
+        }
+    }
 }
 
 fn emit_branch(
     step: &Step,
     branch: &BranchOp,
+    merge: Option<&Step>,
     fetch_contexts: &HashMap<String, FetchContext>,
+    mut snippets: Option<&mut SnippetCache>,
+    label_errors: bool,
     w: &mut CodeWriter,
 ) {
     let cond = emit_condition(&branch.conditions, &branch.combine_with);
 
-    w.line(&format!("// {}", step.label));
+    w.comment(&step.label);
 
-    // For diamond pattern (reconverge_at is Some), declare `let` variable before if/else
-    if let Some(ref merge_id) = branch.reconverge_at {
-        // Find the merge step's output to get the variable name and type
-        // The merge step should follow this branch step in the parent block
-        w.line(&format!("let step_{};", merge_id.replace('-', "_")));
+    // Diamond pattern: the merge's `let` is declared in the scope enclosing
+    // the if/else, and each arm assigns its result to it
+    let merge_var = merge.and_then(|m| m.output.as_ref().map(|out| (m, &out.variable_name)));
+    if let Some((_, var)) = merge_var {
+        w.line_fmt(format_args!("let {};", var));
     }
+    let arm_result = |handle: &str| {
+        let (merge, var) = merge_var?;
+        let Operation::Merge(op) = &merge.operation else {
+            return None;
+        };
+        let input = op.inputs.iter().find(|input| input.handle_name == handle)?;
+        Some(format!("{} = {};", var, emit_value_expr(&input.value)))
+    };
+    let true_result = arm_result("true");
+    let false_result = arm_result("false");
 
-    w.block_open(&format!("if ({})", cond));
-    emit_block(&branch.true_branch, fetch_contexts, w);
+    w.block_open_fmt(format_args!("if ({})", cond));
+    if let Some(label) = &branch.true_label {
+        w.comment_fmt(format_args!("{}{} ──", ARM_LABEL_PREFIX, label));
+    }
+    emit_steps(
+        &branch.true_branch.steps,
+        fetch_contexts,
+        snippets.as_deref_mut(),
+        label_errors,
+        true_result.as_deref(),
+        w,
+    );
 
     w.block_else();
-    emit_block(&branch.false_branch, fetch_contexts, w);
+    if let Some(label) = &branch.false_label {
+        w.comment_fmt(format_args!("{}{} ──", ARM_LABEL_PREFIX, label));
+    }
+    emit_steps(
+        &branch.false_branch.steps,
+        fetch_contexts,
+        snippets,
+        label_errors,
+        false_result.as_deref(),
+        w,
+    );
 
     w.block_close();
 }
 
-fn emit_filter_early_return(filter: &FilterOp, message: &str, w: &mut CodeWriter) {
+fn emit_filter_early_return(filter: &FilterOp, message: &ValueExpr, w: &mut CodeWriter) {
     let cond = emit_condition(&filter.conditions, &filter.combine_with);
-    w.block_open(&format!("if (!({}))", cond));
-    w.line(&format!(r#"return "{}";"#, message.replace('"', "\\\"")));
+    w.block_open_fmt(format_args!("if (!({}))", cond));
+    w.line_fmt(format_args!("return {};", emit_value_expr(message)));
     w.block_close();
 }
@@ -0,0 +1,100 @@
+//! Decode an HTTP trigger's payload once.
+//!
+//! `triggerData.input` is the raw request body, a `Uint8Array`. When a step
+//! reads it, the handler decodes it to a string at its top
+//! (`const _payload = new TextDecoder().decode(triggerData.input);`) and each
+//! `input` trigger data reference is rewritten to that local before emission,
+//! on a copy of the IR. Paths below `input` are left as they are.
+
+use std::borrow::Cow;
+
+use crate::ir::types::*;
+use crate::ir::validate::{collect_trigger_data_refs_from_step, visit_operation_value_exprs_mut};
+
+use super::writer::CodeWriter;
+
+/// The decoded payload's local name.
+const PAYLOAD_LOCAL: &str = "_payload";
+const PAYLOAD_FIELD: &str = "input";
+
+/// Whether the workflow has an HTTP trigger and some step reads its payload.
+pub fn reads_http_payload(ir: &WorkflowIR) -> bool {
+    matches!(ir.trigger, TriggerDef::Http(_)) && block_reads_payload(&ir.handler_body)
+}
+
+fn block_reads_payload(block: &Block) -> bool {
+    block.steps.iter().any(|step| {
+        collect_trigger_data_refs_from_step(step)
+            .iter()
+            .any(|field| field == PAYLOAD_FIELD)
+            || match &step.operation {
+                Operation::Branch(branch) => {
+                    block_reads_payload(&branch.true_branch)
+                        || block_reads_payload(&branch.false_branch)
+                }
+                _ => false,
+            }
+    })
+}
+
+/// Point payload references at the decoded local. IRs that do not read the
+/// payload are used as-is.
+pub fn resolve_payload_refs(ir: &WorkflowIR) -> Cow<'_, WorkflowIR> {
+    if !reads_http_payload(ir) {
+        return Cow::Borrowed(ir);
+    }
+
+    let mut resolved = ir.clone();
+    rewrite_block(&mut resolved.handler_body);
+    Cow::Owned(resolved)
+}
+
+fn rewrite_block(block: &mut Block) {
+    for step in &mut block.steps {
+        visit_operation_value_exprs_mut(&mut step.operation, &mut rewrite_expr);
+        if let Operation::Branch(branch) = &mut step.operation {
+            rewrite_block(&mut branch.true_branch);
+            rewrite_block(&mut branch.false_branch);
+        }
+    }
+}
+
+fn rewrite_expr(expr: &mut ValueExpr) {
+    match expr {
+        ValueExpr::TriggerDataRef { field } if field == PAYLOAD_FIELD => {
+            *expr = ValueExpr::raw(PAYLOAD_LOCAL);
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    rewrite_expr(value);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                rewrite_expr(value);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
+/// Declare the decoded payload, at the top of the handler.
+pub fn emit_payload_decode(w: &mut CodeWriter) {
+    w.comment("HTTP trigger payload");
+    w.line_fmt(format_args!(
+        "const {} = new TextDecoder().decode(triggerData.{});",
+        PAYLOAD_LOCAL, PAYLOAD_FIELD
+    ));
+    w.blank();
+}
@@ -1,5 +1,6 @@
 //! Scan the IR to determine which imports are needed and emit them.
 
+use super::operations::block_tag_constant;
 use super::writer::CodeWriter;
 use crate::ir::types::*;
 
@@ -22,6 +23,8 @@ pub struct ImportSet {
     pub bytes_to_hex: bool,
     pub prepare_report_request: bool,
     pub tx_status: bool,
+    pub latest_block_number: bool,
+    pub last_finalized_block_number: bool,
 
     // viem
     pub encode_function_data: bool,
@@ -86,7 +89,8 @@ fn scan_operation(op: &Operation, imports: &mut ImportSet) {
             imports.ok_fn = true;
             match &http.consensus {
                 ConsensusStrategy::Identical => imports.consensus_identical = true,
-                ConsensusStrategy::MedianByFields { .. } => imports.consensus_by_fields = true,
+                ConsensusStrategy::MedianByFields { .. }
+                | ConsensusStrategy::IdenticalFields { .. } => imports.consensus_by_fields = true,
                 ConsensusStrategy::Custom { .. } => {}
             }
         }
@@ -95,14 +99,20 @@ fn scan_operation(op: &Operation, imports: &mut ImportSet) {
             imports.ok_fn = true;
             match &ai.consensus {
                 ConsensusStrategy::Identical => imports.consensus_identical = true,
-                ConsensusStrategy::MedianByFields { .. } => imports.consensus_by_fields = true,
+                ConsensusStrategy::MedianByFields { .. }
+                | ConsensusStrategy::IdenticalFields { .. } => imports.consensus_by_fields = true,
                 ConsensusStrategy::Custom { .. } => {}
             }
         }
-        Operation::EvmRead(_) => {
+        Operation::EvmRead(read) => {
             imports.encode_call_msg = true;
             imports.encode_function_data = true;
             imports.decode_function_result = true;
+            match read.block_number.as_ref().and_then(block_tag_constant) {
+                Some("LATEST_BLOCK_NUMBER") => imports.latest_block_number = true,
+                Some("LAST_FINALIZED_BLOCK_NUMBER") => imports.last_finalized_block_number = true,
+                _ => {}
+            }
         }
         Operation::AbiEncode(op) => {
             if op.function_name.is_some() {
@@ -155,6 +165,12 @@ pub fn emit_imports(imports: &ImportSet, w: &mut CodeWriter) {
     if imports.tx_status {
         sdk_items.push("TxStatus");
     }
+    if imports.latest_block_number {
+        sdk_items.push("LATEST_BLOCK_NUMBER");
+    }
+    if imports.last_finalized_block_number {
+        sdk_items.push("LAST_FINALIZED_BLOCK_NUMBER");
+    }
 
     let mut sdk_types: Vec<&str> = Vec::new();
     if imports.runtime_type {
@@ -180,7 +196,7 @@ pub fn emit_imports(imports: &ImportSet, w: &mut CodeWriter) {
 
     let all_sdk: Vec<&str> = sdk_items.into_iter().chain(sdk_types).collect();
     if !all_sdk.is_empty() {
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "import {{ {} }} from \"@chainlink/cre-sdk\";",
             all_sdk.join(", ")
         ));
@@ -210,7 +226,7 @@ pub fn emit_imports(imports: &ImportSet, w: &mut CodeWriter) {
         viem_items.push("parseAbi");
     }
     if !viem_items.is_empty() {
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "import {{ {} }} from \"viem\";",
             viem_items.join(", ")
         ));
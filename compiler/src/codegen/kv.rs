@@ -0,0 +1,75 @@
+//! KvRead / KvWrite emission.
+//!
+//! Everything that depends on the shape of the CRE SDK's key-value
+//! capability (the client class, its method names, the request and response
+//! fields) is in this module, so a change to that API is a change here only:
+//!
+//! ```ts
+//! const kvClient = new cre.capabilities.KeyValueClient();
+//! const step_x: string | undefined = kvClient.get(runtime, { key: "lastBlock" }).result().value;
+//! kvClient.set(runtime, { key: "lastBlock", value: String(step_y) }).result();
+//! ```
+
+use super::value_expr::emit_value_expr;
+use super::writer::CodeWriter;
+use crate::ir::types::*;
+
+/// The client's local name in the handler.
+const CLIENT: &str = "kvClient";
+const CLIENT_CLASS: &str = "cre.capabilities.KeyValueClient";
+const GET: &str = "get";
+const SET: &str = "set";
+/// Field of the get response holding the stored string, absent when the
+/// key was never set.
+const VALUE_FIELD: &str = "value";
+
+/// Whether any step (branch arms included) reads or writes the store.
+pub fn uses_kv(block: &Block) -> bool {
+    block.steps.iter().any(|s| match &s.operation {
+        Operation::KvRead(_) | Operation::KvWrite(_) => true,
+        Operation::Branch(b) => uses_kv(&b.true_branch) || uses_kv(&b.false_branch),
+        _ => false,
+    })
+}
+
+/// `const kvClient = new cre.capabilities.KeyValueClient();`
+pub fn emit_kv_client(w: &mut CodeWriter) {
+    w.line_fmt(format_args!("const {} = new {}();", CLIENT, CLIENT_CLASS));
+}
+
+/// `const step_x: string | undefined = kvClient.get(...).result().value;`
+pub fn emit_kv_read(step: &Step, op: &KvReadOp, w: &mut CodeWriter) {
+    w.comment(&step.label);
+    let call = kv_call(GET, &[("key", emit_value_expr(&op.key))]);
+    match &step.output {
+        Some(out) => w.line_fmt(format_args!(
+            "const {}: {} = {}.{};",
+            out.variable_name, out.ts_type, call, VALUE_FIELD
+        )),
+        None => w.line_fmt(format_args!("{};", call)),
+    }
+}
+
+/// `kvClient.set(...).result();`. The store holds strings, so the value is
+/// passed through `String(...)` (numbers and bigints keep their digits).
+pub fn emit_kv_write(step: &Step, op: &KvWriteOp, w: &mut CodeWriter) {
+    w.comment(&step.label);
+    let call = kv_call(
+        SET,
+        &[
+            ("key", emit_value_expr(&op.key)),
+            ("value", format!("String({})", emit_value_expr(&op.value))),
+        ],
+    );
+    w.line_fmt(format_args!("{};", call));
+}
+
+/// `kvClient.<method>(runtime, { field: expr, ... }).result()`
+fn kv_call(method: &str, fields: &[(&str, String)]) -> String {
+    let fields = fields
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}.{}(runtime, {{ {} }}).result()", CLIENT, method, fields)
+}
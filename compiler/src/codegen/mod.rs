@@ -1,25 +1,55 @@
 //! Codegen pass: WorkflowIR → CRE TypeScript project bundle.
 //!
-//! Public API: `codegen(ir) -> CodegenOutput`
+//! Public API: `codegen(ir) -> CodegenOutput`, `codegen_with_options(ir, options)`,
+//! `codegen_streaming(ir, sink)`,
+//! `codegen_cached(ir, snippets)`, `codegen_cancellable(ir, options, cancel)`,
+//! `CodegenOutput::diff(other) -> Vec<FileDiff>`
 
 mod config_schema;
+mod destructure;
+mod diff;
 mod fetch_fns;
-mod files;
+pub(crate) mod files;
 mod handler;
+pub(crate) mod hash;
+mod http_payload;
 mod imports;
+mod kv;
+mod names;
 mod operations;
+mod secrets;
+mod snippets;
 mod trigger;
 mod value_expr;
 mod writer;
 
-use crate::ir::types::WorkflowIR;
+use std::sync::Arc;
+
+use crate::cancel::{self, CancellationToken};
+use crate::error::{CompilerError, Phase};
+use crate::ir::types::{Block, Environment, Operation, WorkflowIR};
+use names::Names;
 use writer::CodeWriter;
 
-/// A generated file with its path and content.
+pub use diff::{DiffLine, FileDiff};
+pub use files::current_timestamp;
+pub use snippets::SnippetCache;
+
+/// A generated file with its path and content. The content is shared, so
+/// cloning a file (or a whole [`CodegenOutput`]) never copies the source text.
 #[derive(Debug, Clone)]
 pub struct GeneratedFile {
     pub path: String,
-    pub content: String,
+    pub content: Arc<str>,
+}
+
+impl GeneratedFile {
+    fn new(path: impl Into<String>, content: String) -> Self {
+        Self {
+            path: path.into(),
+            content: content.into(),
+        }
+    }
 }
 
 /// The complete output of the codegen pass.
@@ -28,60 +58,211 @@ pub struct CodegenOutput {
     pub files: Vec<GeneratedFile>,
 }
 
+/// Caller settings for [`codegen_with_options`]. The default produces the
+/// same bundle as [`codegen`].
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    /// Production bundle: `main.ts` carries no generated comments (step
+    /// labels, section banners, notes, the auto-return marker), fetch
+    /// functions and their configs get short numbered names (`_f1`, `_c1`)
+    /// listed in `names.json`, and `audit.json` and `writes.md` are not
+    /// emitted.
+    pub compact: bool,
+    /// Rethrow an error thrown by a capability call (HTTP request, EVM
+    /// read or write, KV operation, AI call) as
+    /// `Error("[step:<id> \"<label>\"] " + message, { cause })`, so CRE logs
+    /// name the failing step. Errors thrown by Error nodes and user code
+    /// pass through unchanged. On by default.
+    pub label_step_errors: bool,
+    /// Treat every warning as fatal: [`crate::compile::compile_with_options`]
+    /// returns the warnings as its errors instead of a bundle. For CI gating;
+    /// code generation itself is unaffected.
+    pub strict: bool,
+    /// Version range of `@chainlink/cre-sdk` in `package.json`; `None` keeps
+    /// the default (`^1.0.9`).
+    pub sdk_version: Option<String>,
+    /// Tool the `package.json` scripts run binaries with.
+    pub package_manager: PackageManager,
+    /// Leave out `project.yaml` and `secrets.yaml`, for a workflow that
+    /// shares them with others at the root of one CRE project (see
+    /// [`crate::project::compile_project`]).
+    pub omit_project_files: bool,
+    /// Name fetch functions, their configs and the configs' types after a
+    /// hash of the operation (`fetch_1a2b3c4d`) instead of its step id, so
+    /// renaming a node leaves them unchanged. Compact names still win.
+    pub content_named_fetch_fns: bool,
+    /// Timestamp written to `manifest.json` as `generatedAt` (e.g.
+    /// [`current_timestamp`]). `None` leaves it out, so the same IR always
+    /// gives the same bundle.
+    pub generated_at: Option<String>,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self {
+            compact: false,
+            label_step_errors: true,
+            strict: false,
+            sdk_version: None,
+            package_manager: PackageManager::default(),
+            omit_project_files: false,
+            content_named_fetch_fns: false,
+            generated_at: None,
+        }
+    }
+}
+
+/// Package manager the generated project is installed with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PackageManager {
+    #[default]
+    Bun,
+    Npm,
+    Pnpm,
+}
+
+impl PackageManager {
+    /// Command prefix that runs a binary from an installed package.
+    pub fn exec(&self) -> &'static str {
+        match self {
+            PackageManager::Bun => "bun x",
+            PackageManager::Npm => "npx",
+            PackageManager::Pnpm => "pnpm exec",
+        }
+    }
+}
+
 /// Generate a complete CRE TypeScript project from a validated WorkflowIR.
+/// `manifest.json` carries no timestamp (see [`CodegenOptions::generated_at`]),
+/// so the same IR always gives the same bundle.
 pub fn codegen(ir: &WorkflowIR) -> CodegenOutput {
-    let mut output_files = Vec::new();
-
-    // Generate main.ts
-    let main_ts = gen_main_ts(ir);
-    output_files.push(GeneratedFile {
-        path: "main.ts".into(),
-        content: main_ts,
-    });
-
-    // Generate supporting files
-    let env = if ir.metadata.is_testnet {
-        "staging"
-    } else {
-        "production"
+    codegen_with_options(ir, &CodegenOptions::default())
+}
+
+/// Generate the project one file at a time, handing each to `sink` as soon as
+/// it is complete. Only the file being built and the manifest hashes are held,
+/// so peak memory stays flat for very large workflows. Files arrive in the
+/// same order as [`CodegenOutput::files`].
+pub fn codegen_streaming(ir: &WorkflowIR, sink: &mut dyn FnMut(GeneratedFile)) {
+    stream_project(ir, &CodegenOptions::default(), None, None, sink)
+        .expect("codegen without a token is never cancelled")
+}
+
+/// [`codegen`] with caller-supplied [`CodegenOptions`].
+pub fn codegen_with_options(ir: &WorkflowIR, options: &CodegenOptions) -> CodegenOutput {
+    let mut files = Vec::new();
+    stream_project(ir, options, None, None, &mut |f| files.push(f))
+        .expect("codegen without a token is never cancelled");
+    CodegenOutput { files }
+}
+
+/// [`codegen_with_options`], stopping with X001 once `cancel` is cancelled.
+/// Emission is checked before each fetch function, handler step and file.
+pub fn codegen_cancellable(
+    ir: &WorkflowIR,
+    options: &CodegenOptions,
+    cancel: &CancellationToken,
+) -> Result<CodegenOutput, Vec<CompilerError>> {
+    let mut files = Vec::new();
+    stream_project(ir, options, None, Some(cancel), &mut |f| files.push(f))?;
+    Ok(CodegenOutput { files })
+}
+
+/// [`codegen`], reusing the code of steps unchanged since the compilation
+/// that last used `snippets` and leaving this one's step code in it for the
+/// next. The output is identical to an uncached run.
+pub fn codegen_cached(ir: &WorkflowIR, snippets: &mut SnippetCache) -> CodegenOutput {
+    codegen_cached_with(ir, &CodegenOptions::default(), snippets)
+}
+
+/// [`codegen_cached`] with caller-supplied [`CodegenOptions`].
+pub(crate) fn codegen_cached_with(
+    ir: &WorkflowIR,
+    options: &CodegenOptions,
+    snippets: &mut SnippetCache,
+) -> CodegenOutput {
+    snippets.begin();
+    let mut files = Vec::new();
+    stream_project(ir, options, Some(&mut *snippets), None, &mut |f| {
+        files.push(f)
+    })
+    .expect("codegen without a token is never cancelled");
+    snippets.finish();
+    CodegenOutput { files }
+}
+
+fn stream_project(
+    ir: &WorkflowIR,
+    options: &CodegenOptions,
+    snippets: Option<&mut SnippetCache>,
+    cancel: Option<&CancellationToken>,
+    sink: &mut dyn FnMut(GeneratedFile),
+) -> Result<(), Vec<CompilerError>> {
+    let mut hashes = Vec::new();
+    let mut emit = |path: &str, content: String| {
+        cancel::check(cancel, Phase::Codegen)?;
+        hashes.push((path.to_string(), hash::sha256_hex(content.as_bytes())));
+        sink(GeneratedFile::new(path, content));
+        Ok::<_, Vec<CompilerError>>(())
     };
-    output_files.push(GeneratedFile {
-        path: format!("config.{env}.json"),
-        content: files::gen_config_json(ir),
-    });
-    output_files.push(GeneratedFile {
-        path: "secrets.yaml".into(),
-        content: files::gen_secrets_yaml(ir),
-    });
-    output_files.push(GeneratedFile {
-        path: "workflow.yaml".into(),
-        content: files::gen_workflow_yaml(ir),
-    });
-    output_files.push(GeneratedFile {
-        path: "project.yaml".into(),
-        content: files::gen_project_yaml(ir),
-    });
-    output_files.push(GeneratedFile {
-        path: "package.json".into(),
-        content: files::gen_package_json(ir),
-    });
-    output_files.push(GeneratedFile {
-        path: "tsconfig.json".into(),
-        content: files::gen_tsconfig_json(),
-    });
-    output_files.push(GeneratedFile {
-        path: ".env".into(),
-        content: files::gen_dot_env(ir),
-    });
-
-    CodegenOutput {
-        files: output_files,
+
+    let mut names = Names::new(options.compact, options.content_named_fetch_fns);
+    let main_ts = gen_main_ts(ir, &mut names, options.label_step_errors, snippets, cancel)?;
+    emit("main.ts", main_ts)?;
+
+    // Supporting files
+    let env = Environment::of(&ir.metadata).as_str();
+    emit(&format!("config.{env}.json"), files::gen_config_json(ir))?;
+    if !options.omit_project_files {
+        emit("secrets.yaml", files::gen_secrets_yaml(ir))?;
+    }
+    emit("workflow.yaml", files::gen_workflow_yaml(ir))?;
+    if !options.omit_project_files {
+        emit("project.yaml", files::gen_project_yaml(ir))?;
+    }
+    emit("package.json", files::gen_package_json(ir, options))?;
+    emit("tsconfig.json", files::gen_tsconfig_json())?;
+    emit(".env", files::gen_dot_env(ir))?;
+    emit(".gitignore", files::gen_gitignore())?;
+    if options.compact {
+        emit("names.json", files::gen_names_json(names.renamed()))?;
+    } else {
+        emit("audit.json", files::gen_audit_json(ir))?;
+        if let Some(writes) = files::gen_writes_md(ir) {
+            emit("writes.md", writes)?;
+        }
     }
+
+    // The manifest hashes everything above, so it must come last.
+    sink(files::gen_manifest(
+        ir,
+        &hashes,
+        options.generated_at.as_deref(),
+    ));
+    Ok(())
 }
 
 /// Generate the `main.ts` file content.
-fn gen_main_ts(ir: &WorkflowIR) -> String {
-    let mut w = CodeWriter::new();
+fn gen_main_ts(
+    ir: &WorkflowIR,
+    names: &mut Names,
+    label_errors: bool,
+    snippets: Option<&mut SnippetCache>,
+    cancel: Option<&CancellationToken>,
+) -> Result<String, Vec<CompilerError>> {
+    let decode_payload = http_payload::reads_http_payload(ir);
+    let ir = &*destructure::resolve_destructured_refs(ir);
+    let ir = &*http_payload::resolve_payload_refs(ir);
+    let shared_secrets = secrets::collect_shared_secrets(ir);
+    let ir = &*secrets::resolve_shared_secret_refs(ir, &shared_secrets);
+    // Typed before optional config reads become raw `runtime.config.X!`
+    let return_type = handler::infer_return_type(ir);
+    let ir = &*config_schema::resolve_optional_config_refs(ir);
+    let mut w = CodeWriter::with_capacity(
+        MAIN_TS_BASE_BYTES + MAIN_TS_BYTES_PER_STEP * count_steps(&ir.handler_body),
+    );
+    w.set_compact(names.is_compact());
+    w.set_cancel(cancel.cloned());
 
     // 1. IMPORTS
     let import_set = imports::collect_imports(ir);
@@ -95,33 +276,67 @@ fn gen_main_ts(ir: &WorkflowIR) -> String {
     // 2.5 EVM LOG MODULE CONSTANTS (eventAbi, eventSignature)
     handler::emit_evm_log_module_consts(ir, &mut w);
 
+    // 2.6 TRIGGER CHAIN CLIENT (shared by initWorkflow and the handler)
+    trigger::emit_trigger_chain_client(ir, &mut w);
+
     // 3. FETCH FUNCTIONS (top-level, before handler)
-    let fetch_fn_list = fetch_fns::collect_fetch_fns(&ir.handler_body);
-    let fetch_contexts = if !fetch_fn_list.is_empty() {
-        fetch_fns::emit_fetch_fns(&fetch_fn_list, &mut w)
+    let fetch_fn_list = fetch_fns::collect_fetch_fns(&ir.handler_body, names.is_content_named());
+    let mut fetch_contexts = if !fetch_fn_list.is_empty() {
+        let mut output_types = std::collections::HashMap::new();
+        crate::ir::returns::collect_output_types(&ir.handler_body, &mut output_types);
+        fetch_fns::emit_fetch_fns(&fetch_fn_list, &output_types, names, &mut w)
     } else {
         std::collections::HashMap::new()
     };
 
-    // 4. HANDLER
-    handler::emit_handler(ir, &fetch_contexts, &mut w);
+    // 4. HANDLER (secrets read by several steps are fetched once at its top)
+    secrets::link_fetch_contexts(&shared_secrets, &ir.handler_body, &mut fetch_contexts);
+    handler::emit_handler(
+        ir,
+        &return_type,
+        &fetch_contexts,
+        &shared_secrets,
+        decode_payload,
+        label_errors,
+        snippets,
+        &mut w,
+    );
     w.blank();
 
     // 5. INIT WORKFLOW + MAIN
     trigger::emit_init_and_main(ir, &mut w);
 
-    w.finish()
+    // 6. STEP MAP (generated code back to source nodes)
+    handler::emit_step_map(&ir.handler_body, &mut w);
+
+    w.finish_checked()
+}
+
+/// Rough size of `main.ts` without any steps, and per step, used to pre-size
+/// its buffer so large workflows are not copied through repeated regrowth.
+const MAIN_TS_BASE_BYTES: usize = 4096;
+const MAIN_TS_BYTES_PER_STEP: usize = 1024;
+
+fn count_steps(block: &Block) -> usize {
+    block
+        .steps
+        .iter()
+        .map(|step| match &step.operation {
+            Operation::Branch(branch) => {
+                1 + count_steps(&branch.true_branch) + count_steps(&branch.false_branch)
+            }
+            _ => 1,
+        })
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ir::types::*;
 
-    #[test]
-    fn codegen_produces_all_files() {
-        use crate::ir::types::*;
-
-        let ir = WorkflowIR {
+    fn minimal_ir() -> WorkflowIR {
+        WorkflowIR {
             metadata: WorkflowMetadata {
                 id: "test-workflow".into(),
                 name: "Test Workflow".into(),
@@ -129,6 +344,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::config("schedule"),
@@ -139,6 +355,8 @@ mod tests {
                 zod_type: ZodType::String,
                 default_value: Some("*/5 * * * *".into()),
                 description: None,
+                referenced_by: vec![],
+                optional_in_config: false,
             }],
             required_secrets: vec![],
             evm_chains: vec![],
@@ -150,12 +368,18 @@ mod tests {
                     label: "Return success".into(),
                     operation: Operation::Return(ReturnOp {
                         expression: ValueExpr::string("done"),
+                        auto_generated: false,
                     }),
                     output: None,
+                    notes: None,
                 }],
             },
-        };
+        }
+    }
 
+    #[test]
+    fn codegen_produces_all_files() {
+        let ir = minimal_ir();
         let output = codegen(&ir);
         let file_paths: Vec<&str> = output.files.iter().map(|f| f.path.as_str()).collect();
 
@@ -167,5 +391,50 @@ mod tests {
         assert!(file_paths.contains(&"package.json"));
         assert!(file_paths.contains(&"tsconfig.json"));
         assert!(file_paths.contains(&".env"));
+        assert!(file_paths.contains(&".gitignore"));
+        assert!(file_paths.contains(&"audit.json"));
+        assert!(file_paths.contains(&"manifest.json"));
+    }
+
+    #[test]
+    fn manifest_hashes_every_other_file() {
+        let options = CodegenOptions {
+            generated_at: Some("2025-01-01T00:00:00Z".into()),
+            ..CodegenOptions::default()
+        };
+        let output = codegen_with_options(&minimal_ir(), &options);
+        let manifest_file = output.files.last().unwrap();
+        assert_eq!(manifest_file.path, "manifest.json");
+
+        let manifest: serde_json::Value = serde_json::from_str(&manifest_file.content).unwrap();
+        assert_eq!(manifest["compilerVersion"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest["workflowId"], "test-workflow");
+        assert_eq!(manifest["workflowVersion"], "1.0.0");
+        assert_eq!(manifest["generatedAt"], "2025-01-01T00:00:00Z");
+
+        let entries = manifest["files"].as_array().unwrap();
+        assert_eq!(entries.len(), output.files.len() - 1);
+        for (entry, file) in entries.iter().zip(&output.files) {
+            assert_eq!(entry["path"], file.path.as_str());
+            assert_eq!(
+                entry["sha256"],
+                hash::sha256_hex(file.content.as_bytes()).as_str()
+            );
+        }
+        assert!(!entries.iter().any(|e| e["path"] == "manifest.json"));
+    }
+
+    #[test]
+    fn manifest_has_no_timestamp_unless_one_is_given() {
+        let ir = minimal_ir();
+        let output = codegen(&ir);
+        let manifest: serde_json::Value =
+            serde_json::from_str(&output.files.last().unwrap().content).unwrap();
+        assert!(manifest.get("generatedAt").is_none(), "{manifest}");
+
+        let again = codegen(&ir);
+        for (a, b) in output.files.iter().zip(&again.files) {
+            assert_eq!(a.content, b.content, "{}", a.path);
+        }
     }
 }
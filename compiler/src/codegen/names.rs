@@ -0,0 +1,94 @@
+//! Names of generated helpers that compact mode shortens.
+//!
+//! By default, fetch functions, their augmented configs and the configs'
+//! types are named after their step (`fetch_http_1`, `_fetchCfg_http_1`,
+//! `Fetch_http_1_Config`). In compact mode the n-th fetch function becomes
+//! `_f<n>`, its config `_c<n>` and the config's type `_T<n>`, and every rename
+//! is recorded so `names.json` can map runtime errors back to the long names.
+//! Names are chosen while emitting, so user code and string literals are
+//! never touched.
+//!
+//! With content naming, the step id in the default names is replaced by a
+//! hash of the operation ([`content_key`]): `fetch_1a2b3c4d`,
+//! `_fetchCfg_1a2b3c4d`, `Fetch_1a2b3c4d_Config`.
+
+use super::hash::sha256_hex;
+
+/// Picks helper names and records the renames made in compact mode.
+pub struct Names {
+    compact: bool,
+    content_named: bool,
+    /// `(short, original)` in order of emission.
+    renamed: Vec<(String, String)>,
+}
+
+impl Names {
+    pub fn new(compact: bool, content_named: bool) -> Self {
+        Names {
+            compact,
+            content_named,
+            renamed: Vec::new(),
+        }
+    }
+
+    /// Name of the `index`-th (0-based) fetch function, whose default name
+    /// is `default`.
+    pub fn fetch_fn(&mut self, index: usize, default: &str) -> String {
+        self.pick(format!("_f{}", index + 1), default)
+    }
+
+    /// Name of the augmented config passed to the `index`-th fetch function.
+    pub fn fetch_cfg(&mut self, index: usize, default: &str) -> String {
+        self.pick(format!("_c{}", index + 1), default)
+    }
+
+    /// Name of the type of the `index`-th fetch function's augmented config.
+    pub fn fetch_cfg_type(&mut self, index: usize, default: &str) -> String {
+        self.pick(format!("_T{}", index + 1), default)
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Whether default names are built from [`content_key`] rather than the
+    /// step id.
+    pub fn is_content_named(&self) -> bool {
+        self.content_named
+    }
+
+    /// `(short, original)` for every rename, in order of emission.
+    pub fn renamed(&self) -> &[(String, String)] {
+        &self.renamed
+    }
+
+    fn pick(&mut self, short: String, default: &str) -> String {
+        if !self.compact {
+            return default.to_string();
+        }
+        self.renamed.push((short.clone(), default.to_string()));
+        short
+    }
+}
+
+/// Stand-in for the step id in content-named helpers: the first 8 hex
+/// digits of the SHA-256 of the operation's IR JSON.
+pub fn content_key<T: serde::Serialize>(op: &T) -> String {
+    let json = serde_json::to_vec(op).expect("IR operations always serialize");
+    sha256_hex(&json)[..8].to_string()
+}
+
+/// Default name of a step's fetch function: `fetch_http_1`.
+pub fn fetch_fn_name(step_id: &str) -> String {
+    format!("fetch_{}", step_id.replace('-', "_"))
+}
+
+/// Default name of a step's augmented fetch config: `_fetchCfg_http_1`.
+pub fn fetch_cfg_name(step_id: &str) -> String {
+    format!("_fetchCfg_{}", step_id.replace('-', "_"))
+}
+
+/// Default name of the type of a step's augmented fetch config: `Fetch_http_1_Config`.
+pub fn fetch_cfg_type_name(step_id: &str) -> String {
+    format!("Fetch_{}_Config", step_id.replace('-', "_"))
+}
@@ -6,7 +6,8 @@
 use std::collections::HashMap;
 
 use super::fetch_fns::FetchContext;
-use super::value_expr::emit_value_expr;
+use super::names::{fetch_cfg_name, fetch_cfg_type_name, fetch_fn_name};
+use super::value_expr::{emit_bigint_value_expr, emit_value_expr};
 use super::writer::CodeWriter;
 use crate::ir::types::*;
 
@@ -19,37 +20,46 @@ pub fn emit_http_request(
     fetch_contexts: &HashMap<String, FetchContext>,
     w: &mut CodeWriter,
 ) {
-    let fetch_fn_name = format!("fetch_{}", step.id.replace('-', "_"));
+    let ctx = fetch_contexts.get(&step.id);
+    let fetch_fn_name = ctx.map_or_else(|| fetch_fn_name(&step.id), |c| c.fn_name.clone());
     let consensus_expr = emit_consensus(&op.consensus);
 
     if let Some(ref out) = step.output {
-        w.line(&format!("// {}", step.label));
+        w.comment(&step.label);
 
-        let ctx = fetch_contexts.get(&step.id);
         let has_dynamic = ctx.map_or(false, |c| !c.dynamic_refs.is_empty());
         let has_auth = ctx.map_or(false, |c| c.has_auth);
 
         if has_dynamic || has_auth {
-            // Fetch the auth secret if needed
-            if let Some(ref auth) = op.authentication {
-                let secret_var = format!("_authSecret_{}", step.id.replace('-', "_"));
-                w.line(&format!(
+            // Fetch the auth secret if needed (unless hoisted to the handler top)
+            let shared_secret_var = ctx.and_then(|c| c.shared_secret_var.clone());
+            let secret_var = shared_secret_var
+                .clone()
+                .unwrap_or_else(|| format!("_authSecret_{}", step.id.replace('-', "_")));
+            if let Some(ref auth) = op.authentication
+                && shared_secret_var.is_none()
+            {
+                w.line_fmt(format_args!(
                     "const {} = runtime.getSecret({{ id: \"{}\" }}).result();",
                     secret_var, auth.token_secret,
                 ));
             }
 
             // Build augmented config
-            let cfg_var = format!("_fetchCfg_{}", step.id.replace('-', "_"));
-            w.block_open(&format!("const {} =", cfg_var));
+            let cfg_var = ctx
+                .and_then(|c| c.cfg_var.clone())
+                .unwrap_or_else(|| fetch_cfg_name(&step.id));
+            let cfg_type = ctx
+                .and_then(|c| c.cfg_type.clone())
+                .unwrap_or_else(|| fetch_cfg_type_name(&step.id));
+            w.block_open_fmt(format_args!("const {}: {} =", cfg_var, cfg_type));
             w.line("...runtime.config,");
             if op.authentication.is_some() {
-                let secret_var = format!("_authSecret_{}", step.id.replace('-', "_"));
-                w.line(&format!("_authToken: {}.value,", secret_var));
+                w.line_fmt(format_args!("_authToken: {}.value,", secret_var));
             }
             if let Some(c) = ctx {
                 for dyn_ref in &c.dynamic_refs {
-                    w.line(&format!(
+                    w.line_fmt(format_args!(
                         "{}: {},",
                         dyn_ref.config_key,
                         emit_value_expr(&dyn_ref.handler_expr),
@@ -58,12 +68,12 @@ pub fn emit_http_request(
             }
             w.dedent();
             w.line("};");
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = httpClient.sendRequest(runtime, {}, {})({}).result();",
                 out.variable_name, fetch_fn_name, consensus_expr, cfg_var,
             ));
         } else {
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = httpClient.sendRequest(runtime, {}, {})(runtime.config).result();",
                 out.variable_name, fetch_fn_name, consensus_expr
             ));
@@ -79,7 +89,7 @@ pub fn emit_http_request(
 ///   2. `encodeCallMsg({ from, to, data })` (from CRE SDK) to wrap the call message
 ///   3. `evmClient.callContract(runtime, { call })` to execute the read
 pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
-    w.line(&format!("// {}", step.label));
+    w.comment(&step.label);
 
     let binding = &op.evm_client_binding;
     let contract = emit_value_expr(&op.contract_address);
@@ -90,7 +100,10 @@ pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
 
         // 1. encodeFunctionData
         let calldata_var = format!("_calldata_{}", safe_id);
-        w.line(&format!("const {} = encodeFunctionData({{", calldata_var));
+        w.line_fmt(format_args!(
+            "const {} = encodeFunctionData({{",
+            calldata_var
+        ));
         w.indent();
         // Wrap in array if not already one (single ABI item → [item])
         let abi_array = if abi.starts_with('[') {
@@ -98,14 +111,18 @@ pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
         } else {
             format!("[{}]", abi)
         };
-        w.line(&format!("abi: {} as const,", abi_array));
-        w.line(&format!("functionName: \"{}\",", op.function_name));
+        w.line_fmt(format_args!("abi: {} as const,", abi_array));
+        w.line_fmt(format_args!("functionName: \"{}\",", op.function_name));
         if !op.args.is_empty() {
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "args: [{}],",
                 op.args
                     .iter()
-                    .map(|a| emit_value_expr(&a.value))
+                    .map(|a| if needs_bigint(&a.abi_type) {
+                        emit_bigint_value_expr(&a.value)
+                    } else {
+                        emit_value_expr(&a.value)
+                    })
                     .collect::<Vec<_>>()
                     .join(", ")
             ));
@@ -120,48 +137,68 @@ pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
             .map(|a| emit_value_expr(a))
             .unwrap_or_else(|| "\"0x0000000000000000000000000000000000000000\"".to_string());
 
+        let block_number = op
+            .block_number
+            .as_ref()
+            .map(|block| emit_block_number(block, &safe_id, w));
+
         let raw_var = format!("_raw_{}", safe_id);
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = {}.callContract(runtime, {{",
             raw_var, binding,
         ));
         w.indent();
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "call: encodeCallMsg({{ from: {}, to: {}, data: {} }}),",
             from_addr, contract, calldata_var,
         ));
+        if let Some(block_number) = block_number {
+            w.line_fmt(format_args!("blockNumber: {},", block_number));
+        }
         w.dedent();
         w.line("}).result();");
 
         // 3. Decode raw bytes
         let bytes_var = format!("_bytes_{}", safe_id);
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = new Uint8Array(Object.keys({}.data).length);",
             bytes_var, raw_var
         ));
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "for (let i = 0; i < {}.length; i++) {0}[i] = {}.data[i];",
             bytes_var, raw_var
         ));
         let decoded_var = format!("_decoded_{}", safe_id);
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = decodeFunctionResult({{",
             decoded_var,
         ));
         w.indent();
-        w.line(&format!("abi: {} as const,", abi_array));
-        w.line(&format!("functionName: \"{}\",", op.function_name));
-        w.line(&format!(
+        w.line_fmt(format_args!("abi: {} as const,", abi_array));
+        w.line_fmt(format_args!("functionName: \"{}\",", op.function_name));
+        w.line_fmt(format_args!(
             "data: `0x${{Buffer.from({}).toString(\"hex\")}}` as `0x${{string}}`,",
             bytes_var
         ));
         w.dedent();
         w.line("});");
 
-        // 4. Wrap decoded result into named object (matches frontend normalizeReadResult)
-        if op.output_names.len() <= 1 {
+        // 4. Wrap decoded result into named object (matches frontend normalizeReadResult),
+        //    or bind each output to its own local when destructured
+        if out.destructure_fields.is_some() {
+            let locals: Vec<String> = out
+                .destructured_vars()
+                .into_iter()
+                .map(|(_, local)| local)
+                .collect();
+            w.line_fmt(format_args!(
+                "const [{}] = {};",
+                locals.join(", "),
+                decoded_var
+            ));
+        } else if op.output_names.len() <= 1 {
             let field_name = op.output_names.first().map(|s| s.as_str()).unwrap_or("value");
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = {{ {}: {} }};",
                 out.variable_name, field_name, decoded_var
             ));
@@ -172,7 +209,7 @@ pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
                 .enumerate()
                 .map(|(i, name)| format!("{}: {}[{}]", name, decoded_var, i))
                 .collect();
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = {{ {} }};",
                 out.variable_name,
                 fields.join(", ")
@@ -185,7 +222,7 @@ pub fn emit_evm_read(step: &Step, op: &EvmReadOp, w: &mut CodeWriter) {
 ///   1. `runtime.report(prepareReportRequest(data))` — generate signed report
 ///   2. `evmClient.writeReport(runtime, { receiver, report, gasConfig })` — submit via KeystoneForwarder
 pub fn emit_evm_write(step: &Step, op: &EvmWriteOp, w: &mut CodeWriter) {
-    w.line(&format!("// {}", step.label));
+    w.comment(&step.label);
     let binding = &op.evm_client_binding;
     let receiver = emit_value_expr(&op.receiver_address);
     let gas_str = emit_gas_limit_string(&op.gas_limit);
@@ -194,30 +231,30 @@ pub fn emit_evm_write(step: &Step, op: &EvmWriteOp, w: &mut CodeWriter) {
     if let Some(ref out) = step.output {
         // Step 1: generate signed report
         let report_var = format!("report_{}", step.id.replace('-', "_"));
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = runtime.report(prepareReportRequest({})).result();",
             report_var, data,
         ));
 
         // Step 2: submit report on-chain
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = {}.writeReport(runtime, {{",
             out.variable_name, binding,
         ));
         w.indent();
-        w.line(&format!("receiver: {},", receiver));
-        w.line(&format!("report: {},", report_var));
-        w.line(&format!("gasConfig: {{ gasLimit: {} }},", gas_str));
+        w.line_fmt(format_args!("receiver: {},", receiver));
+        w.line_fmt(format_args!("report: {},", report_var));
+        w.line_fmt(format_args!("gasConfig: {{ gasLimit: {} }},", gas_str));
         w.dedent();
         w.line("}).result();");
 
         // Step 3: check txStatus
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "if ({}.txStatus !== TxStatus.SUCCESS) {{",
             out.variable_name,
         ));
         w.indent();
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "throw new Error(`Failed to write report: ${{{}.errorMessage || {}.txStatus}}`);",
             out.variable_name, out.variable_name,
         ));
@@ -226,11 +263,11 @@ pub fn emit_evm_write(step: &Step, op: &EvmWriteOp, w: &mut CodeWriter) {
 
         // Step 4: log txHash
         let tx_hash_var = format!("txHash_{}", step.id.replace('-', "_"));
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = {}.txHash || new Uint8Array(32);",
             tx_hash_var, out.variable_name,
         ));
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "runtime.log(`Write report transaction succeeded at txHash: ${{bytesToHex({})}}`);",
             tx_hash_var,
         ));
@@ -249,11 +286,12 @@ fn emit_gas_limit_string(expr: &ValueExpr) -> String {
     }
 }
 
-/// Emit a CodeNode (IIFE with injected bindings).
+/// Emit a CodeNode (IIFE with injected bindings). With an `iterate_over`
+/// input, the user code runs inside a `.map()` over it.
 pub fn emit_code_node(step: &Step, op: &CodeNodeOp, w: &mut CodeWriter) {
-    w.line(&format!("// {}", step.label));
+    w.comment(&step.label);
     if let Some(ref out) = step.output {
-        w.line(&format!("const {} = (() => {{", out.variable_name));
+        w.line_fmt(format_args!("const {} = (() => {{", out.variable_name));
     } else {
         w.line("(() => {");
     }
@@ -261,13 +299,19 @@ pub fn emit_code_node(step: &Step, op: &CodeNodeOp, w: &mut CodeWriter) {
 
     // Inject input bindings
     for binding in &op.input_bindings {
-        w.line(&format!(
+        w.line_fmt(format_args!(
             "const {} = {};",
             binding.variable_name,
             emit_value_expr(&binding.value),
         ));
     }
 
+    // The mapper parameter shadows the array, so the user code sees one item.
+    if let Some(item) = &op.iterate_over {
+        w.line_fmt(format_args!("return {}.map(({}) => {{", item, item));
+        w.indent();
+    }
+
     // User code
     for line in op.code.lines() {
         w.line(line);
@@ -276,62 +320,128 @@ pub fn emit_code_node(step: &Step, op: &CodeNodeOp, w: &mut CodeWriter) {
     // Auto-generate return statement from declared output fields
     if !op.output_fields.is_empty() {
         let fields = op.output_fields.join(", ");
-        w.line(&format!("return {{ {} }};", fields));
+        w.line_fmt(format_args!("return {{ {} }};", fields));
     }
 
+    if op.iterate_over.is_some() {
+        w.dedent();
+        w.line("});");
+    }
     w.dedent();
     w.line("})();");
 }
 
+/// Emit a Merge that joins independent chains: `Append` builds an array of
+/// the inputs; `Custom` runs the merge code with the inputs as `inputs`.
+pub fn emit_standalone_merge(step: &Step, op: &MergeOp, w: &mut CodeWriter) {
+    let Some(ref out) = step.output else {
+        return;
+    };
+    let inputs: Vec<String> = op
+        .inputs
+        .iter()
+        .map(|i| emit_value_expr(&i.value))
+        .collect();
+    let inputs = format!("[{}]", inputs.join(", "));
+    match &op.strategy {
+        MergeStrategy::Append => {
+            w.comment(&step.label);
+            w.line_fmt(format_args!("const {} = {};", out.variable_name, inputs));
+        }
+        MergeStrategy::Custom { expr } => {
+            w.comment(&step.label);
+            w.line_fmt(format_args!("const {} = (() => {{", out.variable_name));
+            w.indent();
+            w.line_fmt(format_args!("const inputs = {};", inputs));
+            for line in expr.lines() {
+                w.line(line);
+            }
+            w.dedent();
+            w.line("})();");
+        }
+        // Only meaningful after a Branch; rejected for standalone merges (E016).
+        MergeStrategy::PassThrough => {}
+    }
+}
+
+/// Scalar `int*`/`uint*` ABI types, which viem expects as BigInt. Arrays of
+/// them (`uint256[]`) are passed through unchanged.
 fn needs_bigint(ty: &str) -> bool {
-    ty.starts_with("uint") || ty.starts_with("int")
+    (ty.starts_with("uint") || ty.starts_with("int")) && !ty.contains('[')
 }
 
 /// Emit an AbiEncode expression.
 pub fn emit_abi_encode(step: &Step, op: &AbiEncodeOp, w: &mut CodeWriter) {
-    w.line(&format!("// {}", step.label));
-
-    // Build param_name -> abi_type map from abi_json for BigInt wrapping
-    let type_map: HashMap<String, String> =
-        serde_json::from_str::<Vec<serde_json::Value>>(&op.abi_json)
-            .unwrap_or_default()
-            .into_iter()
-            .filter_map(|v| {
-                let name = v.get("name")?.as_str()?.to_string();
-                let ty = v.get("type")?.as_str()?.to_string();
-                Some((name, ty))
-            })
-            .collect();
-
-    let args: Vec<String> = op
-        .data_mappings
-        .iter()
-        .map(|m| {
-            let val = emit_value_expr(&m.value);
-            if let Some(ty) = type_map.get(&m.param_name) {
-                if needs_bigint(ty) {
-                    return format!("BigInt({})", val);
-                }
+    w.comment(&step.label);
+
+    // Build param_name -> abi_type map from abi_json for BigInt wrapping.
+    // A function ABI item carries its parameters under `inputs`.
+    let params = match serde_json::from_str::<serde_json::Value>(&op.abi_json) {
+        Ok(serde_json::Value::Array(items)) => items,
+        Ok(serde_json::Value::Object(mut item)) => match item.remove("inputs") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    let type_map: HashMap<String, String> = params
+        .into_iter()
+        .filter_map(|v| {
+            let name = v.get("name")?.as_str()?.to_string();
+            let ty = v.get("type")?.as_str()?.to_string();
+            Some((name, ty))
+        })
+        .collect();
+
+    // Several mappings for one array parameter (a multicall's calls) are its
+    // elements, in order.
+    let mut grouped: Vec<(&str, Vec<String>)> = Vec::new();
+    for m in &op.data_mappings {
+        let ty = type_map.get(&m.param_name);
+        let value = match ty {
+            Some(ty) if needs_bigint(ty) && m.value_kind.is_none() => {
+                emit_bigint_value_expr(&m.value)
+            }
+            _ => emit_value_expr(&m.value),
+        };
+        let is_array = ty.is_some_and(|ty| ty.ends_with(']'));
+        match grouped
+            .iter_mut()
+            .find(|(name, _)| is_array && *name == m.param_name)
+        {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((&m.param_name, vec![value])),
+        }
+    }
+    let args: Vec<String> = grouped
+        .into_iter()
+        .map(|(_, mut values)| {
+            if values.len() == 1 {
+                values.remove(0)
+            } else {
+                format!("[{}]", values.join(", "))
             }
-            val
         })
         .collect();
 
     if let Some(ref out) = step.output {
-        w.line(&format!("const {} = {{", out.variable_name));
+        w.line_fmt(format_args!("const {} = {{", out.variable_name));
         w.indent();
         if let Some(ref fn_name) = op.function_name {
             // Convenience node: full function ABI with functionName
             w.line("encoded: encodeFunctionData({");
             w.indent();
-            w.line(&format!("abi: [{}],", op.abi_json));
-            w.line(&format!("functionName: \"{}\",", fn_name));
-            w.line(&format!("args: [{}],", args.join(", ")));
+            w.line_fmt(format_args!("abi: [{}],", op.abi_json));
+            w.line_fmt(format_args!(
+                "functionName: {},",
+                emit_value_expr(&ValueExpr::string(fn_name.as_str()))
+            ));
+            w.line_fmt(format_args!("args: [{}],", args.join(", ")));
             w.dedent();
             w.line("}),");
         } else {
             // Standalone: parameter-only ABI encoding (no function selector)
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "encoded: encodeAbiParameters({}, [{}]),",
                 op.abi_json,
                 args.join(", ")
@@ -344,29 +454,91 @@ pub fn emit_abi_encode(step: &Step, op: &AbiEncodeOp, w: &mut CodeWriter) {
 
 /// Emit an AbiDecode expression.
 pub fn emit_abi_decode(step: &Step, op: &AbiDecodeOp, w: &mut CodeWriter) {
-    w.line(&format!("// {}", step.label));
+    w.comment(&step.label);
     let input = emit_value_expr(&op.input);
 
     if let Some(ref out) = step.output {
-        if let Some(ref fields) = out.destructure_fields {
-            w.line(&format!(
-                "const {{ {} }} = decodeFunctionResult({{",
-                fields.join(", ")
+        if out.destructure_fields.is_some() {
+            w.line_fmt(format_args!(
+                "const {} = decodeFunctionResult({{",
+                out.destructured_object()
             ));
         } else {
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = decodeFunctionResult({{",
                 out.variable_name
             ));
         }
         w.indent();
-        w.line(&format!("abi: {},", op.abi_json));
-        w.line(&format!("data: {},", input));
+        w.line_fmt(format_args!("abi: {},", op.abi_json));
+        w.line_fmt(format_args!("data: {},", input));
         w.dedent();
         w.line("});");
     }
 }
 
+/// Emit a Compute step: `const step_x = <arithmetic>;`.
+/// Operands are coerced with `Number(...)` or `BigInt(...)` so mixed inputs
+/// (JSON strings, EvmRead bigints) combine without runtime type errors.
+pub fn emit_compute(step: &Step, op: &ComputeOp, w: &mut CodeWriter) {
+    w.comment(&step.label);
+    if let Some(ref out) = step.output {
+        w.line_fmt(format_args!(
+            "const {} = {};",
+            out.variable_name,
+            emit_compute_expr(&op.expr, op.mode)
+        ));
+    }
+}
+
+fn emit_compute_expr(expr: &ComputeExpr, mode: NumericMode) -> String {
+    match expr {
+        ComputeExpr::Literal { value } => match mode {
+            NumericMode::Number => value.clone(),
+            NumericMode::BigInt => format!("{}n", value),
+        },
+        ComputeExpr::Value { value } => match mode {
+            NumericMode::Number => format!("Number({})", emit_value_expr(value)),
+            NumericMode::BigInt => format!("BigInt({})", emit_value_expr(value)),
+        },
+        ComputeExpr::Neg { operand } => match operand.as_ref() {
+            // Parenthesize so `-(-x)` never becomes the `--` operator.
+            ComputeExpr::Binary { .. } | ComputeExpr::Neg { .. } => {
+                format!("-({})", emit_compute_expr(operand, mode))
+            }
+            _ => format!("-{}", emit_compute_expr(operand, mode)),
+        },
+        ComputeExpr::Binary { op, left, right } => {
+            let prec = arith_precedence(*op);
+            let mut l = emit_compute_expr(left, mode);
+            if matches!(left.as_ref(), ComputeExpr::Binary { op: lop, .. } if arith_precedence(*lop) < prec)
+            {
+                l = format!("({})", l);
+            }
+            let mut r = emit_compute_expr(right, mode);
+            if matches!(right.as_ref(), ComputeExpr::Binary { op: rop, .. } if arith_precedence(*rop) <= prec)
+            {
+                r = format!("({})", r);
+            }
+            let symbol = match op {
+                ArithOp::Add => "+",
+                ArithOp::Sub => "-",
+                ArithOp::Mul => "*",
+                ArithOp::Div => "/",
+                ArithOp::Mod => "%",
+            };
+            format!("{} {} {}", l, symbol, r)
+        }
+    }
+}
+
+fn arith_precedence(op: ArithOp) -> u8 {
+    match op {
+        ArithOp::Add | ArithOp::Sub => 1,
+        ArithOp::Mul | ArithOp::Div | ArithOp::Mod => 2,
+    }
+}
+
 /// Emit an AiCall (uses HTTP pattern with provider-specific body).
 /// Fetches the API key secret and passes it through augmented config to the fetch function.
 pub fn emit_ai_call(
@@ -375,34 +547,44 @@ pub fn emit_ai_call(
     fetch_contexts: &HashMap<String, FetchContext>,
     w: &mut CodeWriter,
 ) {
-    let fetch_fn_name = format!("fetch_{}", step.id.replace('-', "_"));
+    let ctx = fetch_contexts.get(&step.id);
+    let fetch_fn_name = ctx.map_or_else(|| fetch_fn_name(&step.id), |c| c.fn_name.clone());
     let consensus_expr = emit_consensus(&op.consensus);
 
     if let Some(ref out) = step.output {
-        w.line(&format!("// {}", step.label));
-
-        // Fetch the API key secret
-        let secret_var = format!("_aiApiKey_{}", step.id.replace('-', "_"));
-        let secret_name = fetch_contexts
-            .get(&step.id)
-            .and_then(|c| c.ai_api_key_secret.as_deref())
-            .unwrap_or(&op.api_key_secret);
-        w.line(&format!(
-            "const {} = runtime.getSecret({{ id: \"{}\" }}).result();",
-            secret_var, secret_name,
-        ));
+        w.comment(&step.label);
+
+        // Fetch the API key secret (unless hoisted to the handler top)
+        let secret_var = match ctx.and_then(|c| c.shared_secret_var.clone()) {
+            Some(shared) => shared,
+            None => {
+                let secret_var = format!("_aiApiKey_{}", step.id.replace('-', "_"));
+                let secret_name = ctx
+                    .and_then(|c| c.ai_api_key_secret.as_deref())
+                    .unwrap_or(&op.api_key_secret);
+                w.line_fmt(format_args!(
+                    "const {} = runtime.getSecret({{ id: \"{}\" }}).result();",
+                    secret_var, secret_name,
+                ));
+                secret_var
+            }
+        };
 
-        let ctx = fetch_contexts.get(&step.id);
         let has_dynamic = ctx.map_or(false, |c| !c.dynamic_refs.is_empty());
 
         if has_dynamic {
             // Build augmented config with upstream refs
-            let cfg_var = format!("_fetchCfg_{}", step.id.replace('-', "_"));
-            w.block_open(&format!("const {} =", cfg_var));
+            let cfg_var = ctx
+                .and_then(|c| c.cfg_var.clone())
+                .unwrap_or_else(|| fetch_cfg_name(&step.id));
+            let cfg_type = ctx
+                .and_then(|c| c.cfg_type.clone())
+                .unwrap_or_else(|| fetch_cfg_type_name(&step.id));
+            w.block_open_fmt(format_args!("const {}: {} =", cfg_var, cfg_type));
             w.line("...runtime.config,");
             if let Some(c) = ctx {
                 for dyn_ref in &c.dynamic_refs {
-                    w.line(&format!(
+                    w.line_fmt(format_args!(
                         "{}: {},",
                         dyn_ref.config_key,
                         emit_value_expr(&dyn_ref.handler_expr),
@@ -411,13 +593,13 @@ pub fn emit_ai_call(
             }
             w.dedent();
             w.line("};");
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = httpClient.sendRequest(runtime, {}, {})({}, {}.value).result();",
                 out.variable_name, fetch_fn_name, consensus_expr, cfg_var, secret_var,
             ));
         } else {
             // No dynamic refs — pass runtime.config directly
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "const {} = httpClient.sendRequest(runtime, {}, {})(runtime.config, {}.value).result();",
                 out.variable_name, fetch_fn_name, consensus_expr, secret_var,
             ));
@@ -425,18 +607,67 @@ pub fn emit_ai_call(
     }
 }
 
+/// SDK constant for a block tag in [`EvmReadOp::block_number`].
+pub fn block_tag_constant(block: &ValueExpr) -> Option<&'static str> {
+    match block {
+        ValueExpr::Literal(LiteralValue::String { value }) => match value.as_str() {
+            "latest" => Some("LATEST_BLOCK_NUMBER"),
+            "finalized" => Some("LAST_FINALIZED_BLOCK_NUMBER"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The `blockNumber` of a `callContract` request. The request takes a
+/// protobuf big integer (`absVal`: big-endian magnitude in base64), so a
+/// literal number is converted from its hex digits and any other value from
+/// a `_block_<id>` hex string declared here.
+fn emit_block_number(block: &ValueExpr, safe_id: &str, w: &mut CodeWriter) -> String {
+    if let Some(constant) = block_tag_constant(block) {
+        return constant.to_string();
+    }
+    let hex = match block {
+        ValueExpr::Literal(LiteralValue::Integer { value }) if *value >= 0 => {
+            let hex = format!("{:x}", value);
+            format!("\"{}{}\"", if hex.len() % 2 == 1 { "0" } else { "" }, hex)
+        }
+        _ => {
+            let hex_var = format!("_block_{}", safe_id);
+            w.line_fmt(format_args!(
+                "const {} = {}.toString(16);",
+                hex_var,
+                emit_bigint_value_expr(block)
+            ));
+            format!("{0}.length % 2 ? `0${{{0}}}` : {0}", hex_var)
+        }
+    };
+    format!(
+        "{{ absVal: Buffer.from({}, \"hex\").toString(\"base64\"), sign: \"1\" }}",
+        hex
+    )
+}
+
 /// Emit an ErrorThrow.
 pub fn emit_error_throw(_step: &Step, op: &ErrorThrowOp, w: &mut CodeWriter) {
     let msg = emit_value_expr(&op.message);
-    w.line(&format!("throw new Error({});", msg));
+    w.line_fmt(format_args!("throw new Error({});", msg));
 }
 
 /// Emit a Return.
 pub fn emit_return(_step: &Step, op: &ReturnOp, w: &mut CodeWriter) {
     let expr = emit_value_expr(&op.expression);
-    w.line(&format!("return {};", expr));
+    if op.auto_generated && !w.is_compact() {
+        w.line_fmt(format_args!("return {}; {}", expr, AUTO_RETURN_MARKER));
+    } else {
+        w.line_fmt(format_args!("return {};", expr));
+    }
 }
 
+/// Trailing comment on Returns synthesized by lowering; `CodegenOutput::diff`
+/// skips lines carrying it. Left out in compact mode.
+pub const AUTO_RETURN_MARKER: &str = "// auto-generated return";
+
 fn emit_consensus(consensus: &ConsensusStrategy) -> String {
     match consensus {
         ConsensusStrategy::Identical => "consensusIdenticalAggregation()".to_string(),
@@ -450,6 +681,16 @@ fn emit_consensus(consensus: &ConsensusStrategy) -> String {
                 field_entries.join(", ")
             )
         }
+        ConsensusStrategy::IdenticalFields { fields } => {
+            let field_entries: Vec<String> = fields
+                .iter()
+                .map(|f| format!("{}: cre.consensus.identical()", f))
+                .collect();
+            format!(
+                "new cre.consensus.ConsensusAggregationByFields({{ {} }})",
+                field_entries.join(", ")
+            )
+        }
         ConsensusStrategy::Custom { expr } => expr.clone(),
     }
 }
@@ -465,6 +706,7 @@ mod tests {
             label: label.into(),
             operation: op,
             output,
+            notes: None,
         }
     }
 
@@ -475,6 +717,7 @@ mod tests {
             "Return result",
             Operation::Return(ReturnOp {
                 expression: ValueExpr::string("done"),
+                auto_generated: false,
             }),
             None,
         );
@@ -513,4 +756,184 @@ mod tests {
         let out = w.finish();
         assert_eq!(out.trim(), "throw new Error(\"failed\");");
     }
+
+    fn abi_encode_step(function_name: Option<&str>, abi_json: &str) -> Step {
+        make_step(
+            "enc-1",
+            "Encode transfer",
+            Operation::AbiEncode(AbiEncodeOp {
+                function_name: function_name.map(String::from),
+                abi_json: abi_json.into(),
+                data_mappings: vec![
+                    AbiDataMapping {
+                        param_name: "to".into(),
+                        value: ValueExpr::string("0xabc"),
+                        value_kind: None,
+                    },
+                    AbiDataMapping {
+                        param_name: "amount".into(),
+                        value: ValueExpr::integer(5),
+                        value_kind: None,
+                    },
+                ],
+            }),
+            Some(OutputBinding {
+                variable_name: "step_enc_1".into(),
+                ts_type: "{ encoded: string }".into(),
+                destructure_fields: None,
+            }),
+        )
+    }
+
+    fn emit_abi_encode_for(step: &Step) -> String {
+        let mut w = CodeWriter::new();
+        emit_abi_encode(
+            step,
+            match &step.operation {
+                Operation::AbiEncode(op) => op,
+                _ => unreachable!(),
+            },
+            &mut w,
+        );
+        w.finish()
+    }
+
+    #[test]
+    fn test_abi_encode_parameters_only() {
+        let step = abi_encode_step(
+            None,
+            r#"[{"name":"to","type":"address"},{"name":"amount","type":"uint256"}]"#,
+        );
+        let out = emit_abi_encode_for(&step);
+        assert!(out.contains("encoded: encodeAbiParameters([{\"name\":\"to\""));
+        assert!(out.contains(", [\"0xabc\", BigInt(\"5\")]),"));
+        assert!(!out.contains("encodeFunctionData"));
+    }
+
+    #[test]
+    fn test_abi_encode_function_call() {
+        let step = abi_encode_step(
+            Some("transfer"),
+            r#"{"type":"function","name":"transfer","inputs":[{"name":"to","type":"address"},{"name":"amount","type":"uint256"}],"outputs":[],"stateMutability":"nonpayable"}"#,
+        );
+        let out = emit_abi_encode_for(&step);
+        assert!(out.contains("encoded: encodeFunctionData({"));
+        assert!(out.contains("abi: [{\"type\":\"function\",\"name\":\"transfer\""));
+        assert!(out.contains("functionName: \"transfer\","));
+        assert!(out.contains("args: [\"0xabc\", BigInt(\"5\")],"));
+        assert!(!out.contains("encodeAbiParameters"));
+    }
+
+    #[test]
+    fn test_abi_encode_coerces_only_non_bigint_integer_args() {
+        let mut step = abi_encode_step(
+            None,
+            r#"[{"name":"to","type":"address"},{"name":"ok","type":"bool"},{"name":"min","type":"uint256"},{"name":"amount","type":"uint256"}]"#,
+        );
+        let Operation::AbiEncode(op) = &mut step.operation else {
+            unreachable!()
+        };
+        op.data_mappings = vec![
+            AbiDataMapping {
+                param_name: "to".into(),
+                value: ValueExpr::config("recipient"),
+                value_kind: None,
+            },
+            AbiDataMapping {
+                param_name: "ok".into(),
+                value: ValueExpr::boolean(true),
+                value_kind: None,
+            },
+            AbiDataMapping {
+                param_name: "min".into(),
+                value: ValueExpr::config("minAmount"),
+                value_kind: None,
+            },
+            AbiDataMapping {
+                param_name: "amount".into(),
+                value: ValueExpr::binding("read-1", "balance"),
+                value_kind: Some(ValueKind::BigInt),
+            },
+        ];
+        let out = emit_abi_encode_for(&step);
+        assert!(
+            out.contains(
+                "[runtime.config.recipient, true, BigInt(runtime.config.minAmount), step_read_1.balance]"
+            ),
+            "{}",
+            out
+        );
+    }
+
+    fn emit_compute_for(expr: ComputeExpr, mode: NumericMode) -> String {
+        let op = ComputeOp { expr, mode };
+        let step = make_step(
+            "math-1",
+            "Scale amount",
+            Operation::Compute(op.clone()),
+            Some(OutputBinding {
+                variable_name: "step_math_1".into(),
+                ts_type: "bigint".into(),
+                destructure_fields: None,
+            }),
+        );
+        let mut w = CodeWriter::new();
+        emit_compute(&step, &op, &mut w);
+        w.finish()
+    }
+
+    fn binary(op: ArithOp, left: ComputeExpr, right: ComputeExpr) -> ComputeExpr {
+        ComputeExpr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn lit(value: &str) -> ComputeExpr {
+        ComputeExpr::Literal {
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn test_compute_bigint_mode() {
+        // ({{read-1.value}} + 1) * 1000000000000000000
+        let expr = binary(
+            ArithOp::Mul,
+            binary(
+                ArithOp::Add,
+                ComputeExpr::Value {
+                    value: ValueExpr::binding("read-1", "value"),
+                },
+                lit("1"),
+            ),
+            lit("1000000000000000000"),
+        );
+        let out = emit_compute_for(expr, NumericMode::BigInt);
+        assert_eq!(
+            out.trim(),
+            "// Scale amount\nconst step_math_1 = (BigInt(step_read_1.value) + 1n) * 1000000000000000000n;"
+        );
+    }
+
+    #[test]
+    fn test_compute_number_mode() {
+        // 10 - (4 - {{http-1.body.n}}) with a negated literal on the right
+        let expr = binary(
+            ArithOp::Sub,
+            lit("10"),
+            binary(
+                ArithOp::Sub,
+                lit("4"),
+                ComputeExpr::Neg {
+                    operand: Box::new(ComputeExpr::Value {
+                        value: ValueExpr::binding("http-1", "body.n"),
+                    }),
+                },
+            ),
+        );
+        let out = emit_compute_for(expr, NumericMode::Number);
+        assert!(out.contains("const step_math_1 = 10 - (4 - -Number(step_http_1.body.n));"));
+    }
 }
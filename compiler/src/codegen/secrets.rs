@@ -0,0 +1,183 @@
+//! Hoist secrets read by more than one step.
+//!
+//! HTTP bearer auth and AI calls each fetch their secret right before the
+//! request, and a `{{secrets.NAME}}` reference reads it where it is used. When
+//! several steps read the same secret, it is fetched once at the top of the
+//! handler instead and every step reuses that binding.
+//!
+//! Secrets flagged `optional_in_config` are never hoisted: every read sits in a
+//! branch arm, so fetching one up front would fail the runs that leave it unset.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::fetch_fns::FetchContext;
+use super::writer::CodeWriter;
+use crate::ir::types::*;
+use crate::ir::validate::{collect_secret_value_refs_from_step, visit_operation_value_exprs_mut};
+
+/// A secret fetched once at the top of the handler.
+pub struct SharedSecret {
+    pub name: String,
+    /// Handler-level variable holding the `getSecret` result.
+    pub var_name: String,
+}
+
+/// Secrets read by two or more steps, in order of first use.
+pub fn collect_shared_secrets(ir: &WorkflowIR) -> Vec<SharedSecret> {
+    let mut uses: Vec<(String, usize)> = Vec::new();
+    count_secret_uses(&ir.handler_body, &mut uses);
+
+    let mut shared: Vec<SharedSecret> = Vec::new();
+    for (name, _) in uses.into_iter().filter(|(_, count)| *count > 1) {
+        if ir
+            .required_secrets
+            .iter()
+            .any(|s| s.name == name && s.optional_in_config)
+        {
+            continue;
+        }
+        // `API-KEY` and `API_KEY` sanitize alike; later ones get a suffix.
+        let base = format!("_secret_{}", sanitize_identifier(&name));
+        let mut var_name = base.clone();
+        let mut suffix = 2;
+        while shared.iter().any(|s| s.var_name == var_name) {
+            var_name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        shared.push(SharedSecret { name, var_name });
+    }
+    shared
+}
+
+/// The secret an operation fetches right before its request, if any.
+fn fetched_secret(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::HttpRequest(op) => op.authentication.as_ref().map(|a| a.token_secret.as_str()),
+        Operation::AiCall(op) => Some(op.api_key_secret.as_str()),
+        _ => None,
+    }
+}
+
+fn count_secret_uses(block: &Block, uses: &mut Vec<(String, usize)>) {
+    for step in &block.steps {
+        if let Operation::Branch(branch) = &step.operation {
+            count_secret_uses(&branch.true_branch, uses);
+            count_secret_uses(&branch.false_branch, uses);
+        }
+        // Each step counts once per secret, however many times it reads it
+        let mut names = collect_secret_value_refs_from_step(step);
+        names.extend(fetched_secret(&step.operation).map(str::to_string));
+        let mut counted: Vec<&str> = Vec::new();
+        for name in &names {
+            if counted.contains(&name.as_str()) {
+                continue;
+            }
+            counted.push(name);
+            match uses.iter_mut().find(|(n, _)| n == name) {
+                Some((_, count)) => *count += 1,
+                None => uses.push((name.clone(), 1)),
+            }
+        }
+    }
+}
+
+/// Point `{{secrets.NAME}}` references to a shared secret at its handler-level
+/// variable, on a copy of the IR. IRs without shared secrets are used as-is.
+pub fn resolve_shared_secret_refs<'a>(
+    ir: &'a WorkflowIR,
+    shared: &[SharedSecret],
+) -> Cow<'a, WorkflowIR> {
+    if shared.is_empty() {
+        return Cow::Borrowed(ir);
+    }
+
+    let mut resolved = ir.clone();
+    rewrite_block(&mut resolved.handler_body, shared);
+    Cow::Owned(resolved)
+}
+
+fn rewrite_block(block: &mut Block, shared: &[SharedSecret]) {
+    for step in &mut block.steps {
+        visit_operation_value_exprs_mut(&mut step.operation, &mut |expr| {
+            rewrite_expr(expr, shared)
+        });
+        if let Operation::Branch(branch) = &mut step.operation {
+            rewrite_block(&mut branch.true_branch, shared);
+            rewrite_block(&mut branch.false_branch, shared);
+        }
+    }
+}
+
+fn rewrite_expr(expr: &mut ValueExpr, shared: &[SharedSecret]) {
+    match expr {
+        ValueExpr::SecretRef { name } => {
+            if let Some(s) = shared.iter().find(|s| s.name == *name) {
+                *expr = ValueExpr::raw(format!("{}.value", s.var_name));
+            }
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    rewrite_expr(value, shared);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                rewrite_expr(value, shared);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
+/// Point the fetch context of every step reading a shared secret at its
+/// handler-level variable.
+pub fn link_fetch_contexts(
+    shared: &[SharedSecret],
+    block: &Block,
+    fetch_contexts: &mut HashMap<String, FetchContext>,
+) {
+    for step in &block.steps {
+        if let Operation::Branch(branch) = &step.operation {
+            link_fetch_contexts(shared, &branch.true_branch, fetch_contexts);
+            link_fetch_contexts(shared, &branch.false_branch, fetch_contexts);
+        }
+        if let Some(name) = fetched_secret(&step.operation)
+            && let Some(s) = shared.iter().find(|s| s.name == name)
+            && let Some(ctx) = fetch_contexts.get_mut(&step.id)
+        {
+            ctx.shared_secret_var = Some(s.var_name.clone());
+        }
+    }
+}
+
+/// Emit one `getSecret` per shared secret.
+pub fn emit_shared_secrets(shared: &[SharedSecret], w: &mut CodeWriter) {
+    if shared.is_empty() {
+        return;
+    }
+    w.comment("Secrets used by more than one step");
+    for s in shared {
+        w.line_fmt(format_args!(
+            "const {} = runtime.getSecret({{ id: \"{}\" }}).result();",
+            s.var_name, s.name,
+        ));
+    }
+    w.blank();
+}
+
+fn sanitize_identifier(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
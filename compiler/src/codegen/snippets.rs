@@ -0,0 +1,70 @@
+//! Reuse the generated code of unchanged steps across compilations.
+//!
+//! A leaf step's code depends only on the step, its fetch context, and the
+//! indent and mode (compact or not) it is written in, so a digest of those
+//! keys its snippet.
+//! Branches, filters and merges wrap other steps and are always emitted
+//! afresh; the leaf steps inside them still come from the cache.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::fetch_fns::FetchContext;
+use super::hash::sha256_hex;
+use super::writer::CodeWriter;
+use crate::ir::types::Step;
+
+/// Generated step code keyed by a digest of everything it was generated from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnippetCache {
+    snippets: BTreeMap<String, String>,
+    /// Snippets written by the compilation in progress. Only these are kept
+    /// once it finishes, so the cache never outgrows one workflow.
+    #[serde(skip)]
+    used: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub(crate) hits: usize,
+    #[serde(skip)]
+    pub(crate) misses: usize,
+}
+
+impl SnippetCache {
+    pub(crate) fn begin(&mut self) {
+        self.used.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.snippets = std::mem::take(&mut self.used);
+    }
+
+    /// Write `step`'s code, reusing the cached snippet when there is one and
+    /// otherwise generating it with `emit`.
+    pub(crate) fn emit(
+        &mut self,
+        step: &Step,
+        ctx: Option<&FetchContext>,
+        w: &mut CodeWriter,
+        emit: impl FnOnce(&mut CodeWriter),
+    ) {
+        let inputs = serde_json::to_string(&(w.indent_level(), w.is_compact(), step, ctx))
+            .expect("IR serialization cannot fail");
+        let key = sha256_hex(inputs.as_bytes());
+        let snippet = match self.snippets.remove(&key) {
+            Some(snippet) => {
+                self.hits += 1;
+                snippet
+            }
+            None => {
+                self.misses += 1;
+                let mut out = w.splice_writer();
+                emit(&mut out);
+                out.finish()
+            }
+        };
+        w.push_raw(&snippet);
+        self.used.insert(key, snippet);
+    }
+}
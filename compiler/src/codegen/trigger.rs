@@ -1,9 +1,25 @@
 //! Emit `initWorkflow` and `main()` entry point.
+//!
+//! An EvmLog trigger's chain client is constructed once at module scope, so
+//! the `logTrigger` registration in `initWorkflow` and the handler's reads
+//! and writes on that chain share one instance.
 
+use super::handler::emit_evm_client;
 use super::value_expr::emit_value_expr_init;
 use super::writer::CodeWriter;
 use crate::ir::types::*;
 
+/// Emit the module-scope client of the chain an EvmLog trigger listens on.
+pub fn emit_trigger_chain_client(ir: &WorkflowIR, w: &mut CodeWriter) {
+    if !matches!(ir.trigger, TriggerDef::EvmLog(_)) {
+        return;
+    }
+    for chain in ir.evm_chains.iter().filter(|c| c.used_for_trigger) {
+        emit_evm_client(chain, ir.metadata.is_testnet, w);
+        w.blank();
+    }
+}
+
 /// Emit the `initWorkflow` function and `main()` entry point.
 pub fn emit_init_and_main(ir: &WorkflowIR, w: &mut CodeWriter) {
     emit_init_workflow(ir, w);
@@ -24,7 +40,7 @@ fn emit_init_workflow(ir: &WorkflowIR, w: &mut CodeWriter) {
     match &ir.trigger {
         TriggerDef::Cron(cron) => emit_cron_init(cron, handler_name, w),
         TriggerDef::Http(http) => emit_http_init(http, ir, handler_name, w),
-        TriggerDef::EvmLog(evm_log) => emit_evm_log_init(evm_log, ir, handler_name, w),
+        TriggerDef::EvmLog(evm_log) => emit_evm_log_init(evm_log, handler_name, w),
     }
 
     w.block_close_semi();
@@ -37,13 +53,13 @@ fn emit_cron_init(cron: &CronTriggerDef, handler_name: &str, w: &mut CodeWriter)
     w.indent();
     w.line("new cre.capabilities.CronCapability().trigger({");
     w.indent();
-    w.line(&format!(
+    w.line_fmt(format_args!(
         "schedule: {},",
         emit_value_expr_init(&cron.schedule)
     ));
     w.dedent();
     w.line("}),");
-    w.line(&format!("{},", handler_name));
+    w.line_fmt(format_args!("{},", handler_name));
     w.dedent();
     w.line("),");
     w.dedent();
@@ -66,7 +82,7 @@ fn emit_http_init(http: &HttpTriggerDef, ir: &WorkflowIR, handler_name: &str, w:
         w.line("authorizedKeys: [");
         w.indent();
         for key in &http.authorized_keys {
-            w.line(&format!(
+            w.line_fmt(format_args!(
                 "{{ type: \"KEY_TYPE_ECDSA_EVM\", publicKey: \"{}\" }},",
                 key
             ));
@@ -77,37 +93,16 @@ fn emit_http_init(http: &HttpTriggerDef, ir: &WorkflowIR, handler_name: &str, w:
         w.line("}),");
     }
 
-    w.line(&format!("{},", handler_name));
+    w.line_fmt(format_args!("{},", handler_name));
     w.dedent();
     w.line("),");
     w.dedent();
     w.line("];");
 }
 
-fn emit_evm_log_init(
-    evm_log: &EvmLogTriggerDef,
-    ir: &WorkflowIR,
-    handler_name: &str,
-    w: &mut CodeWriter,
-) {
-    // Find the chain for the trigger
-    w.line(&format!(
-        "const network = getNetwork({{ chainFamily: \"evm\", chainSelectorName: \"{}\", isTestnet: {} }});",
-        evm_log.evm_client_binding.replace("evmClient_", "").replace('_', "-"),
-        ir.metadata.is_testnet,
-    ));
-    w.blank();
-    w.block_open("if (!network)");
-    w.line(&format!(
-        "throw new Error(\"Network not found for chain selector\");",
-    ));
-    w.block_close();
-    w.blank();
-    w.line("const evmClient = new cre.capabilities.EVMClient(network.chainSelector.selector);");
-    w.blank();
-
+fn emit_evm_log_init(evm_log: &EvmLogTriggerDef, handler_name: &str, w: &mut CodeWriter) {
     // Event topic hash
-    w.line(&format!(
+    w.line_fmt(format_args!(
         "const eventTopicHash = keccak256(toHex(\"{}\"));",
         evm_log.event_signature
     ));
@@ -117,7 +112,7 @@ fn emit_evm_log_init(
     w.indent();
     w.line("cre.handler(");
     w.indent();
-    w.line("evmClient.logTrigger({");
+    w.line_fmt(format_args!("{}.logTrigger({{", evm_log.evm_client_binding));
     w.indent();
 
     // Addresses
@@ -126,18 +121,31 @@ fn emit_evm_log_init(
         .iter()
         .map(|a| emit_value_expr_init(a))
         .collect();
-    w.line(&format!("addresses: [{}],", addrs.join(", ")));
+    w.line_fmt(format_args!("addresses: [{}],", addrs.join(", ")));
 
-    // Topics
-    if evm_log.topic_filters.is_empty() {
+    // Topics: slot 0 is the event signature, slot `index` holds its filter;
+    // indexed arguments left unfiltered match any value
+    let topic_count = evm_log
+        .topic_filters
+        .iter()
+        .map(|f| f.index as usize + 1)
+        .max()
+        .unwrap_or(1);
+    if topic_count == 1 {
         w.line("topics: [{ values: [eventTopicHash] }],");
     } else {
         w.line("topics: [");
         w.indent();
         w.line("{ values: [eventTopicHash] },");
-        for filter in &evm_log.topic_filters {
-            let values: Vec<String> = filter.values.iter().map(|v| format!("\"{}\"", v)).collect();
-            w.line(&format!("{{ values: [{}] }},", values.join(", ")));
+        for index in 1..topic_count {
+            let values: Vec<String> = evm_log
+                .topic_filters
+                .iter()
+                .filter(|f| f.index as usize == index)
+                .flat_map(|f| &f.values)
+                .map(|v| serde_json::Value::String(v.clone()).to_string())
+                .collect();
+            w.line_fmt(format_args!("{{ values: [{}] }},", values.join(", ")));
         }
         w.dedent();
         w.line("],");
@@ -149,11 +157,11 @@ fn emit_evm_log_init(
         "safe" => "CONFIDENCE_LEVEL_SAFE",
         other => other, // pass through if already in enum format
     };
-    w.line(&format!("confidence: \"{}\",", confidence_enum));
+    w.line_fmt(format_args!("confidence: \"{}\",", confidence_enum));
 
     w.dedent();
     w.line("}),");
-    w.line(&format!("{},", handler_name));
+    w.line_fmt(format_args!("{},", handler_name));
     w.dedent();
     w.line("),");
     w.dedent();
@@ -8,6 +8,35 @@ pub fn emit_value_expr(expr: &ValueExpr) -> String {
     emit_value_expr_ctx(expr, "runtime.config")
 }
 
+/// Convert a `ValueExpr` bound for an `int*`/`uint*` ABI parameter into a
+/// BigInt. Integral literals are passed to `BigInt` as decimal strings, since
+/// a bare number literal above 2^53 has already lost precision by the time
+/// `BigInt` sees it. Anything else is wrapped as `BigInt(expr)`, so config
+/// strings such as `"1000000000000000000000"` convert exactly at runtime.
+pub fn emit_bigint_value_expr(expr: &ValueExpr) -> String {
+    let digits = match expr {
+        ValueExpr::Literal(LiteralValue::Integer { value }) => Some(value.to_string()),
+        ValueExpr::Literal(LiteralValue::Number { value })
+            if value.is_finite() && value.fract() == 0.0 =>
+        {
+            Some(format!("{}", value))
+        }
+        ValueExpr::Literal(LiteralValue::String { value }) if is_integer_string(value) => {
+            Some(value.clone())
+        }
+        _ => None,
+    };
+    match digits {
+        Some(d) => format!("BigInt(\"{}\")", d),
+        None => format!("BigInt({})", emit_value_expr(expr)),
+    }
+}
+
+fn is_integer_string(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
 /// Convert a `ValueExpr` using `config.X` for config refs (initWorkflow context).
 pub fn emit_value_expr_init(expr: &ValueExpr) -> String {
     emit_value_expr_ctx(expr, "config")
@@ -25,37 +54,73 @@ fn emit_value_expr_ctx(expr: &ValueExpr, config_prefix: &str) -> String {
                 format!("triggerData.{}", field)
             }
         }
+        // Handler context only: fetch functions receive secrets through
+        // their augmented config (see `fetch_fns`).
+        ValueExpr::SecretRef { name } => {
+            format!("runtime.getSecret({{ id: \"{}\" }}).result().value", name)
+        }
         ValueExpr::Template { parts } => emit_template_ctx(parts, config_prefix),
         ValueExpr::RawExpr { expr } => expr.clone(),
+        // Parenthesized so it composes inside templates and binary operands.
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => format!(
+            "({} ? {} : {})",
+            emit_value_expr_ctx(cond, config_prefix),
+            emit_value_expr_ctx(then, config_prefix),
+            emit_value_expr_ctx(otherwise, config_prefix)
+        ),
     }
 }
 
 fn emit_literal(lit: &LiteralValue) -> String {
     match lit {
         LiteralValue::String { value } => format!("\"{}\"", escape_string(value)),
-        LiteralValue::Number { value } => {
-            if value.fract() == 0.0 && value.is_finite() {
-                format!("{}", *value as i64)
-            } else {
-                format!("{}", value)
-            }
-        }
+        LiteralValue::Number { value } => emit_number(*value),
         LiteralValue::Integer { value } => format!("{}", value),
         LiteralValue::Boolean { value } => format!("{}", value),
         LiteralValue::Null => "null".to_string(),
+        // Checked by IR validation (E014); JSON is a valid JS expression.
         LiteralValue::Json { value } => value.clone(),
     }
 }
 
-fn emit_binding(binding: &BindingRef) -> String {
-    let var_name = binding_var_name(&binding.step_id);
-    if binding.field_path.is_empty() {
-        var_name
+/// f64 → JS number literal. Rust's `Display` already drops the `.0` of
+/// integral values (`42.0` → `42`) without the `i64` overflow of a cast;
+/// only non-finite values need JS spellings.
+fn emit_number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
     } else {
-        format!("{}.{}", var_name, binding.field_path)
+        format!("{}", value)
     }
 }
 
+fn emit_binding(binding: &BindingRef) -> String {
+    let var_name = binding_var_name(&binding.step_id);
+    format!("{}{}", var_name, emit_field_access(&binding.field_path))
+}
+
+/// Member accesses for a field path: `items.0.name` → `.items[0].name`.
+/// Numeric segments are array indices; `length` is an ordinary member.
+pub fn emit_field_access(field_path: &str) -> String {
+    field_path
+        .split('.')
+        .filter(|s| !s.is_empty())
+        .map(|segment| {
+            if segment.bytes().all(|b| b.is_ascii_digit()) {
+                format!("[{}]", segment)
+            } else {
+                format!(".{}", segment)
+            }
+        })
+        .collect()
+}
+
 /// Convert a step ID to its TypeScript variable name.
 /// Convention: `step_{sanitized_id}` where `-` becomes `_`.
 pub fn binding_var_name(step_id: &str) -> String {
@@ -80,23 +145,79 @@ fn emit_template_ctx(parts: &[TemplatePart], config_prefix: &str) -> String {
     out
 }
 
-/// Emit a condition expression from `ConditionIR` list + combinator.
-pub fn emit_condition(conditions: &[ConditionIR], combine: &LogicCombinator) -> String {
-    let parts: Vec<String> = conditions.iter().map(emit_single_condition).collect();
+/// Emit a condition expression from a `ConditionTree` list + combinator.
+/// Nested groups are parenthesized where they meet a sibling.
+pub fn emit_condition(conditions: &[ConditionTree], combine: &LogicCombinator) -> String {
+    emit_condition_group(conditions, combine).0
+}
+
+/// The expression for a list of conditions, and whether it needs
+/// parentheses as an operand of `&&` / `||`.
+fn emit_condition_group(conditions: &[ConditionTree], combine: &LogicCombinator) -> (String, bool) {
+    let mut parts: Vec<(String, bool)> = conditions.iter().map(emit_condition_tree).collect();
     let joiner = match combine {
         LogicCombinator::And => " && ",
         LogicCombinator::Or => " || ",
     };
-    if parts.len() == 1 {
-        parts[0].clone()
-    } else {
-        parts.join(joiner)
+    match parts.len() {
+        // Validation rejects empty groups; emit the combinator's identity.
+        0 => match combine {
+            LogicCombinator::And => ("true".into(), false),
+            LogicCombinator::Or => ("false".into(), false),
+        },
+        1 => parts.remove(0),
+        _ => {
+            let operands: Vec<String> = parts
+                .into_iter()
+                .map(|(expr, compound)| {
+                    if compound {
+                        format!("({})", expr)
+                    } else {
+                        expr
+                    }
+                })
+                .collect();
+            (operands.join(joiner), true)
+        }
+    }
+}
+
+fn emit_condition_tree(tree: &ConditionTree) -> (String, bool) {
+    match tree {
+        ConditionTree::Group {
+            combinator,
+            children,
+        } => emit_condition_group(children, combinator),
+        // The empty checks are themselves `||` / `&&` expressions.
+        ConditionTree::Leaf(cond) => (
+            emit_single_condition(cond),
+            matches!(
+                cond.operator,
+                ComparisonOp::IsEmpty | ComparisonOp::IsNotEmpty
+            ),
+        ),
     }
 }
 
 fn emit_single_condition(cond: &ConditionIR) -> String {
-    let lhs = emit_value_expr(&cond.field);
-    let rhs = cond.value.as_ref().map(|v| emit_value_expr(v));
+    // A `bigint` is never `===` to a number or string, and a config string
+    // compares to it lexically, so the other side becomes a `bigint` too.
+    let bigint_side = cond.operator.is_numeric() && cond.field_kind != cond.value_kind;
+    let operand = |expr: &ValueExpr, kind: Option<ValueKind>| match expr {
+        ValueExpr::Literal(
+            LiteralValue::String { .. }
+            | LiteralValue::Number { .. }
+            | LiteralValue::Integer { .. },
+        )
+        | ValueExpr::ConfigRef { .. }
+            if bigint_side && kind.is_none() =>
+        {
+            emit_bigint_value_expr(expr)
+        }
+        _ => emit_value_expr(expr),
+    };
+    let lhs = operand(&cond.field, cond.field_kind);
+    let rhs = cond.value.as_ref().map(|v| operand(v, cond.value_kind));
 
     match &cond.operator {
         ComparisonOp::Equals => format!("{} === {}", lhs, rhs.unwrap()),
@@ -118,10 +239,12 @@ fn emit_single_condition(cond: &ConditionIR) -> String {
             format!("{}.endsWith({})", lhs, rhs.unwrap())
         }
         ComparisonOp::Regex => {
-            format!("new RegExp({}).test({})", rhs.unwrap(), lhs)
+            let pattern = emit_regex_pattern(cond.value.as_ref().unwrap());
+            format!("new RegExp({}).test({})", pattern, lhs)
         }
         ComparisonOp::NotRegex => {
-            format!("!new RegExp({}).test({})", rhs.unwrap(), lhs)
+            let pattern = emit_regex_pattern(cond.value.as_ref().unwrap());
+            format!("!new RegExp({}).test({})", pattern, lhs)
         }
         ComparisonOp::Exists => format!("{} != null", lhs),
         ComparisonOp::NotExists => format!("{} == null", lhs),
@@ -130,6 +253,23 @@ fn emit_single_condition(cond: &ConditionIR) -> String {
     }
 }
 
+/// The pattern argument of `new RegExp(...)`. A template literal would eat
+/// the backslashes of `\d` or `\.`, so a templated pattern is emitted as a
+/// concatenation of string literals and interpolated values instead.
+fn emit_regex_pattern(pattern: &ValueExpr) -> String {
+    let ValueExpr::Template { parts } = pattern else {
+        return emit_value_expr(pattern);
+    };
+    parts
+        .iter()
+        .map(|part| match part {
+            TemplatePart::Lit { value } => format!("\"{}\"", escape_string(value)),
+            TemplatePart::Expr { value } => format!("String({})", emit_value_expr(value)),
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
 fn escape_string(s: &str) -> String {
     s.replace('\\', "\\\\")
         .replace('"', "\\\"")
@@ -152,6 +292,42 @@ mod tests {
         assert_eq!(emit_value_expr(&ValueExpr::integer(42)), "42");
     }
 
+    #[test]
+    fn literal_number_integral_has_no_fraction() {
+        assert_eq!(emit_value_expr(&ValueExpr::number(42.0)), "42");
+        assert_eq!(emit_value_expr(&ValueExpr::number(-3.0)), "-3");
+        assert_eq!(
+            emit_value_expr(&ValueExpr::number(1e20)),
+            "100000000000000000000"
+        );
+    }
+
+    #[test]
+    fn literal_number_fraction_and_non_finite() {
+        assert_eq!(emit_value_expr(&ValueExpr::number(0.5)), "0.5");
+        assert_eq!(
+            emit_value_expr(&ValueExpr::number(f64::INFINITY)),
+            "Infinity"
+        );
+        assert_eq!(
+            emit_value_expr(&ValueExpr::number(f64::NEG_INFINITY)),
+            "-Infinity"
+        );
+        assert_eq!(emit_value_expr(&ValueExpr::number(f64::NAN)), "NaN");
+    }
+
+    #[test]
+    fn literal_boolean_false() {
+        assert_eq!(emit_value_expr(&ValueExpr::boolean(false)), "false");
+    }
+
+    #[test]
+    fn literal_json_is_emitted_verbatim() {
+        let json = "{ \"status\": \"high\", \"levels\": [1, 2] }";
+        let expr = ValueExpr::Literal(LiteralValue::Json { value: json.into() });
+        assert_eq!(emit_value_expr(&expr), json);
+    }
+
     #[test]
     fn literal_boolean() {
         assert_eq!(emit_value_expr(&ValueExpr::boolean(true)), "true");
@@ -178,6 +354,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn binding_with_array_index_and_length() {
+        assert_eq!(
+            emit_value_expr(&ValueExpr::binding("http-1", "body.items.0.name")),
+            "step_http_1.body.items[0].name"
+        );
+        assert_eq!(
+            emit_value_expr(&ValueExpr::binding("http-1", "body.items.length")),
+            "step_http_1.body.items.length"
+        );
+    }
+
+    #[test]
+    fn bigint_literals_keep_every_digit() {
+        assert_eq!(
+            emit_bigint_value_expr(&ValueExpr::integer(5)),
+            "BigInt(\"5\")"
+        );
+        assert_eq!(
+            emit_bigint_value_expr(&ValueExpr::Literal(LiteralValue::Number { value: 1e21 })),
+            "BigInt(\"1000000000000000000000\")"
+        );
+        assert_eq!(
+            emit_bigint_value_expr(&ValueExpr::string("0x10")),
+            "BigInt(\"0x10\")"
+        );
+        assert_eq!(
+            emit_bigint_value_expr(&ValueExpr::config("amount")),
+            "BigInt(runtime.config.amount)"
+        );
+    }
+
     #[test]
     fn config_ref() {
         assert_eq!(
@@ -222,14 +430,24 @@ mod tests {
         assert_eq!(emit_value_expr(&ValueExpr::raw("Date.now()")), "Date.now()");
     }
 
+    #[test]
+    fn secret_ref_is_fetched_not_inlined() {
+        assert_eq!(
+            emit_value_expr(&ValueExpr::secret("API_KEY")),
+            "runtime.getSecret({ id: \"API_KEY\" }).result().value"
+        );
+    }
+
     #[test]
     fn condition_equals() {
         let cond = emit_condition(
-            &[ConditionIR {
+            &[ConditionTree::Leaf(ConditionIR {
                 field: ValueExpr::binding("parse-1", "isApproved"),
                 operator: ComparisonOp::Equals,
                 value: Some(ValueExpr::boolean(true)),
-            }],
+                field_kind: None,
+                value_kind: None,
+            })],
             &LogicCombinator::And,
         );
         assert_eq!(cond, "step_parse_1.isApproved === true");
@@ -239,19 +457,120 @@ mod tests {
     fn condition_combined_or() {
         let cond = emit_condition(
             &[
-                ConditionIR {
+                ConditionTree::Leaf(ConditionIR {
                     field: ValueExpr::binding("a", "x"),
                     operator: ComparisonOp::Gt,
                     value: Some(ValueExpr::integer(10)),
-                },
-                ConditionIR {
+                    field_kind: None,
+                    value_kind: None,
+                }),
+                ConditionTree::Leaf(ConditionIR {
                     field: ValueExpr::binding("a", "y"),
                     operator: ComparisonOp::Lt,
                     value: Some(ValueExpr::integer(5)),
-                },
+                    field_kind: None,
+                    value_kind: None,
+                }),
             ],
             &LogicCombinator::Or,
         );
         assert_eq!(cond, "step_a.x > 10 || step_a.y < 5");
     }
+    fn leaf(field: &str, operator: ComparisonOp, value: Option<ValueExpr>) -> ConditionTree {
+        ConditionTree::Leaf(ConditionIR {
+            field: ValueExpr::binding("a", field),
+            operator,
+            value,
+            field_kind: None,
+            value_kind: None,
+        })
+    }
+
+    #[test]
+    fn condition_nested_groups_are_parenthesized() {
+        // (status == "approved" && amount > 0) || override == true
+        let approved = ConditionTree::Group {
+            combinator: LogicCombinator::And,
+            children: vec![
+                leaf(
+                    "status",
+                    ComparisonOp::Equals,
+                    Some(ValueExpr::string("approved")),
+                ),
+                leaf("amount", ComparisonOp::Gt, Some(ValueExpr::integer(0))),
+            ],
+        };
+        let cond = emit_condition(
+            &[
+                approved,
+                leaf(
+                    "override",
+                    ComparisonOp::Equals,
+                    Some(ValueExpr::boolean(true)),
+                ),
+            ],
+            &LogicCombinator::Or,
+        );
+        assert_eq!(
+            cond,
+            "(step_a.status === \"approved\" && step_a.amount > 0) || step_a.override === true"
+        );
+
+        // A one-entry group is just its entry; the empty checks are grouped
+        // like any other compound operand.
+        let cond = emit_condition(
+            &[
+                ConditionTree::Group {
+                    combinator: LogicCombinator::Or,
+                    children: vec![leaf(
+                        "name",
+                        ComparisonOp::Regex,
+                        Some(ValueExpr::string("^0x")),
+                    )],
+                },
+                leaf("memo", ComparisonOp::IsEmpty, None),
+                leaf("id", ComparisonOp::Exists, None),
+            ],
+            &LogicCombinator::And,
+        );
+        assert_eq!(
+            cond,
+            "new RegExp(\"^0x\").test(step_a.name) && (step_a.memo === \"\" || step_a.memo == null) && step_a.id != null"
+        );
+    }
+
+    #[test]
+    fn regex_pattern_is_never_a_template_literal() {
+        // Backticks and `${` stay inert inside a string literal, and the
+        // backslash of `\d` survives.
+        let cond = emit_condition(
+            &[leaf(
+                "memo",
+                ComparisonOp::Regex,
+                Some(ValueExpr::string("^`${x}`\\d+$")),
+            )],
+            &LogicCombinator::And,
+        );
+        assert_eq!(cond, r#"new RegExp("^`${x}`\\d+$").test(step_a.memo)"#);
+
+        let templated = ValueExpr::Template {
+            parts: vec![
+                TemplatePart::Lit { value: "^`".into() },
+                TemplatePart::Expr {
+                    value: ValueExpr::config("prefix"),
+                },
+                TemplatePart::Lit {
+                    value: "\\.\\d+$".into(),
+                },
+            ],
+        };
+        let cond = emit_condition(
+            &[leaf("memo", ComparisonOp::NotRegex, Some(templated))],
+            &LogicCombinator::And,
+        );
+        assert_eq!(
+            cond,
+            r#"!new RegExp("^`" + String(runtime.config.prefix) + "\\.\\d+$").test(step_a.memo)"#
+        );
+    }
 }
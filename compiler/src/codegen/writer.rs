@@ -1,6 +1,22 @@
 //! Indent-aware string builder for TypeScript code generation.
 //!
 //! CRE TypeScript uses 2-space indentation.
+//!
+//! Emitters with interpolated lines should use `line_fmt(format_args!(..))`
+//! rather than `line(&format!(..))`: the arguments are written straight into
+//! the buffer without an intermediate `String` per line.
+//!
+//! Generated comments go through [`CodeWriter::comment`], which writes nothing
+//! in compact mode.
+//!
+//! Emission loops call [`CodeWriter::cancelled`] and stop early once the
+//! writer's cancellation token fires; [`CodeWriter::finish_checked`] then
+//! reports the cancellation instead of the partial output.
+
+use std::fmt::{self, Write as _};
+
+use crate::cancel::CancellationToken;
+use crate::error::{CompilerError, Phase};
 
 /// Indent-aware string builder that produces formatted TypeScript source code.
 pub struct CodeWriter {
@@ -8,14 +24,64 @@ pub struct CodeWriter {
     indent_level: usize,
     /// True if the current line has not yet been written to.
     at_line_start: bool,
+    /// Leave out generated comments.
+    compact: bool,
+    cancel: Option<CancellationToken>,
+    /// Set by the first [`cancelled`](Self::cancelled) check that fails.
+    cancellation: Option<Vec<CompilerError>>,
 }
 
 impl CodeWriter {
     pub fn new() -> Self {
+        Self::with_capacity(4096)
+    }
+
+    /// A writer whose buffer is pre-sized for roughly `capacity` bytes of output.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            buf: String::with_capacity(4096),
+            buf: String::with_capacity(capacity),
             indent_level: 0,
             at_line_start: true,
+            compact: false,
+            cancel: None,
+            cancellation: None,
+        }
+    }
+
+    /// Leave out generated comments from now on.
+    pub fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    /// Let emission loops stop early once `cancel` is cancelled.
+    pub fn set_cancel(&mut self, cancel: Option<CancellationToken>) {
+        self.cancel = cancel;
+    }
+
+    /// Whether emission should stop because the cancellation token fired.
+    pub fn cancelled(&mut self) -> bool {
+        if self.cancellation.is_none()
+            && let Some(cancel) = &self.cancel
+            && let Err(e) = cancel.check(Phase::Codegen)
+        {
+            self.cancellation = Some(e);
+        }
+        self.cancellation.is_some()
+    }
+
+    /// Write a `// text` line, unless in compact mode.
+    pub fn comment(&mut self, text: &str) {
+        self.comment_fmt(format_args!("{text}"));
+    }
+
+    /// [`comment`](Self::comment) from format arguments.
+    pub fn comment_fmt(&mut self, args: fmt::Arguments<'_>) {
+        if !self.compact {
+            self.line_fmt(format_args!("// {args}"));
         }
     }
 
@@ -27,6 +93,44 @@ impl CodeWriter {
         self.at_line_start = true;
     }
 
+    /// Write a complete line from format arguments (appends newline).
+    pub fn line_fmt(&mut self, args: fmt::Arguments<'_>) {
+        self.write_indent();
+        self.buf
+            .write_fmt(args)
+            .expect("writing to a String cannot fail");
+        self.buf.push('\n');
+        self.at_line_start = true;
+    }
+
+    /// Current indent level, in 2-space steps.
+    pub fn indent_level(&self) -> usize {
+        self.indent_level
+    }
+
+    /// A fresh writer starting at `indent_level`, for output that will be
+    /// spliced back in with [`push_raw`](Self::push_raw).
+    pub fn at_indent(indent_level: usize) -> Self {
+        Self {
+            indent_level,
+            ..Self::with_capacity(256)
+        }
+    }
+
+    /// A fresh writer at this writer's indent and mode, for output that will
+    /// be spliced back in with [`push_raw`](Self::push_raw).
+    pub fn splice_writer(&self) -> Self {
+        Self {
+            compact: self.compact,
+            ..Self::at_indent(self.indent_level)
+        }
+    }
+
+    /// Append already-indented complete lines as they are.
+    pub fn push_raw(&mut self, lines: &str) {
+        self.buf.push_str(lines);
+    }
+
     /// Write an empty line.
     pub fn blank(&mut self) {
         self.buf.push('\n');
@@ -52,7 +156,17 @@ impl CodeWriter {
 
     /// Write `text {` and increase indent (e.g. `if (cond) {`).
     pub fn block_open(&mut self, text: &str) {
-        self.line(&format!("{} {{", text));
+        self.block_open_fmt(format_args!("{text}"));
+    }
+
+    /// [`block_open`](Self::block_open) from format arguments.
+    pub fn block_open_fmt(&mut self, args: fmt::Arguments<'_>) {
+        self.write_indent();
+        self.buf
+            .write_fmt(args)
+            .expect("writing to a String cannot fail");
+        self.buf.push_str(" {\n");
+        self.at_line_start = true;
         self.indent();
     }
 
@@ -80,6 +194,14 @@ impl CodeWriter {
         self.buf
     }
 
+    /// [`finish`](Self::finish), or the X001 error if emission was cancelled.
+    pub fn finish_checked(self) -> Result<String, Vec<CompilerError>> {
+        match self.cancellation {
+            Some(e) => Err(e),
+            None => Ok(self.buf),
+        }
+    }
+
     fn write_indent(&mut self) {
         if self.at_line_start && self.indent_level > 0 {
             for _ in 0..self.indent_level {
@@ -152,6 +274,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn formatted_lines_match_plain_lines() {
+        let mut w = CodeWriter::with_capacity(0);
+        w.block_open_fmt(format_args!("if ({})", "x"));
+        w.line_fmt(format_args!("const {} = {};", "y", 1));
+        w.block_close();
+        assert_eq!(w.finish(), "if (x) {\n  const y = 1;\n}\n");
+    }
+
+    #[test]
+    fn spliced_output_matches_direct_output() {
+        let mut direct = CodeWriter::new();
+        direct.block_open("if (x)");
+        direct.line("a();");
+        direct.block_close();
+
+        let mut spliced = CodeWriter::new();
+        spliced.block_open("if (x)");
+        let mut inner = CodeWriter::at_indent(spliced.indent_level());
+        inner.line("a();");
+        spliced.push_raw(&inner.finish());
+        spliced.block_close();
+        assert_eq!(spliced.finish(), direct.finish());
+    }
+
     #[test]
     fn blank_line() {
         let mut w = CodeWriter::new();
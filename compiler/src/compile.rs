@@ -0,0 +1,187 @@
+//! The whole pipeline in one call, for embedders: parse → validate → lower →
+//! IR validate → codegen.
+//!
+//! With a [`CancellationToken`], each phase stops with X001 once the token is
+//! cancelled, its deadline passes or its step budget runs out.
+
+use crate::cancel::{self, CancellationToken};
+use crate::codegen::{self, CodegenOptions, CodegenOutput};
+use crate::error::{CompilerError, Phase};
+use crate::ir::{self, WorkflowIR};
+use crate::lower::step_cache::StepCache;
+use crate::lower::{self, LowerOptions};
+use crate::parse::{self, Workflow};
+use crate::validate;
+use crate::validate::policy::{PolicyRuleset, apply_policy};
+
+/// A compiled bundle with the warnings collected on the way.
+#[derive(Debug, Clone)]
+pub struct Compiled {
+    pub output: CodegenOutput,
+    pub warnings: Vec<CompilerError>,
+}
+
+/// Compile workflow JSON to a CRE project. Errors from IR validation point
+/// at the node they are about.
+pub fn compile(
+    json: &str,
+    cancel: Option<CancellationToken>,
+) -> Result<Compiled, Vec<CompilerError>> {
+    compile_with_options(json, cancel, &CodegenOptions::default())
+}
+
+/// [`compile`] with caller settings. With `options.strict`, any warning
+/// stops the pipeline before IR validation (see [`check_strict`]).
+pub fn compile_with_options(
+    json: &str,
+    cancel: Option<CancellationToken>,
+    options: &CodegenOptions,
+) -> Result<Compiled, Vec<CompilerError>> {
+    compile_with_policy(json, cancel, options, None)
+}
+
+/// [`compile_with_options`] that also checks the workflow against an
+/// organization policy once IR validation passes. Policy violations
+/// (R001–R005) stop the pipeline like IR errors and point at their node.
+pub fn compile_with_policy(
+    json: &str,
+    cancel: Option<CancellationToken>,
+    options: &CodegenOptions,
+    policy: Option<&PolicyRuleset>,
+) -> Result<Compiled, Vec<CompilerError>> {
+    compile_to_ir(json, cancel, options, policy).map(|(_, compiled)| compiled)
+}
+
+/// [`compile_with_policy`], also returning the IR the bundle was generated
+/// from.
+pub fn compile_to_ir(
+    json: &str,
+    cancel: Option<CancellationToken>,
+    options: &CodegenOptions,
+    policy: Option<&PolicyRuleset>,
+) -> Result<(WorkflowIR, Compiled), Vec<CompilerError>> {
+    let cancel = cancel.as_ref();
+    let (workflow, ir, warnings) = lower_json(json, cancel)?;
+    check_lowered(&workflow, &ir, &warnings, options, policy, cancel)?;
+
+    let output = match cancel {
+        Some(cancel) => codegen::codegen_cancellable(&ir, options, cancel)?,
+        None => codegen::codegen_with_options(&ir, options),
+    };
+    Ok((ir, Compiled { output, warnings }))
+}
+
+/// Everything between lowering and codegen: strict mode, IR validation and
+/// then `policy`. Errors point at the node they are about.
+pub fn check_lowered(
+    workflow: &Workflow,
+    ir: &WorkflowIR,
+    warnings: &[CompilerError],
+    options: &CodegenOptions,
+    policy: Option<&PolicyRuleset>,
+    cancel: Option<&CancellationToken>,
+) -> Result<(), Vec<CompilerError>> {
+    if options.strict {
+        check_strict(warnings)?;
+    }
+
+    let mut ir_errors = match cancel {
+        Some(cancel) => ir::validate_ir_cancellable(ir, cancel)?,
+        None => ir::validate_ir(ir),
+    };
+    if ir_errors.is_empty()
+        && let Some(policy) = policy
+    {
+        ir_errors = apply_policy(ir, policy);
+    }
+    if !ir_errors.is_empty() {
+        return Err(ir_errors
+            .into_iter()
+            .map(|e| CompilerError::from(e).locate_node(workflow))
+            .collect());
+    }
+    Ok(())
+}
+
+/// Strict mode: fail with the W-coded entries of `warnings` if there are
+/// any. The diagnostics keep their W codes; only the outcome changes. Info
+/// notes (I-codes) never fail.
+pub fn check_strict(warnings: &[CompilerError]) -> Result<(), Vec<CompilerError>> {
+    let failing: Vec<_> = warnings
+        .iter()
+        .filter(|w| w.is_warning())
+        .cloned()
+        .collect();
+    if !failing.is_empty() {
+        return Err(failing);
+    }
+    Ok(())
+}
+
+/// Parse, build the graph, validate it and lower it to IR, keeping warnings
+/// (and the I001 notes of any schema migration). Graph validation warnings
+/// never block lowering.
+pub fn lower_json(
+    json: &str,
+    cancel: Option<&CancellationToken>,
+) -> Result<(Workflow, WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    // 1. Parse
+    cancel::check(cancel, Phase::Parse)?;
+    let (workflow, mut warnings) = parse::parse_with_migrations(json)?;
+
+    let options = LowerOptions {
+        cancel: cancel.cloned(),
+        ..LowerOptions::default()
+    };
+    let (ir, lower_warnings) = lower_workflow(&workflow, &options)?;
+    warnings.extend(lower_warnings);
+    Ok((workflow, ir, warnings))
+}
+
+/// [`lower_json`] for an already parsed workflow: build the graph, validate
+/// it and lower it with `options`.
+pub fn lower_workflow(
+    workflow: &Workflow,
+    options: &LowerOptions,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    lower_workflow_with(workflow, options, None)
+}
+
+/// [`lower_workflow`], reusing steps from `cache` (see
+/// [`lower::lower_cached`]).
+pub fn lower_workflow_cached(
+    workflow: &Workflow,
+    options: &LowerOptions,
+    cache: &mut StepCache,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    lower_workflow_with(workflow, options, Some(cache))
+}
+
+fn lower_workflow_with(
+    workflow: &Workflow,
+    options: &LowerOptions,
+    step_cache: Option<&mut StepCache>,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    // 2. Build graph
+    let graph = parse::WorkflowGraph::build(workflow)?;
+
+    // 3. Graph validation (warnings are reported but don't block compilation)
+    cancel::check(options.cancel.as_ref(), Phase::Validate)?;
+    let (validation_warnings, validation_errors): (Vec<_>, Vec<_>) =
+        validate::validate_graph(workflow, &graph)
+            .into_iter()
+            .partition(CompilerError::is_warning);
+    if !validation_errors.is_empty() {
+        return Err(validation_errors);
+    }
+
+    // 4. Lower to IR
+    let mut warnings = graph.warnings.clone();
+    warnings.extend(validation_warnings);
+    let (ir, lower_warnings) = match step_cache {
+        Some(cache) => lower::lower_cached(workflow, &graph, options, cache)?,
+        None => lower::lower_with_options(workflow, &graph, options)?,
+    };
+    warnings.extend(lower_warnings);
+    Ok((ir, warnings))
+}
@@ -1,9 +1,12 @@
 //! Unified compiler error type used across all phases.
 
 use crate::ir::validate::ValidationError;
+use crate::lower::expand::source_node_id;
+use crate::parse::types::Workflow;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Phase {
+    Template,
     Parse,
     Validate,
     Lower,
@@ -14,6 +17,7 @@ pub enum Phase {
 impl std::fmt::Display for Phase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Phase::Template => write!(f, "Template"),
             Phase::Parse => write!(f, "Parse"),
             Phase::Validate => write!(f, "Validate"),
             Phase::Lower => write!(f, "Lower"),
@@ -23,12 +27,81 @@ impl std::fmt::Display for Phase {
     }
 }
 
+/// How serious a diagnostic is. It follows from the code: W-codes are
+/// warnings, I-codes infos, and everything else is an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn of_code(code: &str) -> Self {
+        if code.starts_with('W') {
+            Severity::Warning
+        } else if code.starts_with('I') {
+            Severity::Info
+        } else {
+            Severity::Error
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CompilerError {
     pub code: String,
+    /// Set from `code` by the constructors.
+    pub severity: Severity,
     pub phase: Phase,
     pub message: String,
     pub node_id: Option<String>,
+    /// Follow-up notes, e.g. how to get back under a capability budget.
+    pub related: Vec<RelatedDiagnostic>,
+    /// Location of the offending value in the workflow JSON, e.g.
+    /// `nodes[4].data.config.url`. Empty when unknown.
+    pub path: Vec<JsonPathSegment>,
+}
+
+/// One step of a path into the workflow JSON.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl JsonPathSegment {
+    pub fn key(key: impl Into<String>) -> Self {
+        JsonPathSegment::Key(key.into())
+    }
+}
+
+/// Render a path as `nodes[2].data.config.url`.
+pub fn format_json_path(path: &[JsonPathSegment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            JsonPathSegment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(key);
+            }
+            JsonPathSegment::Index(i) => out.push_str(&format!("[{}]", i)),
+        }
+    }
+    out
+}
+
+/// A note attached to a diagnostic that points at one or more nodes.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RelatedDiagnostic {
+    pub message: String,
+    /// Visual node ids the note is about.
+    pub node_ids: Vec<String>,
 }
 
 impl std::fmt::Display for CompilerError {
@@ -50,9 +123,13 @@ impl From<ValidationError> for CompilerError {
     fn from(e: ValidationError) -> Self {
         CompilerError {
             code: e.code.to_string(),
+            severity: Severity::of_code(e.code),
             phase: Phase::IrValidate,
             message: e.message,
-            node_id: e.step_id,
+            // Expanded steps (`node___sub`) report against their visual node
+            node_id: e.step_id.map(|id| source_node_id(&id).to_string()),
+            related: e.related,
+            path: vec![],
         }
     }
 }
@@ -61,27 +138,103 @@ impl CompilerError {
     pub fn parse(code: &str, message: impl Into<String>) -> Self {
         CompilerError {
             code: code.into(),
+            severity: Severity::of_code(code),
             phase: Phase::Parse,
             message: message.into(),
             node_id: None,
+            related: vec![],
+            path: vec![],
+        }
+    }
+
+    pub fn template(code: &str, message: impl Into<String>, node_id: Option<String>) -> Self {
+        CompilerError {
+            code: code.into(),
+            severity: Severity::of_code(code),
+            phase: Phase::Template,
+            message: message.into(),
+            node_id,
+            related: vec![],
+            path: vec![],
         }
     }
 
     pub fn validate(code: &str, message: impl Into<String>, node_id: Option<String>) -> Self {
         CompilerError {
             code: code.into(),
+            severity: Severity::of_code(code),
             phase: Phase::Validate,
             message: message.into(),
             node_id,
+            related: vec![],
+            path: vec![],
         }
     }
 
     pub fn lower(code: &str, message: impl Into<String>, node_id: Option<String>) -> Self {
         CompilerError {
             code: code.into(),
+            severity: Severity::of_code(code),
             phase: Phase::Lower,
             message: message.into(),
             node_id,
+            related: vec![],
+            path: vec![],
         }
     }
+
+    /// Warnings use W-codes and never fail compilation.
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    /// Informational notes, e.g. a migration applied to an old workflow
+    /// (I001). Like warnings they never fail compilation, and strict mode
+    /// ignores them.
+    pub fn is_info(&self) -> bool {
+        self.severity == Severity::Info
+    }
+
+    pub fn with_path(mut self, path: Vec<JsonPathSegment>) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Prepend `prefix`, e.g. to place a node-relative path under `nodes[i]`.
+    pub fn under(mut self, prefix: &[JsonPathSegment]) -> Self {
+        self.path.splice(0..0, prefix.iter().cloned());
+        self
+    }
+
+    /// Point an error at its node (`nodes[i]`) when `node_id` names one. A
+    /// pathless error gets the node's path; a node-relative one
+    /// (`data.config.url`) is placed under it.
+    pub fn locate_node(self, workflow: &Workflow) -> Self {
+        let node_relative =
+            matches!(self.path.first(), Some(JsonPathSegment::Key(k)) if k == "data");
+        if !self.path.is_empty() && !node_relative {
+            return self;
+        }
+        let index = self
+            .node_id
+            .as_deref()
+            .and_then(|id| workflow.nodes.iter().position(|n| n.id() == id));
+        match index {
+            Some(i) => self.under(&[JsonPathSegment::key("nodes"), JsonPathSegment::Index(i)]),
+            None => self,
+        }
+    }
+
+    /// Serialize for editor integrations; `path` is rendered as a string.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "code": self.code,
+            "severity": self.severity,
+            "phase": self.phase.to_string(),
+            "message": self.message,
+            "node_id": self.node_id,
+            "related": self.related,
+            "path": (!self.path.is_empty()).then(|| format_json_path(&self.path)),
+        })
+    }
 }
@@ -0,0 +1,97 @@
+//! Incremental compilation.
+//!
+//! Recompiling after a small edit reuses the lowered steps and generated step
+//! code of every node the edit cannot have affected: a node is recompiled when
+//! it, anything upstream of it, or a node it references changed. Parsing,
+//! validation, the whole-IR lowering passes and the small supporting files
+//! always run in full. The output is byte-identical to a cold compile.
+//!
+//! The cache is serializable, so an editor can keep it between sessions.
+
+use serde::{Deserialize, Serialize};
+
+use crate::codegen::{self, CodegenOptions, CodegenOutput, SnippetCache};
+use crate::compile::{self, Compiled};
+use crate::error::CompilerError;
+use crate::lower::LowerOptions;
+use crate::lower::step_cache::StepCache;
+use crate::parse;
+use crate::validate::policy::PolicyRuleset;
+
+/// Lowered steps and generated step code from the previous compilation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompilationCache {
+    /// Compiler that filled the cache; entries from another version are dropped.
+    compiler_version: String,
+    steps: StepCache,
+    snippets: SnippetCache,
+}
+
+/// How many steps the last compilation reused (`hits`) or had to lower or
+/// generate afresh (`misses`), counting lowering and codegen separately.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CompilationCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hits and misses of the most recent compilation.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.steps.hits + self.snippets.hits,
+            misses: self.steps.misses + self.snippets.misses,
+        }
+    }
+}
+
+/// Compile workflow JSON through the full pipeline, reusing and refreshing
+/// `cache`. Warnings are not reported; use
+/// [`compile_incremental_with_options`] for those.
+pub fn compile_incremental(
+    json: &str,
+    cache: &mut CompilationCache,
+) -> Result<CodegenOutput, Vec<CompilerError>> {
+    compile(json, cache, &CodegenOptions::default(), None).map(|c| c.output)
+}
+
+/// [`compile::compile_with_policy`] reusing and refreshing `cache`: the
+/// same bundle and warnings, or the same errors.
+pub fn compile_incremental_with_options(
+    json: &str,
+    cache: &mut CompilationCache,
+    options: &CodegenOptions,
+    policy: Option<&PolicyRuleset>,
+) -> Result<Compiled, Vec<CompilerError>> {
+    compile(json, cache, options, policy)
+}
+
+fn compile(
+    json: &str,
+    cache: &mut CompilationCache,
+    options: &CodegenOptions,
+    policy: Option<&PolicyRuleset>,
+) -> Result<Compiled, Vec<CompilerError>> {
+    if cache.compiler_version != env!("CARGO_PKG_VERSION") {
+        *cache = CompilationCache {
+            compiler_version: env!("CARGO_PKG_VERSION").to_string(),
+            ..CompilationCache::default()
+        };
+    }
+    // Stats describe this run only, even if it stops before codegen.
+    cache.snippets.begin();
+
+    let (workflow, mut warnings) = parse::parse_with_migrations(json)?;
+    let (ir, lower_warnings) =
+        compile::lower_workflow_cached(&workflow, &LowerOptions::default(), &mut cache.steps)?;
+    warnings.extend(lower_warnings);
+    compile::check_lowered(&workflow, &ir, &warnings, options, policy, None)?;
+
+    let output = codegen::codegen_cached_with(&ir, options, &mut cache.snippets);
+    Ok(Compiled { output, warnings })
+}
@@ -0,0 +1,419 @@
+//! What each EvmWrite of a workflow calls, for auditors.
+//!
+//! An EvmWrite only carries pre-encoded calldata, usually the `encoded`
+//! output of an AbiEncode step. [`describe_writes`] follows that binding back
+//! to the encode step and rebuilds the call from its ABI: function name,
+//! signature and the source of every argument. Calldata that does not come
+//! straight from an AbiEncode step (a raw expression, a literal, another
+//! step's output) is opaque: it is documented as such and flagged by
+//! [`opaque_calldata_warnings`] (W011).
+//!
+//! Codegen renders the descriptions as `writes.md`.
+//!
+//! [`dependency_graph`] answers a different question for the editor: which
+//! steps read which other steps' outputs, at step and visual-node level.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+use crate::ir::validate::{collect_binding_refs_from_step, visit_steps};
+use crate::lower::reference::{config_path, field};
+
+/// One EvmWrite step, in handler order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteDescription {
+    pub step_id: String,
+    pub label: String,
+    /// Chain selector name, or None if the client binding names no chain.
+    pub chain: Option<String>,
+    pub receiver: String,
+    pub gas_limit: String,
+    pub value_wei: Option<String>,
+    pub calldata: Calldata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Calldata {
+    /// Built by the AbiEncode step `encode_step_id`.
+    Encoded {
+        encode_step_id: String,
+        /// None for parameter-only encoding.
+        function_name: Option<String>,
+        /// `mint(address,uint256)`, or `(address,uint256)` without a function.
+        signature: String,
+        args: Vec<CallArgument>,
+    },
+    /// Not traceable to an AbiEncode step; `expr` is the data expression.
+    Opaque { expr: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallArgument {
+    pub name: String,
+    pub abi_type: String,
+    /// Source expression, or None if no data mapping covers the parameter.
+    pub source: Option<String>,
+}
+
+impl Calldata {
+    /// `mint(to=config.walletAddress, amount=config.mintAmount)`, or None
+    /// for opaque calldata.
+    pub fn call(&self) -> Option<String> {
+        let Calldata::Encoded {
+            function_name,
+            args,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| format!("{}={}", arg.name, arg.source.as_deref().unwrap_or("?")))
+            .collect();
+        Some(format!(
+            "{}({})",
+            function_name.as_deref().unwrap_or(""),
+            args.join(", ")
+        ))
+    }
+}
+
+/// Every EvmWrite step, with its calldata traced back where possible.
+pub fn describe_writes(ir: &WorkflowIR) -> Vec<WriteDescription> {
+    let mut encodes = HashMap::new();
+    collect_abi_encodes(&ir.handler_body, &mut encodes);
+    let mut writes = Vec::new();
+    collect_writes(ir, &ir.handler_body, &encodes, &mut writes);
+    writes
+}
+
+/// W011 for each EvmWrite whose calldata is opaque.
+pub fn opaque_calldata_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    describe_writes(ir)
+        .into_iter()
+        .filter_map(|write| match write.calldata {
+            Calldata::Opaque { expr } => Some(
+                CompilerError::lower(
+                    "W011",
+                    format!(
+                        "EVM write '{}' sends opaque calldata ({}) — encode it with an ABI Encode node so the call can be audited",
+                        write.step_id, expr
+                    ),
+                    Some(write.step_id),
+                )
+                .with_path(config_path(field("encodedData"))),
+            ),
+            Calldata::Encoded { .. } => None,
+        })
+        .collect()
+}
+
+fn collect_abi_encodes<'a>(block: &'a Block, out: &mut HashMap<&'a str, &'a AbiEncodeOp>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::AbiEncode(op) => {
+                out.insert(step.id.as_str(), op);
+            }
+            Operation::Branch(branch) => {
+                collect_abi_encodes(&branch.true_branch, out);
+                collect_abi_encodes(&branch.false_branch, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_writes(
+    ir: &WorkflowIR,
+    block: &Block,
+    encodes: &HashMap<&str, &AbiEncodeOp>,
+    out: &mut Vec<WriteDescription>,
+) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::EvmWrite(op) => out.push(WriteDescription {
+                step_id: step.id.clone(),
+                label: step.label.clone(),
+                chain: ir
+                    .evm_chains
+                    .iter()
+                    .find(|chain| chain.binding_name == op.evm_client_binding)
+                    .map(|chain| chain.chain_selector_name.clone()),
+                receiver: describe_value(&op.receiver_address),
+                gas_limit: describe_value(&op.gas_limit),
+                value_wei: op.value_wei.as_ref().map(describe_value),
+                calldata: trace_calldata(&op.encoded_data, encodes),
+            }),
+            Operation::Branch(branch) => {
+                collect_writes(ir, &branch.true_branch, encodes, out);
+                collect_writes(ir, &branch.false_branch, encodes, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn trace_calldata(data: &ValueExpr, encodes: &HashMap<&str, &AbiEncodeOp>) -> Calldata {
+    let encode = match data {
+        ValueExpr::Binding(binding) if binding.field_path == "encoded" => encodes
+            .get(binding.step_id.as_str())
+            .map(|op| (binding.step_id.as_str(), *op)),
+        _ => None,
+    };
+    let Some((encode_step_id, op)) = encode else {
+        return Calldata::Opaque {
+            expr: describe_value(data),
+        };
+    };
+
+    let params = abi_params(&op.abi_json);
+    let types: Vec<String> = params.iter().map(canonical_type).collect();
+    let args = params
+        .iter()
+        .zip(&types)
+        .map(|(param, abi_type)| {
+            let name = param["name"].as_str().unwrap_or_default().to_string();
+            CallArgument {
+                source: describe_sources(op, &name),
+                abi_type: abi_type.clone(),
+                name,
+            }
+        })
+        .collect();
+    Calldata::Encoded {
+        encode_step_id: encode_step_id.to_string(),
+        function_name: op.function_name.clone(),
+        signature: format!(
+            "{}({})",
+            op.function_name.as_deref().unwrap_or(""),
+            types.join(",")
+        ),
+        args,
+    }
+}
+
+/// The source of parameter `name`, or `[a, b]` when several mappings fill
+/// an array parameter.
+fn describe_sources(op: &AbiEncodeOp, name: &str) -> Option<String> {
+    let sources: Vec<String> = op
+        .data_mappings
+        .iter()
+        .filter(|m| m.param_name == name)
+        .map(|m| describe_value(&m.value))
+        .collect();
+    match sources.len() {
+        0 => None,
+        1 => sources.into_iter().next(),
+        _ => Some(format!("[{}]", sources.join(", "))),
+    }
+}
+
+/// The parameters of an AbiEncode's `abi_json`: a function item's `inputs`
+/// or a bare parameter array.
+fn abi_params(abi_json: &str) -> Vec<Value> {
+    match serde_json::from_str::<Value>(abi_json) {
+        Ok(Value::Array(items)) => items,
+        Ok(Value::Object(mut item)) => match item.remove("inputs") {
+            Some(Value::Array(items)) => items,
+            _ => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+/// Type as it appears in a signature; tuples are spelled out from their
+/// components: `(address,uint256)[]`.
+fn canonical_type(param: &Value) -> String {
+    let abi_type = param["type"].as_str().unwrap_or_default();
+    match (
+        abi_type.strip_prefix("tuple"),
+        param["components"].as_array(),
+    ) {
+        (Some(suffix), Some(components)) => {
+            let inner: Vec<String> = components.iter().map(canonical_type).collect();
+            format!("({}){}", inner.join(","), suffix)
+        }
+        _ => abi_type.to_string(),
+    }
+}
+
+/// Readable source of a value: `config.mintAmount`, `trigger.amount`,
+/// `http-1.body.to`, literals as JSON.
+fn describe_value(expr: &ValueExpr) -> String {
+    match expr {
+        ValueExpr::Literal(literal) => match literal {
+            LiteralValue::String { value } => {
+                serde_json::to_string(value).expect("a string always serializes")
+            }
+            LiteralValue::Number { value } => value.to_string(),
+            LiteralValue::Integer { value } => value.to_string(),
+            LiteralValue::Boolean { value } => value.to_string(),
+            LiteralValue::Null => "null".into(),
+            LiteralValue::Json { value } => value.clone(),
+        },
+        ValueExpr::Binding(binding) if binding.field_path.is_empty() => binding.step_id.clone(),
+        ValueExpr::Binding(binding) => format!("{}.{}", binding.step_id, binding.field_path),
+        ValueExpr::ConfigRef { field } => format!("config.{}", field),
+        ValueExpr::TriggerDataRef { field } => format!("trigger.{}", field),
+        ValueExpr::SecretRef { name } => format!("secret.{}", name),
+        ValueExpr::Template { parts } => {
+            let text: String = parts
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Lit { value } => value.clone(),
+                    TemplatePart::Expr { value } => format!("${{{}}}", describe_value(value)),
+                })
+                .collect();
+            format!("`{}`", text)
+        }
+        ValueExpr::RawExpr { expr } => expr.clone(),
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => format!(
+            "{} ? {} : {}",
+            describe_value(cond),
+            describe_value(then),
+            describe_value(otherwise)
+        ),
+    }
+}
+
+/// Which steps read which other steps' outputs.
+///
+/// Built from every [`ValueExpr::Binding`] a step reads (HTTP URLs, headers
+/// and bodies, branch conditions, merge inputs, ...), so the editor can
+/// highlight what an edit to a node affects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepDepGraph {
+    /// Every step, branch arms included, in source order.
+    pub steps: Vec<StepDepNode>,
+    /// One edge per (step, dependency) pair, in the order the steps read them.
+    pub edges: Vec<StepDepEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepDepNode {
+    pub step_id: String,
+    /// The visual nodes the step was lowered from.
+    pub source_node_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepDepEdge {
+    /// The step that reads the output.
+    pub step_id: String,
+    /// The step whose output it reads.
+    pub depends_on: String,
+}
+
+impl StepDepGraph {
+    /// Steps that read `step_id`'s output directly.
+    pub fn dependents_of(&self, step_id: &str) -> Vec<&str> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.depends_on == step_id)
+            .map(|edge| edge.step_id.as_str())
+            .collect()
+    }
+
+    /// Steps whose outputs `step_id` reads directly.
+    pub fn dependencies_of(&self, step_id: &str) -> Vec<&str> {
+        self.edges
+            .iter()
+            .filter(|edge| edge.step_id == step_id)
+            .map(|edge| edge.depends_on.as_str())
+            .collect()
+    }
+
+    /// Visual nodes whose steps read an output of `node_id`'s steps directly.
+    pub fn node_dependencies_of(&self, node_id: &str) -> Vec<&str> {
+        let mut step_ids = Vec::new();
+        for step in self.steps_of_node(node_id) {
+            for dependency in self.dependencies_of(&step.step_id) {
+                if !step_ids.contains(&dependency) {
+                    step_ids.push(dependency);
+                }
+            }
+        }
+        self.nodes_of_steps(&step_ids, node_id)
+    }
+
+    /// Visual nodes affected by a change to `node_id`: the nodes of every
+    /// step that reads its steps' outputs, directly or through other steps.
+    pub fn transitive_dependents(&self, node_id: &str) -> Vec<&str> {
+        let mut reached: Vec<&str> = Vec::new();
+        let mut pending: Vec<&str> = self
+            .steps_of_node(node_id)
+            .map(|step| step.step_id.as_str())
+            .collect();
+        while let Some(step_id) = pending.pop() {
+            for dependent in self.dependents_of(step_id) {
+                if !reached.contains(&dependent) {
+                    reached.push(dependent);
+                    pending.push(dependent);
+                }
+            }
+        }
+        self.nodes_of_steps(&reached, node_id)
+    }
+
+    fn steps_of_node<'a>(&'a self, node_id: &'a str) -> impl Iterator<Item = &'a StepDepNode> {
+        self.steps
+            .iter()
+            .filter(move |step| step.source_node_ids.iter().any(|id| id == node_id))
+    }
+
+    /// Source nodes of `step_ids` in step order, without duplicates or `except`.
+    fn nodes_of_steps(&self, step_ids: &[&str], except: &str) -> Vec<&str> {
+        let mut nodes = Vec::new();
+        for step in &self.steps {
+            if !step_ids.contains(&step.step_id.as_str()) {
+                continue;
+            }
+            for node_id in &step.source_node_ids {
+                if node_id != except && !nodes.contains(&node_id.as_str()) {
+                    nodes.push(node_id.as_str());
+                }
+            }
+        }
+        nodes
+    }
+}
+
+/// The step-level dependency graph of the handler.
+///
+/// Bindings to steps that don't exist (E003/E004 territory) are left out.
+pub fn dependency_graph(ir: &WorkflowIR) -> StepDepGraph {
+    let mut steps = Vec::new();
+    visit_steps(&ir.handler_body, &mut |step| {
+        steps.push(StepDepNode {
+            step_id: step.id.clone(),
+            source_node_ids: step.source_node_ids.clone(),
+        });
+    });
+
+    let mut edges: Vec<StepDepEdge> = Vec::new();
+    visit_steps(&ir.handler_body, &mut |step| {
+        for binding in collect_binding_refs_from_step(step) {
+            let known = steps.iter().any(|s| s.step_id == binding.step_id);
+            let seen = edges
+                .iter()
+                .any(|e| e.step_id == step.id && e.depends_on == binding.step_id);
+            if known && !seen {
+                edges.push(StepDepEdge {
+                    step_id: step.id.clone(),
+                    depends_on: binding.step_id,
+                });
+            }
+        }
+    });
+
+    StepDepGraph { steps, edges }
+}
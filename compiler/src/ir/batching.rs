@@ -0,0 +1,228 @@
+//! Find HTTP requests that could be merged into one call.
+//!
+//! CRE caps HTTP calls per execution (E009). A common way over the limit is a
+//! fan-out of GETs to one endpoint that differ only in an interpolated id,
+//! e.g. `https://api.example.com/price/{{a.symbol}}` and
+//! `https://api.example.com/price/{{b.symbol}}`. URLs are compared
+//! structurally: literal parts must match exactly, interpolated parts act as
+//! wildcards. Requests with the same method and URL shape whose interpolated
+//! parts differ are reported together, so the user can replace them with one
+//! batched request.
+
+use crate::error::RelatedDiagnostic;
+use crate::ir::types::*;
+
+/// One suggestion per group of two or more batchable requests, in order of
+/// first appearance.
+pub fn batching_suggestions(block: &Block) -> Vec<RelatedDiagnostic> {
+    let mut requests = Vec::new();
+    collect_requests(block, &mut requests);
+
+    let mut groups: Vec<BatchGroup> = Vec::new();
+    for (step, op) in requests {
+        let Some(shape) = UrlShape::of(&op.url) else {
+            continue;
+        };
+        if shape.wildcards.is_empty() {
+            continue;
+        }
+        match groups
+            .iter_mut()
+            .find(|g| g.method == op.method.as_str() && g.shape.literals == shape.literals)
+        {
+            Some(group) => group.members.push((step, shape)),
+            None => groups.push(BatchGroup {
+                method: op.method.as_str(),
+                shape: shape.clone(),
+                members: vec![(step, shape)],
+            }),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|g| g.members.len() > 1)
+        // Requests whose interpolated parts are all the same are duplicates,
+        // not a fan-out; caching (W005) is the fix for those.
+        .filter(|g| g.members.iter().any(|(_, s)| s.wildcards != g.shape.wildcards))
+        .map(|g| RelatedDiagnostic {
+            message: format!(
+                "These {} {} calls to {} differ only in interpolated URL parts and could be batched into one request",
+                g.members.len(),
+                g.method,
+                g.shape.host
+            ),
+            node_ids: g
+                .members
+                .iter()
+                .map(|(step, _)| step.source_node_ids.first().unwrap_or(&step.id).clone())
+                .collect(),
+        })
+        .collect()
+}
+
+struct BatchGroup<'a> {
+    method: &'static str,
+    shape: UrlShape,
+    members: Vec<(&'a Step, UrlShape)>,
+}
+
+/// A URL split into its literal text and its interpolated parts.
+#[derive(Clone)]
+struct UrlShape {
+    host: String,
+    /// Literal runs between interpolations; `literals.len() == wildcards.len() + 1`.
+    literals: Vec<String>,
+    /// Interpolated parts, serialized for comparison.
+    wildcards: Vec<String>,
+}
+
+impl UrlShape {
+    /// `None` when the URL is not a literal or template, or when its scheme
+    /// and host are not fully literal.
+    fn of(url: &ValueExpr) -> Option<UrlShape> {
+        let mut literals = vec![String::new()];
+        let mut wildcards = Vec::new();
+        match url {
+            ValueExpr::Literal(LiteralValue::String { value }) => literals[0].push_str(value),
+            ValueExpr::Template { parts } => {
+                for part in parts {
+                    match part {
+                        TemplatePart::Lit { value } => {
+                            literals.last_mut().expect("never empty").push_str(value)
+                        }
+                        TemplatePart::Expr { value } => {
+                            wildcards.push(serde_json::to_string(value).ok()?);
+                            literals.push(String::new());
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+
+        let rest = literals[0]
+            .strip_prefix("https://")
+            .or_else(|| literals[0].strip_prefix("http://"))?;
+        let host_end = rest.find(['/', '?', '#'])?;
+        let host = rest[..host_end].to_string();
+        if host.is_empty() {
+            return None;
+        }
+        Some(UrlShape {
+            host,
+            literals,
+            wildcards,
+        })
+    }
+}
+
+fn collect_requests<'a>(block: &'a Block, out: &mut Vec<(&'a Step, &'a HttpRequestOp)>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::HttpRequest(op) => out.push((step, op)),
+            Operation::Branch(branch) => {
+                collect_requests(&branch.true_branch, out);
+                collect_requests(&branch.false_branch, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_step(id: &str, url: ValueExpr) -> Step {
+        Step {
+            id: id.into(),
+            source_node_ids: vec![id.into()],
+            label: id.into(),
+            operation: Operation::HttpRequest(HttpRequestOp {
+                method: HttpMethod::Get,
+                url,
+                headers: vec![],
+                query_params: vec![],
+                body: None,
+                authentication: None,
+                cache_max_age_seconds: None,
+                timeout_ms: None,
+                expected_status_codes: vec![200],
+                response_format: HttpResponseFormat::Json,
+                follow_redirects: true,
+                ignore_ssl: false,
+                consensus: ConsensusStrategy::Identical,
+            }),
+            output: None,
+            notes: None,
+        }
+    }
+
+    fn price_url(source_step: &str) -> ValueExpr {
+        ValueExpr::Template {
+            parts: vec![
+                TemplatePart::Lit {
+                    value: "https://api.example.com/price/".into(),
+                },
+                TemplatePart::Expr {
+                    value: ValueExpr::binding(source_step, "symbol"),
+                },
+                TemplatePart::Lit {
+                    value: "?currency=usd".into(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn templated_urls_to_same_endpoint_are_grouped() {
+        let block = Block {
+            steps: vec![
+                get_step("http-1", price_url("a")),
+                get_step(
+                    "http-2",
+                    ValueExpr::string("https://api.example.com/status"),
+                ),
+                get_step("http-3", price_url("b")),
+                get_step("http-4", price_url("c")),
+            ],
+        };
+
+        let suggestions = batching_suggestions(&block);
+
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].node_ids, vec!["http-1", "http-3", "http-4"]);
+        assert!(
+            suggestions[0]
+                .message
+                .contains("These 3 GET calls to api.example.com")
+        );
+    }
+
+    #[test]
+    fn same_interpolation_or_different_literals_are_not_grouped() {
+        let other_path = ValueExpr::Template {
+            parts: vec![
+                TemplatePart::Lit {
+                    value: "https://api.example.com/volume/".into(),
+                },
+                TemplatePart::Expr {
+                    value: ValueExpr::binding("b", "symbol"),
+                },
+                TemplatePart::Lit {
+                    value: "?currency=usd".into(),
+                },
+            ],
+        };
+        let block = Block {
+            steps: vec![
+                get_step("http-1", price_url("a")),
+                get_step("http-2", price_url("a")),
+                get_step("http-3", other_path),
+            ],
+        };
+
+        assert!(batching_suggestions(&block).is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! The `{{step.field}}` bindings a workflow produces, for editor tooling.
+//!
+//! [`produced_bindings`] lists every step output with the branch arms it
+//! sits in, and [`bindings_visible_at`] narrows that to the outputs a step
+//! may reference: those produced before it, outside any branch arm it is not
+//! itself in. This is what autocomplete offers.
+
+use serde::Serialize;
+
+use crate::ir::types::*;
+
+/// A step output, with where it can be referenced from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProducedBinding {
+    /// The `Step.id` that produces the binding.
+    pub step_id: String,
+    pub binding: OutputBinding,
+    /// The enclosing branch arms, as in [`Block::walk_steps`]: each branch
+    /// step id followed by `"true"` or `"false"`, outermost first. Empty for
+    /// a top-level step.
+    pub scope: Vec<String>,
+}
+
+impl ProducedBinding {
+    /// Whether a step in `scope` (in the same form as [`Self::scope`]) sits
+    /// in every branch arm this binding does.
+    pub fn in_scope_of(&self, scope: &[&str]) -> bool {
+        scope.len() >= self.scope.len() && self.scope.iter().zip(scope).all(|(a, b)| a == b)
+    }
+}
+
+/// Every step output in the handler, branch arms included, in source order.
+pub fn produced_bindings(ir: &WorkflowIR) -> Vec<ProducedBinding> {
+    let mut found = Vec::new();
+    walk_steps(ir, |step, scope| found.extend(produced(step, scope)));
+    found
+}
+
+/// The bindings step `step_id` may reference: outputs of the steps before
+/// it whose branch arms enclose it too. Empty when there is no such step.
+pub fn bindings_visible_at(ir: &WorkflowIR, step_id: &str) -> Vec<ProducedBinding> {
+    let mut before = Vec::new();
+    let mut target_scope = None;
+    walk_steps(ir, |step, scope| {
+        if target_scope.is_some() {
+            return;
+        }
+        if step.id == step_id {
+            target_scope = Some(scope.to_vec());
+        } else {
+            before.extend(produced(step, scope));
+        }
+    });
+    let Some(scope) = target_scope else {
+        return vec![];
+    };
+    before.retain(|b| b.in_scope_of(&scope));
+    before
+}
+
+fn produced(step: &Step, scope: &[&str]) -> Option<ProducedBinding> {
+    Some(ProducedBinding {
+        step_id: step.id.clone(),
+        binding: step.output.clone()?,
+        scope: scope.iter().map(|s| s.to_string()).collect(),
+    })
+}
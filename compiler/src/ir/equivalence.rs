@@ -0,0 +1,95 @@
+//! Whether two IRs describe the same workflow up to step ids.
+//!
+//! `parse::canonicalize` promises that the canonical workflow lowers to the
+//! original's IR with only ids changed. [`equivalent_up_to_ids`] checks that
+//! promise: it renames the original IR's step ids (and the `step_<id>`
+//! variables derived from them) and compares the result with the canonical
+//! IR. The steps of each block are compared as a set, because both IRs list
+//! them in a topological order of the same dependencies and only steps that
+//! do not depend on each other can change places.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::ir::types::WorkflowIR;
+use crate::lower::expand::EXPANDED_STEP_SEPARATOR;
+
+/// Whether `a`, with its node ids renamed by `renames`, is the same IR as `b`.
+pub fn equivalent_up_to_ids(
+    a: &WorkflowIR,
+    b: &WorkflowIR,
+    renames: &HashMap<String, String>,
+) -> bool {
+    normalized(a, renames) == normalized(b, &HashMap::new())
+}
+
+fn normalized(ir: &WorkflowIR, renames: &HashMap<String, String>) -> Value {
+    let variables: HashMap<String, String> = renames
+        .iter()
+        .map(|(old, new)| (variable_name(old), variable_name(new)))
+        .collect();
+    let mut value = serde_json::to_value(ir).expect("WorkflowIR always serializes to JSON");
+    normalize(&mut value, renames, &variables);
+    value
+}
+
+fn normalize(
+    value: &mut Value,
+    renames: &HashMap<String, String>,
+    variables: &HashMap<String, String>,
+) {
+    match value {
+        Value::String(s) => {
+            *s = rename_id(s, renames).unwrap_or_else(|| rename_variables(s, variables))
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|v| normalize(v, renames, variables)),
+        Value::Object(map) => {
+            map.values_mut()
+                .for_each(|v| normalize(v, renames, variables));
+            for key in ["steps", "referenced_by"] {
+                if let Some(Value::Array(items)) = map.get_mut(key) {
+                    items.sort_by_cached_key(Value::to_string);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The renamed id if `s` is a node id or a step expanded from one.
+fn rename_id(s: &str, renames: &HashMap<String, String>) -> Option<String> {
+    if let Some(new) = renames.get(s) {
+        return Some(new.clone());
+    }
+    let (node_id, sub) = s.split_once(EXPANDED_STEP_SEPARATOR)?;
+    let new = renames.get(node_id)?;
+    Some(format!("{}{}{}", new, EXPANDED_STEP_SEPARATOR, sub))
+}
+
+/// Rename whole identifiers only, in one pass, so a new name is never
+/// renamed again.
+fn rename_variables(s: &str, variables: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut token = String::new();
+    let flush = |token: &mut String, out: &mut String| {
+        out.push_str(variables.get(token.as_str()).unwrap_or(token));
+        token.clear();
+    };
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            token.push(c);
+        } else {
+            flush(&mut token, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut token, &mut out);
+    out
+}
+
+fn variable_name(node_id: &str) -> String {
+    format!("step_{}", node_id.replace('-', "_"))
+}
@@ -0,0 +1,225 @@
+//! Stable JSON import/export of the IR for external tooling.
+//!
+//! Services that consume a `WorkflowIR` directly (instead of the generated
+//! TypeScript) read it through `to_json` / `from_json`. The document is the
+//! serialized IR with two extra top-level fields:
+//!
+//! ```json
+//! { "schema": "6flow-ir", "version": 2, "metadata": { ... }, "trigger": { ... }, ... }
+//! ```
+//!
+//! Variant tags are pinned in `ir::types`; field names are pinned by the
+//! fixtures under `tests/fixtures/ir/`, which must keep deserializing
+//! unchanged. Any change to the serialized shape bumps [`IR_SCHEMA_VERSION`];
+//! an optional field skipped when unset leaves old documents unchanged and
+//! does not.
+//!
+//! Reading is strict: a field this version does not know is an error, so a
+//! typo or a document from a newer compiler is not silently half-read.
+//! [`ReadOptions::allow_unknown_operation_fields`] relaxes that inside step
+//! operations only.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{JsonPathSegment, format_json_path};
+use crate::ir::types::WorkflowIR;
+use crate::parse::error_path;
+
+/// Value of the top-level `schema` field.
+pub const IR_SCHEMA: &str = "6flow-ir";
+
+/// Version of the serialized IR format written by [`to_json`] and the only
+/// one [`from_json`] reads.
+///
+/// Version 2: a Filter's early-return `message` is a `ValueExpr`.
+pub const IR_SCHEMA_VERSION: u64 = 2;
+
+/// Settings for [`from_json_with_options`]. The default is strict.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Ignore unknown fields inside a step's `operation` (e.g. ones added by
+    /// a newer compiler). Unknown fields anywhere else are still rejected.
+    pub allow_unknown_operation_fields: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IrIoError {
+    /// Not JSON, or the value at `path` has the wrong shape.
+    Malformed {
+        path: Vec<JsonPathSegment>,
+        message: String,
+    },
+    /// `schema` is missing or is not [`IR_SCHEMA`].
+    WrongSchema { found: Option<String> },
+    /// `version` is missing or is not [`IR_SCHEMA_VERSION`].
+    UnsupportedVersion { found: Option<u64> },
+    /// A field that this version of the IR does not have.
+    UnknownField { path: Vec<JsonPathSegment> },
+}
+
+impl std::fmt::Display for IrIoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IrIoError::Malformed { path, message } if path.is_empty() => {
+                write!(f, "Malformed IR JSON: {}", message)
+            }
+            IrIoError::Malformed { path, message } => {
+                write!(
+                    f,
+                    "Malformed IR JSON at {}: {}",
+                    format_json_path(path),
+                    message
+                )
+            }
+            IrIoError::WrongSchema {
+                found: Some(schema),
+            } => {
+                write!(f, "Expected schema \"{}\", found \"{}\"", IR_SCHEMA, schema)
+            }
+            IrIoError::WrongSchema { found: None } => {
+                write!(f, "Missing schema field (expected \"{}\")", IR_SCHEMA)
+            }
+            IrIoError::UnsupportedVersion {
+                found: Some(version),
+            } => write!(
+                f,
+                "Unsupported IR version {} (this compiler reads version {})",
+                version, IR_SCHEMA_VERSION
+            ),
+            IrIoError::UnsupportedVersion { found: None } => {
+                write!(f, "Missing version field (expected {})", IR_SCHEMA_VERSION)
+            }
+            IrIoError::UnknownField { path } => {
+                write!(f, "Unknown field {}", format_json_path(path))
+            }
+        }
+    }
+}
+
+impl std::error::Error for IrIoError {}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    schema: &'static str,
+    version: u64,
+    #[serde(flatten)]
+    ir: &'a WorkflowIR,
+}
+
+/// Serialize `ir` as a versioned, pretty-printed IR document.
+pub fn to_json(ir: &WorkflowIR) -> String {
+    let document = Document {
+        schema: IR_SCHEMA,
+        version: IR_SCHEMA_VERSION,
+        ir,
+    };
+    serde_json::to_string_pretty(&document).expect("WorkflowIR always serializes to JSON")
+}
+
+/// Read an IR document written by [`to_json`], rejecting unknown fields.
+pub fn from_json(json: &str) -> Result<WorkflowIR, IrIoError> {
+    from_json_with_options(json, &ReadOptions::default())
+}
+
+/// Like [`from_json`], with the strictness set by `options`.
+pub fn from_json_with_options(json: &str, options: &ReadOptions) -> Result<WorkflowIR, IrIoError> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| IrIoError::Malformed {
+        path: vec![],
+        message: e.to_string(),
+    })?;
+    let Some(document) = value.as_object_mut() else {
+        return Err(IrIoError::Malformed {
+            path: vec![],
+            message: "expected a JSON object".into(),
+        });
+    };
+
+    match document.remove("schema") {
+        Some(Value::String(schema)) if schema == IR_SCHEMA => {}
+        Some(Value::String(schema)) => {
+            return Err(IrIoError::WrongSchema {
+                found: Some(schema),
+            });
+        }
+        _ => return Err(IrIoError::WrongSchema { found: None }),
+    }
+    match document.remove("version").as_ref().and_then(Value::as_u64) {
+        Some(IR_SCHEMA_VERSION) => {}
+        found => return Err(IrIoError::UnsupportedVersion { found }),
+    }
+
+    let ir: WorkflowIR =
+        serde_path_to_error::deserialize(&value).map_err(|e| IrIoError::Malformed {
+            path: error_path(&e),
+            message: e.into_inner().to_string(),
+        })?;
+
+    // Whatever serde skipped is exactly what does not survive a round trip.
+    let known = serde_json::to_value(&ir).expect("WorkflowIR always serializes to JSON");
+    let mut path = Vec::new();
+    if let Some(path) = find_unknown_field(&value, &known, false, options, &mut path) {
+        return Err(IrIoError::UnknownField { path });
+    }
+    Ok(ir)
+}
+
+/// Path to the first key of `input` that `known` (its re-serialization)
+/// lacks. A null or empty-list value is not reported: optional fields are
+/// skipped when serializing exactly those, so `known` drops them. Keys under an `operation` are skipped if the options allow it,
+/// until a nested `steps` list (a branch arm) starts another step.
+fn find_unknown_field(
+    input: &Value,
+    known: &Value,
+    in_operation: bool,
+    options: &ReadOptions,
+    path: &mut Vec<JsonPathSegment>,
+) -> Option<Vec<JsonPathSegment>> {
+    match (input, known) {
+        (Value::Object(input), Value::Object(known)) => {
+            for (key, value) in input {
+                path.push(JsonPathSegment::key(key.as_str()));
+                let found = match known.get(key) {
+                    Some(known) => {
+                        let in_operation = match key.as_str() {
+                            "operation" => true,
+                            "steps" => false,
+                            _ => in_operation,
+                        };
+                        find_unknown_field(value, known, in_operation, options, path)
+                    }
+                    None if is_skipped_when_serializing(value) => None,
+                    None if in_operation && options.allow_unknown_operation_fields => None,
+                    None => Some(path.clone()),
+                };
+                path.pop();
+                if found.is_some() {
+                    return found;
+                }
+            }
+            None
+        }
+        (Value::Array(input), Value::Array(known)) => {
+            for (i, (value, known)) in input.iter().zip(known).enumerate() {
+                path.push(JsonPathSegment::Index(i));
+                let found = find_unknown_field(value, known, in_operation, options, path);
+                path.pop();
+                if found.is_some() {
+                    return found;
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Whether `value` is what an unset optional field (`Option::is_none`,
+/// `Vec::is_empty`) looks like, which serializing leaves out.
+fn is_skipped_when_serializing(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
@@ -0,0 +1,225 @@
+//! Syntax check for the patterns of `regex` / `notRegex` conditions.
+//!
+//! A condition's pattern is passed to `new RegExp(...)` without flags, so a
+//! malformed pattern only shows up as a `SyntaxError` once the handler runs.
+//! [`check_pattern`] follows the JavaScript grammar in its web-compatible
+//! form (`{` and `]` may stand for themselves) and reports the first error
+//! with its character position. It does not judge what a pattern matches.
+//! Shared by node validation (N034) and the IR backstop (E020).
+
+/// Why a pattern would not compile, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    /// Zero-based character offset into the pattern.
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for RegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.reason, self.position)
+    }
+}
+
+/// Check that `pattern` is a valid JavaScript regular expression.
+pub fn check_pattern(pattern: &str) -> Result<(), RegexError> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let error = |position, reason| Err(RegexError { position, reason });
+
+    // Open groups: (position of '(', whether the group may be quantified).
+    let mut groups: Vec<(usize, bool)> = Vec::new();
+    // Whether the previous token is something a quantifier can apply to.
+    let mut can_repeat = false;
+    // Whether the previous token is a quantifier that is not yet lazy.
+    let mut after_quantifier = false;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let quantifier_end = match c {
+            '*' | '+' | '?' => Some(i + 1),
+            '{' => braced_quantifier(&chars, i)?,
+            _ => None,
+        };
+        if let Some(end) = quantifier_end {
+            if c == '?' && after_quantifier {
+                after_quantifier = false;
+            } else if !can_repeat {
+                return error(i, "nothing to repeat");
+            } else {
+                can_repeat = false;
+                after_quantifier = true;
+            }
+            i = end;
+            continue;
+        }
+        after_quantifier = false;
+        match c {
+            '\\' => {
+                let Some(&escaped) = chars.get(i + 1) else {
+                    return error(i, "'\\' at end of pattern");
+                };
+                // Word boundaries are assertions and cannot be repeated.
+                can_repeat = !matches!(escaped, 'b' | 'B');
+                i += 2;
+                continue;
+            }
+            '[' => {
+                i = class_end(&chars, i)?;
+                can_repeat = true;
+                continue;
+            }
+            '(' => {
+                let (len, quantifiable) = group_opening(&chars, i)?;
+                groups.push((i, quantifiable));
+                can_repeat = false;
+                i += len;
+                continue;
+            }
+            ')' => {
+                let Some((_, quantifiable)) = groups.pop() else {
+                    return error(i, "unmatched ')'");
+                };
+                can_repeat = quantifiable;
+            }
+            '|' | '^' | '$' => can_repeat = false,
+            _ => can_repeat = true,
+        }
+        i += 1;
+    }
+    match groups.last() {
+        Some(&(position, _)) => error(position, "unterminated group"),
+        None => Ok(()),
+    }
+}
+
+/// `{n}`, `{n,}` or `{n,m}` starting at `start`: the index just past it, or
+/// `None` when the brace is a literal character.
+fn braced_quantifier(chars: &[char], start: usize) -> Result<Option<usize>, RegexError> {
+    let digits = |from: usize| {
+        let len = chars[from..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let value: String = chars[from..from + len].iter().collect();
+        (len, value.parse::<u64>().unwrap_or(u64::MAX))
+    };
+    let (min_len, min) = digits(start + 1);
+    if min_len == 0 {
+        return Ok(None);
+    }
+    let mut i = start + 1 + min_len;
+    let mut max = Some(min);
+    if chars.get(i) == Some(&',') {
+        let (max_len, value) = digits(i + 1);
+        max = (max_len > 0).then_some(value);
+        i += 1 + max_len;
+    }
+    if chars.get(i) != Some(&'}') {
+        return Ok(None);
+    }
+    if max.is_some_and(|max| max < min) {
+        return Err(RegexError {
+            position: start,
+            reason: "numbers out of order in {} quantifier",
+        });
+    }
+    Ok(Some(i + 1))
+}
+
+/// The character class opening at `start`: the index just past its `]`.
+fn class_end(chars: &[char], start: usize) -> Result<usize, RegexError> {
+    let mut i = start + 1;
+    if chars.get(i) == Some(&'^') {
+        i += 1;
+    }
+    // The previous class atom when it is a single literal character.
+    let mut previous: Option<char> = None;
+    while i < chars.len() {
+        let (atom, len) = match chars[i] {
+            ']' => return Ok(i + 1),
+            '\\' => match chars.get(i + 1) {
+                Some(&escaped) => (class_escape_char(escaped), 2),
+                None => break,
+            },
+            c => (Some(c), 1),
+        };
+        if atom == Some('-')
+            && len == 1
+            && let Some(low) = previous
+            && let Some(&next) = chars.get(i + 1)
+            && next != ']'
+        {
+            let (high, high_len) = if next == '\\' {
+                match chars.get(i + 2) {
+                    Some(&escaped) => (class_escape_char(escaped), 2),
+                    None => break,
+                }
+            } else {
+                (Some(next), 1)
+            };
+            if let Some(high) = high
+                && high < low
+            {
+                return Err(RegexError {
+                    position: i - 1,
+                    reason: "range out of order in character class",
+                });
+            }
+            previous = None;
+            i += 1 + high_len;
+            continue;
+        }
+        previous = atom;
+        i += len;
+    }
+    Err(RegexError {
+        position: start,
+        reason: "unterminated character class",
+    })
+}
+
+/// The character a class escape stands for, or `None` for a class such as
+/// `\d` or an escape that is not a single plain character.
+fn class_escape_char(escaped: char) -> Option<char> {
+    match escaped {
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        'f' => Some('\u{c}'),
+        'v' => Some('\u{b}'),
+        c if c.is_ascii_alphanumeric() => None,
+        c => Some(c),
+    }
+}
+
+/// The group opening at `start`: the length of its opening syntax and
+/// whether the group may be quantified (lookbehinds may not).
+fn group_opening(chars: &[char], start: usize) -> Result<(usize, bool), RegexError> {
+    if chars.get(start + 1) != Some(&'?') {
+        return Ok((1, true));
+    }
+    let invalid = Err(RegexError {
+        position: start,
+        reason: "invalid group",
+    });
+    match (chars.get(start + 2), chars.get(start + 3)) {
+        (Some(':' | '=' | '!'), _) => Ok((3, true)),
+        (Some('<'), Some('=' | '!')) => Ok((4, false)),
+        (Some('<'), _) => {
+            let name_len = chars[start + 3..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '$')
+                .count();
+            let starts_with_digit = chars.get(start + 3).is_some_and(|c| c.is_ascii_digit());
+            if name_len == 0 || starts_with_digit || chars.get(start + 3 + name_len) != Some(&'>') {
+                return Err(RegexError {
+                    position: start,
+                    reason: "invalid capture group name",
+                });
+            }
+            Ok((4 + name_len, true))
+        }
+        _ => invalid,
+    }
+}
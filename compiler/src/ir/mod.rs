@@ -1,5 +1,17 @@
+pub mod analysis;
+pub mod batching;
+pub mod bindings;
+pub mod equivalence;
+pub mod io;
+pub mod js_regex;
+pub mod report;
+pub mod returns;
 pub mod types;
+pub mod url;
 pub mod validate;
 
+pub use report::{
+    CapabilityReport, capability_report, handler_size_warnings, unbalanced_branch_warnings,
+};
 pub use types::*;
-pub use validate::validate_ir;
+pub use validate::{validate_ir, validate_ir_cancellable};
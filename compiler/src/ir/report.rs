@@ -0,0 +1,224 @@
+//! Capability usage of a workflow, for display in the editor.
+//!
+//! CRE caps HTTP calls, EVM reads and EVM writes per execution (E009–E011),
+//! and KV reads and writes together (E018, adjustable per workflow).
+//! The report counts them the same way IR validation does, so the editor can
+//! show "3/5 HTTP calls used" before the limit is hit, and lists the
+//! secrets, chains and config fields the workflow needs.
+//!
+//! [`unbalanced_branch_warnings`] (W013) flags branches whose arms use very
+//! different numbers of capability calls.
+//! [`handler_size_warnings`] (W019) flags handlers large enough that the
+//! compiled workflow is likely to exceed CRE's artifact size limit.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+
+/// CRE capability budget limits per workflow execution.
+pub const MAX_HTTP_CALLS: usize = 5;
+pub const MAX_EVM_READS: usize = 10;
+pub const MAX_EVM_WRITES: usize = 5;
+/// Default for KV reads and writes together; `globalConfig.maxKvOperations`
+/// overrides it.
+pub const MAX_KV_OPERATIONS: usize = 10;
+
+/// Branch arms whose calls of one capability differ by at least this many
+/// are reported as unbalanced (W013).
+pub const UNBALANCED_BRANCH_THRESHOLD: usize = 3;
+
+/// Steps, branch arms included, above which a handler is reported as likely
+/// too large to deploy (W019).
+pub const MAX_HANDLER_STEPS: usize = 200;
+/// Branch nesting above which a handler is reported as likely too large to
+/// deploy (W019).
+pub const MAX_BRANCH_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityReport {
+    /// HTTP requests and AI calls; every AI call is an HTTP request.
+    pub http_calls: CapabilityUsage,
+    pub evm_reads: CapabilityUsage,
+    pub evm_writes: CapabilityUsage,
+    /// KV reads and writes.
+    pub kv_operations: CapabilityUsage,
+    /// AI calls alone, already included in `http_calls`.
+    pub ai_calls: usize,
+    /// Secret names, as declared in `secrets.yaml`.
+    pub secrets: Vec<String>,
+    /// Chain selector names of the EVM chains used.
+    pub chains: Vec<String>,
+    /// Fields of the generated config schema.
+    pub config_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityUsage {
+    pub used: usize,
+    pub limit: usize,
+}
+
+pub fn capability_report(ir: &WorkflowIR) -> CapabilityReport {
+    let counts = count_capabilities(&ir.handler_body);
+    CapabilityReport {
+        http_calls: CapabilityUsage {
+            used: counts.http,
+            limit: MAX_HTTP_CALLS,
+        },
+        evm_reads: CapabilityUsage {
+            used: counts.evm_read,
+            limit: MAX_EVM_READS,
+        },
+        evm_writes: CapabilityUsage {
+            used: counts.evm_write,
+            limit: MAX_EVM_WRITES,
+        },
+        kv_operations: CapabilityUsage {
+            used: counts.kv,
+            limit: kv_operation_limit(ir),
+        },
+        ai_calls: counts.ai,
+        secrets: ir.required_secrets.iter().map(|s| s.name.clone()).collect(),
+        chains: ir
+            .evm_chains
+            .iter()
+            .map(|c| c.chain_selector_name.clone())
+            .collect(),
+        config_fields: ir.config_schema.iter().map(|f| f.name.clone()).collect(),
+    }
+}
+
+/// The workflow's own KV operation limit, or [`MAX_KV_OPERATIONS`].
+pub fn kv_operation_limit(ir: &WorkflowIR) -> usize {
+    ir.metadata.max_kv_operations.unwrap_or(MAX_KV_OPERATIONS)
+}
+
+/// Capability calls on the worst execution path.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct CapabilityCounts {
+    /// Includes AI calls.
+    pub http: usize,
+    pub evm_read: usize,
+    pub evm_write: usize,
+    /// KV reads and writes.
+    pub kv: usize,
+    pub ai: usize,
+}
+
+pub(crate) fn count_capabilities(block: &Block) -> CapabilityCounts {
+    let mut counts = CapabilityCounts::default();
+    for step in &block.steps {
+        match &step.operation {
+            Operation::HttpRequest(_) => counts.http += 1,
+            Operation::AiCall(_) => {
+                counts.http += 1;
+                counts.ai += 1;
+            }
+            Operation::EvmRead(_) => counts.evm_read += 1,
+            Operation::EvmWrite(_) => counts.evm_write += 1,
+            Operation::KvRead(_) | Operation::KvWrite(_) => counts.kv += 1,
+            Operation::Branch(branch) => {
+                // Count the worse branch (both could run in different executions,
+                // but CRE counts per execution so we take the max of each branch)
+                let t = count_capabilities(&branch.true_branch);
+                let f = count_capabilities(&branch.false_branch);
+                counts.http += t.http.max(f.http);
+                counts.evm_read += t.evm_read.max(f.evm_read);
+                counts.evm_write += t.evm_write.max(f.evm_write);
+                counts.kv += t.kv.max(f.kv);
+                counts.ai += t.ai.max(f.ai);
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// W013 for each branch (nested ones included) whose arms differ by at least
+/// [`UNBALANCED_BRANCH_THRESHOLD`] calls of one capability. The budget
+/// already counts the busier arm, so this is only a hint: a lopsided branch
+/// often means steps were wired to the wrong arm.
+pub fn unbalanced_branch_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let mut warnings = Vec::new();
+    collect_unbalanced_branches(&ir.handler_body, &mut warnings);
+    warnings
+}
+
+fn collect_unbalanced_branches(block: &Block, warnings: &mut Vec<CompilerError>) {
+    for step in &block.steps {
+        let Operation::Branch(branch) = &step.operation else {
+            continue;
+        };
+        let t = count_capabilities(&branch.true_branch);
+        let f = count_capabilities(&branch.false_branch);
+        let unbalanced: Vec<String> = [
+            ("HTTP calls", t.http, f.http),
+            ("EVM reads", t.evm_read, f.evm_read),
+            ("EVM writes", t.evm_write, f.evm_write),
+            ("KV operations", t.kv, f.kv),
+        ]
+        .into_iter()
+        .filter(|(_, t, f)| t.abs_diff(*f) >= UNBALANCED_BRANCH_THRESHOLD)
+        .map(|(kind, t, f)| format!("{} {} on the true arm, {} on the false arm", t, kind, f))
+        .collect();
+        if !unbalanced.is_empty() {
+            warnings.push(CompilerError::lower(
+                "W013",
+                format!(
+                    "Branch '{}' is unbalanced ({}) — check that each step is on the intended arm",
+                    step.id,
+                    unbalanced.join("; ")
+                ),
+                Some(step.id.clone()),
+            ));
+        }
+        collect_unbalanced_branches(&branch.true_branch, warnings);
+        collect_unbalanced_branches(&branch.false_branch, warnings);
+    }
+}
+
+/// W019 when the handler has more than [`MAX_HANDLER_STEPS`] steps (branch
+/// arms included) or nests branches more than [`MAX_BRANCH_DEPTH`] deep.
+/// CRE only rejects an oversized artifact at deploy time; step count and
+/// nesting are rough proxies for the size of the generated code, so this
+/// flags the risk early.
+pub fn handler_size_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let mut steps = 0;
+    let mut deepest: Option<(usize, &str)> = None;
+    walk_steps(ir, |step, scope| {
+        steps += 1;
+        if matches!(step.operation, Operation::Branch(_)) {
+            // The scope holds an id and an arm per enclosing branch.
+            let depth = scope.len() / 2 + 1;
+            if deepest.is_none_or(|(max, _)| depth > max) {
+                deepest = Some((depth, &step.id));
+            }
+        }
+    });
+
+    let mut warnings = Vec::new();
+    if steps > MAX_HANDLER_STEPS {
+        warnings.push(CompilerError::lower(
+            "W019",
+            format!(
+                "Handler has {} steps (more than {}) — the compiled workflow may exceed CRE's size limit and fail to deploy; consider splitting it into several workflows",
+                steps, MAX_HANDLER_STEPS
+            ),
+            None,
+        ));
+    }
+    if let Some((depth, branch_id)) = deepest
+        && depth > MAX_BRANCH_DEPTH
+    {
+        warnings.push(CompilerError::lower(
+            "W019",
+            format!(
+                "Branch '{}' is nested {} levels deep (more than {}) — the compiled workflow may exceed CRE's size limit and fail to deploy; consider flattening the conditions",
+                branch_id, depth, MAX_BRANCH_DEPTH
+            ),
+            Some(branch_id.to_string()),
+        ));
+    }
+    warnings
+}
@@ -0,0 +1,179 @@
+//! What the handler returns.
+//!
+//! Codegen annotates the handler with the union of the types its Return
+//! steps yield ([`return_types`]). An early-return Filter returns its message
+//! (normally a string) when the condition fails, so a workflow whose Return steps yield
+//! only objects (or numbers, ...) ends up returning two kinds of value.
+//! [`filter_return_warnings`] (W015) points that out, so callers of the
+//! workflow are not surprised by a bare string.
+
+use std::collections::HashMap;
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+
+/// TypeScript types a handler return can widen to, in annotation order.
+pub const RETURN_TYPE_ORDER: [&str; 6] =
+    ["string", "number", "bigint", "boolean", "object", "null"];
+
+/// The types the handler's `Return` steps yield, in [`RETURN_TYPE_ORDER`].
+///
+/// Returns whose type isn't known statically (trigger refs, `Raw` config
+/// fields, raw expressions, bindings typed `any`) don't constrain it, and
+/// Filters are not counted.
+pub fn return_types(ir: &WorkflowIR) -> Vec<&'static str> {
+    let mut output_types = HashMap::new();
+    collect_output_types(&ir.handler_body, &mut output_types);
+    let types = ReturnTypeScope {
+        output_types,
+        config_schema: &ir.config_schema,
+    };
+
+    let mut found = Vec::new();
+    collect_return_types(&ir.handler_body, &types, &mut found);
+    RETURN_TYPE_ORDER
+        .iter()
+        .filter(|t| found.contains(t))
+        .copied()
+        .collect()
+}
+
+/// Whether some Filter returns its message when its condition fails.
+pub fn has_early_return_filter(block: &Block) -> bool {
+    block.steps.iter().any(|step| match &step.operation {
+        Operation::Filter(FilterOp {
+            non_match_behavior: FilterNonMatchBehavior::EarlyReturn { .. },
+            ..
+        }) => true,
+        Operation::Branch(branch) => {
+            has_early_return_filter(&branch.true_branch)
+                || has_early_return_filter(&branch.false_branch)
+        }
+        _ => false,
+    })
+}
+
+/// W015 for each early-return Filter in a workflow whose typed Return steps
+/// never yield a string.
+pub fn filter_return_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let types = return_types(ir);
+    if types.is_empty() || types.contains(&"string") {
+        return vec![];
+    }
+    let mut warnings = Vec::new();
+    collect_filter_return_warnings(&ir.handler_body, &types.join(" | "), &mut warnings);
+    warnings
+}
+
+fn collect_filter_return_warnings(block: &Block, types: &str, warnings: &mut Vec<CompilerError>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::Filter(FilterOp {
+                non_match_behavior: FilterNonMatchBehavior::EarlyReturn { message },
+                ..
+            }) => {
+                let returned = match message {
+                    ValueExpr::Literal(LiteralValue::String { value }) => {
+                        format!("the string \"{}\"", value)
+                    }
+                    _ => "its non-match message".to_string(),
+                };
+                warnings.push(CompilerError::lower(
+                    "W015",
+                    format!(
+                        "Filter '{}' returns {} when its condition fails, but the workflow's Return steps return {} — the handler returns either",
+                        step.id, returned, types
+                    ),
+                    Some(step.id.clone()),
+                ))
+            }
+            Operation::Branch(branch) => {
+                collect_filter_return_warnings(&branch.true_branch, types, warnings);
+                collect_filter_return_warnings(&branch.false_branch, types, warnings);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// TypeScript type of every step output, by step id.
+pub fn collect_output_types<'a>(block: &'a Block, types: &mut HashMap<&'a str, &'a str>) {
+    for step in &block.steps {
+        if let Some(output) = &step.output {
+            types.insert(step.id.as_str(), output.ts_type.as_str());
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            collect_output_types(&branch.true_branch, types);
+            collect_output_types(&branch.false_branch, types);
+        }
+    }
+}
+
+/// What a Return's expression can be typed from: step outputs and the
+/// config schema.
+struct ReturnTypeScope<'a> {
+    output_types: HashMap<&'a str, &'a str>,
+    config_schema: &'a [ConfigField],
+}
+
+fn collect_return_types(block: &Block, types: &ReturnTypeScope, found: &mut Vec<&'static str>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::Return(ret) => {
+                if let Some(ty) = return_expr_type(&ret.expression, types)
+                    && !found.contains(&ty)
+                {
+                    found.push(ty);
+                }
+            }
+            Operation::Branch(branch) => {
+                collect_return_types(&branch.true_branch, types, found);
+                collect_return_types(&branch.false_branch, types, found);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn return_expr_type(expr: &ValueExpr, types: &ReturnTypeScope) -> Option<&'static str> {
+    match expr {
+        ValueExpr::Literal(LiteralValue::String { .. }) | ValueExpr::Template { .. } => {
+            Some("string")
+        }
+        ValueExpr::Literal(LiteralValue::Number { .. } | LiteralValue::Integer { .. }) => {
+            Some("number")
+        }
+        ValueExpr::Literal(LiteralValue::Boolean { .. }) => Some("boolean"),
+        ValueExpr::Literal(LiteralValue::Null) => Some("null"),
+        ValueExpr::Literal(LiteralValue::Json { .. }) => Some("object"),
+        // Only a whole-output binding has a known type; field paths into it don't.
+        ValueExpr::Binding(b) if b.field_path.is_empty() => {
+            let ty = types.output_types.get(b.step_id.as_str())?;
+            match ty.trim() {
+                "string" => Some("string"),
+                "number" => Some("number"),
+                "bigint" => Some("bigint"),
+                "boolean" => Some("boolean"),
+                t if t.starts_with('{') || t.ends_with("[]") => Some("object"),
+                _ => None,
+            }
+        }
+        ValueExpr::ConfigRef { field } => {
+            let field = types.config_schema.iter().find(|f| &f.name == field)?;
+            match field.zod_type {
+                ZodType::String => Some("string"),
+                ZodType::Number => Some("number"),
+                ZodType::Boolean => Some("boolean"),
+                ZodType::Raw(_) => None,
+            }
+        }
+        // Typed only when both outcomes have the same type.
+        ValueExpr::Conditional {
+            then, otherwise, ..
+        } => {
+            let ty = return_expr_type(then, types)?;
+            (return_expr_type(otherwise, types)? == ty).then_some(ty)
+        }
+        _ => None,
+    }
+}
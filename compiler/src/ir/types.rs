@@ -5,6 +5,11 @@
 //! structured branching that maps directly to a CRE handler function body.
 //! SYNC NOTE: Node/config changes in `shared/model/node.ts` can require IR updates
 //! here (new/changed trigger or operation shapes), plus matching lower/codegen/test updates.
+//!
+//! `ir::io` promises external tools a stable JSON format, so every enum
+//! variant pins its serialized name with an explicit `#[serde(rename)]`:
+//! renaming a variant in Rust must not change its tag. Changing a serialized
+//! name is a format change and needs `io::IR_SCHEMA_VERSION` bumped.
 
 use serde::{Deserialize, Serialize};
 
@@ -33,6 +38,16 @@ pub struct WorkflowIR {
     pub handler_body: Block,
 }
 
+impl WorkflowIR {
+    /// Every step of the handler body, branch arms included, in the order
+    /// [`walk_steps`] visits them.
+    pub fn steps_iter(&self) -> impl Iterator<Item = &Step> {
+        let mut steps = Vec::new();
+        walk_steps(self, |step, _| steps.push(step));
+        steps.into_iter()
+    }
+}
+
 /// A user-defined RPC endpoint for a specific blockchain.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcEntry {
@@ -48,6 +63,10 @@ pub struct WorkflowMetadata {
     pub version: String,
     pub is_testnet: bool,
     pub default_chain_selector: Option<String>,
+    /// KV reads and writes allowed per execution, when the workflow sets
+    /// its own limit (E018). `None` means `MAX_KV_OPERATIONS`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_kv_operations: Option<usize>,
 }
 
 // =============================================================================
@@ -62,14 +81,25 @@ pub struct ConfigField {
     pub zod_type: ZodType,
     pub default_value: Option<String>,
     pub description: Option<String>,
+    /// Step ids that read this field. Filled in by `lower::usage` after lowering.
+    #[serde(default)]
+    pub referenced_by: Vec<String>,
+    /// Every reference sits inside a branch arm, so the field may be left unset
+    /// for runs that never take that path.
+    #[serde(default)]
+    pub optional_in_config: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ZodType {
+    #[serde(rename = "String")]
     String,
+    #[serde(rename = "Number")]
     Number,
+    #[serde(rename = "Boolean")]
     Boolean,
     /// For complex/nested objects, stores the raw Zod expression.
+    #[serde(rename = "Raw")]
     Raw(String),
 }
 
@@ -83,6 +113,64 @@ pub struct SecretDeclaration {
     pub name: String,
     /// Environment variable name for `.env`.
     pub env_variable: String,
+    /// Overrides `env_variable` for staging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_variable_staging: Option<String>,
+    /// Overrides `env_variable` for production.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_variable_production: Option<String>,
+    /// Step ids that read this secret. Filled in by `lower::usage` after lowering.
+    #[serde(default)]
+    pub referenced_by: Vec<String>,
+    /// Every reference sits inside a branch arm, so the secret may be left unset
+    /// for runs that never take that path.
+    #[serde(default)]
+    pub optional_in_config: bool,
+}
+
+impl SecretDeclaration {
+    /// The environment variable holding this secret in `environment`.
+    pub fn env_variable_for(&self, environment: Environment) -> &str {
+        let env_override = match environment {
+            Environment::Staging => &self.env_variable_staging,
+            Environment::Production => &self.env_variable_production,
+        };
+        env_override.as_deref().unwrap_or(&self.env_variable)
+    }
+
+    /// Whether staging and production read the secret from different variables.
+    pub fn varies_by_environment(&self) -> bool {
+        self.env_variable_for(Environment::Staging)
+            != self.env_variable_for(Environment::Production)
+    }
+}
+
+/// A CRE deployment target (`staging-settings` / `production-settings` in
+/// `workflow.yaml`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Staging,
+    Production,
+}
+
+impl Environment {
+    pub const ALL: [Environment; 2] = [Environment::Staging, Environment::Production];
+
+    /// The target a generated bundle is configured for: staging on testnets.
+    pub fn of(metadata: &WorkflowMetadata) -> Self {
+        if metadata.is_testnet {
+            Environment::Staging
+        } else {
+            Environment::Production
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Environment::Staging => "staging",
+            Environment::Production => "production",
+        }
+    }
 }
 
 // =============================================================================
@@ -107,8 +195,11 @@ pub struct EvmChainUsage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum TriggerDef {
+    #[serde(rename = "Cron")]
     Cron(CronTriggerDef),
+    #[serde(rename = "Http")]
     Http(HttpTriggerDef),
+    #[serde(rename = "EvmLog")]
     EvmLog(EvmLogTriggerDef),
 }
 
@@ -144,12 +235,16 @@ pub struct TopicFilter {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TriggerParam {
     /// No second parameter (shouldn't happen in practice).
+    #[serde(rename = "None")]
     None,
     /// `trigger: CronTrigger` — has `scheduledTime`, `actualTime`.
+    #[serde(rename = "CronTrigger")]
     CronTrigger,
     /// `triggerData: HTTPPayload` — has `input: Uint8Array`.
+    #[serde(rename = "HttpRequest")]
     HttpRequest,
     /// `log: EVMLog` — has topics (Uint8Array[]), data (Uint8Array), address (string), blockNumber (bigint), logIndex (number), transactionHash (Uint8Array).
+    #[serde(rename = "EvmLog")]
     EvmLog,
 }
 
@@ -163,40 +258,55 @@ pub enum TriggerParam {
 #[serde(tag = "kind")]
 pub enum ValueExpr {
     /// A literal value: `"hello"`, `42`, `true`.
+    #[serde(rename = "Literal")]
     Literal(LiteralValue),
     /// Reference to a previous step's output binding.
+    #[serde(rename = "Binding")]
     Binding(BindingRef),
     /// Reference to `runtime.config.fieldName`.
+    #[serde(rename = "ConfigRef")]
     ConfigRef { field: String },
     /// Reference to trigger data: `triggerData.fieldName`.
+    #[serde(rename = "TriggerDataRef")]
     TriggerDataRef { field: String },
+    /// A secret's value, `{{secrets.NAME}}`. The handler fetches it with
+    /// `runtime.getSecret`; an HTTP request gets it through its fetch
+    /// function's augmented config. Not allowed in query parameters (L016).
+    #[serde(rename = "SecretRef")]
+    SecretRef { name: String },
     /// Template string with interpolated expressions.
     /// `"https://api.com/${step_x.id}/status"`
+    #[serde(rename = "Template")]
     Template { parts: Vec<TemplatePart> },
     /// Raw TypeScript expression emitted verbatim (escape hatch).
+    #[serde(rename = "RawExpr")]
     RawExpr { expr: String },
+    /// Inline `cond ? then : otherwise`, for picking a value without a
+    /// Branch step.
+    #[serde(rename = "Conditional")]
+    Conditional {
+        cond: Box<ValueExpr>,
+        then: Box<ValueExpr>,
+        otherwise: Box<ValueExpr>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "literal_type")]
 pub enum LiteralValue {
-    String {
-        value: String,
-    },
-    Number {
-        value: f64,
-    },
-    Integer {
-        value: i64,
-    },
-    Boolean {
-        value: bool,
-    },
+    #[serde(rename = "String")]
+    String { value: String },
+    #[serde(rename = "Number")]
+    Number { value: f64 },
+    #[serde(rename = "Integer")]
+    Integer { value: i64 },
+    #[serde(rename = "Boolean")]
+    Boolean { value: bool },
+    #[serde(rename = "Null")]
     Null,
     /// JSON object/array as a string.
-    Json {
-        value: String,
-    },
+    #[serde(rename = "Json")]
+    Json { value: String },
 }
 
 /// Reference to a named output from a previous step.
@@ -204,15 +314,18 @@ pub enum LiteralValue {
 pub struct BindingRef {
     /// The `Step.id` that produced this value.
     pub step_id: String,
-    /// Dot-separated field path. Empty string = the entire value.
-    /// Example: `"body"`, `"eventArgs.from"`, `""`.
+    /// Dot-separated field path. Empty string = the entire value. A numeric
+    /// segment is an array index (`items.0.name` → `items[0].name`).
+    /// Example: `"body"`, `"eventArgs.from"`, `"body.items.length"`, `""`.
     pub field_path: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "part_type")]
 pub enum TemplatePart {
+    #[serde(rename = "Lit")]
     Lit { value: String },
+    #[serde(rename = "Expr")]
     Expr { value: ValueExpr },
 }
 
@@ -254,6 +367,10 @@ impl ValueExpr {
         }
     }
 
+    pub fn secret(name: impl Into<String>) -> Self {
+        ValueExpr::SecretRef { name: name.into() }
+    }
+
     pub fn trigger_data(field: impl Into<String>) -> Self {
         ValueExpr::TriggerDataRef {
             field: field.into(),
@@ -263,6 +380,14 @@ impl ValueExpr {
     pub fn raw(expr: impl Into<String>) -> Self {
         ValueExpr::RawExpr { expr: expr.into() }
     }
+
+    pub fn conditional(cond: ValueExpr, then: ValueExpr, otherwise: ValueExpr) -> Self {
+        ValueExpr::Conditional {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        }
+    }
 }
 
 // =============================================================================
@@ -282,10 +407,35 @@ pub struct OutputBinding {
     pub variable_name: String,
     /// TypeScript type annotation for the binding.
     pub ts_type: String,
-    /// If Some, codegen emits `const { a, b } = expr` instead of `const x = expr`.
+    /// If Some, codegen emits `const { a: step_x_a, b: step_x_b } = expr`
+    /// instead of `const step_x = expr`; see [`OutputBinding::destructured_vars`].
     pub destructure_fields: Option<Vec<String>>,
 }
 
+impl OutputBinding {
+    /// `(field, local name)` for each destructured field. Locals are prefixed
+    /// with `variable_name`, so two steps destructuring the same field do not
+    /// collide.
+    pub fn destructured_vars(&self) -> Vec<(&str, String)> {
+        self.destructure_fields
+            .iter()
+            .flatten()
+            .map(|field| (field.as_str(), format!("{}_{}", self.variable_name, field)))
+            .collect()
+    }
+
+    /// TypeScript object literal rebuilding the destructured value, e.g.
+    /// `{ from: step_x_from, value: step_x_value }`.
+    pub fn destructured_object(&self) -> String {
+        let entries: Vec<String> = self
+            .destructured_vars()
+            .into_iter()
+            .map(|(field, local)| format!("{field}: {local}"))
+            .collect();
+        format!("{{ {} }}", entries.join(", "))
+    }
+}
+
 // =============================================================================
 // BLOCK & STEPS — the execution plan
 // =============================================================================
@@ -300,6 +450,44 @@ pub struct Block {
     pub steps: Vec<Step>,
 }
 
+impl Block {
+    /// Call `f` on every step of this block, branch arms included, in source
+    /// order (a branch step before its true arm, then its false arm). The
+    /// second argument is the step's scope: for each enclosing branch, its
+    /// step id followed by `"true"` or `"false"`, outermost first, so a
+    /// top-level step gets `[]` and a step in the false arm of `if-1` nested
+    /// in the true arm of `if-0` gets `["if-0", "true", "if-1", "false"]`.
+    pub fn walk_steps<'a>(&'a self, f: &mut dyn FnMut(&'a Step, &[&'a str])) {
+        walk_block(self, &mut Vec::new(), f);
+    }
+}
+
+/// [`Block::walk_steps`] over the handler body.
+pub fn walk_steps<'a, F: FnMut(&'a Step, &[&'a str])>(ir: &'a WorkflowIR, mut f: F) {
+    ir.handler_body.walk_steps(&mut f);
+}
+
+fn walk_block<'a>(
+    block: &'a Block,
+    scope: &mut Vec<&'a str>,
+    f: &mut dyn FnMut(&'a Step, &[&'a str]),
+) {
+    for step in &block.steps {
+        f(step, scope);
+        if let Operation::Branch(branch) = &step.operation {
+            let arms = [
+                ("true", &branch.true_branch),
+                ("false", &branch.false_branch),
+            ];
+            for (arm, body) in arms {
+                scope.extend([step.id.as_str(), arm]);
+                walk_block(body, scope, f);
+                scope.truncate(scope.len() - 2);
+            }
+        }
+    }
+}
+
 /// A single step in the execution plan.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Step {
@@ -313,6 +501,9 @@ pub struct Step {
     pub operation: Operation,
     /// The output binding. None for steps that don't produce a value (Log, Return, Error).
     pub output: Option<OutputBinding>,
+    /// The source node's `settings.notes`, emitted as a doc comment and in `audit.json`.
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
 // =============================================================================
@@ -325,28 +516,82 @@ pub struct Step {
 #[serde(tag = "type")]
 pub enum Operation {
     // --- CRE Capability Calls ---
+    #[serde(rename = "HttpRequest")]
     HttpRequest(HttpRequestOp),
+    #[serde(rename = "EvmRead")]
     EvmRead(EvmReadOp),
+    #[serde(rename = "EvmWrite")]
     EvmWrite(EvmWriteOp),
+    #[serde(rename = "KvRead")]
+    KvRead(KvReadOp),
+    #[serde(rename = "KvWrite")]
+    KvWrite(KvWriteOp),
 
     // --- Transforms (inline TypeScript) ---
+    #[serde(rename = "CodeNode")]
     CodeNode(CodeNodeOp),
+    #[serde(rename = "AbiEncode")]
     AbiEncode(AbiEncodeOp),
+    #[serde(rename = "AbiDecode")]
     AbiDecode(AbiDecodeOp),
+    #[serde(rename = "Compute")]
+    Compute(ComputeOp),
 
     // --- Control Flow ---
+    #[serde(rename = "Branch")]
     Branch(BranchOp),
+    #[serde(rename = "Filter")]
     Filter(FilterOp),
+    #[serde(rename = "Merge")]
     Merge(MergeOp),
 
     // --- AI ---
+    #[serde(rename = "AiCall")]
     AiCall(AiCallOp),
 
     // --- Output ---
+    #[serde(rename = "ErrorThrow")]
     ErrorThrow(ErrorThrowOp),
+    #[serde(rename = "Return")]
     Return(ReturnOp),
 }
 
+impl Operation {
+    /// Variant name, as in the serialized `type` tag.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Operation::HttpRequest(_) => "HttpRequest",
+            Operation::EvmRead(_) => "EvmRead",
+            Operation::EvmWrite(_) => "EvmWrite",
+            Operation::KvRead(_) => "KvRead",
+            Operation::KvWrite(_) => "KvWrite",
+            Operation::CodeNode(_) => "CodeNode",
+            Operation::AbiEncode(_) => "AbiEncode",
+            Operation::AbiDecode(_) => "AbiDecode",
+            Operation::Compute(_) => "Compute",
+            Operation::Branch(_) => "Branch",
+            Operation::Filter(_) => "Filter",
+            Operation::Merge(_) => "Merge",
+            Operation::AiCall(_) => "AiCall",
+            Operation::ErrorThrow(_) => "ErrorThrow",
+            Operation::Return(_) => "Return",
+        }
+    }
+
+    /// Whether the step calls a CRE capability (HTTP, EVM, KV or AI).
+    pub fn is_capability_call(&self) -> bool {
+        matches!(
+            self,
+            Operation::HttpRequest(_)
+                | Operation::EvmRead(_)
+                | Operation::EvmWrite(_)
+                | Operation::KvRead(_)
+                | Operation::KvWrite(_)
+                | Operation::AiCall(_)
+        )
+    }
+}
+
 // =============================================================================
 // HTTP REQUEST
 // =============================================================================
@@ -364,52 +609,133 @@ pub struct HttpRequestOp {
     pub timeout_ms: Option<u32>,
     pub expected_status_codes: Vec<u16>,
     pub response_format: HttpResponseFormat,
+    /// Always true in a valid IR: CRE follows redirects and has no option to
+    /// turn that off (N021).
+    #[serde(default = "default_true")]
+    pub follow_redirects: bool,
+    /// Carried for diagnostics only; CRE always verifies TLS, so it is
+    /// rejected on mainnet (N025) and a warning on testnet (W008).
+    #[serde(default)]
+    pub ignore_ssl: bool,
     pub consensus: ConsensusStrategy,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpMethod {
+    #[serde(rename = "Get")]
     Get,
+    #[serde(rename = "Post")]
     Post,
+    #[serde(rename = "Put")]
     Put,
+    #[serde(rename = "Delete")]
     Delete,
+    #[serde(rename = "Patch")]
     Patch,
+    #[serde(rename = "Head")]
     Head,
 }
 
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Head => "HEAD",
+        }
+    }
+
+    /// Methods whose responses may be served from the CRE response cache.
+    /// Caching a POST/PUT/... would replay one write's response for the next.
+    pub fn is_cacheable(&self) -> bool {
+        matches!(self, HttpMethod::Get | HttpMethod::Head)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpBody {
     pub content_type: HttpContentType,
+    /// The body as one value: a JSON value for `Json`, a string otherwise.
+    /// Null when the body is given as `fields`.
     pub data: ValueExpr,
+    /// A `FormUrlEncoded` body written as a JSON object, one value per key.
+    /// Codegen URL-encodes them at run time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<(String, ValueExpr)>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpContentType {
+    #[serde(rename = "Json")]
     Json,
+    #[serde(rename = "FormUrlEncoded")]
     FormUrlEncoded,
+    #[serde(rename = "Raw")]
     Raw,
 }
 
+impl HttpContentType {
+    /// `Content-Type` sent when the request sets none.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            HttpContentType::Json => "application/json",
+            HttpContentType::FormUrlEncoded => "application/x-www-form-urlencoded",
+            HttpContentType::Raw => "text/plain",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpAuth {
     pub token_secret: String,
 }
 
+/// How the fetch function decodes the raw response bytes into the step's `body`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpResponseFormat {
+    /// `body` is the parsed JSON value.
+    #[serde(rename = "Json")]
     Json,
+    /// `body` is the UTF-8 decoded text.
+    #[serde(rename = "Text")]
     Text,
+    /// `body` is the raw bytes as a base64 string (JSON-safe for consensus).
+    #[serde(rename = "Binary")]
     Binary,
 }
 
+impl HttpResponseFormat {
+    /// TypeScript type of `body` in the step's output binding.
+    pub fn body_ts_type(&self) -> &'static str {
+        match self {
+            HttpResponseFormat::Json => "any",
+            HttpResponseFormat::Text | HttpResponseFormat::Binary => "string",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ConsensusStrategy {
     /// `consensusIdenticalAggregation<T>()`
+    #[serde(rename = "Identical")]
     Identical,
     /// `ConsensusAggregationByFields<T>({ field: median, ... })`
+    #[serde(rename = "MedianByFields")]
     MedianByFields { fields: Vec<String> },
+    /// `ConsensusAggregationByFields<T>({ field: identical, ... })`: nodes
+    /// agree on these top-level fields and may differ on the rest.
+    #[serde(rename = "IdenticalFields")]
+    IdenticalFields { fields: Vec<String> },
     /// Custom consensus expression (escape hatch).
+    #[serde(rename = "Custom")]
     Custom { expr: String },
 }
 
@@ -417,6 +743,9 @@ pub enum ConsensusStrategy {
 // EVM READ
 // =============================================================================
 
+/// Block tags an EVM read accepts as `blockNumber`, besides a number.
+pub const EVM_BLOCK_TAGS: [&str; 2] = ["latest", "finalized"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvmReadOp {
     /// References `EvmChainUsage.binding_name`.
@@ -426,6 +755,8 @@ pub struct EvmReadOp {
     pub abi_json: String,
     pub args: Vec<EvmArg>,
     pub from_address: Option<ValueExpr>,
+    /// Block to read at: one of [`EVM_BLOCK_TAGS`] as a string literal, or a
+    /// block number. `None` reads at the capability's default (latest).
     pub block_number: Option<ValueExpr>,
     /// Normalized output field names from ABI outputs, matching frontend `normalizeReadResult`.
     /// Single unnamed output → ["value"], single named → ["name"], multiple → ["name_or_outputN", ...]
@@ -453,6 +784,24 @@ pub struct EvmWriteOp {
     pub value_wei: Option<ValueExpr>,
 }
 
+// =============================================================================
+// KEY-VALUE STATE
+// =============================================================================
+
+/// Read the value stored under `key` by an earlier execution. The output is
+/// `string | undefined`: `undefined` until a KvWrite has stored the key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvReadOp {
+    pub key: ValueExpr,
+}
+
+/// Store `value` (as a string) under `key` for later executions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvWriteOp {
+    pub key: ValueExpr,
+    pub value: ValueExpr,
+}
+
 // =============================================================================
 // CODE NODE
 // =============================================================================
@@ -468,12 +817,28 @@ pub struct EvmWriteOp {
 ///   // --- end user code ---
 /// })();
 /// ```
+///
+/// In `RunOnceForEach` mode the user code is the body of a `.map()` over the
+/// `iterate_over` input, which it sees one item at a time:
+/// ```typescript
+/// const step_code_1 = (() => {
+///   const rate = config.rate;
+///   const prices = step_http_1.body;
+///   return prices.map((prices) => {
+///     ${user_code}
+///   });
+/// })();
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeNodeOp {
     pub code: String,
     /// Bindings injected as `const` declarations before user code.
     pub input_bindings: Vec<CodeInputBinding>,
     pub execution_mode: CodeExecutionMode,
+    /// `variable_name` of the input binding mapped over in `RunOnceForEach`
+    /// mode. Without one the node runs once, as in `RunOnceForAll`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterate_over: Option<String>,
     pub timeout_ms: Option<u32>,
     /// Output field keys declared by the user; used to generate the return statement.
     pub output_fields: Vec<String>,
@@ -489,7 +854,9 @@ pub struct CodeInputBinding {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CodeExecutionMode {
+    #[serde(rename = "RunOnceForAll")]
     RunOnceForAll,
+    #[serde(rename = "RunOnceForEach")]
     RunOnceForEach,
 }
 
@@ -510,6 +877,10 @@ pub struct AbiEncodeOp {
 pub struct AbiDataMapping {
     pub param_name: String,
     pub value: ValueExpr,
+    /// Set when `value` is already a `bigint`, so an `int*`/`uint*`
+    /// parameter takes it without `BigInt(...)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_kind: Option<ValueKind>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -519,6 +890,62 @@ pub struct AbiDecodeOp {
     pub output_names: Vec<String>,
 }
 
+// =============================================================================
+// COMPUTE (math node)
+// =============================================================================
+
+/// Inline arithmetic: `const step_x = <expr>;`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComputeOp {
+    pub expr: ComputeExpr,
+    pub mode: NumericMode,
+}
+
+/// Arithmetic AST parsed from a math node's expression string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum ComputeExpr {
+    /// Numeric literal as written, e.g. `"2.5"`. In BigInt mode lowering
+    /// normalizes it to plain integer digits (`"1e18"` → `"1000000000000000000"`).
+    #[serde(rename = "Literal")]
+    Literal { value: String },
+    /// A `{{ref}}` operand.
+    #[serde(rename = "Value")]
+    Value { value: ValueExpr },
+    /// Unary minus.
+    #[serde(rename = "Neg")]
+    Neg { operand: Box<ComputeExpr> },
+    #[serde(rename = "Binary")]
+    Binary {
+        op: ArithOp,
+        left: Box<ComputeExpr>,
+        right: Box<ComputeExpr>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArithOp {
+    #[serde(rename = "Add")]
+    Add,
+    #[serde(rename = "Sub")]
+    Sub,
+    #[serde(rename = "Mul")]
+    Mul,
+    #[serde(rename = "Div")]
+    Div,
+    #[serde(rename = "Mod")]
+    Mod,
+}
+
+/// How operands are coerced: `Number(x)` or `BigInt(x)` (integer-only, exact).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumericMode {
+    #[serde(rename = "Number")]
+    Number,
+    #[serde(rename = "BigInt")]
+    BigInt,
+}
+
 // =============================================================================
 // BRANCH (if node)
 // =============================================================================
@@ -526,15 +953,58 @@ pub struct AbiDecodeOp {
 /// Conditional branch: `if (condition) { ... } else { ... }`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BranchOp {
-    pub conditions: Vec<ConditionIR>,
+    /// Combined with `combine_with`; each entry may itself be a group.
+    pub conditions: Vec<ConditionTree>,
     pub combine_with: LogicCombinator,
     pub true_branch: Block,
     pub false_branch: Block,
+    /// Name of each arm, from the label of the If node's outgoing edge.
+    /// Emitted as a comment at the top of the arm.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub true_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub false_label: Option<String>,
     /// Step ID of the Merge that reconverges these branches.
     /// None if both branches terminate independently (both return/error).
     pub reconverge_at: Option<String>,
 }
 
+/// A condition or a parenthesized group of them. Serialized untagged, so a
+/// leaf is a plain `ConditionIR` object and a group is
+/// `{ "combinator": ..., "children": [...] }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConditionTree {
+    Group {
+        combinator: LogicCombinator,
+        children: Vec<ConditionTree>,
+    },
+    Leaf(ConditionIR),
+}
+
+impl ConditionTree {
+    /// Every leaf condition, depth first.
+    pub fn leaves(&self) -> Vec<&ConditionIR> {
+        match self {
+            ConditionTree::Group { children, .. } => {
+                children.iter().flat_map(ConditionTree::leaves).collect()
+            }
+            ConditionTree::Leaf(cond) => vec![cond],
+        }
+    }
+
+    /// Every leaf condition, depth first, mutably.
+    pub fn leaves_mut(&mut self) -> Vec<&mut ConditionIR> {
+        match self {
+            ConditionTree::Group { children, .. } => children
+                .iter_mut()
+                .flat_map(ConditionTree::leaves_mut)
+                .collect(),
+            ConditionTree::Leaf(cond) => vec![cond],
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConditionIR {
     /// Left-hand side: the value to test.
@@ -542,31 +1012,93 @@ pub struct ConditionIR {
     pub operator: ComparisonOp,
     /// Right-hand side. None for unary operators (Exists, IsEmpty).
     pub value: Option<ValueExpr>,
+    /// What lowering knows about `field` at runtime. When one side of a
+    /// numeric comparison is a `bigint`, codegen converts a literal or
+    /// config operand on the other side with `BigInt(...)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field_kind: Option<ValueKind>,
+    /// What lowering knows about `value` at runtime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_kind: Option<ValueKind>,
+}
+
+/// Runtime type of a value, where lowering can tell and codegen needs it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueKind {
+    /// An `int*`/`uint*` ABI value wider than 48 bits, which viem decodes as
+    /// a `bigint`, or the result of BigInt math.
+    #[serde(rename = "BigInt")]
+    BigInt,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ComparisonOp {
+    #[serde(rename = "Equals")]
     Equals,
+    #[serde(rename = "NotEquals")]
     NotEquals,
+    #[serde(rename = "Gt")]
     Gt,
+    #[serde(rename = "Gte")]
     Gte,
+    #[serde(rename = "Lt")]
     Lt,
+    #[serde(rename = "Lte")]
     Lte,
+    #[serde(rename = "Contains")]
     Contains,
+    #[serde(rename = "NotContains")]
     NotContains,
+    #[serde(rename = "StartsWith")]
     StartsWith,
+    #[serde(rename = "EndsWith")]
     EndsWith,
+    #[serde(rename = "Regex")]
     Regex,
+    #[serde(rename = "NotRegex")]
     NotRegex,
+    #[serde(rename = "Exists")]
     Exists,
+    #[serde(rename = "NotExists")]
     NotExists,
+    #[serde(rename = "IsEmpty")]
     IsEmpty,
+    #[serde(rename = "IsNotEmpty")]
     IsNotEmpty,
 }
 
+impl ComparisonOp {
+    /// Operators that compare magnitudes or identity, where a `bigint` and
+    /// a string or number never match without conversion.
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            ComparisonOp::Equals
+                | ComparisonOp::NotEquals
+                | ComparisonOp::Gt
+                | ComparisonOp::Gte
+                | ComparisonOp::Lt
+                | ComparisonOp::Lte
+        )
+    }
+
+    /// Operators that only inspect the left-hand side (`value` is `None`).
+    pub fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            ComparisonOp::Exists
+                | ComparisonOp::NotExists
+                | ComparisonOp::IsEmpty
+                | ComparisonOp::IsNotEmpty
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LogicCombinator {
+    #[serde(rename = "And")]
     And,
+    #[serde(rename = "Or")]
     Or,
 }
 
@@ -577,7 +1109,7 @@ pub enum LogicCombinator {
 /// Guard clause. Does NOT fork — either continues or early-returns.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterOp {
-    pub conditions: Vec<ConditionIR>,
+    pub conditions: Vec<ConditionTree>,
     pub combine_with: LogicCombinator,
     pub non_match_behavior: FilterNonMatchBehavior,
 }
@@ -585,9 +1117,11 @@ pub struct FilterOp {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum FilterNonMatchBehavior {
-    /// `if (!condition) { return "msg" }`
-    EarlyReturn { message: String },
+    /// `if (!condition) { return <message> }`
+    #[serde(rename = "EarlyReturn")]
+    EarlyReturn { message: ValueExpr },
     /// Wraps remaining steps in `if (condition) { ... }`.
+    #[serde(rename = "Skip")]
     Skip,
 }
 
@@ -595,14 +1129,16 @@ pub enum FilterNonMatchBehavior {
 // MERGE
 // =============================================================================
 
-/// Reconvergence point after a Branch.
+/// Reconvergence point after a Branch, or a fan-in of independent chains.
 ///
 /// For diamond patterns, codegen declares a `let` variable before the if/else
 /// and each branch assigns to it. The Merge step's output binding IS that variable.
+/// A merge not tied to a Branch combines its inputs per `strategy` instead.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MergeOp {
-    /// The Branch step this reconverges from.
-    pub branch_step_id: String,
+    /// The Branch step this reconverges from; `None` for a standalone merge.
+    #[serde(default)]
+    pub branch_step_id: Option<String>,
     pub strategy: MergeStrategy,
     pub inputs: Vec<MergeInput>,
 }
@@ -617,10 +1153,13 @@ pub struct MergeInput {
 #[serde(tag = "type")]
 pub enum MergeStrategy {
     /// Use whichever branch was taken. Codegen uses `let` + assign in each branch.
+    #[serde(rename = "PassThrough")]
     PassThrough,
-    /// Append all inputs into an array.
+    /// Append all inputs into an array, in input order.
+    #[serde(rename = "Append")]
     Append,
-    /// Custom merge expression.
+    /// Custom merge code; it sees the inputs as the array `inputs`.
+    #[serde(rename = "Custom")]
     Custom { expr: String },
 }
 
@@ -632,7 +1171,7 @@ pub enum MergeStrategy {
 /// provider-specific prompt formatting and response parsing.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiCallOp {
-    pub provider: String,
+    pub provider: AiProvider,
     pub base_url: ValueExpr,
     pub model: ValueExpr,
     pub api_key_secret: String,
@@ -646,10 +1185,117 @@ pub struct AiCallOp {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AiResponseFormat {
+    #[serde(rename = "Text")]
     Text,
+    #[serde(rename = "Json")]
     Json,
 }
 
+/// AI API family. Decides the request body shape, auth headers and where the
+/// generated text sits in the response; everything provider-specific lives on
+/// this enum so codegen and validation can't disagree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AiProvider {
+    #[serde(rename = "OpenAi")]
+    OpenAi,
+    #[serde(rename = "Anthropic")]
+    Anthropic,
+    #[serde(rename = "Google")]
+    Google,
+    /// Any API speaking the OpenAI chat-completions format (requires a base URL).
+    #[serde(rename = "OpenAiCompatible")]
+    OpenAiCompatible { label: String },
+}
+
+/// Value of an AI request header, rendered by codegen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiHeaderValue {
+    /// The raw API key.
+    ApiKey,
+    /// `Bearer <API key>`.
+    BearerApiKey,
+    Fixed(&'static str),
+}
+
+impl AiProvider {
+    /// Canonical provider names accepted by `parse` (aliases aside).
+    pub const SUPPORTED: [&'static str; 4] = ["openai", "anthropic", "google", "openai-compatible"];
+
+    /// Case-insensitive lookup of a frontend provider string, including aliases
+    /// ("gpt", "chatgpt", "claude", "gemini"). `None` for anything unrecognized.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let trimmed = raw.trim();
+        match trimmed.to_ascii_lowercase().as_str() {
+            "openai" | "gpt" | "chatgpt" => Some(AiProvider::OpenAi),
+            "anthropic" | "claude" => Some(AiProvider::Anthropic),
+            "google" | "gemini" => Some(AiProvider::Google),
+            "openai-compatible" | "openai_compatible" | "openaicompatible" | "custom" => {
+                Some(AiProvider::OpenAiCompatible {
+                    label: trimmed.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Model used when the node leaves `model` empty.
+    pub fn default_model(&self) -> Option<&'static str> {
+        match self {
+            AiProvider::OpenAi => Some("gpt-5-mini"),
+            AiProvider::Anthropic => Some("claude-sonnet-4-6"),
+            AiProvider::Google => Some("gemini-2.5-flash-lite"),
+            AiProvider::OpenAiCompatible { .. } => None,
+        }
+    }
+
+    /// Endpoint used when the node leaves `base_url` empty. Google's endpoint
+    /// embeds the model name, so it needs a literal `model`.
+    pub fn default_base_url(&self, model: &str) -> Option<String> {
+        match self {
+            AiProvider::OpenAi => Some("https://api.openai.com/v1/chat/completions".into()),
+            AiProvider::Anthropic => Some("https://api.anthropic.com/v1/messages".into()),
+            AiProvider::Google if !model.is_empty() && !model.contains("{{") => Some(format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent"
+            )),
+            AiProvider::Google | AiProvider::OpenAiCompatible { .. } => None,
+        }
+    }
+
+    /// Headers (besides `Content-Type`) every request to this provider needs.
+    pub fn auth_headers(&self) -> &'static [(&'static str, AiHeaderValue)] {
+        match self {
+            AiProvider::OpenAi | AiProvider::OpenAiCompatible { .. } => {
+                &[("Authorization", AiHeaderValue::BearerApiKey)]
+            }
+            AiProvider::Anthropic => &[
+                ("x-api-key", AiHeaderValue::ApiKey),
+                ("anthropic-version", AiHeaderValue::Fixed("2023-06-01")),
+            ],
+            AiProvider::Google => &[("x-goog-api-key", AiHeaderValue::ApiKey)],
+        }
+    }
+
+    /// Path of the generated text in the parsed response body. Must match
+    /// `AI_OUTPUT_SCHEMA` in `shared/listAIProviders.ts`.
+    pub fn response_text_path(&self) -> &'static str {
+        match self {
+            AiProvider::OpenAi | AiProvider::OpenAiCompatible { .. } => {
+                "choices[0].message.content"
+            }
+            AiProvider::Anthropic => "content[0].text",
+            AiProvider::Google => "candidates[0].content.parts[0].text",
+        }
+    }
+
+    /// Top-level response field holding the generated content. Default AI
+    /// consensus compares only this field, since ids, timestamps and token
+    /// usage differ from node to node.
+    pub fn content_field(&self) -> &'static str {
+        let path = self.response_text_path();
+        &path[..path.find(['.', '[']).unwrap_or(path.len())]
+    }
+}
+
 // =============================================================================
 // OUTPUT / SIDE EFFECTS
 // =============================================================================
@@ -664,4 +1310,8 @@ pub struct ErrorThrowOp {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReturnOp {
     pub expression: ValueExpr,
+    /// Synthesized by lowering for a path without an explicit Return node
+    /// (see `AutoReturnMode`), not authored by the user.
+    #[serde(default)]
+    pub auto_generated: bool,
 }
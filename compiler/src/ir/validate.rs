@@ -3,14 +3,17 @@
 //! Validates that a `WorkflowIR` satisfies all structural, binding, semantic,
 //! and control flow invariants before codegen.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use crate::cancel::CancellationToken;
+use crate::error::{CompilerError, Phase, RelatedDiagnostic};
+use crate::ir::batching::batching_suggestions;
+use crate::ir::js_regex;
+use crate::ir::report::{
+    MAX_EVM_READS, MAX_EVM_WRITES, MAX_HTTP_CALLS, count_capabilities, kv_operation_limit,
+};
 use crate::ir::types::*;
-
-/// CRE capability budget limits per workflow execution.
-const MAX_HTTP_CALLS: usize = 5;
-const MAX_EVM_READS: usize = 10;
-const MAX_EVM_WRITES: usize = 5;
+use crate::ir::url;
 
 #[derive(Debug, Clone)]
 pub struct ValidationError {
@@ -18,6 +21,8 @@ pub struct ValidationError {
     pub message: String,
     /// The step ID where the error was found, if applicable.
     pub step_id: Option<String>,
+    /// Follow-up notes carried over to `CompilerError::related`.
+    pub related: Vec<RelatedDiagnostic>,
 }
 
 impl std::fmt::Display for ValidationError {
@@ -31,18 +36,47 @@ impl std::fmt::Display for ValidationError {
 
 /// Validate a WorkflowIR against all invariants. Returns all errors found.
 pub fn validate_ir(ir: &WorkflowIR) -> Vec<ValidationError> {
-    let mut errors = Vec::new();
+    validate_ir_checked(ir, &mut || Ok(())).expect("validation without a token is never cancelled")
+}
+
+/// [`validate_ir`], stopping with X001 between invariants once `cancel` is
+/// cancelled.
+pub fn validate_ir_cancellable(
+    ir: &WorkflowIR,
+    cancel: &CancellationToken,
+) -> Result<Vec<ValidationError>, Vec<CompilerError>> {
+    validate_ir_checked(ir, &mut || cancel.check(Phase::IrValidate))
+}
 
-    validate_handler_body_non_empty(ir, &mut errors);
-    validate_unique_step_ids(ir, &mut errors);
-    validate_forward_bindings(ir, &mut errors);
-    validate_branch_merge_consistency(ir, &mut errors);
-    validate_secret_refs(ir, &mut errors);
-    validate_evm_chain_refs(ir, &mut errors);
-    validate_cre_budget(ir, &mut errors);
-    validate_return_paths(ir, &mut errors);
+type Invariant<'a> = &'a dyn Fn(&mut Vec<ValidationError>);
 
-    errors
+fn validate_ir_checked(
+    ir: &WorkflowIR,
+    check: &mut dyn FnMut() -> Result<(), Vec<CompilerError>>,
+) -> Result<Vec<ValidationError>, Vec<CompilerError>> {
+    let mut errors = Vec::new();
+    let invariants: [Invariant; 15] = [
+        &|errors| validate_handler_body_non_empty(ir, errors),
+        &|errors| validate_unique_step_ids(ir, errors),
+        &|errors| validate_forward_bindings(ir, errors),
+        &|errors| validate_branch_merge_consistency(ir, errors),
+        &|errors| validate_standalone_merges(&ir.handler_body, errors),
+        &|errors| validate_secret_refs(ir, errors),
+        &|errors| validate_evm_chain_refs(ir, errors),
+        &|errors| validate_trigger_chain(ir, errors),
+        &|errors| validate_cre_budget(ir, errors),
+        &|errors| validate_return_paths(ir, errors),
+        &|errors| validate_json_literals(&ir.handler_body, errors),
+        &|errors| validate_http_urls(&ir.handler_body, errors),
+        &|errors| validate_regex_patterns(&ir.handler_body, errors),
+        &|errors| validate_abi_function_names(&ir.handler_body, errors),
+        &|errors| validate_destructured_names(&ir.handler_body, &HashMap::new(), errors),
+    ];
+    for invariant in invariants {
+        check()?;
+        invariant(&mut errors);
+    }
+    Ok(errors)
 }
 
 // ---------------------------------------------------------------------------
@@ -55,6 +89,7 @@ fn validate_handler_body_non_empty(ir: &WorkflowIR, errors: &mut Vec<ValidationE
             code: "E001",
             message: "Handler body must contain at least one step".into(),
             step_id: None,
+            related: vec![],
         });
     }
 }
@@ -65,24 +100,16 @@ fn validate_handler_body_non_empty(ir: &WorkflowIR, errors: &mut Vec<ValidationE
 
 fn validate_unique_step_ids(ir: &WorkflowIR, errors: &mut Vec<ValidationError>) {
     let mut seen = HashSet::new();
-    collect_step_ids(&ir.handler_body, &mut seen, errors);
-}
-
-fn collect_step_ids(block: &Block, seen: &mut HashSet<String>, errors: &mut Vec<ValidationError>) {
-    for step in &block.steps {
-        if !seen.insert(step.id.clone()) {
+    walk_steps(ir, |step, _| {
+        if !seen.insert(step.id.as_str()) {
             errors.push(ValidationError {
                 code: "E002",
                 message: format!("Duplicate step ID '{}'", step.id),
                 step_id: Some(step.id.clone()),
+                related: vec![],
             });
         }
-        // Recurse into branch blocks
-        if let Operation::Branch(branch) = &step.operation {
-            collect_step_ids(&branch.true_branch, seen, errors);
-            collect_step_ids(&branch.false_branch, seen, errors);
-        }
-    }
+    });
 }
 
 // ---------------------------------------------------------------------------
@@ -101,10 +128,28 @@ fn validate_block_bindings(
 ) {
     // Snapshot the scope at block entry so we can restore after branches
     let mut scope = parent_scope.clone();
+    // Scopes at the end of each arm of the last reconverging branch, keyed by
+    // its merge step id, so the merge's inputs can be checked per arm.
+    let mut arm_scopes: Option<(&str, HashSet<String>, HashSet<String>)> = None;
 
     for step in &block.steps {
+        // Merge inputs named after an arm must resolve in that arm's scope
+        let merge_arms = match (&step.operation, arm_scopes.take()) {
+            (Operation::Merge(merge), Some((merge_id, true_scope, false_scope)))
+                if merge_id == step.id =>
+            {
+                validate_merge_input_scopes(step, merge, &scope, &true_scope, &false_scope, errors);
+                true
+            }
+            _ => false,
+        };
+
         // Check all ValueExpr refs in this step point to bindings in scope
-        let refs = collect_binding_refs_from_step(step);
+        let refs = if merge_arms {
+            Vec::new()
+        } else {
+            collect_binding_refs_from_step(step)
+        };
         for binding_ref in &refs {
             if !scope.contains(&binding_ref.step_id) {
                 errors.push(ValidationError {
@@ -115,6 +160,7 @@ fn validate_block_bindings(
                         step.id, binding_ref.step_id
                     ),
                     step_id: Some(step.id.clone()),
+                    related: vec![],
                 });
             }
         }
@@ -126,6 +172,10 @@ fn validate_block_bindings(
 
             let mut false_scope = scope.clone();
             validate_block_bindings(&branch.false_branch, &mut false_scope, errors);
+
+            if let Some(merge_id) = &branch.reconverge_at {
+                arm_scopes = Some((merge_id.as_str(), true_scope, false_scope));
+            }
         }
 
         // Add this step's output to the scope AFTER checking refs
@@ -140,8 +190,49 @@ fn validate_block_bindings(
     *parent_scope = scope;
 }
 
-/// Extract all BindingRefs from a step's operation.
-fn collect_binding_refs_from_step(step: &Step) -> Vec<BindingRef> {
+/// Check a merge's inputs against the arm they come from: the `true` / `false`
+/// handles see bindings produced in that arm of the branch; any other handle
+/// only sees the scope shared by both arms.
+fn validate_merge_input_scopes(
+    step: &Step,
+    merge: &MergeOp,
+    shared_scope: &HashSet<String>,
+    true_scope: &HashSet<String>,
+    false_scope: &HashSet<String>,
+    errors: &mut Vec<ValidationError>,
+) {
+    for input in &merge.inputs {
+        let (arm_scope, arm) = match input.handle_name.as_str() {
+            "true" => (true_scope, "true arm"),
+            "false" => (false_scope, "false arm"),
+            _ => (shared_scope, "scope shared by both arms"),
+        };
+        let mut refs = Vec::new();
+        collect_binding_refs_from_value_expr(&input.value, &mut refs);
+        for binding_ref in refs {
+            if !arm_scope.contains(&binding_ref.step_id) {
+                errors.push(ValidationError {
+                    code: "E013",
+                    message: format!(
+                        "Merge '{}' input '{}' references binding '{}', which is not \
+                         available in the {} of branch '{}'",
+                        step.id,
+                        input.handle_name,
+                        binding_ref.step_id,
+                        arm,
+                        merge.branch_step_id.as_deref().unwrap_or_default()
+                    ),
+                    step_id: Some(step.id.clone()),
+                    related: vec![],
+                });
+            }
+        }
+    }
+}
+
+/// Extract all BindingRefs from a step's operation (not including nested
+/// branch arms).
+pub fn collect_binding_refs_from_step(step: &Step) -> Vec<BindingRef> {
     let mut refs = Vec::new();
     collect_binding_refs_from_operation(&step.operation, &mut refs);
     refs
@@ -157,93 +248,332 @@ fn collect_binding_refs_from_value_expr(expr: &ValueExpr, refs: &mut Vec<Binding
                 }
             }
         }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                collect_binding_refs_from_value_expr(value, refs);
+            }
+        }
         ValueExpr::Literal(_)
         | ValueExpr::ConfigRef { .. }
         | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
         | ValueExpr::RawExpr { .. } => {}
     }
 }
 
+fn visit_compute_value_exprs(expr: &ComputeExpr, f: &mut dyn FnMut(&ValueExpr)) {
+    match expr {
+        ComputeExpr::Literal { .. } => {}
+        ComputeExpr::Value { value } => f(value),
+        ComputeExpr::Neg { operand } => visit_compute_value_exprs(operand, f),
+        ComputeExpr::Binary { left, right, .. } => {
+            visit_compute_value_exprs(left, f);
+            visit_compute_value_exprs(right, f);
+        }
+    }
+}
+
 fn collect_binding_refs_from_operation(op: &Operation, refs: &mut Vec<BindingRef>) {
+    visit_operation_value_exprs(op, &mut |expr| {
+        collect_binding_refs_from_value_expr(expr, refs)
+    });
+}
+
+/// Config field names read directly by `step` (not including nested branch arms).
+pub fn collect_config_refs_from_step(step: &Step) -> Vec<String> {
+    let mut fields = Vec::new();
+    visit_operation_value_exprs(&step.operation, &mut |expr| {
+        collect_config_refs_from_value_expr(expr, &mut fields)
+    });
+    fields
+}
+
+fn collect_config_refs_from_value_expr(expr: &ValueExpr, fields: &mut Vec<String>) {
+    match expr {
+        ValueExpr::ConfigRef { field } => fields.push(field.clone()),
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    collect_config_refs_from_value_expr(value, fields);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                collect_config_refs_from_value_expr(value, fields);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
+/// Trigger data fields read directly by `step` (not including nested branch arms).
+pub fn collect_trigger_data_refs_from_step(step: &Step) -> Vec<String> {
+    let mut fields = Vec::new();
+    visit_operation_value_exprs(&step.operation, &mut |expr| {
+        collect_trigger_data_refs_from_value_expr(expr, &mut fields)
+    });
+    fields
+}
+
+fn collect_trigger_data_refs_from_value_expr(expr: &ValueExpr, fields: &mut Vec<String>) {
+    match expr {
+        ValueExpr::TriggerDataRef { field } => fields.push(field.clone()),
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    collect_trigger_data_refs_from_value_expr(value, fields);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                collect_trigger_data_refs_from_value_expr(value, fields);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
+/// Call `f` on every `ValueExpr` the operation reads directly (including the
+/// operands of a `Compute` expression). Branch arms are not visited — callers
+/// walk nested blocks themselves so they can track scope.
+fn visit_operation_value_exprs(op: &Operation, f: &mut dyn FnMut(&ValueExpr)) {
     match op {
         Operation::HttpRequest(o) => {
-            collect_binding_refs_from_value_expr(&o.url, refs);
+            f(&o.url);
             for (_, v) in &o.headers {
-                collect_binding_refs_from_value_expr(v, refs);
+                f(v);
             }
             for (_, v) in &o.query_params {
-                collect_binding_refs_from_value_expr(v, refs);
+                f(v);
             }
             if let Some(body) = &o.body {
-                collect_binding_refs_from_value_expr(&body.data, refs);
+                f(&body.data);
+                for (_, v) in &body.fields {
+                    f(v);
+                }
             }
         }
         Operation::EvmRead(o) => {
-            collect_binding_refs_from_value_expr(&o.contract_address, refs);
+            f(&o.contract_address);
             for arg in &o.args {
-                collect_binding_refs_from_value_expr(&arg.value, refs);
+                f(&arg.value);
             }
             if let Some(v) = &o.from_address {
-                collect_binding_refs_from_value_expr(v, refs);
+                f(v);
             }
             if let Some(v) = &o.block_number {
-                collect_binding_refs_from_value_expr(v, refs);
+                f(v);
             }
         }
         Operation::EvmWrite(o) => {
-            collect_binding_refs_from_value_expr(&o.receiver_address, refs);
-            collect_binding_refs_from_value_expr(&o.gas_limit, refs);
-            collect_binding_refs_from_value_expr(&o.encoded_data, refs);
+            f(&o.receiver_address);
+            f(&o.gas_limit);
+            f(&o.encoded_data);
             if let Some(v) = &o.value_wei {
-                collect_binding_refs_from_value_expr(v, refs);
+                f(v);
             }
         }
+        Operation::KvRead(o) => {
+            f(&o.key);
+        }
+        Operation::KvWrite(o) => {
+            f(&o.key);
+            f(&o.value);
+        }
         Operation::CodeNode(o) => {
             for binding in &o.input_bindings {
-                collect_binding_refs_from_value_expr(&binding.value, refs);
+                f(&binding.value);
             }
         }
         Operation::AbiEncode(o) => {
             for mapping in &o.data_mappings {
-                collect_binding_refs_from_value_expr(&mapping.value, refs);
+                f(&mapping.value);
             }
         }
         Operation::AbiDecode(o) => {
-            collect_binding_refs_from_value_expr(&o.input, refs);
+            f(&o.input);
+        }
+        Operation::Compute(o) => {
+            visit_compute_value_exprs(&o.expr, f);
         }
         Operation::Branch(o) => {
-            for cond in &o.conditions {
-                collect_binding_refs_from_value_expr(&cond.field, refs);
+            for cond in o.conditions.iter().flat_map(ConditionTree::leaves) {
+                f(&cond.field);
                 if let Some(v) = &cond.value {
-                    collect_binding_refs_from_value_expr(v, refs);
+                    f(v);
                 }
             }
             // Don't recurse into branch blocks here — handled separately
         }
         Operation::Filter(o) => {
-            for cond in &o.conditions {
-                collect_binding_refs_from_value_expr(&cond.field, refs);
+            for cond in o.conditions.iter().flat_map(ConditionTree::leaves) {
+                f(&cond.field);
                 if let Some(v) = &cond.value {
-                    collect_binding_refs_from_value_expr(v, refs);
+                    f(v);
                 }
             }
+            if let FilterNonMatchBehavior::EarlyReturn { message } = &o.non_match_behavior {
+                f(message);
+            }
         }
         Operation::Merge(o) => {
             for input in &o.inputs {
-                collect_binding_refs_from_value_expr(&input.value, refs);
+                f(&input.value);
             }
         }
         Operation::AiCall(o) => {
-            collect_binding_refs_from_value_expr(&o.base_url, refs);
-            collect_binding_refs_from_value_expr(&o.model, refs);
-            collect_binding_refs_from_value_expr(&o.system_prompt, refs);
-            collect_binding_refs_from_value_expr(&o.user_prompt, refs);
+            f(&o.base_url);
+            f(&o.model);
+            f(&o.system_prompt);
+            f(&o.user_prompt);
         }
         Operation::ErrorThrow(o) => {
-            collect_binding_refs_from_value_expr(&o.message, refs);
+            f(&o.message);
         }
         Operation::Return(o) => {
-            collect_binding_refs_from_value_expr(&o.expression, refs);
+            f(&o.expression);
+        }
+    }
+}
+
+/// Call `f` on every step of `block`, branch arms included, in source order
+/// (a branch step before its arms). [`Block::walk_steps`] also passes the
+/// branch scope.
+pub fn visit_steps(block: &Block, f: &mut dyn FnMut(&Step)) {
+    block.walk_steps(&mut |step, _| f(step));
+}
+
+/// Mutable counterpart of [`visit_operation_value_exprs`], for passes that
+/// rewrite references in place. Branch arms are not visited.
+pub fn visit_operation_value_exprs_mut(op: &mut Operation, f: &mut dyn FnMut(&mut ValueExpr)) {
+    match op {
+        Operation::HttpRequest(o) => {
+            f(&mut o.url);
+            for (_, v) in &mut o.headers {
+                f(v);
+            }
+            for (_, v) in &mut o.query_params {
+                f(v);
+            }
+            if let Some(body) = &mut o.body {
+                f(&mut body.data);
+                for (_, v) in &mut body.fields {
+                    f(v);
+                }
+            }
+        }
+        Operation::EvmRead(o) => {
+            f(&mut o.contract_address);
+            for arg in &mut o.args {
+                f(&mut arg.value);
+            }
+            if let Some(v) = &mut o.from_address {
+                f(v);
+            }
+            if let Some(v) = &mut o.block_number {
+                f(v);
+            }
+        }
+        Operation::EvmWrite(o) => {
+            f(&mut o.receiver_address);
+            f(&mut o.gas_limit);
+            f(&mut o.encoded_data);
+            if let Some(v) = &mut o.value_wei {
+                f(v);
+            }
+        }
+        Operation::KvRead(o) => {
+            f(&mut o.key);
+        }
+        Operation::KvWrite(o) => {
+            f(&mut o.key);
+            f(&mut o.value);
+        }
+        Operation::CodeNode(o) => {
+            for binding in &mut o.input_bindings {
+                f(&mut binding.value);
+            }
+        }
+        Operation::AbiEncode(o) => {
+            for mapping in &mut o.data_mappings {
+                f(&mut mapping.value);
+            }
+        }
+        Operation::AbiDecode(o) => {
+            f(&mut o.input);
+        }
+        Operation::Compute(o) => {
+            visit_compute_value_exprs_mut(&mut o.expr, f);
+        }
+        Operation::Branch(o) => {
+            for cond in o.conditions.iter_mut().flat_map(ConditionTree::leaves_mut) {
+                f(&mut cond.field);
+                if let Some(v) = &mut cond.value {
+                    f(v);
+                }
+            }
+        }
+        Operation::Filter(o) => {
+            for cond in o.conditions.iter_mut().flat_map(ConditionTree::leaves_mut) {
+                f(&mut cond.field);
+                if let Some(v) = &mut cond.value {
+                    f(v);
+                }
+            }
+            if let FilterNonMatchBehavior::EarlyReturn { message } = &mut o.non_match_behavior {
+                f(message);
+            }
+        }
+        Operation::Merge(o) => {
+            for input in &mut o.inputs {
+                f(&mut input.value);
+            }
+        }
+        Operation::AiCall(o) => {
+            f(&mut o.base_url);
+            f(&mut o.model);
+            f(&mut o.system_prompt);
+            f(&mut o.user_prompt);
+        }
+        Operation::ErrorThrow(o) => {
+            f(&mut o.message);
+        }
+        Operation::Return(o) => {
+            f(&mut o.expression);
+        }
+    }
+}
+
+fn visit_compute_value_exprs_mut(expr: &mut ComputeExpr, f: &mut dyn FnMut(&mut ValueExpr)) {
+    match expr {
+        ComputeExpr::Literal { .. } => {}
+        ComputeExpr::Value { value } => f(value),
+        ComputeExpr::Neg { operand } => visit_compute_value_exprs_mut(operand, f),
+        ComputeExpr::Binary { left, right, .. } => {
+            visit_compute_value_exprs_mut(left, f);
+            visit_compute_value_exprs_mut(right, f);
         }
     }
 }
@@ -274,20 +604,24 @@ fn validate_block_branch_merge(block: &Block, errors: &mut Vec<ValidationError>)
                                     step.id, merge_id, next_step.id
                                 ),
                                 step_id: Some(step.id.clone()),
+                                related: vec![],
                             });
                         }
                         // Verify the next step is actually a Merge referencing this branch
                         if let Some(next_step) = next {
                             if let Operation::Merge(merge) = &next_step.operation {
-                                if merge.branch_step_id != step.id {
+                                if merge.branch_step_id.as_deref() != Some(step.id.as_str()) {
                                     errors.push(ValidationError {
                                         code: "E005",
                                         message: format!(
                                             "Merge '{}' references branch_step_id='{}', \
                                              but should reference '{}'",
-                                            next_step.id, merge.branch_step_id, step.id
+                                            next_step.id,
+                                            merge.branch_step_id.as_deref().unwrap_or("none"),
+                                            step.id
                                         ),
                                         step_id: Some(next_step.id.clone()),
+                                        related: vec![],
                                     });
                                 }
                             } else {
@@ -299,6 +633,7 @@ fn validate_block_branch_merge(block: &Block, errors: &mut Vec<ValidationError>)
                                         next_step.id, step.id
                                     ),
                                     step_id: Some(next_step.id.clone()),
+                                    related: vec![],
                                 });
                             }
                         }
@@ -312,6 +647,7 @@ fn validate_block_branch_merge(block: &Block, errors: &mut Vec<ValidationError>)
                                 step.id, merge_id
                             ),
                             step_id: Some(step.id.clone()),
+                            related: vec![],
                         });
                     }
                 }
@@ -353,6 +689,7 @@ fn validate_block_secret_refs(
                         name, step.id
                     ),
                     step_id: Some(step.id.clone()),
+                    related: vec![],
                 });
             }
         }
@@ -363,7 +700,17 @@ fn validate_block_secret_refs(
     }
 }
 
-fn collect_secret_refs_from_step(step: &Step) -> Vec<String> {
+/// Every secret name referenced by steps in `block`, including nested branch arms.
+pub fn collect_secret_refs(block: &Block) -> HashSet<String> {
+    block
+        .steps
+        .iter()
+        .flat_map(collect_secret_refs_from_step)
+        .collect()
+}
+
+/// Secret names referenced by `step`, including nested branch arms.
+pub fn collect_secret_refs_from_step(step: &Step) -> Vec<String> {
     let mut secrets = Vec::new();
     match &step.operation {
         Operation::HttpRequest(o) => {
@@ -382,9 +729,47 @@ fn collect_secret_refs_from_step(step: &Step) -> Vec<String> {
         }
         _ => {}
     }
+    secrets.extend(collect_secret_value_refs_from_step(step));
     secrets
 }
 
+/// `SecretRef` names read directly by `step`'s value expressions (not
+/// including nested branch arms or fixed fields such as `token_secret`).
+pub fn collect_secret_value_refs_from_step(step: &Step) -> Vec<String> {
+    let mut secrets = Vec::new();
+    visit_operation_value_exprs(&step.operation, &mut |expr| {
+        collect_secret_refs_from_value_expr(expr, &mut secrets)
+    });
+    secrets
+}
+
+pub fn collect_secret_refs_from_value_expr(expr: &ValueExpr, secrets: &mut Vec<String>) {
+    match expr {
+        ValueExpr::SecretRef { name } => secrets.push(name.clone()),
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    collect_secret_refs_from_value_expr(value, secrets);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                collect_secret_refs_from_value_expr(value, secrets);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::Binding(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Invariant: every evm_client_binding references a declared chain
 // ---------------------------------------------------------------------------
@@ -406,6 +791,7 @@ fn validate_evm_chain_refs(ir: &WorkflowIR, errors: &mut Vec<ValidationError>) {
                     trigger.evm_client_binding
                 ),
                 step_id: None,
+                related: vec![],
             });
         }
     }
@@ -419,12 +805,7 @@ fn validate_block_evm_refs(
     errors: &mut Vec<ValidationError>,
 ) {
     for step in &block.steps {
-        let binding = match &step.operation {
-            Operation::EvmRead(o) => Some(&o.evm_client_binding),
-            Operation::EvmWrite(o) => Some(&o.evm_client_binding),
-            _ => None,
-        };
-        if let Some(b) = binding {
+        if let Some(b) = evm_binding_of_step(step) {
             if !declared.contains(b.as_str()) {
                 errors.push(ValidationError {
                     code: "E008",
@@ -433,6 +814,7 @@ fn validate_block_evm_refs(
                         step.id, b
                     ),
                     step_id: Some(step.id.clone()),
+                    related: vec![],
                 });
             }
         }
@@ -443,93 +825,183 @@ fn validate_block_evm_refs(
     }
 }
 
+// ---------------------------------------------------------------------------
+// Invariant: only an EvmLog trigger's chain is marked `used_for_trigger`
+// ---------------------------------------------------------------------------
+
+/// Codegen constructs the `used_for_trigger` chain's client at module scope
+/// and registers the log trigger on it, so an EvmLog trigger needs exactly
+/// one such chain, bound to its `evm_client_binding`, and other triggers none.
+fn validate_trigger_chain(ir: &WorkflowIR, errors: &mut Vec<ValidationError>) {
+    let trigger_chains: Vec<&EvmChainUsage> = ir
+        .evm_chains
+        .iter()
+        .filter(|c| c.used_for_trigger)
+        .collect();
+    let names = || {
+        trigger_chains
+            .iter()
+            .map(|c| format!("'{}'", c.chain_selector_name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let message = match (&ir.trigger, trigger_chains.as_slice()) {
+        (TriggerDef::EvmLog(trigger), [chain])
+            if chain.binding_name != trigger.evm_client_binding =>
+        {
+            format!(
+                "Chain '{}' is marked used_for_trigger but the EvmLog trigger uses '{}'",
+                chain.chain_selector_name, trigger.evm_client_binding
+            )
+        }
+        (TriggerDef::EvmLog(_), [_]) => return,
+        (TriggerDef::EvmLog(_), []) => {
+            "EvmLog trigger needs one chain marked used_for_trigger, found none".to_string()
+        }
+        (TriggerDef::EvmLog(_), _) => format!(
+            "EvmLog trigger needs exactly one chain marked used_for_trigger, found {}",
+            names()
+        ),
+        (_, []) => return,
+        (_, _) => format!(
+            "Only an EvmLog trigger uses a chain, but {} marked used_for_trigger",
+            names()
+        ),
+    };
+    errors.push(ValidationError {
+        code: "E017",
+        message,
+        step_id: None,
+        related: vec![],
+    });
+}
+
+/// Every evm_client_binding referenced by the trigger or by steps, including nested branch arms.
+pub fn collect_evm_binding_refs(ir: &WorkflowIR) -> HashSet<String> {
+    let mut bindings = HashSet::new();
+    if let TriggerDef::EvmLog(trigger) = &ir.trigger {
+        bindings.insert(trigger.evm_client_binding.clone());
+    }
+    collect_block_evm_binding_refs(&ir.handler_body, &mut bindings);
+    bindings
+}
+
+fn collect_block_evm_binding_refs(block: &Block, bindings: &mut HashSet<String>) {
+    for step in &block.steps {
+        if let Some(b) = evm_binding_of_step(step) {
+            bindings.insert(b.clone());
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            collect_block_evm_binding_refs(&branch.true_branch, bindings);
+            collect_block_evm_binding_refs(&branch.false_branch, bindings);
+        }
+    }
+}
+
+fn evm_binding_of_step(step: &Step) -> Option<&String> {
+    match &step.operation {
+        Operation::EvmRead(o) => Some(&o.evm_client_binding),
+        Operation::EvmWrite(o) => Some(&o.evm_client_binding),
+        _ => None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Invariant: standalone merges combine at least two inputs
+// ---------------------------------------------------------------------------
+
+/// A merge not tied to a Branch must combine two or more inputs with Append
+/// or Custom. Its inputs are checked for scope like any other step's refs.
+fn validate_standalone_merges(block: &Block, errors: &mut Vec<ValidationError>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::Merge(merge) if merge.branch_step_id.is_none() => {
+                if merge.inputs.len() < 2 {
+                    errors.push(ValidationError {
+                        code: "E016",
+                        message: format!(
+                            "Merge '{}' is not tied to a branch and has {} input(s); \
+                             a standalone merge needs at least 2",
+                            step.id,
+                            merge.inputs.len()
+                        ),
+                        step_id: Some(step.id.clone()),
+                        related: vec![],
+                    });
+                }
+                if matches!(merge.strategy, MergeStrategy::PassThrough) {
+                    errors.push(ValidationError {
+                        code: "E016",
+                        message: format!(
+                            "Merge '{}' is not tied to a branch, so it must combine its inputs \
+                             (Append or Custom) instead of passing one through",
+                            step.id
+                        ),
+                        step_id: Some(step.id.clone()),
+                        related: vec![],
+                    });
+                }
+            }
+            Operation::Branch(branch) => {
+                validate_standalone_merges(&branch.true_branch, errors);
+                validate_standalone_merges(&branch.false_branch, errors);
+            }
+            _ => {}
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Invariant: CRE capability budget
 // ---------------------------------------------------------------------------
 
 fn validate_cre_budget(ir: &WorkflowIR, errors: &mut Vec<ValidationError>) {
-    let mut http_count = 0;
-    let mut evm_read_count = 0;
-    let mut evm_write_count = 0;
-    count_capabilities(
-        &ir.handler_body,
-        &mut http_count,
-        &mut evm_read_count,
-        &mut evm_write_count,
-    );
-
-    if http_count > MAX_HTTP_CALLS {
+    let counts = count_capabilities(&ir.handler_body);
+    if counts.http > MAX_HTTP_CALLS {
         errors.push(ValidationError {
             code: "E009",
             message: format!(
                 "Workflow uses {} HTTP calls, exceeding CRE limit of {}",
-                http_count, MAX_HTTP_CALLS
+                counts.http, MAX_HTTP_CALLS
             ),
             step_id: None,
+            related: batching_suggestions(&ir.handler_body),
         });
     }
-    if evm_read_count > MAX_EVM_READS {
+    if counts.evm_read > MAX_EVM_READS {
         errors.push(ValidationError {
             code: "E010",
             message: format!(
                 "Workflow uses {} EVM reads, exceeding CRE limit of {}",
-                evm_read_count, MAX_EVM_READS
+                counts.evm_read, MAX_EVM_READS
             ),
             step_id: None,
+            related: vec![],
         });
     }
-    if evm_write_count > MAX_EVM_WRITES {
+    if counts.evm_write > MAX_EVM_WRITES {
         errors.push(ValidationError {
             code: "E011",
             message: format!(
                 "Workflow uses {} EVM writes, exceeding CRE limit of {}",
-                evm_write_count, MAX_EVM_WRITES
+                counts.evm_write, MAX_EVM_WRITES
             ),
             step_id: None,
+            related: vec![],
         });
     }
-}
-
-fn count_capabilities(
-    block: &Block,
-    http: &mut usize,
-    evm_read: &mut usize,
-    evm_write: &mut usize,
-) {
-    for step in &block.steps {
-        match &step.operation {
-            Operation::HttpRequest(_) | Operation::AiCall(_) => *http += 1,
-            Operation::EvmRead(_) => *evm_read += 1,
-            Operation::EvmWrite(_) => *evm_write += 1,
-            Operation::Branch(branch) => {
-                // Count the worse branch (both could run in different executions,
-                // but CRE counts per execution so we take the max of each branch)
-                let mut true_http = 0;
-                let mut true_read = 0;
-                let mut true_write = 0;
-                count_capabilities(
-                    &branch.true_branch,
-                    &mut true_http,
-                    &mut true_read,
-                    &mut true_write,
-                );
-
-                let mut false_http = 0;
-                let mut false_read = 0;
-                let mut false_write = 0;
-                count_capabilities(
-                    &branch.false_branch,
-                    &mut false_http,
-                    &mut false_read,
-                    &mut false_write,
-                );
-
-                *http += true_http.max(false_http);
-                *evm_read += true_read.max(false_read);
-                *evm_write += true_write.max(false_write);
-            }
-            _ => {}
-        }
+    let kv_limit = kv_operation_limit(ir);
+    if counts.kv > kv_limit {
+        errors.push(ValidationError {
+            code: "E018",
+            message: format!(
+                "Workflow uses {} KV operations, exceeding the limit of {}",
+                counts.kv, kv_limit
+            ),
+            step_id: None,
+            related: vec![],
+        });
     }
 }
 
@@ -538,12 +1010,48 @@ fn count_capabilities(
 // ---------------------------------------------------------------------------
 
 fn validate_return_paths(ir: &WorkflowIR, errors: &mut Vec<ValidationError>) {
-    if !block_terminates(&ir.handler_body) {
-        errors.push(ValidationError {
+    if block_terminates(&ir.handler_body) {
+        return;
+    }
+    let error = match final_filter(&ir.handler_body) {
+        Some((step, FilterNonMatchBehavior::Skip)) => ValidationError {
+            code: "E012",
+            message: format!(
+                "Filter '{}' skips nothing: it is the last step, so execution ends without a Return or ErrorThrow step whether or not its condition holds",
+                step.id
+            ),
+            step_id: Some(step.id.clone()),
+            related: vec![],
+        },
+        Some((step, FilterNonMatchBehavior::EarlyReturn { .. })) => ValidationError {
+            code: "E012",
+            message: format!(
+                "Filter '{}' is the last step: when its condition holds, execution ends without a Return or ErrorThrow step",
+                step.id
+            ),
+            step_id: Some(step.id.clone()),
+            related: vec![],
+        },
+        None => ValidationError {
             code: "E012",
             message: "Not all execution paths end with a Return or ErrorThrow step".into(),
             step_id: None,
-        });
+            related: vec![],
+        },
+    };
+    errors.push(error);
+}
+
+/// A Filter that ends `block` or one of the arms it forks into, with its
+/// non-match behavior.
+fn final_filter(block: &Block) -> Option<(&Step, &FilterNonMatchBehavior)> {
+    let last = block.steps.last()?;
+    match &last.operation {
+        Operation::Filter(filter) => Some((last, &filter.non_match_behavior)),
+        Operation::Branch(branch) if branch.reconverge_at.is_none() => {
+            final_filter(&branch.true_branch).or_else(|| final_filter(&branch.false_branch))
+        }
+        _ => None,
     }
 }
 
@@ -570,10 +1078,237 @@ fn block_terminates(block: &Block) -> bool {
                 false
             }
         }
+        // Both behaviors carry on when the condition holds, so a Filter never
+        // ends a path by itself, whatever it does on a failed condition.
+        Operation::Filter(_) => false,
         _ => false,
     }
 }
 
+// ---------------------------------------------------------------------------
+// Invariant: Json literals hold valid JSON (codegen emits them verbatim)
+// ---------------------------------------------------------------------------
+
+fn validate_json_literals(block: &Block, errors: &mut Vec<ValidationError>) {
+    for step in &block.steps {
+        visit_operation_value_exprs(&step.operation, &mut |expr| {
+            check_json_literal(expr, &step.id, errors)
+        });
+        if let Operation::Branch(branch) = &step.operation {
+            validate_json_literals(&branch.true_branch, errors);
+            validate_json_literals(&branch.false_branch, errors);
+        }
+    }
+}
+
+fn check_json_literal(expr: &ValueExpr, step_id: &str, errors: &mut Vec<ValidationError>) {
+    match expr {
+        ValueExpr::Literal(LiteralValue::Json { value }) => {
+            if let Err(e) = serde_json::from_str::<serde_json::Value>(value) {
+                errors.push(ValidationError {
+                    code: "E014",
+                    message: format!(
+                        "Step '{}' has a JSON literal that does not parse: {}",
+                        step_id, e
+                    ),
+                    step_id: Some(step_id.to_string()),
+                    related: vec![],
+                });
+            }
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    check_json_literal(value, step_id, errors);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                check_json_literal(value, step_id, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Invariant: HTTP URLs have a literal scheme and host and no credentials
+// ---------------------------------------------------------------------------
+
+/// Backstop for N026–N029: node validation sees the config text, this sees
+/// what lowering produced. A URL that is neither a literal nor a template has
+/// nothing literal about it, so its host is dynamic.
+fn validate_http_urls(block: &Block, errors: &mut Vec<ValidationError>) {
+    visit_steps(block, &mut |step| {
+        let Operation::HttpRequest(op) = &step.operation else {
+            return;
+        };
+        let problem = match url::ir_url_parts(&op.url).map(|parts| url::parse_url(&parts)) {
+            None | Some(Ok(url::UrlShape { host: None, .. })) => "has no literal host".to_string(),
+            Some(Err(problem)) => format!("is malformed: {}", problem),
+            Some(Ok(shape)) if shape.has_credentials => "embeds credentials".to_string(),
+            Some(Ok(shape)) => match shape
+                .query_keys
+                .iter()
+                .find(|key| op.query_params.iter().any(|(name, _)| name == *key))
+            {
+                Some(key) => format!("repeats query parameter '{}'", key),
+                None => return,
+            },
+        };
+        errors.push(ValidationError {
+            code: "E019",
+            message: format!("HTTP request in step '{}' {}", step.id, problem),
+            step_id: Some(step.id.clone()),
+            related: vec![],
+        });
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Invariant: literal regex patterns compile
+// ---------------------------------------------------------------------------
+
+/// Backstop for N034. Patterns built from expressions are left to run time.
+fn validate_regex_patterns(block: &Block, errors: &mut Vec<ValidationError>) {
+    visit_steps(block, &mut |step| {
+        let conditions = match &step.operation {
+            Operation::Branch(op) => &op.conditions,
+            Operation::Filter(op) => &op.conditions,
+            _ => return,
+        };
+        for cond in conditions.iter().flat_map(ConditionTree::leaves) {
+            if !matches!(cond.operator, ComparisonOp::Regex | ComparisonOp::NotRegex) {
+                continue;
+            }
+            let Some(ValueExpr::Literal(LiteralValue::String { value })) = &cond.value else {
+                continue;
+            };
+            if let Err(problem) = js_regex::check_pattern(value) {
+                errors.push(ValidationError {
+                    code: "E020",
+                    message: format!(
+                        "Regex pattern '{}' in step '{}' is invalid: {}",
+                        value, step.id, problem
+                    ),
+                    step_id: Some(step.id.clone()),
+                    related: vec![],
+                });
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Invariant: ABI encode function names are identifiers declared in the ABI
+// ---------------------------------------------------------------------------
+
+/// `[A-Za-z_$][A-Za-z0-9_$]*` — a Solidity function name.
+pub fn is_solidity_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Backstop for N035: `encodeFunctionData` needs a function of that name in
+/// the ABI, which may be a single item or an array of items.
+fn validate_abi_function_names(block: &Block, errors: &mut Vec<ValidationError>) {
+    visit_steps(block, &mut |step| {
+        let Operation::AbiEncode(op) = &step.operation else {
+            return;
+        };
+        let Some(name) = &op.function_name else {
+            return;
+        };
+        let problem = if !is_solidity_identifier(name) {
+            "is not a Solidity identifier"
+        } else if !abi_declares_function(&op.abi_json, name) {
+            "is not a function in its ABI"
+        } else {
+            return;
+        };
+        errors.push(ValidationError {
+            code: "E021",
+            message: format!(
+                "ABI encode function name '{}' in step '{}' {}",
+                name, step.id, problem
+            ),
+            step_id: Some(step.id.clone()),
+            related: vec![],
+        });
+    });
+}
+
+fn abi_declares_function(abi_json: &str, name: &str) -> bool {
+    let Ok(abi) = serde_json::from_str::<serde_json::Value>(abi_json) else {
+        return false;
+    };
+    let items = match &abi {
+        serde_json::Value::Array(items) => items.as_slice(),
+        item => std::slice::from_ref(item),
+    };
+    items.iter().any(|item| {
+        item.get("type").and_then(|t| t.as_str()) == Some("function")
+            && item.get("name").and_then(|n| n.as_str()) == Some(name)
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Invariant: destructured locals don't collide with other names in scope
+// ---------------------------------------------------------------------------
+
+/// Names declared in scope → (declaring step, whether it is a destructured local).
+type DeclaredNames = HashMap<String, (String, bool)>;
+
+fn validate_destructured_names(
+    block: &Block,
+    parent_scope: &DeclaredNames,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut scope = parent_scope.clone();
+    for step in &block.steps {
+        if let Operation::Branch(branch) = &step.operation {
+            validate_destructured_names(&branch.true_branch, &scope, errors);
+            validate_destructured_names(&branch.false_branch, &scope, errors);
+        }
+        let Some(out) = &step.output else { continue };
+        let destructured = out.destructure_fields.is_some();
+        let names = if destructured {
+            out.destructured_vars()
+                .into_iter()
+                .map(|(_, local)| local)
+                .collect()
+        } else {
+            vec![out.variable_name.clone()]
+        };
+        for name in names {
+            match scope.get(&name) {
+                Some((other, other_destructured)) if destructured || *other_destructured => {
+                    errors.push(ValidationError {
+                        code: "E015",
+                        message: format!(
+                            "Step '{}' declares '{}', which step '{}' already declares in the same scope",
+                            step.id, name, other
+                        ),
+                        step_id: Some(step.id.clone()),
+                        related: vec![],
+                    });
+                }
+                _ => {
+                    scope.insert(name, (step.id.clone(), destructured));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -587,6 +1322,7 @@ mod tests {
                 version: "1.0.0".into(),
                 is_testnet: true,
                 default_chain_selector: None,
+                max_kv_operations: None,
             },
             trigger: TriggerDef::Cron(CronTriggerDef {
                 schedule: ValueExpr::config("schedule"),
@@ -597,6 +1333,8 @@ mod tests {
                 zod_type: ZodType::String,
                 default_value: Some("0 */5 * * * *".into()),
                 description: None,
+                referenced_by: vec![],
+                optional_in_config: false,
             }],
             required_secrets: vec![],
             evm_chains: vec![],
@@ -608,8 +1346,10 @@ mod tests {
                     label: "Return success".into(),
                     operation: Operation::Return(ReturnOp {
                         expression: ValueExpr::string("Done"),
+                        auto_generated: false,
                     }),
                     output: None,
+                    notes: None,
                 }],
             },
         }
@@ -640,8 +1380,10 @@ mod tests {
             label: "Dup".into(),
             operation: Operation::Return(ReturnOp {
                 expression: ValueExpr::string("dup"),
+                auto_generated: false,
             }),
             output: None,
+            notes: None,
         });
         let errors = validate_ir(&ir);
         assert!(errors.iter().any(|e| e.code == "E002"));
@@ -666,6 +1408,8 @@ mod tests {
                     timeout_ms: None,
                     expected_status_codes: vec![200],
                     response_format: HttpResponseFormat::Json,
+                    follow_redirects: true,
+                    ignore_ssl: false,
                     consensus: ConsensusStrategy::Identical,
                 }),
                 output: Some(OutputBinding {
@@ -673,6 +1417,7 @@ mod tests {
                     ts_type: "{ statusCode: number; body: any }".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             },
             Step {
                 id: "code-1".into(),
@@ -685,6 +1430,7 @@ mod tests {
                         value: ValueExpr::binding("http-1", "body"),
                     }],
                     execution_mode: CodeExecutionMode::RunOnceForAll,
+                    iterate_over: None,
                     timeout_ms: None,
                     output_fields: vec![],
                 }),
@@ -693,6 +1439,7 @@ mod tests {
                     ts_type: "any".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             },
             Step {
                 id: "return-1".into(),
@@ -700,8 +1447,10 @@ mod tests {
                 label: "Return".into(),
                 operation: Operation::Return(ReturnOp {
                     expression: ValueExpr::string("ok"),
+                    auto_generated: false,
                 }),
                 output: None,
+                notes: None,
             },
         ];
         let errors = validate_ir(&ir);
@@ -723,6 +1472,7 @@ mod tests {
                         value: ValueExpr::binding("http-1", "body"),
                     }],
                     execution_mode: CodeExecutionMode::RunOnceForAll,
+                    iterate_over: None,
                     timeout_ms: None,
                     output_fields: vec![],
                 }),
@@ -731,6 +1481,7 @@ mod tests {
                     ts_type: "any".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             },
             Step {
                 id: "http-1".into(),
@@ -747,6 +1498,8 @@ mod tests {
                     timeout_ms: None,
                     expected_status_codes: vec![200],
                     response_format: HttpResponseFormat::Json,
+                    follow_redirects: true,
+                    ignore_ssl: false,
                     consensus: ConsensusStrategy::Identical,
                 }),
                 output: Some(OutputBinding {
@@ -754,6 +1507,7 @@ mod tests {
                     ts_type: "{ statusCode: number; body: any }".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             },
             Step {
                 id: "return-1".into(),
@@ -761,8 +1515,10 @@ mod tests {
                 label: "Return".into(),
                 operation: Operation::Return(ReturnOp {
                     expression: ValueExpr::string("ok"),
+                    auto_generated: false,
                 }),
                 output: None,
+                notes: None,
             },
         ];
         let errors = validate_ir(&ir);
@@ -791,6 +1547,8 @@ mod tests {
                     timeout_ms: None,
                     expected_status_codes: vec![200],
                     response_format: HttpResponseFormat::Json,
+                    follow_redirects: true,
+                    ignore_ssl: false,
                     consensus: ConsensusStrategy::Identical,
                 }),
                 output: Some(OutputBinding {
@@ -798,6 +1556,7 @@ mod tests {
                     ts_type: "{ statusCode: number; body: string }".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             },
         );
         // required_secrets is empty — should fail
@@ -817,10 +1576,12 @@ mod tests {
                 code: "/* noop */".into(),
                 input_bindings: vec![],
                 execution_mode: CodeExecutionMode::RunOnceForAll,
+                iterate_over: None,
                 timeout_ms: None,
                 output_fields: vec![],
             }),
             output: None,
+            notes: None,
         }];
         let errors = validate_ir(&ir);
         assert!(errors.iter().any(|e| e.code == "E012"));
@@ -846,6 +1607,8 @@ mod tests {
                     timeout_ms: None,
                     expected_status_codes: vec![200],
                     response_format: HttpResponseFormat::Json,
+                    follow_redirects: true,
+                    ignore_ssl: false,
                     consensus: ConsensusStrategy::Identical,
                 }),
                 output: Some(OutputBinding {
@@ -853,6 +1616,7 @@ mod tests {
                     ts_type: "any".into(),
                     destructure_fields: None,
                 }),
+                notes: None,
             })
             .collect();
         steps.push(Step {
@@ -861,8 +1625,10 @@ mod tests {
             label: "Return".into(),
             operation: Operation::Return(ReturnOp {
                 expression: ValueExpr::string("ok"),
+                auto_generated: false,
             }),
             output: None,
+            notes: None,
         });
         ir.handler_body.steps = steps;
         let errors = validate_ir(&ir);
@@ -1,7 +1,17 @@
+pub mod cancel;
+#[cfg(feature = "cli")]
+pub mod cli;
 pub mod codegen;
+pub mod compile;
 pub mod error;
+pub mod incremental;
 pub mod ir;
 pub mod lower;
 pub mod parse;
+pub mod project;
+pub mod subgraph;
+pub mod template;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod validate;
 pub mod wasm;
@@ -0,0 +1,161 @@
+//! Post-lowering type propagation for `bigint` values.
+//!
+//! viem decodes `int*`/`uint*` ABI values wider than 48 bits as `bigint`, and
+//! BigInt math yields one. A `bigint` is never `===` to a string or number,
+//! and a config string such as `"1000"` does not compare to it numerically,
+//! so numeric conditions with one `bigint` side and ABI Encode arguments that
+//! already are one are marked with [`ValueKind::BigInt`] for codegen. A
+//! literal compared with a `bigint` that `BigInt(...)` cannot take exactly is
+//! reported (W021).
+
+use std::collections::HashSet;
+
+use super::builder::abi_value_ts_type;
+use crate::error::CompilerError;
+use crate::ir::types::*;
+use crate::ir::validate::visit_steps;
+
+/// `(step id, field path)` of every step output known to be a `bigint`. The
+/// field path is empty when the whole output is one.
+type BigintValues = HashSet<(String, String)>;
+
+/// Mark condition operands and ABI Encode arguments that are `bigint`s.
+/// Returns W021 for each literal compared with a `bigint` that `BigInt(...)`
+/// would reject or round.
+pub fn annotate_bigint_values(ir: &mut WorkflowIR) -> Vec<CompilerError> {
+    let mut bigints = BigintValues::new();
+    visit_steps(&ir.handler_body, &mut |step| {
+        for field in bigint_fields(&step.operation) {
+            bigints.insert((step.id.clone(), field));
+        }
+    });
+    let mut warnings = Vec::new();
+    if !bigints.is_empty() {
+        annotate_block(&mut ir.handler_body, &bigints, &mut warnings);
+    }
+    warnings
+}
+
+/// Fields of an operation's output that hold a `bigint`.
+fn bigint_fields(op: &Operation) -> Vec<String> {
+    let (outputs, names) = match op {
+        Operation::EvmRead(op) => (
+            function_outputs(&op.abi_json, &op.function_name),
+            &op.output_names,
+        ),
+        Operation::AbiDecode(op) => (
+            abi_types(serde_json::from_str(&op.abi_json).ok()),
+            &op.output_names,
+        ),
+        Operation::Compute(op) if op.mode == NumericMode::BigInt => return vec![String::new()],
+        _ => return vec![],
+    };
+    outputs
+        .iter()
+        .zip(names)
+        .filter(|(ty, _)| abi_value_ts_type(ty) == "bigint")
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+/// Output types of `function_name` in an ABI item or an array of items.
+fn function_outputs(abi_json: &str, function_name: &str) -> Vec<String> {
+    let item = match serde_json::from_str::<serde_json::Value>(abi_json) {
+        Ok(serde_json::Value::Array(items)) => items
+            .into_iter()
+            .find(|item| item.get("name").and_then(|n| n.as_str()) == Some(function_name)),
+        Ok(item) => Some(item),
+        Err(_) => None,
+    };
+    abi_types(item.and_then(|mut item| item.get_mut("outputs").map(serde_json::Value::take)))
+}
+
+/// The `type` of each entry of an ABI parameter array.
+fn abi_types(params: Option<serde_json::Value>) -> Vec<String> {
+    let Some(serde_json::Value::Array(params)) = params else {
+        return vec![];
+    };
+    params
+        .iter()
+        .map(|p| {
+            p.get("type")
+                .and_then(|t| t.as_str())
+                .unwrap_or("")
+                .to_string()
+        })
+        .collect()
+}
+
+fn kind_of(value: &ValueExpr, bigints: &BigintValues) -> Option<ValueKind> {
+    let ValueExpr::Binding(binding) = value else {
+        return None;
+    };
+    bigints
+        .contains(&(binding.step_id.clone(), binding.field_path.clone()))
+        .then_some(ValueKind::BigInt)
+}
+
+fn annotate_block(block: &mut Block, bigints: &BigintValues, warnings: &mut Vec<CompilerError>) {
+    for step in &mut block.steps {
+        let conditions = match &mut step.operation {
+            Operation::Branch(branch) => {
+                annotate_block(&mut branch.true_branch, bigints, warnings);
+                annotate_block(&mut branch.false_branch, bigints, warnings);
+                &mut branch.conditions
+            }
+            Operation::Filter(filter) => &mut filter.conditions,
+            Operation::AbiEncode(op) => {
+                for mapping in &mut op.data_mappings {
+                    mapping.value_kind = kind_of(&mapping.value, bigints);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        for cond in conditions.iter_mut().flat_map(ConditionTree::leaves_mut) {
+            if !cond.operator.is_numeric() {
+                continue;
+            }
+            let Some(value) = &cond.value else { continue };
+            cond.field_kind = kind_of(&cond.field, bigints);
+            cond.value_kind = kind_of(value, bigints);
+            let other = match (cond.field_kind, cond.value_kind) {
+                (Some(_), None) => value,
+                (None, Some(_)) => &cond.field,
+                _ => continue,
+            };
+            if let Some(literal) = inexact_bigint_literal(other) {
+                warnings.push(CompilerError::lower(
+                    "W021",
+                    format!(
+                        "Condition in step '{}' compares a bigint with {}, which BigInt cannot represent exactly — use a whole number",
+                        step.id, literal
+                    ),
+                    Some(step.id.clone()),
+                ));
+            }
+        }
+    }
+}
+
+/// The literal as written, when `BigInt(...)` would throw on it or it has
+/// already lost precision as a JS number.
+fn inexact_bigint_literal(value: &ValueExpr) -> Option<String> {
+    const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+    match value {
+        ValueExpr::Literal(LiteralValue::Number { value })
+            if value.fract() != 0.0 || value.abs() > MAX_SAFE_INTEGER =>
+        {
+            Some(value.to_string())
+        }
+        ValueExpr::Literal(LiteralValue::String { value }) => {
+            let digits = value.strip_prefix('-').unwrap_or(value);
+            let hex = digits
+                .strip_prefix("0x")
+                .is_some_and(|hex| !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit()));
+            let decimal = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+            (!hex && !decimal).then(|| format!("\"{}\"", value))
+        }
+        _ => None,
+    }
+}
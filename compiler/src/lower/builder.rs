@@ -5,17 +5,30 @@
 
 use std::collections::{HashMap, HashSet};
 
-use crate::error::CompilerError;
+use crate::cancel::{self, CancellationToken};
+use crate::error::{CompilerError, JsonPathSegment, Phase};
 use crate::ir::types::*;
 use crate::parse::graph::WorkflowGraph;
-use crate::parse::types::{Workflow, WorkflowNode};
+use crate::parse::types::{
+    AbiFunction, AiConsensusMode, AutoReturnMode, ConditionNode, FilterNonMatchMode, Workflow,
+    WorkflowNode,
+};
 
+use super::compute::{MAX_INTEGER_LITERAL_DIGITS, integer_literal, parse_compute_expr};
 use super::expand::{self, ExpandedStep};
-use super::reference::resolve_value_expr;
+use super::extract::resolve_chain_selector;
+use super::http_body;
+use super::merge;
+use super::reference::{RefScope, config_path, field, indexed_field, keyed_field};
+use super::step_cache::StepCache;
 
 const AUTO_RETURN_ID_PREFIX: &str = "auto-return";
 const AUTO_RETURN_LABEL: &str = "Auto Return";
 const AUTO_RETURN_MESSAGE: &str = "Workflow completed";
+const FILTER_NON_MATCH_MESSAGE: &str = "Filter condition not met";
+/// Target handle marking the incoming edge a node reads its implicit input
+/// from, for nodes with several predecessors (below a merge, on a diamond).
+pub const DATA_INPUT_HANDLE: &str = "data";
 
 fn sanitize_label(label: &str) -> String {
     let s: String = label
@@ -31,12 +44,17 @@ fn sanitize_label(label: &str) -> String {
     }
 }
 
-/// Build the handler body from a topo-sorted list of node IDs.
+/// Build the handler body from a topo-sorted list of node IDs. With
+/// `step_cache`, nodes reuse the step lowered by an earlier compilation.
+/// Stops with X001 once `cancel` is cancelled.
 pub fn build_handler_body(
     topo_order: &[String],
     workflow: &Workflow,
     graph: &WorkflowGraph,
     id_map: &HashMap<String, String>,
+    auto_return: AutoReturnMode,
+    step_cache: Option<&mut StepCache>,
+    cancel: Option<&CancellationToken>,
 ) -> Result<Block, Vec<CompilerError>> {
     let node_map: HashMap<&str, &WorkflowNode> =
         workflow.nodes.iter().map(|n| (n.id(), n)).collect();
@@ -53,9 +71,41 @@ pub fn build_handler_body(
         .map(|s| s.as_str())
         .collect();
 
+    let default_chain = workflow.global_config.default_chain_selector.as_deref();
+
     let mut consumed = HashSet::new();
-    let mut steps = build_steps(&non_trigger, &node_map, graph, id_map, &mut consumed)?;
-    ensure_terminating_step(&mut steps);
+    let mut steps = build_steps(
+        &non_trigger,
+        &node_map,
+        graph,
+        id_map,
+        default_chain,
+        &mut consumed,
+        step_cache,
+        cancel,
+    )?;
+
+    match auto_return {
+        AutoReturnMode::Error => {
+            let mut leaves = Vec::new();
+            collect_open_leaves(&steps, None, &mut leaves);
+            // Nothing after the trigger: the trigger itself is the open leaf.
+            if steps.is_empty()
+                && let Some(trigger) = workflow.nodes.iter().find(|n| n.is_trigger())
+            {
+                leaves.push(trigger.id());
+            }
+            if !leaves.is_empty() {
+                return Err(leaves.into_iter().map(open_path_error).collect());
+            }
+        }
+        AutoReturnMode::AutoString => ensure_terminating_step(&mut steps),
+        AutoReturnMode::AutoSummary => {
+            let mut ids = HashSet::new();
+            collect_step_ids(&steps, &mut ids);
+            close_open_paths_with_summary(&mut steps, &mut ids);
+        }
+    }
 
     Ok(Block { steps })
 }
@@ -65,16 +115,88 @@ fn ensure_terminating_step(steps: &mut Vec<Step>) {
         return;
     }
 
-    let step_id = next_auto_return_step_id(steps);
-    steps.push(Step {
+    let mut ids = HashSet::new();
+    collect_step_ids(steps, &mut ids);
+    let step_id = next_auto_return_step_id(&ids);
+    steps.push(auto_return_step(
+        step_id,
+        ValueExpr::string(AUTO_RETURN_MESSAGE),
+    ));
+}
+
+fn auto_return_step(step_id: String, expression: ValueExpr) -> Step {
+    Step {
         id: step_id.clone(),
-        source_node_ids: vec![step_id.clone()],
+        source_node_ids: vec![step_id],
         label: AUTO_RETURN_LABEL.into(),
         operation: Operation::Return(ReturnOp {
-            expression: ValueExpr::string(AUTO_RETURN_MESSAGE),
+            expression,
+            auto_generated: true,
         }),
         output: None,
-    });
+        notes: None,
+    }
+}
+
+/// `AutoSummary`: end every open path with a Return of its last step's output.
+/// Paths that fork without reconverging get one Return per arm, since an arm's
+/// bindings are not in scope after the branch.
+fn close_open_paths_with_summary(steps: &mut Vec<Step>, ids: &mut HashSet<String>) {
+    if steps_terminate(steps) {
+        return;
+    }
+
+    if let Some(Step {
+        operation: Operation::Branch(branch),
+        ..
+    }) = steps.last_mut()
+        && branch.reconverge_at.is_none()
+    {
+        close_open_paths_with_summary(&mut branch.true_branch.steps, ids);
+        close_open_paths_with_summary(&mut branch.false_branch.steps, ids);
+        return;
+    }
+
+    // `__stringify` is the handler's BigInt-safe JSON.stringify.
+    let expression = match steps.last().and_then(|s| s.output.as_ref()) {
+        Some(output) if output.destructure_fields.is_some() => {
+            ValueExpr::raw(format!("__stringify({})", output.destructured_object()))
+        }
+        Some(output) => ValueExpr::raw(format!("__stringify({})", output.variable_name)),
+        None => ValueExpr::string(AUTO_RETURN_MESSAGE),
+    };
+    let step_id = next_auto_return_step_id(ids);
+    ids.insert(step_id.clone());
+    steps.push(auto_return_step(step_id, expression));
+}
+
+/// `Error` mode: collect the node each non-terminating path through `steps`
+/// stops at. An empty branch arm stops at the branch itself.
+fn collect_open_leaves<'a>(steps: &'a [Step], parent: Option<&'a str>, leaves: &mut Vec<&'a str>) {
+    let Some(last) = steps.last() else {
+        leaves.extend(parent);
+        return;
+    };
+
+    match &last.operation {
+        Operation::Return(_) | Operation::ErrorThrow(_) => {}
+        Operation::Branch(branch) if branch.reconverge_at.is_none() => {
+            collect_open_leaves(&branch.true_branch.steps, Some(&last.id), leaves);
+            collect_open_leaves(&branch.false_branch.steps, Some(&last.id), leaves);
+        }
+        _ => leaves.push(last.source_node_ids.first().unwrap_or(&last.id)),
+    }
+}
+
+fn open_path_error(node_id: &str) -> CompilerError {
+    CompilerError::lower(
+        "L009",
+        format!(
+            "Execution path ends at '{}' without a Return or Error node",
+            node_id
+        ),
+        Some(node_id.to_string()),
+    )
 }
 
 fn steps_terminate(steps: &[Step]) -> bool {
@@ -92,10 +214,7 @@ fn steps_terminate(steps: &[Step]) -> bool {
     }
 }
 
-fn next_auto_return_step_id(steps: &[Step]) -> String {
-    let mut ids = HashSet::new();
-    collect_step_ids(steps, &mut ids);
-
+fn next_auto_return_step_id(ids: &HashSet<String>) -> String {
     let mut index = 0;
     loop {
         let candidate = if index == 0 {
@@ -122,18 +241,23 @@ fn collect_step_ids(steps: &[Step], ids: &mut HashSet<String>) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_steps(
     node_ids: &[&str],
     node_map: &HashMap<&str, &WorkflowNode>,
     graph: &WorkflowGraph,
     id_map: &HashMap<String, String>,
+    default_chain: Option<&str>,
     consumed: &mut HashSet<String>,
+    mut step_cache: Option<&mut StepCache>,
+    cancel: Option<&CancellationToken>,
 ) -> Result<Vec<Step>, Vec<CompilerError>> {
     let mut steps = Vec::new();
     let mut errors = Vec::new();
     let mut i = 0;
 
     while i < node_ids.len() {
+        cancel::check(cancel, Phase::Lower)?;
         let node_id = node_ids[i];
 
         if consumed.contains(node_id) {
@@ -158,7 +282,10 @@ fn build_steps(
                     node_map,
                     graph,
                     id_map,
+                    default_chain,
                     consumed,
+                    step_cache.as_deref_mut(),
+                    cancel,
                 ) {
                     Ok(branch_steps) => steps.extend(branch_steps),
                     Err(e) => errors.extend(e),
@@ -166,12 +293,21 @@ fn build_steps(
             }
             _ => {
                 // Check if it's a convenience node
-                if let Some(expanded) = expand::expand_node(node, id_map) {
+                let refs = RefScope::new(node_id, id_map);
+                if let Some(expanded) = expand::expand_node(node, &refs) {
                     for es in expanded {
-                        steps.push(expanded_to_step(es));
+                        steps.push(expanded_to_step(es, node.notes()));
+                    }
+                    if let Err(e) = refs.finish() {
+                        errors.extend(e);
                     }
                 } else {
-                    match lower_node(node, graph, node_map, id_map) {
+                    let lower = || lower_node(node, graph, node_map, id_map, default_chain);
+                    let lowered = match step_cache.as_deref_mut() {
+                        Some(cache) => cache.get_or_lower(node_id, lower),
+                        None => lower(),
+                    };
+                    match lowered {
                         Ok(step) => steps.push(step),
                         Err(e) => errors.extend(e),
                     }
@@ -189,6 +325,7 @@ fn build_steps(
     Ok(steps)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_branch(
     if_node_id: &str,
     if_config: &crate::parse::types::IfConfig,
@@ -196,7 +333,10 @@ fn build_branch(
     node_map: &HashMap<&str, &WorkflowNode>,
     graph: &WorkflowGraph,
     id_map: &HashMap<String, String>,
+    default_chain: Option<&str>,
     consumed: &mut HashSet<String>,
+    mut step_cache: Option<&mut StepCache>,
+    cancel: Option<&CancellationToken>,
 ) -> Result<Vec<Step>, Vec<CompilerError>> {
     let mut result_steps = Vec::new();
 
@@ -204,11 +344,19 @@ fn build_branch(
     let edges = graph.outgoing_edges(if_node_id);
     let mut true_target = None;
     let mut false_target = None;
+    let mut true_label = None;
+    let mut false_label = None;
 
     for (target, label) in &edges {
+        // One line, since it ends up in a `//` comment.
+        let arm_label = label
+            .label
+            .as_deref()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|l| !l.is_empty());
         match label.source_handle.as_deref() {
-            Some("true") => true_target = Some(*target),
-            Some("false") => false_target = Some(*target),
+            Some("true") => (true_target, true_label) = (Some(*target), arm_label),
+            Some("false") => (false_target, false_label) = (Some(*target), arm_label),
             _ => {}
         }
     }
@@ -238,37 +386,41 @@ fn build_branch(
 
     // Build true branch steps
     let true_refs: Vec<&str> = true_nodes.iter().map(|s| s.as_str()).collect();
-    let true_steps = build_steps(&true_refs, node_map, graph, id_map, consumed)?;
+    let true_steps = build_steps(
+        &true_refs,
+        node_map,
+        graph,
+        id_map,
+        default_chain,
+        consumed,
+        step_cache.as_deref_mut(),
+        cancel,
+    )?;
     let true_block = Block { steps: true_steps };
 
     // Build false branch steps
     let false_refs: Vec<&str> = false_nodes.iter().map(|s| s.as_str()).collect();
-    let false_steps = build_steps(&false_refs, node_map, graph, id_map, consumed)?;
+    let false_steps = build_steps(
+        &false_refs,
+        node_map,
+        graph,
+        id_map,
+        default_chain,
+        consumed,
+        step_cache,
+        cancel,
+    )?;
     let false_block = Block { steps: false_steps };
 
     // Build conditions
-    let conditions: Vec<ConditionIR> = if_config
-        .conditions
-        .iter()
-        .map(|c| {
-            let field = resolve_value_expr(&c.field, id_map);
-            let value = c.value.as_ref().map(|v| resolve_value_expr(v, id_map));
-            let operator = parse_comparison_op(&c.operator);
-            ConditionIR {
-                field,
-                operator,
-                value,
-            }
-        })
-        .collect();
+    let refs = RefScope::new(if_node_id, id_map);
+    let conditions = lower_conditions(if_node_id, &if_config.conditions, &refs)?;
+    refs.finish()?;
 
-    let combine_with = if if_config.combine_with == "or" {
-        LogicCombinator::Or
-    } else {
-        LogicCombinator::And
-    };
+    let combine_with = parse_combinator(&if_config.combine_with);
 
     let reconverge_at = merge_node_id.clone();
+    let arm_results = [arm_result(&true_block), arm_result(&false_block)];
 
     let branch_step = Step {
         id: if_node_id.to_string(),
@@ -282,9 +434,15 @@ fn build_branch(
             combine_with,
             true_branch: true_block,
             false_branch: false_block,
+            true_label,
+            false_label,
             reconverge_at: reconverge_at.clone(),
         }),
         output: None,
+        notes: node_map
+            .get(if_node_id)
+            .and_then(|n| n.notes())
+            .map(str::to_string),
     };
 
     result_steps.push(branch_step);
@@ -294,6 +452,7 @@ fn build_branch(
         consumed.insert(merge_id.clone());
 
         // Determine merge inputs from the last step of each branch
+        let [true_result, false_result] = arm_results;
         let merge_step = Step {
             id: merge_id.clone(),
             source_node_ids: vec![merge_id.clone()],
@@ -302,16 +461,16 @@ fn build_branch(
                 .map(|n| n.label().to_string())
                 .unwrap_or_else(|| merge_id.clone()),
             operation: Operation::Merge(MergeOp {
-                branch_step_id: if_node_id.to_string(),
+                branch_step_id: Some(if_node_id.to_string()),
                 strategy: MergeStrategy::PassThrough,
                 inputs: vec![
                     MergeInput {
                         handle_name: "true".into(),
-                        value: ValueExpr::raw("/* true branch result */"),
+                        value: true_result,
                     },
                     MergeInput {
                         handle_name: "false".into(),
-                        value: ValueExpr::raw("/* false branch result */"),
+                        value: false_result,
                     },
                 ],
             }),
@@ -320,6 +479,10 @@ fn build_branch(
                 ts_type: "any".into(),
                 destructure_fields: None,
             }),
+            notes: node_map
+                .get(merge_id.as_str())
+                .and_then(|n| n.notes())
+                .map(str::to_string),
         };
 
         result_steps.push(merge_step);
@@ -328,6 +491,21 @@ fn build_branch(
     Ok(result_steps)
 }
 
+/// What a branch arm hands to its merge: the output of its last step, or
+/// `null` when that step has none (or the arm is empty).
+fn arm_result(arm: &Block) -> ValueExpr {
+    match arm.steps.last() {
+        Some(last) => match &last.output {
+            Some(output) if output.destructure_fields.is_some() => {
+                ValueExpr::raw(output.destructured_object())
+            }
+            Some(_) => ValueExpr::binding(last.id.clone(), ""),
+            None => ValueExpr::null(),
+        },
+        None => ValueExpr::null(),
+    }
+}
+
 /// Find the reconvergence point (merge node) for a branch.
 /// This is the first node in topo order that is reachable from both true and false targets.
 fn find_reconvergence(
@@ -383,6 +561,10 @@ fn collect_branch_nodes(
     }
 
     let reachable = collect_reachable(start, graph);
+    // The merge and everything after it belong to the enclosing block
+    let after_merge = merge_id
+        .map(|id| collect_reachable(id, graph))
+        .unwrap_or_default();
     let mut branch_nodes = Vec::new();
 
     // Walk in topo order, collecting nodes that are reachable from start
@@ -391,7 +573,7 @@ fn collect_branch_nodes(
         if consumed.contains(*node_id) {
             continue;
         }
-        if Some(*node_id) == merge_id {
+        if after_merge.contains(*node_id) {
             continue;
         }
         if reachable.contains(*node_id) {
@@ -402,17 +584,20 @@ fn collect_branch_nodes(
     branch_nodes
 }
 
-fn expanded_to_step(es: ExpandedStep) -> Step {
+fn expanded_to_step(es: ExpandedStep, notes: Option<&str>) -> Step {
     Step {
         id: es.id,
         source_node_ids: vec![es.source_node_id],
         label: es.label,
         operation: es.operation,
         output: es.output,
+        notes: notes.map(str::to_string),
     }
 }
 
 /// Resolve the input for a node that implicitly consumes its graph predecessor's output.
+/// The source of an edge into the [`DATA_INPUT_HANDLE`] is used when there is
+/// one, otherwise the first predecessor.
 /// `http_field` is used when the predecessor is HttpRequest (e.g. "body"),
 /// `default_field` is used for all other predecessor types.
 fn resolve_predecessor_input(
@@ -423,13 +608,28 @@ fn resolve_predecessor_input(
     node_map: &HashMap<&str, &WorkflowNode>,
     id_map: &HashMap<String, String>,
 ) -> ValueExpr {
-    let preds = graph.predecessors(node_id);
-    let Some(pred_id) = preds.first() else {
+    let explicit = graph
+        .incoming_edges(node_id)
+        .into_iter()
+        .find(|(_, label)| label.target_handle.as_deref() == Some(DATA_INPUT_HANDLE))
+        .map(|(source, _)| source);
+    let Some(pred_id) = explicit.or_else(|| graph.predecessors(node_id).first().copied()) else {
         return ValueExpr::raw("/* no predecessor */");
     };
+    resolve_node_output(pred_id, http_field, default_field, node_map, id_map)
+}
 
+/// The output of node `pred_id`, as read by a node downstream of it. Fields
+/// are chosen as in [`resolve_predecessor_input`].
+pub(super) fn resolve_node_output(
+    pred_id: &str,
+    http_field: &str,
+    default_field: &str,
+    node_map: &HashMap<&str, &WorkflowNode>,
+    id_map: &HashMap<String, String>,
+) -> ValueExpr {
     // If predecessor is a trigger node, reference triggerData instead of a step binding
-    if let Some(pred_node) = node_map.get(*pred_id) {
+    if let Some(pred_node) = node_map.get(pred_id) {
         match pred_node.node_type() {
             "cronTrigger" | "httpTrigger" | "evmLogTrigger" => {
                 let field = match pred_node.node_type() {
@@ -450,11 +650,11 @@ fn resolve_predecessor_input(
 
     // Resolve through id_map in case predecessor was a convenience node
     let step_id = id_map
-        .get(*pred_id)
+        .get(pred_id)
         .cloned()
         .unwrap_or_else(|| pred_id.to_string());
 
-    let field = match node_map.get(*pred_id).map(|n| n.node_type()) {
+    let field = match node_map.get(pred_id).map(|n| n.node_type()) {
         Some("httpRequest") | Some("ai") => http_field,
         _ => default_field,
     };
@@ -468,26 +668,35 @@ fn lower_node(
     graph: &WorkflowGraph,
     node_map: &HashMap<&str, &WorkflowNode>,
     id_map: &HashMap<String, String>,
+    default_chain: Option<&str>,
 ) -> Result<Step, Vec<CompilerError>> {
     let node_id = node.id();
     let label = node.label().to_string();
+    let refs = RefScope::new(node_id, id_map);
 
     let (operation, output) = match node {
-        WorkflowNode::HttpRequest(n) => lower_http_request(node_id, &n.data.config, id_map),
-        WorkflowNode::EvmRead(n) => lower_evm_read(node_id, &n.data.config, id_map),
-        WorkflowNode::EvmWrite(n) => lower_evm_write(node_id, &n.data.config, id_map)?,
+        WorkflowNode::HttpRequest(n) => lower_http_request(node_id, &n.data.config, &refs),
+        WorkflowNode::EvmRead(n) => lower_evm_read(node_id, &n.data.config, &refs, default_chain)?,
+        WorkflowNode::EvmWrite(n) => {
+            lower_evm_write(node_id, &n.data.config, &refs, default_chain)?
+        }
         WorkflowNode::CodeNode(n) => {
-            lower_code_node(node_id, &n.data.config, graph, node_map, id_map)
+            lower_code_node(node_id, &n.data.config, graph, node_map, &refs)?
         }
-        WorkflowNode::AbiEncode(n) => lower_abi_encode(node_id, &n.data.config, id_map),
+        WorkflowNode::AbiEncode(n) => lower_abi_encode(node_id, &n.data.config, &refs),
         WorkflowNode::AbiDecode(n) => {
             lower_abi_decode(node_id, &n.data.config, graph, node_map, id_map)
         }
-        WorkflowNode::Filter(n) => lower_filter(node_id, &n.data.config, id_map),
-        WorkflowNode::Ai(n) => lower_ai(node_id, &n.data.config, id_map),
-        WorkflowNode::Error(n) => lower_error(node_id, &n.data.config, id_map),
-        WorkflowNode::Return(n) => lower_return(node_id, &n.data.config, id_map),
-        WorkflowNode::Merge(n) => lower_merge_standalone(node_id, &n.data.config),
+        WorkflowNode::Math(n) => lower_math(node_id, &n.data.config, node_map, &refs)?,
+        WorkflowNode::Filter(n) => lower_filter(node_id, &n.data.config, &refs)?,
+        WorkflowNode::KvGet(n) => lower_kv_get(node_id, &n.data.config, &refs),
+        WorkflowNode::KvSet(n) => lower_kv_set(node_id, &n.data.config, &refs),
+        WorkflowNode::Ai(n) => lower_ai(node_id, &n.data.config, &refs)?,
+        WorkflowNode::Error(n) => lower_error(node_id, &n.data.config, &refs),
+        WorkflowNode::Return(n) => lower_return(node_id, &n.data.config, &refs),
+        WorkflowNode::Merge(n) => {
+            merge::lower_standalone_merge(node_id, &n.data.config, graph, node_map, id_map)?
+        }
         _ => {
             return Err(vec![CompilerError::lower(
                 "L003",
@@ -499,6 +708,7 @@ fn lower_node(
             )]);
         }
     };
+    refs.finish()?;
 
     Ok(Step {
         id: node_id.to_string(),
@@ -506,6 +716,7 @@ fn lower_node(
         label,
         operation,
         output,
+        notes: node.notes().map(str::to_string),
     })
 }
 
@@ -516,7 +727,7 @@ fn lower_node(
 fn lower_http_request(
     node_id: &str,
     config: &crate::parse::types::HttpRequestConfig,
-    id_map: &HashMap<String, String>,
+    refs: &RefScope,
 ) -> (Operation, Option<OutputBinding>) {
     let method = match config.method.to_uppercase().as_str() {
         "GET" => HttpMethod::Get,
@@ -528,14 +739,14 @@ fn lower_http_request(
         _ => HttpMethod::Get,
     };
 
-    let url = resolve_value_expr(&config.url, id_map);
+    let url = refs.resolve(&config.url, field("url"));
 
     let headers: Vec<(String, ValueExpr)> = config
         .headers
         .as_ref()
         .map(|h| {
             h.iter()
-                .map(|(k, v)| (k.clone(), resolve_value_expr(v, id_map)))
+                .map(|(k, v)| (k.clone(), refs.resolve(v, keyed_field("headers", k))))
                 .collect()
         })
         .unwrap_or_default();
@@ -545,7 +756,12 @@ fn lower_http_request(
         .as_ref()
         .map(|q| {
             q.iter()
-                .map(|(k, v)| (k.clone(), resolve_value_expr(v, id_map)))
+                .map(|(k, v)| {
+                    (
+                        k.clone(),
+                        refs.resolve(v, keyed_field("queryParameters", k)),
+                    )
+                })
                 .collect()
         })
         .unwrap_or_default();
@@ -556,9 +772,28 @@ fn lower_http_request(
             "formUrlEncoded" => HttpContentType::FormUrlEncoded,
             _ => HttpContentType::Raw,
         };
-        HttpBody {
-            content_type,
-            data: resolve_value_expr(&b.data, id_map),
+        let form = match content_type {
+            HttpContentType::FormUrlEncoded => form_fields(&b.data),
+            _ => None,
+        };
+        match form {
+            Some(fields) => HttpBody {
+                content_type,
+                data: ValueExpr::null(),
+                fields: fields
+                    .into_iter()
+                    .map(|(key, value)| (key, refs.resolve(&value, keyed_field("body", "data"))))
+                    .collect(),
+            },
+            None => HttpBody {
+                data: match content_type {
+                    HttpContentType::Json => http_body::json_literal(&b.data),
+                    _ => None,
+                }
+                .unwrap_or_else(|| refs.resolve(&b.data, keyed_field("body", "data"))),
+                content_type,
+                fields: vec![],
+            },
         }
     });
 
@@ -575,10 +810,7 @@ fn lower_http_request(
         _ => HttpResponseFormat::Json,
     };
 
-    let body_ts_type = match response_format {
-        HttpResponseFormat::Json => "any",
-        HttpResponseFormat::Text | HttpResponseFormat::Binary => "string",
-    };
+    let body_ts_type = response_format.body_ts_type();
 
     let op = Operation::HttpRequest(HttpRequestOp {
         method,
@@ -594,6 +826,8 @@ fn lower_http_request(
             .clone()
             .unwrap_or_else(|| vec![200]),
         response_format,
+        follow_redirects: config.follow_redirects.unwrap_or(true),
+        ignore_ssl: config.ignore_ssl.unwrap_or(false),
         consensus: ConsensusStrategy::Identical,
     });
 
@@ -609,71 +843,209 @@ fn lower_http_request(
     (op, output)
 }
 
+/// The pairs of a form body written as a JSON object, e.g.
+/// `{ "amount": {{h1.body.amount}}, "memo": "paid {{trigger.id}}" }`, with
+/// each value as text that may hold references. A reference may stand
+/// unquoted as a value. `None` when `data` is not such an object: it is then
+/// sent as the pre-built string it is. Keys come out sorted.
+fn form_fields(data: &str) -> Option<Vec<(String, String)>> {
+    // Swap each reference for a placeholder so the text parses as JSON.
+    let mut json = String::new();
+    let mut references = Vec::new();
+    let (mut in_string, mut escaped) = (false, false);
+    let mut rest = data.trim();
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{")
+            && let Some(end) = rest.find("}}")
+        {
+            let placeholder = format!("\\u0000{}\\u0000", references.len());
+            references.push(&rest[..end + 2]);
+            if in_string {
+                json.push_str(&placeholder);
+            } else {
+                json.push_str(&format!("\"{}\"", placeholder));
+            }
+            rest = &rest[end + 2..];
+            continue;
+        }
+        json.push(c);
+        if escaped {
+            escaped = false;
+        } else if in_string && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_string = !in_string;
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    let Ok(serde_json::Value::Object(object)) = serde_json::from_str(&json) else {
+        return None;
+    };
+    // Nested values are re-serialized, which escapes the placeholder.
+    let restore = |text: String| {
+        (0..references.len()).fold(text, |text, i| {
+            text.replace(&format!("\u{0}{}\u{0}", i), references[i])
+                .replace(&format!("\\u0000{}\\u0000", i), references[i])
+        })
+    };
+    Some(
+        object
+            .into_iter()
+            .map(|(key, value)| {
+                let text = match value {
+                    serde_json::Value::String(s) => s,
+                    serde_json::Value::Null => String::new(),
+                    other => other.to_string(),
+                };
+                (key, restore(text))
+            })
+            .collect(),
+    )
+}
+
 fn lower_evm_read(
     node_id: &str,
     config: &crate::parse::types::EvmReadConfig,
-    id_map: &HashMap<String, String>,
-) -> (Operation, Option<OutputBinding>) {
+    refs: &RefScope,
+    default_chain: Option<&str>,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
     use super::trigger::make_evm_binding_name;
 
-    let binding_name = make_evm_binding_name(&config.chain_selector_name);
+    let chain = resolve_node_chain(
+        node_id,
+        config.chain_selector_name.as_deref(),
+        default_chain,
+    )?;
+    let binding_name = make_evm_binding_name(chain);
     let abi_json = serde_json::to_string(&config.abi).unwrap_or_default();
 
     let args: Vec<EvmArg> = config
         .args
         .iter()
-        .map(|a| EvmArg {
+        .enumerate()
+        .map(|(i, a)| EvmArg {
             abi_type: a.abi_type.clone(),
-            value: resolve_value_expr(&a.value, id_map),
+            value: refs.resolve(&a.value, indexed_field("args", i, "value")),
         })
         .collect();
 
-    let output_names: Vec<String> = config
-        .abi
-        .outputs
-        .iter()
-        .enumerate()
-        .map(|(i, o)| {
-            if !o.name.is_empty() {
-                o.name.clone()
-            } else if config.abi.outputs.len() == 1 {
-                "value".to_string()
-            } else {
-                format!("output{}", i)
-            }
-        })
-        .collect();
+    let output_names = evm_read_output_names(&config.abi);
+    let output = Some(OutputBinding {
+        variable_name: format!("step_{}", node_id.replace('-', "_")),
+        ts_type: evm_read_output_type(&config.abi, &output_names),
+        destructure_fields: destructures_evm_read(&config.abi).then(|| output_names.clone()),
+    });
 
     let op = Operation::EvmRead(EvmReadOp {
         evm_client_binding: binding_name,
-        contract_address: resolve_value_expr(&config.contract_address, id_map),
+        contract_address: refs.resolve(&config.contract_address, field("contractAddress")),
         function_name: config.function_name.clone(),
         abi_json,
         args,
         from_address: config
             .from_address
             .as_ref()
-            .map(|a| resolve_value_expr(a, id_map)),
+            .map(|a| refs.resolve(a, field("fromAddress"))),
         block_number: config
             .block_number
-            .as_ref()
-            .map(|b| resolve_value_expr(b, id_map)),
+            .as_deref()
+            .and_then(|b| lower_block_number(b, refs)),
         output_names,
     });
 
-    let output = Some(OutputBinding {
-        variable_name: format!("step_{}", node_id.replace('-', "_")),
-        ts_type: "any".into(),
-        destructure_fields: None,
-    });
+    Ok((op, output))
+}
 
-    (op, output)
+/// A tag (any case) becomes its lowercase name and a decimal block number an
+/// integer; anything else is a reference. Empty means the default block.
+fn lower_block_number(raw: &str, refs: &RefScope) -> Option<ValueExpr> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let tag = trimmed.to_ascii_lowercase();
+    if EVM_BLOCK_TAGS.contains(&tag.as_str()) {
+        return Some(ValueExpr::string(tag));
+    }
+    match trimmed.parse::<i64>() {
+        Ok(number) if number >= 0 => Some(ValueExpr::integer(number)),
+        _ => Some(refs.resolve(trimmed, field("blockNumber"))),
+    }
+}
+
+/// A read with several outputs, all named with distinct identifiers, binds
+/// each output to its own local instead of an object.
+fn destructures_evm_read(abi: &AbiFunction) -> bool {
+    let mut seen = HashSet::new();
+    abi.outputs.len() > 1
+        && abi.outputs.iter().all(|o| {
+            let mut chars = o.name.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+                && seen.insert(o.name.as_str())
+        })
+}
+
+/// `{ balance: bigint; owner: string }` for a read with several outputs,
+/// `any` for a single one. Types follow viem's decoding; arrays and tuples
+/// are `any`.
+fn evm_read_output_type(abi: &AbiFunction, output_names: &[String]) -> String {
+    if abi.outputs.len() <= 1 {
+        return "any".into();
+    }
+    let members: Vec<String> = abi
+        .outputs
+        .iter()
+        .zip(output_names)
+        .map(|(o, name)| format!("{}: {}", name, abi_value_ts_type(&o.abi_type)))
+        .collect();
+    format!("{{ {} }}", members.join("; "))
+}
+
+pub(super) fn abi_value_ts_type(abi_type: &str) -> &'static str {
+    if abi_type.ends_with(']') || abi_type.starts_with('(') || abi_type.starts_with("tuple") {
+        return "any";
+    }
+    match abi_type
+        .strip_prefix("uint")
+        .or_else(|| abi_type.strip_prefix("int"))
+    {
+        // viem decodes integers of up to 48 bits as numbers.
+        Some(bits) if bits.parse::<u32>().is_ok_and(|bits| bits <= 48) => "number",
+        Some(_) => "bigint",
+        None if abi_type == "bool" => "boolean",
+        None if abi_type == "address" || abi_type == "string" || abi_type.starts_with("bytes") => {
+            "string"
+        }
+        None => "any",
+    }
+}
+
+/// Normalized EvmRead output field names, matching frontend `normalizeReadResult`.
+fn evm_read_output_names(abi: &AbiFunction) -> Vec<String> {
+    abi.outputs
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            if !o.name.is_empty() {
+                o.name.clone()
+            } else if abi.outputs.len() == 1 {
+                "value".to_string()
+            } else {
+                format!("output{}", i)
+            }
+        })
+        .collect()
 }
 
 fn lower_evm_write(
     node_id: &str,
     config: &crate::parse::types::EvmWriteConfig,
-    id_map: &HashMap<String, String>,
+    refs: &RefScope,
+    default_chain: Option<&str>,
 ) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
     use super::trigger::make_evm_binding_name;
 
@@ -685,17 +1057,25 @@ fn lower_evm_write(
         )]);
     }
 
-    let binding_name = make_evm_binding_name(&config.chain_selector_name);
+    let chain = resolve_node_chain(
+        node_id,
+        config.chain_selector_name.as_deref(),
+        default_chain,
+    )?;
+    let binding_name = make_evm_binding_name(chain);
     let gas_limit: i64 = config.gas_limit.parse().unwrap_or(500_000);
 
-    let encoded_data = resolve_value_expr(&config.encoded_data, id_map);
+    let encoded_data = refs.resolve(&config.encoded_data, field("encodedData"));
 
     let op = Operation::EvmWrite(EvmWriteOp {
         evm_client_binding: binding_name,
-        receiver_address: resolve_value_expr(&config.receiver_address, id_map),
+        receiver_address: refs.resolve(&config.receiver_address, field("receiverAddress")),
         gas_limit: ValueExpr::integer(gas_limit),
         encoded_data,
-        value_wei: config.value.as_ref().map(|v| resolve_value_expr(v, id_map)),
+        value_wei: config
+            .value
+            .as_ref()
+            .map(|v| refs.resolve(v, field("value"))),
     });
 
     let output = Some(OutputBinding {
@@ -707,13 +1087,35 @@ fn lower_evm_write(
     Ok((op, output))
 }
 
+/// Resolve an EVM node's chain, falling back to the workflow default.
+fn resolve_node_chain<'a>(
+    node_id: &str,
+    node_chain: Option<&'a str>,
+    default_chain: Option<&'a str>,
+) -> Result<&'a str, Vec<CompilerError>> {
+    resolve_chain_selector(node_chain, default_chain).ok_or_else(|| {
+        let message = if node_chain.is_some() {
+            "EVM node has an empty chain selector — pick a chain, or remove the field to use \
+             the workflow's default chain"
+        } else {
+            "EVM node has no chain selector and the workflow has no default chain — \
+             set 'Chain' on the node or a default chain in workflow settings"
+        };
+        vec![CompilerError::lower(
+            "L005",
+            message,
+            Some(node_id.to_string()),
+        )]
+    })
+}
+
 fn lower_code_node(
     node_id: &str,
     config: &crate::parse::types::CodeNodeConfig,
     graph: &WorkflowGraph,
     node_map: &HashMap<&str, &WorkflowNode>,
-    id_map: &HashMap<String, String>,
-) -> (Operation, Option<OutputBinding>) {
+    refs: &RefScope,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
     // Whole-object aliases: const NodeLabel = step_x;  (one per upstream non-trigger node)
     let mut seen_aliases: HashSet<String> = HashSet::new();
     let mut node_aliases: Vec<CodeInputBinding> = Vec::new();
@@ -726,7 +1128,8 @@ fn lower_code_node(
         }
         let alias = sanitize_label(pred_node.label());
         if seen_aliases.insert(alias.clone()) {
-            let step_id = id_map
+            let step_id = refs
+                .id_map()
                 .get(pred_id)
                 .cloned()
                 .unwrap_or_else(|| pred_id.to_string());
@@ -740,62 +1143,234 @@ fn lower_code_node(
     let explicit_bindings: Vec<CodeInputBinding> = config
         .input_variables
         .iter()
-        .map(|var| {
+        .enumerate()
+        .map(|(i, var)| {
             // Input variables reference previous step outputs
-            let value = resolve_value_expr(var, id_map);
+            let value = refs.resolve(var, indexed_field("inputVariables", i, ""));
             CodeInputBinding {
-                variable_name: var.replace("{{", "").replace("}}", "").replace('.', "_"),
+                variable_name: code_variable_name(var),
                 value,
             }
         })
         .collect();
 
-    let input_bindings: Vec<CodeInputBinding> =
-        node_aliases.into_iter().chain(explicit_bindings).collect();
-
     let execution_mode = if config.execution_mode == "runOnceForEach" {
         CodeExecutionMode::RunOnceForEach
     } else {
         CodeExecutionMode::RunOnceForAll
     };
 
+    // The iteration source must be one of the declared input variables.
+    let iterate_over = match execution_mode {
+        CodeExecutionMode::RunOnceForAll => None,
+        CodeExecutionMode::RunOnceForEach => {
+            let Some(source) = config.iterate_over.as_deref() else {
+                return Err(vec![CompilerError::lower(
+                    "L014",
+                    format!(
+                        "Code node '{}' runs once for each item but does not set iterateOver",
+                        node_id
+                    ),
+                    Some(node_id.to_string()),
+                )]);
+            };
+            let name = code_variable_name(source.trim());
+            if !explicit_bindings.iter().any(|b| b.variable_name == name) {
+                return Err(vec![CompilerError::lower(
+                    "L015",
+                    format!(
+                        "Code node '{}' iterates over '{}', which is not one of its input variables",
+                        node_id, source
+                    ),
+                    Some(node_id.to_string()),
+                )
+                .with_path(config_path(field("iterateOver")))]);
+            }
+            Some(name)
+        }
+    };
+
+    let input_bindings: Vec<CodeInputBinding> =
+        node_aliases.into_iter().chain(explicit_bindings).collect();
+
     let output_fields: Vec<String> = config.output_fields.iter().map(|f| f.key.clone()).collect();
 
+    // One result per item when mapping.
+    let ts_type = if iterate_over.is_some() {
+        "any[]"
+    } else {
+        "any"
+    };
+
     let op = Operation::CodeNode(CodeNodeOp {
         code: config.code.clone(),
         input_bindings,
         execution_mode,
+        iterate_over,
         timeout_ms: config.timeout,
         output_fields,
     });
 
     let output = Some(OutputBinding {
         variable_name: format!("step_{}", node_id.replace('-', "_")),
-        ts_type: "any".into(),
+        ts_type: ts_type.into(),
         destructure_fields: None,
     });
 
-    (op, output)
+    Ok((op, output))
 }
 
-fn lower_abi_encode(
+/// Local name a code node input variable is bound to: `{{h1.body}}` → `h1_body`.
+fn code_variable_name(var: &str) -> String {
+    var.replace("{{", "").replace("}}", "").replace('.', "_")
+}
+
+fn lower_math(
+    node_id: &str,
+    config: &crate::parse::types::MathConfig,
+    node_map: &HashMap<&str, &WorkflowNode>,
+    refs: &RefScope,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
+    let math_error = |message: String| {
+        vec![CompilerError::lower(
+            "L006",
+            message,
+            Some(node_id.to_string()),
+        )]
+    };
+
+    refs.check(&config.expression, field("expression"));
+    let mut expr = parse_compute_expr(&config.expression, refs.id_map())
+        .map_err(|e| math_error(format!("Invalid math expression: {}", e)))?;
+
+    let mode = match config.mode.as_deref() {
+        Some("number") => NumericMode::Number,
+        Some("bigint") => NumericMode::BigInt,
+        _ if has_evm_integer_operand(&expr, node_map) => NumericMode::BigInt,
+        _ => NumericMode::Number,
+    };
+
+    if mode == NumericMode::BigInt {
+        normalize_bigint_literals(&mut expr).map_err(|literal| {
+            math_error(format!(
+                "Literal '{}' is not a whole number of at most {} digits, which BigInt math \
+                 cannot represent",
+                literal, MAX_INTEGER_LITERAL_DIGITS
+            ))
+        })?;
+    }
+
+    let ts_type = match mode {
+        NumericMode::Number => "number",
+        NumericMode::BigInt => "bigint",
+    };
+
+    let op = Operation::Compute(ComputeOp { expr, mode });
+
+    let output = Some(OutputBinding {
+        variable_name: format!("step_{}", node_id.replace('-', "_")),
+        ts_type: ts_type.into(),
+        destructure_fields: None,
+    });
+
+    Ok((op, output))
+}
+
+/// True if any operand reads an integer (`uintN`/`intN`) output of an EvmRead node.
+/// Those arrive as `bigint`, so the whole expression must use BigInt math.
+fn has_evm_integer_operand(expr: &ComputeExpr, node_map: &HashMap<&str, &WorkflowNode>) -> bool {
+    match expr {
+        ComputeExpr::Literal { .. } => false,
+        ComputeExpr::Value { value } => {
+            let ValueExpr::Binding(binding) = value else {
+                return false;
+            };
+            let Some(WorkflowNode::EvmRead(n)) = node_map.get(binding.step_id.as_str()) else {
+                return false;
+            };
+            let abi = &n.data.config.abi;
+            let field = binding.field_path.split('.').next().unwrap_or("");
+            let output = if field.is_empty() && abi.outputs.len() == 1 {
+                abi.outputs.first()
+            } else {
+                evm_read_output_names(abi)
+                    .iter()
+                    .position(|name| name == field)
+                    .and_then(|i| abi.outputs.get(i))
+            };
+            output.is_some_and(|o| o.abi_type.starts_with("uint") || o.abi_type.starts_with("int"))
+        }
+        ComputeExpr::Neg { operand } => has_evm_integer_operand(operand, node_map),
+        ComputeExpr::Binary { left, right, .. } => {
+            has_evm_integer_operand(left, node_map) || has_evm_integer_operand(right, node_map)
+        }
+    }
+}
+
+/// Rewrite literals to plain integer digits for BigInt emission.
+/// Returns the offending literal if one isn't a whole number.
+fn normalize_bigint_literals(expr: &mut ComputeExpr) -> Result<(), String> {
+    match expr {
+        ComputeExpr::Literal { value } => {
+            *value = integer_literal(value).ok_or_else(|| value.clone())?;
+            Ok(())
+        }
+        ComputeExpr::Value { .. } => Ok(()),
+        ComputeExpr::Neg { operand } => normalize_bigint_literals(operand),
+        ComputeExpr::Binary { left, right, .. } => {
+            normalize_bigint_literals(left)?;
+            normalize_bigint_literals(right)
+        }
+    }
+}
+
+pub(super) fn lower_abi_encode(
     node_id: &str,
     config: &crate::parse::types::AbiEncodeConfig,
-    id_map: &HashMap<String, String>,
+    refs: &RefScope,
 ) -> (Operation, Option<OutputBinding>) {
-    let abi_json = serde_json::to_string(&config.abi_params).unwrap_or_default();
+    let function_name = config
+        .function_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string);
+
+    // Function-call encoding needs a full function ABI item; parameter-only
+    // encoding takes the bare parameter array.
+    let abi_json = match &function_name {
+        Some(name) => serde_json::to_string(&AbiFunction {
+            abi_type: "function".into(),
+            name: name.clone(),
+            inputs: config.abi_params.clone(),
+            outputs: vec![],
+            state_mutability: "nonpayable".into(),
+        }),
+        None => serde_json::to_string(&config.abi_params),
+    }
+    .unwrap_or_default();
 
-    let data_mappings: Vec<AbiDataMapping> = config
+    let mut data_mappings: Vec<AbiDataMapping> = config
         .data_mapping
         .iter()
-        .map(|m| AbiDataMapping {
+        .enumerate()
+        .map(|(i, m)| AbiDataMapping {
             param_name: m.param_name.clone(),
-            value: resolve_value_expr(&m.source, id_map),
+            value: refs.resolve(&m.source, indexed_field("dataMapping", i, "source")),
+            value_kind: None,
         })
         .collect();
+    // Codegen emits args in mapping order; align it with the ABI parameter order.
+    data_mappings.sort_by_key(|m| {
+        config
+            .abi_params
+            .iter()
+            .position(|p| p.name == m.param_name)
+            .unwrap_or(usize::MAX)
+    });
 
     let op = Operation::AbiEncode(AbiEncodeOp {
-        function_name: None,
+        function_name,
         abi_json,
         data_mappings,
     });
@@ -835,63 +1410,75 @@ fn lower_abi_decode(
 }
 
 fn lower_filter(
-    _node_id: &str,
+    node_id: &str,
     config: &crate::parse::types::FilterConfig,
-    id_map: &HashMap<String, String>,
-) -> (Operation, Option<OutputBinding>) {
-    let conditions: Vec<ConditionIR> = config
-        .conditions
-        .iter()
-        .map(|c| {
-            let field = resolve_value_expr(&c.field, id_map);
-            let value = c.value.as_ref().map(|v| resolve_value_expr(v, id_map));
-            let operator = parse_comparison_op(&c.operator);
-            ConditionIR {
-                field,
-                operator,
-                value,
-            }
-        })
-        .collect();
+    refs: &RefScope,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
+    let conditions = lower_conditions(node_id, &config.conditions, refs)?;
 
-    let combine_with = if config.combine_with == "or" {
-        LogicCombinator::Or
-    } else {
-        LogicCombinator::And
+    let combine_with = parse_combinator(&config.combine_with);
+
+    let non_match_behavior = match config.non_match_behavior {
+        FilterNonMatchMode::EarlyReturn => FilterNonMatchBehavior::EarlyReturn {
+            message: match config.non_match_message.as_deref() {
+                Some(message) if !message.trim().is_empty() => {
+                    refs.resolve(message, field("nonMatchMessage"))
+                }
+                _ => ValueExpr::string(FILTER_NON_MATCH_MESSAGE),
+            },
+        },
+        FilterNonMatchMode::Skip => FilterNonMatchBehavior::Skip,
     };
 
     let op = Operation::Filter(FilterOp {
         conditions,
         combine_with,
-        non_match_behavior: FilterNonMatchBehavior::EarlyReturn {
-            message: "Filter condition not met".into(),
-        },
+        non_match_behavior,
     });
 
-    (op, None)
+    Ok((op, None))
 }
 
 fn lower_ai(
     node_id: &str,
     config: &crate::parse::types::AiNodeConfig,
-    id_map: &HashMap<String, String>,
-) -> (Operation, Option<OutputBinding>) {
+    refs: &RefScope,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
+    let provider = AiProvider::parse(&config.provider).ok_or_else(|| {
+        vec![CompilerError::lower(
+            "L008",
+            format!("Unsupported AI provider '{}'", config.provider),
+            Some(node_id.to_string()),
+        )]
+    })?;
+
     let response_format = match config.response_format.as_deref() {
         Some("json") => AiResponseFormat::Json,
         _ => AiResponseFormat::Text,
     };
 
+    // Empty model / base URL fall back to the provider's defaults.
+    let model = match (config.model.trim(), provider.default_model()) {
+        ("", Some(default)) => default,
+        _ => config.model.as_str(),
+    };
+    let base_url = match config.base_url.trim() {
+        "" => provider.default_base_url(model).unwrap_or_default(),
+        _ => config.base_url.clone(),
+    };
+
+    let consensus = ai_consensus(&provider, config);
     let op = Operation::AiCall(AiCallOp {
-        provider: config.provider.clone(),
-        base_url: resolve_value_expr(&config.base_url, id_map),
-        model: resolve_value_expr(&config.model, id_map),
+        provider,
+        base_url: refs.resolve(&base_url, field("baseUrl")),
+        model: refs.resolve(model, field("model")),
         api_key_secret: config.api_key_secret.clone(),
-        system_prompt: resolve_value_expr(&config.system_prompt, id_map),
-        user_prompt: resolve_value_expr(&config.user_prompt, id_map),
+        system_prompt: refs.resolve(&config.system_prompt, field("systemPrompt")),
+        user_prompt: refs.resolve(&config.user_prompt, field("userPrompt")),
         temperature: config.temperature,
         max_tokens: config.max_tokens,
         response_format,
-        consensus: ConsensusStrategy::Identical,
+        consensus,
     });
 
     let output = Some(OutputBinding {
@@ -900,16 +1487,70 @@ fn lower_ai(
         destructure_fields: None,
     });
 
+    Ok((op, output))
+}
+
+/// Provider responses carry per-request ids, timestamps and token usage,
+/// so by default nodes only have to agree on the generated content.
+fn ai_consensus(
+    provider: &AiProvider,
+    config: &crate::parse::types::AiNodeConfig,
+) -> ConsensusStrategy {
+    match config.consensus {
+        AiConsensusMode::Content => ConsensusStrategy::IdenticalFields {
+            fields: vec![provider.content_field().to_string()],
+        },
+        AiConsensusMode::Identical => ConsensusStrategy::Identical,
+        AiConsensusMode::Custom => ConsensusStrategy::Custom {
+            expr: config
+                .consensus_expression
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        },
+    }
+}
+
+/// The output is `undefined` until some execution has set the key.
+fn lower_kv_get(
+    node_id: &str,
+    config: &crate::parse::types::KvGetConfig,
+    refs: &RefScope,
+) -> (Operation, Option<OutputBinding>) {
+    let op = Operation::KvRead(KvReadOp {
+        key: refs.resolve(&config.key, field("key")),
+    });
+
+    let output = Some(OutputBinding {
+        variable_name: format!("step_{}", node_id.replace('-', "_")),
+        ts_type: "string | undefined".into(),
+        destructure_fields: None,
+    });
+
     (op, output)
 }
 
+fn lower_kv_set(
+    _node_id: &str,
+    config: &crate::parse::types::KvSetConfig,
+    refs: &RefScope,
+) -> (Operation, Option<OutputBinding>) {
+    let op = Operation::KvWrite(KvWriteOp {
+        key: refs.resolve(&config.key, field("key")),
+        value: refs.resolve(&config.value, field("value")),
+    });
+
+    (op, None)
+}
+
 fn lower_error(
     _node_id: &str,
     config: &crate::parse::types::ErrorConfig,
-    id_map: &HashMap<String, String>,
+    refs: &RefScope,
 ) -> (Operation, Option<OutputBinding>) {
     let op = Operation::ErrorThrow(ErrorThrowOp {
-        message: resolve_value_expr(&config.error_message, id_map),
+        message: refs.resolve(&config.error_message, field("errorMessage")),
     });
 
     (op, None)
@@ -918,34 +1559,94 @@ fn lower_error(
 fn lower_return(
     _node_id: &str,
     config: &crate::parse::types::ReturnConfig,
-    id_map: &HashMap<String, String>,
+    refs: &RefScope,
 ) -> (Operation, Option<OutputBinding>) {
     let op = Operation::Return(ReturnOp {
-        expression: resolve_value_expr(&config.return_expression, id_map),
+        expression: refs.resolve(&config.return_expression, field("returnExpression")),
+        auto_generated: false,
     });
 
     (op, None)
 }
 
-fn lower_merge_standalone(
+/// Lower `if` / `filter` conditions. Binary operators without a right-hand
+/// value are rejected here rather than producing a half-written comparison.
+fn lower_conditions(
     node_id: &str,
-    _config: &crate::parse::types::MergeConfig,
-) -> (Operation, Option<OutputBinding>) {
-    // Standalone merge nodes (not paired with an if-branch) are treated as pass-through.
-    // The branch builder handles if-associated merges.
-    let op = Operation::Merge(MergeOp {
-        branch_step_id: "unknown".into(),
-        strategy: MergeStrategy::PassThrough,
-        inputs: vec![],
-    });
+    conditions: &[ConditionNode],
+    refs: &RefScope,
+) -> Result<Vec<ConditionTree>, Vec<CompilerError>> {
+    lower_condition_list(node_id, conditions, &[], "", refs)
+}
 
-    let output = Some(OutputBinding {
-        variable_name: format!("step_{}", node_id.replace('-', "_")),
-        ts_type: "any".into(),
-        destructure_fields: None,
-    });
+/// Lower one `conditions` list. `list_path` is the path of the enclosing
+/// group below `data.config` and `label_prefix` its number ("2." for the
+/// second entry), so nested conditions are reported as "Condition 2.1".
+fn lower_condition_list(
+    node_id: &str,
+    conditions: &[ConditionNode],
+    list_path: &[JsonPathSegment],
+    label_prefix: &str,
+    refs: &RefScope,
+) -> Result<Vec<ConditionTree>, Vec<CompilerError>> {
+    conditions
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let path = |name: &str| {
+                let mut path = list_path.to_vec();
+                path.extend(indexed_field("conditions", i, name));
+                path
+            };
+            let label = format!("{}{}", label_prefix, i + 1);
+            let c = match node {
+                ConditionNode::Group(group) => {
+                    let children = lower_condition_list(
+                        node_id,
+                        &group.conditions,
+                        &path(""),
+                        &format!("{}.", label),
+                        refs,
+                    )?;
+                    return Ok(ConditionTree::Group {
+                        combinator: parse_combinator(&group.combine_with),
+                        children,
+                    });
+                }
+                ConditionNode::Leaf(c) => c,
+            };
+            let field = refs.resolve(&c.field, path("field"));
+            let value = c.value.as_ref().map(|v| refs.resolve(v, path("value")));
+            let operator = parse_comparison_op(&c.operator);
+            if value.is_none() && !operator.is_unary() {
+                return Err(vec![CompilerError::lower(
+                    "L007",
+                    format!(
+                        "Condition {} uses operator '{}', which needs a value to compare against",
+                        label, c.operator
+                    ),
+                    Some(node_id.to_string()),
+                )]);
+            }
+            Ok(ConditionTree::Leaf(ConditionIR {
+                field,
+                operator,
+                value,
+                field_kind: None,
+                value_kind: None,
+            }))
+        })
+        .collect()
+}
 
-    (op, output)
+/// `combineWith` of an `if` / `filter` node or condition group. Anything
+/// but "or" means AND.
+fn parse_combinator(combine_with: &str) -> LogicCombinator {
+    if combine_with == "or" {
+        LogicCombinator::Or
+    } else {
+        LogicCombinator::And
+    }
 }
 
 fn parse_comparison_op(op: &str) -> ComparisonOp {
@@ -0,0 +1,427 @@
+//! Parse math node expressions (`{{read-1.value}} * 2 + 1`) into `ComputeExpr`.
+//!
+//! A small Pratt parser over numbers, `{{ref}}` operands, `+ - * / %`,
+//! unary minus and parentheses. `*`, `/` and `%` bind tighter than `+` and `-`;
+//! all binary operators are left-associative.
+
+use std::collections::HashMap;
+
+use super::reference::resolve_value_expr;
+use crate::ir::types::*;
+
+/// A syntax error in a math expression. `position` is the 1-based character
+/// column where the problem was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputeParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl std::fmt::Display for ComputeParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+/// Parse an expression string. `{{ref}}` operands resolve through `id_map`
+/// exactly like other node fields.
+pub fn parse_compute_expr(
+    input: &str,
+    id_map: &HashMap<String, String>,
+) -> Result<ComputeExpr, ComputeParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: input.chars().count() + 1,
+        id_map,
+    };
+    let expr = parser.parse_expr(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(tok) => Err(ComputeParseError {
+            message: format!("Unexpected {}", tok.kind.describe()),
+            position: tok.position,
+        }),
+    }
+}
+
+/// True if any `/` or `%` in the expression has a literal zero divisor.
+pub fn divides_by_literal_zero(expr: &ComputeExpr) -> bool {
+    match expr {
+        ComputeExpr::Literal { .. } | ComputeExpr::Value { .. } => false,
+        ComputeExpr::Neg { operand } => divides_by_literal_zero(operand),
+        ComputeExpr::Binary { op, left, right } => {
+            let zero_divisor = matches!(op, ArithOp::Div | ArithOp::Mod) && is_literal_zero(right);
+            zero_divisor || divides_by_literal_zero(left) || divides_by_literal_zero(right)
+        }
+    }
+}
+
+fn is_literal_zero(expr: &ComputeExpr) -> bool {
+    match expr {
+        ComputeExpr::Literal { value } => value.parse::<f64>().is_ok_and(|v| v == 0.0),
+        ComputeExpr::Neg { operand } => is_literal_zero(operand),
+        _ => false,
+    }
+}
+
+/// Digits of the largest uint256, the widest integer a BigInt literal needs.
+pub const MAX_INTEGER_LITERAL_DIGITS: i64 = 78;
+
+/// Expand a numeric literal (`"1e18"`, `"2.50"`, `"42"`) to plain integer digits.
+/// None if the literal isn't a whole number or has more than
+/// [`MAX_INTEGER_LITERAL_DIGITS`] digits, so `1e999999999999` is rejected
+/// before any digits are written out.
+pub fn integer_literal(text: &str) -> Option<String> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(pos) => (&text[..pos], text[pos + 1..].parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mut digits = format!("{}{}", int_part, frac_part);
+    // Zero is an integer at any scale (`0e-5`, `0.0`).
+    if digits.trim_start_matches('0').is_empty() {
+        return Some("0".to_string());
+    }
+    let mut scale = exponent.saturating_sub(frac_part.len() as i64);
+
+    // Drop trailing zeros that a negative scale would cut off anyway.
+    while scale < 0 && digits.ends_with('0') {
+        digits.pop();
+        scale += 1;
+    }
+    if scale < 0 {
+        return None;
+    }
+    let significant = digits.trim_start_matches('0').len() as i64;
+    if significant.saturating_add(scale) > MAX_INTEGER_LITERAL_DIGITS {
+        return None;
+    }
+    digits.extend(std::iter::repeat_n('0', scale as usize));
+
+    let trimmed = digits.trim_start_matches('0');
+    Some(if trimmed.is_empty() { "0" } else { trimmed }.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Tokenizer
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(String),
+    Ref(String),
+    Op(ArithOp),
+    LParen,
+    RParen,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Number(n) => format!("number '{}'", n),
+            TokenKind::Ref(r) => format!("reference '{{{{{}}}}}'", r),
+            TokenKind::Op(op) => format!("operator '{}'", op_symbol(*op)),
+            TokenKind::LParen => "'('".into(),
+            TokenKind::RParen => "')'".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// 1-based character column.
+    position: usize,
+}
+
+fn op_symbol(op: ArithOp) -> &'static str {
+    match op {
+        ArithOp::Add => "+",
+        ArithOp::Sub => "-",
+        ArithOp::Mul => "*",
+        ArithOp::Div => "/",
+        ArithOp::Mod => "%",
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ComputeParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '+' => TokenKind::Op(ArithOp::Add),
+            '-' => TokenKind::Op(ArithOp::Sub),
+            '*' => TokenKind::Op(ArithOp::Mul),
+            '/' => TokenKind::Op(ArithOp::Div),
+            '%' => TokenKind::Op(ArithOp::Mod),
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '{' if chars.get(i + 1) == Some(&'{') => {
+                let start = i + 2;
+                let close = (start..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == '}' && chars[j + 1] == '}')
+                    .ok_or_else(|| ComputeParseError {
+                        message: "Unclosed '{{' reference".into(),
+                        position,
+                    })?;
+                let inner: String = chars[start..close].iter().collect();
+                let inner = inner.trim().to_string();
+                if inner.is_empty() {
+                    return Err(ComputeParseError {
+                        message: "Empty '{{}}' reference".into(),
+                        position,
+                    });
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Ref(inner),
+                    position,
+                });
+                i = close + 2;
+                continue;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        while j < chars.len() && chars[j].is_ascii_digit() {
+                            j += 1;
+                        }
+                        i = j;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                if text.parse::<f64>().is_err() {
+                    return Err(ComputeParseError {
+                        message: format!("Invalid number '{}'", text),
+                        position,
+                    });
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number(text),
+                    position,
+                });
+                continue;
+            }
+            other => {
+                return Err(ComputeParseError {
+                    message: format!("Unexpected character '{}'", other),
+                    position,
+                });
+            }
+        };
+        tokens.push(Token { kind, position });
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+// ---------------------------------------------------------------------------
+// Pratt parser
+// ---------------------------------------------------------------------------
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Column just past the end of input, for "unexpected end" errors.
+    end: usize,
+    id_map: &'a HashMap<String, String>,
+}
+
+/// Binding power of unary minus: tighter than any binary operator.
+const PREFIX_BP: u8 = 5;
+
+fn infix_binding_power(op: ArithOp) -> (u8, u8) {
+    match op {
+        ArithOp::Add | ArithOp::Sub => (1, 2),
+        ArithOp::Mul | ArithOp::Div | ArithOp::Mod => (3, 4),
+    }
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn parse_expr(&mut self, min_bp: u8) -> Result<ComputeExpr, ComputeParseError> {
+        let mut lhs = self.parse_prefix()?;
+
+        while let Some(Token {
+            kind: TokenKind::Op(op),
+            ..
+        }) = self.peek()
+        {
+            let op = *op;
+            let (l_bp, r_bp) = infix_binding_power(op);
+            if l_bp < min_bp {
+                break;
+            }
+            self.next();
+            let rhs = self.parse_expr(r_bp)?;
+            lhs = ComputeExpr::Binary {
+                op,
+                left: Box::new(lhs),
+                right: Box::new(rhs),
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_prefix(&mut self) -> Result<ComputeExpr, ComputeParseError> {
+        let Some(tok) = self.next() else {
+            return Err(ComputeParseError {
+                message: "Unexpected end of expression".into(),
+                position: self.end,
+            });
+        };
+
+        match tok.kind {
+            TokenKind::Number(value) => Ok(ComputeExpr::Literal { value }),
+            TokenKind::Ref(inner) => Ok(ComputeExpr::Value {
+                value: resolve_value_expr(&format!("{{{{{}}}}}", inner), self.id_map),
+            }),
+            TokenKind::Op(ArithOp::Sub) => {
+                let operand = self.parse_expr(PREFIX_BP)?;
+                Ok(ComputeExpr::Neg {
+                    operand: Box::new(operand),
+                })
+            }
+            TokenKind::LParen => {
+                let inner = self.parse_expr(0)?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => Ok(inner),
+                    Some(other) => Err(ComputeParseError {
+                        message: format!("Expected ')' but found {}", other.kind.describe()),
+                        position: other.position,
+                    }),
+                    None => Err(ComputeParseError {
+                        message: format!("Unclosed '(' opened at position {}", tok.position),
+                        position: self.end,
+                    }),
+                }
+            }
+            other => Err(ComputeParseError {
+                message: format!(
+                    "Expected a number or reference but found {}",
+                    other.describe()
+                ),
+                position: tok.position,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> ComputeExpr {
+        parse_compute_expr(input, &HashMap::new()).expect("Should parse")
+    }
+
+    /// Render the AST fully parenthesized so precedence is visible in asserts.
+    fn show(expr: &ComputeExpr) -> String {
+        match expr {
+            ComputeExpr::Literal { value } => value.clone(),
+            ComputeExpr::Value { value } => match value {
+                ValueExpr::Binding(b) => format!("{}.{}", b.step_id, b.field_path),
+                other => format!("{:?}", other),
+            },
+            ComputeExpr::Neg { operand } => format!("(-{})", show(operand)),
+            ComputeExpr::Binary { op, left, right } => {
+                format!("({} {} {})", show(left), op_symbol(*op), show(right))
+            }
+        }
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(show(&parse("1 + 2 * 3")), "(1 + (2 * 3))");
+        assert_eq!(show(&parse("1 * 2 + 3 % 4")), "((1 * 2) + (3 % 4))");
+    }
+
+    #[test]
+    fn operators_are_left_associative() {
+        assert_eq!(show(&parse("10 - 4 - 3")), "((10 - 4) - 3)");
+        assert_eq!(show(&parse("8 / 4 / 2")), "((8 / 4) / 2)");
+    }
+
+    #[test]
+    fn parentheses_and_unary_minus() {
+        assert_eq!(show(&parse("(1 + 2) * -3")), "((1 + 2) * (-3))");
+        assert_eq!(
+            show(&parse("-{{read-1.value}} * 1e18")),
+            "((-read-1.value) * 1e18)"
+        );
+    }
+
+    #[test]
+    fn errors_report_position() {
+        let err = parse_compute_expr("1 + * 2", &HashMap::new()).unwrap_err();
+        assert_eq!(err.position, 5);
+
+        let err = parse_compute_expr("(1 + 2", &HashMap::new()).unwrap_err();
+        assert_eq!(err.position, 7);
+        assert!(err.message.contains("Unclosed '('"));
+
+        let err = parse_compute_expr("2 $ 3", &HashMap::new()).unwrap_err();
+        assert_eq!(err.position, 3);
+    }
+
+    #[test]
+    fn detects_division_by_literal_zero() {
+        assert!(divides_by_literal_zero(&parse("{{a.x}} / 0")));
+        assert!(divides_by_literal_zero(&parse("1 + 5 % 0.0")));
+        assert!(divides_by_literal_zero(&parse("{{a.x}} / 0e-5")));
+        assert!(!divides_by_literal_zero(&parse("{{a.x}} / (1 - 1)")));
+    }
+
+    #[test]
+    fn integer_literal_expansion() {
+        assert_eq!(
+            integer_literal("1e18").as_deref(),
+            Some("1000000000000000000")
+        );
+        assert_eq!(integer_literal("2.50e1").as_deref(), Some("25"));
+        assert_eq!(integer_literal("007").as_deref(), Some("7"));
+        assert_eq!(integer_literal("1.5"), None);
+        assert_eq!(integer_literal("0e-5").as_deref(), Some("0"));
+        assert_eq!(integer_literal("0.000").as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn integer_literal_rejects_more_digits_than_uint256() {
+        assert_eq!(integer_literal("1e77").map(|d| d.len()), Some(78));
+        assert_eq!(integer_literal("1e78"), None);
+        assert_eq!(integer_literal("1e999999999999"), None);
+        assert_eq!(integer_literal("1.5e-9223372036854775808"), None);
+        assert_eq!(integer_literal("0e999999999999").as_deref(), Some("0"));
+    }
+}
@@ -5,11 +5,24 @@
 //! SYNC NOTE: Convenience-node mappings here must track node types/configs in
 //! `shared/model/node.ts` and `compiler/src/parse/types.rs`.
 
-use std::collections::HashMap;
-
+use super::builder::lower_abi_encode;
+use super::reference::{RefScope, field, keyed_field};
+use super::trigger::make_evm_binding_name;
 use crate::ir::types::*;
 use crate::parse::types::*;
 
+/// Separator between the source node ID and the sub-step name in expanded step IDs.
+pub const EXPANDED_STEP_SEPARATOR: &str = "___";
+
+/// Map a step ID back to the visual node it came from, so errors highlight a
+/// node the editor knows about: `mint-1___encode` → `mint-1`, `http-1` → `http-1`.
+/// Node ids never contain the separator (V014).
+pub fn source_node_id(step_id: &str) -> &str {
+    step_id
+        .split_once(EXPANDED_STEP_SEPARATOR)
+        .map_or(step_id, |(node_id, _)| node_id)
+}
+
 /// An expanded step ready for the builder.
 pub struct ExpandedStep {
     pub id: String,
@@ -20,18 +33,158 @@ pub struct ExpandedStep {
 }
 
 /// Expand a convenience node into primitive steps.
-/// Returns None if the node is not a convenience node.
-pub fn expand_node(
-    _node: &WorkflowNode,
-    _id_map: &HashMap<String, String>,
-) -> Option<Vec<ExpandedStep>> {
-    // No convenience nodes are currently defined.
-    None
+/// Returns None if the node is not a convenience node. References in the
+/// node's config are resolved through `refs`.
+pub fn expand_node(node: &WorkflowNode, refs: &RefScope) -> Option<Vec<ExpandedStep>> {
+    match node {
+        WorkflowNode::BroadcastWrite(n) => Some(expand_broadcast_write(
+            node.id(),
+            &n.data.label,
+            &n.data.config,
+            refs,
+        )),
+        _ => None,
+    }
 }
 
 /// Returns the "output" step ID for a convenience node (the last expanded step).
 /// This is used so downstream references resolve to the right step.
-pub fn output_step_id(_node: &WorkflowNode) -> Option<String> {
-    // No convenience nodes are currently defined.
-    None
+pub fn output_step_id(node: &WorkflowNode) -> Option<String> {
+    match node {
+        WorkflowNode::BroadcastWrite(_) => Some(broadcast_tx_hashes_step_id(node.id())),
+        _ => None,
+    }
+}
+
+/// `broadcast-1___encode`
+pub fn broadcast_encode_step_id(node_id: &str) -> String {
+    format!("{}{}encode", node_id, EXPANDED_STEP_SEPARATOR)
+}
+
+/// `broadcast-1___write_ethereum-testnet-sepolia`; characters other than
+/// ASCII letters, digits, `-` and `_` in the chain name become `_`.
+pub fn broadcast_write_step_id(node_id: &str, chain: &str) -> String {
+    let chain: String = chain
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}write_{}", node_id, EXPANDED_STEP_SEPARATOR, chain)
+}
+
+/// `broadcast-1___tx_hashes`, the step `{{broadcast-1.txHashes}}` reads.
+pub fn broadcast_tx_hashes_step_id(node_id: &str) -> String {
+    format!("{}{}tx_hashes", node_id, EXPANDED_STEP_SEPARATOR)
+}
+
+/// broadcastWrite → AbiEncode, one EvmWrite per chain (in list order), and a
+/// CodeNode collecting the transaction hashes as `txHashes: string[]`.
+fn expand_broadcast_write(
+    node_id: &str,
+    label: &str,
+    config: &BroadcastWriteConfig,
+    refs: &RefScope,
+) -> Vec<ExpandedStep> {
+    let step_var = |step_id: &str| format!("step_{}", step_id.replace('-', "_"));
+    let encode_id = broadcast_encode_step_id(node_id);
+    let (encode_op, _) = lower_abi_encode(&encode_id, &config.encode, refs);
+    let mut steps = vec![ExpandedStep {
+        id: encode_id.clone(),
+        source_node_id: node_id.to_string(),
+        label: format!("{} (encode)", label),
+        operation: encode_op,
+        output: Some(OutputBinding {
+            variable_name: step_var(&encode_id),
+            ts_type: "{ encoded: string }".into(),
+            destructure_fields: None,
+        }),
+    }];
+
+    let gas_limit: i64 = config.gas_limit.parse().unwrap_or(500_000);
+    let mut tx_hashes = Vec::new();
+    for chain in &config.chain_selector_names {
+        let chain = chain.trim();
+        let write_id = broadcast_write_step_id(node_id, chain);
+        let receiver_field = if config.receiver_addresses.contains_key(chain) {
+            keyed_field("receiverAddresses", chain)
+        } else {
+            field("receiverAddress")
+        };
+        tx_hashes.push(CodeInputBinding {
+            variable_name: format!("txHash{}", tx_hashes.len()),
+            value: ValueExpr::binding(write_id.clone(), "txHash"),
+        });
+        steps.push(ExpandedStep {
+            id: write_id.clone(),
+            source_node_id: node_id.to_string(),
+            label: format!("{} ({})", label, chain),
+            operation: Operation::EvmWrite(EvmWriteOp {
+                evm_client_binding: make_evm_binding_name(chain),
+                receiver_address: refs.resolve(config.receiver_for(chain), receiver_field),
+                gas_limit: ValueExpr::integer(gas_limit),
+                encoded_data: ValueExpr::binding(encode_id.clone(), "encoded"),
+                value_wei: None,
+            }),
+            output: Some(OutputBinding {
+                variable_name: step_var(&write_id),
+                ts_type: "any".into(),
+                destructure_fields: None,
+            }),
+        });
+    }
+
+    let hashes: Vec<&str> = tx_hashes.iter().map(|b| b.variable_name.as_str()).collect();
+    let tx_hashes_id = broadcast_tx_hashes_step_id(node_id);
+    steps.push(ExpandedStep {
+        id: tx_hashes_id.clone(),
+        source_node_id: node_id.to_string(),
+        label: format!("{} (tx hashes)", label),
+        operation: Operation::CodeNode(CodeNodeOp {
+            code: format!(
+                "const txHashes = [{}].map((h) => bytesToHex(h || new Uint8Array(32)));",
+                hashes.join(", ")
+            ),
+            input_bindings: tx_hashes,
+            execution_mode: CodeExecutionMode::RunOnceForAll,
+            iterate_over: None,
+            timeout_ms: None,
+            output_fields: vec!["txHashes".into()],
+        }),
+        output: Some(OutputBinding {
+            variable_name: step_var(&tx_hashes_id),
+            ts_type: "{ txHashes: string[] }".into(),
+            destructure_fields: None,
+        }),
+    });
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CompilerError;
+    use crate::ir::validate::ValidationError;
+
+    #[test]
+    fn expanded_step_maps_to_source_node() {
+        assert_eq!(source_node_id("mint-1___encode"), "mint-1");
+        assert_eq!(source_node_id("http-1"), "http-1");
+    }
+
+    #[test]
+    fn ir_error_on_expanded_step_points_at_source_node() {
+        let err = CompilerError::from(ValidationError {
+            code: "E003",
+            message: "bad binding".into(),
+            step_id: Some("mint-1___write".into()),
+            related: vec![],
+        });
+        assert_eq!(err.node_id.as_deref(), Some("mint-1"));
+    }
 }
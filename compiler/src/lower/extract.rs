@@ -2,8 +2,9 @@
 //! SYNC NOTE: Node-to-resource extraction matches must stay aligned with
 //! node types/configs in `shared/model/node.ts`.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use crate::error::CompilerError;
 use crate::ir::types::*;
 use crate::parse::types::{GlobalConfig, Workflow, WorkflowNode};
 
@@ -17,54 +18,85 @@ pub fn extract_secrets(global: &GlobalConfig) -> Vec<SecretDeclaration> {
         .map(|s| SecretDeclaration {
             name: s.name.clone(),
             env_variable: s.env_variable.clone(),
+            env_variable_staging: s.env_variable_staging.clone(),
+            env_variable_production: s.env_variable_production.clone(),
+            referenced_by: vec![],
+            optional_in_config: false,
         })
         .collect()
 }
 
 /// Extract distinct EVM chains used across all nodes.
-/// `trigger_chain` is the chain used by the trigger (if any), which gets `used_for_trigger: true`.
-pub fn extract_evm_chains(
-    workflow: &Workflow,
-    trigger_chain: Option<(&str, &str)>,
-) -> Vec<EvmChainUsage> {
-    let mut seen = HashSet::new();
-    let mut chains = Vec::new();
-
-    // Add trigger chain first if present
-    if let Some((selector, binding)) = trigger_chain {
-        seen.insert(selector.to_string());
-        chains.push(EvmChainUsage {
-            chain_selector_name: selector.to_string(),
-            binding_name: binding.to_string(),
-            used_for_trigger: true,
-        });
-    }
+/// `trigger_chain` is the chain selector used by the trigger (if any), which
+/// gets `used_for_trigger: true`.
+pub fn extract_evm_chains(workflow: &Workflow, trigger_chain: Option<&str>) -> Vec<EvmChainUsage> {
+    let default_chain = workflow.global_config.default_chain_selector.as_deref();
+    let node_chains = workflow.nodes.iter().flat_map(|node| {
+        chain_selectors(node)
+            .into_iter()
+            .filter_map(|selector| resolve_chain_selector(selector, default_chain))
+    });
 
-    for node in &workflow.nodes {
-        let chain_selector = get_chain_selector(node);
-        if let Some(selector) = chain_selector {
-            if seen.insert(selector.clone()) {
-                let binding = make_evm_binding_name(&selector);
-                chains.push(EvmChainUsage {
-                    chain_selector_name: selector,
-                    binding_name: binding,
-                    used_for_trigger: false,
-                });
-            }
+    // Trigger chain first
+    let usages = trigger_chain
+        .map(|selector| (selector, true))
+        .into_iter()
+        .chain(node_chains.map(|selector| (selector, false)));
+    merge_chain_usages(usages)
+}
+
+/// One `EvmChainUsage` per chain selector, in order of first use. A chain is
+/// `used_for_trigger` if any of its usages is, and its binding is always
+/// derived from the selector, so the trigger and every step agree on it.
+fn merge_chain_usages<'a>(usages: impl IntoIterator<Item = (&'a str, bool)>) -> Vec<EvmChainUsage> {
+    let mut chains: Vec<EvmChainUsage> = Vec::new();
+    for (selector, used_for_trigger) in usages {
+        let selector = selector.trim();
+        match chains
+            .iter_mut()
+            .find(|c| c.chain_selector_name == selector)
+        {
+            Some(chain) => chain.used_for_trigger |= used_for_trigger,
+            None => chains.push(EvmChainUsage {
+                chain_selector_name: selector.to_string(),
+                binding_name: make_evm_binding_name(selector),
+                used_for_trigger,
+            }),
         }
     }
-
     chains
 }
 
-fn get_chain_selector(node: &WorkflowNode) -> Option<String> {
+/// Chain selectors an EVM node names (`None` for one left to the workflow
+/// default); a broadcast write names one per target.
+fn chain_selectors(node: &WorkflowNode) -> Vec<Option<&str>> {
     match node {
-        WorkflowNode::EvmRead(n) => Some(n.data.config.chain_selector_name.clone()),
-        WorkflowNode::EvmWrite(n) => Some(n.data.config.chain_selector_name.clone()),
-        _ => None,
+        WorkflowNode::EvmRead(n) => vec![n.data.config.chain_selector_name.as_deref()],
+        WorkflowNode::EvmWrite(n) => vec![n.data.config.chain_selector_name.as_deref()],
+        WorkflowNode::BroadcastWrite(n) => n
+            .data
+            .config
+            .chain_selector_names
+            .iter()
+            .map(|chain| Some(chain.as_str()))
+            .collect(),
+        _ => vec![],
     }
 }
 
+/// Pick the chain for an EVM node: its own `chainSelectorName` if set,
+/// otherwise the workflow's `defaultChainSelector`. None if neither is set,
+/// or if the node's selector is blank: that never falls back to the default.
+pub fn resolve_chain_selector<'a>(
+    node_chain: Option<&'a str>,
+    default_chain: Option<&'a str>,
+) -> Option<&'a str> {
+    node_chain
+        .or(default_chain)
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+}
+
 /// Extract user-configurable fields into config_schema.
 /// This is called after trigger config extraction to add fields from specific node patterns.
 pub fn extract_config_from_nodes(workflow: &Workflow, existing: &mut Vec<ConfigField>) {
@@ -86,3 +118,108 @@ pub fn extract_config_from_nodes(workflow: &Workflow, existing: &mut Vec<ConfigF
 
     let _ = existing_names;
 }
+
+/// Drop repeated config fields, keeping the first declaration of each name.
+/// A repeat with the same Zod type is only reported (W010); one with a
+/// different type fails with L013, since `configSchema` can hold just one.
+pub fn dedupe_config_fields(
+    fields: &mut Vec<ConfigField>,
+) -> Result<Vec<CompilerError>, Vec<CompilerError>> {
+    let mut first_type: HashMap<String, ZodType> = HashMap::new();
+    let mut warnings = Vec::new();
+    let mut errors = Vec::new();
+
+    fields.retain(|field| match first_type.get(&field.name) {
+        None => {
+            first_type.insert(field.name.clone(), field.zod_type.clone());
+            true
+        }
+        Some(zod_type) if *zod_type == field.zod_type => {
+            warnings.push(CompilerError::lower(
+                "W010",
+                format!(
+                    "Config field '{}' is declared more than once — the duplicate was dropped",
+                    field.name
+                ),
+                None,
+            ));
+            false
+        }
+        Some(zod_type) => {
+            errors.push(CompilerError::lower(
+                "L013",
+                format!(
+                    "Config field '{}' is declared as both {} and {}",
+                    field.name,
+                    zod_type_name(zod_type),
+                    zod_type_name(&field.zod_type)
+                ),
+                None,
+            ));
+            false
+        }
+    });
+
+    if errors.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(errors)
+    }
+}
+
+fn zod_type_name(zod_type: &ZodType) -> &str {
+    match zod_type {
+        ZodType::String => "z.string()",
+        ZodType::Number => "z.number()",
+        ZodType::Boolean => "z.boolean()",
+        ZodType::Raw(expr) => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, zod_type: ZodType) -> ConfigField {
+        ConfigField {
+            name: name.into(),
+            zod_type,
+            default_value: None,
+            description: None,
+            referenced_by: vec![],
+            optional_in_config: false,
+        }
+    }
+
+    #[test]
+    fn identical_duplicate_is_dropped_with_warning() {
+        let mut fields = vec![
+            field("schedule", ZodType::String),
+            field("threshold", ZodType::Number),
+            field("schedule", ZodType::String),
+        ];
+
+        let warnings = dedupe_config_fields(&mut fields).unwrap();
+
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["schedule", "threshold"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W010");
+        assert!(warnings[0].message.contains("'schedule'"));
+    }
+
+    #[test]
+    fn conflicting_duplicate_is_an_error() {
+        let mut fields = vec![
+            field("threshold", ZodType::Number),
+            field("threshold", ZodType::String),
+        ];
+
+        let errors = dedupe_config_fields(&mut fields).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "L013");
+        assert!(errors[0].message.contains("z.number()"));
+        assert!(errors[0].message.contains("z.string()"));
+    }
+}
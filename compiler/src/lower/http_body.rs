@@ -0,0 +1,54 @@
+//! JSON request bodies.
+//!
+//! Codegen sends a JSON body as `JSON.stringify(data)`. Literal body text
+//! that parses as JSON is lowered to a JSON literal, so an object is
+//! stringified once instead of being sent as a quoted string. Literal text
+//! that does not parse is still sent as a JSON string, and reported (W017),
+//! since that is rarely what a JSON body was meant to carry.
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+use crate::ir::validate::visit_steps;
+
+/// `data` as a JSON literal when it has no references and parses as JSON.
+pub fn json_literal(data: &str) -> Option<ValueExpr> {
+    let data = data.trim();
+    if data.contains("{{") || serde_json::from_str::<serde_json::Value>(data).is_err() {
+        return None;
+    }
+    Some(ValueExpr::Literal(LiteralValue::Json {
+        value: data.to_string(),
+    }))
+}
+
+/// W017 for each JSON body whose literal data is not valid JSON.
+pub fn invalid_json_body_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let mut warnings = Vec::new();
+    visit_steps(&ir.handler_body, &mut |step| {
+        let Operation::HttpRequest(op) = &step.operation else {
+            return;
+        };
+        let Some(HttpBody {
+            content_type: HttpContentType::Json,
+            data: ValueExpr::Literal(LiteralValue::String { value }),
+            ..
+        }) = &op.body
+        else {
+            return;
+        };
+        if value.trim().is_empty() {
+            return;
+        }
+        if let Err(e) = serde_json::from_str::<serde_json::Value>(value) {
+            warnings.push(CompilerError::lower(
+                "W017",
+                format!(
+                    "HTTP request '{}' has a JSON body that is not valid JSON ({}) — it is sent as a JSON string",
+                    step.id, e
+                ),
+                Some(step.id.clone()),
+            ));
+        }
+    });
+    warnings
+}
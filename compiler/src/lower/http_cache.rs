@@ -0,0 +1,247 @@
+//! Post-lowering check of HTTP response caching.
+//!
+//! CRE's `cacheSettings` lets repeated identical requests share one response.
+//! That is only sound for reads: caching a POST would hand the first write's
+//! response to every later write without ever sending it. `cacheMaxAge` on a
+//! non-GET/HEAD request is dropped from the IR and reported as a warning.
+//!
+//! `globalConfig.defaultCacheMaxAge` fills in GET/HEAD requests that leave
+//! `cacheMaxAge` unset. Identical GET/HEAD requests on one execution path that
+//! still have no caching are reported, since each one costs an HTTP call.
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+
+/// Give every GET/HEAD request without `cache_max_age_seconds` the workflow default.
+pub fn apply_default_cache_max_age(ir: &mut WorkflowIR, default_max_age: Option<u32>) {
+    if let Some(max_age) = default_max_age {
+        apply_default_in_block(&mut ir.handler_body, max_age);
+    }
+}
+
+fn apply_default_in_block(block: &mut Block, max_age: u32) {
+    for step in &mut block.steps {
+        match &mut step.operation {
+            Operation::HttpRequest(op)
+                if op.cache_max_age_seconds.is_none() && op.method.is_cacheable() =>
+            {
+                op.cache_max_age_seconds = Some(max_age);
+            }
+            Operation::Branch(branch) => {
+                apply_default_in_block(&mut branch.true_branch, max_age);
+                apply_default_in_block(&mut branch.false_branch, max_age);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Clear `cache_max_age_seconds` on every non-cacheable HTTP request.
+/// Returns one warning per request that had caching configured.
+pub fn drop_uncacheable_cache_settings(ir: &mut WorkflowIR) -> Vec<CompilerError> {
+    let mut warnings = Vec::new();
+    drop_in_block(&mut ir.handler_body, &mut warnings);
+    warnings
+}
+
+fn drop_in_block(block: &mut Block, warnings: &mut Vec<CompilerError>) {
+    for step in &mut block.steps {
+        match &mut step.operation {
+            Operation::HttpRequest(op)
+                if op.cache_max_age_seconds.is_some() && !op.method.is_cacheable() =>
+            {
+                op.cache_max_age_seconds = None;
+                warnings.push(CompilerError::lower(
+                    "W004",
+                    format!(
+                        "HTTP request '{}' uses {} with cacheMaxAge set — response caching only applies to GET/HEAD, so it was ignored",
+                        step.id,
+                        op.method.as_str()
+                    ),
+                    Some(step.id.clone()),
+                ));
+            }
+            Operation::Branch(branch) => {
+                drop_in_block(&mut branch.true_branch, warnings);
+                drop_in_block(&mut branch.false_branch, warnings);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Warn about each uncached GET/HEAD request that repeats an earlier request
+/// on the same execution path. Requests in opposite branch arms never both run.
+pub fn warn_identical_uncached_requests(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let mut warnings = Vec::new();
+    warn_in_block(&ir.handler_body, &mut Vec::new(), &mut warnings);
+    warnings
+}
+
+fn warn_in_block<'a>(
+    block: &'a Block,
+    earlier: &mut Vec<(&'a Step, serde_json::Value)>,
+    warnings: &mut Vec<CompilerError>,
+) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::HttpRequest(op)
+                if op.cache_max_age_seconds.is_none() && op.method.is_cacheable() =>
+            {
+                let Ok(request) = serde_json::to_value(op) else {
+                    continue;
+                };
+                if let Some((first, _)) = earlier.iter().find(|(_, r)| *r == request) {
+                    warnings.push(CompilerError::lower(
+                        "W005",
+                        format!(
+                            "HTTP request '{}' is identical to '{}' and neither is cached — set cacheMaxAge (or globalConfig.defaultCacheMaxAge) so the repeat is served from the CRE cache",
+                            step.id, first.id
+                        ),
+                        Some(step.id.clone()),
+                    ));
+                } else {
+                    earlier.push((step, request));
+                }
+            }
+            Operation::Branch(branch) => {
+                // Arm requests are forgotten again: steps after the branch
+                // cannot tell which arm ran.
+                let before_branch = earlier.len();
+                warn_in_block(&branch.true_branch, earlier, warnings);
+                earlier.truncate(before_branch);
+                warn_in_block(&branch.false_branch, earlier, warnings);
+                earlier.truncate(before_branch);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ir_with_steps_no_return;
+
+    fn http_step(id: &str, method: HttpMethod, cache_max_age_seconds: Option<u32>) -> Step {
+        Step {
+            id: id.into(),
+            source_node_ids: vec![id.into()],
+            label: "Fetch".into(),
+            operation: Operation::HttpRequest(HttpRequestOp {
+                method,
+                url: ValueExpr::string("https://api.example.com/price"),
+                headers: vec![],
+                query_params: vec![],
+                body: None,
+                authentication: None,
+                cache_max_age_seconds,
+                timeout_ms: None,
+                expected_status_codes: vec![200],
+                response_format: HttpResponseFormat::Json,
+                follow_redirects: true,
+                ignore_ssl: false,
+                consensus: ConsensusStrategy::Identical,
+            }),
+            output: None,
+            notes: None,
+        }
+    }
+
+    fn cache_of(step: &Step) -> Option<u32> {
+        match &step.operation {
+            Operation::HttpRequest(op) => op.cache_max_age_seconds,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn default_cache_max_age_fills_unset_gets_only() {
+        let mut ir = ir_with_steps_no_return(vec![
+            http_step("http-1", HttpMethod::Get, None),
+            http_step("http-2", HttpMethod::Get, Some(30)),
+            http_step("http-3", HttpMethod::Post, None),
+        ]);
+
+        apply_default_cache_max_age(&mut ir, Some(120));
+
+        let steps = &ir.handler_body.steps;
+        assert_eq!(cache_of(&steps[0]), Some(120));
+        assert_eq!(cache_of(&steps[1]), Some(30));
+        assert_eq!(cache_of(&steps[2]), None);
+    }
+
+    #[test]
+    fn identical_uncached_gets_warn_once_per_repeat() {
+        let ir = ir_with_steps_no_return(vec![
+            http_step("http-1", HttpMethod::Get, None),
+            http_step("http-2", HttpMethod::Get, None),
+            http_step("http-3", HttpMethod::Post, None),
+            http_step("http-4", HttpMethod::Post, None),
+        ]);
+
+        let warnings = warn_identical_uncached_requests(&ir);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W005");
+        assert_eq!(warnings[0].node_id.as_deref(), Some("http-2"));
+        assert!(warnings[0].message.contains("'http-1'"));
+    }
+
+    #[test]
+    fn identical_requests_are_fine_when_cached_or_in_opposite_arms() {
+        let branch = Step {
+            id: "if-1".into(),
+            source_node_ids: vec!["if-1".into()],
+            label: "If".into(),
+            operation: Operation::Branch(BranchOp {
+                conditions: vec![],
+                combine_with: LogicCombinator::And,
+                true_branch: Block {
+                    steps: vec![http_step("http-2", HttpMethod::Get, None)],
+                },
+                false_branch: Block {
+                    steps: vec![http_step("http-3", HttpMethod::Get, None)],
+                },
+                true_label: None,
+                false_label: None,
+                reconverge_at: None,
+            }),
+            output: None,
+            notes: None,
+        };
+        let mut ir =
+            ir_with_steps_no_return(vec![http_step("http-1", HttpMethod::Get, Some(60)), branch]);
+        assert!(warn_identical_uncached_requests(&ir).is_empty());
+
+        // Uncached, the first request is repeated in both arms.
+        if let Operation::HttpRequest(op) = &mut ir.handler_body.steps[0].operation {
+            op.cache_max_age_seconds = None;
+        }
+        let warnings = warn_identical_uncached_requests(&ir);
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn cached_get_is_kept_without_warning() {
+        let mut ir = ir_with_steps_no_return(vec![http_step("http-1", HttpMethod::Get, Some(60))]);
+
+        let warnings = drop_uncacheable_cache_settings(&mut ir);
+
+        assert!(warnings.is_empty());
+        assert_eq!(cache_of(&ir.handler_body.steps[0]), Some(60));
+    }
+
+    #[test]
+    fn cached_post_is_dropped_with_warning() {
+        let mut ir = ir_with_steps_no_return(vec![http_step("http-1", HttpMethod::Post, Some(60))]);
+
+        let warnings = drop_uncacheable_cache_settings(&mut ir);
+
+        assert_eq!(cache_of(&ir.handler_body.steps[0]), None);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W004");
+        assert_eq!(warnings[0].node_id.as_deref(), Some("http-1"));
+        assert!(warnings[0].message.contains("POST"));
+    }
+}
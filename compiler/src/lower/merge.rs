@@ -0,0 +1,180 @@
+//! Lower Merge nodes that join independent chains.
+//!
+//! A Merge that reconverges an If's arms is built with the branch (see
+//! `builder::build_branch`). Any other Merge combines the outputs of all its
+//! predecessors: one `MergeInput` per incoming edge, named after the edge's
+//! target handle and ordered by it (`input1`, `input2`, ...). `append`
+//! collects the inputs into an array and `custom` runs the node's code over
+//! them. `position` and `combinations` are lowered as `append` for now, with
+//! a W009 warning; `matchingFields` fails with L012.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+use crate::parse::graph::{EdgeLabel, WorkflowGraph};
+use crate::parse::types::{MergeConfig, MergeStrategyDef, Workflow, WorkflowNode};
+
+use super::builder::resolve_node_output;
+use super::reference::{config_path, field};
+
+pub fn lower_standalone_merge(
+    node_id: &str,
+    config: &MergeConfig,
+    graph: &WorkflowGraph,
+    node_map: &HashMap<&str, &WorkflowNode>,
+    id_map: &HashMap<String, String>,
+) -> Result<(Operation, Option<OutputBinding>), Vec<CompilerError>> {
+    let mut errors = Vec::new();
+    let edges = graph.incoming_edges(node_id);
+
+    if let Some(expected) = config.number_of_inputs
+        && expected as usize != edges.len()
+    {
+        errors.push(
+            CompilerError::lower(
+                "L011",
+                format!(
+                    "Merge '{}' expects {} inputs (numberOfInputs) but {} are connected",
+                    node_id,
+                    expected,
+                    edges.len()
+                ),
+                Some(node_id.to_string()),
+            )
+            .with_path(config_path(field("numberOfInputs"))),
+        );
+    }
+
+    let (strategy, ts_type) = match &config.strategy {
+        MergeStrategyDef::Append
+        | MergeStrategyDef::Position { .. }
+        | MergeStrategyDef::Combinations => (MergeStrategy::Append, "any[]"),
+        MergeStrategyDef::Custom { code } => (MergeStrategy::Custom { expr: code.clone() }, "any"),
+        MergeStrategyDef::MatchingFields { .. } => {
+            errors.push(
+                CompilerError::lower(
+                    "L012",
+                    format!(
+                        "Merge '{}' uses the 'matchingFields' strategy, which is only supported \
+                         when the merge reconverges an If node",
+                        node_id
+                    ),
+                    Some(node_id.to_string()),
+                )
+                .with_path(config_path(field("strategy"))),
+            );
+            (MergeStrategy::PassThrough, "any")
+        }
+    };
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let handles = input_handles(&edges);
+    let mut inputs: Vec<(HandleOrder, MergeInput)> = edges
+        .iter()
+        .zip(handles)
+        .map(|((source, _), handle_name)| {
+            (
+                HandleOrder::of(&handle_name),
+                MergeInput {
+                    value: resolve_node_output(source, "", "", node_map, id_map),
+                    handle_name,
+                },
+            )
+        })
+        .collect();
+    inputs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let op = Operation::Merge(MergeOp {
+        branch_step_id: None,
+        strategy,
+        inputs: inputs.into_iter().map(|(_, input)| input).collect(),
+    });
+    let output = Some(OutputBinding {
+        variable_name: format!("step_{}", node_id.replace('-', "_")),
+        ts_type: ts_type.into(),
+        destructure_fields: None,
+    });
+    Ok((op, output))
+}
+
+/// The target handle of each edge, or `input<n>` (1-based, in edge order)
+/// when it has none or shares it with another edge.
+fn input_handles(edges: &[(&str, &EdgeLabel)]) -> Vec<String> {
+    let handles: Vec<Option<&str>> = edges
+        .iter()
+        .map(|(_, label)| label.target_handle.as_deref())
+        .collect();
+    let is_unique = |h: &str| handles.iter().filter(|other| **other == Some(h)).count() == 1;
+    handles
+        .iter()
+        .enumerate()
+        .map(|(i, handle)| match handle {
+            Some(h) if is_unique(h) => h.to_string(),
+            _ => format!("input{}", i + 1),
+        })
+        .collect()
+}
+
+/// Sort key putting `input2` before `input10`.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct HandleOrder(String, u64);
+
+impl HandleOrder {
+    fn of(handle: &str) -> Self {
+        let prefix = handle.trim_end_matches(|c: char| c.is_ascii_digit());
+        let number = handle[prefix.len()..].parse().unwrap_or(0);
+        HandleOrder(prefix.to_string(), number)
+    }
+}
+
+/// W009 for each standalone merge whose strategy was lowered as `append`
+/// although it asked for `position` or `combinations`.
+pub fn approximated_strategy_warnings(workflow: &Workflow, ir: &WorkflowIR) -> Vec<CompilerError> {
+    let mut standalone = HashSet::new();
+    collect_standalone_merges(&ir.handler_body, &mut standalone);
+
+    workflow
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            WorkflowNode::Merge(n) if standalone.contains(node.id()) => {
+                let mode = match n.data.config.strategy {
+                    MergeStrategyDef::Position { .. } => "position",
+                    MergeStrategyDef::Combinations => "combinations",
+                    _ => return None,
+                };
+                Some(
+                    CompilerError::lower(
+                        "W009",
+                        format!(
+                            "Merge '{}' uses the '{}' strategy, which is not supported yet — its inputs are appended into an array instead",
+                            node.id(),
+                            mode
+                        ),
+                        Some(node.id().to_string()),
+                    )
+                    .with_path(config_path(field("strategy"))),
+                )
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn collect_standalone_merges<'a>(block: &'a Block, ids: &mut HashSet<&'a str>) {
+    for step in &block.steps {
+        match &step.operation {
+            Operation::Merge(merge) if merge.branch_step_id.is_none() => {
+                ids.insert(step.id.as_str());
+            }
+            Operation::Branch(branch) => {
+                collect_standalone_merges(&branch.true_branch, ids);
+                collect_standalone_merges(&branch.false_branch, ids);
+            }
+            _ => {}
+        }
+    }
+}
@@ -4,24 +4,125 @@
 //! SYNC NOTE: When node types/configs change in `shared/model/node.ts`,
 //! re-check this orchestrator and the lower submodules for full coverage.
 
+pub mod bigint;
 pub mod builder;
+pub mod compute;
 pub mod expand;
 pub mod extract;
+pub mod http_body;
+pub mod http_cache;
+pub mod merge;
+pub mod multicall;
+pub mod prune;
 pub mod reference;
+pub mod secret_refs;
+pub mod step_cache;
 pub mod topo;
 pub mod trigger;
+pub mod usage;
 
 use std::collections::HashMap;
 
+use crate::cancel::CancellationToken;
 use crate::error::CompilerError;
 use crate::ir::types::*;
 use crate::parse::graph::WorkflowGraph;
-use crate::parse::types::Workflow;
+use crate::parse::types::{AutoReturnMode, Workflow};
+use step_cache::StepCache;
 
 /// Lower a parsed workflow + graph into a WorkflowIR.
 pub fn lower(workflow: &Workflow, graph: &WorkflowGraph) -> Result<WorkflowIR, Vec<CompilerError>> {
+    lower_with_warnings(workflow, graph).map(|(ir, _)| ir)
+}
+
+/// Caller overrides for lowering settings that otherwise come from the
+/// workflow's `globalConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct LowerOptions {
+    /// Overrides `globalConfig.autoReturn` when set.
+    pub auto_return: Option<AutoReturnMode>,
+    /// Lower [`crate::parse::canonicalize`]d workflow instead, so node order,
+    /// editor-assigned ids and positions do not change the IR. Errors,
+    /// warnings and step ids then use the canonical ids.
+    pub canonicalize: bool,
+    /// Stop with X001 once this token is cancelled.
+    pub cancel: Option<CancellationToken>,
+    /// Do not warn about branches whose arms use very different numbers of
+    /// capability calls (W013).
+    pub allow_unbalanced_branches: bool,
+    /// Fold consecutive EVM writes to one receiver into a single `multicall`
+    /// write, which counts once against the write budget (see [`multicall`]).
+    pub batch_evm_writes: bool,
+}
+
+/// Lower a parsed workflow + graph into a WorkflowIR, also returning
+/// non-fatal warnings (e.g. unused secrets or chains that were pruned, or
+/// response caching ignored on a POST).
+pub fn lower_with_warnings(
+    workflow: &Workflow,
+    graph: &WorkflowGraph,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    lower_with_options(workflow, graph, &LowerOptions::default())
+}
+
+/// [`lower_with_warnings`] with caller-supplied [`LowerOptions`].
+/// Errors and warnings about a node carry its `nodes[i]` path.
+pub fn lower_with_options(
+    workflow: &Workflow,
+    graph: &WorkflowGraph,
+    options: &LowerOptions,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    lower_located(workflow, graph, options, None)
+}
+
+/// [`lower_with_options`], reusing steps from `cache` for nodes unchanged
+/// since the compilation that last used it. The IR is identical to an
+/// uncached run.
+pub fn lower_cached(
+    workflow: &Workflow,
+    graph: &WorkflowGraph,
+    options: &LowerOptions,
+    cache: &mut StepCache,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    lower_located(workflow, graph, options, Some(cache))
+}
+
+fn lower_located(
+    workflow: &Workflow,
+    graph: &WorkflowGraph,
+    options: &LowerOptions,
+    step_cache: Option<&mut StepCache>,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
+    if options.canonicalize {
+        let canonical = crate::parse::canonicalize(workflow);
+        let graph = WorkflowGraph::build(&canonical)?;
+        let options = LowerOptions {
+            canonicalize: false,
+            ..options.clone()
+        };
+        return lower_located(&canonical, &graph, &options, step_cache);
+    }
+    let locate = |errors: Vec<CompilerError>| -> Vec<CompilerError> {
+        errors
+            .into_iter()
+            .map(|e| e.locate_node(workflow))
+            .collect()
+    };
+    match lower_workflow(workflow, graph, options, step_cache) {
+        Ok((ir, warnings)) => Ok((ir, locate(warnings))),
+        Err(errors) => Err(locate(errors)),
+    }
+}
+
+fn lower_workflow(
+    workflow: &Workflow,
+    graph: &WorkflowGraph,
+    options: &LowerOptions,
+    mut step_cache: Option<&mut StepCache>,
+) -> Result<(WorkflowIR, Vec<CompilerError>), Vec<CompilerError>> {
     // 1. Topological sort (position-aware: topmost then leftmost, matching n8n v1 order)
-    let topo_order = topo::topo_sort(graph, workflow)?;
+    let cancel = options.cancel.as_ref();
+    let topo_order = topo::topo_sort(graph, workflow, cancel)?;
 
     // 2. Find trigger node
     let trigger_node = workflow
@@ -52,27 +153,48 @@ pub fn lower(workflow: &Workflow, graph: &WorkflowGraph) -> Result<WorkflowIR, V
     // 5. Extract global resources
     let secrets = extract::extract_secrets(&workflow.global_config);
 
-    let trigger_chain = trigger_result
-        .evm_chain_for_trigger
-        .as_ref()
-        .map(|(s, b)| (s.as_str(), b.as_str()));
+    let trigger_chain = trigger_result.evm_chain_for_trigger.as_deref();
     let evm_chains = extract::extract_evm_chains(workflow, trigger_chain);
 
-    // 6. Extract additional config fields from nodes
+    // 6. Extract additional config fields from nodes; repeated names are
+    //    dropped (W010) unless their types conflict (L013)
     extract::extract_config_from_nodes(workflow, &mut config_fields);
+    let config_warnings = extract::dedupe_config_fields(&mut config_fields)?;
 
     // 7. Build handler body
-    let handler_body = builder::build_handler_body(&topo_order, workflow, graph, &id_map)?;
+    let auto_return = options
+        .auto_return
+        .unwrap_or(workflow.global_config.auto_return);
+    if let Some(cache) = step_cache.as_deref_mut() {
+        cache.begin(workflow, graph, &topo_order, &id_map);
+    }
+    let handler_body = builder::build_handler_body(
+        &topo_order,
+        workflow,
+        graph,
+        &id_map,
+        auto_return,
+        step_cache.as_deref_mut(),
+        cancel,
+    )?;
+    if let Some(cache) = step_cache {
+        cache.finish();
+    }
 
     // 8. Assemble IR
-    let ir = WorkflowIR {
+    let mut ir = WorkflowIR {
         metadata: WorkflowMetadata {
             id: workflow.id.clone(),
             name: workflow.name.clone(),
             description: workflow.description.clone(),
             version: workflow.version.clone(),
             is_testnet: workflow.global_config.is_testnet,
-            default_chain_selector: None,
+            default_chain_selector: extract::resolve_chain_selector(
+                None,
+                workflow.global_config.default_chain_selector.as_deref(),
+            )
+            .map(str::to_string),
+            max_kv_operations: workflow.global_config.max_kv_operations,
         },
         trigger: trigger_result.trigger_def,
         trigger_param: trigger_result.trigger_param,
@@ -91,7 +213,41 @@ pub fn lower(workflow: &Workflow, graph: &WorkflowGraph) -> Result<WorkflowIR, V
         handler_body,
     };
 
-    Ok(ir)
+    // 9. Secret references must be declared and stay out of query parameters
+    //    (L016, L017). Checked before pruning, which would drop them.
+    secret_refs::check_secret_refs(&ir)?;
+
+    // 10. Drop secrets and chains the handler body never references
+    let mut warnings = config_warnings;
+    warnings.extend(prune::prune_unused_resources(&mut ir));
+    warnings.extend(merge::approximated_strategy_warnings(workflow, &ir));
+    warnings.extend(crate::ir::analysis::opaque_calldata_warnings(&ir));
+    warnings.extend(crate::ir::returns::filter_return_warnings(&ir));
+    warnings.extend(trigger::unknown_trigger_field_warnings(&ir));
+    warnings.extend(http_body::invalid_json_body_warnings(&ir));
+    if !options.allow_unbalanced_branches {
+        warnings.extend(crate::ir::report::unbalanced_branch_warnings(&ir));
+    }
+    warnings.extend(bigint::annotate_bigint_values(&mut ir));
+
+    // 11. Response caching: apply the workflow default, drop caching from
+    //     non-GET/HEAD requests, and flag identical requests left uncached
+    http_cache::apply_default_cache_max_age(&mut ir, workflow.global_config.default_cache_max_age);
+    warnings.extend(http_cache::drop_uncacheable_cache_settings(&mut ir));
+    warnings.extend(http_cache::warn_identical_uncached_requests(&ir));
+
+    // 12. Optionally batch consecutive writes to one receiver
+    if options.batch_evm_writes {
+        multicall::batch_evm_writes(&mut ir);
+    }
+    // Sized after batching, which can fold many writes into one (W019)
+    warnings.extend(crate::ir::report::handler_size_warnings(&ir));
+
+    // 13. Record which steps use each config field / secret, and relax the
+    //     ones only needed inside a branch arm
+    usage::annotate_resource_usage(&mut ir);
+
+    Ok((ir, warnings))
 }
 
 /// Build a mapping from node IDs (and labels) to their resolved step IDs.
@@ -0,0 +1,215 @@
+//! Opt-in batching of EVM writes (`LowerOptions::batch_evm_writes`).
+//!
+//! CRE caps EVM writes per execution (E011), and each write is a report and a
+//! transaction of its own. Consecutive EvmWrites to the same receiver on the
+//! same chain are folded into one write whose calldata is
+//! `multicall(bytes[] data)` over the individual calldatas, encoded by a new
+//! AbiEncode step. The receiver must accept that payload and run each call.
+//!
+//! Writes are consecutive when only AbiEncode steps sit between them and
+//! nothing in between reads an earlier write of the run. Writes that send a
+//! value are left alone. The batched write keeps the first write's id and
+//! takes the last one's place, its gas limit is the sum of theirs, and
+//! references to the other writes read it instead.
+
+use std::collections::HashMap;
+
+use super::expand::EXPANDED_STEP_SEPARATOR;
+use crate::ir::types::*;
+use crate::ir::validate::{collect_binding_refs_from_step, visit_operation_value_exprs_mut};
+
+/// ABI item for `multicall(bytes[] data) returns (bytes[] results)`.
+const MULTICALL_ABI: &str = r#"{"type":"function","name":"multicall","inputs":[{"name":"data","type":"bytes[]"}],"outputs":[{"name":"results","type":"bytes[]"}],"stateMutability":"nonpayable"}"#;
+
+/// `write-1___multicall`: the AbiEncode step building a batch's calldata.
+pub fn multicall_encode_step_id(write_id: &str) -> String {
+    format!("{}{}multicall", write_id, EXPANDED_STEP_SEPARATOR)
+}
+
+/// Fold every run of consecutive writes to one receiver, branch arms
+/// included, into a single multicall write.
+pub fn batch_evm_writes(ir: &mut WorkflowIR) {
+    let mut merged_into = HashMap::new();
+    batch_block(&mut ir.handler_body, &mut merged_into);
+    if !merged_into.is_empty() {
+        rewrite_block(&mut ir.handler_body, &merged_into);
+    }
+}
+
+fn batch_block(block: &mut Block, merged_into: &mut HashMap<String, String>) {
+    let mut i = 0;
+    while i < block.steps.len() {
+        if let Operation::Branch(branch) = &mut block.steps[i].operation {
+            batch_block(&mut branch.true_branch, merged_into);
+            batch_block(&mut branch.false_branch, merged_into);
+        }
+        let run = write_run(&block.steps, i);
+        if run.len() < 2 {
+            i += 1;
+            continue;
+        }
+        let (encode, write) = batched_write(&block.steps, &run);
+        for &index in &run[1..] {
+            merged_into.insert(block.steps[index].id.clone(), write.id.clone());
+        }
+        let last = run[run.len() - 1];
+        block.steps.splice(last..=last, [encode, write]);
+        for &index in run[..run.len() - 1].iter().rev() {
+            block.steps.remove(index);
+        }
+        // Past the batched write, which now sits `run.len() - 1` steps
+        // earlier than `last + 1`.
+        i = last + 2 - (run.len() - 1);
+    }
+}
+
+/// Indices of the writes that batch with the one at `start`, itself first.
+/// Fewer than two when there is nothing to batch.
+fn write_run(steps: &[Step], start: usize) -> Vec<usize> {
+    let Some(first) = batchable(&steps[start]) else {
+        return vec![];
+    };
+    let mut run = vec![start];
+    for (i, step) in steps.iter().enumerate().skip(start + 1) {
+        let reads_run = collect_binding_refs_from_step(step)
+            .iter()
+            .any(|r| run.iter().any(|&w| steps[w].id == r.step_id));
+        if reads_run {
+            break;
+        }
+        match &step.operation {
+            Operation::AbiEncode(_) => {}
+            Operation::EvmWrite(_) if batchable(step).is_some_and(|w| same_target(first, w)) => {
+                run.push(i)
+            }
+            _ => break,
+        }
+    }
+    run
+}
+
+/// The step's write, when it may join a batch: no value sent and a literal
+/// gas limit that can be summed.
+fn batchable(step: &Step) -> Option<&EvmWriteOp> {
+    match &step.operation {
+        Operation::EvmWrite(op) if op.value_wei.is_none() && gas_limit(op).is_some() => Some(op),
+        _ => None,
+    }
+}
+
+fn gas_limit(op: &EvmWriteOp) -> Option<i64> {
+    match &op.gas_limit {
+        ValueExpr::Literal(LiteralValue::Integer { value }) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Same chain and receiver, compared structurally.
+fn same_target(a: &EvmWriteOp, b: &EvmWriteOp) -> bool {
+    a.evm_client_binding == b.evm_client_binding
+        && serde_json::to_string(&a.receiver_address).ok()
+            == serde_json::to_string(&b.receiver_address).ok()
+}
+
+/// The multicall AbiEncode step and the write sending it, for the writes at
+/// `run`.
+fn batched_write(steps: &[Step], run: &[usize]) -> (Step, Step) {
+    let writes: Vec<(&Step, &EvmWriteOp)> = run
+        .iter()
+        .filter_map(|&i| batchable(&steps[i]).map(|op| (&steps[i], op)))
+        .collect();
+    let (first, first_op) = writes[0];
+    let mut source_node_ids: Vec<String> = Vec::new();
+    for (step, _) in &writes {
+        for id in &step.source_node_ids {
+            if !source_node_ids.contains(id) {
+                source_node_ids.push(id.clone());
+            }
+        }
+    }
+    let labels: Vec<&str> = writes.iter().map(|(step, _)| step.label.as_str()).collect();
+
+    let encode_id = multicall_encode_step_id(&first.id);
+    let encode = Step {
+        id: encode_id.clone(),
+        source_node_ids: source_node_ids.clone(),
+        label: format!("Multicall: {} (encode)", labels.join(", ")),
+        operation: Operation::AbiEncode(AbiEncodeOp {
+            function_name: Some("multicall".into()),
+            abi_json: MULTICALL_ABI.into(),
+            data_mappings: writes
+                .iter()
+                .map(|(_, op)| AbiDataMapping {
+                    param_name: "data".into(),
+                    value: op.encoded_data.clone(),
+                    value_kind: None,
+                })
+                .collect(),
+        }),
+        output: Some(OutputBinding {
+            variable_name: format!("step_{}", encode_id.replace('-', "_")),
+            ts_type: "{ encoded: string }".into(),
+            destructure_fields: None,
+        }),
+        notes: None,
+    };
+    let write = Step {
+        id: first.id.clone(),
+        source_node_ids,
+        label: format!("Multicall: {}", labels.join(", ")),
+        operation: Operation::EvmWrite(EvmWriteOp {
+            evm_client_binding: first_op.evm_client_binding.clone(),
+            receiver_address: first_op.receiver_address.clone(),
+            gas_limit: ValueExpr::integer(writes.iter().filter_map(|(_, op)| gas_limit(op)).sum()),
+            encoded_data: ValueExpr::binding(encode_id, "encoded"),
+            value_wei: None,
+        }),
+        output: first.output.clone(),
+        notes: first.notes.clone(),
+    };
+    (encode, write)
+}
+
+/// Point bindings to a merged write at the batched write.
+fn rewrite_block(block: &mut Block, merged_into: &HashMap<String, String>) {
+    for step in &mut block.steps {
+        visit_operation_value_exprs_mut(&mut step.operation, &mut |expr| {
+            rewrite_expr(expr, merged_into)
+        });
+        if let Operation::Branch(branch) = &mut step.operation {
+            rewrite_block(&mut branch.true_branch, merged_into);
+            rewrite_block(&mut branch.false_branch, merged_into);
+        }
+    }
+}
+
+fn rewrite_expr(expr: &mut ValueExpr, merged_into: &HashMap<String, String>) {
+    match expr {
+        ValueExpr::Binding(binding) => {
+            if let Some(write_id) = merged_into.get(&binding.step_id) {
+                binding.step_id = write_id.clone();
+            }
+        }
+        ValueExpr::Template { parts } => {
+            for part in parts {
+                if let TemplatePart::Expr { value } = part {
+                    rewrite_expr(value, merged_into);
+                }
+            }
+        }
+        ValueExpr::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            for value in [cond, then, otherwise] {
+                rewrite_expr(value, merged_into);
+            }
+        }
+        ValueExpr::Literal(_)
+        | ValueExpr::ConfigRef { .. }
+        | ValueExpr::TriggerDataRef { .. }
+        | ValueExpr::SecretRef { .. }
+        | ValueExpr::RawExpr { .. } => {}
+    }
+}
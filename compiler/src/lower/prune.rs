@@ -0,0 +1,140 @@
+//! Post-lowering pruning of unused global resources.
+//!
+//! `extract_secrets` and `extract_evm_chains` declare resources from the workflow
+//! config and node list, not from what the handler body actually ends up using.
+//! This pass drops declarations that nothing in the IR references, so the generated
+//! `secrets.yaml` / `project.yaml` don't demand env vars and RPCs the workflow never
+//! touches. Each pruned resource is reported as a warning.
+
+use crate::error::CompilerError;
+use crate::ir::types::*;
+use crate::ir::validate::{collect_evm_binding_refs, collect_secret_refs};
+
+/// Remove secrets and EVM chains that no step (or trigger) references.
+/// Chains used by the trigger are always kept. Returns one warning per pruned resource.
+pub fn prune_unused_resources(ir: &mut WorkflowIR) -> Vec<CompilerError> {
+    let mut warnings = Vec::new();
+
+    let used_secrets = collect_secret_refs(&ir.handler_body);
+    ir.required_secrets.retain(|secret| {
+        let used = used_secrets.contains(&secret.name);
+        if !used {
+            warnings.push(CompilerError::lower(
+                "W001",
+                format!(
+                    "Secret '{}' is declared but not used by any node — it was left out of secrets.yaml",
+                    secret.name
+                ),
+                None,
+            ));
+        }
+        used
+    });
+
+    let used_bindings = collect_evm_binding_refs(ir);
+    ir.evm_chains.retain(|chain| {
+        let used = chain.used_for_trigger || used_bindings.contains(&chain.binding_name);
+        if !used {
+            warnings.push(CompilerError::lower(
+                "W002",
+                format!(
+                    "Chain '{}' is not used by any connected EVM node — it was left out of project.yaml",
+                    chain.chain_selector_name
+                ),
+                None,
+            ));
+        }
+        used
+    });
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ir_with_steps_no_return;
+
+    fn chain(selector: &str, used_for_trigger: bool) -> EvmChainUsage {
+        EvmChainUsage {
+            chain_selector_name: selector.into(),
+            binding_name: format!("evmClient_{}", selector.replace('-', "_")),
+            used_for_trigger,
+        }
+    }
+
+    fn evm_read_step(id: &str, binding: &str) -> Step {
+        Step {
+            id: id.into(),
+            source_node_ids: vec![id.into()],
+            label: "Read".into(),
+            operation: Operation::EvmRead(EvmReadOp {
+                evm_client_binding: binding.into(),
+                contract_address: ValueExpr::string("0x0000000000000000000000000000000000000001"),
+                function_name: "totalSupply".into(),
+                abi_json: "{}".into(),
+                args: vec![],
+                from_address: None,
+                block_number: None,
+                output_names: vec![],
+            }),
+            output: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn unused_secret_is_pruned_with_warning() {
+        let mut ir = ir_with_steps_no_return(vec![]);
+        ir.required_secrets = vec![SecretDeclaration {
+            name: "UNUSED_KEY".into(),
+            env_variable: "UNUSED_KEY_VAR".into(),
+            env_variable_staging: None,
+            env_variable_production: None,
+            referenced_by: vec![],
+            optional_in_config: false,
+        }];
+
+        let warnings = prune_unused_resources(&mut ir);
+
+        assert!(ir.required_secrets.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W001");
+        assert!(warnings[0].message.contains("UNUSED_KEY"));
+    }
+
+    #[test]
+    fn chain_without_referencing_step_is_pruned() {
+        let mut ir = ir_with_steps_no_return(vec![evm_read_step(
+            "read-1",
+            "evmClient_ethereum_testnet_sepolia",
+        )]);
+        ir.evm_chains = vec![
+            chain("ethereum-testnet-sepolia", false),
+            chain("ethereum-mainnet", false),
+        ];
+
+        let warnings = prune_unused_resources(&mut ir);
+
+        assert_eq!(ir.evm_chains.len(), 1);
+        assert_eq!(
+            ir.evm_chains[0].chain_selector_name,
+            "ethereum-testnet-sepolia"
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W002");
+        assert!(warnings[0].message.contains("ethereum-mainnet"));
+    }
+
+    #[test]
+    fn trigger_chain_is_never_pruned() {
+        let mut ir = ir_with_steps_no_return(vec![]);
+        ir.evm_chains = vec![chain("ethereum-testnet-sepolia", true)];
+
+        let warnings = prune_unused_resources(&mut ir);
+
+        assert_eq!(ir.evm_chains.len(), 1);
+        assert!(ir.evm_chains[0].used_for_trigger);
+        assert!(warnings.is_empty());
+    }
+}
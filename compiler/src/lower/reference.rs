@@ -1,9 +1,201 @@
 //! Parse `{{nodeId.field}}` strings into `ValueExpr`.
+//!
+//! Node lowering resolves config strings through a [`RefScope`], which
+//! reports malformed references (unclosed or nested `{{`, empty node or field
+//! parts, unknown node ids) as L010 errors instead of letting them through
+//! as literal text. Single braces, as in JSON bodies, are plain text.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 
+use crate::error::{CompilerError, JsonPathSegment, format_json_path};
 use crate::ir::types::*;
 
+/// Resolves the references in one node's config, collecting an L010 error
+/// for each malformed one.
+pub struct RefScope<'a> {
+    node_id: &'a str,
+    id_map: &'a HashMap<String, String>,
+    errors: RefCell<Vec<CompilerError>>,
+}
+
+impl<'a> RefScope<'a> {
+    pub fn new(node_id: &'a str, id_map: &'a HashMap<String, String>) -> Self {
+        RefScope {
+            node_id,
+            id_map,
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn id_map(&self) -> &'a HashMap<String, String> {
+        self.id_map
+    }
+
+    /// Resolve `input`, read from the node's `data.config` at `field`.
+    pub fn resolve(&self, input: &str, field: Vec<JsonPathSegment>) -> ValueExpr {
+        self.check(input, field);
+        resolve_value_expr(input, self.id_map)
+    }
+
+    /// Report malformed references in `input` without resolving it.
+    pub fn check(&self, input: &str, field: Vec<JsonPathSegment>) {
+        if let Err(reason) = check_references(input, self.id_map) {
+            let path = config_path(field);
+            let message = format!(
+                "Invalid reference in '{}': {}",
+                format_json_path(&path[2..]),
+                reason
+            );
+            self.errors.borrow_mut().push(
+                CompilerError::lower("L010", message, Some(self.node_id.to_string()))
+                    .with_path(path),
+            );
+        }
+    }
+
+    /// The errors collected so far, if any.
+    pub fn finish(self) -> Result<(), Vec<CompilerError>> {
+        let errors = self.errors.into_inner();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Node-relative path of a config field: `data.config.<field>`.
+pub fn config_path(field: Vec<JsonPathSegment>) -> Vec<JsonPathSegment> {
+    let mut path = vec![JsonPathSegment::key("data"), JsonPathSegment::key("config")];
+    path.extend(field);
+    path
+}
+
+/// Path of a top-level config field, relative to `data.config`.
+pub fn field(name: &str) -> Vec<JsonPathSegment> {
+    vec![JsonPathSegment::key(name)]
+}
+
+/// `<list>[index].<name>` (or `<list>[index]` when `name` is empty),
+/// relative to `data.config`.
+pub fn indexed_field(list: &str, index: usize, name: &str) -> Vec<JsonPathSegment> {
+    let mut path = vec![JsonPathSegment::key(list), JsonPathSegment::Index(index)];
+    if !name.is_empty() {
+        path.push(JsonPathSegment::key(name));
+    }
+    path
+}
+
+/// `<map>.<key>`, relative to `data.config`.
+pub fn keyed_field(map: &str, key: &str) -> Vec<JsonPathSegment> {
+    vec![JsonPathSegment::key(map), JsonPathSegment::key(key)]
+}
+
+/// Check every `{{...}}` in `input`: it must be closed, not nested, have
+/// non-empty node and field parts, and name `config`, `trigger`, `secrets`
+/// (or `secret`), or a node known to `id_map` (ids, labels and convenience-node
+/// aliases); `env` is rejected. Returns the first problem found, with its
+/// 1-based column in `input`.
+pub fn check_references(input: &str, id_map: &HashMap<String, String>) -> Result<(), String> {
+    let column = |byte: usize| input[..byte].chars().count() + 1;
+    let mut offset = 0;
+    while let Some(found) = input[offset..].find("{{") {
+        // In `{{{`, the first brace is literal text.
+        let mut start = offset + found;
+        while input[start + 2..].starts_with('{') {
+            start += 1;
+        }
+        let inner_start = start + 2;
+        let Some(len) = input[inner_start..].find("}}") else {
+            return Err(format!("unclosed '{{{{' at column {}", column(start)));
+        };
+        let inner = &input[inner_start..inner_start + len];
+        if let Some(nested) = inner.find("{{") {
+            return Err(format!(
+                "nested '{{{{' at column {}; references cannot contain other references",
+                column(inner_start + nested)
+            ));
+        }
+        check_single_ref(inner.trim(), id_map).map_err(|reason| {
+            format!(
+                "{} in '{{{{{}}}}}' at column {}",
+                reason,
+                inner,
+                column(start)
+            )
+        })?;
+        offset = inner_start + len + 2;
+    }
+    Ok(())
+}
+
+fn check_single_ref(inner: &str, id_map: &HashMap<String, String>) -> Result<(), String> {
+    if inner.is_empty() {
+        return Err("empty reference".into());
+    }
+    let (node_id, field_path) = split_ref(inner);
+    if node_id.trim().is_empty() {
+        return Err("missing node id".into());
+    }
+    if inner.contains('.') && field_path.trim().is_empty() {
+        return Err("missing field after '.'".into());
+    }
+    if node_id == "env" {
+        return Err(
+            "environment variables are not available when the workflow runs; \
+             use '{{config.NAME}}' or '{{secrets.NAME}}'"
+                .into(),
+        );
+    }
+    if !matches!(node_id, "config" | "trigger" | "secrets" | "secret")
+        && !id_map.contains_key(node_id)
+    {
+        return Err(format!("unknown node '{}'", node_id));
+    }
+    check_array_indexes(field_path)
+}
+
+/// Array indexes in a field path, `items[0]` or `items.0`, must be
+/// non-negative integers.
+fn check_array_indexes(field_path: &str) -> Result<(), String> {
+    let bad_index = |index: &str| {
+        Err(format!(
+            "array index '{}' must be a non-negative integer",
+            index
+        ))
+    };
+    let mut rest = field_path;
+    while let Some(open) = rest.find('[') {
+        let Some(len) = rest[open..].find(']') else {
+            return Err("unclosed '[' in field path".into());
+        };
+        let index = &rest[open + 1..open + len];
+        if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+            return bad_index(index);
+        }
+        rest = &rest[open + len + 1..];
+    }
+    for segment in field_path.split('.') {
+        if let Some(digits) = segment.strip_prefix('-')
+            && !digits.is_empty()
+            && digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return bad_index(segment);
+        }
+    }
+    Ok(())
+}
+
+/// `items[0].name` → `items.0.name`, the form bindings store indexes in.
+fn normalize_field_path(field_path: &str) -> String {
+    field_path
+        .replace('[', ".")
+        .replace(']', "")
+        .trim_start_matches('.')
+        .to_string()
+}
+
 /// Parse a string that may contain `{{nodeId.field}}` references into a ValueExpr.
 ///
 /// `id_map` maps original node IDs to expanded step IDs (for convenience nodes).
@@ -11,7 +203,11 @@ pub fn resolve_value_expr(input: &str, id_map: &HashMap<String, String>) -> Valu
     let trimmed = input.trim();
 
     // Pure reference: entire string is {{nodeId.field}}
-    if trimmed.starts_with("{{") && trimmed.ends_with("}}") && trimmed.matches("{{").count() == 1 {
+    if trimmed.starts_with("{{")
+        && trimmed.ends_with("}}")
+        && trimmed.matches("{{").count() == 1
+        && trimmed.find("}}") == Some(trimmed.len() - 2)
+    {
         let inner = &trimmed[2..trimmed.len() - 2];
         return parse_single_ref(inner, id_map);
     }
@@ -32,7 +228,7 @@ pub fn resolve_value_expr(input: &str, id_map: &HashMap<String, String>) -> Valu
 }
 
 fn parse_single_ref(inner: &str, id_map: &HashMap<String, String>) -> ValueExpr {
-    let (node_id, field_path) = split_ref(inner);
+    let (node_id, field_path) = split_ref(inner.trim());
 
     // Check if this is a config ref
     if node_id == "config" {
@@ -44,6 +240,11 @@ fn parse_single_ref(inner: &str, id_map: &HashMap<String, String>) -> ValueExpr
         return ValueExpr::trigger_data(field_path);
     }
 
+    // `{{secrets.NAME}}` (or `{{secret.NAME}}`): checked after lowering (L016, L017).
+    if matches!(node_id, "secrets" | "secret") {
+        return ValueExpr::secret(field_path);
+    }
+
     // Resolve through id_map for expanded convenience nodes and trigger aliases
     let step_id = id_map
         .get(node_id)
@@ -61,7 +262,7 @@ fn parse_single_ref(inner: &str, id_map: &HashMap<String, String>) -> ValueExpr
         return ValueExpr::raw(field_path);
     }
 
-    ValueExpr::binding(step_id, field_path)
+    ValueExpr::binding(step_id, normalize_field_path(field_path))
 }
 
 fn split_ref(s: &str) -> (&str, &str) {
@@ -75,7 +276,12 @@ fn parse_template_parts(input: &str, id_map: &HashMap<String, String>) -> Vec<Te
     let mut parts = Vec::new();
     let mut remaining = input;
 
-    while let Some(start) = remaining.find("{{") {
+    while let Some(mut start) = remaining.find("{{") {
+        // In `{{{`, the first brace is literal text.
+        while remaining[start + 2..].starts_with('{') {
+            start += 1;
+        }
+
         // Literal before the reference
         if start > 0 {
             parts.push(TemplatePart::Lit {
@@ -143,6 +349,22 @@ mod tests {
         assert!(matches!(result, ValueExpr::TriggerDataRef { field } if field == "body"));
     }
 
+    #[test]
+    fn secret_ref() {
+        for input in ["{{secrets.API_KEY}}", "{{secret.API_KEY}}"] {
+            let result = resolve_value_expr(input, &HashMap::new());
+            assert!(matches!(result, ValueExpr::SecretRef { name } if name == "API_KEY"));
+            assert!(check_references(input, &HashMap::new()).is_ok());
+        }
+    }
+
+    #[test]
+    fn env_ref_is_rejected() {
+        let err = check_references("https://{{env.API_HOST}}/v1", &HashMap::new()).unwrap_err();
+        assert!(err.starts_with("environment variables are not"), "{err}");
+        assert!(err.ends_with("in '{{env.API_HOST}}' at column 9"), "{err}");
+    }
+
     #[test]
     fn template_with_mixed() {
         let result = resolve_value_expr("https://api.com/{{parse-1.id}}/status", &HashMap::new());
@@ -187,4 +409,115 @@ mod tests {
                 if step_id == "http_step_0" && field_path == "body")
         );
     }
+
+    fn known_ids() -> HashMap<String, String> {
+        HashMap::from([
+            ("http-1".to_string(), "http-1".to_string()),
+            ("Fetch Price".to_string(), "http-1".to_string()),
+        ])
+    }
+
+    #[test]
+    fn unclosed_reference_reports_column() {
+        let err = check_references("price: {{http-1.body", &known_ids()).unwrap_err();
+        assert_eq!(err, "unclosed '{{' at column 8");
+        let err = check_references("{{http-1.body}", &known_ids()).unwrap_err();
+        assert_eq!(err, "unclosed '{{' at column 1");
+    }
+
+    #[test]
+    fn unknown_node_is_rejected() {
+        let err = check_references("x {{unknown-node.x}}", &known_ids()).unwrap_err();
+        assert_eq!(
+            err,
+            "unknown node 'unknown-node' in '{{unknown-node.x}}' at column 3"
+        );
+        // Ids, labels, `config` and `trigger` are all known.
+        let ok =
+            "{{http-1.body}} {{Fetch Price.body}} {{config.a}} {{trigger.b}} {{ http-1.body }}";
+        assert_eq!(check_references(ok, &known_ids()), Ok(()));
+    }
+
+    #[test]
+    fn empty_node_or_field_is_rejected() {
+        let err = check_references("{{.field}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("missing node id"), "{err}");
+        let err = check_references("{{http-1.}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("missing field after '.'"), "{err}");
+        let err = check_references("a {{ }}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("empty reference"), "{err}");
+        // A whole-output reference has no field and no dot.
+        assert_eq!(check_references("{{http-1}}", &known_ids()), Ok(()));
+    }
+
+    #[test]
+    fn bracket_indexes_become_numeric_segments() {
+        let result = resolve_value_expr("{{http-1.body.items[0].name}}", &known_ids());
+        assert!(
+            matches!(&result, ValueExpr::Binding(BindingRef { field_path, .. }) if field_path == "body.items.0.name"),
+            "{result:?}"
+        );
+        let result = resolve_value_expr("{{http-1.body.items.length}}", &known_ids());
+        assert!(
+            matches!(&result, ValueExpr::Binding(BindingRef { field_path, .. }) if field_path == "body.items.length"),
+            "{result:?}"
+        );
+        assert_eq!(
+            check_references(
+                "{{http-1.body.items[2][0]}} {{http-1.body.items.3}}",
+                &known_ids()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn negative_or_non_integer_index_is_rejected() {
+        let err = check_references("{{http-1.body.items[-1]}}", &known_ids()).unwrap_err();
+        assert!(
+            err.starts_with("array index '-1' must be a non-negative integer"),
+            "{err}"
+        );
+        let err = check_references("{{http-1.body.items.-2}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("array index '-2'"), "{err}");
+        let err = check_references("{{http-1.body.items[first]}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("array index 'first'"), "{err}");
+        let err = check_references("{{http-1.body.items[0}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("unclosed '[' in field path"), "{err}");
+    }
+
+    #[test]
+    fn nested_reference_is_rejected() {
+        let err = check_references("{{a.{{http-1.body}}}}", &known_ids()).unwrap_err();
+        assert!(err.starts_with("nested '{{' at column 5"), "{err}");
+    }
+
+    #[test]
+    fn json_body_braces_are_literal_text() {
+        let body = r#"{"a": "b", "nested": {"price": {{http-1.body}}}}"#;
+        assert_eq!(check_references(body, &known_ids()), Ok(()));
+        match resolve_value_expr(body, &known_ids()) {
+            ValueExpr::Template { parts } => {
+                assert_eq!(parts.len(), 3);
+                assert!(
+                    matches!(&parts[0], TemplatePart::Lit { value } if value == r#"{"a": "b", "nested": {"price": "#)
+                );
+                assert!(matches!(&parts[2], TemplatePart::Lit { value } if value == "}}"));
+            }
+            other => panic!("Expected Template, got {:?}", other),
+        }
+        assert!(matches!(
+            resolve_value_expr(r#"{"a": "b"}"#, &known_ids()),
+            ValueExpr::Literal(LiteralValue::String { value }) if value == r#"{"a": "b"}"#
+        ));
+        // In `{{{`, only the last two braces open the reference.
+        assert_eq!(check_references("{{{http-1.body}}}", &known_ids()), Ok(()));
+        match resolve_value_expr("{{{http-1.body}}}", &known_ids()) {
+            ValueExpr::Template { parts } => assert!(matches!(
+                &parts[1],
+                TemplatePart::Expr { value: ValueExpr::Binding(BindingRef { step_id, .. }) } if step_id == "http-1"
+            )),
+            other => panic!("Expected Template, got {:?}", other),
+        }
+    }
 }
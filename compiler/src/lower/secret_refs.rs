@@ -0,0 +1,99 @@
+//! `{{secrets.NAME}}` references (L016, L017).
+//!
+//! A secret may be read by any handler-side value: an HTTP request's URL,
+//! headers or body, a Code node input, and so on. Codegen fetches it with
+//! `runtime.getSecret` in the handler; for an HTTP request it reaches the fetch
+//! function through the augmented config, so neither its value nor a literal
+//! copy ends up in the generated source. Every secret read must be declared in
+//! `globalConfig.secrets`, and query parameters may not read one.
+
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::ir::types::*;
+use crate::ir::validate::{
+    collect_secret_refs_from_value_expr, collect_secret_value_refs_from_step,
+};
+
+use super::reference::{config_path, field, keyed_field};
+
+/// Check every `SecretRef` in the handler body. Runs before unused secrets
+/// are pruned, so a secret read only through a reference is kept.
+pub fn check_secret_refs(ir: &WorkflowIR) -> Result<(), Vec<CompilerError>> {
+    let mut errors = Vec::new();
+    check_block(&ir.handler_body, &ir.required_secrets, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_block(block: &Block, declared: &[SecretDeclaration], errors: &mut Vec<CompilerError>) {
+    for step in &block.steps {
+        let mut names = collect_secret_value_refs_from_step(step);
+        names.sort();
+        names.dedup();
+        for name in names
+            .iter()
+            .filter(|name| !declared.iter().any(|s| s.name == **name))
+        {
+            let mut error = CompilerError::lower(
+                "L017",
+                format!(
+                    "Step '{}' reads secret '{}', which is not declared in globalConfig.secrets",
+                    step.id, name
+                ),
+                Some(step.id.clone()),
+            );
+            if let Some(field) = http_field_reading(step, name) {
+                error = error.with_path(config_path(field));
+            }
+            errors.push(error);
+        }
+        if let Operation::HttpRequest(op) = &step.operation {
+            for (key, value) in &op.query_params {
+                let mut names = Vec::new();
+                collect_secret_refs_from_value_expr(value, &mut names);
+                if let Some(name) = names.first() {
+                    errors.push(
+                        CompilerError::lower(
+                            "L016",
+                            format!(
+                                "Query parameter '{}' of step '{}' reads secret '{}'; put the secret in the URL template or a header instead",
+                                key, step.id, name
+                            ),
+                            Some(step.id.clone()),
+                        )
+                        .with_path(config_path(keyed_field("queryParameters", key))),
+                    );
+                }
+            }
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            check_block(&branch.true_branch, declared, errors);
+            check_block(&branch.false_branch, declared, errors);
+        }
+    }
+}
+
+/// The URL, header or body of an HTTP request step that reads secret
+/// `name`, if any.
+fn http_field_reading(step: &Step, name: &str) -> Option<Vec<JsonPathSegment>> {
+    let Operation::HttpRequest(op) = &step.operation else {
+        return None;
+    };
+    let reads = |value: &ValueExpr| {
+        let mut names = Vec::new();
+        collect_secret_refs_from_value_expr(value, &mut names);
+        names.iter().any(|n| n == name)
+    };
+    if reads(&op.url) {
+        return Some(field("url"));
+    }
+    if let Some((key, _)) = op.headers.iter().find(|(_, value)| reads(value)) {
+        return Some(keyed_field("headers", key));
+    }
+    op.body
+        .as_ref()
+        .filter(|body| reads(&body.data) || body.fields.iter().any(|(_, v)| reads(v)))
+        .map(|_| keyed_field("body", "data"))
+}
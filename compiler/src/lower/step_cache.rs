@@ -0,0 +1,159 @@
+//! Reuse lowered steps across compilations.
+//!
+//! A node lowers to the same step as long as nothing it can read has changed.
+//! Each node's key is a digest of:
+//! - the node itself, minus its canvas position;
+//! - the keys of its graph predecessors and the target handles of their
+//!   edges, so a change anywhere upstream invalidates everything downstream
+//!   of it;
+//! - the nodes its `{{ref}}` strings name, which need not be ancestors (a
+//!   node on a parallel path may run earlier in the handler);
+//! - the id/label lookup table and the default chain, which every node reads.
+//!
+//! If/branch nodes are always lowered afresh; the nodes inside their arms
+//! still come from the cache.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codegen::hash::sha256_hex;
+use crate::error::CompilerError;
+use crate::ir::types::Step;
+use crate::parse::graph::WorkflowGraph;
+use crate::parse::types::Workflow;
+
+/// Lowered steps keyed by a digest of everything their node's lowering reads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepCache {
+    steps: HashMap<String, Step>,
+    /// Key of every node in the workflow being lowered.
+    #[serde(skip)]
+    keys: HashMap<String, String>,
+    /// Steps lowered or reused by the compilation in progress. Only these are
+    /// kept once it finishes, so the cache never outgrows one workflow.
+    #[serde(skip)]
+    used: HashMap<String, Step>,
+    #[serde(skip)]
+    pub(crate) hits: usize,
+    #[serde(skip)]
+    pub(crate) misses: usize,
+}
+
+impl StepCache {
+    /// Compute the node keys for `workflow`. `topo_order` must list every
+    /// node after its predecessors.
+    pub(crate) fn begin(
+        &mut self,
+        workflow: &Workflow,
+        graph: &WorkflowGraph,
+        topo_order: &[String],
+        id_map: &HashMap<String, String>,
+    ) {
+        self.used.clear();
+        self.hits = 0;
+        self.misses = 0;
+
+        let mut shared = String::new();
+        let mut entries: Vec<_> = id_map.iter().collect();
+        entries.sort();
+        for (name, id) in entries {
+            shared.push_str(&format!("{name}\u{0}{id}\u{0}"));
+        }
+        shared.push_str(
+            workflow
+                .global_config
+                .default_chain_selector
+                .as_deref()
+                .unwrap_or(""),
+        );
+        let shared = sha256_hex(shared.as_bytes());
+
+        let contents: HashMap<&str, String> = workflow
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut value = serde_json::to_value(node).expect("node serialization cannot fail");
+                if let Some(fields) = value.as_object_mut() {
+                    fields.remove("position");
+                }
+                (node.id(), value.to_string())
+            })
+            .collect();
+
+        // Each node's content chained with its predecessors', in topo order.
+        let mut upstream: HashMap<&str, String> = HashMap::new();
+        for id in topo_order {
+            let Some(content) = contents.get(id.as_str()) else {
+                continue;
+            };
+            let mut input = content.clone();
+            for (pred, label) in graph.incoming_edges(id) {
+                input.push('\u{0}');
+                input.push_str(pred);
+                input.push('\u{0}');
+                input.push_str(label.target_handle.as_deref().unwrap_or(""));
+                input.push('\u{0}');
+                input.push_str(upstream.get(pred).map_or("", String::as_str));
+            }
+            upstream.insert(id.as_str(), sha256_hex(input.as_bytes()));
+        }
+
+        self.keys.clear();
+        for (id, content) in &contents {
+            let mut input = format!(
+                "{shared}\u{0}{}",
+                upstream.get(id).map_or("", String::as_str)
+            );
+            for name in referenced_names(content) {
+                let target = id_map.get(name).map_or(name, String::as_str);
+                if let Some(referenced) = contents.get(target) {
+                    input.push('\u{0}');
+                    input.push_str(referenced);
+                }
+            }
+            self.keys
+                .insert(id.to_string(), sha256_hex(input.as_bytes()));
+        }
+    }
+
+    /// Keep only the steps the finished compilation used.
+    pub(crate) fn finish(&mut self) {
+        self.steps = std::mem::take(&mut self.used);
+    }
+
+    /// The cached step for `node_id`, or the result of `lower` (cached when
+    /// it succeeds).
+    pub(crate) fn get_or_lower(
+        &mut self,
+        node_id: &str,
+        lower: impl FnOnce() -> Result<Step, Vec<CompilerError>>,
+    ) -> Result<Step, Vec<CompilerError>> {
+        let Some(key) = self.keys.get(node_id) else {
+            return lower();
+        };
+        let step = match self.steps.remove(key) {
+            Some(step) => {
+                self.hits += 1;
+                step
+            }
+            None => {
+                self.misses += 1;
+                lower()?
+            }
+        };
+        self.used.insert(key.clone(), step.clone());
+        Ok(step)
+    }
+}
+
+/// First segment of every `{{name.field}}` reference in `text`.
+fn referenced_names(text: &str) -> Vec<&str> {
+    text.split("{{")
+        .skip(1)
+        .map(|rest| {
+            let end = rest.find(['.', '}']).unwrap_or(rest.len());
+            rest[..end].trim()
+        })
+        .collect()
+}
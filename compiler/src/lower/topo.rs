@@ -1,20 +1,25 @@
 //! Topological sort of the workflow graph.
 //!
 //! When multiple nodes are ready (no unmet dependencies), the topmost node
-//! (lowest canvas y) executes first; ties broken by leftmost (lowest x).
-//! This matches n8n v1 execution order behavior.
+//! (lowest canvas y) executes first; ties broken by leftmost (lowest x), then
+//! by node id. This matches n8n v1 execution order behavior, and the final id
+//! tiebreak keeps the order independent of `HashMap` iteration so the same
+//! workflow always produces the same step order.
 
 use std::collections::HashMap;
 
-use crate::error::CompilerError;
+use crate::cancel::{self, CancellationToken};
+use crate::error::{CompilerError, Phase};
 use crate::parse::graph::WorkflowGraph;
 use crate::parse::types::Workflow;
 
 /// Returns node IDs in topological order with position-based sibling ordering.
-/// Trigger is always first (it has no incoming edges).
+/// Trigger is always first (it has no incoming edges). Stops with X001 once
+/// `cancel` is cancelled.
 pub fn topo_sort(
     graph: &WorkflowGraph,
     workflow: &Workflow,
+    cancel: Option<&CancellationToken>,
 ) -> Result<Vec<String>, Vec<CompilerError>> {
     // Position lookup: node_id -> (y, x)
     let positions: HashMap<&str, (f64, f64)> = workflow
@@ -23,7 +28,8 @@ pub fn topo_sort(
         .map(|n| (n.id(), (n.position().y, n.position().x)))
         .collect();
 
-    let all_ids: Vec<String> = graph.node_indices.keys().cloned().collect();
+    let mut all_ids: Vec<String> = graph.node_indices.keys().cloned().collect();
+    all_ids.sort();
 
     // Kahn's algorithm: compute in-degrees
     let mut in_degree: HashMap<String, usize> =
@@ -45,6 +51,7 @@ pub fn topo_sort(
     let mut result = Vec::with_capacity(all_ids.len());
 
     while !ready.is_empty() {
+        cancel::check(cancel, Phase::Lower)?;
         let node_id = ready.remove(0);
         result.push(node_id.clone());
 
@@ -62,10 +69,9 @@ pub fn topo_sort(
     }
 
     if result.len() != all_ids.len() {
-        let cycle_node = in_degree
+        let cycle_node = all_ids
             .into_iter()
-            .find(|(_, deg)| *deg > 0)
-            .map(|(id, _)| id)
+            .find(|id| in_degree.get(id).is_some_and(|deg| *deg > 0))
             .unwrap_or_else(|| "unknown".to_string());
         return Err(vec![CompilerError::lower(
             "L001",
@@ -87,6 +93,8 @@ fn sort_by_position(ids: &mut Vec<String>, positions: &HashMap<&str, (f64, f64)>
             .get(b.as_str())
             .copied()
             .unwrap_or((f64::MAX, f64::MAX));
-        ay.total_cmp(&by).then(ax.total_cmp(&bx))
+        ay.total_cmp(&by)
+            .then(ax.total_cmp(&bx))
+            .then_with(|| a.cmp(b))
     });
 }
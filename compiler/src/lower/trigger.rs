@@ -2,18 +2,22 @@
 //! SYNC NOTE: Trigger variants here must stay aligned with
 //! `shared/model/node.ts` and `compiler/src/parse/types.rs`.
 
+use std::collections::HashMap;
+
 use crate::error::CompilerError;
 use crate::ir::types::*;
+use crate::ir::validate::collect_trigger_data_refs_from_step;
 use crate::parse::types::{
     CronTriggerConfig, EvmLogTriggerConfig, HttpTriggerConfig, WorkflowNode,
 };
 
-use super::reference::resolve_value_expr;
+use super::reference::{RefScope, indexed_field};
 
 pub struct TriggerResult {
     pub trigger_def: TriggerDef,
     pub trigger_param: TriggerParam,
-    pub evm_chain_for_trigger: Option<(String, String)>,
+    /// Chain selector an EvmLog trigger listens on.
+    pub evm_chain_for_trigger: Option<String>,
 }
 
 pub fn lower_trigger(
@@ -23,7 +27,7 @@ pub fn lower_trigger(
     match node {
         WorkflowNode::CronTrigger(n) => lower_cron_trigger(&n.data.config, config_fields),
         WorkflowNode::HttpTrigger(n) => lower_http_trigger(&n.data.config),
-        WorkflowNode::EvmLogTrigger(n) => lower_evm_log_trigger(&n.data.config),
+        WorkflowNode::EvmLogTrigger(n) => lower_evm_log_trigger(node.id(), &n.data.config),
         _ => Err(vec![CompilerError::lower(
             "L002",
             format!("Node '{}' is not a trigger", node.id()),
@@ -48,6 +52,8 @@ fn lower_cron_trigger(
         zod_type: ZodType::String,
         default_value: Some(combined_schedule),
         description: Some("Cron schedule (min 30s interval)".into()),
+        referenced_by: vec![],
+        optional_in_config: false,
     });
 
     let schedule = ValueExpr::config("schedule");
@@ -75,15 +81,22 @@ fn lower_http_trigger(config: &HttpTriggerConfig) -> Result<TriggerResult, Vec<C
 }
 
 fn lower_evm_log_trigger(
+    node_id: &str,
     config: &EvmLogTriggerConfig,
 ) -> Result<TriggerResult, Vec<CompilerError>> {
-    let binding_name = make_evm_binding_name(&config.chain_selector_name);
+    let chain_selector = config.chain_selector_name.trim();
+    let binding_name = make_evm_binding_name(chain_selector);
 
+    // The trigger runs before any step, so only `{{config.*}}` can resolve.
+    let no_nodes = HashMap::new();
+    let refs = RefScope::new(node_id, &no_nodes);
     let contract_addresses: Vec<ValueExpr> = config
         .contract_addresses
         .iter()
-        .map(|a| resolve_value_expr(a, &std::collections::HashMap::new()))
+        .enumerate()
+        .map(|(i, a)| refs.resolve(a, indexed_field("contractAddresses", i, "")))
         .collect();
+    refs.finish()?;
 
     let mut topic_filters = Vec::new();
     if let Some(filters) = &config.topic_filters {
@@ -116,7 +129,7 @@ fn lower_evm_log_trigger(
 
     Ok(TriggerResult {
         trigger_def: TriggerDef::EvmLog(EvmLogTriggerDef {
-            evm_client_binding: binding_name.clone(),
+            evm_client_binding: binding_name,
             contract_addresses,
             event_signature: config.event_signature.clone(),
             event_abi_json,
@@ -124,7 +137,7 @@ fn lower_evm_log_trigger(
             confidence,
         }),
         trigger_param: TriggerParam::EvmLog,
-        evm_chain_for_trigger: Some((config.chain_selector_name.clone(), binding_name)),
+        evm_chain_for_trigger: Some(chain_selector.to_string()),
     })
 }
 
@@ -132,3 +145,50 @@ pub fn make_evm_binding_name(chain_selector: &str) -> String {
     let sanitized = chain_selector.replace('-', "_");
     format!("evmClient_{}", sanitized)
 }
+
+/// Top-level `triggerData` fields each trigger type provides.
+fn trigger_data_fields(trigger: &TriggerDef) -> &'static [&'static str] {
+    match trigger {
+        TriggerDef::Cron(_) => &["scheduledTime", "actualTime"],
+        TriggerDef::Http(_) => &["input"],
+        TriggerDef::EvmLog(_) => &["topics", "data", "address", "eventArgs"],
+    }
+}
+
+/// Warn (W012) about each step that reads a trigger data field the
+/// workflow's trigger does not provide. Only the first segment of a field
+/// path is checked: `input.user.id` is fine on an HTTP trigger.
+pub fn unknown_trigger_field_warnings(ir: &WorkflowIR) -> Vec<CompilerError> {
+    let fields = trigger_data_fields(&ir.trigger);
+    let mut warnings = Vec::new();
+    warn_unknown_fields_in_block(&ir.handler_body, fields, &mut warnings);
+    warnings
+}
+
+fn warn_unknown_fields_in_block(block: &Block, fields: &[&str], warnings: &mut Vec<CompilerError>) {
+    for step in &block.steps {
+        let mut unknown: Vec<String> = Vec::new();
+        for field in collect_trigger_data_refs_from_step(step) {
+            let root = field.split(['.', '[']).next().unwrap_or_default();
+            if !root.is_empty() && !fields.contains(&root) && !unknown.contains(&field) {
+                unknown.push(field);
+            }
+        }
+        for field in unknown {
+            warnings.push(CompilerError::lower(
+                "W012",
+                format!(
+                    "Step '{}' reads trigger field '{}', which this trigger does not provide (expected one of: {})",
+                    step.id,
+                    field,
+                    fields.join(", ")
+                ),
+                Some(step.id.clone()),
+            ));
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            warn_unknown_fields_in_block(&branch.true_branch, fields, warnings);
+            warn_unknown_fields_in_block(&branch.false_branch, fields, warnings);
+        }
+    }
+}
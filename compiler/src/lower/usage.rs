@@ -0,0 +1,138 @@
+//! Post-lowering analysis of where config fields and secrets are used.
+//!
+//! Records on each `ConfigField` / `SecretDeclaration` the ids of the steps that
+//! read it, and marks it `optional_in_config` when every one of those steps sits
+//! inside a branch arm. A Slack webhook used only on the failure path, for
+//! example, no longer has to be filled with a dummy value for runs that never
+//! fail. Resources referenced at the top level of the handler (or by nothing at
+//! all, e.g. the trigger schedule) stay required.
+
+use std::collections::HashMap;
+
+use crate::ir::types::*;
+use crate::ir::validate::{collect_config_refs_from_step, collect_secret_refs_from_step};
+
+#[derive(Default)]
+struct Usage {
+    steps: Vec<String>,
+    top_level: bool,
+}
+
+/// Populate `referenced_by` and `optional_in_config` on every config field and secret.
+pub fn annotate_resource_usage(ir: &mut WorkflowIR) {
+    let mut config_usage: HashMap<String, Usage> = HashMap::new();
+    let mut secret_usage: HashMap<String, Usage> = HashMap::new();
+    walk_block(
+        &ir.handler_body,
+        false,
+        &mut config_usage,
+        &mut secret_usage,
+    );
+
+    for field in &mut ir.config_schema {
+        let usage = config_usage.remove(&field.name).unwrap_or_default();
+        field.optional_in_config = !usage.steps.is_empty() && !usage.top_level;
+        field.referenced_by = usage.steps;
+    }
+    for secret in &mut ir.required_secrets {
+        let usage = secret_usage.remove(&secret.name).unwrap_or_default();
+        secret.optional_in_config = !usage.steps.is_empty() && !usage.top_level;
+        secret.referenced_by = usage.steps;
+    }
+}
+
+fn walk_block(
+    block: &Block,
+    in_branch: bool,
+    config_usage: &mut HashMap<String, Usage>,
+    secret_usage: &mut HashMap<String, Usage>,
+) {
+    for step in &block.steps {
+        for field in collect_config_refs_from_step(step) {
+            record(config_usage, field, &step.id, in_branch);
+        }
+        if let Operation::Branch(branch) = &step.operation {
+            // Conditions are evaluated whether or not an arm runs; only the arms
+            // themselves are conditional.
+            walk_block(&branch.true_branch, true, config_usage, secret_usage);
+            walk_block(&branch.false_branch, true, config_usage, secret_usage);
+        } else {
+            for secret in collect_secret_refs_from_step(step) {
+                record(secret_usage, secret, &step.id, in_branch);
+            }
+        }
+    }
+}
+
+fn record(usage: &mut HashMap<String, Usage>, name: String, step_id: &str, in_branch: bool) {
+    let entry = usage.entry(name).or_default();
+    if !entry.steps.iter().any(|s| s == step_id) {
+        entry.steps.push(step_id.to_string());
+    }
+    entry.top_level |= !in_branch;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{branch_step, config_field, ir_with_steps_no_return, return_step};
+
+    fn ir_with(config_schema: Vec<ConfigField>, steps: Vec<Step>) -> WorkflowIR {
+        WorkflowIR {
+            config_schema,
+            ..ir_with_steps_no_return(steps)
+        }
+    }
+
+    #[test]
+    fn branch_only_config_field_is_optional() {
+        let mut ir = ir_with(
+            vec![config_field("slackWebhook")],
+            vec![branch_step(
+                "if-1",
+                vec![return_step("ok", ValueExpr::string("ok"))],
+                vec![return_step("fail", ValueExpr::config("slackWebhook"))],
+            )],
+        );
+
+        annotate_resource_usage(&mut ir);
+
+        let field = &ir.config_schema[0];
+        assert!(field.optional_in_config);
+        assert_eq!(field.referenced_by, vec!["fail".to_string()]);
+    }
+
+    #[test]
+    fn config_field_used_at_top_level_and_in_branch_stays_required() {
+        let mut ir = ir_with(
+            vec![config_field("threshold")],
+            vec![
+                return_step("log", ValueExpr::config("threshold")),
+                branch_step(
+                    "if-1",
+                    vec![return_step("ok", ValueExpr::config("threshold"))],
+                    vec![return_step("fail", ValueExpr::string("fail"))],
+                ),
+            ],
+        );
+
+        annotate_resource_usage(&mut ir);
+
+        let field = &ir.config_schema[0];
+        assert!(!field.optional_in_config);
+        assert_eq!(
+            field.referenced_by,
+            vec!["log".to_string(), "ok".to_string()]
+        );
+    }
+
+    #[test]
+    fn unreferenced_config_field_stays_required() {
+        let mut ir = ir_with(vec![config_field("schedule")], vec![]);
+
+        annotate_resource_usage(&mut ir);
+
+        assert!(!ir.config_schema[0].optional_in_config);
+        assert!(ir.config_schema[0].referenced_by.is_empty());
+    }
+}
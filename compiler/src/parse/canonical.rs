@@ -0,0 +1,222 @@
+//! Canonical form of a workflow, independent of how the editor laid it out.
+//!
+//! Two exports of the same logical workflow can differ in node array order,
+//! editor-assigned ids and canvas positions, and each of those leaks into the
+//! generated code: step order, binding names, config field order.
+//! [`canonicalize`] removes all three, so content-addressed deployments can
+//! dedupe them:
+//!
+//! - Nodes are numbered by a breadth-first walk from the trigger. A node's
+//!   successors are visited by source handle, then node type, then data
+//!   (label and config, with references to already numbered nodes rewritten),
+//!   and each node is renamed `<type>-<n>`, counting per type: `httpRequest-1`.
+//! - Edges and `{{nodeId.field}}` references in node configs follow the new
+//!   ids. Nodes are listed in walk order; edges are sorted by their endpoints
+//!   and renamed `edge-<n>`.
+//! - Positions are zeroed. Lowering then orders steps that do not depend on
+//!   each other by id instead of by canvas position; the IR is otherwise the
+//!   same as the original's (`ir::equivalence` checks this).
+//!
+//! Labels are kept, so `{{Label.field}}` references need no rewrite. Siblings
+//! with the same handle, type and data keep their input order. Nodes the
+//! trigger does not reach (which validation rejects) are numbered last.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde_json::Value;
+
+use super::types::{Workflow, WorkflowEdge, WorkflowNode};
+
+/// The canonical form of `workflow`. Lowering it gives the same IR as
+/// lowering `workflow`, with the ids from [`canonical_ids`].
+pub fn canonicalize(workflow: &Workflow) -> Workflow {
+    let order = walk_order(workflow);
+    let ids = number_nodes(&order);
+    let rename = |id: &str| ids.get(id).cloned();
+
+    let nodes = order
+        .iter()
+        .map(|node| {
+            let mut value = serde_json::to_value(node).expect("a workflow node always serializes");
+            value["id"] = ids[node.id()].clone().into();
+            value["position"] = serde_json::json!({ "x": 0.0, "y": 0.0 });
+            rewrite_strings(&mut value["data"]["config"], &rename);
+            serde_json::from_value(value).expect("a canonical node is still a valid node")
+        })
+        .collect();
+
+    let mut edges: Vec<WorkflowEdge> = workflow
+        .edges
+        .iter()
+        .map(|edge| WorkflowEdge {
+            source: rename(&edge.source).unwrap_or_else(|| edge.source.clone()),
+            target: rename(&edge.target).unwrap_or_else(|| edge.target.clone()),
+            ..edge.clone()
+        })
+        .collect();
+    edges.sort_by(|a, b| {
+        (&a.source, &a.source_handle, &a.target, &a.target_handle).cmp(&(
+            &b.source,
+            &b.source_handle,
+            &b.target,
+            &b.target_handle,
+        ))
+    });
+    for (i, edge) in edges.iter_mut().enumerate() {
+        edge.id = format!("edge-{}", i + 1);
+    }
+
+    Workflow {
+        nodes,
+        edges,
+        ..workflow.clone()
+    }
+}
+
+/// Original node id → canonical id.
+pub fn canonical_ids(workflow: &Workflow) -> HashMap<String, String> {
+    number_nodes(&walk_order(workflow))
+}
+
+fn number_nodes(order: &[&WorkflowNode]) -> HashMap<String, String> {
+    let mut per_type: HashMap<&str, usize> = HashMap::new();
+    order
+        .iter()
+        .map(|node| {
+            let n = per_type.entry(node.node_type()).or_default();
+            *n += 1;
+            (node.id().to_string(), format!("{}-{}", node.node_type(), n))
+        })
+        .collect()
+}
+
+/// Nodes in canonical order: breadth-first from the trigger, then from each
+/// unreached node in turn.
+fn walk_order(workflow: &Workflow) -> Vec<&WorkflowNode> {
+    let node_ids: HashSet<&str> = workflow.nodes.iter().map(|n| n.id()).collect();
+    let nodes: HashMap<&str, &WorkflowNode> = workflow.nodes.iter().map(|n| (n.id(), n)).collect();
+    let data: HashMap<&str, Value> = workflow
+        .nodes
+        .iter()
+        .map(|n| {
+            let value = serde_json::to_value(n).expect("a workflow node always serializes");
+            (n.id(), value["data"].clone())
+        })
+        .collect();
+    let mut successors: HashMap<&str, Vec<(&str, &str)>> = HashMap::new();
+    for edge in &workflow.edges {
+        if nodes.contains_key(edge.target.as_str()) {
+            successors.entry(edge.source.as_str()).or_default().push((
+                edge.source_handle.as_deref().unwrap_or_default(),
+                edge.target.as_str(),
+            ));
+        }
+    }
+
+    // Position in the walk of each node reached so far.
+    let mut visited: HashMap<&str, usize> = HashMap::new();
+    let mut order: Vec<&WorkflowNode> = Vec::new();
+    // Data with each reference to a node replaced by its walk position, or
+    // `?` if it has not been reached yet.
+    let key = |node: &WorkflowNode, visited: &HashMap<&str, usize>| {
+        let mut data = data[node.id()].clone();
+        rewrite_strings(&mut data, &|id| {
+            node_ids
+                .contains(id)
+                .then(|| visited.get(id).map_or("?".into(), |i| format!("#{}", i)))
+        });
+        (node.node_type(), data.to_string())
+    };
+
+    loop {
+        let root = workflow
+            .nodes
+            .iter()
+            .filter(|n| !visited.contains_key(n.id()))
+            .min_by_key(|n| (!n.is_trigger(), key(n, &visited)));
+        let Some(root) = root else { break };
+        visited.insert(root.id(), order.len());
+        order.push(root);
+
+        let mut queue = VecDeque::from([root]);
+        while let Some(node) = queue.pop_front() {
+            let mut next: Vec<_> = successors
+                .get(node.id())
+                .into_iter()
+                .flatten()
+                .filter(|(_, target)| !visited.contains_key(target))
+                .map(|(handle, target)| (*handle, key(nodes[target], &visited), *target))
+                .collect();
+            next.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+            for (_, _, target) in next {
+                if !visited.contains_key(target) {
+                    visited.insert(target, order.len());
+                    order.push(nodes[target]);
+                    queue.push_back(nodes[target]);
+                }
+            }
+        }
+    }
+    order
+}
+
+/// Rewrite the node part of every `{{node.field}}` reference in the strings
+/// of `value` for which `rename` returns a new name.
+fn rewrite_strings(value: &mut Value, rename: &dyn Fn(&str) -> Option<String>) {
+    match value {
+        Value::String(s) if s.contains("{{") => *s = rewrite_refs(s, rename),
+        Value::Array(items) => items.iter_mut().for_each(|v| rewrite_strings(v, rename)),
+        Value::Object(map) => map.values_mut().for_each(|v| rewrite_strings(v, rename)),
+        _ => {}
+    }
+}
+
+/// Scans references the way `lower::reference` resolves them: in `{{{`, the
+/// first brace is literal text, and an unclosed `{{` ends the scan.
+fn rewrite_refs(input: &str, rename: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(mut start) = rest.find("{{") {
+        while rest[start + 2..].starts_with('{') {
+            start += 1;
+        }
+        let inner_start = start + 2;
+        let Some(len) = rest[inner_start..].find("}}") else {
+            break;
+        };
+        let inner = &rest[inner_start..inner_start + len];
+        let head_len = inner.find('.').unwrap_or(inner.len());
+        let head = inner[..head_len].trim();
+
+        out.push_str(&rest[..inner_start]);
+        match rename(head) {
+            Some(new) => {
+                out.push_str(&inner[..head_len].replacen(head, &new, 1));
+                out.push_str(&inner[head_len..]);
+            }
+            None => out.push_str(inner),
+        }
+        out.push_str("}}");
+        rest = &rest[inner_start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_only_known_reference_heads() {
+        let rename = |id: &str| (id == "http-1").then(|| "httpRequest-1".to_string());
+        assert_eq!(
+            rewrite_refs(
+                "{{http-1.body}} {{ http-1 }} {{config.http-1}} {{{http-1.x}}",
+                &rename
+            ),
+            "{{httpRequest-1.body}} {{ httpRequest-1 }} {{config.http-1}} {{{httpRequest-1.x}}"
+        );
+        assert_eq!(rewrite_refs("{{http-1.body", &rename), "{{http-1.body");
+    }
+}
@@ -1,21 +1,25 @@
 //! petgraph-based directed graph wrapper for the visual workflow.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef};
 
 use super::types::Workflow;
-use crate::error::CompilerError;
+use crate::error::{CompilerError, RelatedDiagnostic};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EdgeLabel {
     pub source_handle: Option<String>,
     pub target_handle: Option<String>,
+    pub label: Option<String>,
 }
 
 pub struct WorkflowGraph {
     pub graph: DiGraph<String, EdgeLabel>,
     pub node_indices: HashMap<String, NodeIndex>,
+    /// Non-fatal issues found while building (e.g. duplicate edges that were dropped).
+    pub warnings: Vec<CompilerError>,
 }
 
 impl WorkflowGraph {
@@ -23,6 +27,7 @@ impl WorkflowGraph {
         let mut graph = DiGraph::new();
         let mut node_indices = HashMap::new();
         let mut errors = Vec::new();
+        let mut warnings = Vec::new();
 
         // Add all nodes
         for node in &workflow.nodes {
@@ -31,19 +36,38 @@ impl WorkflowGraph {
             node_indices.insert(id, idx);
         }
 
-        // Add all edges
+        // Add all edges, keeping only the first of any identical duplicates so
+        // predecessor/successor lists don't double-count a connection.
+        let mut seen_edges = HashSet::new();
         for edge in &workflow.edges {
             let source_idx = node_indices.get(&edge.source);
             let target_idx = node_indices.get(&edge.target);
 
             match (source_idx, target_idx) {
                 (Some(&s), Some(&t)) => {
+                    let key = (
+                        &edge.source,
+                        &edge.target,
+                        &edge.source_handle,
+                        &edge.target_handle,
+                    );
+                    if !seen_edges.insert(key) {
+                        warnings.push(CompilerError::parse(
+                            "W003",
+                            format!(
+                                "Edge '{}' duplicates an existing edge from '{}' to '{}' and was ignored",
+                                edge.id, edge.source, edge.target
+                            ),
+                        ));
+                        continue;
+                    }
                     graph.add_edge(
                         s,
                         t,
                         EdgeLabel {
                             source_handle: edge.source_handle.clone(),
                             target_handle: edge.target_handle.clone(),
+                            label: edge.label.clone(),
                         },
                     );
                 }
@@ -75,20 +99,91 @@ impl WorkflowGraph {
         Ok(WorkflowGraph {
             graph,
             node_indices,
+            warnings,
         })
     }
 
+    /// Check that the workflow is a single DAG entered only through its
+    /// trigger. Nodes the trigger cannot reach are grouped into the orphan
+    /// subgraphs they form, with one W007 warning per subgraph listing its
+    /// node ids. Returns nothing unless there is exactly one trigger (V001).
+    ///
+    /// V005 already rejects each unreachable node at compile time; this is
+    /// the editor-facing view that points at whole disconnected pieces.
+    pub fn validate_single_entry(&self, workflow: &Workflow) -> Vec<CompilerError> {
+        let mut triggers = workflow.nodes.iter().filter(|n| n.is_trigger());
+        let (Some(trigger), None) = (triggers.next(), triggers.next()) else {
+            return vec![];
+        };
+        let Some(&trigger_idx) = self.node_indices.get(trigger.id()) else {
+            return vec![];
+        };
+
+        let mut visited = HashSet::new();
+        let mut bfs = Bfs::new(&self.graph, trigger_idx);
+        while let Some(nx) = bfs.next(&self.graph) {
+            visited.insert(nx);
+        }
+
+        let mut warnings = Vec::new();
+        for node in &workflow.nodes {
+            let Some(&start) = self.node_indices.get(node.id()) else {
+                continue;
+            };
+            if !visited.insert(start) {
+                continue;
+            }
+            // Collect the orphan's whole subgraph, following edges both ways
+            // but never back into the part the trigger reaches.
+            let mut subgraph = vec![start];
+            let mut stack = vec![start];
+            while let Some(idx) = stack.pop() {
+                for next in self.graph.neighbors_undirected(idx) {
+                    if visited.insert(next) {
+                        subgraph.push(next);
+                        stack.push(next);
+                    }
+                }
+            }
+            // Report ids in workflow order.
+            let node_ids: Vec<String> = workflow
+                .nodes
+                .iter()
+                .filter(|n| {
+                    self.node_indices
+                        .get(n.id())
+                        .is_some_and(|idx| subgraph.contains(idx))
+                })
+                .map(|n| n.id().to_string())
+                .collect();
+            let quoted: Vec<String> = node_ids.iter().map(|id| format!("'{id}'")).collect();
+            let mut warning = CompilerError::validate(
+                "W007",
+                format!(
+                    "Nodes {} are not connected to the trigger '{}' and will never execute",
+                    quoted.join(", "),
+                    trigger.id()
+                ),
+                node_ids.first().cloned(),
+            );
+            warning.related.push(RelatedDiagnostic {
+                message: "Disconnected subgraph".into(),
+                node_ids,
+            });
+            warnings.push(warning.locate_node(workflow));
+        }
+        warnings
+    }
+
     pub fn successors(&self, node_id: &str) -> Vec<(&str, &EdgeLabel)> {
         let Some(&idx) = self.node_indices.get(node_id) else {
             return vec![];
         };
+        // Walk edges (not neighbors) so parallel edges to the same target,
+        // e.g. both If handles, each keep their own label.
         self.graph
-            .neighbors_directed(idx, petgraph::Direction::Outgoing)
-            .map(|n| {
-                let edge_idx = self.graph.find_edge(idx, n).unwrap();
-                let label = &self.graph[edge_idx];
-                (self.graph[n].as_str(), label)
-            })
+            .edges_directed(idx, petgraph::Direction::Outgoing)
+            .map(|e| (self.graph[e.target()].as_str(), e.weight()))
             .collect()
     }
 
@@ -106,6 +201,22 @@ impl WorkflowGraph {
         self.successors(node_id)
     }
 
+    /// Incoming edges as `(source, label)`, in the order the edges were added.
+    pub fn incoming_edges(&self, node_id: &str) -> Vec<(&str, &EdgeLabel)> {
+        let Some(&idx) = self.node_indices.get(node_id) else {
+            return vec![];
+        };
+        let mut edges: Vec<_> = self
+            .graph
+            .edges_directed(idx, petgraph::Direction::Incoming)
+            .collect();
+        edges.sort_by_key(|e| e.id().index());
+        edges
+            .into_iter()
+            .map(|e| (self.graph[e.source()].as_str(), e.weight()))
+            .collect()
+    }
+
     pub fn incoming_count(&self, node_id: &str) -> usize {
         self.predecessors(node_id).len()
     }
@@ -0,0 +1,221 @@
+//! Schema migrations for saved workflows.
+//!
+//! The editor occasionally renames or reshapes node config fields, which
+//! would make older saved workflows fail to deserialize. A workflow records
+//! the shape it was saved in as `schemaVersion` (1 when absent). Before typed
+//! deserialization, [`migrate`] upgrades an older document at the JSON level,
+//! one [`Migration`] per version step, and reports every node a migration
+//! changed. A document newer than [`CURRENT_SCHEMA_VERSION`] is rejected
+//! (P004) rather than half-understood.
+
+use serde_json::{Map, Value};
+
+use crate::error::{CompilerError, JsonPathSegment};
+
+/// One version step of the workflow schema.
+pub struct Migration {
+    /// Upgrades documents at this version to `from_version + 1`.
+    pub from_version: u32,
+    pub description: &'static str,
+    /// Rewrite one node's `data.config`, given the node's `type`. Returns
+    /// whether anything changed.
+    pub apply: fn(node_type: &str, config: &mut Map<String, Value>) -> bool,
+}
+
+/// Every migration, in version order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 1,
+        description: "HTTP request `cacheTtl` renamed to `cacheMaxAge`",
+        apply: rename_cache_ttl,
+    },
+    Migration {
+        from_version: 2,
+        description: "`combineWith` of filter / if conditions lowercased (`AND` → `and`)",
+        apply: lowercase_combine_with,
+    },
+    Migration {
+        from_version: 3,
+        description: "HTTP request `authentication` string (`bearer:<secret>`, `none`) expanded to `{ type, tokenSecret }`",
+        apply: expand_authentication_string,
+    },
+    Migration {
+        from_version: 4,
+        description: "HTTP request string `body` wrapped as `{ contentType: \"json\", data }`",
+        apply: wrap_body_string,
+    },
+];
+
+/// Schema version the types in [`crate::parse::types`] describe.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1 + MIGRATIONS.len() as u32;
+
+/// A migration that changed one node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub from_version: u32,
+    pub description: &'static str,
+    pub node_index: usize,
+    pub node_id: Option<String>,
+}
+
+impl AppliedMigration {
+    /// Info diagnostic I001 at the migrated node's config.
+    pub fn to_diagnostic(&self) -> CompilerError {
+        CompilerError {
+            node_id: self.node_id.clone(),
+            ..CompilerError::parse(
+                "I001",
+                format!(
+                    "Migrated from schemaVersion {}: {}",
+                    self.from_version, self.description
+                ),
+            )
+        }
+        .with_path(vec![
+            JsonPathSegment::key("nodes"),
+            JsonPathSegment::Index(self.node_index),
+            JsonPathSegment::key("data"),
+            JsonPathSegment::key("config"),
+        ])
+    }
+}
+
+/// The document's `schemaVersion`: 1 when absent, P004 when it is not a
+/// positive integer.
+pub fn schema_version(doc: &Value) -> Result<u32, Vec<CompilerError>> {
+    let Some(version) = doc.get("schemaVersion") else {
+        return Ok(1);
+    };
+    version
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| *v >= 1)
+        .ok_or_else(|| {
+            vec![
+                CompilerError::parse(
+                    "P004",
+                    format!("schemaVersion must be a positive integer (got {})", version),
+                )
+                .with_path(vec![JsonPathSegment::key("schemaVersion")]),
+            ]
+        })
+}
+
+/// Upgrade `doc` to [`CURRENT_SCHEMA_VERSION`] in place, returning one entry
+/// per node each migration changed. A document from a newer editor is P004.
+pub fn migrate(doc: &mut Value) -> Result<Vec<AppliedMigration>, Vec<CompilerError>> {
+    let version = schema_version(doc)?;
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(vec![
+            CompilerError::parse(
+                "P004",
+                format!(
+                    "Workflow has schemaVersion {}, but this compiler supports up to {}; update the compiler",
+                    version, CURRENT_SCHEMA_VERSION
+                ),
+            )
+            .with_path(vec![JsonPathSegment::key("schemaVersion")]),
+        ]);
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from_version >= version) {
+        let Some(nodes) = doc.get_mut("nodes").and_then(Value::as_array_mut) else {
+            break;
+        };
+        for (node_index, node) in nodes.iter_mut().enumerate() {
+            let node_type = node
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let node_id = node.get("id").and_then(Value::as_str).map(str::to_string);
+            let Some(config) = node
+                .get_mut("data")
+                .and_then(|d| d.get_mut("config"))
+                .and_then(Value::as_object_mut)
+            else {
+                continue;
+            };
+            if (migration.apply)(&node_type, config) {
+                applied.push(AppliedMigration {
+                    from_version: migration.from_version,
+                    description: migration.description,
+                    node_index,
+                    node_id,
+                });
+            }
+        }
+    }
+    if let Some(object) = doc.as_object_mut() {
+        object.insert("schemaVersion".into(), CURRENT_SCHEMA_VERSION.into());
+    }
+    Ok(applied)
+}
+
+fn rename_cache_ttl(node_type: &str, config: &mut Map<String, Value>) -> bool {
+    if node_type != "httpRequest" {
+        return false;
+    }
+    let Some(ttl) = config.remove("cacheTtl") else {
+        return false;
+    };
+    config.entry("cacheMaxAge").or_insert(ttl);
+    true
+}
+
+fn lowercase_combine_with(node_type: &str, config: &mut Map<String, Value>) -> bool {
+    if !matches!(node_type, "filter" | "if") {
+        return false;
+    }
+    lowercase_group(config)
+}
+
+/// Lowercase `combineWith` in a condition list and its nested groups.
+fn lowercase_group(group: &mut Map<String, Value>) -> bool {
+    let mut changed = false;
+    if let Some(Value::String(combine_with)) = group.get_mut("combineWith") {
+        let lower = combine_with.to_lowercase();
+        if *combine_with != lower {
+            *combine_with = lower;
+            changed = true;
+        }
+    }
+    if let Some(Value::Array(conditions)) = group.get_mut("conditions") {
+        for condition in conditions.iter_mut().filter_map(Value::as_object_mut) {
+            changed |= lowercase_group(condition);
+        }
+    }
+    changed
+}
+
+fn expand_authentication_string(node_type: &str, config: &mut Map<String, Value>) -> bool {
+    if node_type != "httpRequest" {
+        return false;
+    }
+    let Some(Value::String(auth)) = config.get("authentication") else {
+        return false;
+    };
+    let expanded = match auth.trim().split_once(':') {
+        Some(("bearer", secret)) => {
+            serde_json::json!({ "type": "bearerToken", "tokenSecret": secret.trim() })
+        }
+        None if matches!(auth.trim(), "" | "none") => serde_json::json!({ "type": "none" }),
+        // Anything else is left for deserialization to report.
+        _ => return false,
+    };
+    config.insert("authentication".into(), expanded);
+    true
+}
+
+fn wrap_body_string(node_type: &str, config: &mut Map<String, Value>) -> bool {
+    if node_type != "httpRequest" {
+        return false;
+    }
+    let Some(Value::String(data)) = config.get("body") else {
+        return false;
+    };
+    let body = serde_json::json!({ "contentType": "json", "data": data });
+    config.insert("body".into(), body);
+    true
+}
@@ -1,23 +1,203 @@
 //! Parse phase: JSON → Rust types + graph construction.
 
+pub mod canonical;
 pub mod graph;
+pub mod migrate;
 pub mod types;
 
+pub use canonical::canonicalize;
 pub use graph::WorkflowGraph;
 pub use types::*;
 
-use crate::error::CompilerError;
+use serde::de::DeserializeOwned;
+
+use crate::error::{CompilerError, JsonPathSegment};
 
 /// Deserialize a workflow JSON string into a `Workflow` struct.
+/// The error's `path` points at the value that failed to deserialize; nodes
+/// of a type this version does not know are each reported as P003.
+/// Documents with an older `schemaVersion` are migrated first (see
+/// [`parse_with_migrations`]).
 pub fn parse(json: &str) -> Result<Workflow, Vec<CompilerError>> {
-    serde_json::from_str::<Workflow>(json).map_err(|e| {
-        vec![CompilerError::parse(
-            "P001",
-            format!("Failed to parse workflow JSON: {}", e),
-        )]
+    parse_with_migrations(json).map(|(workflow, _)| workflow)
+}
+
+/// [`parse`], also returning an info diagnostic (I001) for every node that
+/// a [`migrate`] step rewrote. A `schemaVersion` newer than
+/// [`migrate::CURRENT_SCHEMA_VERSION`] or not a positive integer is P004.
+pub fn parse_with_migrations(
+    json: &str,
+) -> Result<(Workflow, Vec<CompilerError>), Vec<CompilerError>> {
+    // Text that is not JSON at all is reported by the typed pass below.
+    let Ok(mut doc) = serde_json::from_str::<serde_json::Value>(json) else {
+        return deserialize(json).map(|workflow| (workflow, vec![]));
+    };
+    let applied = migrate::migrate(&mut doc)?;
+    let mut workflow = if applied.is_empty() {
+        deserialize(json)?
+    } else {
+        deserialize(&doc.to_string())?
+    };
+    workflow.schema_version = migrate::CURRENT_SCHEMA_VERSION;
+    let infos = applied.iter().map(|a| a.to_diagnostic()).collect();
+    Ok((workflow, infos))
+}
+
+fn deserialize(json: &str) -> Result<Workflow, Vec<CompilerError>> {
+    let de = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize::<_, Workflow>(de).map_err(|e| {
+        let unsupported = unsupported_node_types(json);
+        if !unsupported.is_empty() {
+            return unsupported;
+        }
+        let mut path = json_path(e.path());
+        match path.as_slice() {
+            [JsonPathSegment::Key(nodes), JsonPathSegment::Index(i)] if nodes == "nodes" => {
+                path.extend(node_error_path(json, *i).unwrap_or_default());
+            }
+            _ => path = error_path(&e),
+        }
+        vec![
+            CompilerError::parse(
+                "P001",
+                format!("Failed to parse workflow JSON: {}", e.into_inner()),
+            )
+            .with_path(path),
+        ]
     })
 }
 
+/// Node `type` tags this compiler version understands.
+pub const NODE_TYPES: &[&str] = &[
+    "cronTrigger",
+    "httpTrigger",
+    "evmLogTrigger",
+    "httpRequest",
+    "evmRead",
+    "evmWrite",
+    "broadcastWrite",
+    "codeNode",
+    "abiEncode",
+    "abiDecode",
+    "merge",
+    "math",
+    "filter",
+    "if",
+    "kvGet",
+    "kvSet",
+    "ai",
+    "return",
+    "error",
+];
+
+/// P003 for every node whose `type` is a string not in [`NODE_TYPES`], e.g.
+/// a node added by a newer editor. Serde would only report the first one,
+/// without its node id.
+fn unsupported_node_types(json: &str) -> Vec<CompilerError> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return vec![];
+    };
+    let Some(nodes) = value.get("nodes").and_then(|n| n.as_array()) else {
+        return vec![];
+    };
+    nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, node)| {
+            let node_type = node.get("type")?.as_str()?;
+            if NODE_TYPES.contains(&node_type) {
+                return None;
+            }
+            let node_id = node.get("id").and_then(|id| id.as_str());
+            let message = format!(
+                "Node '{}' has type '{}', which this compiler version does not support \
+                 (supported: {})",
+                node_id.unwrap_or("?"),
+                node_type,
+                NODE_TYPES.join(", ")
+            );
+            Some(
+                CompilerError {
+                    node_id: node_id.map(str::to_string),
+                    ..CompilerError::parse("P003", message)
+                }
+                .with_path(vec![
+                    JsonPathSegment::key("nodes"),
+                    JsonPathSegment::Index(i),
+                    JsonPathSegment::key("type"),
+                ]),
+            )
+        })
+        .collect()
+}
+
+/// Path to the failing value. A missing field is reported by its parent
+/// object, so the field name is appended to point at where it belongs.
+pub(crate) fn error_path(
+    e: &serde_path_to_error::Error<serde_json::Error>,
+) -> Vec<JsonPathSegment> {
+    let mut path = json_path(e.path());
+    let message = e.inner().to_string();
+    if let Some(rest) = message.strip_prefix("missing field `")
+        && let Some((field, _)) = rest.split_once('`')
+    {
+        path.push(JsonPathSegment::key(field));
+    }
+    path
+}
+
+fn json_path(path: &serde_path_to_error::Path) -> Vec<JsonPathSegment> {
+    use serde_path_to_error::Segment;
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(JsonPathSegment::Index(*index)),
+            Segment::Map { key } => Some(JsonPathSegment::key(key.as_str())),
+            Segment::Enum { .. } | Segment::Unknown => None,
+        })
+        .collect()
+}
+
+/// Path inside node `index` to the field that failed to deserialize.
+///
+/// `WorkflowNode` is tagged by `type`, so serde buffers each node before
+/// picking a variant and the path stops at `nodes[i]`. Deserializing the
+/// node again as its concrete `NodeBase<Config>` recovers the field.
+fn node_error_path(json: &str, index: usize) -> Option<Vec<JsonPathSegment>> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let node = value.get("nodes")?.get(index)?;
+    match node.get("type")?.as_str()? {
+        "cronTrigger" => node_base_error_path::<CronTriggerConfig>(node),
+        "httpTrigger" => node_base_error_path::<HttpTriggerConfig>(node),
+        "evmLogTrigger" => node_base_error_path::<EvmLogTriggerConfig>(node),
+        "httpRequest" => node_base_error_path::<HttpRequestConfig>(node),
+        "evmRead" => node_base_error_path::<EvmReadConfig>(node),
+        "evmWrite" => node_base_error_path::<EvmWriteConfig>(node),
+        "broadcastWrite" => node_base_error_path::<BroadcastWriteConfig>(node),
+        "codeNode" => node_base_error_path::<CodeNodeConfig>(node),
+        "abiEncode" => node_base_error_path::<AbiEncodeConfig>(node),
+        "abiDecode" => node_base_error_path::<AbiDecodeConfig>(node),
+        "merge" => node_base_error_path::<MergeConfig>(node),
+        "math" => node_base_error_path::<MathConfig>(node),
+        "filter" => node_base_error_path::<FilterConfig>(node),
+        "if" => node_base_error_path::<IfConfig>(node),
+        "kvGet" => node_base_error_path::<KvGetConfig>(node),
+        "kvSet" => node_base_error_path::<KvSetConfig>(node),
+        "ai" => node_base_error_path::<AiNodeConfig>(node),
+        "return" => node_base_error_path::<ReturnConfig>(node),
+        "error" => node_base_error_path::<ErrorConfig>(node),
+        // Unknown tag: the node itself is the problem.
+        _ => None,
+    }
+}
+
+fn node_base_error_path<C: DeserializeOwned>(
+    node: &serde_json::Value,
+) -> Option<Vec<JsonPathSegment>> {
+    serde_path_to_error::deserialize::<_, NodeBase<C>>(node)
+        .err()
+        .map(|e| error_path(&e))
+}
+
 /// Parse JSON and build the graph in one step.
 pub fn parse_and_build(json: &str) -> Result<(Workflow, WorkflowGraph), Vec<CompilerError>> {
     let workflow = parse(json)?;
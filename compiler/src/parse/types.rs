@@ -4,6 +4,13 @@
 //! SYNC NOTE: Keep this file aligned with `shared/model/node.ts`.
 //! When NodeType/config shapes change, also review validate/lower modules
 //! and frontend registry/config renderers listed in `shared/model/node.ts`.
+//!
+//! Fields the types do not model are ignored, so a workflow saved by a newer
+//! frontend still parses; do not add `deny_unknown_fields`. A node `type`
+//! that `WorkflowNode` lacks is still a parse error (P003 at `nodes[i].type`).
+//! Lists whose empty value is meaningful default to empty when omitted.
+
+use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +21,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Workflow {
+    /// Shape the document was saved in; `parse` migrates older documents
+    /// and sets it to `migrate::CURRENT_SCHEMA_VERSION`. 1 when absent.
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     pub id: String,
     pub name: String,
     pub description: Option<String>,
@@ -25,6 +36,10 @@ pub struct Workflow {
     pub updated_at: String,
 }
 
+fn default_schema_version() -> u32 {
+    1
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkflowEdge {
@@ -33,14 +48,64 @@ pub struct WorkflowEdge {
     pub target: String,
     pub source_handle: Option<String>,
     pub target_handle: Option<String>,
+    /// Text the editor shows on the edge. On an If node's outgoing edge it
+    /// names the arm in the generated code.
+    pub label: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GlobalConfig {
     pub is_testnet: bool,
+    #[serde(default)]
     pub secrets: Vec<SecretReference>,
+    #[serde(default)]
     pub rpcs: Vec<RpcEntry>,
+    /// Chain used by EVM nodes that leave `chainSelectorName` empty.
+    pub default_chain_selector: Option<String>,
+    /// What to do when an execution path ends without a Return/Error node.
+    #[serde(default)]
+    pub auto_return: AutoReturnMode,
+    /// `cacheMaxAge` for GET/HEAD requests that leave it unset.
+    pub default_cache_max_age: Option<u32>,
+    /// Parameters of a workflow template, filled in by `template::instantiate`.
+    pub template_params: Option<Vec<TemplateParam>>,
+    /// KV reads and writes allowed per execution, in place of the default
+    /// `MAX_KV_OPERATIONS`.
+    pub max_kv_operations: Option<usize>,
+    /// Hosts a testnet workflow may call over plain `http://` without a W006
+    /// warning, e.g. `localhost` for a local mock API. Mainnet still requires
+    /// https everywhere.
+    #[serde(default)]
+    pub allow_http_hosts: Vec<String>,
+}
+
+/// A value the user supplies when instantiating a template, referenced in
+/// node configs as `${param:NAME}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateParam {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<String>,
+}
+
+/// Handling of execution paths that end without an explicit Return or Error node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoReturnMode {
+    /// Lowering fails with L009, naming each node a path ends on.
+    Error,
+    /// Append `return "Workflow completed"` after the handler body.
+    #[default]
+    #[serde(alias = "auto_string")]
+    AutoString,
+    /// End each open path with `__stringify(<leaf output>)`, or the
+    /// `AutoString` message when the leaf has no output binding.
+    #[serde(alias = "auto_summary")]
+    AutoSummary,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +120,10 @@ pub struct RpcEntry {
 pub struct SecretReference {
     pub name: String,
     pub env_variable: String,
+    /// Overrides `env_variable` for staging.
+    pub env_variable_staging: Option<String>,
+    /// Overrides `env_variable` for production.
+    pub env_variable_production: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,7 +172,7 @@ pub struct NodeData<C> {
 }
 
 // =============================================================================
-// WORKFLOW NODE — tagged union over 17 node types
+// WORKFLOW NODE — tagged union over 19 node types
 // =============================================================================
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -124,6 +193,8 @@ pub enum WorkflowNode {
     EvmRead(NodeBase<EvmReadConfig>),
     #[serde(rename = "evmWrite")]
     EvmWrite(NodeBase<EvmWriteConfig>),
+    #[serde(rename = "broadcastWrite")]
+    BroadcastWrite(NodeBase<BroadcastWriteConfig>),
 
     // Transforms
     #[serde(rename = "codeNode")]
@@ -134,6 +205,8 @@ pub enum WorkflowNode {
     AbiDecode(NodeBase<AbiDecodeConfig>),
     #[serde(rename = "merge")]
     Merge(NodeBase<MergeConfig>),
+    #[serde(rename = "math")]
+    Math(NodeBase<MathConfig>),
 
     // Control Flow
     #[serde(rename = "filter")]
@@ -141,6 +214,12 @@ pub enum WorkflowNode {
     #[serde(rename = "if")]
     If(NodeBase<IfConfig>),
 
+    // State
+    #[serde(rename = "kvGet")]
+    KvGet(NodeBase<KvGetConfig>),
+    #[serde(rename = "kvSet")]
+    KvSet(NodeBase<KvSetConfig>),
+
     // AI
     #[serde(rename = "ai")]
     Ai(NodeBase<AiNodeConfig>),
@@ -170,12 +249,16 @@ impl WorkflowNode {
             WorkflowNode::HttpRequest(n) => &n.id,
             WorkflowNode::EvmRead(n) => &n.id,
             WorkflowNode::EvmWrite(n) => &n.id,
+            WorkflowNode::BroadcastWrite(n) => &n.id,
             WorkflowNode::CodeNode(n) => &n.id,
             WorkflowNode::AbiEncode(n) => &n.id,
             WorkflowNode::AbiDecode(n) => &n.id,
             WorkflowNode::Merge(n) => &n.id,
+            WorkflowNode::Math(n) => &n.id,
             WorkflowNode::Filter(n) => &n.id,
             WorkflowNode::If(n) => &n.id,
+            WorkflowNode::KvGet(n) => &n.id,
+            WorkflowNode::KvSet(n) => &n.id,
             WorkflowNode::Ai(n) => &n.id,
             WorkflowNode::Return(n) => &n.id,
             WorkflowNode::Error(n) => &n.id,
@@ -190,12 +273,16 @@ impl WorkflowNode {
             WorkflowNode::HttpRequest(n) => &n.data.label,
             WorkflowNode::EvmRead(n) => &n.data.label,
             WorkflowNode::EvmWrite(n) => &n.data.label,
+            WorkflowNode::BroadcastWrite(n) => &n.data.label,
             WorkflowNode::CodeNode(n) => &n.data.label,
             WorkflowNode::AbiEncode(n) => &n.data.label,
             WorkflowNode::AbiDecode(n) => &n.data.label,
             WorkflowNode::Merge(n) => &n.data.label,
+            WorkflowNode::Math(n) => &n.data.label,
             WorkflowNode::Filter(n) => &n.data.label,
             WorkflowNode::If(n) => &n.data.label,
+            WorkflowNode::KvGet(n) => &n.data.label,
+            WorkflowNode::KvSet(n) => &n.data.label,
             WorkflowNode::Ai(n) => &n.data.label,
             WorkflowNode::Return(n) => &n.data.label,
             WorkflowNode::Error(n) => &n.data.label,
@@ -210,12 +297,16 @@ impl WorkflowNode {
             WorkflowNode::HttpRequest(_) => "httpRequest",
             WorkflowNode::EvmRead(_) => "evmRead",
             WorkflowNode::EvmWrite(_) => "evmWrite",
+            WorkflowNode::BroadcastWrite(_) => "broadcastWrite",
             WorkflowNode::CodeNode(_) => "codeNode",
             WorkflowNode::AbiEncode(_) => "abiEncode",
             WorkflowNode::AbiDecode(_) => "abiDecode",
             WorkflowNode::Merge(_) => "merge",
+            WorkflowNode::Math(_) => "math",
             WorkflowNode::Filter(_) => "filter",
             WorkflowNode::If(_) => "if",
+            WorkflowNode::KvGet(_) => "kvGet",
+            WorkflowNode::KvSet(_) => "kvSet",
             WorkflowNode::Ai(_) => "ai",
             WorkflowNode::Return(_) => "return",
             WorkflowNode::Error(_) => "error",
@@ -231,6 +322,15 @@ impl WorkflowNode {
         )
     }
 
+    /// Output handle names this node type exposes (mirrors `outputs` in the frontend node registry).
+    pub fn output_handles(&self) -> &'static [&'static str] {
+        match self {
+            WorkflowNode::If(_) => &["true", "false"],
+            WorkflowNode::Return(_) | WorkflowNode::Error(_) => &[],
+            _ => &["output"],
+        }
+    }
+
     pub fn is_terminal(&self) -> bool {
         matches!(self, WorkflowNode::Return(_) | WorkflowNode::Error(_))
     }
@@ -243,17 +343,53 @@ impl WorkflowNode {
             WorkflowNode::HttpRequest(n) => &n.position,
             WorkflowNode::EvmRead(n) => &n.position,
             WorkflowNode::EvmWrite(n) => &n.position,
+            WorkflowNode::BroadcastWrite(n) => &n.position,
             WorkflowNode::CodeNode(n) => &n.position,
             WorkflowNode::AbiEncode(n) => &n.position,
             WorkflowNode::AbiDecode(n) => &n.position,
             WorkflowNode::Merge(n) => &n.position,
+            WorkflowNode::Math(n) => &n.position,
             WorkflowNode::Filter(n) => &n.position,
             WorkflowNode::If(n) => &n.position,
+            WorkflowNode::KvGet(n) => &n.position,
+            WorkflowNode::KvSet(n) => &n.position,
             WorkflowNode::Ai(n) => &n.position,
             WorkflowNode::Return(n) => &n.position,
             WorkflowNode::Error(n) => &n.position,
         }
     }
+
+    pub fn settings(&self) -> Option<&NodeSettings> {
+        match self {
+            WorkflowNode::CronTrigger(n) => n.settings.as_ref(),
+            WorkflowNode::HttpTrigger(n) => n.settings.as_ref(),
+            WorkflowNode::EvmLogTrigger(n) => n.settings.as_ref(),
+            WorkflowNode::HttpRequest(n) => n.settings.as_ref(),
+            WorkflowNode::EvmRead(n) => n.settings.as_ref(),
+            WorkflowNode::EvmWrite(n) => n.settings.as_ref(),
+            WorkflowNode::BroadcastWrite(n) => n.settings.as_ref(),
+            WorkflowNode::CodeNode(n) => n.settings.as_ref(),
+            WorkflowNode::AbiEncode(n) => n.settings.as_ref(),
+            WorkflowNode::AbiDecode(n) => n.settings.as_ref(),
+            WorkflowNode::Merge(n) => n.settings.as_ref(),
+            WorkflowNode::Math(n) => n.settings.as_ref(),
+            WorkflowNode::Filter(n) => n.settings.as_ref(),
+            WorkflowNode::If(n) => n.settings.as_ref(),
+            WorkflowNode::KvGet(n) => n.settings.as_ref(),
+            WorkflowNode::KvSet(n) => n.settings.as_ref(),
+            WorkflowNode::Ai(n) => n.settings.as_ref(),
+            WorkflowNode::Return(n) => n.settings.as_ref(),
+            WorkflowNode::Error(n) => n.settings.as_ref(),
+        }
+    }
+
+    /// `settings.notes`, trimmed; `None` when unset or blank.
+    pub fn notes(&self) -> Option<&str> {
+        self.settings()
+            .and_then(|s| s.notes.as_deref())
+            .map(str::trim)
+            .filter(|notes| !notes.is_empty())
+    }
 }
 
 // =============================================================================
@@ -315,7 +451,8 @@ pub struct HttpTriggerConfig {
     pub authentication: WebhookAuth,
     pub response_mode: String,
     pub response_code: Option<u16>,
-    pub response_headers: Option<std::collections::HashMap<String, String>>,
+    /// Sorted by header name, like a request's `headers`.
+    pub response_headers: Option<BTreeMap<String, String>>,
     pub allowed_origins: Option<Vec<String>>,
 }
 
@@ -359,14 +496,18 @@ pub struct HttpRequestConfig {
     pub method: String,
     pub url: String,
     pub authentication: Option<HttpAuthConfig>,
-    pub headers: Option<std::collections::HashMap<String, String>>,
-    pub query_parameters: Option<std::collections::HashMap<String, String>>,
+    /// Sorted by name rather than kept in JSON key order, so reordering the
+    /// keys in the editor does not change the generated code.
+    pub headers: Option<BTreeMap<String, String>>,
+    /// Sorted by name, like `headers`.
+    pub query_parameters: Option<BTreeMap<String, String>>,
     pub body: Option<HttpBodyConfig>,
     pub cache_max_age: Option<u32>,
     pub timeout: Option<u32>,
     pub expected_status_codes: Option<Vec<u16>>,
     pub response_format: Option<String>,
     pub follow_redirects: Option<bool>,
+    #[serde(rename = "ignoreSSL", alias = "ignoreSsl")]
     pub ignore_ssl: Option<bool>,
 }
 
@@ -392,10 +533,13 @@ pub struct HttpBodyConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EvmReadConfig {
-    pub chain_selector_name: String,
+    /// `None` to use the workflow's `defaultChainSelector`.
+    #[serde(default)]
+    pub chain_selector_name: Option<String>,
     pub contract_address: String,
     pub abi: AbiFunction,
     pub function_name: String,
+    #[serde(default)]
     pub args: Vec<EvmArgDef>,
     pub from_address: Option<String>,
     pub block_number: Option<String>,
@@ -404,13 +548,44 @@ pub struct EvmReadConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EvmWriteConfig {
-    pub chain_selector_name: String,
+    /// `None` to use the workflow's `defaultChainSelector`.
+    #[serde(default)]
+    pub chain_selector_name: Option<String>,
     pub receiver_address: String,
     pub gas_limit: String,
     pub encoded_data: String,
     pub value: Option<String>,
 }
 
+/// Mirror one ABI-encoded write onto several chains. Expanded by
+/// `lower::expand` into one AbiEncode step and one EvmWrite per chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastWriteConfig {
+    /// One EVM write per chain, in this order.
+    pub chain_selector_names: Vec<String>,
+    /// Receiver on every chain without an entry in `receiver_addresses`.
+    #[serde(default)]
+    pub receiver_address: String,
+    /// Per-chain receivers, keyed by chain selector name.
+    #[serde(default)]
+    pub receiver_addresses: BTreeMap<String, String>,
+    pub gas_limit: String,
+    /// Shared by every chain: the report is encoded once.
+    #[serde(flatten)]
+    pub encode: AbiEncodeConfig,
+}
+
+impl BroadcastWriteConfig {
+    /// The receiver on `chain`: its `receiver_addresses` entry, otherwise
+    /// the shared `receiver_address`.
+    pub fn receiver_for(&self, chain: &str) -> &str {
+        self.receiver_addresses
+            .get(chain)
+            .map_or(self.receiver_address.as_str(), String::as_str)
+    }
+}
+
 // =============================================================================
 // TRANSFORM CONFIGS
 // =============================================================================
@@ -421,7 +596,10 @@ pub struct CodeNodeConfig {
     pub code: String,
     pub language: Option<String>,
     pub execution_mode: String,
+    #[serde(default)]
     pub input_variables: Vec<String>,
+    /// Input variable a `runOnceForEach` node maps over.
+    pub iterate_over: Option<String>,
     pub timeout: Option<u32>,
     #[serde(default)]
     pub output_fields: Vec<CodeOutputField>,
@@ -435,11 +613,22 @@ pub struct CodeOutputField {
     pub field_type: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MathConfig {
+    /// Arithmetic expression, e.g. `{{read-1.value}} * 2 + 1`.
+    pub expression: String,
+    /// "auto" (default), "number" or "bigint".
+    pub mode: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AbiEncodeConfig {
     pub abi_params: Vec<AbiParameter>,
     pub data_mapping: Vec<AbiDataMappingDef>,
+    /// When set, encode as a call to this function (with selector) instead of bare parameters.
+    pub function_name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -495,17 +684,51 @@ pub enum MergeStrategyDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FilterConfig {
-    pub conditions: Vec<Condition>,
+    pub conditions: Vec<ConditionNode>,
     pub combine_with: String,
+    #[serde(default)]
+    pub non_match_behavior: FilterNonMatchMode,
+    /// Returned when the condition fails in `earlyReturn` mode; may hold
+    /// `{{...}}` references. Defaults to "Filter condition not met".
+    #[serde(default)]
+    pub non_match_message: Option<String>,
+}
+
+/// What a filter does when its condition fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterNonMatchMode {
+    /// The handler returns the non-match message.
+    #[default]
+    EarlyReturn,
+    /// The rest of the block is skipped and execution continues after it.
+    Skip,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IfConfig {
-    pub conditions: Vec<Condition>,
+    pub conditions: Vec<ConditionNode>,
     #[serde(rename = "combineWith")]
     pub combine_with: String,
 }
 
+/// An entry of a `conditions` list: a single condition, or a nested group
+/// with its own combinator. A flat list of conditions is still valid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConditionNode {
+    Group(ConditionGroup),
+    Leaf(Condition),
+}
+
+/// A parenthesized group: `(a AND b)` inside an OR list, or the reverse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionGroup {
+    pub conditions: Vec<ConditionNode>,
+    pub combine_with: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Condition {
     pub field: String,
@@ -531,6 +754,48 @@ pub struct AiNodeConfig {
     pub response_format: Option<String>,
     pub timeout: Option<u32>,
     pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub consensus: AiConsensusMode,
+    /// Consensus expression used in `custom` mode, e.g.
+    /// `consensusIdenticalAggregation()`.
+    #[serde(default)]
+    pub consensus_expression: Option<String>,
+}
+
+/// How the DON agrees on an AI call's response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AiConsensusMode {
+    /// Nodes must agree on the provider's generated content only; ids,
+    /// timestamps and token usage may differ.
+    #[default]
+    Content,
+    /// Nodes must return byte-identical responses.
+    Identical,
+    /// `consensusExpression` is used as written.
+    Custom,
+}
+
+// =============================================================================
+// STATE CONFIGS
+// =============================================================================
+
+/// Read a value an earlier execution stored under `key`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KvGetConfig {
+    /// May contain `{{...}}` references.
+    pub key: String,
+}
+
+/// Store `value` under `key` for later executions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KvSetConfig {
+    /// May contain `{{...}}` references.
+    pub key: String,
+    /// Stored as a string, e.g. `{{read-1.blockNumber}}`.
+    pub value: String,
 }
 
 // =============================================================================
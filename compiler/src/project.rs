@@ -0,0 +1,290 @@
+//! Compile several workflows into one CRE project.
+//!
+//! Teams deploy related workflows (a price feed, its alerting, settlement)
+//! into one CRE project, which has a single `project.yaml` and
+//! `secrets.yaml` at its root. [`compile_project`] compiles each workflow
+//! into its own `<workflow-id>/` directory and merges what they share: the
+//! RPCs of `project.yaml`, the secrets of `secrets.yaml` and the
+//! dependencies of a root `package.json`. A workflow that fails to compile,
+//! or whose RPCs or secrets contradict an earlier workflow's (J001–J003), is
+//! reported under its id and left out; the others still make up the project.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::codegen::files::{self, ProjectRpc, RpcSource};
+use crate::codegen::{CodegenOptions, GeneratedFile};
+use crate::compile::compile_to_ir;
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::ir::{Environment, WorkflowIR};
+
+/// `name` of the root `package.json` unless [`ProjectOptions::name`] is set.
+pub const DEFAULT_PROJECT_NAME: &str = "cre-project";
+
+/// Caller settings for [`compile_project`].
+#[derive(Debug, Clone, Default)]
+pub struct ProjectOptions {
+    /// `name` of the root `package.json`; `None` is [`DEFAULT_PROJECT_NAME`].
+    pub name: Option<String>,
+    /// Settings every workflow is compiled with.
+    pub codegen: CodegenOptions,
+}
+
+/// A compiled project.
+#[derive(Debug, Clone)]
+pub struct ProjectOutput {
+    /// The root `project.yaml`, `secrets.yaml` and `package.json`, then the
+    /// bundle of each included workflow under `<workflow-id>/`.
+    pub files: Vec<GeneratedFile>,
+    /// One entry per input workflow, in input order.
+    pub workflows: Vec<ProjectWorkflow>,
+}
+
+/// The outcome for one workflow of a project.
+#[derive(Debug, Clone)]
+pub struct ProjectWorkflow {
+    /// The workflow's id, or `workflows[i]` when it cannot be read.
+    pub id: String,
+    /// The workflow's warnings when it is part of the project, or the errors
+    /// that kept it out.
+    pub result: Result<Vec<CompilerError>, Vec<CompilerError>>,
+}
+
+/// Compile `workflows` (workflow JSON documents) into one project.
+///
+/// Each workflow is compiled on its own with `options.codegen`, without its
+/// own `project.yaml` and `secrets.yaml`. Its RPCs and secrets are then
+/// merged into the project's: RPCs from `globalConfig.rpcs` take priority
+/// over the placeholder RPC of a chain another workflow merely uses, and
+/// the fallback chain is listed only when no workflow names one. Errors:
+/// J001 an RPC URL for a chain an earlier workflow gives a different URL,
+/// J002 a secret an earlier workflow maps to a different environment
+/// variable, J003 a workflow id that is not a plain directory name or is
+/// already taken.
+pub fn compile_project(workflows: &[&str], options: &ProjectOptions) -> ProjectOutput {
+    let codegen_options = CodegenOptions {
+        omit_project_files: true,
+        ..options.codegen.clone()
+    };
+    let mut project = MergedProject::default();
+    let mut workflow_files = Vec::new();
+    let mut outcomes = Vec::new();
+
+    for (index, json) in workflows.iter().enumerate() {
+        let outcome = match compile_to_ir(json, None, &codegen_options, None) {
+            Err(errors) => ProjectWorkflow {
+                id: workflow_id(json).unwrap_or_else(|| format!("workflows[{}]", index)),
+                result: Err(errors),
+            },
+            Ok((ir, compiled)) => {
+                let id = ir.metadata.id.clone();
+                let result = project.add(&ir, &options.codegen).map(|()| {
+                    workflow_files.extend(compiled.output.files.into_iter().map(|file| {
+                        GeneratedFile {
+                            path: format!("{}/{}", id, file.path),
+                            content: file.content,
+                        }
+                    }));
+                    compiled.warnings
+                });
+                ProjectWorkflow { id, result }
+            }
+        };
+        outcomes.push(outcome);
+    }
+
+    let name = options.name.as_deref().unwrap_or(DEFAULT_PROJECT_NAME);
+    let mut project_files = vec![
+        GeneratedFile {
+            path: "project.yaml".into(),
+            content: files::render_project_yaml(|environment| project.rpcs(environment)).into(),
+        },
+        GeneratedFile {
+            path: "secrets.yaml".into(),
+            content: files::render_secrets_yaml(
+                project
+                    .secrets
+                    .iter()
+                    .map(|(name, env_variable, _)| (name.as_str(), env_variable.as_str())),
+            )
+            .into(),
+        },
+        GeneratedFile {
+            path: "package.json".into(),
+            content: files::render_package_json(name, &project.dependencies(), &options.codegen)
+                .into(),
+        },
+    ];
+    project_files.extend(workflow_files);
+
+    ProjectOutput {
+        files: project_files,
+        workflows: outcomes,
+    }
+}
+
+fn workflow_id(json: &str) -> Option<String> {
+    let doc: Value = serde_json::from_str(json).ok()?;
+    Some(doc.get("id")?.as_str()?.to_string())
+}
+
+/// What the included workflows share, in the order they were added.
+#[derive(Default)]
+struct MergedProject {
+    ids: Vec<String>,
+    /// `globalConfig.rpcs` URL of each chain, with the workflow giving it.
+    user_rpcs: HashMap<String, (String, String)>,
+    /// `project.yaml` RPCs by environment, first workflow's fallback apart.
+    rpcs: HashMap<&'static str, Vec<ProjectRpc>>,
+    fallbacks: HashMap<&'static str, ProjectRpc>,
+    /// `(name, environment variable, workflow id)`.
+    secrets: Vec<(String, String, String)>,
+    dependencies: Vec<(&'static str, String)>,
+}
+
+impl MergedProject {
+    /// Check `ir` against the workflows already added and, if it agrees
+    /// with them, add it.
+    fn add(&mut self, ir: &WorkflowIR, options: &CodegenOptions) -> Result<(), Vec<CompilerError>> {
+        let id = &ir.metadata.id;
+        let mut errors = Vec::new();
+        if !is_directory_name(id) {
+            errors.push(
+                CompilerError::validate(
+                    "J003",
+                    format!(
+                        "Workflow id '{}' cannot name its project directory; use letters, digits, '-', '_' and '.'",
+                        id
+                    ),
+                    None,
+                )
+                .with_path(vec![JsonPathSegment::key("id")]),
+            );
+        } else if self.ids.contains(id) {
+            errors.push(
+                CompilerError::validate(
+                    "J003",
+                    format!("Another workflow in the project already has id '{}'", id),
+                    None,
+                )
+                .with_path(vec![JsonPathSegment::key("id")]),
+            );
+        }
+        for (index, rpc) in ir.user_rpcs.iter().enumerate() {
+            if let Some((url, other)) = self.user_rpcs.get(&rpc.chain_name)
+                && *url != rpc.url
+            {
+                errors.push(
+                    CompilerError::validate(
+                        "J001",
+                        format!(
+                            "RPC for '{}' is '{}', but workflow '{}' uses '{}'; a project has one RPC per chain",
+                            rpc.chain_name, rpc.url, other, url
+                        ),
+                        None,
+                    )
+                    .with_path(vec![
+                        JsonPathSegment::key("globalConfig"),
+                        JsonPathSegment::key("rpcs"),
+                        JsonPathSegment::Index(index),
+                        JsonPathSegment::key("url"),
+                    ]),
+                );
+            }
+        }
+        let environment = Environment::of(&ir.metadata);
+        for secret in &ir.required_secrets {
+            let env_variable = secret.env_variable_for(environment);
+            if let Some((_, other_variable, other)) = self
+                .secrets
+                .iter()
+                .find(|(name, _, _)| *name == secret.name)
+                && other_variable != env_variable
+            {
+                errors.push(CompilerError::validate(
+                    "J002",
+                    format!(
+                        "Secret '{}' reads '{}', but workflow '{}' maps it to '{}'; secrets.yaml has one variable per secret",
+                        secret.name, env_variable, other, other_variable
+                    ),
+                    None,
+                ));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.ids.push(id.clone());
+        for rpc in &ir.user_rpcs {
+            self.user_rpcs
+                .entry(rpc.chain_name.clone())
+                .or_insert_with(|| (rpc.url.clone(), id.clone()));
+        }
+        for environment in Environment::ALL {
+            for rpc in files::project_rpcs(ir, environment) {
+                self.add_rpc(environment, rpc);
+            }
+        }
+        for secret in &ir.required_secrets {
+            if !self.secrets.iter().any(|(name, _, _)| *name == secret.name) {
+                self.secrets.push((
+                    secret.name.clone(),
+                    secret.env_variable_for(environment).to_string(),
+                    id.clone(),
+                ));
+            }
+        }
+        for (package, version) in files::package_dependencies(ir, options) {
+            if !self.dependencies.iter().any(|(p, _)| *p == package) {
+                self.dependencies.push((package, version.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_rpc(&mut self, environment: Environment, rpc: ProjectRpc) {
+        if rpc.source == RpcSource::Fallback {
+            self.fallbacks.entry(environment.as_str()).or_insert(rpc);
+            return;
+        }
+        let rpcs = self.rpcs.entry(environment.as_str()).or_default();
+        match rpcs.iter_mut().find(|r| r.chain_name == rpc.chain_name) {
+            // A user's RPC replaces the placeholder of a chain another
+            // workflow only uses.
+            Some(existing) if existing.source == RpcSource::Detected => *existing = rpc,
+            Some(_) => {}
+            None => rpcs.push(rpc),
+        }
+    }
+
+    fn rpcs(&self, environment: Environment) -> Vec<ProjectRpc> {
+        match self.rpcs.get(environment.as_str()) {
+            Some(rpcs) if !rpcs.is_empty() => rpcs.clone(),
+            _ => self
+                .fallbacks
+                .get(environment.as_str())
+                .cloned()
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn dependencies(&self) -> Vec<(&str, &str)> {
+        self.dependencies
+            .iter()
+            .map(|(package, version)| (*package, version.as_str()))
+            .collect()
+    }
+}
+
+/// Whether `id` is usable as one directory name on any platform.
+fn is_directory_name(id: &str) -> bool {
+    !id.is_empty()
+        && id != "."
+        && id != ".."
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
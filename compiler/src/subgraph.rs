@@ -0,0 +1,272 @@
+//! Compile one node and its upstream chain, for "test this node" in the editor.
+//!
+//! [`compile_subgraph`] keeps the target node and every node it depends on,
+//! runs the slice from a cron trigger, and returns the target's output (the
+//! `AutoSummary` return). Capability calls upstream of the target are
+//! replaced by stubs that return the sample value supplied for their node,
+//! so the bundle can run against sample data without touching any API or
+//! chain. The target itself runs for real.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::codegen::{self, CodegenOptions};
+use crate::compile::{Compiled, lower_workflow};
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::ir::{self, *};
+use crate::lower::LowerOptions;
+use crate::parse;
+use crate::parse::types::{
+    AutoReturnMode, CronTriggerConfig, NodeBase, NodeData, ReturnConfig, Workflow, WorkflowEdge,
+    WorkflowNode,
+};
+
+/// Schedule of the synthesized trigger when the workflow's own is not a cron.
+const STUB_SCHEDULE: &str = "0 */10 * * * *";
+
+/// Compile `target_node_id` with its transitive predecessors.
+///
+/// `sample_inputs` is a JSON object keyed by node id; each value is what the
+/// stub for that node returns (for an HTTP request, the response with its
+/// `statusCode` and `body`). Stubbed steps are labeled `[stub]` and carry a
+/// note, so the generated code says which calls were not made.
+///
+/// Errors: S001 unknown target (or the trigger), S002 sample inputs that are
+/// not a JSON object, S003 a stubbed node without a sample input; otherwise
+/// whatever the pipeline reports for the slice, pointing into the original
+/// workflow.
+pub fn compile_subgraph(
+    workflow_json: &str,
+    target_node_id: &str,
+    sample_inputs: &str,
+) -> Result<Compiled, Vec<CompilerError>> {
+    let workflow = parse::parse(workflow_json)?;
+    match workflow.nodes.iter().find(|n| n.id() == target_node_id) {
+        Some(node) if !node.is_trigger() => {}
+        Some(_) => {
+            return Err(vec![
+                CompilerError::validate(
+                    "S001",
+                    format!(
+                        "Node '{}' is the trigger; pick a node after it to test",
+                        target_node_id
+                    ),
+                    Some(target_node_id.to_string()),
+                )
+                .locate_node(&workflow),
+            ]);
+        }
+        None => {
+            return Err(vec![CompilerError::validate(
+                "S001",
+                format!("Node '{}' does not exist in the workflow", target_node_id),
+                None,
+            )]);
+        }
+    }
+    let samples = match serde_json::from_str::<Value>(sample_inputs) {
+        Ok(Value::Object(samples)) => samples,
+        _ => {
+            return Err(vec![CompilerError::validate(
+                "S002",
+                "Sample inputs must be a JSON object keyed by node id",
+                None,
+            )]);
+        }
+    };
+
+    let slice = slice_workflow(&workflow, target_node_id);
+    let options = LowerOptions {
+        auto_return: Some(AutoReturnMode::AutoSummary),
+        ..LowerOptions::default()
+    };
+    let relocate = |errors: Vec<CompilerError>| -> Vec<CompilerError> {
+        errors
+            .into_iter()
+            .map(|e| relocate_error(e, &slice, &workflow))
+            .collect()
+    };
+    let (mut ir, warnings) = lower_workflow(&slice, &options).map_err(relocate)?;
+
+    let mut missing = Vec::new();
+    stub_block(
+        &mut ir.handler_body,
+        target_node_id,
+        &samples.into_iter().collect(),
+        &mut missing,
+    );
+    if !missing.is_empty() {
+        return Err(missing
+            .into_iter()
+            .map(|node_id| {
+                CompilerError::validate(
+                    "S003",
+                    format!(
+                        "Node '{}' is stubbed when testing '{}' but has no sample input",
+                        node_id, target_node_id
+                    ),
+                    Some(node_id),
+                )
+                .locate_node(&workflow)
+            })
+            .collect());
+    }
+
+    // Stubs are not capability calls, so they no longer count against the
+    // CRE budgets.
+    let ir_errors = ir::validate_ir(&ir);
+    if !ir_errors.is_empty() {
+        return Err(ir_errors
+            .into_iter()
+            .map(|e| CompilerError::from(e).locate_node(&workflow))
+            .collect());
+    }
+
+    let output = codegen::codegen_with_options(&ir, &CodegenOptions::default());
+    Ok(Compiled {
+        output,
+        warnings: relocate(warnings),
+    })
+}
+
+/// The target, its transitive predecessors and a cron trigger in place of the
+/// original one (same id, so edges from it still connect). Nodes keep their
+/// original order. An `if` arm that does not lead to the target ends in a
+/// Return saying so, since an `if` needs both arms.
+fn slice_workflow(workflow: &Workflow, target_node_id: &str) -> Workflow {
+    let mut keep = HashSet::from([target_node_id]);
+    let mut pending = vec![target_node_id];
+    while let Some(node_id) = pending.pop() {
+        for edge in workflow.edges.iter().filter(|e| e.target == node_id) {
+            if keep.insert(edge.source.as_str()) {
+                pending.push(edge.source.as_str());
+            }
+        }
+    }
+
+    let mut slice = workflow.clone();
+    slice
+        .nodes
+        .retain(|n| n.is_trigger() || keep.contains(n.id()));
+    slice
+        .edges
+        .retain(|e| keep.contains(e.source.as_str()) && keep.contains(e.target.as_str()));
+    for node in &mut slice.nodes {
+        if node.is_trigger() && !matches!(node, WorkflowNode::CronTrigger(_)) {
+            *node = WorkflowNode::CronTrigger(NodeBase {
+                id: node.id().to_string(),
+                position: node.position().clone(),
+                data: NodeData {
+                    label: node.label().to_string(),
+                    config: CronTriggerConfig {
+                        schedule: STUB_SCHEDULE.into(),
+                        timezone: None,
+                    },
+                },
+                settings: None,
+            });
+        }
+    }
+
+    let mut untested = Vec::new();
+    for node in &slice.nodes {
+        let WorkflowNode::If(branch) = node else {
+            continue;
+        };
+        for handle in node.output_handles() {
+            let taken = slice
+                .edges
+                .iter()
+                .any(|e| e.source == branch.id && e.source_handle.as_deref() == Some(handle));
+            if !taken {
+                untested.push((branch.id.clone(), branch.position.clone(), *handle));
+            }
+        }
+    }
+    for (branch_id, position, handle) in untested {
+        let id = format!("{}-{}-untested", branch_id, handle);
+        slice.nodes.push(WorkflowNode::Return(NodeBase {
+            id: id.clone(),
+            position,
+            data: NodeData {
+                label: format!("Untested '{}' arm", handle),
+                config: ReturnConfig {
+                    return_expression: format!("Not on the path to '{}'", target_node_id),
+                },
+            },
+            settings: None,
+        }));
+        slice.edges.push(WorkflowEdge {
+            id: id.clone(),
+            source: branch_id,
+            target: id,
+            source_handle: Some(handle.to_string()),
+            target_handle: None,
+            label: None,
+        });
+    }
+    slice
+}
+
+/// Replace the capability calls of every node but the target with a Code
+/// step returning its sample input. Nodes without one are added to `missing`.
+fn stub_block(
+    block: &mut Block,
+    target_node_id: &str,
+    samples: &HashMap<String, Value>,
+    missing: &mut Vec<String>,
+) {
+    for step in &mut block.steps {
+        if let Operation::Branch(branch) = &mut step.operation {
+            stub_block(&mut branch.true_branch, target_node_id, samples, missing);
+            stub_block(&mut branch.false_branch, target_node_id, samples, missing);
+            continue;
+        }
+        let node_id = step.source_node_ids.first().unwrap_or(&step.id).clone();
+        if node_id == target_node_id || !step.operation.is_capability_call() {
+            continue;
+        }
+        let code = match (&step.output, samples.get(&node_id)) {
+            (None, _) => "// Stubbed: the call is not made.".to_string(),
+            (Some(_), Some(sample)) => format!("return {};", sample),
+            (Some(_), None) => {
+                if !missing.contains(&node_id) {
+                    missing.push(node_id);
+                }
+                continue;
+            }
+        };
+        step.notes = Some(format!(
+            "Stub: returns the sample input for '{}' instead of calling {}.",
+            node_id,
+            step.operation.kind()
+        ));
+        step.label = format!("[stub] {}", step.label);
+        step.operation = Operation::CodeNode(CodeNodeOp {
+            code,
+            input_bindings: vec![],
+            execution_mode: CodeExecutionMode::RunOnceForAll,
+            iterate_over: None,
+            timeout_ms: None,
+            output_fields: vec![],
+        });
+    }
+}
+
+/// Point a `nodes[i]` path of the slice at the same node in the original.
+fn relocate_error(
+    mut error: CompilerError,
+    slice: &Workflow,
+    original: &Workflow,
+) -> CompilerError {
+    if let [JsonPathSegment::Key(key), JsonPathSegment::Index(index), ..] =
+        error.path.as_mut_slice()
+        && key == "nodes"
+        && let Some(node) = slice.nodes.get(*index)
+        && let Some(original_index) = original.nodes.iter().position(|n| n.id() == node.id())
+    {
+        *index = original_index;
+    }
+    error
+}
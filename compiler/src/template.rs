@@ -0,0 +1,312 @@
+//! Instantiate workflow templates.
+//!
+//! A template is an ordinary workflow whose node data contains `${param:NAME}`
+//! placeholders, with each parameter declared in `globalConfig.templateParams`.
+//! Instantiation substitutes the supplied (or default) values and returns a
+//! plain workflow with `templateParams` cleared, ready for the usual pipeline.
+//!
+//! To insert a template into an existing workflow, instantiate it with an id
+//! prefix: every node and edge id is prefixed, and edges and `{{nodeId.field}}`
+//! references are rewritten to match.
+
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::parse::types::Workflow;
+
+const PLACEHOLDER_OPEN: &str = "${param:";
+
+/// Substitute template parameters, keeping node ids as they are.
+///
+/// Errors: T001 required parameter missing, T002 supplied parameter not
+/// declared, T003 declared parameter never used, T004 placeholder naming an
+/// undeclared parameter (or left unterminated), T005 substituted values that
+/// do not fit their fields.
+pub fn instantiate(
+    template_workflow: &Workflow,
+    params: &HashMap<String, String>,
+) -> Result<Workflow, Vec<CompilerError>> {
+    instantiate_with_id_prefix(template_workflow, params, "")
+}
+
+/// Like [`instantiate`], and prefix every node and edge id with `id_prefix`.
+pub fn instantiate_with_id_prefix(
+    template_workflow: &Workflow,
+    params: &HashMap<String, String>,
+    id_prefix: &str,
+) -> Result<Workflow, Vec<CompilerError>> {
+    let declared = template_workflow
+        .global_config
+        .template_params
+        .as_deref()
+        .unwrap_or_default();
+    let mut errors = Vec::new();
+
+    let mut unknown: Vec<&String> = params
+        .keys()
+        .filter(|name| !declared.iter().any(|p| &p.name == *name))
+        .collect();
+    unknown.sort();
+    for name in unknown {
+        errors.push(CompilerError::template(
+            "T002",
+            format!("Parameter '{}' is not declared by the template", name),
+            None,
+        ));
+    }
+
+    let mut values = HashMap::new();
+    for param in declared {
+        match params.get(&param.name).or(param.default.as_ref()) {
+            Some(value) => {
+                values.insert(param.name.as_str(), Some(value.as_str()));
+            }
+            None if param.required => {
+                // Already reported; its placeholders are left as they are.
+                values.insert(param.name.as_str(), None);
+                errors.push(
+                    CompilerError::template(
+                        "T001",
+                        format!(
+                            "Required template parameter '{}' was not provided",
+                            param.name
+                        ),
+                        None,
+                    )
+                    .with_path(params_path()),
+                );
+            }
+            None => {
+                values.insert(param.name.as_str(), Some(""));
+            }
+        }
+    }
+
+    let mut workflow =
+        serde_json::to_value(template_workflow).expect("workflow serialization cannot fail");
+    let mut used = HashSet::new();
+    if let Some(nodes) = workflow["nodes"].as_array_mut() {
+        for (i, node) in nodes.iter_mut().enumerate() {
+            let node_id = node["id"].as_str().map(String::from);
+            let mut path = vec![
+                JsonPathSegment::key("nodes"),
+                JsonPathSegment::Index(i),
+                JsonPathSegment::key("data"),
+            ];
+            let mut subst = Substitution {
+                values: &values,
+                used: &mut used,
+                node_id,
+                errors: &mut errors,
+            };
+            subst.walk(&mut node["data"], &mut path);
+        }
+    }
+
+    for param in declared {
+        if !used.contains(param.name.as_str()) {
+            errors.push(
+                CompilerError::template(
+                    "T003",
+                    format!(
+                        "Template parameter '{}' is not used by any node",
+                        param.name
+                    ),
+                    None,
+                )
+                .with_path(params_path()),
+            );
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    workflow["globalConfig"]["templateParams"] = Value::Null;
+    if !id_prefix.is_empty() {
+        prefix_ids(&mut workflow, id_prefix);
+    }
+    // Substitution only replaces strings with strings; should a substituted
+    // value still not fit its field, report it rather than panic.
+    serde_json::from_value(workflow).map_err(|e| {
+        vec![CompilerError::template(
+            "T005",
+            format!("Instantiated template is not a valid workflow: {}", e),
+            None,
+        )]
+    })
+}
+
+fn params_path() -> Vec<JsonPathSegment> {
+    vec![
+        JsonPathSegment::key("globalConfig"),
+        JsonPathSegment::key("templateParams"),
+    ]
+}
+
+struct Substitution<'a> {
+    values: &'a HashMap<&'a str, Option<&'a str>>,
+    used: &'a mut HashSet<String>,
+    node_id: Option<String>,
+    errors: &'a mut Vec<CompilerError>,
+}
+
+impl Substitution<'_> {
+    fn walk(&mut self, value: &mut Value, path: &mut Vec<JsonPathSegment>) {
+        match value {
+            Value::String(s) if s.contains(PLACEHOLDER_OPEN) => {
+                let substituted = self.substitute(s, path);
+                *s = substituted;
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    path.push(JsonPathSegment::Index(i));
+                    self.walk(item, path);
+                    path.pop();
+                }
+            }
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    path.push(JsonPathSegment::key(key.as_str()));
+                    self.walk(field, path);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn substitute(&mut self, input: &str, path: &[JsonPathSegment]) -> String {
+        let mut out = String::with_capacity(input.len());
+        let mut remaining = input;
+        while let Some(start) = remaining.find(PLACEHOLDER_OPEN) {
+            out.push_str(&remaining[..start]);
+            let after_open = &remaining[start + PLACEHOLDER_OPEN.len()..];
+            let Some(end) = after_open.find('}') else {
+                self.error(format!("Unterminated placeholder in '{}'", input), path);
+                out.push_str(&remaining[start..]);
+                return out;
+            };
+            let name = &after_open[..end];
+            let placeholder = &remaining[start..start + PLACEHOLDER_OPEN.len() + end + 1];
+            match self.values.get(name) {
+                Some(value) => {
+                    self.used.insert(name.to_string());
+                    out.push_str(value.unwrap_or(placeholder));
+                }
+                None => {
+                    self.error(
+                        format!(
+                            "Placeholder '${{param:{}}}' names an undeclared parameter",
+                            name
+                        ),
+                        path,
+                    );
+                    out.push_str(placeholder);
+                }
+            }
+            remaining = &after_open[end + 1..];
+        }
+        out.push_str(remaining);
+        out
+    }
+
+    fn error(&mut self, message: String, path: &[JsonPathSegment]) {
+        self.errors.push(
+            CompilerError::template("T004", message, self.node_id.clone()).with_path(path.to_vec()),
+        );
+    }
+}
+
+/// Prefix node and edge ids and rewrite edges and `{{nodeId.field}}`
+/// references to the new node ids.
+fn prefix_ids(workflow: &mut Value, prefix: &str) {
+    let mut renamed = HashMap::new();
+    if let Some(nodes) = workflow["nodes"].as_array_mut() {
+        for node in nodes.iter_mut() {
+            if let Some(id) = node["id"].as_str() {
+                let new_id = format!("{prefix}{id}");
+                renamed.insert(id.to_string(), new_id.clone());
+                node["id"] = Value::String(new_id);
+            }
+        }
+        for node in nodes.iter_mut() {
+            rewrite_refs(&mut node["data"], &renamed);
+        }
+    }
+    if let Some(edges) = workflow["edges"].as_array_mut() {
+        for edge in edges.iter_mut() {
+            for key in ["source", "target"] {
+                if let Some(new_id) = edge[key].as_str().and_then(|id| renamed.get(id)) {
+                    edge[key] = Value::String(new_id.clone());
+                }
+            }
+            if let Some(id) = edge["id"].as_str() {
+                edge["id"] = Value::String(format!("{prefix}{id}"));
+            }
+        }
+    }
+}
+
+fn rewrite_refs(value: &mut Value, renamed: &HashMap<String, String>) {
+    match value {
+        Value::String(s) if s.contains("{{") => *s = rewrite_refs_in_str(s, renamed),
+        Value::Array(items) => items.iter_mut().for_each(|v| rewrite_refs(v, renamed)),
+        Value::Object(fields) => fields.values_mut().for_each(|v| rewrite_refs(v, renamed)),
+        _ => {}
+    }
+}
+
+/// `{{h1.body}}` → `{{tpl-h1.body}}`, `{{ h1[0].x }}` → `{{ tpl-h1[0].x }}`.
+/// `config`/`trigger` refs and unknown ids are left alone.
+fn rewrite_refs_in_str(input: &str, renamed: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut remaining = input;
+    while let Some(start) = remaining.find("{{") {
+        let inner = &remaining[start + 2..];
+        let padding = inner.len() - inner.trim_start().len();
+        out.push_str(&remaining[..start + 2 + padding]);
+        let inner = &inner[padding..];
+        let id_end = inner
+            .find(|c: char| matches!(c, '.' | '[' | '}') || c.is_whitespace())
+            .unwrap_or(inner.len());
+        match renamed.get(&inner[..id_end]) {
+            Some(new_id) => {
+                out.push_str(new_id);
+                remaining = &inner[id_end..];
+            }
+            None => remaining = inner,
+        }
+    }
+    out.push_str(remaining);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refs_are_rewritten_by_first_segment_only() {
+        let renamed = HashMap::from([("h1".to_string(), "tpl-h1".to_string())]);
+        assert_eq!(
+            rewrite_refs_in_str(
+                "{{h1.body.h1}} and {{h1}} but not {{h10.x}} or {{config.h1}}",
+                &renamed
+            ),
+            "{{tpl-h1.body.h1}} and {{tpl-h1}} but not {{h10.x}} or {{config.h1}}"
+        );
+    }
+
+    #[test]
+    fn indexed_and_padded_refs_are_rewritten() {
+        let renamed = HashMap::from([("h1".to_string(), "tpl-h1".to_string())]);
+        assert_eq!(
+            rewrite_refs_in_str("{{h1[0].price}} {{ h1.body }} {{ h1 }}", &renamed),
+            "{{tpl-h1[0].price}} {{ tpl-h1.body }} {{ tpl-h1 }}"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Random workflow generators for property-based tests, and fixtures shared
+//! by unit tests.
+//!
+//! Available to unit tests, and to integration tests / fuzzers through the
+//! `testing` feature. `arb_workflow()` produces workflows that always
+//! deserialize (the JSON matches `parse::types`) but are otherwise arbitrary:
+//! empty strings, unicode ids, empty ABIs, malformed references and random
+//! edge handles are all fair game. The pipeline must reject such input with
+//! errors, never panic.
+
+use proptest::prelude::*;
+use serde_json::{Value, json};
+
+use crate::ir::types::*;
+use crate::parse::types::Workflow;
+
+/// A testnet cron workflow whose handler runs exactly `steps`, with no
+/// config fields, secrets or chains declared. Unlike `ir_with_steps` in
+/// `tests/helpers`, no Return is appended.
+pub fn ir_with_steps_no_return(steps: Vec<Step>) -> WorkflowIR {
+    WorkflowIR {
+        metadata: WorkflowMetadata {
+            id: "test-workflow".into(),
+            name: "Test Workflow".into(),
+            description: None,
+            version: "1.0.0".into(),
+            is_testnet: true,
+            default_chain_selector: None,
+            max_kv_operations: None,
+        },
+        trigger: TriggerDef::Cron(CronTriggerDef {
+            schedule: ValueExpr::string("0 */10 * * * *"),
+        }),
+        trigger_param: TriggerParam::CronTrigger,
+        config_schema: vec![],
+        required_secrets: vec![],
+        evm_chains: vec![],
+        user_rpcs: vec![],
+        handler_body: Block { steps },
+    }
+}
+
+/// A required string config field named `name`, referenced by no step.
+pub fn config_field(name: &str) -> ConfigField {
+    ConfigField {
+        name: name.into(),
+        zod_type: ZodType::String,
+        default_value: None,
+        description: None,
+        referenced_by: vec![],
+        optional_in_config: false,
+    }
+}
+
+/// A Return step `id` returning `expression`.
+pub fn return_step(id: &str, expression: ValueExpr) -> Step {
+    Step {
+        id: id.into(),
+        source_node_ids: vec![id.into()],
+        label: "Return".into(),
+        operation: Operation::Return(ReturnOp {
+            expression,
+            auto_generated: false,
+        }),
+        output: None,
+        notes: None,
+    }
+}
+
+/// A Branch step `id` with an always-true condition and the given arms.
+pub fn branch_step(id: &str, true_steps: Vec<Step>, false_steps: Vec<Step>) -> Step {
+    Step {
+        id: id.into(),
+        source_node_ids: vec![id.into()],
+        label: "Branch".into(),
+        operation: Operation::Branch(BranchOp {
+            conditions: vec![ConditionTree::Leaf(ConditionIR {
+                field: ValueExpr::boolean(true),
+                operator: ComparisonOp::Equals,
+                value: Some(ValueExpr::boolean(true)),
+                field_kind: None,
+                value_kind: None,
+            })],
+            combine_with: LogicCombinator::And,
+            true_branch: Block { steps: true_steps },
+            false_branch: Block { steps: false_steps },
+            true_label: None,
+            false_label: None,
+            reconverge_at: None,
+        }),
+        output: None,
+        notes: None,
+    }
+}
+
+/// Maximum number of non-trigger nodes in a generated workflow.
+pub const MAX_ACTION_NODES: usize = 8;
+
+/// Strings the frontend can plausibly send for a free-form text field.
+fn arb_text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just("{{".to_string()),
+        Just("{{}}".to_string()),
+        Just("{{config.}}".to_string()),
+        Just("{{trigger.}}".to_string()),
+        Just("0x".to_string()),
+        "[a-zA-Z0-9 ._-]{0,12}",
+        "\\PC{0,8}",
+    ]
+}
+
+/// A text value that may also reference an earlier node.
+fn arb_value(ref_ids: Vec<String>) -> impl Strategy<Value = String> {
+    if ref_ids.is_empty() {
+        return arb_text().boxed();
+    }
+    prop_oneof![
+        arb_text(),
+        (prop::sample::select(ref_ids), "[a-z.]{0,8}")
+            .prop_map(|(id, field)| format!("{{{{{id}.{field}}}}}")),
+        (arb_text(), prop::sample::select(vec!["config", "trigger"]))
+            .prop_map(|(field, root)| format!("{{{{{root}.{field}}}}}")),
+    ]
+    .boxed()
+}
+
+fn arb_abi_type() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("uint256".to_string()),
+        Just("int8".to_string()),
+        Just("address".to_string()),
+        Just("bool".to_string()),
+        Just("bytes32".to_string()),
+        Just("string".to_string()),
+        Just("tuple".to_string()),
+        arb_text(),
+    ]
+}
+
+fn arb_abi_params() -> impl Strategy<Value = Vec<Value>> {
+    prop::collection::vec(
+        (arb_text(), arb_abi_type()).prop_map(|(name, ty)| json!({ "name": name, "type": ty })),
+        0..3,
+    )
+}
+
+fn arb_chain() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        Just("ethereum-testnet-sepolia".to_string()),
+        Just("ethereum-mainnet".to_string()),
+        arb_text(),
+    ]
+}
+
+fn arb_conditions(ref_ids: Vec<String>) -> impl Strategy<Value = Vec<Value>> {
+    let operator = prop_oneof![
+        Just("equals".to_string()),
+        Just("gt".to_string()),
+        Just("contains".to_string()),
+        Just("exists".to_string()),
+        Just("isEmpty".to_string()),
+        arb_text(),
+    ];
+    prop::collection::vec(
+        (
+            arb_value(ref_ids.clone()),
+            operator,
+            prop::option::of(arb_value(ref_ids)),
+        )
+            .prop_map(|(field, operator, value)| {
+                json!({ "field": field, "operator": operator, "value": value })
+            }),
+        0..3,
+    )
+}
+
+/// `(type, config)` for a random non-trigger node that may reference `ref_ids`.
+fn arb_action(ref_ids: Vec<String>) -> impl Strategy<Value = (&'static str, Value)> {
+    let v = || arb_value(ref_ids.clone());
+    prop_oneof![
+        (
+            prop::sample::select(vec!["GET", "POST", "PUT", "DELETE", ""]),
+            v(),
+            prop::option::of(v()),
+            prop::option::of(0u32..600),
+        )
+            .prop_map(|(method, url, body, cache)| {
+                let body = body.map(|data| json!({ "contentType": "json", "data": data }));
+                (
+                    "httpRequest",
+                    json!({
+                        "method": method, "url": url, "body": body, "cacheMaxAge": cache
+                    }),
+                )
+            }),
+        (
+            arb_chain(),
+            v(),
+            arb_text(),
+            arb_abi_params(),
+            arb_abi_params()
+        )
+            .prop_map(|(chain, address, function_name, inputs, outputs)| {
+                (
+                    "evmRead",
+                    json!({
+                        "chainSelectorName": chain,
+                        "contractAddress": address,
+                        "functionName": function_name,
+                        "abi": {
+                            "type": "function", "name": function_name, "inputs": inputs,
+                            "outputs": outputs, "stateMutability": "view"
+                        },
+                        "args": []
+                    }),
+                )
+            }),
+        (arb_chain(), v(), arb_text(), v()).prop_map(|(chain, receiver, gas, data)| {
+            (
+                "evmWrite",
+                json!({
+                    "chainSelectorName": chain,
+                    "receiverAddress": receiver,
+                    "gasLimit": gas,
+                    "encodedData": data
+                }),
+            )
+        }),
+        (
+            prop::collection::vec(arb_chain(), 0..3),
+            v(),
+            arb_text(),
+            arb_abi_params(),
+            prop::collection::vec((arb_text(), v()), 0..3),
+        )
+            .prop_map(|(chains, receiver, gas, params, mappings)| {
+                let mappings: Vec<Value> = mappings
+                    .into_iter()
+                    .map(|(param, source)| json!({ "paramName": param, "source": source }))
+                    .collect();
+                (
+                    "broadcastWrite",
+                    json!({
+                        "chainSelectorNames": chains,
+                        "receiverAddress": receiver,
+                        "gasLimit": gas,
+                        "abiParams": params,
+                        "dataMapping": mappings
+                    }),
+                )
+            }),
+        (arb_text(), prop::collection::vec(v(), 0..3)).prop_map(|(code, inputs)| {
+            (
+                "codeNode",
+                json!({
+                    "code": code, "executionMode": "runOnceForAll", "inputVariables": inputs
+                }),
+            )
+        }),
+        (
+            arb_abi_params(),
+            prop::collection::vec((arb_text(), v()), 0..3)
+        )
+            .prop_map(|(params, mappings)| {
+                let mappings: Vec<Value> = mappings
+                    .into_iter()
+                    .map(|(param, source)| json!({ "paramName": param, "source": source }))
+                    .collect();
+                (
+                    "abiEncode",
+                    json!({ "abiParams": params, "dataMapping": mappings }),
+                )
+            }),
+        (arb_abi_params(), prop::collection::vec(arb_text(), 0..3)).prop_map(|(params, names)| (
+            "abiDecode",
+            json!({ "abiParams": params, "outputNames": names })
+        )),
+        (
+            prop_oneof![
+                v(),
+                (v(), v()).prop_map(|(a, b)| format!("{a} * ({b} + 1) / 0"))
+            ],
+            prop::option::of(prop::sample::select(vec!["auto", "number", "bigint", ""])),
+        )
+            .prop_map(|(expression, mode)| {
+                ("math", json!({ "expression": expression, "mode": mode }))
+            }),
+        v().prop_map(|key| ("kvGet", json!({ "key": key }))),
+        (v(), v()).prop_map(|(key, value)| ("kvSet", json!({ "key": key, "value": value }))),
+        arb_conditions(ref_ids.clone()).prop_map(|conditions| {
+            (
+                "filter",
+                json!({ "conditions": conditions, "combineWith": "and" }),
+            )
+        }),
+        arb_conditions(ref_ids.clone()).prop_map(|conditions| {
+            (
+                "if",
+                json!({ "conditions": conditions, "combineWith": "or" }),
+            )
+        }),
+        v().prop_map(|expr| ("return", json!({ "returnExpression": expr }))),
+        v().prop_map(|message| ("error", json!({ "errorMessage": message }))),
+    ]
+}
+
+/// Node id for position `index`; the suffix keeps ids unique even when the
+/// random prefix collides or is empty.
+fn node_id(prefix: &str, index: usize) -> String {
+    format!("{prefix}{index}")
+}
+
+/// A random workflow: one cron trigger, up to [`MAX_ACTION_NODES`] actions,
+/// and edges that mostly (but not always) form a DAG rooted at the trigger.
+pub fn arb_workflow() -> impl Strategy<Value = Workflow> {
+    (
+        prop::collection::vec("\\PC{0,3}", 1..=MAX_ACTION_NODES + 1),
+        any::<bool>(),
+        prop::option::of(arb_chain()),
+    )
+        .prop_flat_map(|(prefixes, is_testnet, default_chain)| {
+            let ids: Vec<String> = prefixes
+                .iter()
+                .enumerate()
+                .map(|(i, p)| node_id(p, i))
+                .collect();
+            // Each action may only reference nodes placed before it.
+            let actions: Vec<_> = (1..ids.len())
+                .map(|i| arb_action(ids[..i].to_vec()))
+                .collect();
+            // Parent of each action, plus an optional handle and an optional extra edge.
+            let edges: Vec<_> = (1..ids.len())
+                .map(|i| {
+                    (
+                        0..i,
+                        prop::option::of(prop::sample::select(vec!["output", "true", "false", ""])),
+                        prop::option::of(0..ids.len()),
+                    )
+                })
+                .collect();
+            (
+                Just(ids),
+                actions,
+                edges,
+                Just(is_testnet),
+                Just(default_chain),
+                arb_text(),
+            )
+        })
+        .prop_map(
+            |(ids, actions, edges, is_testnet, default_chain, schedule)| {
+                let mut nodes = vec![json!({
+                    "id": ids[0],
+                    "type": "cronTrigger",
+                    "position": { "x": 0, "y": 0 },
+                    "data": { "label": "Trigger", "config": { "schedule": schedule } }
+                })];
+                for (i, (node_type, config)) in actions.into_iter().enumerate() {
+                    let id = &ids[i + 1];
+                    nodes.push(json!({
+                        "id": id,
+                        "type": node_type,
+                        "position": { "x": 0, "y": (i + 1) * 100 },
+                        "data": { "label": id, "config": config }
+                    }));
+                }
+
+                let mut edge_values = Vec::new();
+                for (i, (parent, handle, extra)) in edges.into_iter().enumerate() {
+                    let target = &ids[i + 1];
+                    edge_values.push(json!({
+                        "id": format!("e{}", edge_values.len()),
+                        "source": ids[parent],
+                        "target": target,
+                        "sourceHandle": handle,
+                    }));
+                    if let Some(extra) = extra {
+                        edge_values.push(json!({
+                            "id": format!("e{}", edge_values.len()),
+                            "source": ids[extra],
+                            "target": target,
+                        }));
+                    }
+                }
+
+                serde_json::from_value(json!({
+                    "id": "fuzz-workflow",
+                    "name": "Fuzz",
+                    "version": "1.0.0",
+                    "nodes": nodes,
+                    "edges": edge_values,
+                    "globalConfig": {
+                        "isTestnet": is_testnet,
+                        "secrets": [{ "name": "API_KEY", "envVariable": "API_KEY_VAR" }],
+                        "rpcs": [],
+                        "defaultChainSelector": default_chain
+                    },
+                    "createdAt": "",
+                    "updatedAt": ""
+                }))
+                .expect("generated workflow JSON must match parse::types")
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn generated_workflows_round_trip_through_parse(workflow in arb_workflow()) {
+            let json = serde_json::to_string(&workflow).unwrap();
+            let parsed = crate::parse::parse(&json).unwrap();
+            prop_assert_eq!(parsed.nodes.iter().filter(|n| n.is_trigger()).count(), 1);
+            prop_assert_eq!(parsed.nodes.len(), workflow.nodes.len());
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Config document checks against a lowered workflow's `config_schema`
+//! (C001–C005, W014).
+//!
+//! A hand-edited `config.staging.json` that no longer matches the schema only
+//! fails once the Zod schema runs inside CRE. [`check_config_values`] applies
+//! the same rules in Rust so the editor can flag them as the user types:
+//! required fields, the JSON type of each value, keys the schema does not
+//! declare, and the cron syntax of the trigger's schedule.
+
+use serde_json::Value;
+
+use crate::ir::types::*;
+use crate::ir::validate::ValidationError;
+
+/// Check `config_json` against `ir.config_schema`. Custom (`Raw`) Zod types
+/// are checked as far as their outermost call goes (`z.object`, `z.array`,
+/// `z.enum([...])`); any other one is reported as unchecked (W014).
+pub fn check_config_values(ir: &WorkflowIR, config_json: &str) -> Vec<ValidationError> {
+    let document = match serde_json::from_str::<Value>(config_json) {
+        Ok(Value::Object(document)) => document,
+        Ok(other) => {
+            return vec![error(
+                "C001",
+                format!(
+                    "Config must be a JSON object, got {}",
+                    json_type_name(&other)
+                ),
+            )];
+        }
+        Err(e) => return vec![error("C001", format!("Config is not valid JSON: {}", e))],
+    };
+
+    let mut errors = Vec::new();
+    for field in &ir.config_schema {
+        let shape = FieldShape::of(&field.zod_type);
+        let nullable = field.optional_in_config || shape.nullable;
+        let value = match document.get(&field.name) {
+            None if field.default_value.is_some() || nullable => continue,
+            None => {
+                errors.push(error(
+                    "C002",
+                    format!(
+                        "Config field '{}' is required ({})",
+                        field.name,
+                        shape.describe()
+                    ),
+                ));
+                continue;
+            }
+            Some(Value::Null) if nullable => continue,
+            Some(value) => value,
+        };
+        if let Some(problem) = shape.check(value) {
+            errors.push(error(
+                "C003",
+                format!("Config field '{}' {}", field.name, problem),
+            ));
+        } else if let Kind::Unchecked(expr) = shape.kind {
+            errors.push(error(
+                "W014",
+                format!(
+                    "Config field '{}' has custom type {} and was not checked",
+                    field.name, expr
+                ),
+            ));
+        }
+    }
+
+    for key in document.keys() {
+        if !ir.config_schema.iter().any(|f| f.name == *key) {
+            errors.push(error(
+                "C004",
+                format!("Config key '{}' is not declared by the workflow", key),
+            ));
+        }
+    }
+
+    if let TriggerDef::Cron(cron) = &ir.trigger
+        && let ValueExpr::ConfigRef { field } = &cron.schedule
+        && let Some(Value::String(schedule)) = document.get(field)
+        && let Err(problem) = check_cron_schedule(schedule)
+    {
+        errors.push(error(
+            "C005",
+            format!(
+                "Config field '{}' is not a valid cron schedule: {}",
+                field, problem
+            ),
+        ));
+    }
+
+    errors
+}
+
+fn error(code: &'static str, message: String) -> ValidationError {
+    ValidationError {
+        code,
+        message,
+        step_id: None,
+        related: vec![],
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// What a Zod type accepts, as far as it is understood here.
+struct FieldShape<'a> {
+    kind: Kind<'a>,
+    /// `.nullable()` / `.optional()` / `.nullish()` on a `Raw` type.
+    nullable: bool,
+}
+
+enum Kind<'a> {
+    String,
+    Number,
+    Boolean,
+    Object,
+    Array,
+    Enum(Vec<&'a str>),
+    Unchecked(&'a str),
+}
+
+impl<'a> FieldShape<'a> {
+    fn of(zod_type: &'a ZodType) -> Self {
+        let expr = match zod_type {
+            ZodType::String => return Self::plain(Kind::String),
+            ZodType::Number => return Self::plain(Kind::Number),
+            ZodType::Boolean => return Self::plain(Kind::Boolean),
+            ZodType::Raw(expr) => expr.trim(),
+        };
+        let mut base = expr;
+        let mut nullable = false;
+        while let Some(rest) = [".nullable()", ".optional()", ".nullish()"]
+            .iter()
+            .find_map(|modifier| base.strip_suffix(modifier))
+        {
+            base = rest;
+            nullable = true;
+        }
+        let kind = if base.starts_with("z.object(") {
+            Kind::Object
+        } else if base.starts_with("z.array(") {
+            Kind::Array
+        } else if let Some(values) = parse_enum(base) {
+            Kind::Enum(values)
+        } else {
+            match base {
+                "z.string()" => Kind::String,
+                "z.number()" => Kind::Number,
+                "z.boolean()" => Kind::Boolean,
+                _ => Kind::Unchecked(expr),
+            }
+        };
+        FieldShape { kind, nullable }
+    }
+
+    fn plain(kind: Kind<'a>) -> Self {
+        FieldShape {
+            kind,
+            nullable: false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.kind {
+            Kind::String => "a string".into(),
+            Kind::Number => "a number".into(),
+            Kind::Boolean => "a boolean".into(),
+            Kind::Object => "an object".into(),
+            Kind::Array => "an array".into(),
+            Kind::Enum(values) => format!("one of {}", values.join(", ")),
+            Kind::Unchecked(expr) => format!("custom type {}", expr),
+        }
+    }
+
+    /// Why `value` does not fit, if it does not.
+    fn check(&self, value: &Value) -> Option<String> {
+        let fits = match (&self.kind, value) {
+            (Kind::String, Value::String(_))
+            | (Kind::Number, Value::Number(_))
+            | (Kind::Boolean, Value::Bool(_))
+            | (Kind::Object, Value::Object(_))
+            | (Kind::Array, Value::Array(_))
+            | (Kind::Unchecked(_), _) => true,
+            (Kind::Enum(values), Value::String(s)) => {
+                if values.iter().any(|v| v == s) {
+                    return None;
+                }
+                return Some(format!("must be {}, got \"{}\"", self.describe(), s));
+            }
+            _ => false,
+        };
+        (!fits).then(|| format!("must be {}, got {}", self.describe(), json_type_name(value)))
+    }
+}
+
+/// The values of `z.enum(["a", "b"])`, or `None` if `expr` is not a plain
+/// enum of string literals.
+fn parse_enum(expr: &str) -> Option<Vec<&str>> {
+    let inner = expr.strip_prefix("z.enum([")?.strip_suffix("])")?;
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| item.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+        })
+        .collect()
+}
+
+/// Range of each cron field, for 6-field schedules (seconds first).
+const CRON_FIELDS: [(&str, u32, u32); 6] = [
+    ("second", 0, 59),
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day of month", 1, 31),
+    ("month", 1, 12),
+    ("day of week", 0, 7),
+];
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const DAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Check a CRE cron schedule: an optional `TZ=<zone> ` prefix, then five
+/// fields (minute first) or six (second first).
+fn check_cron_schedule(schedule: &str) -> Result<(), String> {
+    let mut rest = schedule.trim();
+    if let Some(tz) = rest.strip_prefix("TZ=") {
+        let (zone, fields) = tz.split_once(' ').ok_or("no fields after the time zone")?;
+        if zone.is_empty() {
+            return Err("empty time zone".into());
+        }
+        rest = fields;
+    }
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let specs = match fields.len() {
+        5 => &CRON_FIELDS[1..],
+        6 => &CRON_FIELDS[..],
+        n => return Err(format!("expected 5 or 6 fields, got {}", n)),
+    };
+    for (field, &(name, min, max)) in fields.iter().zip(specs) {
+        check_cron_field(field, name, min, max)
+            .map_err(|problem| format!("{} field '{}': {}", name, field, problem))?;
+    }
+    Ok(())
+}
+
+fn check_cron_field(field: &str, name: &str, min: u32, max: u32) -> Result<(), String> {
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        if let Some(step) = step {
+            match step.parse::<u32>() {
+                Ok(s) if s > 0 => {}
+                _ => return Err(format!("invalid step '{}'", step)),
+            }
+        }
+        if range == "*" {
+            continue;
+        }
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start = cron_value(start, name, min, max)?;
+        let end = cron_value(end, name, min, max)?;
+        if start > end {
+            return Err(format!("range {} is backwards", range));
+        }
+    }
+    Ok(())
+}
+
+fn cron_value(value: &str, name: &str, min: u32, max: u32) -> Result<u32, String> {
+    let names: &[&str] = match name {
+        "month" => &MONTH_NAMES,
+        "day of week" => &DAY_NAMES,
+        _ => &[],
+    };
+    if let Some(i) = names.iter().position(|n| n.eq_ignore_ascii_case(value)) {
+        // Months count from 1, days of the week from 0.
+        return Ok(i as u32 + min);
+    }
+    match value.parse::<u32>() {
+        Ok(v) if (min..=max).contains(&v) => Ok(v),
+        Ok(v) => Err(format!("{} is outside {}-{}", v, min, max)),
+        Err(_) => Err(format!("'{}' is not a number", value)),
+    }
+}
@@ -0,0 +1,73 @@
+//! Workflow-wide `globalConfig` validation rules (G001).
+
+use std::collections::HashMap;
+
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::ir::types::Environment;
+use crate::parse::types::{GlobalConfig, SecretReference};
+
+/// Run all `globalConfig` rules. Error paths start at `globalConfig`.
+pub fn validate_global_config(global: &GlobalConfig) -> Vec<CompilerError> {
+    let mut errors = Vec::new();
+
+    g001_secret_env_variables_unique(global, &mut errors);
+
+    errors
+}
+
+/// The `.env` variable a secret is read from in `environment`, and the
+/// config key it comes from.
+fn secret_env_variable(secret: &SecretReference, environment: Environment) -> (&'static str, &str) {
+    let env_override = match environment {
+        Environment::Staging => ("envVariableStaging", &secret.env_variable_staging),
+        Environment::Production => ("envVariableProduction", &secret.env_variable_production),
+    };
+    match env_override {
+        (key, Some(var)) => (key, var.as_str()),
+        (_, None) => ("envVariable", secret.env_variable.as_str()),
+    }
+}
+
+/// Two secrets read from the same variable would get the same value.
+fn g001_secret_env_variables_unique(global: &GlobalConfig, errors: &mut Vec<CompilerError>) {
+    // (secret index, config key, variable, earlier secret index) → environments
+    type Collision<'a> = (usize, &'static str, &'a str, usize);
+    let mut collisions: Vec<(Collision, Vec<&str>)> = Vec::new();
+    for environment in Environment::ALL {
+        let mut first_use: HashMap<&str, usize> = HashMap::new();
+        for (i, secret) in global.secrets.iter().enumerate() {
+            let (key, var) = secret_env_variable(secret, environment);
+            let Some(&first) = first_use.get(var) else {
+                first_use.insert(var, i);
+                continue;
+            };
+            let collision = (i, key, var, first);
+            match collisions.iter_mut().find(|(c, _)| *c == collision) {
+                Some((_, environments)) => environments.push(environment.as_str()),
+                None => collisions.push((collision, vec![environment.as_str()])),
+            }
+        }
+    }
+
+    for ((i, key, var, first), environments) in collisions {
+        errors.push(
+            CompilerError::validate(
+                "G001",
+                format!(
+                    "Secrets '{}' and '{}' both read environment variable '{}' in {}",
+                    global.secrets[first].name,
+                    global.secrets[i].name,
+                    var,
+                    environments.join(" and ")
+                ),
+                None,
+            )
+            .with_path(vec![
+                JsonPathSegment::key("globalConfig"),
+                JsonPathSegment::key("secrets"),
+                JsonPathSegment::Index(i),
+                JsonPathSegment::key(key),
+            ]),
+        );
+    }
+}
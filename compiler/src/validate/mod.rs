@@ -1,26 +1,45 @@
 //! Graph-level validation phase (pre-IR).
 //!
-//! Validates the raw workflow graph before lowering to IR.
+//! Validates the raw workflow graph before lowering to IR. `config` and
+//! `policy` are the exceptions: they check a config document or an
+//! organization policy against a lowered workflow.
 
+pub mod config;
+pub mod global_config;
 pub mod node_rules;
+pub mod policy;
 pub mod structural;
 
-use crate::error::CompilerError;
+use crate::error::{CompilerError, JsonPathSegment};
 use crate::parse::graph::WorkflowGraph;
 use crate::parse::types::{GlobalConfig, Workflow, WorkflowNode};
 
-/// Validate the entire workflow graph (structural + node configs).
+/// Validate the entire workflow graph (structural, `globalConfig` and node
+/// configs).
+/// Errors about a node carry a `path` from the workflow root.
 pub fn validate_graph(workflow: &Workflow, graph: &WorkflowGraph) -> Vec<CompilerError> {
-    let mut errors = structural::validate_structural(workflow, graph);
+    let mut errors: Vec<CompilerError> = structural::validate_structural(workflow, graph)
+        .into_iter()
+        .map(|e| e.locate_node(workflow))
+        .collect();
+    errors.extend(global_config::validate_global_config(
+        &workflow.global_config,
+    ));
 
-    for node in &workflow.nodes {
-        errors.extend(validate_node(node, &workflow.global_config));
+    for (i, node) in workflow.nodes.iter().enumerate() {
+        let node_path = [JsonPathSegment::key("nodes"), JsonPathSegment::Index(i)];
+        errors.extend(
+            validate_node(node, &workflow.global_config)
+                .into_iter()
+                .map(|e| e.under(&node_path)),
+        );
     }
 
     errors
 }
 
-/// Validate a single node's configuration.
+/// Validate a single node's configuration. Error paths are relative to the
+/// node (`data.config.<field>`).
 pub fn validate_node(node: &WorkflowNode, global: &GlobalConfig) -> Vec<CompilerError> {
     node_rules::validate_node_config(node, global)
 }
@@ -2,7 +2,14 @@
 //! SYNC NOTE: Match arms here must track `WorkflowNode` in
 //! `compiler/src/parse/types.rs` and `shared/model/node.ts`.
 
-use crate::error::CompilerError;
+use std::collections::HashMap;
+
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::ir::js_regex::check_pattern;
+use crate::ir::types::{AiProvider, EVM_BLOCK_TAGS};
+use crate::ir::url::{UrlProblem, config_url_parts, parse_url, redact_url};
+use crate::ir::validate::is_solidity_identifier;
+use crate::lower::compute::{divides_by_literal_zero, parse_compute_expr};
 use crate::parse::types::*;
 
 /// Validate a single node's config. Returns all errors found.
@@ -13,61 +20,103 @@ pub fn validate_node_config(node: &WorkflowNode, global: &GlobalConfig) -> Vec<C
     match node {
         WorkflowNode::CronTrigger(n) => {
             if n.data.config.schedule.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N001",
-                    "Cron trigger schedule must not be empty",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N001",
+                        "Cron trigger schedule must not be empty",
+                        node_id,
+                    )
+                    .with_path(config_path("schedule")),
+                );
             }
         }
         WorkflowNode::HttpTrigger(n) => {
             let valid_methods = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD"];
             if !valid_methods.contains(&n.data.config.http_method.as_str()) {
-                errors.push(CompilerError::validate(
-                    "N002",
-                    format!("Invalid HTTP method '{}'", n.data.config.http_method),
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N002",
+                        format!("Invalid HTTP method '{}'", n.data.config.http_method),
+                        node_id,
+                    )
+                    .with_path(config_path("httpMethod")),
+                );
             }
         }
         WorkflowNode::EvmLogTrigger(n) => {
             if n.data.config.contract_addresses.is_empty() {
-                errors.push(CompilerError::validate(
-                    "N003",
-                    "EVM log trigger must have at least one contract address",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N003",
+                        "EVM log trigger must have at least one contract address",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("contractAddresses")),
+                );
             }
             if n.data.config.contract_addresses.len() > 5 {
-                errors.push(CompilerError::validate(
-                    "N003",
-                    "EVM log trigger cannot have more than 5 contract addresses (CRE limit)",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N003",
+                        "EVM log trigger cannot have more than 5 contract addresses (CRE limit)",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("contractAddresses")),
+                );
             }
             if n.data.config.event_signature.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N003",
-                    "EVM log trigger event signature must not be empty",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N003",
+                        "EVM log trigger event signature must not be empty",
+                        node_id,
+                    )
+                    .with_path(config_path("eventSignature")),
+                );
             }
         }
         WorkflowNode::HttpRequest(n) => {
             if n.data.config.url.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N004",
-                    "HTTP request URL must not be empty",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N004",
+                        "HTTP request URL must not be empty",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("url")),
+                );
             }
             let valid_methods = ["GET", "POST", "PUT", "DELETE", "PATCH", "HEAD"];
             if !valid_methods.contains(&n.data.config.method.as_str()) {
-                errors.push(CompilerError::validate(
-                    "N004",
-                    format!("Invalid HTTP method '{}'", n.data.config.method),
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N004",
+                        format!("Invalid HTTP method '{}'", n.data.config.method),
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("method")),
+                );
+            }
+            validate_http_url(&n.data.config, global, node_id.clone(), &mut errors);
+            validate_http_transport_flags(
+                &n.data.config,
+                global.is_testnet,
+                node_id.clone(),
+                &mut errors,
+            );
+            let method = n.data.config.method.as_str();
+            if n.data.config.body.is_some() && matches!(method, "GET" | "HEAD") {
+                errors.push(
+                    CompilerError::validate(
+                        "W016",
+                        format!(
+                            "{} request has a body — many servers and proxies ignore or reject it; send the data as query parameters or use POST",
+                            method
+                        ),
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("body")),
+                );
             }
             // Check auth secret references exist
             if let Some(auth) = &n.data.config.authentication {
@@ -75,144 +124,538 @@ pub fn validate_node_config(node: &WorkflowNode, global: &GlobalConfig) -> Vec<C
             }
         }
         WorkflowNode::EvmRead(n) => {
+            if is_blank_chain(&n.data.config.chain_selector_name) {
+                errors.push(
+                    CompilerError::validate(
+                        "N005",
+                        "EVM read chain selector must not be empty — pick a chain, or remove it to use the workflow's default chain",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("chainSelectorName")),
+                );
+            }
             if n.data.config.contract_address.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N005",
-                    "EVM read contract address must not be empty",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N005",
+                        "EVM read contract address must not be empty",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("contractAddress")),
+                );
             }
             if n.data.config.function_name.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N005",
-                    "EVM read function name must not be empty",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N005",
+                        "EVM read function name must not be empty",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("functionName")),
+                );
+            }
+            if let Some(block) = &n.data.config.block_number
+                && let Some(message) = block_number_problem(block)
+            {
+                errors.push(
+                    CompilerError::validate("N033", message, node_id)
+                        .with_path(config_path("blockNumber")),
+                );
             }
         }
         WorkflowNode::EvmWrite(n) => {
+            if is_blank_chain(&n.data.config.chain_selector_name) {
+                errors.push(
+                    CompilerError::validate(
+                        "N006",
+                        "EVM write chain selector must not be empty — pick a chain, or remove it to use the workflow's default chain",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("chainSelectorName")),
+                );
+            }
             if n.data.config.receiver_address.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N006",
-                    "EVM write receiver address must not be empty",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N006",
+                        "EVM write receiver address must not be empty",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("receiverAddress")),
+                );
             }
             if n.data.config.encoded_data.trim().is_empty() {
+                errors.push(
+                    CompilerError::validate(
+                        "N006",
+                        "EVM write encoded data reference must not be empty",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("encodedData")),
+                );
+            } else if !is_encoded_data_source(&n.data.config.encoded_data) {
                 errors.push(CompilerError::validate(
-                    "N006",
-                    "EVM write encoded data reference must not be empty",
+                    "N030",
+                    format!(
+                        "EVM write encoded data '{}' is neither a reference nor hex calldata — drag an ABI Encode node's output here (e.g. {{{{encode-1.encoded}}}}) or enter 0x-prefixed calldata",
+                        n.data.config.encoded_data.trim()
+                    ),
                     node_id.clone(),
-                ));
+                )
+                .with_path(config_path("encodedData")));
             }
             if let Ok(gas) = n.data.config.gas_limit.parse::<u64>() {
                 if gas > 5_000_000 {
-                    errors.push(CompilerError::validate(
-                        "N006",
-                        "EVM write gas limit exceeds CRE maximum (5,000,000)",
-                        node_id,
-                    ));
+                    errors.push(
+                        CompilerError::validate(
+                            "N006",
+                            "EVM write gas limit exceeds CRE maximum (5,000,000)",
+                            node_id,
+                        )
+                        .with_path(config_path("gasLimit")),
+                    );
                 }
             }
         }
+        WorkflowNode::BroadcastWrite(n) => {
+            validate_broadcast_write(&n.data.config, node.id(), &mut errors);
+        }
         WorkflowNode::CodeNode(n) => {
             if n.data.config.code.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N008",
-                    "Code node must have non-empty code",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate("N008", "Code node must have non-empty code", node_id)
+                        .with_path(config_path("code")),
+                );
             }
         }
         WorkflowNode::AbiEncode(n) => {
             if n.data.config.abi_params.is_empty() {
-                errors.push(CompilerError::validate(
-                    "N009",
-                    "ABI encode must have at least one parameter",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N009",
+                        "ABI encode must have at least one parameter",
+                        node_id,
+                    )
+                    .with_path(config_path("abiParams")),
+                );
             }
+            validate_abi_function_name(&n.data.config, node.id(), &mut errors);
+            validate_abi_encode_mappings(&n.data.config, node.id(), &mut errors);
         }
         WorkflowNode::AbiDecode(n) => {
             if n.data.config.abi_params.is_empty() {
-                errors.push(CompilerError::validate(
-                    "N010",
-                    "ABI decode must have at least one parameter",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N010",
+                        "ABI decode must have at least one parameter",
+                        node_id,
+                    )
+                    .with_path(config_path("abiParams")),
+                );
             }
         }
         WorkflowNode::Merge(_) => {}
+        WorkflowNode::Math(n) => {
+            validate_math_config(&n.data.config, node.id(), &mut errors);
+        }
         WorkflowNode::Filter(n) => {
             if n.data.config.conditions.is_empty() {
-                errors.push(CompilerError::validate(
-                    "N011",
-                    "Filter must have at least one condition",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N011",
+                        "Filter must have at least one condition",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("conditions")),
+                );
             }
+            validate_condition_groups(
+                &n.data.config.conditions,
+                config_path("conditions"),
+                1,
+                &node_id,
+                &mut errors,
+            );
         }
         WorkflowNode::If(n) => {
             if n.data.config.conditions.is_empty() {
-                errors.push(CompilerError::validate(
-                    "N012",
-                    "If node must have at least one condition",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N012",
+                        "If node must have at least one condition",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("conditions")),
+                );
+            }
+            validate_condition_groups(
+                &n.data.config.conditions,
+                config_path("conditions"),
+                1,
+                &node_id,
+                &mut errors,
+            );
+        }
+        WorkflowNode::KvGet(n) => {
+            if n.data.config.key.trim().is_empty() {
+                errors.push(
+                    CompilerError::validate("N024", "KV get key must not be empty", node_id)
+                        .with_path(config_path("key")),
+                );
+            }
+        }
+        WorkflowNode::KvSet(n) => {
+            if n.data.config.key.trim().is_empty() {
+                errors.push(
+                    CompilerError::validate("N024", "KV set key must not be empty", node_id)
+                        .with_path(config_path("key")),
+                );
             }
         }
         WorkflowNode::Ai(n) => {
             if n.data.config.api_key_secret.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N013",
-                    "AI node must have an API key secret",
-                    node_id.clone(),
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N013",
+                        "AI node must have an API key secret",
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("apiKeySecret")),
+                );
             }
             if !global
                 .secrets
                 .iter()
                 .any(|s| s.name == n.data.config.api_key_secret)
             {
-                errors.push(CompilerError::validate(
-                    "N013",
-                    format!(
-                        "Secret '{}' not declared in globalConfig.secrets",
-                        n.data.config.api_key_secret
-                    ),
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate(
+                        "N013",
+                        format!(
+                            "Secret '{}' not declared in globalConfig.secrets",
+                            n.data.config.api_key_secret
+                        ),
+                        node_id.clone(),
+                    )
+                    .with_path(config_path("apiKeySecret")),
+                );
             }
+            validate_ai_sampling(&n.data.config, node_id.clone(), &mut errors);
+            validate_ai_consensus(&n.data.config, node_id.clone(), &mut errors);
+            validate_ai_provider(&n.data.config, node_id, &mut errors);
         }
         WorkflowNode::Return(n) => {
             if n.data.config.return_expression.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N014",
-                    "Return expression must not be empty",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate("N014", "Return expression must not be empty", node_id)
+                        .with_path(config_path("returnExpression")),
+                );
             }
         }
         WorkflowNode::Error(n) => {
             if n.data.config.error_message.trim().is_empty() {
-                errors.push(CompilerError::validate(
-                    "N016",
-                    "Error message must not be empty",
-                    node_id,
-                ));
+                errors.push(
+                    CompilerError::validate("N016", "Error message must not be empty", node_id)
+                        .with_path(config_path("errorMessage")),
+                );
             }
         }
     }
 
+    errors.extend(check_deprecations(node, DEPRECATIONS));
     errors
 }
 
+/// A node type, or one config field of it, that still compiles but is being
+/// phased out.
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+    /// Node `type`, e.g. `ai`.
+    pub node_type: &'static str,
+    /// Config field (`data.config.<field>`); `None` deprecates the node type.
+    pub field: Option<&'static str>,
+    /// Why it is going away.
+    pub message: &'static str,
+    /// What to use instead, if anything.
+    pub replacement: Option<&'static str>,
+    /// Compiler version that stops accepting it.
+    pub removal_version: &'static str,
+}
+
+/// Deprecations checked by node validation.
+pub const DEPRECATIONS: &[Deprecation] = &[Deprecation {
+    node_type: "ai",
+    field: Some("maxRetries"),
+    message: "it has no effect; the generated workflow makes each AI call once",
+    replacement: None,
+    removal_version: "0.3.0",
+}];
+
+/// W018: one warning per entry of `registry` that `node` uses. A field
+/// counts as used when it is set to anything but `null`.
+pub fn check_deprecations(node: &WorkflowNode, registry: &[Deprecation]) -> Vec<CompilerError> {
+    let node_type = node.node_type();
+    let entries: Vec<&Deprecation> = registry
+        .iter()
+        .filter(|d| d.node_type == node_type)
+        .collect();
+    if entries.is_empty() {
+        return vec![];
+    }
+    let config = entries
+        .iter()
+        .any(|d| d.field.is_some())
+        .then(|| serde_json::to_value(node).ok())
+        .flatten();
+
+    let mut warnings = Vec::new();
+    for deprecation in entries {
+        let (subject, path) = match deprecation.field {
+            None => (format!("Node type '{}'", node_type), vec![]),
+            Some(field) => {
+                let set = config
+                    .as_ref()
+                    .and_then(|node| node.pointer(&format!("/data/config/{}", field)))
+                    .is_some_and(|value| !value.is_null());
+                if !set {
+                    continue;
+                }
+                (
+                    format!("Field '{}' of {} nodes", field, node_type),
+                    config_path(field),
+                )
+            }
+        };
+        let mut message = format!(
+            "{} is deprecated and will be removed in {}: {}",
+            subject, deprecation.removal_version, deprecation.message
+        );
+        if let Some(replacement) = deprecation.replacement {
+            message.push_str(&format!("; use {} instead", replacement));
+        }
+        warnings.push(
+            CompilerError::validate("W018", message, Some(node.id().to_string())).with_path(path),
+        );
+    }
+    warnings
+}
+
+/// `data.config.<field>`, relative to the node. `validate_graph` places it
+/// under `nodes[i]`.
+fn config_path(field: &str) -> Vec<JsonPathSegment> {
+    vec![
+        JsonPathSegment::key("data"),
+        JsonPathSegment::key("config"),
+        JsonPathSegment::key(field),
+    ]
+}
+
+/// `data.config.<list>[index].<field>`, relative to the node.
+fn indexed_config_path(list: &str, index: usize, field: &str) -> Vec<JsonPathSegment> {
+    let mut path = config_path(list);
+    path.push(JsonPathSegment::Index(index));
+    path.push(JsonPathSegment::key(field));
+    path
+}
+
+/// N019 / W006 / N026–N029: what the URL's literal text says about the
+/// endpoint. It must parse as http(s) (N026) with a literal scheme and host,
+/// since every consensus node has to call the same endpoint (N027), and
+/// carry no `user:pass@` (N028) or query keys repeated in `queryParameters`
+/// (N029). Plain http is an error on mainnet (N019) and a warning on testnet
+/// (W006), unless the host is listed in `allowHttpHosts`. Messages quote the
+/// URL with credentials and query values elided.
+fn validate_http_url(
+    config: &HttpRequestConfig,
+    global: &GlobalConfig,
+    node_id: Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    let url = config.url.trim();
+    if url.is_empty() {
+        return;
+    }
+    let shown = redact_url(url);
+    let url_error = |code: &str, message: String| {
+        CompilerError::validate(code, message, node_id.clone()).with_path(config_path("url"))
+    };
+    let shape = match parse_url(&config_url_parts(url)) {
+        Ok(shape) => shape,
+        Err(UrlProblem::DynamicScheme) => {
+            errors.push(url_error(
+                "N027",
+                format!(
+                    "HTTP request URL '{}' must start with a literal scheme and host: every node in the DON has to call the same endpoint",
+                    shown
+                ),
+            ));
+            return;
+        }
+        Err(problem) => {
+            errors.push(url_error(
+                "N026",
+                format!("HTTP request URL '{}' is malformed: {}", shown, problem),
+            ));
+            return;
+        }
+    };
+
+    if shape.scheme == "http" {
+        let allowed = global.is_testnet
+            && shape.host.as_ref().is_some_and(|host| {
+                global
+                    .allow_http_hosts
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(host))
+            });
+        if !allowed && global.is_testnet {
+            errors.push(url_error(
+                "W006",
+                format!(
+                    "HTTP request URL '{}' uses 'http://' — allowed on testnet, but mainnet workflows require https",
+                    shown
+                ),
+            ));
+        } else if !allowed {
+            errors.push(url_error(
+                "N019",
+                format!(
+                    "HTTP request URL must use https on mainnet (got '{}')",
+                    shown
+                ),
+            ));
+        }
+    }
+    if shape.host.is_none() {
+        errors.push(url_error(
+            "N027",
+            format!(
+                "HTTP request URL '{}' has a templated host: every node in the DON has to call the same endpoint, so put only the path or query in a template",
+                shown
+            ),
+        ));
+    }
+    if shape.has_credentials {
+        errors.push(url_error(
+            "N028",
+            format!(
+                "HTTP request URL '{}' embeds credentials; use authentication with a secret instead",
+                shown
+            ),
+        ));
+    }
+    if let Some(params) = &config.query_parameters {
+        for key in shape
+            .query_keys
+            .iter()
+            .filter(|key| params.contains_key(*key))
+        {
+            errors.push(
+                CompilerError::validate(
+                    "N029",
+                    format!(
+                        "Query parameter '{}' is set both in the URL '{}' and in queryParameters",
+                        key, shown
+                    ),
+                    node_id.clone(),
+                )
+                .with_path(config_path("queryParameters")),
+            );
+        }
+    }
+}
+
+/// N021 / N025 / W008: the CRE HTTP capability always follows redirects and
+/// always verifies TLS certificates, so neither can be switched off.
+/// `followRedirects: false` is rejected. `ignoreSSL: true` is rejected on
+/// mainnet, where it means the workflow expects to talk to an endpoint whose
+/// certificate cannot be trusted; on testnet it is a warning, since the
+/// request still works against a valid certificate.
+fn validate_http_transport_flags(
+    config: &HttpRequestConfig,
+    is_testnet: bool,
+    node_id: Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    if config.follow_redirects == Some(false) {
+        errors.push(
+            CompilerError::validate(
+                "N021",
+                "followRedirects: false is not supported — CRE HTTP requests always follow redirects",
+                node_id.clone(),
+            )
+            .with_path(config_path("followRedirects")),
+        );
+    }
+    if config.ignore_ssl == Some(true) {
+        let error = if is_testnet {
+            CompilerError::validate(
+                "W008",
+                "ignoreSSL has no effect — CRE always verifies TLS certificates, so a request to an endpoint without a valid certificate will fail",
+                node_id,
+            )
+        } else {
+            CompilerError::validate(
+                "N025",
+                "ignoreSSL: true is not allowed on mainnet — skipping certificate checks would let anyone on the network path forge the response; use an endpoint with a valid TLS certificate",
+                node_id,
+            )
+        };
+        errors.push(error.with_path(config_path("ignoreSSL")));
+    }
+}
+
+/// EVM write calldata must come from a single `{{node.field}}` reference
+/// (normally an ABI Encode output) or be a literal `0x` hex string.
+fn is_encoded_data_source(data: &str) -> bool {
+    let data = data.trim();
+    if let Some(inner) = data.strip_prefix("{{").and_then(|d| d.strip_suffix("}}")) {
+        return !inner.trim().is_empty() && !inner.contains("{{") && !inner.contains("}}");
+    }
+    data.strip_prefix("0x")
+        .is_some_and(|hex| hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// N033: an EVM read's `blockNumber` is empty (the default block), a tag
+/// from [`EVM_BLOCK_TAGS`] in any case, a decimal block number or a single
+/// `{{node.field}}` reference.
+fn block_number_problem(block: &str) -> Option<String> {
+    let block = block.trim();
+    let tag = block.to_ascii_lowercase();
+    if block.is_empty()
+        || EVM_BLOCK_TAGS.contains(&tag.as_str())
+        || block.parse::<i64>().is_ok_and(|n| n >= 0)
+    {
+        return None;
+    }
+    if let Some(inner) = block.strip_prefix("{{").and_then(|b| b.strip_suffix("}}"))
+        && !inner.trim().is_empty()
+        && !inner.contains("{{")
+        && !inner.contains("}}")
+    {
+        return None;
+    }
+    Some(match tag.as_str() {
+        "pending" | "safe" | "earliest" => format!(
+            "EVM read block '{}' is not supported: CRE reads at the latest or finalized block, or at a given block number",
+            block
+        ),
+        _ => format!(
+            "EVM read block '{}' is neither a tag ({}), a block number nor a single {{{{node.field}}}} reference",
+            block,
+            EVM_BLOCK_TAGS.join(", ")
+        ),
+    })
+}
+
 fn validate_http_auth_secrets(
     auth: &HttpAuthConfig,
     global: &GlobalConfig,
     node_id: &str,
     errors: &mut Vec<CompilerError>,
 ) {
+    let mut secret_path = config_path("authentication");
+    secret_path.push(JsonPathSegment::key("tokenSecret"));
     let secret_names: Vec<&str> = match auth {
         HttpAuthConfig::None => vec![],
         HttpAuthConfig::BearerToken { token_secret } => vec![token_secret.as_str()],
@@ -220,14 +663,454 @@ fn validate_http_auth_secrets(
 
     for name in secret_names {
         if !global.secrets.iter().any(|s| s.name == name) {
-            errors.push(CompilerError::validate(
-                "N004",
+            errors.push(
+                CompilerError::validate(
+                    "N004",
+                    format!(
+                        "HTTP auth references secret '{}' not declared in globalConfig.secrets",
+                        name
+                    ),
+                    Some(node_id.to_string()),
+                )
+                .with_path(secret_path.clone()),
+            );
+        }
+    }
+}
+
+/// N018: the provider must be one codegen knows how to call, and an empty
+/// `baseUrl` is only allowed when the provider has a default endpoint.
+fn validate_ai_provider(
+    config: &AiNodeConfig,
+    node_id: Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    let Some(provider) = AiProvider::parse(&config.provider) else {
+        errors.push(
+            CompilerError::validate(
+                "N018",
+                format!(
+                    "Unknown AI provider '{}' (supported: {})",
+                    config.provider,
+                    AiProvider::SUPPORTED.join(", ")
+                ),
+                node_id,
+            )
+            .with_path(config_path("provider")),
+        );
+        return;
+    };
+
+    let model = match config.model.trim() {
+        "" => provider.default_model().unwrap_or_default(),
+        model => model,
+    };
+    if config.base_url.trim().is_empty() && provider.default_base_url(model).is_none() {
+        errors.push(CompilerError::validate(
+            "N018",
+            format!(
+                "AI node must set a base URL: provider '{}' has no default endpoint for model '{}'",
+                config.provider, model
+            ),
+            node_id,
+        )
+        .with_path(config_path("baseUrl")));
+    }
+}
+
+/// N032: `custom` consensus needs a `consensusExpression`, and an expression
+/// is only used in `custom` mode, so setting it in another mode is a mistake.
+fn validate_ai_consensus(
+    config: &AiNodeConfig,
+    node_id: Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    let expression = config
+        .consensus_expression
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+    let message = match (config.consensus, expression) {
+        (AiConsensusMode::Custom, None) => {
+            "AI consensus 'custom' requires a consensusExpression".to_string()
+        }
+        (AiConsensusMode::Content | AiConsensusMode::Identical, Some(expression)) => format!(
+            "AI consensusExpression '{}' is only used when consensus is 'custom'",
+            expression
+        ),
+        _ => return,
+    };
+    errors.push(
+        CompilerError::validate("N032", message, node_id)
+            .with_path(config_path("consensusExpression")),
+    );
+}
+
+/// Largest `maxTokens` accepted; no provider returns more output per call.
+const AI_MAX_TOKENS_CAP: u32 = 128_000;
+
+/// N020: `temperature` must be within the provider's range (0–1 for
+/// Anthropic, 0–2 otherwise) and `maxTokens` within 1..=128000. Anthropic's
+/// messages API rejects requests without `max_tokens`, so it is required there.
+fn validate_ai_sampling(
+    config: &AiNodeConfig,
+    node_id: Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    let provider = AiProvider::parse(&config.provider);
+    let is_anthropic = provider == Some(AiProvider::Anthropic);
+
+    if let Some(temperature) = config.temperature {
+        let max = if is_anthropic { 1.0 } else { 2.0 };
+        if !(0.0..=max).contains(&temperature) {
+            errors.push(
+                CompilerError::validate(
+                    "N020",
+                    format!(
+                        "AI temperature {} is out of range: provider '{}' accepts 0 to {}",
+                        temperature, config.provider, max
+                    ),
+                    node_id.clone(),
+                )
+                .with_path(config_path("temperature")),
+            );
+        }
+    }
+
+    match config.max_tokens {
+        Some(max_tokens) if max_tokens == 0 || max_tokens > AI_MAX_TOKENS_CAP => {
+            errors.push(
+                CompilerError::validate(
+                    "N020",
+                    format!(
+                        "AI maxTokens {} is out of range: must be between 1 and {}",
+                        max_tokens, AI_MAX_TOKENS_CAP
+                    ),
+                    node_id,
+                )
+                .with_path(config_path("maxTokens")),
+            );
+        }
+        None if is_anthropic => {
+            errors.push(
+                CompilerError::validate(
+                    "N020",
+                    format!(
+                        "AI provider '{}' requires maxTokens to be set",
+                        config.provider
+                    ),
+                    node_id,
+                )
+                .with_path(config_path("maxTokens")),
+            );
+        }
+        _ => {}
+    }
+}
+
+/// A chain selector that is set but blank. Omitting it falls back to the
+/// workflow default; an empty one would lower to a nameless chain client.
+fn is_blank_chain(chain: &Option<String>) -> bool {
+    chain.as_deref().is_some_and(|c| c.trim().is_empty())
+}
+
+/// N031: a broadcast write names at least one chain, each once, and has a
+/// receiver on every chain. Gas limit and ABI mappings follow the EVM write
+/// (N006) and ABI encode (N009) rules.
+fn validate_broadcast_write(
+    config: &BroadcastWriteConfig,
+    node_id: &str,
+    errors: &mut Vec<CompilerError>,
+) {
+    let error = |code: &str, message: String, path: Vec<JsonPathSegment>| {
+        CompilerError::validate(code, message, Some(node_id.to_string())).with_path(path)
+    };
+    let chains = &config.chain_selector_names;
+    if chains.is_empty() {
+        errors.push(error(
+            "N031",
+            "Broadcast write must target at least one chain".into(),
+            config_path("chainSelectorNames"),
+        ));
+    }
+    for (i, chain) in chains.iter().enumerate() {
+        let chain = chain.trim();
+        let mut path = config_path("chainSelectorNames");
+        path.push(JsonPathSegment::Index(i));
+        if chain.is_empty() {
+            errors.push(error(
+                "N031",
+                "Broadcast write chain selector must not be empty".into(),
+                path,
+            ));
+        } else if chains[..i].iter().any(|c| c.trim() == chain) {
+            errors.push(error(
+                "N031",
+                format!("Broadcast write targets chain '{}' more than once", chain),
+                path,
+            ));
+        } else if config.receiver_for(chain).trim().is_empty() {
+            errors.push(error(
+                "N031",
                 format!(
-                    "HTTP auth references secret '{}' not declared in globalConfig.secrets",
+                    "Broadcast write has no receiver address for chain '{}' — set a shared receiver address or one for this chain",
+                    chain
+                ),
+                config_path("receiverAddress"),
+            ));
+        }
+    }
+    for chain in config.receiver_addresses.keys() {
+        if !chains.iter().any(|c| c.trim() == chain) {
+            let mut path = config_path("receiverAddresses");
+            path.push(JsonPathSegment::key(chain));
+            errors.push(error(
+                "N031",
+                format!(
+                    "Broadcast write has a receiver address for chain '{}', which it does not target",
+                    chain
+                ),
+                path,
+            ));
+        }
+    }
+
+    if let Ok(gas) = config.gas_limit.parse::<u64>()
+        && gas > 5_000_000
+    {
+        errors.push(error(
+            "N006",
+            "Broadcast write gas limit exceeds CRE maximum (5,000,000)".into(),
+            config_path("gasLimit"),
+        ));
+    }
+    if config.encode.abi_params.is_empty() {
+        errors.push(error(
+            "N009",
+            "Broadcast write must encode at least one ABI parameter".into(),
+            config_path("abiParams"),
+        ));
+    }
+    validate_abi_function_name(&config.encode, node_id, errors);
+    validate_abi_encode_mappings(&config.encode, node_id, errors);
+}
+
+/// N035: a `functionName` is written into `encodeFunctionData` as the name
+/// of a Solidity function, so it must be an identifier. Blank means
+/// parameter-only encoding.
+fn validate_abi_function_name(
+    config: &AbiEncodeConfig,
+    node_id: &str,
+    errors: &mut Vec<CompilerError>,
+) {
+    let Some(name) = config.function_name.as_deref().map(str::trim) else {
+        return;
+    };
+    if !name.is_empty() && !is_solidity_identifier(name) {
+        errors.push(
+            CompilerError::validate(
+                "N035",
+                format!(
+                    "ABI encode function name '{}' is not a Solidity identifier (letters, digits, '_' and '$', not starting with a digit)",
                     name
                 ),
                 Some(node_id.to_string()),
-            ));
+            )
+            .with_path(config_path("functionName")),
+        );
+    }
+}
+
+/// Every `dataMapping` entry must name an ABI parameter, and every ABI
+/// parameter must be mapped exactly once — otherwise the encoded args
+/// would contain `undefined` or be silently misaligned.
+fn validate_abi_encode_mappings(
+    config: &AbiEncodeConfig,
+    node_id: &str,
+    errors: &mut Vec<CompilerError>,
+) {
+    let mut mapped: Vec<&str> = Vec::new();
+    for (i, mapping) in config.data_mapping.iter().enumerate() {
+        let name = mapping.param_name.as_str();
+        let path = || indexed_config_path("dataMapping", i, "paramName");
+        if !config.abi_params.iter().any(|p| p.name == name) {
+            errors.push(
+                CompilerError::validate(
+                    "N009",
+                    format!(
+                        "ABI encode mapping '{}' does not match any ABI parameter",
+                        name
+                    ),
+                    Some(node_id.to_string()),
+                )
+                .with_path(path()),
+            );
+        } else if mapped.contains(&name) {
+            errors.push(
+                CompilerError::validate(
+                    "N009",
+                    format!("ABI parameter '{}' is mapped more than once", name),
+                    Some(node_id.to_string()),
+                )
+                .with_path(path()),
+            );
+        }
+        mapped.push(name);
+    }
+
+    for (i, param) in config.abi_params.iter().enumerate() {
+        if !mapped.contains(&param.name.as_str()) {
+            errors.push(
+                CompilerError::validate(
+                    "N009",
+                    format!("ABI parameter '{}' has no data mapping", param.name),
+                    Some(node_id.to_string()),
+                )
+                .with_path(indexed_config_path("abiParams", i, "name")),
+            );
+        }
+    }
+}
+
+/// Deepest nesting of condition groups in an `if` / `filter` node, counting
+/// the node's own list as level 1.
+const MAX_CONDITION_DEPTH: usize = 4;
+
+/// N022 / N023: a nested condition group must have at least one condition
+/// and groups may only nest `MAX_CONDITION_DEPTH` levels deep. Leaf patterns
+/// are checked by [`validate_regex_condition`]. `path` is the `conditions`
+/// list at nesting `depth`.
+fn validate_condition_groups(
+    conditions: &[ConditionNode],
+    path: Vec<JsonPathSegment>,
+    depth: usize,
+    node_id: &Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    for (i, condition) in conditions.iter().enumerate() {
+        let mut group_path = path.clone();
+        group_path.push(JsonPathSegment::Index(i));
+        let group = match condition {
+            ConditionNode::Group(group) => group,
+            ConditionNode::Leaf(leaf) => {
+                group_path.push(JsonPathSegment::key("value"));
+                validate_regex_condition(leaf, group_path, node_id, errors);
+                continue;
+            }
+        };
+        if depth == MAX_CONDITION_DEPTH {
+            errors.push(
+                CompilerError::validate(
+                    "N023",
+                    format!(
+                        "Condition groups can be nested at most {} levels deep",
+                        MAX_CONDITION_DEPTH
+                    ),
+                    node_id.clone(),
+                )
+                .with_path(group_path),
+            );
+            continue;
+        }
+        group_path.push(JsonPathSegment::key("conditions"));
+        if group.conditions.is_empty() {
+            errors.push(
+                CompilerError::validate(
+                    "N022",
+                    "Condition group must have at least one condition",
+                    node_id.clone(),
+                )
+                .with_path(group_path),
+            );
+            continue;
+        }
+        validate_condition_groups(&group.conditions, group_path, depth + 1, node_id, errors);
+    }
+}
+
+/// N034 / W020: the pattern of a `regex` / `notRegex` condition must compile
+/// as a JavaScript regular expression. A pattern with references is only
+/// known at run time, so it is flagged instead of checked.
+fn validate_regex_condition(
+    condition: &Condition,
+    value_path: Vec<JsonPathSegment>,
+    node_id: &Option<String>,
+    errors: &mut Vec<CompilerError>,
+) {
+    if !matches!(condition.operator.as_str(), "regex" | "notRegex") {
+        return;
+    }
+    let Some(pattern) = condition.value.as_deref().map(str::trim) else {
+        return;
+    };
+    if pattern.contains("{{") {
+        errors.push(
+            CompilerError::validate(
+                "W020",
+                "Regex pattern is built from references and can only be checked when the workflow runs",
+                node_id.clone(),
+            )
+            .with_path(value_path),
+        );
+    } else if let Err(problem) = check_pattern(pattern) {
+        errors.push(
+            CompilerError::validate(
+                "N034",
+                format!("Invalid regex pattern '{}': {}", pattern, problem),
+                node_id.clone(),
+            )
+            .with_path(value_path),
+        );
+    }
+}
+
+fn validate_math_config(config: &MathConfig, node_id: &str, errors: &mut Vec<CompilerError>) {
+    let node_id = Some(node_id.to_string());
+
+    if let Some(mode) = config.mode.as_deref()
+        && !["auto", "number", "bigint"].contains(&mode)
+    {
+        errors.push(
+            CompilerError::validate(
+                "N017",
+                format!(
+                    "Invalid math mode '{}' (expected auto, number or bigint)",
+                    mode
+                ),
+                node_id.clone(),
+            )
+            .with_path(config_path("mode")),
+        );
+    }
+
+    if config.expression.trim().is_empty() {
+        errors.push(
+            CompilerError::validate("N017", "Math expression must not be empty", node_id)
+                .with_path(config_path("expression")),
+        );
+        return;
+    }
+
+    // References only need to be syntactically valid here; binding scope is
+    // checked on the IR (E003).
+    match parse_compute_expr(&config.expression, &HashMap::new()) {
+        Ok(expr) => {
+            if divides_by_literal_zero(&expr) {
+                errors.push(
+                    CompilerError::validate(
+                        "N017",
+                        "Math expression divides by a literal zero",
+                        node_id,
+                    )
+                    .with_path(config_path("expression")),
+                );
+            }
+        }
+        Err(e) => {
+            errors.push(
+                CompilerError::validate("N017", format!("Invalid math expression: {}", e), node_id)
+                    .with_path(config_path("expression")),
+            );
         }
     }
 }
@@ -0,0 +1,451 @@
+//! Organization policy checks against a lowered workflow (R001–R005).
+//!
+//! A [`PolicyRuleset`] is supplied by whoever embeds the compiler, usually as
+//! JSON, and narrows what a workflow may do beyond the CRE limits: which hosts
+//! it may call, which chains it may touch, which operations and AI providers
+//! it may use, and caps on gas, tokens and capability calls. [`apply_policy`]
+//! reports every rule a workflow breaks, naming the rule in the message.
+
+use serde::Deserialize;
+
+use crate::ir::report::capability_report;
+use crate::ir::types::*;
+use crate::ir::validate::{ValidationError, visit_steps};
+
+/// Rules a workflow must follow. Every rule is optional; an empty ruleset
+/// accepts everything. An `allow*` list, when present, is exhaustive.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PolicyRuleset {
+    pub http: HttpPolicy,
+    pub evm: EvmPolicy,
+    pub operations: OperationPolicy,
+    pub ai: AiPolicy,
+    pub budgets: BudgetPolicy,
+}
+
+/// Hosts of HTTP requests and AI calls (R001). An entry is a host name
+/// (`api.example.com`) or a wildcard for its subdomains (`*.example.com`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpPolicy {
+    pub allow_hosts: Option<Vec<String>>,
+    pub deny_hosts: Vec<String>,
+}
+
+/// Chains by selector name, e.g. `ethereum-mainnet` (R002), and the gas cap
+/// of EVM writes (R005).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EvmPolicy {
+    pub allow_chains: Option<Vec<String>>,
+    pub deny_chains: Vec<String>,
+    /// Reject EVM writes to any chain whose selector name does not contain
+    /// `testnet`.
+    pub deny_mainnet_writes: bool,
+    pub max_gas_limit: Option<u64>,
+}
+
+/// Operation kinds as in the IR's `type` tag, e.g. `EvmWrite` (R003).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OperationPolicy {
+    pub allow: Option<Vec<String>>,
+    pub deny: Vec<String>,
+}
+
+/// AI providers by any name `AiProvider::parse` accepts (R004), and the cap
+/// on `maxTokens` (R005).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AiPolicy {
+    pub allow_providers: Option<Vec<String>>,
+    pub deny_providers: Vec<String>,
+    pub max_tokens: Option<u32>,
+}
+
+/// Capability calls on the worst execution path, as counted by
+/// `capability_report` (R005).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BudgetPolicy {
+    pub max_http_calls: Option<usize>,
+    pub max_evm_reads: Option<usize>,
+    pub max_evm_writes: Option<usize>,
+    pub max_kv_operations: Option<usize>,
+}
+
+/// Check `ir` against `ruleset`. A rule that cannot be decided from the IR,
+/// such as a host allowlist on a URL whose host comes from config, fails.
+pub fn apply_policy(ir: &WorkflowIR, ruleset: &PolicyRuleset) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    if let TriggerDef::EvmLog(trigger) = &ir.trigger
+        && let Some(chain) = chain_name(ir, &trigger.evm_client_binding)
+    {
+        check_chain(
+            &ruleset.evm,
+            chain,
+            "the trigger listens on",
+            None,
+            &mut errors,
+        );
+    }
+
+    visit_steps(&ir.handler_body, &mut |step| {
+        check_step(ir, ruleset, step, &mut errors);
+    });
+
+    let report = capability_report(ir);
+    let budgets = [
+        (
+            "budgets.maxHttpCalls",
+            ruleset.budgets.max_http_calls,
+            report.http_calls.used,
+            "HTTP calls",
+        ),
+        (
+            "budgets.maxEvmReads",
+            ruleset.budgets.max_evm_reads,
+            report.evm_reads.used,
+            "EVM reads",
+        ),
+        (
+            "budgets.maxEvmWrites",
+            ruleset.budgets.max_evm_writes,
+            report.evm_writes.used,
+            "EVM writes",
+        ),
+        (
+            "budgets.maxKvOperations",
+            ruleset.budgets.max_kv_operations,
+            report.kv_operations.used,
+            "KV operations",
+        ),
+    ];
+    for (rule, cap, used, what) in budgets {
+        if let Some(cap) = cap
+            && used > cap
+        {
+            errors.push(error(
+                "R005",
+                rule,
+                format!(
+                    "the workflow makes up to {} {}, over the cap of {}",
+                    used, what, cap
+                ),
+                None,
+            ));
+        }
+    }
+
+    errors
+}
+
+fn check_step(
+    ir: &WorkflowIR,
+    ruleset: &PolicyRuleset,
+    step: &Step,
+    errors: &mut Vec<ValidationError>,
+) {
+    let step_id = Some(step.id.as_str());
+    let kind = step.operation.kind();
+    if let Some(allow) = &ruleset.operations.allow
+        && !allow.iter().any(|k| k == kind)
+    {
+        errors.push(error(
+            "R003",
+            "operations.allow",
+            format!(
+                "step '{}' is a {} operation, which is not allowed",
+                step.id, kind
+            ),
+            step_id,
+        ));
+    }
+    if ruleset.operations.deny.iter().any(|k| k == kind) {
+        errors.push(error(
+            "R003",
+            "operations.deny",
+            format!(
+                "step '{}' is a {} operation, which is denied",
+                step.id, kind
+            ),
+            step_id,
+        ));
+    }
+
+    match &step.operation {
+        Operation::HttpRequest(op) => check_host(&ruleset.http, step, &op.url, errors),
+        Operation::AiCall(op) => {
+            check_host(&ruleset.http, step, &op.base_url, errors);
+            check_provider(&ruleset.ai, step, &op.provider, errors);
+            if let Some(cap) = ruleset.ai.max_tokens {
+                match op.max_tokens {
+                    Some(max_tokens) if max_tokens <= cap => {}
+                    Some(max_tokens) => errors.push(error(
+                        "R005",
+                        "ai.maxTokens",
+                        format!(
+                            "step '{}' allows {} tokens, over the cap of {}",
+                            step.id, max_tokens, cap
+                        ),
+                        step_id,
+                    )),
+                    None => errors.push(error(
+                        "R005",
+                        "ai.maxTokens",
+                        format!(
+                            "step '{}' sets no maxTokens, so the cap of {} cannot be enforced",
+                            step.id, cap
+                        ),
+                        step_id,
+                    )),
+                }
+            }
+        }
+        Operation::EvmRead(op) => {
+            if let Some(chain) = chain_name(ir, &op.evm_client_binding) {
+                let subject = format!("step '{}' reads from", step.id);
+                check_chain(&ruleset.evm, chain, &subject, step_id, errors);
+            }
+        }
+        Operation::EvmWrite(op) => {
+            if let Some(chain) = chain_name(ir, &op.evm_client_binding) {
+                let subject = format!("step '{}' writes to", step.id);
+                check_chain(&ruleset.evm, chain, &subject, step_id, errors);
+                if ruleset.evm.deny_mainnet_writes && !chain.contains("testnet") {
+                    errors.push(error(
+                        "R002",
+                        "evm.denyMainnetWrites",
+                        format!("step '{}' writes to mainnet chain '{}'", step.id, chain),
+                        step_id,
+                    ));
+                }
+            }
+            if let Some(cap) = ruleset.evm.max_gas_limit {
+                match literal_integer(&op.gas_limit) {
+                    Some(gas) if gas <= cap => {}
+                    Some(gas) => errors.push(error(
+                        "R005",
+                        "evm.maxGasLimit",
+                        format!(
+                            "step '{}' has a gas limit of {}, over the cap of {}",
+                            step.id, gas, cap
+                        ),
+                        step_id,
+                    )),
+                    None => errors.push(error(
+                        "R005",
+                        "evm.maxGasLimit",
+                        format!(
+                            "step '{}' has a gas limit that is not a literal, so the cap of {} cannot be enforced",
+                            step.id, cap
+                        ),
+                        step_id,
+                    )),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `subject` starts the message, e.g. "step 'x' writes to".
+fn check_chain(
+    policy: &EvmPolicy,
+    chain: &str,
+    subject: &str,
+    step_id: Option<&str>,
+    errors: &mut Vec<ValidationError>,
+) {
+    if let Some(allow) = &policy.allow_chains
+        && !allow.iter().any(|c| c == chain)
+    {
+        errors.push(error(
+            "R002",
+            "evm.allowChains",
+            format!("{} chain '{}', which is not allowed", subject, chain),
+            step_id,
+        ));
+    }
+    if policy.deny_chains.iter().any(|c| c == chain) {
+        errors.push(error(
+            "R002",
+            "evm.denyChains",
+            format!("{} chain '{}', which is denied", subject, chain),
+            step_id,
+        ));
+    }
+}
+
+fn check_host(
+    policy: &HttpPolicy,
+    step: &Step,
+    url: &ValueExpr,
+    errors: &mut Vec<ValidationError>,
+) {
+    if policy.allow_hosts.is_none() && policy.deny_hosts.is_empty() {
+        return;
+    }
+    let step_id = Some(step.id.as_str());
+    let Some(host) = url_host(url) else {
+        if policy.allow_hosts.is_some() {
+            errors.push(error(
+                "R001",
+                "http.allowHosts",
+                format!(
+                    "step '{}' calls a URL whose host is not known before the workflow runs",
+                    step.id
+                ),
+                step_id,
+            ));
+        }
+        return;
+    };
+    if let Some(allow) = &policy.allow_hosts
+        && !allow.iter().any(|pattern| host_matches(pattern, &host))
+    {
+        errors.push(error(
+            "R001",
+            "http.allowHosts",
+            format!(
+                "step '{}' calls host '{}', which is not allowed",
+                step.id, host
+            ),
+            step_id,
+        ));
+    }
+    if policy
+        .deny_hosts
+        .iter()
+        .any(|pattern| host_matches(pattern, &host))
+    {
+        errors.push(error(
+            "R001",
+            "http.denyHosts",
+            format!("step '{}' calls host '{}', which is denied", step.id, host),
+            step_id,
+        ));
+    }
+}
+
+fn check_provider(
+    policy: &AiPolicy,
+    step: &Step,
+    provider: &AiProvider,
+    errors: &mut Vec<ValidationError>,
+) {
+    let step_id = Some(step.id.as_str());
+    let listed = |names: &[String]| {
+        names.iter().any(|name| {
+            AiProvider::parse(name)
+                .is_some_and(|p| std::mem::discriminant(&p) == std::mem::discriminant(provider))
+        })
+    };
+    if let Some(allow) = &policy.allow_providers
+        && !listed(allow)
+    {
+        errors.push(error(
+            "R004",
+            "ai.allowProviders",
+            format!(
+                "step '{}' uses AI provider '{}', which is not allowed",
+                step.id,
+                provider_name(provider)
+            ),
+            step_id,
+        ));
+    }
+    if listed(&policy.deny_providers) {
+        errors.push(error(
+            "R004",
+            "ai.denyProviders",
+            format!(
+                "step '{}' uses AI provider '{}', which is denied",
+                step.id,
+                provider_name(provider)
+            ),
+            step_id,
+        ));
+    }
+}
+
+fn chain_name<'a>(ir: &'a WorkflowIR, binding: &str) -> Option<&'a str> {
+    ir.evm_chains
+        .iter()
+        .find(|c| c.binding_name == binding)
+        .map(|c| c.chain_selector_name.as_str())
+}
+
+/// The lowercase host of a URL, read from its literal prefix: the whole URL
+/// when it is a literal, or the text before the first template expression.
+/// `None` when that prefix does not reach the end of the host.
+fn url_host(url: &ValueExpr) -> Option<String> {
+    let (prefix, complete) = match url {
+        ValueExpr::Literal(LiteralValue::String { value }) => (value.clone(), true),
+        ValueExpr::Template { parts } => {
+            let mut prefix = String::new();
+            let mut complete = true;
+            for part in parts {
+                match part {
+                    TemplatePart::Lit { value } => prefix.push_str(value),
+                    TemplatePart::Expr { .. } => {
+                        complete = false;
+                        break;
+                    }
+                }
+            }
+            (prefix, complete)
+        }
+        _ => return None,
+    };
+    let (_, rest) = prefix.split_once("://")?;
+    let authority = match rest.find(['/', '?', '#']) {
+        Some(end) => &rest[..end],
+        None if complete => rest,
+        None => return None,
+    };
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = host.split(':').next().unwrap_or(host);
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// `pattern` is a host name or `*.` followed by a domain, which matches
+/// that domain's subdomains.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.')),
+        None => host == pattern,
+    }
+}
+
+fn literal_integer(value: &ValueExpr) -> Option<u64> {
+    match value {
+        ValueExpr::Literal(LiteralValue::Integer { value }) => u64::try_from(*value).ok(),
+        ValueExpr::Literal(LiteralValue::String { value }) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Canonical name of a provider, as in `AiProvider::SUPPORTED`.
+fn provider_name(provider: &AiProvider) -> &'static str {
+    match provider {
+        AiProvider::OpenAi => "openai",
+        AiProvider::Anthropic => "anthropic",
+        AiProvider::Google => "google",
+        AiProvider::OpenAiCompatible { .. } => "openai-compatible",
+    }
+}
+
+fn error(code: &'static str, rule: &str, detail: String, step_id: Option<&str>) -> ValidationError {
+    ValidationError {
+        code,
+        message: format!("Policy rule '{}': {}", rule, detail),
+        step_id: step_id.map(str::to_string),
+        related: vec![],
+    }
+}
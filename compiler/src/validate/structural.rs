@@ -1,14 +1,18 @@
-//! Graph-level structural validation rules (V001–V010).
+//! Graph-level structural validation rules (V001–V014).
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use petgraph::algo::is_cyclic_directed;
 use petgraph::visit::Bfs;
 
-use crate::error::CompilerError;
+use crate::error::{CompilerError, JsonPathSegment};
+use crate::lower::expand::EXPANDED_STEP_SEPARATOR;
 use crate::parse::graph::WorkflowGraph;
 use crate::parse::types::{Workflow, WorkflowNode};
 
+/// Distinct keys a workflow may read or write with kvGet/kvSet nodes (V013).
+pub const MAX_KV_KEYS: usize = 16;
+
 /// Run all structural validation rules. Returns all errors found.
 pub fn validate_structural(workflow: &Workflow, graph: &WorkflowGraph) -> Vec<CompilerError> {
     let mut errors = Vec::new();
@@ -18,10 +22,14 @@ pub fn validate_structural(workflow: &Workflow, graph: &WorkflowGraph) -> Vec<Co
     v003_no_duplicate_edges(workflow, &mut errors);
     v004_no_cycles(graph, &mut errors);
     v005_all_reachable_from_trigger(workflow, graph, &mut errors);
-    v006_trigger_no_incoming(workflow, graph, &mut errors);
+    v006_trigger_no_incoming(workflow, &mut errors);
     v008_if_has_two_outgoing(workflow, graph, &mut errors);
     v009_merge_has_multiple_incoming(workflow, graph, &mut errors);
     v010_no_self_loops(workflow, graph, &mut errors);
+    v011_source_handles_exist(workflow, &mut errors);
+    v012_if_handles_have_single_target(workflow, &mut errors);
+    v013_kv_keys_within_cap(workflow, &mut errors);
+    v014_node_ids_have_no_step_separator(workflow, &mut errors);
 
     errors
 }
@@ -126,28 +134,50 @@ fn v005_all_reachable_from_trigger(
         let Some(&idx) = graph.node_indices.get(node.id()) else {
             continue;
         };
-        if !reachable.contains(&idx) {
-            errors.push(CompilerError::validate(
-                "V005",
-                format!("Node '{}' is not reachable from the trigger", node.id()),
-                Some(node.id().to_string()),
-            ));
+        if reachable.contains(&idx) {
+            continue;
         }
+        // A second root: the trigger must be the only node without inputs.
+        let message = if graph.incoming_count(node.id()) == 0 {
+            format!(
+                "Node '{}' has no incoming edges; only the trigger '{}' may start the workflow",
+                node.id(),
+                trigger.id()
+            )
+        } else {
+            format!("Node '{}' is not reachable from the trigger", node.id())
+        };
+        errors.push(CompilerError::validate(
+            "V005",
+            message,
+            Some(node.id().to_string()),
+        ));
     }
 }
 
-fn v006_trigger_no_incoming(
-    workflow: &Workflow,
-    graph: &WorkflowGraph,
-    errors: &mut Vec<CompilerError>,
-) {
-    for node in &workflow.nodes {
-        if node.is_trigger() && graph.incoming_count(node.id()) > 0 {
-            errors.push(CompilerError::validate(
-                "V006",
-                format!("Trigger node '{}' must not have incoming edges", node.id()),
-                Some(node.id().to_string()),
-            ));
+/// One error per edge into a trigger, at `edges[i].target`.
+fn v006_trigger_no_incoming(workflow: &Workflow, errors: &mut Vec<CompilerError>) {
+    for (i, edge) in workflow.edges.iter().enumerate() {
+        let targets_trigger = workflow
+            .nodes
+            .iter()
+            .any(|n| n.is_trigger() && n.id() == edge.target);
+        if targets_trigger {
+            errors.push(
+                CompilerError::validate(
+                    "V006",
+                    format!(
+                        "Edge '{}' from '{}' targets trigger node '{}'; a trigger must not have incoming edges",
+                        edge.id, edge.source, edge.target
+                    ),
+                    Some(edge.target.clone()),
+                )
+                .with_path(vec![
+                    JsonPathSegment::key("edges"),
+                    JsonPathSegment::Index(i),
+                    JsonPathSegment::key("target"),
+                ]),
+            );
         }
     }
 }
@@ -227,3 +257,133 @@ fn v010_no_self_loops(workflow: &Workflow, graph: &WorkflowGraph, errors: &mut V
     // Also check petgraph for any self-loops
     let _ = graph; // Already checked via edge iteration above
 }
+
+fn v011_source_handles_exist(workflow: &Workflow, errors: &mut Vec<CompilerError>) {
+    let nodes: HashMap<&str, &WorkflowNode> = workflow.nodes.iter().map(|n| (n.id(), n)).collect();
+
+    for edge in &workflow.edges {
+        let (Some(node), Some(handle)) = (nodes.get(edge.source.as_str()), &edge.source_handle)
+        else {
+            continue;
+        };
+        let handles = node.output_handles();
+        if !handles.contains(&handle.as_str()) {
+            let expected = if handles.is_empty() {
+                "it has no outputs".to_string()
+            } else {
+                format!("expected one of: {}", handles.join(", "))
+            };
+            errors.push(CompilerError::validate(
+                "V011",
+                format!(
+                    "Edge '{}' leaves '{}' from handle '{}', which a {} node does not have ({})",
+                    edge.id,
+                    edge.source,
+                    handle,
+                    node.node_type(),
+                    expected
+                ),
+                Some(edge.source.clone()),
+            ));
+        }
+    }
+}
+
+fn v012_if_handles_have_single_target(workflow: &Workflow, errors: &mut Vec<CompilerError>) {
+    let if_ids: HashSet<&str> = workflow
+        .nodes
+        .iter()
+        .filter(|n| matches!(n, WorkflowNode::If(_)))
+        .map(|n| n.id())
+        .collect();
+
+    // (if node, handle) -> distinct targets, in edge order
+    let mut targets: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for edge in &workflow.edges {
+        let Some(handle) = edge.source_handle.as_deref() else {
+            continue;
+        };
+        if !if_ids.contains(edge.source.as_str()) {
+            continue;
+        }
+        let entry = targets.entry((edge.source.as_str(), handle)).or_default();
+        if !entry.contains(&edge.target.as_str()) {
+            entry.push(edge.target.as_str());
+        }
+    }
+
+    let mut conflicts: Vec<_> = targets.into_iter().filter(|(_, t)| t.len() > 1).collect();
+    conflicts.sort();
+    for ((node_id, handle), node_targets) in conflicts {
+        errors.push(CompilerError::validate(
+            "V012",
+            format!(
+                "If node '{}' has multiple edges from its '{}' handle (to {}) — each branch must lead to exactly one node",
+                node_id,
+                handle,
+                node_targets
+                    .iter()
+                    .map(|t| format!("'{}'", t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Some(node_id.to_string()),
+        ));
+    }
+}
+
+/// Keys are compared as written, so a templated key (`price-{{config.asset}}`)
+/// counts once however many keys it expands to at run time.
+fn v013_kv_keys_within_cap(workflow: &Workflow, errors: &mut Vec<CompilerError>) {
+    // distinct key -> first node using it, in node order
+    let mut keys: Vec<(&str, &str)> = Vec::new();
+    for node in &workflow.nodes {
+        let key = match node {
+            WorkflowNode::KvGet(n) => n.data.config.key.trim(),
+            WorkflowNode::KvSet(n) => n.data.config.key.trim(),
+            _ => continue,
+        };
+        if !key.is_empty() && !keys.iter().any(|(k, _)| *k == key) {
+            keys.push((key, node.id()));
+        }
+    }
+
+    if let Some((key, node_id)) = keys.get(MAX_KV_KEYS) {
+        errors.push(CompilerError::validate(
+            "V013",
+            format!(
+                "Workflow uses {} distinct KV keys, exceeding the limit of {} (first key over the limit: '{}')",
+                keys.len(),
+                MAX_KV_KEYS,
+                key
+            ),
+            Some(node_id.to_string()),
+        ));
+    }
+}
+
+/// Lowering names the steps a node expands to `<node id>___<part>` and maps
+/// step ids back to nodes at the first separator, so a node id containing
+/// one would be attributed to the wrong node (or collide with a step).
+fn v014_node_ids_have_no_step_separator(workflow: &Workflow, errors: &mut Vec<CompilerError>) {
+    for (i, node) in workflow.nodes.iter().enumerate() {
+        if node.id().contains(EXPANDED_STEP_SEPARATOR) {
+            errors.push(
+                CompilerError::validate(
+                    "V014",
+                    format!(
+                        "Node id '{}' contains '{}', which is reserved for the steps a node expands to",
+                        node.id(),
+                        EXPANDED_STEP_SEPARATOR
+                    ),
+                    Some(node.id().to_string()),
+                )
+                .with_path(vec![
+                    JsonPathSegment::key("nodes"),
+                    JsonPathSegment::Index(i),
+                    JsonPathSegment::key("id"),
+                ]),
+            );
+        }
+    }
+}
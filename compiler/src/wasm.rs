@@ -2,11 +2,17 @@
 
 use wasm_bindgen::prelude::*;
 
-use crate::codegen;
-use crate::error::CompilerError;
+use crate::cancel::CancellationToken;
+use crate::codegen::CodegenOptions;
+use crate::compile::{Compiled, compile_with_policy, lower_json};
+use crate::error::{CompilerError, RelatedDiagnostic, Severity, format_json_path};
+use crate::ir::CapabilityReport;
+use crate::ir::analysis::StepDepGraph;
+use crate::validate::policy::PolicyRuleset;
 
 /// Validate a workflow JSON: parse + graph validation.
-/// Returns a JSON array of CompilerError objects.
+/// Returns a JSON array of CompilerError objects, led by the I001 notes of
+/// any schema migration.
 #[wasm_bindgen]
 pub fn validate_workflow(json: &str) -> JsValue {
     let result = validate_workflow_inner(json);
@@ -14,8 +20,8 @@ pub fn validate_workflow(json: &str) -> JsValue {
 }
 
 fn validate_workflow_inner(json: &str) -> Vec<ErrorDto> {
-    let workflow = match crate::parse::parse(json) {
-        Ok(w) => w,
+    let (workflow, migrations) = match crate::parse::parse_with_migrations(json) {
+        Ok(parsed) => parsed,
         Err(errors) => return errors.into_iter().map(ErrorDto::from).collect(),
     };
 
@@ -24,7 +30,9 @@ fn validate_workflow_inner(json: &str) -> Vec<ErrorDto> {
         Err(errors) => return errors.into_iter().map(ErrorDto::from).collect(),
     };
 
-    let errors = crate::validate::validate_graph(&workflow, &graph);
+    let mut errors = migrations;
+    errors.extend(crate::validate::validate_graph(&workflow, &graph));
+    errors.extend(graph.validate_single_entry(&workflow));
     errors.into_iter().map(ErrorDto::from).collect()
 }
 
@@ -42,9 +50,12 @@ fn validate_node_inner(node_json: &str, global_config_json: &str) -> Vec<ErrorDt
         Err(e) => {
             return vec![ErrorDto {
                 code: "P001".into(),
+                severity: Severity::Error,
                 phase: "Parse".into(),
                 message: format!("Failed to parse node JSON: {}", e),
                 node_id: None,
+                related: vec![],
+                path: None,
             }];
         }
     };
@@ -55,9 +66,12 @@ fn validate_node_inner(node_json: &str, global_config_json: &str) -> Vec<ErrorDt
         Err(e) => {
             return vec![ErrorDto {
                 code: "P001".into(),
+                severity: Severity::Error,
                 phase: "Parse".into(),
                 message: format!("Failed to parse global config JSON: {}", e),
                 node_id: None,
+                related: vec![],
+                path: None,
             }];
         }
     };
@@ -66,75 +80,156 @@ fn validate_node_inner(node_json: &str, global_config_json: &str) -> Vec<ErrorDt
     errors.into_iter().map(ErrorDto::from).collect()
 }
 
+/// Check a config document (e.g. `config.staging.json`) against the config
+/// schema of a workflow JSON: parse → validate → lower, then
+/// `validate::config::check_config_values`. Returns a JSON array of
+/// CompilerError objects; lowering errors are returned as they are.
+#[wasm_bindgen]
+pub fn check_config_values(json: &str, config_json: &str) -> JsValue {
+    let result = check_config_values_inner(json, config_json);
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
+
+fn check_config_values_inner(json: &str, config_json: &str) -> Vec<ErrorDto> {
+    match lower_json(json, None) {
+        Ok((_, ir, _)) => crate::validate::config::check_config_values(&ir, config_json)
+            .into_iter()
+            .map(|e| ErrorDto::from(CompilerError::from(e)))
+            .collect(),
+        Err(errors) => errors.into_iter().map(ErrorDto::from).collect(),
+    }
+}
+
 /// Full pipeline: parse → validate → lower → IR validate → codegen.
-/// Returns a JSON object with either `files` (success) or `errors` (failure).
+/// Returns a JSON object with either `files` + `warnings` (success) or `errors` (failure).
 #[wasm_bindgen]
 pub fn compile_workflow(json: &str) -> JsValue {
-    let result = compile_workflow_inner(json);
+    let result = compile_workflow_inner(json, None, None);
     serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
 }
 
-fn compile_workflow_inner(json: &str) -> CompileResult {
-    // 1. Parse
-    let workflow = match crate::parse::parse(json) {
-        Ok(w) => w,
-        Err(errors) => {
-            return CompileResult::Errors {
-                errors: errors.into_iter().map(ErrorDto::from).collect(),
-            };
-        }
-    };
+/// [`compile_workflow`] that gives up with an X001 error after `steps`
+/// cancellation checks (roughly one per node, IR invariant, generated step
+/// and file), so pathological inputs cannot stall the page.
+#[wasm_bindgen]
+pub fn compile_workflow_with_budget(json: &str, steps: u32) -> JsValue {
+    let cancel = CancellationToken::with_step_budget(steps as usize);
+    let result = compile_workflow_inner(json, Some(cancel), None);
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
 
-    // 2. Build graph
-    let graph = match crate::parse::WorkflowGraph::build(&workflow) {
-        Ok(g) => g,
-        Err(errors) => {
-            return CompileResult::Errors {
-                errors: errors.into_iter().map(ErrorDto::from).collect(),
-            };
-        }
+/// [`compile_workflow`] checked against an organization policy: a JSON
+/// `PolicyRuleset`. Without one it compiles as [`compile_workflow`] does; a
+/// ruleset that does not parse is reported as P001.
+#[wasm_bindgen]
+pub fn compile_workflow_with_policy(json: &str, policy_json: Option<String>) -> JsValue {
+    let result = match policy_json
+        .as_deref()
+        .map(serde_json::from_str::<PolicyRuleset>)
+    {
+        None => compile_workflow_inner(json, None, None),
+        Some(Ok(policy)) => compile_workflow_inner(json, None, Some(&policy)),
+        Some(Err(e)) => CompileResult::Errors {
+            errors: vec![ErrorDto {
+                code: "P001".into(),
+                severity: Severity::Error,
+                phase: "Parse".into(),
+                message: format!("Failed to parse policy JSON: {}", e),
+                node_id: None,
+                related: vec![],
+                path: None,
+            }],
+        },
     };
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
+
+fn compile_workflow_inner(
+    json: &str,
+    cancel: Option<CancellationToken>,
+    policy: Option<&PolicyRuleset>,
+) -> CompileResult {
+    compile_result(compile_with_policy(
+        json,
+        cancel,
+        &CodegenOptions::default(),
+        policy,
+    ))
+}
+
+/// Compile `target_node_id` and its upstream chain for "test this node".
+/// Capability calls before the target return the values in `sample_inputs`,
+/// a JSON object keyed by node id. Returns the same shape as
+/// [`compile_workflow`].
+#[wasm_bindgen]
+pub fn compile_subgraph(json: &str, target_node_id: &str, sample_inputs: &str) -> JsValue {
+    let result = compile_result(crate::subgraph::compile_subgraph(
+        json,
+        target_node_id,
+        sample_inputs,
+    ));
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
 
-    // 3. Graph validation
-    let validation_errors = crate::validate::validate_graph(&workflow, &graph);
-    if !validation_errors.is_empty() {
-        return CompileResult::Errors {
-            errors: validation_errors.into_iter().map(ErrorDto::from).collect(),
-        };
+fn compile_result(result: Result<Compiled, Vec<CompilerError>>) -> CompileResult {
+    match result {
+        Ok(compiled) => CompileResult::Success {
+            files: compiled
+                .output
+                .files
+                .into_iter()
+                .map(|f| FileDto {
+                    path: f.path,
+                    content: f.content.to_string(),
+                })
+                .collect(),
+            warnings: compiled.warnings.into_iter().map(ErrorDto::from).collect(),
+        },
+        Err(errors) => CompileResult::Errors {
+            errors: errors.into_iter().map(ErrorDto::from).collect(),
+        },
     }
+}
 
-    // 4. Lower to IR
-    let ir = match crate::lower::lower(&workflow, &graph) {
-        Ok(ir) => ir,
-        Err(errors) => {
-            return CompileResult::Errors {
-                errors: errors.into_iter().map(ErrorDto::from).collect(),
-            };
-        }
-    };
+/// Capability usage of a workflow: parse → validate → lower, then count.
+/// Returns a JSON object with either `report` + `warnings` or `errors`.
+/// Budget overruns (E009–E011) are not errors here; `used` exceeds `limit`.
+#[wasm_bindgen]
+pub fn capability_report(json: &str) -> JsValue {
+    let result = capability_report_inner(json);
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
 
-    // 5. IR validation
-    let ir_errors = crate::ir::validate_ir(&ir);
-    if !ir_errors.is_empty() {
-        let errors: Vec<ErrorDto> = ir_errors
-            .into_iter()
-            .map(|e| ErrorDto::from(CompilerError::from(e)))
-            .collect();
-        return CompileResult::Errors { errors };
+fn capability_report_inner(json: &str) -> ReportResult {
+    match lower_json(json, None) {
+        Ok((_, ir, warnings)) => ReportResult::Success {
+            report: crate::ir::capability_report(&ir),
+            warnings: warnings.into_iter().map(ErrorDto::from).collect(),
+        },
+        Err(errors) => ReportResult::Errors {
+            errors: errors.into_iter().map(ErrorDto::from).collect(),
+        },
     }
+}
 
-    // 6. Codegen
-    let output = codegen::codegen(&ir);
+/// Step dependencies of a workflow: parse → validate → lower, then follow
+/// every binding. Returns a JSON object with either `graph` + `warnings` or
+/// `errors`; the editor maps `source_node_ids` back to canvas nodes.
+#[wasm_bindgen]
+pub fn dependency_graph(json: &str) -> JsValue {
+    let result = dependency_graph_inner(json);
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
 
-    CompileResult::Success {
-        files: output
-            .files
-            .into_iter()
-            .map(|f| FileDto {
-                path: f.path,
-                content: f.content,
-            })
-            .collect(),
+fn dependency_graph_inner(json: &str) -> DependencyGraphResult {
+    match lower_json(json, None) {
+        Ok((_, ir, warnings)) => DependencyGraphResult::Success {
+            graph: crate::ir::analysis::dependency_graph(&ir),
+            warnings: warnings.into_iter().map(ErrorDto::from).collect(),
+        },
+        Err(errors) => DependencyGraphResult::Errors {
+            errors: errors.into_iter().map(ErrorDto::from).collect(),
+        },
     }
 }
 
@@ -145,18 +240,25 @@ fn compile_workflow_inner(json: &str) -> CompileResult {
 #[derive(serde::Serialize, serde::Deserialize)]
 struct ErrorDto {
     code: String,
+    severity: Severity,
     phase: String,
     message: String,
     node_id: Option<String>,
+    related: Vec<RelatedDiagnostic>,
+    /// e.g. `nodes[2].data.config.url`; relative to the node for `validate_node`.
+    path: Option<String>,
 }
 
 impl From<CompilerError> for ErrorDto {
     fn from(e: CompilerError) -> Self {
         ErrorDto {
             code: e.code,
+            severity: e.severity,
             phase: e.phase.to_string(),
             message: e.message,
             node_id: e.node_id,
+            related: e.related,
+            path: (!e.path.is_empty()).then(|| format_json_path(&e.path)),
         }
     }
 }
@@ -171,7 +273,34 @@ struct FileDto {
 #[serde(tag = "status")]
 enum CompileResult {
     #[serde(rename = "success")]
-    Success { files: Vec<FileDto> },
+    Success {
+        files: Vec<FileDto>,
+        warnings: Vec<ErrorDto>,
+    },
+    #[serde(rename = "errors")]
+    Errors { errors: Vec<ErrorDto> },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "status")]
+enum ReportResult {
+    #[serde(rename = "success")]
+    Success {
+        report: CapabilityReport,
+        warnings: Vec<ErrorDto>,
+    },
+    #[serde(rename = "errors")]
+    Errors { errors: Vec<ErrorDto> },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "status")]
+enum DependencyGraphResult {
+    #[serde(rename = "success")]
+    Success {
+        graph: StepDepGraph,
+        warnings: Vec<ErrorDto>,
+    },
     #[serde(rename = "errors")]
     Errors { errors: Vec<ErrorDto> },
 }
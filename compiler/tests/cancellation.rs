@@ -0,0 +1,81 @@
+//! Cooperative cancellation: every phase stops with X001 once its token fires.
+
+use std::time::{Duration, Instant};
+
+use compiler::cancel::CancellationToken;
+use compiler::codegen::{self, CodegenOptions};
+use compiler::compile::compile;
+use compiler::error::{CompilerError, Phase};
+use compiler::ir::{WorkflowIR, validate_ir_cancellable};
+use compiler::lower::{self, LowerOptions};
+use compiler::parse;
+
+const KYC: &str = include_str!("fixtures/example_workflow.json");
+
+fn cancelled() -> CancellationToken {
+    let token = CancellationToken::new();
+    token.cancel();
+    token
+}
+
+fn kyc_ir() -> WorkflowIR {
+    let workflow = parse::parse(KYC).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    lower::lower(&workflow, &graph).unwrap()
+}
+
+fn assert_cancelled_in(errors: &[CompilerError], phase: Phase) {
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert_eq!(errors[0].code, "X001");
+    assert_eq!(errors[0].phase, phase);
+}
+
+#[test]
+fn cancelled_token_stops_each_phase() {
+    let workflow = parse::parse(KYC).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let options = LowerOptions {
+        cancel: Some(cancelled()),
+        ..Default::default()
+    };
+    let errors = lower::lower_with_options(&workflow, &graph, &options).unwrap_err();
+    assert_cancelled_in(&errors, Phase::Lower);
+
+    let ir = kyc_ir();
+    let errors = validate_ir_cancellable(&ir, &cancelled()).unwrap_err();
+    assert_cancelled_in(&errors, Phase::IrValidate);
+
+    let errors =
+        codegen::codegen_cancellable(&ir, &CodegenOptions::default(), &cancelled()).unwrap_err();
+    assert_cancelled_in(&errors, Phase::Codegen);
+
+    let errors = compile(KYC, Some(cancelled())).unwrap_err();
+    assert_cancelled_in(&errors, Phase::Parse);
+}
+
+#[test]
+fn past_deadline_cancels_compilation() {
+    let token = CancellationToken::with_deadline(Instant::now() - Duration::from_secs(1));
+    let errors = compile(KYC, Some(token.clone())).unwrap_err();
+    assert_cancelled_in(&errors, Phase::Parse);
+    assert!(token.is_cancelled());
+}
+
+#[test]
+fn step_budget_stops_a_compilation_midway() {
+    let errors = compile(KYC, Some(CancellationToken::with_step_budget(10))).unwrap_err();
+    assert_eq!(errors[0].code, "X001");
+    assert_ne!(errors[0].phase, Phase::Parse);
+
+    // A token that never fires changes nothing.
+    let plain = compile(KYC, None).unwrap();
+    let budgeted = compile(KYC, Some(CancellationToken::with_step_budget(10_000))).unwrap();
+    assert_eq!(budgeted.output.files.len(), plain.output.files.len());
+    for (a, b) in budgeted.output.files.iter().zip(&plain.output.files) {
+        assert_eq!(a.path, b.path);
+        if a.path != "manifest.json" {
+            assert_eq!(a.content, b.content);
+        }
+    }
+    assert_eq!(budgeted.warnings.len(), plain.warnings.len());
+}
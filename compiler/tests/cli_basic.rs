@@ -0,0 +1,100 @@
+//! The `sixflow-compile` subcommands, run through `compiler::cli::run` on
+//! fixture workflows.
+
+use std::path::PathBuf;
+
+use compiler::ir::io;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn fixture(name: &str) -> String {
+    format!("{}/{}", FIXTURES, name)
+}
+
+/// Exit code, stdout and stderr of one invocation.
+fn run(args: &[&str]) -> (i32, String, String) {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = compiler::cli::run(&args, &mut out, &mut err);
+    (
+        code,
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(err).unwrap(),
+    )
+}
+
+/// A fresh directory under the system temp dir, unique to this test.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sixflow-cli-{}-{}", test, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn compile_writes_the_project_and_lists_its_files() {
+    let dir = scratch_dir("compile");
+    let (code, out, err) = run(&[
+        "compile",
+        &fixture("example_workflow.json"),
+        "-o",
+        dir.to_str().unwrap(),
+    ]);
+
+    assert_eq!(code, 0, "{}", err);
+    let main_ts = std::fs::read_to_string(dir.join("main.ts")).unwrap();
+    assert!(main_ts.contains("Runner"), "{}", main_ts);
+    assert!(dir.join("config.staging.json").exists());
+    assert!(out.lines().any(|l| l.ends_with("  main.ts")), "{}", out);
+    assert!(out.contains("files written to"), "{}", out);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_passes_a_valid_workflow_and_renders_errors_otherwise() {
+    let (code, out, err) = run(&["check", &fixture("linear_workflow.json")]);
+    assert_eq!(code, 0, "{}", err);
+    assert!(out.ends_with(": ok\n"), "{}", out);
+
+    let (code, out, err) = run(&["check", &fixture("cycle.json")]);
+    assert_eq!(code, 1);
+    assert!(out.is_empty(), "{}", out);
+    assert!(err.starts_with("error: [Validate:V004] "), "{}", err);
+}
+
+#[test]
+fn ir_prints_a_document_io_reads_back() {
+    let (code, out, err) = run(&["ir", &fixture("example_workflow.json")]);
+    assert_eq!(code, 0, "{}", err);
+    let ir = io::from_json(&out).expect("ir output should be an IR document");
+    assert_eq!(ir.metadata.id, "example-tokenization-workflow");
+}
+
+#[test]
+fn diff_reports_changed_files_and_exits_1() {
+    let (code, out, err) = run(&[
+        "diff",
+        &fixture("linear_workflow.json"),
+        &fixture("linear_workflow.json"),
+    ]);
+    assert_eq!((code, out.as_str()), (0, ""), "{}", err);
+
+    let (code, out, _) = run(&[
+        "diff",
+        &fixture("linear_workflow.json"),
+        &fixture("example_workflow.json"),
+    ]);
+    assert_eq!(code, 1);
+    assert!(out.starts_with("modified main.ts\n"), "{}", out);
+    assert!(out.contains("\nadded writes.md\n"), "{}", out);
+}
+
+#[test]
+fn bad_usage_and_missing_files_exit_2() {
+    let (code, _, err) = run(&["compile", &fixture("linear_workflow.json")]);
+    assert_eq!(code, 2);
+    assert!(err.starts_with("usage:"), "{}", err);
+
+    let (code, _, err) = run(&["check", &fixture("no_such_workflow.json")]);
+    assert_eq!(code, 2);
+    assert!(err.starts_with("cannot read "), "{}", err);
+}
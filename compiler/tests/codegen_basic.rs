@@ -2,7 +2,7 @@
 
 mod helpers;
 
-use compiler::codegen::codegen;
+use compiler::codegen::{DiffLine, FileDiff, codegen};
 use compiler::ir::*;
 
 #[test]
@@ -61,6 +61,43 @@ fn branching_workflow_codegen_package_json_snapshot() {
     insta::assert_snapshot!("branching_workflow_package_json", pkg.content);
 }
 
+#[test]
+fn package_json_uses_configured_sdk_version_and_package_manager() {
+    let options = compiler::codegen::CodegenOptions {
+        sdk_version: Some("1.2.0".into()),
+        package_manager: compiler::codegen::PackageManager::Npm,
+        ..Default::default()
+    };
+    let output =
+        compiler::codegen::codegen_with_options(&helpers::branching_workflow_ir(), &options);
+    let pkg = file(&output, "package.json").unwrap();
+    assert!(pkg.contains("\"@chainlink/cre-sdk\": \"1.2.0\""), "{}", pkg);
+    assert!(
+        pkg.contains("\"postinstall\": \"npx cre-setup\""),
+        "{}",
+        pkg
+    );
+    assert!(!pkg.contains("bun x"), "{}", pkg);
+
+    let options = compiler::codegen::CodegenOptions {
+        package_manager: compiler::codegen::PackageManager::Pnpm,
+        ..Default::default()
+    };
+    let output =
+        compiler::codegen::codegen_with_options(&helpers::branching_workflow_ir(), &options);
+    let pkg = file(&output, "package.json").unwrap();
+    assert!(
+        pkg.contains("\"@chainlink/cre-sdk\": \"^1.0.9\""),
+        "{}",
+        pkg
+    );
+    assert!(
+        pkg.contains("\"postinstall\": \"pnpm exec cre-setup\""),
+        "{}",
+        pkg
+    );
+}
+
 #[test]
 fn branching_workflow_codegen_produces_all_files() {
     let ir = helpers::branching_workflow_ir();
@@ -75,7 +112,159 @@ fn branching_workflow_codegen_produces_all_files() {
     assert!(paths.contains(&"package.json"));
     assert!(paths.contains(&"tsconfig.json"));
     assert!(paths.contains(&".env"));
-    assert_eq!(output.files.len(), 8);
+    assert!(paths.contains(&"writes.md"));
+    assert_eq!(output.files.len(), 12);
+}
+
+#[test]
+fn nested_diamond_codegen_main_ts_snapshot() {
+    let output = codegen(&helpers::nested_diamond_ir());
+    let main_ts = output.files.iter().find(|f| f.path == "main.ts").unwrap();
+
+    insta::assert_snapshot!("nested_diamond_main_ts", main_ts.content);
+}
+
+#[test]
+fn open_inner_branch_codegen_main_ts_snapshot() {
+    let output = codegen(&helpers::branch_with_open_inner_branch_ir());
+    let main_ts = output.files.iter().find(|f| f.path == "main.ts").unwrap();
+
+    insta::assert_snapshot!("open_inner_branch_main_ts", main_ts.content);
+}
+
+#[test]
+fn step_map_lists_every_step_with_its_source_nodes() {
+    fn expected_entries(block: &Block, entries: &mut Vec<String>) {
+        for step in &block.steps {
+            let variable = step.output.as_ref().map_or("-", |o| &o.variable_name);
+            entries.push(format!(
+                "// {} | {} | {} | {}",
+                step.id,
+                variable,
+                step.source_node_ids.join(", "),
+                step.label
+            ));
+            if let Operation::Branch(branch) = &step.operation {
+                expected_entries(&branch.true_branch, entries);
+                expected_entries(&branch.false_branch, entries);
+            }
+        }
+    }
+
+    let mut ir = helpers::nested_diamond_ir();
+    ir.handler_body.steps[0].source_node_ids = vec!["fetch".into(), "parse".into()];
+    let output = codegen(&ir);
+    let main_ts = output.files.iter().find(|f| f.path == "main.ts").unwrap();
+    let (_, map) = main_ts
+        .content
+        .split_once("// Step map: step id | variable | source node ids | label\n")
+        .expect("main.ts should end with the step map");
+
+    let mut expected = Vec::new();
+    expected_entries(&ir.handler_body, &mut expected);
+    assert_eq!(map.lines().collect::<Vec<_>>(), expected);
+    assert!(map.starts_with("// http-1 | step_http_1 | fetch, parse | http-1\n"));
+    assert!(map.contains("// inner-merge | step_inner_merge | inner-merge | inner-merge\n"));
+}
+
+#[test]
+fn kyc_mint_writes_md_documents_the_decoded_call() {
+    let encode = helpers::make_step_with_output(
+        "encode-1",
+        Operation::AbiEncode(AbiEncodeOp {
+            function_name: Some("mint".into()),
+            abi_json: r#"{"type":"function","name":"mint","inputs":[{"name":"to","type":"address"},{"name":"amount","type":"uint256"}],"outputs":[],"stateMutability":"nonpayable"}"#.into(),
+            data_mappings: vec![
+                AbiDataMapping {
+                    param_name: "to".into(),
+                    value: ValueExpr::config("walletAddress"),
+                    value_kind: None,
+                },
+                AbiDataMapping {
+                    param_name: "amount".into(),
+                    value: ValueExpr::config("mintAmount"),
+                    value_kind: None,
+                },
+            ],
+        }),
+        "{ encoded: string }",
+    );
+    let write = helpers::make_step(
+        "write-1",
+        helpers::evm_write_op(
+            "evmClient_eth_sepolia",
+            "0x1234567890abcdef1234567890abcdef12345678",
+            ValueExpr::binding("encode-1", "encoded"),
+        ),
+    );
+    let ir = helpers::ir_with_steps_and_deps(
+        vec![encode, write],
+        vec![],
+        vec![("ethereum-testnet-sepolia", "evmClient_eth_sepolia", false)],
+    );
+
+    let output = codegen(&ir);
+    let writes = output.files.iter().find(|f| f.path == "writes.md").unwrap();
+    assert_eq!(
+        &*writes.content,
+        "# EVM writes: Test Workflow\n\
+         \n\
+         ## write-1 — write-1\n\
+         \n\
+         - Chain: `ethereum-testnet-sepolia`\n\
+         - Target: `\"0x1234567890abcdef1234567890abcdef12345678\"`\n\
+         - Call: `mint(to=config.walletAddress, amount=config.mintAmount)`\n\
+         - Signature: `mint(address,uint256)`\n\
+         - Encoded by: `encode-1`\n\
+         - Arguments:\n\
+        \x20 - `to` (address): `config.walletAddress`\n\
+        \x20 - `amount` (uint256): `config.mintAmount`\n\
+         - Gas limit: `500000`\n\
+         - Value: none\n"
+    );
+    assert!(compiler::ir::analysis::opaque_calldata_warnings(&ir).is_empty());
+}
+
+#[test]
+fn opaque_write_calldata_is_flagged() {
+    let ir = helpers::branching_workflow_ir();
+
+    let warnings = compiler::ir::analysis::opaque_calldata_warnings(&ir);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].code, "W011");
+    assert_eq!(warnings[0].node_id.as_deref(), Some("write-1"));
+
+    let output = codegen(&ir);
+    let writes = output.files.iter().find(|f| f.path == "writes.md").unwrap();
+    assert!(
+        writes
+            .content
+            .contains("- Call: opaque calldata `http-1.body.data`\n")
+    );
+}
+
+#[test]
+fn conditional_value_expr_emits_a_ternary() {
+    let ir = helpers::ir_with_steps(vec![
+        helpers::make_step_with_output("http-1", helpers::http_get("https://example.com"), "any"),
+        helpers::make_step(
+            "return-1",
+            helpers::return_op(ValueExpr::conditional(
+                ValueExpr::binding("http-1", "body.ok"),
+                ValueExpr::config("onSuccess"),
+                ValueExpr::string("failed"),
+            )),
+        ),
+    ]);
+    let output = codegen(&ir);
+    let main_ts = output.files.iter().find(|f| f.path == "main.ts").unwrap();
+    assert!(
+        main_ts
+            .content
+            .contains("return (step_http_1.body.ok ? runtime.config.onSuccess : \"failed\");"),
+        "{}",
+        main_ts.content
+    );
 }
 
 #[test]
@@ -156,3 +345,1280 @@ fn ai_call_with_upstream_refs_uses_augmented_config() {
     // Should NOT contain free variable reference in fetch function
     // (the fetch fn uses config._dyn0, not a handler-local upstream value)
 }
+
+#[test]
+fn kv_steps_share_one_client() {
+    let ir = helpers::ir_with_steps(vec![
+        helpers::make_step_with_output(
+            "kv-1",
+            helpers::kv_read_op("lastRun"),
+            "string | undefined",
+        ),
+        helpers::make_step(
+            "branch-1",
+            helpers::branch_op(
+                ValueExpr::binding("kv-1", ""),
+                ComparisonOp::NotEquals,
+                ValueExpr::trigger_data("scheduledTime"),
+                Block {
+                    steps: vec![helpers::make_step(
+                        "kv-2",
+                        helpers::kv_write_op("lastRun", ValueExpr::trigger_data("scheduledTime")),
+                    )],
+                },
+                Block { steps: vec![] },
+                None,
+            ),
+        ),
+    ]);
+    let output = codegen(&ir);
+    let main_ts = &output
+        .files
+        .iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content;
+
+    let client = "const kvClient = new cre.capabilities.KeyValueClient();";
+    assert_eq!(main_ts.matches(client).count(), 1, "{}", main_ts);
+    let read = main_ts
+        .find("const step_kv_1: string | undefined = kvClient.get(runtime, { key: \"lastRun\" }).result().value;")
+        .expect("typed read");
+    let write = main_ts
+        .find("kvClient.set(runtime, { key: \"lastRun\", value: String(triggerData.scheduledTime) }).result();")
+        .expect("write in the true arm");
+    assert!(
+        main_ts.find(client).unwrap() < read && read < write,
+        "{}",
+        main_ts
+    );
+}
+
+fn handler_signature(ir: &WorkflowIR) -> String {
+    let output = codegen(ir);
+    let main_ts = output
+        .files
+        .iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated");
+    main_ts
+        .content
+        .lines()
+        .find(|l| l.starts_with("const onCronTrigger"))
+        .expect("handler should be emitted")
+        .to_string()
+}
+
+#[test]
+fn string_only_returns_keep_string_handler_type() {
+    let ir = helpers::base_ir();
+    let signature = handler_signature(&ir);
+    assert!(signature.ends_with("): string => {"), "got: {}", signature);
+}
+
+#[test]
+fn object_return_widens_handler_type() {
+    let mut ir = helpers::base_ir();
+    ir.handler_body.steps = vec![helpers::make_step(
+        "if-1",
+        helpers::branch_op(
+            ValueExpr::config("threshold"),
+            ComparisonOp::Gt,
+            ValueExpr::integer(10),
+            Block {
+                steps: vec![helpers::make_step(
+                    "return-obj",
+                    helpers::return_op(ValueExpr::Literal(LiteralValue::Json {
+                        value: "{ \"status\": \"high\" }".into(),
+                    })),
+                )],
+            },
+            Block {
+                steps: vec![helpers::make_step(
+                    "return-str",
+                    helpers::return_op(ValueExpr::string("low")),
+                )],
+            },
+            None,
+        ),
+    )];
+
+    let signature = handler_signature(&ir);
+    assert!(
+        signature.ends_with("): string | object => {"),
+        "got: {}",
+        signature
+    );
+}
+
+#[test]
+fn filter_early_return_with_object_return_widens_handler_type() {
+    let mut ir = helpers::base_ir();
+    ir.handler_body.steps = vec![
+        helpers::make_step(
+            "filter-1",
+            helpers::filter_op(
+                ValueExpr::config("threshold"),
+                ComparisonOp::Gt,
+                ValueExpr::integer(10),
+                FilterNonMatchBehavior::EarlyReturn {
+                    message: ValueExpr::string("Filter condition not met"),
+                },
+            ),
+        ),
+        helpers::make_step(
+            "return-obj",
+            helpers::return_op(ValueExpr::Literal(LiteralValue::Json {
+                value: "{ \"status\": \"high\" }".into(),
+            })),
+        ),
+    ];
+
+    let signature = handler_signature(&ir);
+    assert!(
+        signature.ends_with("): string | object => {"),
+        "got: {}",
+        signature
+    );
+}
+
+#[test]
+fn step_notes_render_as_safe_doc_comments() {
+    let mut single = helpers::make_step("return-1", helpers::return_op(ValueExpr::string("ok")));
+    single.notes = Some("Approved by risk team 2024-06".into());
+    let mut multi = helpers::make_step("return-2", helpers::return_op(ValueExpr::string("ok")));
+    multi.notes = Some("Reviewed by:\n  compliance */ alert('x') /*\n".into());
+    let ir = helpers::ir_with_steps(vec![helpers::make_step(
+        "if-1",
+        helpers::branch_op(
+            ValueExpr::config("threshold"),
+            ComparisonOp::Gt,
+            ValueExpr::integer(10),
+            Block {
+                steps: vec![single],
+            },
+            Block { steps: vec![multi] },
+            None,
+        ),
+    )]);
+
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+
+    assert!(
+        main_ts.contains("    /** Approved by risk team 2024-06 */\n    return \"ok\";"),
+        "{}",
+        main_ts
+    );
+    assert!(
+        main_ts.contains(
+            "    /**\n     * Reviewed by:\n     *   compliance *\\/ alert('x') /*\n     */\n    return"
+        ),
+        "{}",
+        main_ts
+    );
+    assert!(!main_ts.contains("compliance */"));
+}
+
+#[test]
+fn standalone_custom_merge_runs_code_over_inputs_array() {
+    let mut merge = helpers::standalone_merge_op(vec![
+        ("input1", ValueExpr::config("primary")),
+        ("input2", ValueExpr::config("fallback")),
+    ]);
+    if let Operation::Merge(op) = &mut merge {
+        op.strategy = MergeStrategy::Custom {
+            expr: "return inputs.find((v) => v !== \"\");".into(),
+        };
+    }
+    let ir = helpers::ir_with_steps(vec![helpers::make_step_with_output(
+        "merge-1", merge, "any",
+    )]);
+
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+
+    assert!(
+        main_ts.contains(
+            "  const step_merge_1 = (() => {\n    const inputs = [runtime.config.primary, runtime.config.fallback];\n    return inputs.find((v) => v !== \"\");\n  })();"
+        ),
+        "{}",
+        main_ts
+    );
+}
+
+fn main_ts_with_cached_http(op: Operation) -> String {
+    let mut op = op;
+    if let Operation::HttpRequest(http) = &mut op {
+        http.cache_max_age_seconds = Some(60);
+    }
+    let ir = helpers::ir_with_steps(vec![helpers::make_step_with_output(
+        "http-1",
+        op,
+        "{ statusCode: number; body: any; headers: Record<string, string> }",
+    )]);
+    codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content
+        .to_string()
+}
+
+#[test]
+fn cached_get_emits_cache_settings() {
+    let main_ts = main_ts_with_cached_http(helpers::http_get("https://api.example.com/price"));
+    assert!(main_ts.contains("cacheSettings:"), "got:\n{}", main_ts);
+    assert!(main_ts.contains("maxAge: { seconds: 60n },"));
+}
+
+#[test]
+fn http_transport_flags_are_not_emitted() {
+    // CRE has no request field for either flag (N021, N025 / W008).
+    let mut op = helpers::http_get("https://api.example.com/price");
+    if let Operation::HttpRequest(http) = &mut op {
+        http.ignore_ssl = true;
+    }
+    let main_ts = main_ts_with_cached_http(op);
+    assert!(main_ts.contains("cacheSettings:"), "got:\n{}", main_ts);
+    for field in ["ignoreSSL", "ignoreSsl", "insecure", "followRedirects"] {
+        assert!(!main_ts.contains(field), "{} emitted:\n{}", field, main_ts);
+    }
+}
+
+#[test]
+fn cached_post_omits_cache_settings() {
+    let main_ts = main_ts_with_cached_http(helpers::http_post(
+        "https://api.example.com/orders",
+        ValueExpr::string("{}"),
+    ));
+    assert!(!main_ts.contains("cacheSettings"), "got:\n{}", main_ts);
+}
+
+fn main_ts_with_headers(headers: Vec<(&str, ValueExpr)>) -> String {
+    let mut op = helpers::http_get("https://api.example.com/price");
+    if let Operation::HttpRequest(http) = &mut op {
+        http.headers = headers.into_iter().map(|(k, v)| (k.into(), v)).collect();
+    }
+    let ir = helpers::ir_with_steps(vec![helpers::make_step_with_output(
+        "http-1",
+        op,
+        "{ statusCode: number; body: any; headers: Record<string, string> }",
+    )]);
+    codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content
+        .to_string()
+}
+
+#[test]
+fn statically_empty_header_is_omitted() {
+    let main_ts = main_ts_with_headers(vec![
+        ("Authorization", ValueExpr::string("")),
+        ("Accept", ValueExpr::string("application/json")),
+    ]);
+    assert!(!main_ts.contains("Authorization"), "got:\n{}", main_ts);
+    assert!(main_ts.contains("\"Accept\": \"application/json\","));
+
+    let main_ts = main_ts_with_headers(vec![("Authorization", ValueExpr::string(""))]);
+    assert!(!main_ts.contains("headers: {"), "got:\n{}", main_ts);
+}
+
+#[test]
+fn dynamic_header_is_conditionally_spread() {
+    let main_ts = main_ts_with_headers(vec![
+        ("X-Api-Key", ValueExpr::config("apiKey")),
+        (
+            "Authorization",
+            ValueExpr::Template {
+                parts: vec![
+                    TemplatePart::Lit {
+                        value: "Bearer ".into(),
+                    },
+                    TemplatePart::Expr {
+                        value: ValueExpr::config("token"),
+                    },
+                ],
+            },
+        ),
+    ]);
+    assert!(
+        main_ts.contains("...(config.apiKey ? { \"X-Api-Key\": config.apiKey } : {}),"),
+        "got:\n{}",
+        main_ts
+    );
+    // Literal text makes the value non-empty, so it is always sent.
+    assert!(main_ts.contains("\"Authorization\": `Bearer ${config.token}`,"));
+}
+
+fn main_ts_with_body(body: HttpBody, headers: Vec<(&str, ValueExpr)>) -> String {
+    let mut op = helpers::http_post("https://api.example.com/orders", ValueExpr::null());
+    if let Operation::HttpRequest(http) = &mut op {
+        http.body = Some(body);
+        http.headers = headers.into_iter().map(|(k, v)| (k.into(), v)).collect();
+    }
+    main_ts_with_cached_http(op)
+}
+
+#[test]
+fn json_body_is_stringified_and_gets_a_default_content_type() {
+    let body = HttpBody {
+        content_type: HttpContentType::Json,
+        data: ValueExpr::config("order"),
+        fields: vec![],
+    };
+    let main_ts = main_ts_with_body(body.clone(), vec![]);
+    assert!(
+        main_ts.contains(
+            "body: Buffer.from(new TextEncoder().encode(JSON.stringify(config.order))).toString(\"base64\"),"
+        ),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains("\"Content-Type\": \"application/json\","));
+
+    // A Content-Type the user set, in any case, is kept as the only one.
+    let main_ts = main_ts_with_body(
+        body,
+        vec![(
+            "content-type",
+            ValueExpr::string("application/vnd.api+json"),
+        )],
+    );
+    assert!(!main_ts.contains("\"Content-Type\""), "got:\n{}", main_ts);
+    assert!(main_ts.contains("\"content-type\": \"application/vnd.api+json\","));
+}
+
+#[test]
+fn form_body_fields_are_url_encoded() {
+    let main_ts = main_ts_with_body(
+        HttpBody {
+            content_type: HttpContentType::FormUrlEncoded,
+            data: ValueExpr::null(),
+            fields: vec![
+                ("amount".into(), ValueExpr::config("amount")),
+                ("memo".into(), ValueExpr::string("paid & done")),
+            ],
+        },
+        vec![],
+    );
+    assert!(
+        main_ts.contains(
+            "body: Buffer.from(new TextEncoder().encode(new URLSearchParams({ \"amount\": String(config.amount), \"memo\": \"paid & done\" }).toString())).toString(\"base64\"),"
+        ),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains("\"Content-Type\": \"application/x-www-form-urlencoded\","));
+
+    // A pre-built form string is sent as written.
+    let main_ts = main_ts_with_body(
+        HttpBody {
+            content_type: HttpContentType::FormUrlEncoded,
+            data: ValueExpr::string("a=1&b=2"),
+            fields: vec![],
+        },
+        vec![],
+    );
+    assert!(
+        main_ts.contains(
+            "body: Buffer.from(new TextEncoder().encode(\"a=1&b=2\")).toString(\"base64\"),"
+        ),
+        "got:\n{}",
+        main_ts
+    );
+}
+
+#[test]
+fn raw_body_is_base64_encoded_as_text() {
+    let main_ts = main_ts_with_body(
+        HttpBody {
+            content_type: HttpContentType::Raw,
+            data: ValueExpr::string("ping"),
+            fields: vec![],
+        },
+        vec![],
+    );
+    assert!(
+        main_ts.contains(
+            "body: Buffer.from(new TextEncoder().encode(\"ping\")).toString(\"base64\"),"
+        ),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains("\"Content-Type\": \"text/plain\","));
+}
+
+#[test]
+fn destructured_fields_get_step_prefixed_locals() {
+    let decode = |id: &str, fields: Vec<&str>| {
+        helpers::make_step_destructuring(
+            id,
+            helpers::abi_decode_op(ValueExpr::string("0x"), "[]", fields.clone()),
+            fields,
+        )
+    };
+    let ir = helpers::ir_with_steps(vec![
+        decode("decode-1", vec!["from", "value"]),
+        decode("decode-2", vec!["value"]),
+        helpers::make_step(
+            "return-1",
+            helpers::return_op(ValueExpr::Template {
+                parts: vec![
+                    TemplatePart::Expr {
+                        value: ValueExpr::binding("decode-1", "from"),
+                    },
+                    TemplatePart::Lit { value: ":".into() },
+                    TemplatePart::Expr {
+                        value: ValueExpr::binding("decode-2", "value"),
+                    },
+                ],
+            }),
+        ),
+    ]);
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content;
+    assert!(
+        main_ts.contains(
+            "const { from: step_decode_1_from, value: step_decode_1_value } = decodeFunctionResult({"
+        ),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains("const { value: step_decode_2_value } = decodeFunctionResult({"));
+    assert!(main_ts.contains("`${step_decode_1_from}:${step_decode_2_value}`"));
+    assert!(!main_ts.contains("step_decode_1.from"));
+}
+
+#[test]
+fn uint256_args_from_large_values_are_emitted_as_bigint() {
+    let large = "1000000000000000000000000";
+    let mut ir = helpers::ir_with_steps_and_deps(
+        vec![
+            helpers::make_step_with_output(
+                "encode-1",
+                helpers::abi_encode_op(
+                    r#"[{"name":"to","type":"address"},{"name":"amount","type":"uint256"},{"name":"ids","type":"uint256[]"}]"#,
+                    vec![
+                        ("to", ValueExpr::string("0xabc")),
+                        ("amount", ValueExpr::config("amount")),
+                        ("ids", ValueExpr::config("ids")),
+                    ],
+                ),
+                "any",
+            ),
+            helpers::make_step_with_output(
+                "read-1",
+                helpers::evm_read_op_with_args(
+                    "evmClient_sepolia",
+                    "0xdef",
+                    "balanceOfAt",
+                    vec![EvmArg {
+                        abi_type: "uint256".into(),
+                        value: ValueExpr::string(large),
+                    }],
+                ),
+                "any",
+            ),
+        ],
+        vec![],
+        vec![("ethereum-testnet-sepolia", "evmClient_sepolia", false)],
+    );
+    ir.config_schema.push(ConfigField {
+        name: "amount".into(),
+        zod_type: ZodType::String,
+        default_value: Some(large.into()),
+        description: None,
+        referenced_by: vec!["encode-1".into()],
+        optional_in_config: false,
+    });
+
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content;
+    assert!(
+        main_ts.contains(r#"["0xabc", BigInt(runtime.config.amount), runtime.config.ids]"#),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains(&format!(r#"args: [BigInt("{large}")],"#)));
+}
+
+#[test]
+fn secret_shared_by_two_steps_is_fetched_once() {
+    let main_ts = main_ts_with_two_bearer_calls(false);
+
+    assert_eq!(
+        main_ts.matches("runtime.getSecret(").count(),
+        1,
+        "got:\n{}",
+        main_ts
+    );
+    assert!(
+        main_ts
+            .contains("const _secret_API_KEY = runtime.getSecret({ id: \"API_KEY\" }).result();")
+    );
+    assert_eq!(
+        main_ts
+            .matches("_authToken: _secret_API_KEY.value,")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn secret_refs_in_two_steps_share_one_fetch() {
+    let mut http = helpers::http_get("https://api.example.com/a");
+    if let Operation::HttpRequest(op) = &mut http {
+        op.headers = vec![("X-API-Key".into(), ValueExpr::secret("API_KEY"))];
+    }
+    let ir = helpers::ir_with_steps_and_deps(
+        vec![
+            helpers::make_step_with_output("http-1", http, "any"),
+            helpers::make_step_with_output(
+                "code-1",
+                helpers::code_node_op(
+                    "return key.length;",
+                    vec![("key", ValueExpr::secret("API_KEY"))],
+                ),
+                "any",
+            ),
+        ],
+        vec![("API_KEY", "API_KEY_VAR")],
+        vec![],
+    );
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+
+    let fetches = main_ts.matches("runtime.getSecret(").count();
+    assert_eq!(fetches, 1, "{main_ts}");
+    let shared = r#"const _secret_API_KEY = runtime.getSecret({ id: "API_KEY" }).result();"#;
+    assert!(main_ts.contains(shared), "{main_ts}");
+    assert!(
+        main_ts.contains("_dyn0: _secret_API_KEY.value,"),
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains("const key = _secret_API_KEY.value;"),
+        "{main_ts}"
+    );
+}
+
+#[test]
+fn shared_secrets_that_sanitize_alike_get_distinct_names() {
+    let response_type = "{ statusCode: number; body: any; headers: Record<string, string> }";
+    let bearer = |id: &str, secret: &str| {
+        helpers::make_step_with_output(
+            id,
+            helpers::http_get_with_bearer("https://api.example.com/a", secret),
+            response_type,
+        )
+    };
+    let ir = helpers::ir_with_steps_and_deps(
+        vec![
+            bearer("http-1", "API-KEY"),
+            bearer("http-2", "API_KEY"),
+            bearer("http-3", "API-KEY"),
+            bearer("http-4", "API_KEY"),
+        ],
+        vec![("API-KEY", "API_KEY_DASH"), ("API_KEY", "API_KEY_VAR")],
+        vec![],
+    );
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content;
+
+    for shared in [
+        r#"const _secret_API_KEY = runtime.getSecret({ id: "API-KEY" }).result();"#,
+        r#"const _secret_API_KEY_2 = runtime.getSecret({ id: "API_KEY" }).result();"#,
+    ] {
+        assert!(main_ts.contains(shared), "{main_ts}");
+    }
+    for var_name in ["_secret_API_KEY", "_secret_API_KEY_2"] {
+        let auth = format!("_authToken: {var_name}.value,");
+        assert_eq!(main_ts.matches(&auth).count(), 2, "{main_ts}");
+    }
+}
+
+#[test]
+fn optional_secret_is_not_hoisted() {
+    // Only some runs need an optional secret, so it stays next to each read.
+    let main_ts = main_ts_with_two_bearer_calls(true);
+
+    assert_eq!(
+        main_ts.matches("runtime.getSecret(").count(),
+        2,
+        "got:\n{}",
+        main_ts
+    );
+    assert!(!main_ts.contains("_secret_API_KEY"));
+}
+
+#[test]
+fn fetch_config_gets_named_type_with_inferred_members() {
+    let mut op = helpers::http_post(
+        "https://api.example.com/orders",
+        ValueExpr::binding("code-1", "meta"),
+    );
+    if let Operation::HttpRequest(http) = &mut op {
+        http.url = ValueExpr::binding("code-1", "endpoint");
+        http.headers = vec![
+            ("X-Retries".into(), ValueExpr::binding("code-1", "retries")),
+            ("X-Trace".into(), ValueExpr::trigger_data("traceId")),
+        ];
+    }
+    let ir = helpers::ir_with_steps(vec![
+        helpers::make_step_with_output(
+            "code-1",
+            helpers::code_node_op("return { endpoint: \"x\", retries: 3, meta: {} };", vec![]),
+            "{ endpoint: string; retries: number; meta: any }",
+        ),
+        helpers::make_step_with_output("http-1", op, "any"),
+    ]);
+    let main_ts = codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content
+        .to_string();
+
+    // Output field types are used; unknown values are `string` where a
+    // string is required (a header) and `unknown` elsewhere (a JSON body).
+    let expected_type = "\
+type Fetch_http_1_Config = Config & {
+  _dyn0: string;
+  _dyn1: number;
+  _dyn2: string;
+  _dyn3: unknown;
+};";
+    assert!(main_ts.contains(expected_type), "got:\n{}", main_ts);
+    assert!(main_ts.contains(
+        "const fetch_http_1 = (sendRequester: HTTPSendRequester, config: Fetch_http_1_Config) =>"
+    ));
+    assert!(!main_ts.contains("config: any"));
+}
+
+#[test]
+fn authed_fetch_config_is_typed_on_both_sides() {
+    let response_type = "{ statusCode: number; body: any; headers: Record<string, string> }";
+    let mut authed = helpers::http_get_with_bearer("https://api.example.com/a", "API_KEY");
+    if let Operation::HttpRequest(http) = &mut authed {
+        http.url = ValueExpr::binding("http-1", "body.next");
+    }
+    let ir = helpers::ir_with_steps_and_deps(
+        vec![
+            helpers::make_step_with_output(
+                "http-1",
+                helpers::http_get("https://api.example.com/start"),
+                response_type,
+            ),
+            helpers::make_step_with_output("http-2", authed, response_type),
+        ],
+        vec![("API_KEY", "API_KEY_VAR")],
+        vec![],
+    );
+    let output = codegen(&ir);
+    let main_ts = file(&output, "main.ts").unwrap();
+
+    let expected_type = "\
+type Fetch_http_2_Config = Config & {
+  _authToken: string;
+  _dyn0: string;
+};";
+    assert!(main_ts.contains(expected_type), "got:\n{}", main_ts);
+    assert!(main_ts.contains(
+        "const fetch_http_2 = (sendRequester: HTTPSendRequester, config: Fetch_http_2_Config) =>"
+    ));
+    assert!(main_ts.contains("const _fetchCfg_http_2: Fetch_http_2_Config = {"));
+    // A request with neither auth nor dynamic refs reads the plain config.
+    assert!(
+        main_ts
+            .contains("const fetch_http_1 = (sendRequester: HTTPSendRequester, config: Config) =>")
+    );
+    assert!(!main_ts.contains("Fetch_http_1_Config"));
+    assert!(!main_ts.contains(": any"), "got:\n{}", main_ts);
+}
+
+fn main_ts_with_two_bearer_calls(secret_optional: bool) -> String {
+    let response_type = "{ statusCode: number; body: any; headers: Record<string, string> }";
+    let mut ir = helpers::ir_with_steps_and_deps(
+        vec![
+            helpers::make_step_with_output(
+                "http-1",
+                helpers::http_get_with_bearer("https://api.example.com/a", "API_KEY"),
+                response_type,
+            ),
+            helpers::make_step_with_output(
+                "http-2",
+                helpers::http_get_with_bearer("https://api.example.com/b", "API_KEY"),
+                response_type,
+            ),
+        ],
+        vec![("API_KEY", "API_KEY_VAR")],
+        vec![],
+    );
+    ir.required_secrets[0].optional_in_config = secret_optional;
+    codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .expect("main.ts should be generated")
+        .content
+        .to_string()
+}
+
+#[test]
+fn diff_after_toggling_is_testnet() {
+    let testnet = helpers::base_ir();
+    let mut mainnet = testnet.clone();
+    mainnet.metadata.is_testnet = false;
+
+    let before = compiler::codegen::codegen(&testnet);
+    let after = compiler::codegen::codegen(&mainnet);
+    let diffs = before.diff(&after);
+
+    assert!(diffs.contains(&FileDiff::Removed {
+        path: "config.staging.json".into()
+    }));
+    assert!(diffs.contains(&FileDiff::Added {
+        path: "config.production.json".into()
+    }));
+    assert!(
+        !diffs.iter().any(|d| d.path() == "package.json"),
+        "package.json does not depend on the network: {:?}",
+        diffs
+    );
+    let manifest_lines = diffs
+        .iter()
+        .find_map(|d| match d {
+            FileDiff::Modified { path, lines } if path == "manifest.json" => Some(lines),
+            _ => None,
+        })
+        .expect("manifest.json should list the renamed config file");
+    assert!(manifest_lines.contains(&DiffLine::Added(
+        "      \"path\": \"config.production.json\",".into()
+    )));
+    assert!(before.diff(&before).is_empty());
+}
+
+#[test]
+fn streaming_codegen_matches_collected_output() {
+    let ir = helpers::branching_workflow_ir();
+    let collected = compiler::codegen::codegen(&ir);
+
+    let mut streamed = Vec::new();
+    compiler::codegen::codegen_streaming(&ir, &mut |f| streamed.push((f.path, f.content)));
+
+    assert_eq!(streamed.len(), collected.files.len());
+    for ((path, content), file) in streamed.iter().zip(&collected.files) {
+        assert_eq!(path, &file.path);
+        assert_eq!(content, &file.content);
+    }
+}
+
+/// Run with `cargo test --test codegen_basic -- --ignored`.
+#[test]
+#[ignore = "benchmark: generates a 500-step workflow"]
+fn streaming_codegen_of_500_steps_is_fast_and_identical() {
+    let response_type = "{ statusCode: number; body: any; headers: Record<string, string> }";
+    let steps = (0..500)
+        .map(|i| {
+            let url = format!("https://api.example.com/items/{i}");
+            let op = if i % 2 == 0 {
+                helpers::http_get(&url)
+            } else {
+                helpers::http_get_with_bearer(&url, "API_KEY")
+            };
+            helpers::make_step_with_output(&format!("http-{i}"), op, response_type)
+        })
+        .collect();
+    let ir = helpers::ir_with_steps_and_deps(steps, vec![("API_KEY", "API_KEY_VAR")], vec![]);
+
+    let start = std::time::Instant::now();
+    let mut streamed = Vec::new();
+    compiler::codegen::codegen_streaming(&ir, &mut |f| streamed.push(f));
+    let elapsed = start.elapsed();
+    assert!(elapsed.as_secs() < 10, "codegen took {elapsed:?}");
+
+    let collected = compiler::codegen::codegen(&ir);
+    assert_eq!(streamed.len(), collected.files.len());
+    for (a, b) in streamed.iter().zip(&collected.files) {
+        assert_eq!(a.path, b.path);
+        assert_eq!(a.content, b.content);
+    }
+    let main_ts = &streamed[0].content;
+    assert!(main_ts.contains("step_http_499"));
+}
+
+/// The branching workflow plus an AI call reading an upstream output, a code
+/// node with its own comment, step notes and an auto-generated return.
+fn compact_test_ir() -> WorkflowIR {
+    let mut ir = helpers::branching_workflow_ir();
+    let mut code = helpers::make_step_with_output(
+        "code-1",
+        helpers::code_node_op("// user comment stays\nreturn { ok: true };", vec![]),
+        "any",
+    );
+    code.notes = Some("Reviewed by risk team".into());
+    let ai = helpers::make_step_with_output(
+        "ai-1",
+        helpers::ai_call_op_with_refs(
+            "openai",
+            "OPENAI_KEY",
+            ValueExpr::string("You are a helpful assistant."),
+            ValueExpr::Template {
+                parts: vec![
+                    TemplatePart::Lit {
+                        value: "Analyze: ".into(),
+                    },
+                    TemplatePart::Expr {
+                        value: ValueExpr::binding("http-1", "body"),
+                    },
+                ],
+            },
+        ),
+        "any",
+    );
+    ir.handler_body.steps.splice(1..1, [code, ai]);
+    ir
+}
+
+fn file<'a>(output: &'a compiler::codegen::CodegenOutput, path: &str) -> Option<&'a str> {
+    output
+        .files
+        .iter()
+        .find(|f| f.path == path)
+        .map(|f| &*f.content)
+}
+
+#[test]
+fn compact_mode_strips_generated_comments() {
+    let options = compiler::codegen::CodegenOptions {
+        compact: true,
+        ..Default::default()
+    };
+    let output = compiler::codegen::codegen_with_options(&compact_test_ir(), &options);
+    let main_ts = file(&output, "main.ts").unwrap();
+
+    let comment_lines: Vec<&str> = main_ts
+        .lines()
+        .filter(|l| l.trim_start().starts_with("//") || l.contains(" // ") || l.contains("/**"))
+        .collect();
+    assert_eq!(
+        comment_lines,
+        vec!["      // user comment stays"],
+        "{}",
+        main_ts
+    );
+    assert!(file(&output, "audit.json").is_none());
+    assert!(file(&output, "names.json").is_some());
+}
+
+#[test]
+fn compact_names_json_maps_back_to_default_names() {
+    let ir = compact_test_ir();
+    let options = compiler::codegen::CodegenOptions {
+        compact: true,
+        ..Default::default()
+    };
+    let compact = compiler::codegen::codegen_with_options(&ir, &options);
+    let default = codegen(&ir);
+    let compact_ts = file(&compact, "main.ts").unwrap();
+    let default_ts = file(&default, "main.ts").unwrap();
+
+    let names: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(file(&compact, "names.json").unwrap()).unwrap();
+    assert_eq!(names["_f1"], "fetch_http_1");
+    assert_eq!(names["_c1"], "_fetchCfg_http_1");
+    assert_eq!(names["_f2"], "fetch_ai_1");
+    let mut restored = compact_ts.to_string();
+    for (short, original) in &names {
+        let original = original.as_str().unwrap();
+        assert!(
+            compact_ts.contains(short.as_str()),
+            "{short} unused:\n{compact_ts}"
+        );
+        assert!(
+            !compact_ts.contains(original),
+            "{original} left:\n{compact_ts}"
+        );
+        assert!(default_ts.contains(original));
+        restored = replace_identifier(&restored, short, original);
+    }
+    // Renaming back gives the default helpers; only comments are missing.
+    for line in restored.lines() {
+        assert!(default_ts.contains(line), "{line:?} not in default output");
+    }
+}
+
+#[test]
+fn content_named_fetch_fns_survive_step_renames() {
+    let options = compiler::codegen::CodegenOptions {
+        content_named_fetch_fns: true,
+        ..Default::default()
+    };
+    let with_ids = |first: &str, second: &str| {
+        let price = || helpers::http_get("https://api.example.com/price");
+        let ir = helpers::ir_with_steps(vec![
+            helpers::make_step_with_output(first, price(), "any"),
+            helpers::make_step_with_output(second, price(), "any"),
+        ]);
+        let output = compiler::codegen::codegen_with_options(&ir, &options);
+        file(&output, "main.ts").unwrap().to_string()
+    };
+    let fetch_fns = |main_ts: &str| -> Vec<String> {
+        main_ts
+            .lines()
+            .filter_map(|l| l.strip_prefix("const fetch_"))
+            .map(|rest| rest.split(' ').next().unwrap().to_string())
+            .collect()
+    };
+
+    let main_ts = with_ids("http-1", "http-2");
+    let names = fetch_fns(&main_ts);
+    assert_eq!(names.len(), 2, "{main_ts}");
+    // Named by content: 8 hex digits, the identical second request suffixed.
+    assert!(names[0].len() == 8 && names[0].chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(names[1], format!("{}_2", names[0]));
+    assert!(!main_ts.contains("fetch_http_1"));
+
+    let renamed = with_ids("price-feed", "price-backup");
+    assert_eq!(fetch_fns(&renamed), names);
+
+    // A different request gets a different name.
+    let ir = helpers::ir_with_steps(vec![helpers::make_step_with_output(
+        "http-1",
+        helpers::http_get("https://api.example.com/volume"),
+        "any",
+    )]);
+    let output = compiler::codegen::codegen_with_options(&ir, &options);
+    let other = fetch_fns(file(&output, "main.ts").unwrap());
+    assert_eq!(other.len(), 1);
+    assert_ne!(other[0], names[0]);
+}
+
+/// Replace `from` wherever it appears as a whole identifier.
+fn replace_identifier(text: &str, from: &str, to: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find(from) {
+        let (before, after) = (&rest[..at], &rest[at + from.len()..]);
+        out.push_str(before);
+        let bounded = !before.ends_with(is_ident) && !after.starts_with(is_ident);
+        out.push_str(if bounded { to } else { from });
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn default_options_match_plain_codegen() {
+    let ir = compact_test_ir();
+    let plain = compiler::codegen::codegen(&ir);
+    let with_options =
+        compiler::codegen::codegen_with_options(&ir, &compiler::codegen::CodegenOptions::default());
+
+    assert_eq!(with_options.files.len(), plain.files.len());
+    for (a, b) in with_options.files.iter().zip(&plain.files) {
+        assert_eq!(a.path, b.path);
+        assert_eq!(a.content, b.content);
+    }
+}
+
+#[test]
+fn label_step_errors_wraps_capability_calls() {
+    let mut ir = helpers::branching_workflow_ir();
+    ir.handler_body.steps[0].label = "Check \"KYC\"".into();
+    ir.handler_body.steps.insert(
+        1,
+        helpers::make_step("error-1", helpers::error_op(ValueExpr::string("halt"))),
+    );
+    let output = codegen(&ir);
+    let main_ts = file(&output, "main.ts").unwrap();
+
+    assert!(
+        main_ts.contains("let __currentStep = \"\";\n  try {\n"),
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains("__currentStep = \"step:http-1 \\\"Check \\\\\\\"KYC\\\\\\\"\\\"\";\n"),
+        "{main_ts}"
+    );
+    let set = main_ts.find("__currentStep = \"step:http-1").unwrap();
+    let http = main_ts[set..].find("sendRequest").unwrap() + set;
+    let reset = main_ts[set..].find("__currentStep = \"\";").unwrap() + set;
+    assert!(http < reset, "{main_ts}");
+    assert!(main_ts.contains(
+        "} catch (e) {\n    if (!__currentStep) throw e;\n    throw new Error(\"[\" + __currentStep + \"] \" + (e instanceof Error ? e.message : String(e)), { cause: e });\n  }\n"
+    ), "{main_ts}");
+    // The Error node still throws its own error, outside any labeled call.
+    let throw = main_ts.find("throw new Error(\"halt\")").unwrap();
+    assert!(reset < throw, "{main_ts}");
+    // Calls inside branches are labeled too.
+    assert!(
+        main_ts.contains("__currentStep = \"step:write-1 \\\"Write to contract\\\"\";"),
+        "{main_ts}"
+    );
+
+    let options = compiler::codegen::CodegenOptions {
+        label_step_errors: false,
+        ..Default::default()
+    };
+    let unlabeled = compiler::codegen::codegen_with_options(&ir, &options);
+    let unlabeled = file(&unlabeled, "main.ts").unwrap();
+    assert!(!unlabeled.contains("__currentStep"), "{unlabeled}");
+    assert!(!unlabeled.contains("try {"), "{unlabeled}");
+
+    // Without a capability call there is nothing to label.
+    let pure = helpers::ir_with_steps(vec![helpers::make_step(
+        "error-1",
+        helpers::error_op(ValueExpr::string("halt")),
+    )]);
+    let pure = codegen(&pure);
+    assert!(
+        !file(&pure, "main.ts").unwrap().contains("__currentStep"),
+        "{}",
+        file(&pure, "main.ts").unwrap()
+    );
+}
+
+#[test]
+fn http_trigger_payload_is_decoded_once() {
+    let payload = || ValueExpr::trigger_data("input");
+    let mut ir = helpers::ir_with_steps(vec![
+        helpers::make_step_with_output(
+            "http-1",
+            helpers::http_post("https://api.example.com/submit", payload()),
+            "any",
+        ),
+        helpers::make_step_with_output(
+            "code-1",
+            helpers::code_node_op_with_outputs(
+                "const size = body.length;",
+                vec![("body", payload())],
+                vec!["size"],
+            ),
+            "any",
+        ),
+    ]);
+    ir.trigger = TriggerDef::Http(HttpTriggerDef {
+        authorized_keys: vec![],
+    });
+    ir.trigger_param = TriggerParam::HttpRequest;
+
+    let output = codegen(&ir);
+    let main_ts = file(&output, "main.ts").unwrap();
+    assert_eq!(
+        main_ts
+            .matches("const _payload = new TextDecoder().decode(triggerData.input);")
+            .count(),
+        1,
+        "{main_ts}"
+    );
+    assert_eq!(main_ts.matches("triggerData.input").count(), 1, "{main_ts}");
+    assert!(main_ts.contains("const body = _payload;"), "{main_ts}");
+    assert!(main_ts.contains("_payload,"), "{main_ts}");
+
+    // Nothing to decode when no step reads the payload.
+    let mut quiet = helpers::ir_with_steps(vec![]);
+    quiet.trigger = ir.trigger.clone();
+    quiet.trigger_param = TriggerParam::HttpRequest;
+    let output = codegen(&quiet);
+    assert!(!file(&output, "main.ts").unwrap().contains("_payload"));
+}
+
+fn labelled_branch_ir(approved_step: &str) -> WorkflowIR {
+    let mut ir = helpers::base_ir();
+    let mut branch = helpers::branch_op(
+        ValueExpr::config("threshold"),
+        ComparisonOp::Gt,
+        ValueExpr::integer(10),
+        Block {
+            steps: vec![
+                helpers::make_step(
+                    approved_step,
+                    helpers::code_node_op("console.log(\"approved\");", vec![]),
+                ),
+                helpers::make_step("return-yes", helpers::return_op(ValueExpr::string("yes"))),
+            ],
+        },
+        Block {
+            steps: vec![helpers::make_step(
+                "return-no",
+                helpers::return_op(ValueExpr::string("no")),
+            )],
+        },
+        None,
+    );
+    if let Operation::Branch(op) = &mut branch {
+        op.true_label = Some("KYC approved".into());
+        op.false_label = Some("KYC rejected".into());
+    }
+    ir.handler_body.steps = vec![helpers::make_step("if-1", branch)];
+    ir
+}
+
+#[test]
+fn branch_arm_labels_are_emitted_as_comments() {
+    let output = codegen(&labelled_branch_ir("notify-1"));
+    let main_ts = file(&output, "main.ts").unwrap();
+    let approved = main_ts.find("// ── Arm: KYC approved ──").expect(main_ts);
+    let rejected = main_ts.find("// ── Arm: KYC rejected ──").expect(main_ts);
+    let else_at = main_ts.find("} else {").unwrap();
+    assert!(approved < else_at && else_at < rejected, "{}", main_ts);
+
+    let audit: serde_json::Value =
+        serde_json::from_str(file(&output, "audit.json").unwrap()).unwrap();
+    let arm_of = |id: &str| {
+        audit["steps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["id"] == id)
+            .map(|s| s["arm"].clone())
+            .unwrap()
+    };
+    assert_eq!(arm_of("notify-1"), "KYC approved");
+    assert_eq!(arm_of("return-no"), "KYC rejected");
+    assert!(arm_of("if-1").is_null());
+}
+
+#[test]
+fn diff_of_renamed_step_stays_inside_its_labelled_arm() {
+    let before = compiler::codegen::codegen(&labelled_branch_ir("notify-1"));
+    let after = compiler::codegen::codegen(&labelled_branch_ir("notify-approved"));
+    let lines = before
+        .diff(&after)
+        .into_iter()
+        .find_map(|d| match d {
+            FileDiff::Modified { path, lines } if path == "main.ts" => Some(lines),
+            _ => None,
+        })
+        .expect("main.ts changes");
+    let at = |line: &str| {
+        lines
+            .iter()
+            .position(|l| *l == DiffLine::Unchanged(line.into()))
+            .unwrap_or_else(|| panic!("{:?} not unchanged in {:?}", line, lines))
+    };
+    let approved = at("    // ── Arm: KYC approved ──");
+    let rejected = at("    // ── Arm: KYC rejected ──");
+    // The step map at the end of the file lists every step id.
+    let step_map = at("// Step map: step id | variable | source node ids | label");
+    let changed: Vec<usize> = lines[..step_map]
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, DiffLine::Unchanged(_)))
+        .map(|(i, _)| i)
+        .collect();
+    assert!(!changed.is_empty());
+    assert!(
+        changed.iter().all(|&i| approved < i && i < rejected),
+        "{:?}",
+        lines
+    );
+}
+
+#[test]
+fn branch_only_config_fields_are_asserted_non_null_where_read() {
+    let mut http = helpers::http_get("https://example.com");
+    if let Operation::HttpRequest(op) = &mut http {
+        op.url = ValueExpr::config("webhookUrl");
+    }
+    let mut read = helpers::evm_read_op_with_args(
+        "evmClient_sepolia",
+        "0xabc",
+        "balanceOf",
+        vec![EvmArg {
+            abi_type: "uint256".into(),
+            value: ValueExpr::config("amount"),
+        }],
+    );
+    if let Operation::EvmRead(op) = &mut read {
+        op.contract_address = ValueExpr::config("token");
+    }
+    let branch = helpers::make_step(
+        "if-1",
+        helpers::branch_op(
+            ValueExpr::boolean(true),
+            ComparisonOp::Equals,
+            ValueExpr::boolean(true),
+            Block {
+                steps: vec![
+                    helpers::make_step_with_output("http-1", http, "any"),
+                    helpers::make_step_with_output("read-1", read, "any"),
+                ],
+            },
+            Block {
+                steps: vec![helpers::make_step(
+                    "return-token",
+                    helpers::return_op(ValueExpr::config("token")),
+                )],
+            },
+            None,
+        ),
+    );
+    let mut ir = helpers::ir_with_steps_and_deps(
+        vec![branch],
+        vec![],
+        vec![("ethereum-testnet-sepolia", "evmClient_sepolia", false)],
+    );
+    for name in ["webhookUrl", "amount", "token"] {
+        ir.config_schema.push(ConfigField {
+            name: name.into(),
+            zod_type: ZodType::String,
+            default_value: None,
+            description: None,
+            referenced_by: vec![],
+            optional_in_config: true,
+        });
+    }
+
+    let main_ts = &codegen(&ir).files[0].content;
+    // The schema lets config.json leave them null...
+    assert!(main_ts.contains("amount: z.string().nullable().optional(),"));
+    // ...so each read asserts the value is set when its arm runs.
+    assert!(
+        main_ts.contains("_dyn0: runtime.config.webhookUrl!,"),
+        "got:\n{}",
+        main_ts
+    );
+    assert!(main_ts.contains("_dyn0: string;"));
+    assert!(main_ts.contains("url: config._dyn0,"));
+    assert!(main_ts.contains("args: [BigInt(runtime.config.amount!)],"));
+    assert!(main_ts.contains("to: runtime.config.token!,"));
+    // A returned field still types the handler.
+    assert!(main_ts.contains("return runtime.config.token!;"));
+    assert!(main_ts.contains("triggerData: CronTrigger): string =>"));
+}
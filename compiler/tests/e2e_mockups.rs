@@ -79,7 +79,7 @@ fn compile_and_save(fixture_name: &str, output_name: &str) {
     let files: Vec<_> = output
         .files
         .iter()
-        .map(|f| json!({ "path": f.path, "content": f.content }))
+        .map(|f| json!({ "path": f.path, "content": &*f.content }))
         .collect();
 
     let result = json!({
@@ -1,5 +1,6 @@
 //! End-to-end pipeline test: Parse → Validate → Lower → IR Validate → Codegen.
 
+use compiler::ir::equivalence::equivalent_up_to_ids;
 use compiler::ir::validate_ir;
 use compiler::lower;
 use compiler::parse;
@@ -23,6 +24,1110 @@ fn end_to_end_linear_pipeline() {
 
     let output = compiler::codegen::codegen(&ir);
     dbg!(&output);
-    assert_eq!(output.files.len(), 8);
+    assert_eq!(output.files.len(), 11);
     assert!(output.files.iter().any(|f| f.path == "main.ts"));
 }
+
+#[test]
+fn end_to_end_prunes_unused_secret_with_warning() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow
+        .global_config
+        .secrets
+        .push(parse::types::SecretReference {
+            name: "UNUSED_KEY".into(),
+            env_variable: "UNUSED_KEY_VAR".into(),
+            env_variable_staging: None,
+            env_variable_production: None,
+        });
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let (ir, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty());
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w.code == "W001" && w.message.contains("UNUSED_KEY")),
+        "Expected W001 for UNUSED_KEY, got: {:?}",
+        warnings
+    );
+
+    let output = compiler::codegen::codegen(&ir);
+    let secrets_yaml = output
+        .files
+        .iter()
+        .find(|f| f.path == "secrets.yaml")
+        .expect("secrets.yaml should be generated");
+    assert!(secrets_yaml.content.contains("API_KEY"));
+    assert!(!secrets_yaml.content.contains("UNUSED_KEY"));
+}
+
+#[test]
+fn end_to_end_strict_mode_fails_on_warnings() {
+    // An unused secret is only warning W001.
+    let json = include_str!("fixtures/linear_workflow.json").replace(
+        r#"{ "name": "API_KEY", "envVariable": "API_KEY_VAR" }"#,
+        r#"{ "name": "API_KEY", "envVariable": "API_KEY_VAR" },
+            { "name": "UNUSED_KEY", "envVariable": "UNUSED_KEY_VAR" }"#,
+    );
+    let compiled = compiler::compile::compile(&json, None).unwrap();
+    assert!(compiled.warnings.iter().any(|w| w.code == "W001"));
+
+    let strict = compiler::codegen::CodegenOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let errors = compiler::compile::compile_with_options(&json, None, &strict).unwrap_err();
+    assert_eq!(
+        errors.iter().map(|e| e.code.as_str()).collect::<Vec<_>>(),
+        ["W001"]
+    );
+
+    // Without warnings, strict mode changes nothing.
+    let clean = include_str!("fixtures/linear_workflow.json");
+    assert!(compiler::compile::compile_with_options(clean, None, &strict).is_ok());
+}
+
+#[test]
+fn end_to_end_legacy_workflow_compiles_with_migration_notes() {
+    let json = include_str!("fixtures/migrations/v1_workflow.json");
+    let compiled = compiler::compile::compile(json, None).expect("Should compile after migration");
+    let infos: Vec<_> = compiled.warnings.iter().filter(|w| w.is_info()).collect();
+    assert_eq!(infos.len(), 5, "{:?}", compiled.warnings);
+    assert!(infos.iter().all(|i| i.code == "I001"));
+
+    let main_ts = &compiled.output.files[0].content;
+    assert!(main_ts.contains("maxAge: { seconds: 60n }"), "{}", main_ts);
+    assert!(main_ts.contains(" || "), "{}", main_ts);
+
+    // Migration notes are not warnings, so strict mode still compiles.
+    let strict = compiler::codegen::CodegenOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(compiler::compile::compile_with_options(json, None, &strict).is_ok());
+}
+
+#[test]
+fn end_to_end_deprecated_field_warns_without_blocking_codegen() {
+    let json = include_str!("fixtures/sample_mockup.json").replace(
+        r#""responseFormat": "text""#,
+        r#""responseFormat": "text", "maxRetries": 3"#,
+    );
+    let compiled = compiler::compile::compile(&json, None).expect("Should still compile");
+    assert!(compiled.output.files.iter().any(|f| f.path == "main.ts"));
+
+    let deprecations: Vec<_> = compiled
+        .warnings
+        .iter()
+        .filter(|w| w.code == "W018")
+        .collect();
+    assert_eq!(deprecations.len(), 1, "{:?}", compiled.warnings);
+    let warning = deprecations[0].to_json();
+    assert_eq!(warning["severity"], "warning");
+    assert!(
+        warning["path"]
+            .as_str()
+            .unwrap()
+            .ends_with(".data.config.maxRetries"),
+        "{warning}"
+    );
+    assert!(
+        warning["message"]
+            .as_str()
+            .unwrap()
+            .contains("removed in 0.3.0"),
+        "{warning}"
+    );
+
+    let errors = compiler::compile::compile("not json", None).unwrap_err();
+    assert_eq!(errors[0].to_json()["severity"], "error");
+}
+
+#[test]
+fn end_to_end_per_environment_secret_variables() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let secret = &mut workflow.global_config.secrets[0];
+    secret.env_variable_staging = Some("API_KEY_STAGING".into());
+    secret.env_variable_production = Some("API_KEY_PROD".into());
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    let secret = &ir.required_secrets[0];
+    assert_eq!(secret.env_variable, "API_KEY_VAR");
+    assert_eq!(
+        secret.env_variable_staging.as_deref(),
+        Some("API_KEY_STAGING")
+    );
+    assert_eq!(
+        secret.env_variable_production.as_deref(),
+        Some("API_KEY_PROD")
+    );
+
+    let file = |ir: &compiler::ir::WorkflowIR, path: &str| {
+        compiler::codegen::codegen(ir)
+            .files
+            .into_iter()
+            .find(|f| f.path == path)
+            .unwrap()
+            .content
+            .to_string()
+    };
+    // secrets.yaml follows the bundle's environment; .env lists both.
+    assert_eq!(
+        file(&ir, "secrets.yaml"),
+        "secretsNames:\n  API_KEY:\n    - API_KEY_STAGING\n"
+    );
+    let dot_env = file(&ir, ".env");
+    assert!(
+        dot_env.contains(
+            "# API_KEY (staging)\nAPI_KEY_STAGING=<API_KEY>\n# API_KEY (production)\nAPI_KEY_PROD=<API_KEY>\n"
+        ),
+        "{dot_env}"
+    );
+    assert!(!dot_env.contains("API_KEY_VAR"), "{dot_env}");
+
+    let mut mainnet = ir.clone();
+    mainnet.metadata.is_testnet = false;
+    assert!(file(&mainnet, "secrets.yaml").contains("    - API_KEY_PROD\n"));
+}
+
+#[test]
+fn end_to_end_output_is_deterministic_for_tied_positions() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let compile = || {
+        let mut workflow = parse::parse(json).unwrap();
+        // Two siblings of the trigger at the same canvas position as h1, so
+        // only the node-id tiebreak decides their order.
+        for id in ["h3", "h2"] {
+            workflow.nodes.push(
+                serde_json::from_value(serde_json::json!({
+                    "id": id,
+                    "type": "httpRequest",
+                    "position": { "x": 0, "y": 0 },
+                    "data": {
+                        "label": id,
+                        "config": { "method": "GET", "url": format!("https://api.example.com/{id}") }
+                    }
+                }))
+                .unwrap(),
+            );
+            workflow.edges.push(
+                serde_json::from_value(serde_json::json!({
+                    "id": format!("e-{id}"), "source": "t1", "target": id
+                }))
+                .unwrap(),
+            );
+        }
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        assert!(validate::validate_graph(&workflow, &graph).is_empty());
+        let ir = lower::lower(&workflow, &graph).unwrap();
+        assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+        let output = compiler::codegen::codegen(&ir);
+        output
+            .files
+            .into_iter()
+            .find(|f| f.path == "main.ts")
+            .expect("main.ts should be generated")
+            .content
+    };
+
+    let first = compile();
+    for _ in 0..8 {
+        assert_eq!(compile(), first);
+    }
+    let h1 = first.find("api.example.com/data").unwrap();
+    let h2 = first.find("api.example.com/h2").unwrap();
+    let h3 = first.find("api.example.com/h3").unwrap();
+    assert!(
+        h1 < h2 && h2 < h3,
+        "siblings should be ordered by node id:\n{first}"
+    );
+}
+
+#[test]
+fn end_to_end_kyc_compiles_byte_identically() {
+    let compile = || {
+        let workflow = parse::parse(include_str!("fixtures/example_workflow.json")).unwrap();
+        compile_with(&workflow, &lower::LowerOptions::default())
+    };
+
+    let first = compile();
+    for _ in 0..20 {
+        assert_eq!(compile(), first);
+    }
+}
+
+/// The linear workflow with h1's `headers` object written in `keys` order.
+fn linear_with_headers(keys: &[&str]) -> parse::Workflow {
+    let headers: Vec<String> = keys
+        .iter()
+        .map(|key| match *key {
+            "Accept" => r#""Accept": "application/json""#,
+            "X-Trace" => r#""X-Trace": "{{trigger.scheduledTime}}""#,
+            _ => r#""X-Zone": "{{trigger.actualTime}}""#,
+        })
+        .map(String::from)
+        .collect();
+    // Written out by hand: `serde_json::json!` would sort the keys itself.
+    let json = include_str!("fixtures/linear_workflow.json").replace(
+        r#""responseFormat": "json""#,
+        &format!(
+            r#""responseFormat": "json", "headers": {{ {} }}"#,
+            headers.join(", ")
+        ),
+    );
+    parse::parse(&json).unwrap()
+}
+
+#[test]
+fn end_to_end_headers_are_emitted_sorted_by_name() {
+    let default = lower::LowerOptions::default();
+    let sorted = compile_with(
+        &linear_with_headers(&["Accept", "X-Trace", "X-Zone"]),
+        &default,
+    );
+    for keys in [
+        ["X-Zone", "Accept", "X-Trace"],
+        ["X-Trace", "X-Zone", "Accept"],
+    ] {
+        assert_eq!(compile_with(&linear_with_headers(&keys), &default), sorted);
+    }
+
+    let main_ts = &sorted.iter().find(|(path, _)| path == "main.ts").unwrap().1;
+    let accept = main_ts.find(r#""Accept""#).unwrap();
+    let trace = main_ts.find(r#""X-Trace""#).unwrap();
+    let zone = main_ts.find(r#""X-Zone""#).unwrap();
+    assert!(accept < trace && trace < zone, "{main_ts}");
+    // Handler-scoped values reach the fetch function in the same order.
+    let dyn0 = main_ts.find("_dyn0: triggerData.scheduledTime").unwrap();
+    let dyn1 = main_ts.find("_dyn1: triggerData.actualTime").unwrap();
+    assert!(dyn0 < dyn1, "{main_ts}");
+}
+
+/// The linear workflow with h1's bearer authentication replaced by `config`
+/// (a JSON fragment of h1's config).
+fn linear_with_h1_config(config: &str) -> parse::Workflow {
+    let json = include_str!("fixtures/linear_workflow.json").replace(
+        r#""authentication": { "type": "bearerToken", "tokenSecret": "API_KEY" },"#,
+        config,
+    );
+    parse::parse(&json).unwrap()
+}
+
+fn lower_err_codes(workflow: &parse::Workflow) -> Vec<String> {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    lower::lower(workflow, &graph)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.code)
+        .collect()
+}
+
+/// The linear workflow with filter f1 (on h1's status) between h1 and r1;
+/// `config` is merged into f1's config.
+fn linear_with_filter(config: serde_json::Value) -> parse::Workflow {
+    let mut value: serde_json::Value =
+        serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+    let mut filter_config = serde_json::json!({
+        "conditions": [{ "field": "{{h1.statusCode}}", "operator": "equals", "value": "200" }],
+        "combineWith": "and"
+    });
+    filter_config
+        .as_object_mut()
+        .unwrap()
+        .extend(config.as_object().unwrap().clone());
+    value["nodes"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({
+            "id": "f1",
+            "type": "filter",
+            "position": { "x": 0, "y": 0 },
+            "data": { "label": "Only OK", "config": filter_config }
+        }));
+    value["edges"] = serde_json::json!([
+        { "id": "e1", "source": "t1", "target": "h1" },
+        { "id": "e2", "source": "h1", "target": "f1" },
+        { "id": "e3", "source": "f1", "target": "r1" }
+    ]);
+    parse::parse(&value.to_string()).unwrap()
+}
+
+#[test]
+fn end_to_end_filter_returns_its_templated_non_match_message() {
+    let workflow = linear_with_filter(serde_json::json!({
+        "nonMatchMessage": "Skipped run at {{trigger.scheduledTime}}"
+    }));
+    let files = compile_with(&workflow, &lower::LowerOptions::default());
+    let main_ts = &files.iter().find(|(path, _)| path == "main.ts").unwrap().1;
+    assert!(
+        main_ts.contains("return `Skipped run at ${triggerData.scheduledTime}`;"),
+        "{main_ts}"
+    );
+    assert!(!main_ts.contains("Filter condition not met"), "{main_ts}");
+
+    // Without a message the default is returned.
+    let files = compile_with(
+        &linear_with_filter(serde_json::json!({})),
+        &Default::default(),
+    );
+    let main_ts = &files.iter().find(|(path, _)| path == "main.ts").unwrap().1;
+    assert!(
+        main_ts.contains(r#"return "Filter condition not met";"#),
+        "{main_ts}"
+    );
+}
+
+#[test]
+fn end_to_end_secret_header_is_fetched_not_embedded() {
+    let workflow = linear_with_h1_config(r#""headers": { "X-API-Key": "{{secret.API_KEY}}" },"#);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+    let (ir, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty());
+    // The header is the secret's only use, so it is not pruned.
+    assert!(warnings.iter().all(|w| w.code != "W001"), "{warnings:?}");
+    assert_eq!(ir.required_secrets.len(), 1);
+
+    let files = compile_with(&workflow, &lower::LowerOptions::default());
+    let file = |path: &str| &files.iter().find(|(p, _)| p == path).unwrap().1;
+    let main_ts = file("main.ts");
+    assert!(
+        main_ts.contains(r#"_dyn0: runtime.getSecret({ id: "API_KEY" }).result().value"#),
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains(r#""X-API-Key": config._dyn0"#),
+        "{main_ts}"
+    );
+    assert!(!main_ts.contains(r#""X-API-Key": "API_KEY""#), "{main_ts}");
+    assert!(file("secrets.yaml").contains("API_KEY"));
+}
+
+#[test]
+fn end_to_end_secret_in_url_template_is_passed_to_fetch() {
+    let json = include_str!("fixtures/linear_workflow.json")
+        .replace(
+            r#""authentication": { "type": "bearerToken", "tokenSecret": "API_KEY" },"#,
+            "",
+        )
+        .replace(
+            "https://api.example.com/data",
+            "https://api.example.com/data?key={{secrets.API_KEY}}",
+        );
+    let workflow = parse::parse(&json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+    let files = compile_with(&workflow, &lower::LowerOptions::default());
+    let main_ts = &files.iter().find(|(p, _)| p == "main.ts").unwrap().1;
+    assert!(
+        main_ts.contains(r#"_dyn0: runtime.getSecret({ id: "API_KEY" }).result().value"#),
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains("`https://api.example.com/data?key=${config._dyn0}`"),
+        "{main_ts}"
+    );
+}
+
+#[test]
+fn end_to_end_secret_must_be_declared_and_not_a_query_parameter() {
+    let undeclared =
+        linear_with_h1_config(r#""headers": { "X-API-Key": "{{secrets.OTHER_KEY}}" },"#);
+    let graph = parse::WorkflowGraph::build(&undeclared).unwrap();
+    let errors = lower::lower(&undeclared, &graph).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "L017");
+    assert_eq!(
+        compiler::error::format_json_path(&errors[0].path),
+        "nodes[1].data.config.headers.X-API-Key"
+    );
+
+    let in_query = linear_with_h1_config(r#""queryParameters": { "key": "{{secret.API_KEY}}" },"#);
+    assert_eq!(lower_err_codes(&in_query), ["L016"]);
+
+    // Environment variables do not exist once the workflow runs.
+    let env = linear_with_h1_config(r#""headers": { "X-API-Key": "{{env.API_KEY}}" },"#);
+    let graph = parse::WorkflowGraph::build(&env).unwrap();
+    let errors = lower::lower(&env, &graph).unwrap_err();
+    assert_eq!(errors[0].code, "L010");
+    let message = &errors[0].message;
+    assert!(
+        message.contains("environment variables are not available"),
+        "{message}"
+    );
+}
+
+/// t1 → h1 (given response format) → code-1 reading `{{h1.body}}` → r1.
+fn http_into_code_node(response_format: &str) -> (compiler::ir::WorkflowIR, String) {
+    let workflow = http_into_code_workflow(
+        response_format,
+        serde_json::json!({
+            "code": "const size = h1_body.length;",
+            "executionMode": "runOnceForAll",
+            "inputVariables": ["{{h1.body}}"],
+            "outputFields": [{ "key": "size", "type": "number" }]
+        }),
+    );
+
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.is_empty(), "Validation errors: {:?}", errors);
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content
+        .to_string();
+    (ir, main_ts)
+}
+
+/// t1 → h1 → code-1 (given config) → r1.
+fn http_into_code_workflow(
+    response_format: &str,
+    code_config: serde_json::Value,
+) -> parse::Workflow {
+    serde_json::from_value(serde_json::json!({
+        "id": "response-formats",
+        "name": "Response Formats",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "cronTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+            },
+            {
+                "id": "h1",
+                "type": "httpRequest",
+                "position": { "x": 0, "y": 100 },
+                "data": {
+                    "label": "Fetch",
+                    "config": {
+                        "method": "GET",
+                        "url": "https://api.example.com/report",
+                        "responseFormat": response_format
+                    }
+                }
+            },
+            {
+                "id": "code-1",
+                "type": "codeNode",
+                "position": { "x": 0, "y": 200 },
+                "data": { "label": "Inspect", "config": code_config }
+            },
+            {
+                "id": "r1",
+                "type": "return",
+                "position": { "x": 0, "y": 300 },
+                "data": { "label": "Done", "config": { "returnExpression": "done" } }
+            }
+        ],
+        "edges": [
+            { "id": "e1", "source": "t1", "target": "h1" },
+            { "id": "e2", "source": "h1", "target": "code-1" },
+            { "id": "e3", "source": "code-1", "target": "r1" }
+        ],
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap()
+}
+
+#[test]
+fn end_to_end_http_response_formats_decode_body_once() {
+    for (format, decode, body_type) in [
+        (
+            "json",
+            "JSON.parse(Buffer.from(resp.body).toString(\"utf-8\"))",
+            "any",
+        ),
+        (
+            "text",
+            "Buffer.from(resp.body).toString(\"utf-8\")",
+            "string",
+        ),
+        (
+            "binary",
+            "Buffer.from(resp.body).toString(\"base64\")",
+            "string",
+        ),
+    ] {
+        let (ir, main_ts) = http_into_code_node(format);
+
+        let http_output = ir.handler_body.steps[0].output.as_ref().unwrap();
+        assert_eq!(
+            http_output.ts_type,
+            format!(
+                "{{ statusCode: number; body: {}; headers: Record<string, string> }}",
+                body_type
+            )
+        );
+        assert!(
+            main_ts.contains(&format!(
+                "return {{ statusCode: resp.statusCode, body: {}, headers: resp.headers }};",
+                decode
+            )),
+            "{} main.ts:\n{}",
+            format,
+            main_ts
+        );
+        // The code node gets the already-decoded body, with no second decode.
+        assert!(
+            main_ts.contains("const h1_body = step_h1.body;"),
+            "{}",
+            main_ts
+        );
+        assert_eq!(main_ts.matches("resp.body").count(), 1, "{}", main_ts);
+    }
+}
+
+#[test]
+fn end_to_end_run_once_for_each_maps_over_the_iterated_input() {
+    let workflow = http_into_code_workflow(
+        "json",
+        serde_json::json!({
+            "code": "const total = h1_body.price * config_rate;",
+            "executionMode": "runOnceForEach",
+            "inputVariables": ["{{h1.body}}", "{{config.rate}}"],
+            "iterateOver": "{{h1.body}}",
+            "outputFields": [{ "key": "total", "type": "number" }]
+        }),
+    );
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+    let code = &ir.handler_body.steps[1];
+    assert_eq!(code.output.as_ref().unwrap().ts_type, "any[]");
+
+    let main_ts = &compiler::codegen::codegen(&ir).files[0].content;
+    let expected = [
+        "      const h1_body = step_h1.body;",
+        "      const config_rate = runtime.config.rate;",
+        "      return h1_body.map((h1_body) => {",
+        "        const total = h1_body.price * config_rate;",
+        "        return { total };",
+        "      });",
+        "    })();",
+    ]
+    .join("\n");
+    assert!(main_ts.contains(&expected), "main.ts:\n{}", main_ts);
+
+    // Run-once nodes keep the single IIFE.
+    let (ir, main_ts) = http_into_code_node("json");
+    assert_eq!(
+        ir.handler_body.steps[1].output.as_ref().unwrap().ts_type,
+        "any"
+    );
+    assert!(!main_ts.contains(".map("), "{}", main_ts);
+    assert!(
+        main_ts.contains("      const size = h1_body.length;\n      return { size };\n    })();")
+    );
+}
+
+#[test]
+fn end_to_end_run_once_for_each_requires_a_declared_iteration_source() {
+    let lower_with = |iterate_over: Option<&str>| {
+        let mut config = serde_json::json!({
+            "code": "const total = h1_body.price;",
+            "executionMode": "runOnceForEach",
+            "inputVariables": ["{{h1.body}}"],
+            "outputFields": [{ "key": "total", "type": "number" }]
+        });
+        if let Some(source) = iterate_over {
+            config["iterateOver"] = source.into();
+        }
+        let workflow = http_into_code_workflow("json", config);
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        lower::lower(&workflow, &graph).unwrap_err()
+    };
+
+    let errors = lower_with(None);
+    assert_eq!(errors[0].code, "L014");
+    assert_eq!(errors[0].node_id.as_deref(), Some("code-1"));
+
+    let errors = lower_with(Some("prices"));
+    assert_eq!(errors[0].code, "L015");
+    assert!(
+        errors[0].message.contains("'prices'"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(
+        errors[0].to_json()["path"],
+        "nodes[2].data.config.iterateOver"
+    );
+}
+
+#[test]
+fn end_to_end_audit_json_lists_steps_with_notes_and_resources() {
+    let json = include_str!("fixtures/example_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let mut raw = serde_json::to_value(&workflow).unwrap();
+    raw["nodes"][4]["settings"] = serde_json::json!({ "notes": "Approved by risk team 2024-06" });
+    workflow = serde_json::from_value(raw).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty());
+
+    let output = compiler::codegen::codegen(&ir);
+    let audit = output
+        .files
+        .iter()
+        .find(|f| f.path == "audit.json")
+        .unwrap();
+    let audit: serde_json::Value = serde_json::from_str(&audit.content).unwrap();
+
+    assert_eq!(audit["workflowId"], workflow.id.as_str());
+    assert_eq!(audit["workflowVersion"], "1.0.0");
+    assert_eq!(
+        audit["description"],
+        "Mint tokens only for KYC-approved users"
+    );
+    let steps = audit["steps"].as_array().unwrap();
+    let ids: Vec<&str> = steps.iter().map(|s| s["id"].as_str().unwrap()).collect();
+    assert_eq!(
+        ids,
+        [
+            "http-1",
+            "condition-1",
+            "encode-1",
+            "write-1",
+            "return-1",
+            "return-2"
+        ]
+    );
+    let step = |id: &str| steps.iter().find(|s| s["id"] == id).unwrap();
+    assert_eq!(step("http-1")["operation"], "HttpRequest");
+    assert_eq!(
+        step("http-1")["secrets"],
+        serde_json::json!(["KYC_API_KEY"])
+    );
+    assert_eq!(step("write-1")["secrets"], serde_json::json!([]));
+    assert_eq!(
+        step("write-1")["chains"],
+        serde_json::json!(["ethereum-testnet-sepolia"])
+    );
+    assert_eq!(step("write-1")["notes"], "Approved by risk team 2024-06");
+    assert!(step("http-1")["notes"].is_null());
+}
+
+#[test]
+fn end_to_end_capability_report_for_kyc_example() {
+    let json = include_str!("fixtures/example_workflow.json");
+    let workflow = parse::parse(json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).unwrap();
+
+    let report = compiler::ir::capability_report(&ir);
+
+    // http-1, then write-1 in the approved arm.
+    assert_eq!((report.http_calls.used, report.http_calls.limit), (1, 5));
+    assert_eq!((report.evm_reads.used, report.evm_reads.limit), (0, 10));
+    assert_eq!((report.evm_writes.used, report.evm_writes.limit), (1, 5));
+    assert_eq!(report.ai_calls, 0);
+    assert_eq!(report.secrets, ["KYC_API_KEY"]);
+    assert_eq!(report.chains, ["ethereum-testnet-sepolia"]);
+    assert_eq!(report.config_fields, ["schedule"]);
+    assert_eq!(
+        serde_json::to_value(&report).unwrap()["http_calls"],
+        serde_json::json!({ "used": 1, "limit": 5 })
+    );
+}
+
+#[test]
+fn end_to_end_produced_bindings_for_kyc_example() {
+    use compiler::ir::bindings::{bindings_visible_at, produced_bindings};
+
+    let json = include_str!("fixtures/example_workflow.json");
+    let workflow = parse::parse(json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).unwrap();
+
+    let produced = produced_bindings(&ir);
+    let listed: Vec<(&str, &str, Vec<&str>)> = produced
+        .iter()
+        .map(|b| {
+            let scope = b.scope.iter().map(String::as_str).collect();
+            (b.step_id.as_str(), b.binding.variable_name.as_str(), scope)
+        })
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            ("http-1", "step_http_1", vec![]),
+            ("encode-1", "step_encode_1", vec!["condition-1", "true"]),
+            ("write-1", "step_write_1", vec!["condition-1", "true"]),
+        ]
+    );
+    assert_eq!(
+        produced[0].binding.ts_type,
+        "{ statusCode: number; body: any; headers: Record<string, string> }"
+    );
+    assert_eq!(produced[1].binding.ts_type, "{ encoded: string }");
+
+    let visible = |step_id| -> Vec<String> {
+        bindings_visible_at(&ir, step_id)
+            .into_iter()
+            .map(|b| b.step_id)
+            .collect()
+    };
+    assert_eq!(visible("http-1"), Vec::<String>::new());
+    assert_eq!(visible("return-1"), ["http-1", "encode-1", "write-1"]);
+    // The rejected arm cannot see what the approved arm produced.
+    assert_eq!(visible("return-2"), ["http-1"]);
+    assert_eq!(visible("no-such-step"), Vec::<String>::new());
+}
+
+#[test]
+fn end_to_end_evm_log_trigger_shares_one_chain_client() {
+    let workflow: parse::Workflow = serde_json::from_value(serde_json::json!({
+        "id": "evm-log",
+        "name": "EVM Log",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "evmLogTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": {
+                    "label": "On Transfer",
+                    "config": {
+                        "chainSelectorName": "ethereum-testnet-sepolia",
+                        "contractAddresses": ["0x41f77d6aa3F8C8113Bc95831490D5206c5d1cFeE"],
+                        "eventSignature": "Transfer(address,address,uint256)",
+                        "eventAbi": {
+                            "type": "event",
+                            "name": "Transfer",
+                            "inputs": [
+                                { "name": "from", "type": "address", "indexed": true },
+                                { "name": "to", "type": "address", "indexed": true },
+                                { "name": "value", "type": "uint256", "indexed": false }
+                            ]
+                        },
+                        "topicFilters": {
+                            "topic2": ["0x0000000000000000000000000000000000000000000000000000000000000001"]
+                        }
+                    }
+                }
+            },
+            {
+                "id": "read-1",
+                "type": "evmRead",
+                "position": { "x": 0, "y": 100 },
+                "data": {
+                    "label": "Read Supply",
+                    "config": {
+                        "chainSelectorName": "ethereum-testnet-sepolia",
+                        "contractAddress": "0x41f77d6aa3F8C8113Bc95831490D5206c5d1cFeE",
+                        "functionName": "totalSupply",
+                        "args": [],
+                        "abi": {
+                            "type": "function",
+                            "name": "totalSupply",
+                            "inputs": [],
+                            "outputs": [{ "name": "", "type": "uint256" }],
+                            "stateMutability": "view"
+                        }
+                    }
+                }
+            },
+            {
+                "id": "r1",
+                "type": "return",
+                "position": { "x": 0, "y": 200 },
+                "data": { "label": "Done", "config": { "returnExpression": "ok" } }
+            }
+        ],
+        "edges": [
+            { "id": "e1", "source": "t1", "target": "read-1" },
+            { "id": "e2", "source": "read-1", "target": "r1" }
+        ],
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap();
+
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.is_empty(), "Validation errors: {:?}", errors);
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+
+    let client = "evmClient_ethereum_testnet_sepolia";
+    assert_eq!(
+        main_ts.matches("new cre.capabilities.EVMClient(").count(),
+        1,
+        "{}",
+        main_ts
+    );
+    let construction = main_ts
+        .find(&format!(
+            "const {client} = new cre.capabilities.EVMClient(getNetwork({{ chainFamily: \"evm\", chainSelectorName: \"ethereum-testnet-sepolia\", isTestnet: true }})!.chainSelector.selector);"
+        ))
+        .expect("trigger chain client at module scope");
+    let handler = main_ts.find("const onLogTrigger").unwrap();
+    assert!(construction < handler, "{}", main_ts);
+    assert!(
+        main_ts.contains(&format!("{client}.logTrigger({{")),
+        "{}",
+        main_ts
+    );
+    assert!(
+        main_ts.contains(&format!("{client}.callContract(")),
+        "{}",
+        main_ts
+    );
+    // topic2 is filtered, so topic1 is left open.
+    assert!(
+        main_ts.contains(
+            "topics: [\n          { values: [eventTopicHash] },\n          { values: [] },\n          { values: [\"0x0000000000000000000000000000000000000000000000000000000000000001\"] },\n        ],"
+        ),
+        "{}",
+        main_ts
+    );
+}
+
+/// "Process only new blocks": remember the last block seen in the KV store
+/// and only act when the chain has moved on.
+#[test]
+fn end_to_end_kv_state_processes_only_new_blocks() {
+    let workflow: parse::Workflow = serde_json::from_value(serde_json::json!({
+        "id": "new-blocks",
+        "name": "New Blocks",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "cronTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": { "label": "Every Minute", "config": { "schedule": "0 * * * * *" } }
+            },
+            {
+                "id": "read-1",
+                "type": "evmRead",
+                "position": { "x": 0, "y": 100 },
+                "data": {
+                    "label": "Latest Block",
+                    "config": {
+                        "chainSelectorName": "ethereum-testnet-sepolia",
+                        "contractAddress": "0xcA11bde05977b3631167028862bE2a173976CA11",
+                        "functionName": "getBlockNumber",
+                        "args": [],
+                        "abi": {
+                            "type": "function",
+                            "name": "getBlockNumber",
+                            "inputs": [],
+                            "outputs": [{ "name": "blockNumber", "type": "uint256" }],
+                            "stateMutability": "view"
+                        }
+                    }
+                }
+            },
+            {
+                "id": "kv-get-1",
+                "type": "kvGet",
+                "position": { "x": 0, "y": 200 },
+                "data": { "label": "Last Block", "config": { "key": "lastBlock" } }
+            },
+            {
+                "id": "if-1",
+                "type": "if",
+                "position": { "x": 0, "y": 300 },
+                "data": {
+                    "label": "New Block?",
+                    "config": {
+                        "conditions": [
+                            { "field": "{{kv-get-1}}", "operator": "notExists" },
+                            { "field": "{{read-1.blockNumber}}", "operator": "gt", "value": "{{kv-get-1}}" }
+                        ],
+                        "combineWith": "or"
+                    }
+                }
+            },
+            {
+                "id": "kv-set-1",
+                "type": "kvSet",
+                "position": { "x": -100, "y": 400 },
+                "data": {
+                    "label": "Save Block",
+                    "config": { "key": "lastBlock", "value": "{{read-1.blockNumber}}" }
+                }
+            },
+            {
+                "id": "r-new",
+                "type": "return",
+                "position": { "x": -100, "y": 500 },
+                "data": { "label": "Processed", "config": { "returnExpression": "processed" } }
+            },
+            {
+                "id": "r-seen",
+                "type": "return",
+                "position": { "x": 100, "y": 400 },
+                "data": { "label": "Seen", "config": { "returnExpression": "already processed" } }
+            }
+        ],
+        "edges": [
+            { "id": "e1", "source": "t1", "target": "read-1" },
+            { "id": "e2", "source": "read-1", "target": "kv-get-1" },
+            { "id": "e3", "source": "kv-get-1", "target": "if-1" },
+            { "id": "e4", "source": "if-1", "target": "kv-set-1", "sourceHandle": "true" },
+            { "id": "e5", "source": "kv-set-1", "target": "r-new" },
+            { "id": "e6", "source": "if-1", "target": "r-seen", "sourceHandle": "false" }
+        ],
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap();
+
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.is_empty(), "Validation errors: {:?}", errors);
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+
+    let report = compiler::ir::capability_report(&ir);
+    assert_eq!(
+        (report.kv_operations.used, report.kv_operations.limit),
+        (2, 10)
+    );
+
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert_eq!(
+        main_ts
+            .matches("const kvClient = new cre.capabilities.KeyValueClient();")
+            .count(),
+        1,
+        "{}",
+        main_ts
+    );
+    assert!(
+        main_ts.contains(
+            "const step_kv_get_1: string | undefined = kvClient.get(runtime, { key: \"lastBlock\" }).result().value;"
+        ),
+        "{}",
+        main_ts
+    );
+    let branch = main_ts
+        .find("if (step_kv_get_1 == null || step_read_1.blockNumber > step_kv_get_1) {")
+        .expect("branch on the stored block");
+    let set = main_ts
+        .find("kvClient.set(runtime, { key: \"lastBlock\", value: String(step_read_1.blockNumber) }).result();")
+        .expect("store the new block");
+    let processed = main_ts.find("return \"processed\";").unwrap();
+    let else_arm = main_ts.find("} else {").unwrap();
+    assert!(
+        branch < set && set < processed && processed < else_arm,
+        "{}",
+        main_ts
+    );
+
+    // globalConfig.maxKvOperations lowers the budget.
+    let mut limited = workflow.clone();
+    limited.global_config.max_kv_operations = Some(1);
+    let ir = lower::lower(&limited, &graph).unwrap();
+    assert!(validate_ir(&ir).iter().any(|e| e.code == "E018"));
+}
+
+/// The KYC example as another editor session might export it: every node id
+/// prefixed with `id_prefix`, nodes and edges in reverse order, and every
+/// node moved by `offset`.
+fn kyc_copy(id_prefix: &str, offset: f64) -> parse::Workflow {
+    let mut json = include_str!("fixtures/example_workflow.json").to_string();
+    let workflow = parse::parse(&json).unwrap();
+    for node in &workflow.nodes {
+        let id = node.id();
+        json = json
+            .replace(&format!("\"{}\"", id), &format!("\"{}{}\"", id_prefix, id))
+            .replace(
+                &format!("{{{{{}.", id),
+                &format!("{{{{{}{}.", id_prefix, id),
+            );
+    }
+    let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    for list in ["nodes", "edges"] {
+        value[list].as_array_mut().unwrap().reverse();
+    }
+    for node in value["nodes"].as_array_mut().unwrap() {
+        let x = node["position"]["x"].as_f64().unwrap();
+        node["position"]["x"] = (x + offset).into();
+    }
+    parse::parse(&value.to_string()).unwrap()
+}
+
+fn compile_with(
+    workflow: &parse::Workflow,
+    options: &lower::LowerOptions,
+) -> Vec<(String, String)> {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    let (ir, _) = lower::lower_with_options(workflow, &graph, options).unwrap();
+    assert!(validate_ir(&ir).is_empty());
+    compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .map(|f| (f.path, f.content.to_string()))
+        .collect()
+}
+
+#[test]
+fn end_to_end_canonical_kyc_copies_compile_identically() {
+    let a = kyc_copy("a-", 0.0);
+    let b = kyc_copy("node_", 250.0);
+
+    let canonical_a = serde_json::to_value(parse::canonicalize(&a)).unwrap();
+    let canonical_b = serde_json::to_value(parse::canonicalize(&b)).unwrap();
+    assert_eq!(canonical_a, canonical_b);
+    assert_eq!(canonical_a["nodes"][1]["id"], "httpRequest-1");
+
+    let default = lower::LowerOptions::default();
+    assert_ne!(compile_with(&a, &default), compile_with(&b, &default));
+    let canonical = lower::LowerOptions {
+        canonicalize: true,
+        ..Default::default()
+    };
+    assert_eq!(compile_with(&a, &canonical), compile_with(&b, &canonical));
+}
+
+#[test]
+fn end_to_end_canonical_kyc_lowers_to_an_equivalent_ir() {
+    let workflow = parse::parse(include_str!("fixtures/example_workflow.json")).unwrap();
+    let canonical = parse::canonicalize(&workflow);
+    let lower_ir = |workflow: &parse::Workflow| {
+        let graph = parse::WorkflowGraph::build(workflow).unwrap();
+        lower::lower(workflow, &graph).unwrap()
+    };
+    let (ir, canonical_ir) = (lower_ir(&workflow), lower_ir(&canonical));
+
+    let renames = parse::canonical::canonical_ids(&workflow);
+    assert_eq!(renames["encode-1"], "abiEncode-1");
+    assert!(equivalent_up_to_ids(&ir, &canonical_ir, &renames));
+    assert!(!equivalent_up_to_ids(
+        &ir,
+        &canonical_ir,
+        &Default::default()
+    ));
+}
@@ -0,0 +1,88 @@
+//! Property tests: arbitrary-but-deserializable workflows must never panic the
+//! pipeline. Errors are fine; panics are bugs.
+
+use std::collections::HashSet;
+
+use compiler::codegen::codegen;
+use compiler::ir::types::{Block, Operation, WorkflowIR};
+use compiler::ir::validate_ir;
+use compiler::lower;
+use compiler::parse;
+use compiler::testing::arb_workflow;
+use compiler::validate;
+use proptest::prelude::*;
+
+fn collect_step_ids(block: &Block, ids: &mut HashSet<String>) {
+    for step in &block.steps {
+        ids.insert(step.id.clone());
+        if let Operation::Branch(branch) = &step.operation {
+            collect_step_ids(&branch.true_branch, ids);
+            collect_step_ids(&branch.false_branch, ids);
+        }
+    }
+}
+
+/// Parse → graph → validate → lower, returning the IR when every stage passes.
+fn lower_valid(json: &str) -> Option<WorkflowIR> {
+    let workflow = parse::parse(json).ok()?;
+    let graph = parse::WorkflowGraph::build(&workflow).ok()?;
+    if !validate::validate_graph(&workflow, &graph).is_empty() {
+        return None;
+    }
+    lower::lower(&workflow, &graph).ok()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn pipeline_never_panics(workflow in arb_workflow()) {
+        let json = serde_json::to_string(&workflow).unwrap();
+        if let Ok(workflow) = parse::parse(&json) {
+            if let Ok(graph) = parse::WorkflowGraph::build(&workflow) {
+                let _ = validate::validate_graph(&workflow, &graph);
+                // Lowering must cope with graphs validation would have rejected.
+                if let Ok(ir) = lower::lower(&workflow, &graph) {
+                    let _ = validate_ir(&ir);
+                    let _ = codegen(&ir);
+                }
+            }
+            for node in &workflow.nodes {
+                let _ = validate::validate_node(node, &workflow.global_config);
+            }
+        }
+    }
+
+    #[test]
+    fn ir_errors_point_at_existing_steps(workflow in arb_workflow()) {
+        let json = serde_json::to_string(&workflow).unwrap();
+        if let Some(ir) = lower_valid(&json) {
+            let mut step_ids = HashSet::new();
+            collect_step_ids(&ir.handler_body, &mut step_ids);
+            for error in validate_ir(&ir) {
+                if let Some(step_id) = &error.step_id {
+                    prop_assert!(
+                        step_ids.contains(step_id),
+                        "{} refers to unknown step '{}'", error, step_id
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_json_files_parse(workflow in arb_workflow()) {
+        let json = serde_json::to_string(&workflow).unwrap();
+        if let Some(ir) = lower_valid(&json) {
+            prop_assume!(validate_ir(&ir).is_empty());
+            for file in codegen(&ir).files {
+                if file.path.ends_with(".json") {
+                    prop_assert!(
+                        serde_json::from_str::<serde_json::Value>(&file.content).is_ok(),
+                        "{} is not valid JSON:\n{}", file.path, file.content
+                    );
+                }
+            }
+        }
+    }
+}
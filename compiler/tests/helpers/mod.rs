@@ -19,6 +19,7 @@ pub fn branching_workflow_ir() -> WorkflowIR {
             version: "1.0.0".into(),
             is_testnet: true,
             default_chain_selector: Some("ethereum-testnet-sepolia".into()),
+            max_kv_operations: None,
         },
         trigger: TriggerDef::Cron(CronTriggerDef {
             schedule: ValueExpr::config("schedule"),
@@ -30,23 +31,33 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                 zod_type: ZodType::String,
                 default_value: Some("TZ=UTC 0 */10 * * * *".into()),
                 description: Some("Cron schedule (min 30s interval)".into()),
+                referenced_by: vec![],
+                optional_in_config: false,
             },
             ConfigField {
                 name: "walletAddress".into(),
                 zod_type: ZodType::String,
                 default_value: None,
                 description: Some("Wallet address to check".into()),
+                referenced_by: vec![],
+                optional_in_config: false,
             },
             ConfigField {
                 name: "receiverAddress".into(),
                 zod_type: ZodType::String,
                 default_value: None,
                 description: Some("Receiver contract address".into()),
+                referenced_by: vec![],
+                optional_in_config: false,
             },
         ],
         required_secrets: vec![SecretDeclaration {
             name: "KYC_API_KEY".into(),
             env_variable: "KYC_API_KEY_VAR".into(),
+            env_variable_staging: None,
+            env_variable_production: None,
+            referenced_by: vec![],
+            optional_in_config: false,
         }],
         evm_chains: vec![EvmChainUsage {
             chain_selector_name: "ethereum-testnet-sepolia".into(),
@@ -82,6 +93,8 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                         timeout_ms: Some(5000),
                         expected_status_codes: vec![200],
                         response_format: HttpResponseFormat::Json,
+                        follow_redirects: true,
+                        ignore_ssl: false,
                         consensus: ConsensusStrategy::Identical,
                     }),
                     output: Some(OutputBinding {
@@ -91,17 +104,20 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                                 .into(),
                         destructure_fields: None,
                     }),
+                    notes: None,
                 },
                 Step {
                     id: "condition-1".into(),
                     source_node_ids: vec!["condition-1".into()],
                     label: "Check if approved".into(),
                     operation: Operation::Branch(BranchOp {
-                        conditions: vec![ConditionIR {
+                        conditions: vec![ConditionTree::Leaf(ConditionIR {
                             field: ValueExpr::binding("http-1", "body.isApproved"),
                             operator: ComparisonOp::Equals,
                             value: Some(ValueExpr::boolean(true)),
-                        }],
+                            field_kind: None,
+                            value_kind: None,
+                        })],
                         combine_with: LogicCombinator::And,
                         true_branch: Block {
                             steps: vec![
@@ -121,6 +137,7 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                                         ts_type: "{ txHash: string; status: string }".into(),
                                         destructure_fields: None,
                                     }),
+                                    notes: None,
                                 },
                                 Step {
                                     id: "return-1".into(),
@@ -128,8 +145,10 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                                     label: "Return success".into(),
                                     operation: Operation::Return(ReturnOp {
                                         expression: ValueExpr::string("Minted successfully"),
+                                        auto_generated: false,
                                     }),
                                     output: None,
+                                    notes: None,
                                 },
                             ],
                         },
@@ -140,13 +159,18 @@ pub fn branching_workflow_ir() -> WorkflowIR {
                                 label: "Return rejection".into(),
                                 operation: Operation::Return(ReturnOp {
                                     expression: ValueExpr::string("KYC not approved"),
+                                    auto_generated: false,
                                 }),
                                 output: None,
+                                notes: None,
                             }],
                         },
+                        true_label: None,
+                        false_label: None,
                         reconverge_at: None,
                     }),
                     output: None,
+                    notes: None,
                 },
             ],
         },
@@ -167,6 +191,7 @@ pub fn base_ir() -> WorkflowIR {
             version: "1.0.0".into(),
             is_testnet: true,
             default_chain_selector: None,
+            max_kv_operations: None,
         },
         trigger: TriggerDef::Cron(CronTriggerDef {
             schedule: ValueExpr::config("schedule"),
@@ -177,6 +202,8 @@ pub fn base_ir() -> WorkflowIR {
             zod_type: ZodType::String,
             default_value: Some("0 */5 * * * *".into()),
             description: None,
+            referenced_by: vec![],
+            optional_in_config: false,
         }],
         required_secrets: vec![],
         evm_chains: vec![],
@@ -188,8 +215,10 @@ pub fn base_ir() -> WorkflowIR {
                 label: "Return".into(),
                 operation: Operation::Return(ReturnOp {
                     expression: ValueExpr::string("ok"),
+                    auto_generated: false,
                 }),
                 output: None,
+                notes: None,
             }],
         },
     }
@@ -205,8 +234,10 @@ pub fn ir_with_steps(steps: Vec<Step>) -> WorkflowIR {
         label: "Return".into(),
         operation: Operation::Return(ReturnOp {
             expression: ValueExpr::string("ok"),
+            auto_generated: false,
         }),
         output: None,
+        notes: None,
     });
     ir.handler_body.steps = all_steps;
     ir
@@ -224,6 +255,10 @@ pub fn ir_with_steps_and_deps(
         .map(|(name, env)| SecretDeclaration {
             name: name.into(),
             env_variable: env.into(),
+            env_variable_staging: None,
+            env_variable_production: None,
+            referenced_by: vec![],
+            optional_in_config: false,
         })
         .collect();
     ir.evm_chains = evm_chains
@@ -237,6 +272,157 @@ pub fn ir_with_steps_and_deps(
     ir
 }
 
+/// A diamond nested in the true arm of another:
+/// http-1 → outer → (inner → (http-big | skip filter, code-small) → inner-merge | http-retry) → outer-merge.
+pub fn nested_diamond_ir() -> WorkflowIR {
+    ir_with_steps(vec![
+        make_step_with_output("http-1", http_get("https://api.example.com/status"), "any"),
+        make_step(
+            "outer",
+            branch_op(
+                ValueExpr::binding("http-1", "statusCode"),
+                ComparisonOp::Equals,
+                ValueExpr::integer(200),
+                Block {
+                    steps: vec![
+                        make_step(
+                            "inner",
+                            branch_op(
+                                ValueExpr::binding("http-1", "body.amount"),
+                                ComparisonOp::Gt,
+                                ValueExpr::integer(100),
+                                Block {
+                                    steps: vec![make_step_with_output(
+                                        "http-big",
+                                        http_get("https://api.example.com/big"),
+                                        "any",
+                                    )],
+                                },
+                                Block {
+                                    steps: vec![
+                                        make_step(
+                                            "skip-flagged",
+                                            filter_op(
+                                                ValueExpr::binding("http-1", "body.flagged"),
+                                                ComparisonOp::Equals,
+                                                ValueExpr::boolean(false),
+                                                FilterNonMatchBehavior::Skip,
+                                            ),
+                                        ),
+                                        make_step_with_output(
+                                            "code-small",
+                                            code_node_op(
+                                                "return amount;",
+                                                vec![(
+                                                    "amount",
+                                                    ValueExpr::binding("http-1", "body.amount"),
+                                                )],
+                                            ),
+                                            "any",
+                                        ),
+                                    ],
+                                },
+                                Some("inner-merge"),
+                            ),
+                        ),
+                        make_step_with_output(
+                            "inner-merge",
+                            merge_op(
+                                "inner",
+                                vec![
+                                    ("true", ValueExpr::binding("http-big", "body")),
+                                    ("false", ValueExpr::binding("code-small", "")),
+                                ],
+                            ),
+                            "any",
+                        ),
+                    ],
+                },
+                Block {
+                    steps: vec![make_step_with_output(
+                        "http-retry",
+                        http_get("https://api.example.com/retry"),
+                        "any",
+                    )],
+                },
+                Some("outer-merge"),
+            ),
+        ),
+        make_step_with_output(
+            "outer-merge",
+            merge_op(
+                "outer",
+                vec![
+                    ("true", ValueExpr::binding("inner-merge", "")),
+                    ("false", ValueExpr::binding("http-retry", "body")),
+                ],
+            ),
+            "any",
+        ),
+    ])
+}
+
+/// A diamond whose true arm ends in a branch that does not reconverge: one
+/// inner arm returns, the other carries on to the outer merge.
+pub fn branch_with_open_inner_branch_ir() -> WorkflowIR {
+    ir_with_steps(vec![
+        make_step(
+            "outer",
+            branch_op(
+                ValueExpr::config("mode"),
+                ComparisonOp::Equals,
+                ValueExpr::string("full"),
+                Block {
+                    steps: vec![
+                        make_step_with_output("code-a", code_node_op("return 1;", vec![]), "any"),
+                        make_step(
+                            "inner",
+                            branch_op(
+                                ValueExpr::binding("code-a", ""),
+                                ComparisonOp::Gt,
+                                ValueExpr::integer(0),
+                                Block {
+                                    steps: vec![make_step(
+                                        "return-early",
+                                        return_op(ValueExpr::string("early")),
+                                    )],
+                                },
+                                Block {
+                                    steps: vec![make_step_with_output(
+                                        "code-b",
+                                        code_node_op("return 2;", vec![]),
+                                        "any",
+                                    )],
+                                },
+                                None,
+                            ),
+                        ),
+                    ],
+                },
+                Block {
+                    steps: vec![make_step_with_output(
+                        "code-c",
+                        code_node_op("return 3;", vec![]),
+                        "any",
+                    )],
+                },
+                Some("merge-1"),
+            ),
+        ),
+        make_step_with_output(
+            "merge-1",
+            merge_op(
+                "outer",
+                vec![
+                    ("true", ValueExpr::binding("code-a", "")),
+                    ("false", ValueExpr::binding("code-c", "")),
+                ],
+            ),
+            "any",
+        ),
+    ])
+}
+
 // =============================================================================
 // Step builders
 // =============================================================================
@@ -248,6 +434,7 @@ pub fn make_step(id: &str, op: Operation) -> Step {
         label: id.into(),
         operation: op,
         output: None,
+        notes: None,
     }
 }
 
@@ -262,9 +449,19 @@ pub fn make_step_with_output(id: &str, op: Operation, ts_type: &str) -> Step {
             ts_type: ts_type.into(),
             destructure_fields: None,
         }),
+        notes: None,
     }
 }
 
+/// A step whose output is destructured into one const per field.
+pub fn make_step_destructuring(id: &str, op: Operation, fields: Vec<&str>) -> Step {
+    let mut step = make_step_with_output(id, op, "any");
+    if let Some(output) = &mut step.output {
+        output.destructure_fields = Some(fields.into_iter().map(String::from).collect());
+    }
+    step
+}
+
 // =============================================================================
 // Operation builders
 // =============================================================================
@@ -281,6 +478,8 @@ pub fn http_get(url: &str) -> Operation {
         timeout_ms: None,
         expected_status_codes: vec![200],
         response_format: HttpResponseFormat::Json,
+        follow_redirects: true,
+        ignore_ssl: false,
         consensus: ConsensusStrategy::Identical,
     })
 }
@@ -294,12 +493,15 @@ pub fn http_post(url: &str, body: ValueExpr) -> Operation {
         body: Some(HttpBody {
             content_type: HttpContentType::Json,
             data: body,
+            fields: vec![],
         }),
         authentication: None,
         cache_max_age_seconds: None,
         timeout_ms: None,
         expected_status_codes: vec![200, 201],
         response_format: HttpResponseFormat::Json,
+        follow_redirects: true,
+        ignore_ssl: false,
         consensus: ConsensusStrategy::Identical,
     })
 }
@@ -318,6 +520,8 @@ pub fn http_get_with_bearer(url: &str, token_secret: &str) -> Operation {
         timeout_ms: Some(5000),
         expected_status_codes: vec![200],
         response_format: HttpResponseFormat::Json,
+        follow_redirects: true,
+        ignore_ssl: false,
         consensus: ConsensusStrategy::Identical,
     })
 }
@@ -335,6 +539,8 @@ pub fn http_get_with_basic_auth(url: &str, _user_secret: &str, _pass_secret: &st
         timeout_ms: None,
         expected_status_codes: vec![200],
         response_format: HttpResponseFormat::Json,
+        follow_redirects: true,
+        ignore_ssl: false,
         consensus: ConsensusStrategy::Identical,
     })
 }
@@ -400,6 +606,7 @@ pub fn code_node_op_with_outputs(code: &str, inputs: Vec<(&str, ValueExpr)>, out
             })
             .collect(),
         execution_mode: CodeExecutionMode::RunOnceForAll,
+        iterate_over: None,
         timeout_ms: None,
         output_fields: output_fields.into_iter().map(|s| s.to_string()).collect(),
     })
@@ -414,6 +621,7 @@ pub fn abi_encode_op(params: &str, mappings: Vec<(&str, ValueExpr)>) -> Operatio
             .map(|(name, val)| AbiDataMapping {
                 param_name: name.into(),
                 value: val,
+                value_kind: None,
             })
             .collect(),
     })
@@ -429,7 +637,7 @@ pub fn abi_decode_op(input: ValueExpr, params: &str, outputs: Vec<&str>) -> Oper
 
 pub fn ai_call_op(provider: &str, secret: &str) -> Operation {
     Operation::AiCall(AiCallOp {
-        provider: provider.into(),
+        provider: AiProvider::parse(provider).expect("known AI provider"),
         base_url: ValueExpr::string("https://api.openai.com/v1"),
         model: ValueExpr::string("gpt-4"),
         api_key_secret: secret.into(),
@@ -449,7 +657,7 @@ pub fn ai_call_op_with_refs(
     user_prompt: ValueExpr,
 ) -> Operation {
     Operation::AiCall(AiCallOp {
-        provider: provider.into(),
+        provider: AiProvider::parse(provider).expect("known AI provider"),
         base_url: ValueExpr::string("https://api.openai.com/v1"),
         model: ValueExpr::string("gpt-4"),
         api_key_secret: secret.into(),
@@ -469,11 +677,13 @@ pub fn filter_op(
     behavior: FilterNonMatchBehavior,
 ) -> Operation {
     Operation::Filter(FilterOp {
-        conditions: vec![ConditionIR {
+        conditions: vec![ConditionTree::Leaf(ConditionIR {
             field,
             operator: op,
             value: Some(val),
-        }],
+            field_kind: None,
+            value_kind: None,
+        })],
         combine_with: LogicCombinator::And,
         non_match_behavior: behavior,
     })
@@ -488,14 +698,18 @@ pub fn branch_op(
     reconverge_at: Option<&str>,
 ) -> Operation {
     Operation::Branch(BranchOp {
-        conditions: vec![ConditionIR {
+        conditions: vec![ConditionTree::Leaf(ConditionIR {
             field,
             operator: op,
             value: Some(val),
-        }],
+            field_kind: None,
+            value_kind: None,
+        })],
         combine_with: LogicCombinator::And,
         true_branch: true_b,
         false_branch: false_b,
+        true_label: None,
+        false_label: None,
         reconverge_at: reconverge_at.map(String::from),
     })
 }
@@ -506,22 +720,39 @@ pub fn noop_op() -> Operation {
         code: "/* noop */".into(),
         input_bindings: vec![],
         execution_mode: CodeExecutionMode::RunOnceForAll,
+        iterate_over: None,
         timeout_ms: None,
         output_fields: vec![],
     })
 }
 
+pub fn kv_read_op(key: &str) -> Operation {
+    Operation::KvRead(KvReadOp {
+        key: ValueExpr::string(key),
+    })
+}
+
+pub fn kv_write_op(key: &str, value: ValueExpr) -> Operation {
+    Operation::KvWrite(KvWriteOp {
+        key: ValueExpr::string(key),
+        value,
+    })
+}
+
 pub fn error_op(msg: ValueExpr) -> Operation {
     Operation::ErrorThrow(ErrorThrowOp { message: msg })
 }
 
 pub fn return_op(expr: ValueExpr) -> Operation {
-    Operation::Return(ReturnOp { expression: expr })
+    Operation::Return(ReturnOp {
+        expression: expr,
+        auto_generated: false,
+    })
 }
 
 pub fn merge_op(branch_id: &str, inputs: Vec<(&str, ValueExpr)>) -> Operation {
     Operation::Merge(MergeOp {
-        branch_step_id: branch_id.into(),
+        branch_step_id: Some(branch_id.into()),
         strategy: MergeStrategy::PassThrough,
         inputs: inputs
             .into_iter()
@@ -532,3 +763,18 @@ pub fn merge_op(branch_id: &str, inputs: Vec<(&str, ValueExpr)>) -> Operation {
             .collect(),
     })
 }
+
+/// A merge joining independent chains, appending its inputs into an array.
+pub fn standalone_merge_op(inputs: Vec<(&str, ValueExpr)>) -> Operation {
+    Operation::Merge(MergeOp {
+        branch_step_id: None,
+        strategy: MergeStrategy::Append,
+        inputs: inputs
+            .into_iter()
+            .map(|(name, val)| MergeInput {
+                handle_name: name.into(),
+                value: val,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,191 @@
+//! Integration tests for incremental compilation.
+
+use compiler::codegen::{self, CodegenOptions, CodegenOutput};
+use compiler::incremental::{self, CacheStats, CompilationCache};
+use compiler::{compile, ir, lower, parse};
+use serde_json::{Value, json};
+
+/// t1 → h0 → m-1 → … → m-{len} → if-1 → (r-yes | r-no)
+fn chain_workflow(len: usize) -> Value {
+    let mut nodes = vec![
+        json!({
+            "id": "t1",
+            "type": "cronTrigger",
+            "position": { "x": 0, "y": 0 },
+            "data": { "label": "Every 10 minutes", "config": { "schedule": "0 */10 * * * *" } }
+        }),
+        json!({
+            "id": "h0",
+            "type": "httpRequest",
+            "position": { "x": 0, "y": 100 },
+            "data": {
+                "label": "Fetch Prices",
+                "config": { "method": "GET", "url": "https://api.example.com/prices" }
+            }
+        }),
+    ];
+    let mut edges = vec![json!({ "id": "e-h0", "source": "t1", "target": "h0" })];
+    let mut previous = "h0".to_string();
+    for i in 1..=len {
+        let id = format!("m-{i}");
+        nodes.push(json!({
+            "id": id,
+            "type": "math",
+            "position": { "x": 0, "y": 100 * (i + 1) },
+            "data": {
+                "label": format!("Scale {i}"),
+                "config": { "expression": format!("{{{{h0.body.price}}}} * {i}") }
+            }
+        }));
+        edges.push(json!({ "id": format!("e-{id}"), "source": previous, "target": id }));
+        previous = id;
+    }
+    nodes.push(json!({
+        "id": "if-1",
+        "type": "if",
+        "position": { "x": 0, "y": 100 * (len + 2) },
+        "data": {
+            "label": "Expensive?",
+            "config": {
+                "conditions": [
+                    { "field": "{{h0.body.price}}", "operator": "greaterThan", "value": "100" }
+                ],
+                "combineWith": "and"
+            }
+        }
+    }));
+    edges.push(json!({ "id": "e-if", "source": previous, "target": "if-1" }));
+    for (id, handle, x) in [("r-yes", "true", 0), ("r-no", "false", 200)] {
+        nodes.push(json!({
+            "id": id,
+            "type": "return",
+            "position": { "x": x, "y": 100 * (len + 3) },
+            "data": { "label": id, "config": { "returnExpression": format!("{{{{m-{len}}}}}") } }
+        }));
+        edges.push(json!({ "id": format!("e-{id}"), "source": "if-1", "target": id, "sourceHandle": handle }));
+    }
+
+    json!({
+        "id": "incremental-test",
+        "name": "Incremental Test",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": nodes,
+        "edges": edges,
+        "createdAt": "2025-01-01T00:00:00Z",
+        "updatedAt": "2025-01-01T00:00:00Z"
+    })
+}
+
+fn cold_compile(json: &str) -> CodegenOutput {
+    let workflow = parse::parse(json).expect("Should parse");
+    let graph = parse::WorkflowGraph::build(&workflow).expect("Should build graph");
+    let ir = lower::lower(&workflow, &graph).expect("Should lower");
+    assert!(
+        ir::validate_ir(&ir).is_empty(),
+        "{:?}",
+        ir::validate_ir(&ir)
+    );
+    codegen::codegen(&ir)
+}
+
+fn assert_same_output(actual: &CodegenOutput, expected: &CodegenOutput) {
+    assert_eq!(actual.files.len(), expected.files.len());
+    for (a, e) in actual.files.iter().zip(&expected.files) {
+        assert_eq!(a.path, e.path);
+        assert!(
+            a.content == e.content,
+            "{} differs from a cold compile",
+            a.path
+        );
+    }
+}
+
+#[test]
+fn editing_one_node_matches_cold_compile_and_reuses_the_rest() {
+    let mut workflow = chain_workflow(200);
+    let mut cache = CompilationCache::new();
+
+    let json = workflow.to_string();
+    let first = incremental::compile_incremental(&json, &mut cache).expect("Should compile");
+    assert_same_output(&first, &cold_compile(&json));
+    assert_eq!(cache.stats().hits, 0);
+
+    // Edit a node halfway down the chain: it and everything after it change.
+    let m100 = workflow["nodes"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|n| n["id"] == "m-100")
+        .unwrap();
+    m100["data"]["config"]["expression"] = json!("{{h0.body.price}} * 1000");
+    let json = workflow.to_string();
+    let second = incremental::compile_incremental(&json, &mut cache).expect("Should compile");
+
+    assert_same_output(&second, &cold_compile(&json));
+    let CacheStats { hits, misses } = cache.stats();
+    assert!(hits > 0, "Expected cache hits, got {:?}", cache.stats());
+    assert!(
+        misses > 0,
+        "Expected the edited node to miss, got {:?}",
+        cache.stats()
+    );
+    assert!(
+        hits > misses,
+        "Only the edited half should recompile: {:?}",
+        cache.stats()
+    );
+}
+
+#[test]
+fn unchanged_recompile_hits_everything_and_cache_round_trips() {
+    let json = chain_workflow(10).to_string();
+    let mut cache = CompilationCache::new();
+    incremental::compile_incremental(&json, &mut cache).expect("Should compile");
+
+    let mut restored: CompilationCache =
+        serde_json::from_str(&serde_json::to_string(&cache).unwrap()).unwrap();
+    let output = incremental::compile_incremental(&json, &mut restored).expect("Should compile");
+
+    assert_same_output(&output, &cold_compile(&json));
+    assert_eq!(restored.stats().misses, 0, "{:?}", restored.stats());
+    assert!(restored.stats().hits > 0);
+}
+
+#[test]
+fn options_warnings_and_errors_match_compile_with_options() {
+    let mut workflow = chain_workflow(10);
+    // An unused secret: pruned with a warning.
+    workflow["globalConfig"]["secrets"] =
+        json!([{ "name": "UNUSED", "envVariable": "UNUSED_VAR" }]);
+    let json = workflow.to_string();
+    let options = CodegenOptions {
+        compact: true,
+        generated_at: Some("2025-01-01T00:00:00Z".into()),
+        ..CodegenOptions::default()
+    };
+    let codes = |diagnostics: &[compiler::error::CompilerError]| -> Vec<String> {
+        diagnostics.iter().map(|d| d.code.clone()).collect()
+    };
+
+    let mut cache = CompilationCache::new();
+    let cold = compile::compile_with_options(&json, None, &options).expect("Should compile");
+    for _ in 0..2 {
+        let warm = incremental::compile_incremental_with_options(&json, &mut cache, &options, None)
+            .expect("Should compile");
+        assert_same_output(&warm.output, &cold.output);
+        let manifest = &warm.output.files.last().unwrap().content;
+        assert!(manifest.contains("\"generatedAt\": \"2025-01-01T00:00:00Z\""));
+        assert!(!cold.warnings.is_empty());
+        assert_eq!(codes(&warm.warnings), codes(&cold.warnings));
+    }
+
+    let strict = CodegenOptions {
+        strict: true,
+        ..options
+    };
+    let cold = compile::compile_with_options(&json, None, &strict).expect_err("Should fail");
+    let warm = incremental::compile_incremental_with_options(&json, &mut cache, &strict, None)
+        .expect_err("Should fail");
+    assert_eq!(codes(&warm), codes(&cold));
+}
@@ -0,0 +1,252 @@
+//! Stable IR JSON (`ir::io`): the checked-in documents must keep reading
+//! unchanged, and fields this version does not know are rejected.
+
+use std::collections::BTreeSet;
+
+use compiler::error::{JsonPathSegment, format_json_path};
+use compiler::ir::io::{self, IrIoError, ReadOptions};
+use compiler::ir::*;
+
+/// One document per `Operation` variant and per non-Cron trigger. If one of
+/// them stops round-tripping, the serialized IR changed: keep the old names,
+/// or bump `IR_SCHEMA_VERSION` and regenerate the fixtures with `io::to_json`.
+const FIXTURES: [(&str, &str); 17] = [
+    (
+        "http_request",
+        include_str!("fixtures/ir/http_request.json"),
+    ),
+    ("evm_read", include_str!("fixtures/ir/evm_read.json")),
+    ("evm_write", include_str!("fixtures/ir/evm_write.json")),
+    ("kv_read", include_str!("fixtures/ir/kv_read.json")),
+    ("kv_write", include_str!("fixtures/ir/kv_write.json")),
+    ("code_node", include_str!("fixtures/ir/code_node.json")),
+    ("abi_encode", include_str!("fixtures/ir/abi_encode.json")),
+    ("abi_decode", include_str!("fixtures/ir/abi_decode.json")),
+    ("compute", include_str!("fixtures/ir/compute.json")),
+    ("branch", include_str!("fixtures/ir/branch.json")),
+    ("filter", include_str!("fixtures/ir/filter.json")),
+    ("merge", include_str!("fixtures/ir/merge.json")),
+    ("ai_call", include_str!("fixtures/ir/ai_call.json")),
+    ("error_throw", include_str!("fixtures/ir/error_throw.json")),
+    ("return", include_str!("fixtures/ir/return.json")),
+    (
+        "trigger_http",
+        include_str!("fixtures/ir/trigger_http.json"),
+    ),
+    (
+        "trigger_evm_log",
+        include_str!("fixtures/ir/trigger_evm_log.json"),
+    ),
+];
+
+fn fixture(name: &str) -> serde_json::Value {
+    let (_, json) = FIXTURES.iter().find(|(n, _)| *n == name).unwrap();
+    serde_json::from_str(json).unwrap()
+}
+
+fn collect_kinds(block: &Block, kinds: &mut BTreeSet<&'static str>) {
+    for step in &block.steps {
+        kinds.insert(step.operation.kind());
+        if let Operation::Branch(branch) = &step.operation {
+            collect_kinds(&branch.true_branch, kinds);
+            collect_kinds(&branch.false_branch, kinds);
+        }
+    }
+}
+
+#[test]
+fn fixtures_deserialize_unchanged() {
+    for (name, json) in FIXTURES {
+        let ir = io::from_json(json).unwrap_or_else(|e| panic!("{name}: {e}"));
+
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&io::to_json(&ir)).unwrap();
+        assert_eq!(written, expected, "{name} no longer round-trips");
+    }
+}
+
+#[test]
+fn fixtures_cover_every_operation_and_trigger() {
+    let mut kinds = BTreeSet::new();
+    let mut triggers = BTreeSet::new();
+    for (_, json) in FIXTURES {
+        let ir = io::from_json(json).unwrap();
+        collect_kinds(&ir.handler_body, &mut kinds);
+        triggers.insert(match ir.trigger {
+            TriggerDef::Cron(_) => "Cron",
+            TriggerDef::Http(_) => "Http",
+            TriggerDef::EvmLog(_) => "EvmLog",
+        });
+    }
+
+    let all_kinds = [
+        "HttpRequest",
+        "EvmRead",
+        "EvmWrite",
+        "KvRead",
+        "KvWrite",
+        "CodeNode",
+        "AbiEncode",
+        "AbiDecode",
+        "Compute",
+        "Branch",
+        "Filter",
+        "Merge",
+        "AiCall",
+        "ErrorThrow",
+        "Return",
+    ];
+    assert_eq!(kinds, BTreeSet::from(all_kinds));
+    assert_eq!(triggers, BTreeSet::from(["Cron", "Http", "EvmLog"]));
+}
+
+#[test]
+fn unknown_top_level_field_is_rejected() {
+    let mut doc = fixture("return");
+    doc["deployment"] = "prod".into();
+
+    let lenient = ReadOptions {
+        allow_unknown_operation_fields: true,
+    };
+    let err = io::from_json_with_options(&doc.to_string(), &lenient).unwrap_err();
+
+    assert_eq!(
+        err,
+        IrIoError::UnknownField {
+            path: vec![JsonPathSegment::key("deployment")]
+        }
+    );
+}
+
+#[test]
+fn unknown_operation_field_is_rejected_unless_allowed() {
+    let mut doc = fixture("http_request");
+    doc["handler_body"]["steps"][0]["operation"]["retries"] = 3.into();
+    let json = doc.to_string();
+
+    match io::from_json(&json).unwrap_err() {
+        IrIoError::UnknownField { path } => assert_eq!(
+            format_json_path(&path),
+            "handler_body.steps[0].operation.retries"
+        ),
+        other => panic!("Expected UnknownField, got {other}"),
+    }
+
+    let lenient = ReadOptions {
+        allow_unknown_operation_fields: true,
+    };
+    let ir = io::from_json_with_options(&json, &lenient).unwrap();
+    assert_eq!(ir.handler_body.steps[0].operation.kind(), "HttpRequest");
+
+    // A step nested in a branch arm is a step again, not operation data.
+    let mut doc = fixture("branch");
+    doc["handler_body"]["steps"][0]["operation"]["true_branch"]["steps"][0]["retries"] = 3.into();
+    match io::from_json_with_options(&doc.to_string(), &lenient).unwrap_err() {
+        IrIoError::UnknownField { path } => assert_eq!(
+            format_json_path(&path),
+            "handler_body.steps[0].operation.true_branch.steps[0].retries"
+        ),
+        other => panic!("Expected UnknownField, got {other}"),
+    }
+}
+
+#[test]
+fn explicit_nulls_for_optional_fields_are_read() {
+    // Unset optional fields are left out when writing; a document that
+    // spells them out as null (or an empty list) is still valid.
+    let mut doc = fixture("ai_call");
+    doc["metadata"]["max_kv_operations"] = serde_json::Value::Null;
+    doc["required_secrets"][0]["env_variable_staging"] = serde_json::Value::Null;
+    io::from_json(&doc.to_string()).unwrap();
+
+    let mut doc = fixture("branch");
+    doc["handler_body"]["steps"][0]["operation"]["true_label"] = serde_json::Value::Null;
+    io::from_json(&doc.to_string()).unwrap();
+
+    let mut doc = fixture("http_request");
+    doc["handler_body"]["steps"][0]["operation"]["body"]["fields"] = serde_json::json!([]);
+    io::from_json(&doc.to_string()).unwrap();
+}
+
+#[test]
+fn schema_and_version_are_checked() {
+    let mut doc = fixture("return");
+    doc["version"] = (io::IR_SCHEMA_VERSION + 1).into();
+    assert_eq!(
+        io::from_json(&doc.to_string()).unwrap_err(),
+        IrIoError::UnsupportedVersion {
+            found: Some(io::IR_SCHEMA_VERSION + 1)
+        }
+    );
+
+    doc["version"] = io::IR_SCHEMA_VERSION.into();
+    doc["schema"] = "6flow-workflow".into();
+    assert_eq!(
+        io::from_json(&doc.to_string()).unwrap_err(),
+        IrIoError::WrongSchema {
+            found: Some("6flow-workflow".into())
+        }
+    );
+
+    // Plain serde output has no envelope.
+    let ir = io::from_json(FIXTURES[0].1).unwrap();
+    let plain = serde_json::to_string(&ir).unwrap();
+    assert_eq!(
+        io::from_json(&plain).unwrap_err(),
+        IrIoError::WrongSchema { found: None }
+    );
+}
+
+#[test]
+fn malformed_value_reports_its_path() {
+    let mut doc = fixture("evm_read");
+    doc["evm_chains"][0]["used_for_trigger"] = "no".into();
+
+    match io::from_json(&doc.to_string()).unwrap_err() {
+        IrIoError::Malformed { path, .. } => {
+            assert_eq!(format_json_path(&path), "evm_chains[0].used_for_trigger")
+        }
+        other => panic!("Expected Malformed, got {other}"),
+    }
+}
+
+#[test]
+fn nested_condition_groups_round_trip() {
+    // (threshold >= 10 && flag exists) || threshold == 0
+    let mut doc = fixture("branch");
+    let conditions = &mut doc["handler_body"]["steps"][0]["operation"]["conditions"];
+    let gte = conditions[0].clone();
+    let exists = serde_json::json!({
+        "field": { "kind": "ConfigRef", "field": "flag" },
+        "operator": "Exists",
+        "value": null
+    });
+    let zero = serde_json::json!({
+        "field": { "kind": "ConfigRef", "field": "threshold" },
+        "operator": "Equals",
+        "value": { "kind": "Literal", "literal_type": "Integer", "value": 0 }
+    });
+    *conditions = serde_json::json!([
+        { "combinator": "And", "children": [gte, exists] },
+        zero
+    ]);
+    doc["handler_body"]["steps"][0]["operation"]["combine_with"] = "Or".into();
+
+    let ir = io::from_json(&doc.to_string()).unwrap();
+    let Operation::Branch(branch) = &ir.handler_body.steps[0].operation else {
+        panic!("Expected a branch");
+    };
+    match &branch.conditions[..] {
+        [
+            ConditionTree::Group {
+                combinator: LogicCombinator::And,
+                children,
+            },
+            ConditionTree::Leaf(_),
+        ] => assert_eq!(children.len(), 2),
+        other => panic!("Expected a group and a leaf, got {other:?}"),
+    }
+
+    let written: serde_json::Value = serde_json::from_str(&io::to_json(&ir)).unwrap();
+    assert_eq!(written, doc);
+}
@@ -218,6 +218,7 @@ fn test_abi_decode() {
                 ts_type: "any".into(),
                 destructure_fields: Some(vec!["from".into(), "value".into()]),
             }),
+            notes: None,
         },
     ]);
     let errors = validate_ir(&ir);
@@ -319,7 +320,7 @@ fn test_branch_with_merge() {
 
     let rt = roundtrip(&ir);
     if let Operation::Merge(op) = &rt.handler_body.steps[1].operation {
-        assert_eq!(op.branch_step_id, "branch-1");
+        assert_eq!(op.branch_step_id.as_deref(), Some("branch-1"));
         assert_eq!(op.inputs.len(), 2);
     } else {
         panic!("Expected Merge");
@@ -339,7 +340,7 @@ fn test_filter_early_return() {
             ComparisonOp::Equals,
             ValueExpr::string("active"),
             FilterNonMatchBehavior::EarlyReturn {
-                message: "Not active".into(),
+                message: ValueExpr::string("Not active"),
             },
         ),
     )]);
@@ -350,7 +351,9 @@ fn test_filter_early_return() {
     if let Operation::Filter(op) = &rt.handler_body.steps[0].operation {
         assert!(matches!(
             &op.non_match_behavior,
-            FilterNonMatchBehavior::EarlyReturn { message } if message == "Not active"
+            FilterNonMatchBehavior::EarlyReturn {
+                message: ValueExpr::Literal(LiteralValue::String { value }),
+            } if value == "Not active"
         ));
     } else {
         panic!("Expected Filter");
@@ -406,7 +409,7 @@ fn test_ai_call() {
 
     let rt = roundtrip(&ir);
     if let Operation::AiCall(op) = &rt.handler_body.steps[0].operation {
-        assert_eq!(op.provider, "openai");
+        assert_eq!(op.provider, AiProvider::OpenAi);
         assert_eq!(op.temperature, Some(0.7));
         assert_eq!(op.max_tokens, Some(256));
         assert_eq!(op.api_key_secret, "OPENAI_KEY");
@@ -415,6 +418,30 @@ fn test_ai_call() {
     }
 }
 
+#[test]
+fn test_ai_provider_aliases() {
+    for (raw, expected) in [
+        ("OpenAI", AiProvider::OpenAi),
+        ("gpt", AiProvider::OpenAi),
+        ("ChatGPT", AiProvider::OpenAi),
+        ("Anthropic", AiProvider::Anthropic),
+        ("claude", AiProvider::Anthropic),
+        ("Google", AiProvider::Google),
+        (" gemini ", AiProvider::Google),
+        (
+            "openai-compatible",
+            AiProvider::OpenAiCompatible {
+                label: "openai-compatible".into(),
+            },
+        ),
+    ] {
+        assert_eq!(AiProvider::parse(raw), Some(expected), "provider '{}'", raw);
+    }
+    assert_eq!(AiProvider::parse("openAI "), Some(AiProvider::OpenAi));
+    assert_eq!(AiProvider::parse("gemeni"), None);
+    assert_eq!(AiProvider::parse(""), None);
+}
+
 // =============================================================================
 // ERROR THROW
 // =============================================================================
@@ -133,6 +133,75 @@ fn test_nested_branches() {
     assert!(errors.is_empty(), "Expected no errors, got: {:?}", errors);
 }
 
+// =============================================================================
+// Step walking: every step once, with its branch scope
+// =============================================================================
+
+#[test]
+fn test_walk_steps_matches_manual_count_with_branch_scopes() {
+    let arm = |id: &str| Block {
+        steps: vec![make_step_with_output(id, noop_op(), "void")],
+    };
+    let inner = make_step(
+        "inner-branch",
+        branch_op(
+            ValueExpr::binding("http-1", "body"),
+            ComparisonOp::Contains,
+            ValueExpr::string("approved"),
+            arm("x-step"),
+            arm("y-step"),
+            None,
+        ),
+    );
+    let ir = ir_with_steps(vec![
+        make_step_with_output("http-1", http_get("https://example.com"), "any"),
+        make_step(
+            "outer-branch",
+            branch_op(
+                ValueExpr::binding("http-1", "statusCode"),
+                ComparisonOp::Equals,
+                ValueExpr::integer(200),
+                arm("a-step"),
+                Block { steps: vec![inner] },
+                None,
+            ),
+        ),
+    ]);
+
+    fn manual_count(block: &Block) -> usize {
+        block
+            .steps
+            .iter()
+            .map(|step| match &step.operation {
+                Operation::Branch(b) => {
+                    1 + manual_count(&b.true_branch) + manual_count(&b.false_branch)
+                }
+                _ => 1,
+            })
+            .sum()
+    }
+
+    let mut visited = Vec::new();
+    walk_steps(&ir, |step, scope| {
+        visited.push((step.id.clone(), scope.join("/")));
+    });
+    assert_eq!(visited.len(), manual_count(&ir.handler_body));
+    assert_eq!(ir.steps_iter().count(), visited.len());
+    assert_eq!(
+        visited,
+        [
+            ("http-1", ""),
+            ("outer-branch", ""),
+            ("a-step", "outer-branch/true"),
+            ("inner-branch", "outer-branch/false"),
+            ("x-step", "outer-branch/false/inner-branch/true"),
+            ("y-step", "outer-branch/false/inner-branch/false"),
+            ("return-final", ""),
+        ]
+        .map(|(id, scope)| (id.to_string(), scope.to_string()))
+    );
+}
+
 // =============================================================================
 // Both branches terminate: trigger → if → (return) / (error) — no merge
 // =============================================================================
@@ -176,7 +245,7 @@ fn test_filter_then_work() {
                 ComparisonOp::Equals,
                 ValueExpr::boolean(true),
                 FilterNonMatchBehavior::EarlyReturn {
-                    message: "Disabled".into(),
+                    message: ValueExpr::string("Disabled"),
                 },
             ),
         ),
@@ -254,3 +323,69 @@ fn test_code_node_references_prior_steps() {
     let errors = validate_ir(&ir);
     assert!(errors.is_empty(), "Expected no errors, got: {:?}", errors);
 }
+
+// =============================================================================
+// Step dependency graph
+// =============================================================================
+
+#[test]
+fn test_dependency_graph_of_kyc_example() {
+    let workflow = compiler::parse::parse(include_str!("fixtures/example_workflow.json")).unwrap();
+    let graph = compiler::parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = compiler::lower::lower(&workflow, &graph).unwrap();
+
+    let deps = analysis::dependency_graph(&ir);
+    // The branch condition reads `body.isApproved`; the encode step reads the
+    // wallet address and amount.
+    assert_eq!(
+        deps.dependents_of("http-1"),
+        vec!["condition-1", "encode-1"]
+    );
+    assert_eq!(deps.dependencies_of("write-1"), vec!["encode-1"]);
+    assert_eq!(
+        deps.transitive_dependents("http-1"),
+        vec!["condition-1", "encode-1", "write-1"]
+    );
+
+    let json = serde_json::to_value(&deps).unwrap();
+    assert_eq!(
+        json["edges"][0],
+        serde_json::json!({ "step_id": "condition-1", "depends_on": "http-1" })
+    );
+}
+
+#[test]
+fn test_dependency_graph_of_expanded_node() {
+    let mut encode = make_step(
+        "mint___encode",
+        abi_encode_op(
+            r#"[{"name":"to","type":"address"},{"name":"amount","type":"uint256"}]"#,
+            vec![
+                ("to", ValueExpr::binding("fetch", "body.to")),
+                ("amount", ValueExpr::binding("fetch", "body.amount")),
+            ],
+        ),
+    );
+    let mut write = make_step(
+        "mint___write",
+        evm_write_op(
+            "evm_sepolia",
+            "0xabc",
+            ValueExpr::binding("mint___encode", "encoded"),
+        ),
+    );
+    encode.source_node_ids = vec!["mint".into()];
+    write.source_node_ids = vec!["mint".into()];
+    let ir = ir_with_steps(vec![
+        make_step_with_output("fetch", http_get("https://api.example.com"), "any"),
+        encode,
+        write,
+    ]);
+
+    let deps = analysis::dependency_graph(&ir);
+    assert_eq!(deps.dependencies_of("mint___encode"), vec!["fetch"]);
+    assert_eq!(deps.dependencies_of("mint___write"), vec!["mint___encode"]);
+    assert_eq!(deps.node_dependencies_of("mint"), vec!["fetch"]);
+    assert_eq!(deps.transitive_dependents("fetch"), vec!["mint"]);
+    assert!(deps.transitive_dependents("mint").is_empty());
+}
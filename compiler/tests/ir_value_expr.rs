@@ -154,3 +154,31 @@ fn test_raw_expr_roundtrip() {
         panic!("Expected RawExpr, got {:?}", rt);
     }
 }
+
+#[test]
+fn test_conditional_roundtrip() {
+    let v = ValueExpr::conditional(
+        ValueExpr::binding("http-1", "body.ok"),
+        ValueExpr::config("onSuccess"),
+        ValueExpr::string("failed"),
+    );
+    let json = serde_json::to_value(&v).unwrap();
+    assert_eq!(json["kind"], "Conditional");
+    assert_eq!(json["cond"]["kind"], "Binding");
+    let rt = roundtrip(&v);
+    if let ValueExpr::Conditional {
+        cond,
+        then,
+        otherwise,
+    } = &rt
+    {
+        assert!(matches!(cond.as_ref(), ValueExpr::Binding(b) if b.step_id == "http-1"));
+        assert!(matches!(then.as_ref(), ValueExpr::ConfigRef { field } if field == "onSuccess"));
+        assert!(matches!(
+            otherwise.as_ref(),
+            ValueExpr::Literal(LiteralValue::String { value }) if value == "failed"
+        ));
+    } else {
+        panic!("Expected Conditional, got {:?}", rt);
+    }
+}
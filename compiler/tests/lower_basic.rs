@@ -1,6 +1,9 @@
 //! Integration tests for the lowering pass: graph → WorkflowIR.
 
-use compiler::ir::types::{LiteralValue, Operation, ValueExpr};
+use compiler::ir::types::{
+    AiCallOp, AiProvider, ConsensusStrategy, LiteralValue, MergeStrategy, NumericMode, Operation,
+    Step, ValueExpr, ValueKind,
+};
 use compiler::lower;
 use compiler::parse;
 use compiler::validate;
@@ -54,7 +57,7 @@ fn lower_example_workflow_produces_valid_ir() {
 
     match &ir.handler_body.steps[1].operation {
         Operation::Branch(op) => {
-            match &op.conditions[0].field {
+            match &op.conditions[0].leaves()[0].field {
                 ValueExpr::Binding(binding) => {
                     assert_eq!(binding.step_id, "http-1");
                     assert_eq!(binding.field_path, "body.isApproved");
@@ -97,3 +100,1528 @@ fn lower_adds_auto_return_when_workflow_has_no_terminal_node() {
         other => panic!("Expected auto-added Return operation, got {:?}", other),
     }
 }
+
+#[test]
+fn lower_evm_read_falls_back_to_default_chain() {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let workflow = parse::parse(json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let ir = lower::lower(&workflow, &graph).expect("Should lower with default chain");
+
+    assert_eq!(
+        ir.metadata.default_chain_selector.as_deref(),
+        Some("ethereum-testnet-sepolia")
+    );
+    assert_eq!(ir.evm_chains.len(), 1);
+    assert_eq!(
+        ir.evm_chains[0].chain_selector_name,
+        "ethereum-testnet-sepolia"
+    );
+    match &ir.handler_body.steps[0].operation {
+        Operation::EvmRead(op) => {
+            assert_eq!(op.evm_client_binding, "evmClient_ethereum_testnet_sepolia");
+        }
+        other => panic!("Expected EvmRead operation, got {:?}", other),
+    }
+}
+
+#[test]
+fn lower_evm_read_block_number_tags_and_numbers() {
+    let lower_with_block = |block: &str| {
+        let json = include_str!("fixtures/evm_default_chain.json").replace(
+            r#""functionName": "totalSupply","#,
+            &format!(r#""functionName": "totalSupply", "blockNumber": "{block}","#),
+        );
+        let workflow = parse::parse(&json).unwrap();
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        assert!(validate::validate_graph(&workflow, &graph).is_empty());
+        let ir = lower::lower(&workflow, &graph).expect("Should lower successfully");
+        let Operation::EvmRead(op) = &ir.handler_body.steps[0].operation else {
+            panic!("Expected EvmRead operation");
+        };
+        let block_number = op.block_number.clone();
+        let main_ts = compiler::codegen::codegen(&ir)
+            .files
+            .into_iter()
+            .find(|f| f.path == "main.ts")
+            .unwrap()
+            .content;
+        (block_number, main_ts)
+    };
+
+    let (block_number, main_ts) = lower_with_block(" Finalized ");
+    assert!(matches!(
+        block_number,
+        Some(ValueExpr::Literal(LiteralValue::String { value })) if value == "finalized"
+    ));
+    assert!(
+        main_ts.contains("blockNumber: LAST_FINALIZED_BLOCK_NUMBER,"),
+        "{main_ts}"
+    );
+    let sdk_import = main_ts.lines().next().unwrap();
+    assert!(
+        sdk_import.contains(" LAST_FINALIZED_BLOCK_NUMBER,"),
+        "{sdk_import}"
+    );
+
+    // 12345 = 0x3039, sent as its big-endian bytes.
+    let (block_number, main_ts) = lower_with_block("12345");
+    assert!(matches!(
+        block_number,
+        Some(ValueExpr::Literal(LiteralValue::Integer { value: 12345 }))
+    ));
+    assert!(
+        main_ts.contains(
+            r#"blockNumber: { absVal: Buffer.from("3039", "hex").toString("base64"), sign: "1" },"#
+        ),
+        "{main_ts}"
+    );
+    assert!(!main_ts.contains("BLOCK_NUMBER"), "{main_ts}");
+
+    let (block_number, main_ts) = lower_with_block("");
+    assert!(block_number.is_none());
+    assert!(!main_ts.contains("blockNumber:"), "{main_ts}");
+}
+
+#[test]
+fn lower_evm_read_destructures_named_outputs() {
+    let json = include_str!("fixtures/evm_default_chain.json").replace(
+        r#""outputs": [{ "name": "", "type": "uint256" }]"#,
+        r#""outputs": [{ "name": "reserve0", "type": "uint112" }, { "name": "reserve1", "type": "uint112" }, { "name": "blockTimestampLast", "type": "uint32" }]"#,
+    );
+    let workflow = parse::parse(&json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let ir = lower::lower(&workflow, &graph).expect("Should lower successfully");
+
+    let output = ir.handler_body.steps[0].output.as_ref().unwrap();
+    assert_eq!(
+        output.destructure_fields.clone().unwrap(),
+        ["reserve0", "reserve1", "blockTimestampLast"]
+    );
+    assert_eq!(
+        output.ts_type,
+        "{ reserve0: bigint; reserve1: bigint; blockTimestampLast: number }"
+    );
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts.contains(
+            "const [step_read_1_reserve0, step_read_1_reserve1, step_read_1_blockTimestampLast] = _decoded_read_1;"
+        ),
+        "{}",
+        main_ts
+    );
+    assert!(!main_ts.contains("const step_read_1 ="), "{}", main_ts);
+}
+
+#[test]
+fn lower_evm_read_without_any_chain_fails() {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow.global_config.default_chain_selector = None;
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let errors = lower::lower(&workflow, &graph).expect_err("Should fail without a chain");
+
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.code == "L005" && e.node_id.as_deref() == Some("read-1")),
+        "Expected L005 on read-1, got: {:?}",
+        errors
+    );
+}
+
+#[test]
+fn lower_evm_read_with_an_empty_chain_does_not_use_the_default() {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let mut read = serde_json::to_value(&workflow.nodes[1]).unwrap();
+    read["data"]["config"]["chainSelectorName"] = "".into();
+    workflow.nodes[1] = serde_json::from_value(read).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let errors = lower::lower(&workflow, &graph).expect_err("An empty chain is not the default");
+
+    assert!(
+        errors.iter().any(|e| e.code == "L005"
+            && e.node_id.as_deref() == Some("read-1")
+            && e.message.contains("empty chain selector")),
+        "Expected L005 on read-1, got: {:?}",
+        errors
+    );
+}
+
+#[test]
+fn lower_two_evm_reads_on_one_chain_share_its_usage() {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let mut workflow = parse::parse(json).unwrap();
+    // read-2 names the default chain explicitly: t1 → read-1 → read-2 → r1.
+    let mut read_2 = serde_json::to_value(&workflow.nodes[1]).unwrap();
+    read_2["id"] = "read-2".into();
+    read_2["data"]["config"]["chainSelectorName"] = " ethereum-testnet-sepolia ".into();
+    workflow.nodes.push(serde_json::from_value(read_2).unwrap());
+    workflow.edges[1].target = "read-2".into();
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e3".into(),
+        source: "read-2".into(),
+        target: "r1".into(),
+        source_handle: None,
+        target_handle: None,
+        label: None,
+    });
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let ir = lower::lower(&workflow, &graph).expect("Should lower both reads");
+
+    assert_eq!(ir.evm_chains.len(), 1, "{:?}", ir.evm_chains);
+    let binding = &ir.evm_chains[0].binding_name;
+    assert_eq!(binding, "evmClient_ethereum_testnet_sepolia");
+    for step in &ir.handler_body.steps[..2] {
+        match &step.operation {
+            Operation::EvmRead(op) => assert_eq!(&op.evm_client_binding, binding),
+            other => panic!("Expected EvmRead operation, got {:?}", other),
+        }
+    }
+
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    let construction = format!("const {binding} = new cre.capabilities.EVMClient(");
+    assert_eq!(main_ts.matches(&construction).count(), 1, "{}", main_ts);
+}
+
+/// evm_default_chain fixture with a math node spliced in: t1 → read-1 → math-1 → r1.
+fn evm_read_with_math(expression: &str) -> parse::Workflow {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow.nodes.push(
+        serde_json::from_value(serde_json::json!({
+            "id": "math-1",
+            "type": "math",
+            "position": { "x": 300, "y": 0 },
+            "data": {
+                "label": "Scale Supply",
+                "config": { "expression": expression }
+            }
+        }))
+        .unwrap(),
+    );
+    workflow.edges[1].target = "math-1".into();
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e3".into(),
+        source: "math-1".into(),
+        target: "r1".into(),
+        source_handle: None,
+        target_handle: None,
+        label: None,
+    });
+    workflow
+}
+
+#[test]
+fn lower_math_on_evm_uint_output_uses_bigint() {
+    let workflow = evm_read_with_math("{{read-1.value}} * 1e18 / 2");
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let ir = lower::lower(&workflow, &graph).expect("Should lower math node");
+    let step = &ir.handler_body.steps[1];
+    match &step.operation {
+        Operation::Compute(op) => {
+            assert_eq!(op.mode, NumericMode::BigInt);
+            assert_eq!(step.output.as_ref().unwrap().ts_type, "bigint");
+        }
+        other => panic!("Expected Compute operation, got {:?}", other),
+    }
+
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts
+            .contains("const step_math_1 = BigInt(step_read_1.value) * 1000000000000000000n / 2n;"),
+        "main.ts:\n{}",
+        main_ts
+    );
+}
+
+#[test]
+fn lower_math_rejects_fractional_literal_in_bigint_mode() {
+    let workflow = evm_read_with_math("{{read-1.value}} * 1.5");
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let errors = lower::lower(&workflow, &graph).expect_err("1.5 can't be a BigInt");
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.code == "L006" && e.node_id.as_deref() == Some("math-1")),
+        "Expected L006, got: {:?}",
+        errors
+    );
+}
+
+/// evm_default_chain fixture with a filter node spliced in: t1 → read-1 → f1 → r1,
+/// passing when the read value is greater than `threshold`.
+fn evm_read_with_filter(threshold: &str) -> parse::Workflow {
+    let json = include_str!("fixtures/evm_default_chain.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow.nodes.push(
+        serde_json::from_value(serde_json::json!({
+            "id": "f1",
+            "type": "filter",
+            "position": { "x": 300, "y": 0 },
+            "data": {
+                "label": "Enough Supply",
+                "config": {
+                    "conditions": [
+                        { "field": "{{read-1.value}}", "operator": "gt", "value": threshold }
+                    ],
+                    "combineWith": "and"
+                }
+            }
+        }))
+        .unwrap(),
+    );
+    workflow.edges[1].target = "f1".into();
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e3".into(),
+        source: "f1".into(),
+        target: "r1".into(),
+        source_handle: None,
+        target_handle: None,
+        label: None,
+    });
+    workflow
+}
+
+#[test]
+fn evm_uint_compared_with_config_coerces_the_config_side() {
+    let workflow = evm_read_with_filter("{{config.minSupply}}");
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let (ir, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    assert!(warnings.iter().all(|w| w.code != "W021"), "{:?}", warnings);
+    let Operation::Filter(filter) = &ir.handler_body.steps[1].operation else {
+        panic!(
+            "Expected Filter, got {:?}",
+            ir.handler_body.steps[1].operation
+        );
+    };
+    let cond = filter.conditions[0].leaves()[0];
+    assert_eq!(cond.field_kind, Some(ValueKind::BigInt));
+    assert_eq!(cond.value_kind, None);
+
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts.contains("step_read_1.value > BigInt(runtime.config.minSupply)"),
+        "main.ts:\n{}",
+        main_ts
+    );
+}
+
+#[test]
+fn w021_fractional_literal_compared_with_evm_uint() {
+    let workflow = evm_read_with_filter("1.5");
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let (_, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    assert!(
+        warnings
+            .iter()
+            .any(|w| w.code == "W021" && w.node_id.as_deref() == Some("f1")),
+        "Expected W021, got: {:?}",
+        warnings
+    );
+}
+
+/// linear_workflow fixture with an AI node (empty base URL and model) feeding the return.
+fn linear_with_ai(provider: &str) -> parse::Workflow {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow
+        .global_config
+        .secrets
+        .push(parse::types::SecretReference {
+            name: "AI_KEY".into(),
+            env_variable: "AI_KEY_VAR".into(),
+            env_variable_staging: None,
+            env_variable_production: None,
+        });
+    workflow.nodes.push(
+        serde_json::from_value(serde_json::json!({
+            "id": "ai-1",
+            "type": "ai",
+            "position": { "x": 0, "y": 500 },
+            "data": {
+                "label": "Summarize",
+                "config": {
+                    "provider": provider,
+                    "baseUrl": "",
+                    "model": "",
+                    "apiKeySecret": "AI_KEY",
+                    "systemPrompt": "You are terse.",
+                    "userPrompt": "Summarize the data",
+                    "maxTokens": 512
+                }
+            }
+        }))
+        .unwrap(),
+    );
+    let trigger_id = workflow.nodes[0].id().to_string();
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e-ai".into(),
+        source: trigger_id,
+        target: "ai-1".into(),
+        source_handle: None,
+        target_handle: None,
+        label: None,
+    });
+    workflow
+}
+
+#[test]
+fn lower_ai_applies_per_provider_defaults() {
+    for (provider, expected_provider, base_url, header) in [
+        (
+            "OpenAI",
+            AiProvider::OpenAi,
+            "https://api.openai.com/v1/chat/completions",
+            "\"Authorization\": `Bearer ${apiKey}`,",
+        ),
+        (
+            "claude",
+            AiProvider::Anthropic,
+            "https://api.anthropic.com/v1/messages",
+            "\"anthropic-version\": \"2023-06-01\",",
+        ),
+        (
+            "gemini",
+            AiProvider::Google,
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash-lite:generateContent",
+            "\"x-goog-api-key\": apiKey,",
+        ),
+    ] {
+        let workflow = linear_with_ai(provider);
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+        let ir = lower::lower(&workflow, &graph).expect("Should lower AI node");
+        let op = ir
+            .handler_body
+            .steps
+            .iter()
+            .find_map(|s| match &s.operation {
+                Operation::AiCall(op) => Some(op),
+                _ => None,
+            })
+            .expect("AI step");
+        assert_eq!(op.provider, expected_provider);
+
+        let main_ts = compiler::codegen::codegen(&ir)
+            .files
+            .into_iter()
+            .find(|f| f.path == "main.ts")
+            .unwrap()
+            .content;
+        assert!(
+            main_ts.contains(&format!("url: \"{}\",", base_url)),
+            "{} main.ts:\n{}",
+            provider,
+            main_ts
+        );
+        assert!(
+            main_ts.contains(header),
+            "{} main.ts:\n{}",
+            provider,
+            main_ts
+        );
+    }
+}
+
+fn lowered_ai_call(workflow: &parse::Workflow) -> AiCallOp {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    let ir = lower::lower(workflow, &graph).expect("Should lower AI node");
+    ir.handler_body
+        .steps
+        .iter()
+        .find_map(|s| match &s.operation {
+            Operation::AiCall(op) => Some(op.clone()),
+            _ => None,
+        })
+        .expect("AI step")
+}
+
+#[test]
+fn lower_ai_defaults_to_consensus_on_the_generated_content() {
+    for (provider, field) in [
+        ("openai", "choices"),
+        ("anthropic", "content"),
+        ("google", "candidates"),
+    ] {
+        let op = lowered_ai_call(&linear_with_ai(provider));
+        // Responses differ in ids, timestamps and usage from node to node.
+        assert!(
+            !matches!(op.consensus, ConsensusStrategy::Identical),
+            "{provider}: {:?}",
+            op.consensus
+        );
+        assert!(
+            matches!(&op.consensus, ConsensusStrategy::IdenticalFields { fields } if fields == &[field]),
+            "{provider}: {:?}",
+            op.consensus
+        );
+    }
+
+    let workflow = linear_with_ai("openai");
+    let ir = lower::lower(&workflow, &parse::WorkflowGraph::build(&workflow).unwrap()).unwrap();
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts.contains(
+            "new cre.consensus.ConsensusAggregationByFields({ choices: cre.consensus.identical() })"
+        ),
+        "{main_ts}"
+    );
+
+    let with_mode = |consensus: &str, expression: Option<&str>| {
+        let mut workflow = linear_with_ai("openai");
+        let Some(parse::WorkflowNode::Ai(node)) = workflow.nodes.last_mut() else {
+            panic!("AI node");
+        };
+        node.data.config.consensus = serde_json::from_value(serde_json::json!(consensus)).unwrap();
+        node.data.config.consensus_expression = expression.map(String::from);
+        lowered_ai_call(&workflow).consensus
+    };
+    assert!(matches!(
+        with_mode("identical", None),
+        ConsensusStrategy::Identical
+    ));
+    assert!(matches!(
+        with_mode("custom", Some(" consensusIdenticalAggregation() ")),
+        ConsensusStrategy::Custom { expr } if expr == "consensusIdenticalAggregation()"
+    ));
+}
+
+/// t1 → h0 → if-1; each arm fetches once (`h-yes` / `h-no`) and stops.
+/// No path ends in a Return node.
+fn branch_without_returns(auto_return: Option<&str>) -> parse::Workflow {
+    let http = |id: &str, y: u32| {
+        serde_json::json!({
+            "id": id,
+            "type": "httpRequest",
+            "position": { "x": 0, "y": y },
+            "data": {
+                "label": id,
+                "config": { "method": "GET", "url": "https://api.example.com/status" }
+            }
+        })
+    };
+    let mut global_config = serde_json::json!({ "isTestnet": true, "secrets": [], "rpcs": [] });
+    if let Some(mode) = auto_return {
+        global_config["autoReturn"] = mode.into();
+    }
+    serde_json::from_value(serde_json::json!({
+        "id": "open-paths",
+        "name": "Open Paths",
+        "version": "1.0.0",
+        "globalConfig": global_config,
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "cronTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+            },
+            http("h0", 100),
+            {
+                "id": "if-1",
+                "type": "if",
+                "position": { "x": 0, "y": 200 },
+                "data": {
+                    "label": "Healthy?",
+                    "config": {
+                        "conditions": [
+                            { "field": "{{h0.body.ok}}", "operator": "equals", "value": "true" }
+                        ],
+                        "combineWith": "and"
+                    }
+                }
+            },
+            http("h-yes", 300),
+            http("h-no", 400)
+        ],
+        "edges": [
+            { "id": "e1", "source": "t1", "target": "h0" },
+            { "id": "e2", "source": "h0", "target": "if-1" },
+            { "id": "e3", "source": "if-1", "target": "h-yes", "sourceHandle": "true" },
+            { "id": "e4", "source": "if-1", "target": "h-no", "sourceHandle": "false" }
+        ],
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap()
+}
+
+fn lower_open_paths(
+    workflow: &parse::Workflow,
+    options: &lower::LowerOptions,
+) -> Result<compiler::ir::WorkflowIR, Vec<compiler::error::CompilerError>> {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    let errors = validate::validate_graph(workflow, &graph);
+    assert!(errors.is_empty(), "Validation errors: {:?}", errors);
+    lower::lower_with_options(workflow, &graph, options).map(|(ir, _)| ir)
+}
+
+fn as_auto_return(step: &Step) -> &ValueExpr {
+    match &step.operation {
+        Operation::Return(op) => {
+            assert!(
+                op.auto_generated,
+                "{} should be marked auto-generated",
+                step.id
+            );
+            &op.expression
+        }
+        other => panic!("Expected Return, got {:?}", other),
+    }
+}
+
+#[test]
+fn if_edge_labels_become_branch_arm_labels() {
+    let mut workflow = branch_without_returns(None);
+    workflow.edges[2].label = Some("Healthy".into());
+    workflow.edges[3].label = Some("  Degraded\n service ".into());
+    let ir = lower_open_paths(&workflow, &lower::LowerOptions::default()).unwrap();
+    let Operation::Branch(branch) = &ir.handler_body.steps[1].operation else {
+        panic!("Expected Branch");
+    };
+    assert_eq!(branch.true_label.as_deref(), Some("Healthy"));
+    assert_eq!(branch.false_label.as_deref(), Some("Degraded service"));
+
+    let ir = lower_open_paths(
+        &branch_without_returns(None),
+        &lower::LowerOptions::default(),
+    )
+    .unwrap();
+    let Operation::Branch(branch) = &ir.handler_body.steps[1].operation else {
+        panic!("Expected Branch");
+    };
+    assert_eq!(branch.true_label, None);
+}
+
+#[test]
+fn auto_return_string_mode_appends_one_return_after_branch() {
+    let workflow = branch_without_returns(None);
+    let ir = lower_open_paths(&workflow, &lower::LowerOptions::default()).unwrap();
+    assert!(matches!(
+        ir.handler_body.steps[1].operation,
+        Operation::Branch(_)
+    ));
+
+    let last = ir.handler_body.steps.last().unwrap();
+    assert_eq!(last.id, "auto-return");
+    match as_auto_return(last) {
+        ValueExpr::Literal(LiteralValue::String { value }) => {
+            assert_eq!(value, "Workflow completed")
+        }
+        other => panic!("Expected string literal, got {:?}", other),
+    }
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+}
+
+#[test]
+fn auto_return_summary_mode_returns_leaf_output_in_each_arm() {
+    let workflow = branch_without_returns(Some("autoSummary"));
+    let ir = lower_open_paths(&workflow, &lower::LowerOptions::default()).unwrap();
+
+    assert_eq!(ir.handler_body.steps.len(), 2, "no return after the branch");
+    let Operation::Branch(branch) = &ir.handler_body.steps[1].operation else {
+        panic!("Expected Branch");
+    };
+    match as_auto_return(branch.true_branch.steps.last().unwrap()) {
+        ValueExpr::RawExpr { expr } => assert_eq!(expr, "__stringify(step_h_yes)"),
+        other => panic!("Expected stringified leaf output, got {:?}", other),
+    }
+    match as_auto_return(branch.false_branch.steps.last().unwrap()) {
+        ValueExpr::RawExpr { expr } => assert_eq!(expr, "__stringify(step_h_no)"),
+        other => panic!("Expected stringified leaf output, got {:?}", other),
+    }
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+
+    let main_ts = compiler::codegen::codegen(&ir)
+        .files
+        .into_iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts.contains("return __stringify(step_h_yes); // auto-generated return"),
+        "main.ts:\n{}",
+        main_ts
+    );
+}
+
+#[test]
+fn auto_return_error_mode_lists_every_open_leaf() {
+    let workflow = branch_without_returns(Some("error"));
+    let errors = lower_open_paths(&workflow, &lower::LowerOptions::default()).unwrap_err();
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors.iter().all(|e| e.code == "L009"));
+    assert_eq!(errors[0].node_id.as_deref(), Some("h-yes"));
+    assert_eq!(errors[1].node_id.as_deref(), Some("h-no"));
+    assert!(
+        errors[1].message.contains("'h-no'"),
+        "{}",
+        errors[1].message
+    );
+    assert_eq!(errors[1].to_json()["path"], "nodes[4]");
+
+    // Options override the workflow setting.
+    let options = lower::LowerOptions {
+        auto_return: Some(parse::types::AutoReturnMode::AutoString),
+        ..Default::default()
+    };
+    assert!(lower_open_paths(&workflow, &options).is_ok());
+}
+
+#[test]
+fn global_cache_default_applies_and_silences_identical_request_warning() {
+    let mut workflow = branch_without_returns(None);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    // h0, h-yes and h-no all GET the same URL without caching.
+    let (_, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    let w005: Vec<_> = warnings.iter().filter(|w| w.code == "W005").collect();
+    assert_eq!(w005.len(), 2, "{:?}", warnings);
+    assert_eq!(w005[0].node_id.as_deref(), Some("h-yes"));
+    assert_eq!(w005[1].node_id.as_deref(), Some("h-no"));
+
+    workflow.global_config.default_cache_max_age = Some(90);
+    let (ir, warnings) = lower::lower_with_warnings(&workflow, &graph).unwrap();
+    assert!(warnings.iter().all(|w| w.code != "W005"), "{:?}", warnings);
+    let Operation::HttpRequest(h0) = &ir.handler_body.steps[0].operation else {
+        panic!("Expected HttpRequest");
+    };
+    assert_eq!(h0.cache_max_age_seconds, Some(90));
+}
+
+#[test]
+fn malformed_references_fail_lowering_with_field_paths() {
+    let mut json: serde_json::Value =
+        serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+    json["nodes"][1]["data"]["config"]["url"] = "https://api.example.com/{{h2.id}}".into();
+    json["nodes"][2]["data"]["config"]["returnExpression"] = "{{h1.body".into();
+    let workflow = parse::parse(&json.to_string()).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let errors = lower::lower(&workflow, &graph).expect_err("Malformed references should fail");
+
+    let errors: Vec<_> = errors.iter().map(|e| e.to_json()).collect();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert_eq!(errors[0]["code"], "L010");
+    assert_eq!(errors[0]["node_id"], "h1");
+    assert_eq!(errors[0]["path"], "nodes[1].data.config.url");
+    assert!(
+        errors[0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("unknown node 'h2'")
+    );
+    assert_eq!(errors[1]["node_id"], "r1");
+    assert_eq!(errors[1]["path"], "nodes[2].data.config.returnExpression");
+    assert!(
+        errors[1]["message"]
+            .as_str()
+            .unwrap()
+            .contains("unclosed '{{' at column 1")
+    );
+}
+
+#[test]
+fn steps_after_a_reconvergence_stay_in_the_enclosing_block() {
+    // Close the diamond: both arms feed Merge `m1`, which flows into a Return.
+    let mut workflow = serde_json::to_value(branch_without_returns(None)).unwrap();
+    workflow["nodes"].as_array_mut().unwrap().extend([
+        serde_json::json!({
+            "id": "m1",
+            "type": "merge",
+            "position": { "x": 0, "y": 500 },
+            "data": { "label": "Join", "config": { "strategy": { "mode": "append" } } }
+        }),
+        serde_json::json!({
+            "id": "r1",
+            "type": "return",
+            "position": { "x": 0, "y": 600 },
+            "data": { "label": "Done", "config": { "returnExpression": "done" } }
+        }),
+    ]);
+    workflow["edges"].as_array_mut().unwrap().extend([
+        serde_json::json!({ "id": "e5", "source": "h-yes", "target": "m1" }),
+        serde_json::json!({ "id": "e6", "source": "h-no", "target": "m1" }),
+        serde_json::json!({ "id": "e7", "source": "m1", "target": "r1" }),
+    ]);
+    let workflow: parse::Workflow = serde_json::from_value(workflow).unwrap();
+    let ir = lower_open_paths(&workflow, &lower::LowerOptions::default()).unwrap();
+
+    let steps = &ir.handler_body.steps;
+    let ids: Vec<&str> = steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["h0", "if-1", "m1", "r1"]);
+    let Operation::Branch(branch) = &steps[1].operation else {
+        panic!("Expected Branch");
+    };
+    assert_eq!(branch.true_branch.steps.len(), 1);
+    assert_eq!(branch.false_branch.steps.len(), 1);
+
+    // Each arm hands its fetch result to the merge.
+    let (_, merge) = merge_step(steps);
+    let arms: Vec<(&str, &str)> = merge
+        .inputs
+        .iter()
+        .map(|input| match &input.value {
+            ValueExpr::Binding(b) => (input.handle_name.as_str(), b.step_id.as_str()),
+            other => panic!("Expected Binding, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(arms, [("true", "h-yes"), ("false", "h-no")]);
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+}
+
+/// t1 fans out to one HTTP fetch per `(id, targetHandle)`, and every fetch
+/// feeds Merge `m1`, which flows into a Return.
+fn fan_in_merge(merge_config: serde_json::Value, inputs: &[(&str, &str)]) -> parse::Workflow {
+    let mut nodes = vec![serde_json::json!({
+        "id": "t1",
+        "type": "cronTrigger",
+        "position": { "x": 0, "y": 0 },
+        "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+    })];
+    let mut edges = Vec::new();
+    for (i, (id, handle)) in inputs.iter().enumerate() {
+        nodes.push(serde_json::json!({
+            "id": id,
+            "type": "httpRequest",
+            "position": { "x": i * 100, "y": 100 },
+            "data": {
+                "label": id,
+                "config": { "method": "GET", "url": format!("https://api.example.com/{id}") }
+            }
+        }));
+        edges.push(serde_json::json!({ "id": format!("in-{id}"), "source": "t1", "target": id }));
+        edges.push(serde_json::json!({
+            "id": format!("merge-{id}"),
+            "source": id,
+            "target": "m1",
+            "targetHandle": handle
+        }));
+    }
+    nodes.push(serde_json::json!({
+        "id": "m1",
+        "type": "merge",
+        "position": { "x": 0, "y": 200 },
+        "data": { "label": "Combine", "config": merge_config }
+    }));
+    nodes.push(serde_json::json!({
+        "id": "r1",
+        "type": "return",
+        "position": { "x": 0, "y": 300 },
+        "data": { "label": "Done", "config": { "returnExpression": "done" } }
+    }));
+    edges.push(serde_json::json!({ "id": "out", "source": "m1", "target": "r1" }));
+
+    serde_json::from_value(serde_json::json!({
+        "id": "fan-in",
+        "name": "Fan In",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": nodes,
+        "edges": edges,
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap()
+}
+
+fn merge_step(steps: &[Step]) -> (&Step, &compiler::ir::types::MergeOp) {
+    steps
+        .iter()
+        .find_map(|s| match &s.operation {
+            Operation::Merge(op) => Some((s, op)),
+            _ => None,
+        })
+        .expect("merge step")
+}
+
+#[test]
+fn two_http_fan_in_appends_into_array() {
+    // Edges are added input2 first; inputs follow the handles.
+    let workflow = fan_in_merge(
+        serde_json::json!({ "strategy": { "mode": "append" } }),
+        &[("h1", "input2"), ("h2", "input1")],
+    );
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    assert!(
+        compiler::ir::validate_ir(&ir).is_empty(),
+        "{:?}",
+        compiler::ir::validate_ir(&ir)
+    );
+
+    let (step, merge) = merge_step(&ir.handler_body.steps);
+    assert_eq!(merge.branch_step_id, None);
+    assert!(matches!(merge.strategy, MergeStrategy::Append));
+    let handles: Vec<&str> = merge
+        .inputs
+        .iter()
+        .map(|i| i.handle_name.as_str())
+        .collect();
+    assert_eq!(handles, ["input1", "input2"]);
+    assert!(
+        matches!(&merge.inputs[0].value, ValueExpr::Binding(b) if b.step_id == "h2" && b.field_path.is_empty())
+    );
+    assert_eq!(step.output.as_ref().unwrap().ts_type, "any[]");
+
+    let output = compiler::codegen::codegen(&ir);
+    let main_ts = &output
+        .files
+        .iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap()
+        .content;
+    assert!(
+        main_ts.contains("const step_m1 = [step_h2, step_h1];"),
+        "main.ts:\n{}",
+        main_ts
+    );
+}
+
+#[test]
+fn three_input_merge_honors_number_of_inputs() {
+    let inputs = [("h1", "input1"), ("h2", "input2"), ("h3", "input3")];
+    let lower_with = |config: serde_json::Value| {
+        let workflow = fan_in_merge(config, &inputs);
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        lower::lower_with_warnings(&workflow, &graph)
+    };
+
+    let (ir, warnings) = lower_with(serde_json::json!({
+        "strategy": { "mode": "position" },
+        "numberOfInputs": 3
+    }))
+    .unwrap();
+    let (_, merge) = merge_step(&ir.handler_body.steps);
+    assert_eq!(merge.inputs.len(), 3);
+    // `position` is not supported yet: appended, with a warning.
+    assert!(matches!(merge.strategy, MergeStrategy::Append));
+    let w009 = warnings.iter().find(|w| w.code == "W009").expect("W009");
+    assert_eq!(w009.node_id.as_deref(), Some("m1"));
+
+    let errors = lower_with(serde_json::json!({
+        "strategy": { "mode": "append" },
+        "numberOfInputs": 2
+    }))
+    .unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code, "L011");
+    assert!(
+        errors[0].message.contains("expects 2 inputs"),
+        "{}",
+        errors[0].message
+    );
+    assert_eq!(
+        compiler::error::format_json_path(&errors[0].path),
+        "nodes[4].data.config.numberOfInputs"
+    );
+}
+
+#[test]
+fn cron_trigger_fields_are_checked() {
+    let lower_with_url = |url: &str| {
+        let json = include_str!("fixtures/linear_workflow.json")
+            .replace("https://api.example.com/data", url);
+        let workflow = parse::parse(&json).unwrap();
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        lower::lower_with_warnings(&workflow, &graph).unwrap().1
+    };
+
+    let warnings = lower_with_url("https://api.example.com/data?at={{t1.scheduledTime}}");
+    assert!(warnings.iter().all(|w| w.code != "W012"), "{:?}", warnings);
+
+    let warnings = lower_with_url("https://api.example.com/data?at={{Trigger.body.at}}");
+    let w012: Vec<_> = warnings.iter().filter(|w| w.code == "W012").collect();
+    assert_eq!(w012.len(), 1, "{:?}", warnings);
+    assert_eq!(w012[0].node_id.as_deref(), Some("h1"));
+    assert!(w012[0].message.contains("'body.at'"), "{}", w012[0].message);
+    assert!(w012[0].message.contains("scheduledTime, actualTime"));
+}
+
+#[test]
+fn http_transport_flags_survive_lowering() {
+    // linear_workflow.json is on testnet, where ignoreSSL is only a warning.
+    let json = include_str!("fixtures/linear_workflow.json").replace(
+        r#""responseFormat": "json""#,
+        r#""responseFormat": "json", "followRedirects": true, "ignoreSSL": true"#,
+    );
+    let workflow = parse::parse(&json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.iter().all(|e| e.is_warning()), "{:?}", errors);
+
+    let ir = lower::lower(&workflow, &graph).unwrap();
+    let Operation::HttpRequest(op) = &ir.handler_body.steps[0].operation else {
+        panic!("expected an HTTP request step");
+    };
+    assert!(op.follow_redirects);
+    assert!(op.ignore_ssl);
+}
+
+#[test]
+fn form_body_object_is_lowered_to_fields() {
+    let lower_body = |data: &str| {
+        let body = serde_json::json!({ "contentType": "formUrlEncoded", "data": data });
+        let json = include_str!("fixtures/linear_workflow.json")
+            .replace(r#""method": "GET""#, r#""method": "POST""#)
+            .replace(
+                r#""responseFormat": "json""#,
+                &format!(r#""responseFormat": "json", "body": {}"#, body),
+            );
+        let workflow = parse::parse(&json).unwrap();
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        let ir = lower::lower(&workflow, &graph).unwrap();
+        let Operation::HttpRequest(op) = &ir.handler_body.steps[0].operation else {
+            panic!("expected an HTTP request step");
+        };
+        op.body.clone().unwrap()
+    };
+
+    let body =
+        lower_body(r#"{ "at": {{t1.scheduledTime}}, "memo": "run {{t1.scheduledTime}}", "n": 2 }"#);
+    assert!(matches!(body.data, ValueExpr::Literal(LiteralValue::Null)));
+    let keys: Vec<&str> = body.fields.iter().map(|(k, _)| k.as_str()).collect();
+    assert_eq!(keys, ["at", "memo", "n"]);
+    assert!(matches!(
+        &body.fields[0].1,
+        ValueExpr::TriggerDataRef { field } if field == "scheduledTime"
+    ));
+    assert!(matches!(&body.fields[1].1, ValueExpr::Template { .. }));
+    assert!(matches!(
+        &body.fields[2].1,
+        ValueExpr::Literal(LiteralValue::String { value }) if value == "2"
+    ));
+
+    // Anything else is a pre-built form string.
+    let body = lower_body("a=1&at={{t1.scheduledTime}}");
+    assert!(body.fields.is_empty());
+    assert!(matches!(body.data, ValueExpr::Template { .. }));
+}
+
+#[test]
+fn json_body_literal_is_stringified_once() {
+    let lower_body = |data: &str| {
+        let body = serde_json::json!({ "contentType": "json", "data": data });
+        let json = include_str!("fixtures/linear_workflow.json")
+            .replace(r#""method": "GET""#, r#""method": "POST""#)
+            .replace(
+                r#""responseFormat": "json""#,
+                &format!(r#""responseFormat": "json", "body": {}"#, body),
+            );
+        let workflow = parse::parse(&json).unwrap();
+        let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+        lower::lower_with_warnings(&workflow, &graph).unwrap()
+    };
+
+    let (ir, warnings) = lower_body(r#"{ "asset": "ETH", "amounts": [1, 2] }"#);
+    assert!(warnings.iter().all(|w| w.code != "W017"), "{:?}", warnings);
+    let Operation::HttpRequest(op) = &ir.handler_body.steps[0].operation else {
+        panic!("expected an HTTP request step");
+    };
+    assert!(matches!(
+        &op.body.as_ref().unwrap().data,
+        ValueExpr::Literal(LiteralValue::Json { value }) if value.starts_with("{ \"asset\"")
+    ));
+    let main_ts = &compiler::codegen::codegen(&ir).files[0].content;
+    assert!(
+        main_ts.contains(r#"encode(JSON.stringify({ "asset": "ETH", "amounts": [1, 2] }))"#),
+        "{}",
+        main_ts
+    );
+
+    let (ir, warnings) = lower_body("asset=ETH");
+    let w017: Vec<_> = warnings.iter().filter(|w| w.code == "W017").collect();
+    assert_eq!(w017.len(), 1, "{:?}", warnings);
+    assert_eq!(w017[0].node_id.as_deref(), Some("h1"));
+    let Operation::HttpRequest(op) = &ir.handler_body.steps[0].operation else {
+        panic!("expected an HTTP request step");
+    };
+    assert!(matches!(
+        &op.body.as_ref().unwrap().data,
+        ValueExpr::Literal(LiteralValue::String { value }) if value == "asset=ETH"
+    ));
+}
+
+/// t1 → h1, h2 → ABI Decode d1 → r1; `data_from` is the fetch whose edge into
+/// d1 uses the `data` target handle, and `first` is the fetch whose edge into
+/// d1 is listed first.
+fn decode_of_two_fetches(first: &str, data_from: &str) -> parse::Workflow {
+    let fetch = |id: &str| {
+        serde_json::json!({
+            "id": id,
+            "type": "httpRequest",
+            "position": { "x": 0, "y": 100 },
+            "data": {
+                "label": id,
+                "config": { "method": "GET", "url": format!("https://api.example.com/{id}") }
+            }
+        })
+    };
+    let second = if first == "h1" { "h2" } else { "h1" };
+    let into_decode = |id: &str| {
+        let handle = (data_from == id).then_some("data");
+        serde_json::json!({
+            "id": format!("{id}-d1"),
+            "source": id,
+            "target": "d1",
+            "targetHandle": handle
+        })
+    };
+    serde_json::from_value(serde_json::json!({
+        "id": "decode",
+        "name": "Decode",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "cronTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+            },
+            fetch("h1"),
+            fetch("h2"),
+            {
+                "id": "d1",
+                "type": "abiDecode",
+                "position": { "x": 0, "y": 200 },
+                "data": {
+                    "label": "Decode",
+                    "config": {
+                        "abiParams": [{ "name": "price", "type": "uint256" }],
+                        "outputNames": ["price"]
+                    }
+                }
+            },
+            {
+                "id": "r1",
+                "type": "return",
+                "position": { "x": 0, "y": 300 },
+                "data": { "label": "Done", "config": { "returnExpression": "done" } }
+            }
+        ],
+        "edges": [
+            { "id": "t1-h1", "source": "t1", "target": "h1" },
+            { "id": "t1-h2", "source": "t1", "target": "h2" },
+            into_decode(first),
+            into_decode(second),
+            { "id": "d1-r1", "source": "d1", "target": "r1" }
+        ],
+        "createdAt": "",
+        "updatedAt": ""
+    }))
+    .unwrap()
+}
+
+fn decode_input_step(workflow: &parse::Workflow) -> String {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    assert!(validate::validate_graph(workflow, &graph).is_empty());
+    let ir = lower::lower(workflow, &graph).unwrap();
+    let decode = ir
+        .handler_body
+        .steps
+        .iter()
+        .find_map(|s| match &s.operation {
+            Operation::AbiDecode(op) => Some(op),
+            _ => None,
+        })
+        .expect("decode step");
+    match &decode.input {
+        ValueExpr::Binding(binding) => binding.step_id.clone(),
+        other => panic!("expected a binding, got {:?}", other),
+    }
+}
+
+#[test]
+fn data_handle_picks_the_implicit_input() {
+    // Whichever edge is listed first, the `data` one wins.
+    for (first, data_from) in [("h1", "h2"), ("h2", "h1"), ("h1", "h1"), ("h2", "h2")] {
+        let workflow = decode_of_two_fetches(first, data_from);
+        assert_eq!(decode_input_step(&workflow), data_from);
+    }
+}
+
+fn broadcast_write(chains: &[&str]) -> parse::Workflow {
+    let json = serde_json::json!({
+        "id": "broadcast",
+        "name": "Broadcast Mint",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": [
+            {
+                "id": "t1",
+                "type": "cronTrigger",
+                "position": { "x": 0, "y": 0 },
+                "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+            },
+            {
+                "id": "broadcast-1",
+                "type": "broadcastWrite",
+                "position": { "x": 200, "y": 0 },
+                "data": {
+                    "label": "Mirror Mint",
+                    "config": {
+                        "chainSelectorNames": chains,
+                        "receiverAddress": "0x1234567890abcdef1234567890abcdef12345678",
+                        "receiverAddresses": {
+                            "ethereum-mainnet": "0xabcdefabcdefabcdefabcdefabcdefabcdefabcd"
+                        },
+                        "gasLimit": "300000",
+                        "abiParams": [{ "name": "amount", "type": "uint256" }],
+                        "dataMapping": [{ "paramName": "amount", "source": "1000" }]
+                    }
+                }
+            },
+            {
+                "id": "r1",
+                "type": "return",
+                "position": { "x": 400, "y": 0 },
+                "data": {
+                    "label": "Done",
+                    "config": { "returnExpression": "{{broadcast-1.txHashes}}" }
+                }
+            }
+        ],
+        "edges": [
+            { "id": "e1", "source": "t1", "target": "broadcast-1" },
+            { "id": "e2", "source": "broadcast-1", "target": "r1" }
+        ],
+        "createdAt": "2025-01-01T00:00:00Z",
+        "updatedAt": "2025-01-01T00:00:00Z"
+    });
+    parse::parse(&json.to_string()).unwrap()
+}
+
+#[test]
+fn broadcast_write_expands_to_one_write_per_chain() {
+    let workflow = broadcast_write(&[
+        "ethereum-testnet-sepolia",
+        "ethereum-mainnet",
+        "ethereum-testnet-sepolia-base-1",
+    ]);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.is_empty(), "Validation errors: {:?}", errors);
+    let ir = lower::lower(&workflow, &graph).expect("Should lower successfully");
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+
+    let ids: Vec<&str> = ir
+        .handler_body
+        .steps
+        .iter()
+        .map(|s| s.id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            "broadcast-1___encode",
+            "broadcast-1___write_ethereum-testnet-sepolia",
+            "broadcast-1___write_ethereum-mainnet",
+            "broadcast-1___write_ethereum-testnet-sepolia-base-1",
+            "broadcast-1___tx_hashes",
+            "r1",
+        ]
+    );
+    let writes: Vec<_> = ir
+        .handler_body
+        .steps
+        .iter()
+        .filter_map(|s| match &s.operation {
+            Operation::EvmWrite(op) => Some(op),
+            _ => None,
+        })
+        .collect();
+    let bindings: Vec<&str> = writes
+        .iter()
+        .map(|op| op.evm_client_binding.as_str())
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            "evmClient_ethereum_testnet_sepolia",
+            "evmClient_ethereum_mainnet",
+            "evmClient_ethereum_testnet_sepolia_base_1",
+        ]
+    );
+    assert!(matches!(
+        &writes[1].receiver_address,
+        ValueExpr::Literal(LiteralValue::String { value }) if value.starts_with("0xabcdef")
+    ));
+    assert!(matches!(
+        &writes[0].encoded_data,
+        ValueExpr::Binding(b) if b.step_id == "broadcast-1___encode" && b.field_path == "encoded"
+    ));
+    let chains: Vec<&str> = ir
+        .evm_chains
+        .iter()
+        .map(|c| c.chain_selector_name.as_str())
+        .collect();
+    assert_eq!(
+        chains,
+        [
+            "ethereum-testnet-sepolia",
+            "ethereum-mainnet",
+            "ethereum-testnet-sepolia-base-1",
+        ]
+    );
+    assert!(
+        ir.handler_body.steps[..5]
+            .iter()
+            .all(|s| s.source_node_ids == ["broadcast-1"])
+    );
+
+    // {{broadcast-1.txHashes}} reads the aggregate step
+    match &ir.handler_body.steps[5].operation {
+        Operation::Return(op) => assert!(matches!(
+            &op.expression,
+            ValueExpr::Binding(b) if b.step_id == "broadcast-1___tx_hashes" && b.field_path == "txHashes"
+        )),
+        other => panic!("Expected Return operation, got {:?}", other),
+    }
+}
+
+#[test]
+fn broadcast_write_counts_every_chain_against_the_write_budget() {
+    let chains = [
+        "ethereum-testnet-sepolia",
+        "ethereum-mainnet",
+        "ethereum-testnet-sepolia-base-1",
+        "ethereum-testnet-sepolia-arbitrum-1",
+        "ethereum-testnet-sepolia-optimism-1",
+        "polygon-testnet-amoy",
+    ];
+    let workflow = broadcast_write(&chains);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).expect("Should lower successfully");
+    let errors = compiler::ir::validate_ir(&ir);
+    assert!(errors.iter().any(|e| e.code == "E011"), "{:?}", errors);
+
+    let workflow = broadcast_write(&chains[..5]);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let ir = lower::lower(&workflow, &graph).expect("Should lower successfully");
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+}
+
+/// `count` encode/write pairs to one contract, then a return of the last
+/// write's transaction hash.
+fn writes_to_one_contract(count: usize) -> parse::Workflow {
+    let mut nodes = vec![serde_json::json!({
+        "id": "t1",
+        "type": "cronTrigger",
+        "position": { "x": 0, "y": 0 },
+        "data": { "label": "Trigger", "config": { "schedule": "0 */5 * * * *" } }
+    })];
+    let mut edges = Vec::new();
+    let mut previous = "t1".to_string();
+    for i in 1..=count {
+        let (encode, write) = (format!("encode-{i}"), format!("write-{i}"));
+        nodes.push(serde_json::json!({
+            "id": encode,
+            "type": "abiEncode",
+            "position": { "x": 200 * i, "y": 0 },
+            "data": {
+                "label": format!("Encode {i}"),
+                "config": {
+                    "abiParams": [{ "name": "amount", "type": "uint256" }],
+                    "dataMapping": [{ "paramName": "amount", "source": format!("{i}000") }]
+                }
+            }
+        }));
+        nodes.push(serde_json::json!({
+            "id": write,
+            "type": "evmWrite",
+            "position": { "x": 200 * i + 100, "y": 0 },
+            "data": {
+                "label": format!("Write {i}"),
+                "config": {
+                    "chainSelectorName": "ethereum-testnet-sepolia",
+                    "receiverAddress": "0x1234567890abcdef1234567890abcdef12345678",
+                    "gasLimit": "200000",
+                    "encodedData": format!("{{{{{encode}.encoded}}}}")
+                }
+            }
+        }));
+        edges.push(serde_json::json!({ "id": format!("e-{encode}"), "source": previous, "target": encode }));
+        edges.push(
+            serde_json::json!({ "id": format!("e-{write}"), "source": encode, "target": write }),
+        );
+        previous = write;
+    }
+    nodes.push(serde_json::json!({
+        "id": "r1",
+        "type": "return",
+        "position": { "x": 200 * count + 400, "y": 0 },
+        "data": {
+            "label": "Done",
+            "config": { "returnExpression": format!("{{{{{previous}.txHash}}}}") }
+        }
+    }));
+    edges.push(serde_json::json!({ "id": "e-r1", "source": previous, "target": "r1" }));
+    let json = serde_json::json!({
+        "id": "writes",
+        "name": "Writes",
+        "version": "1.0.0",
+        "globalConfig": { "isTestnet": true, "secrets": [], "rpcs": [] },
+        "nodes": nodes,
+        "edges": edges,
+        "createdAt": "2025-01-01T00:00:00Z",
+        "updatedAt": "2025-01-01T00:00:00Z"
+    });
+    parse::parse(&json.to_string()).unwrap()
+}
+
+fn lower_batched(
+    workflow: &parse::Workflow,
+    batch_evm_writes: bool,
+) -> compiler::ir::types::WorkflowIR {
+    let graph = parse::WorkflowGraph::build(workflow).unwrap();
+    let options = lower::LowerOptions {
+        batch_evm_writes,
+        ..Default::default()
+    };
+    lower::lower_with_options(workflow, &graph, &options)
+        .expect("Should lower successfully")
+        .0
+}
+
+#[test]
+fn batch_evm_writes_folds_adjacent_writes_into_one_multicall() {
+    let workflow = writes_to_one_contract(2);
+    let ids = |ir: &compiler::ir::types::WorkflowIR| -> Vec<String> {
+        ir.handler_body.steps.iter().map(|s| s.id.clone()).collect()
+    };
+    assert_eq!(
+        ids(&lower_batched(&workflow, false)),
+        ["encode-1", "write-1", "encode-2", "write-2", "r1"]
+    );
+
+    let ir = lower_batched(&workflow, true);
+    assert!(
+        compiler::ir::validate_ir(&ir).is_empty(),
+        "{:?}",
+        compiler::ir::validate_ir(&ir)
+    );
+    assert_eq!(
+        ids(&ir),
+        [
+            "encode-1",
+            "encode-2",
+            "write-1___multicall",
+            "write-1",
+            "r1"
+        ]
+    );
+    let Operation::EvmWrite(write) = &ir.handler_body.steps[3].operation else {
+        panic!("Expected EvmWrite");
+    };
+    assert!(matches!(
+        &write.gas_limit,
+        ValueExpr::Literal(LiteralValue::Integer { value: 400_000 })
+    ));
+    assert!(matches!(
+        &write.encoded_data,
+        ValueExpr::Binding(b) if b.step_id == "write-1___multicall" && b.field_path == "encoded"
+    ));
+    assert_eq!(
+        ir.handler_body.steps[3].source_node_ids,
+        ["write-1", "write-2"]
+    );
+    // {{write-2.txHash}} reads the batched write
+    let Operation::Return(ret) = &ir.handler_body.steps[4].operation else {
+        panic!("Expected Return");
+    };
+    assert!(matches!(
+        &ret.expression,
+        ValueExpr::Binding(b) if b.step_id == "write-1" && b.field_path == "txHash"
+    ));
+
+    let main_ts = &compiler::codegen::codegen(&ir).files[0].content;
+    assert_eq!(
+        main_ts.matches(".writeReport(runtime").count(),
+        1,
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains("functionName: \"multicall\","),
+        "{main_ts}"
+    );
+    assert!(
+        main_ts.contains("args: [[step_encode_1.encoded, step_encode_2.encoded]],"),
+        "{main_ts}"
+    );
+}
+
+#[test]
+fn batched_writes_count_once_against_the_write_budget() {
+    let workflow = writes_to_one_contract(6);
+    let errors = compiler::ir::validate_ir(&lower_batched(&workflow, false));
+    assert!(errors.iter().any(|e| e.code == "E011"), "{:?}", errors);
+
+    let ir = lower_batched(&workflow, true);
+    assert!(compiler::ir::validate_ir(&ir).is_empty());
+    let writes = ir
+        .handler_body
+        .steps
+        .iter()
+        .filter(|s| matches!(s.operation, Operation::EvmWrite(_)))
+        .count();
+    assert_eq!(writes, 1);
+}
+
+#[test]
+fn handler_size_is_checked_after_batching_writes() {
+    // 100 encode/write pairs and a return: 201 steps, or 103 once batched.
+    let workflow = writes_to_one_contract(100);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let size_warnings = |batch_evm_writes: bool| {
+        let options = lower::LowerOptions {
+            batch_evm_writes,
+            ..Default::default()
+        };
+        let (_, warnings) = lower::lower_with_options(&workflow, &graph, &options).unwrap();
+        warnings.iter().filter(|w| w.code == "W019").count()
+    };
+
+    assert_eq!(size_warnings(false), 1);
+    assert_eq!(size_warnings(true), 0);
+}
@@ -34,6 +34,84 @@ fn parse_invalid_json_returns_error() {
     assert!(errors[0].code == "P001");
 }
 
+/// The linear fixture with its HTTP request moved to `nodes[2]`.
+fn linear_with_http_third() -> serde_json::Value {
+    let mut workflow: serde_json::Value =
+        serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+    workflow["nodes"].as_array_mut().unwrap().swap(1, 2);
+    assert_eq!(workflow["nodes"][2]["type"], "httpRequest");
+    workflow
+}
+
+#[test]
+fn parse_error_path_points_at_node_config_field() {
+    let mut bad_url = linear_with_http_third();
+    bad_url["nodes"][2]["data"]["config"]["url"] = serde_json::json!(42);
+    let errors = parse::parse(&bad_url.to_string()).unwrap_err();
+    assert_eq!(errors[0].code, "P001");
+    assert_eq!(errors[0].to_json()["path"], "nodes[2].data.config.url");
+
+    let mut missing_url = linear_with_http_third();
+    missing_url["nodes"][2]["data"]["config"]
+        .as_object_mut()
+        .unwrap()
+        .remove("url");
+    let errors = parse::parse(&missing_url.to_string()).unwrap_err();
+    assert!(
+        errors[0].message.contains("missing field `url`"),
+        "{}",
+        errors[0]
+    );
+    assert_eq!(errors[0].to_json()["path"], "nodes[2].data.config.url");
+}
+
+#[test]
+fn parse_ignores_unknown_fields() {
+    let mut workflow = linear_with_http_third();
+    workflow["nodes"][2]["data"]["config"]["retryPolicy"] = serde_json::json!({ "maxRetries": 3 });
+    workflow["nodes"][2]["data"]["badge"] = "beta".into();
+    workflow["globalConfig"]["theme"] = "dark".into();
+
+    let workflow = parse::parse(&workflow.to_string()).expect("unknown fields should be ignored");
+    let parse::WorkflowNode::HttpRequest(http) = &workflow.nodes[2] else {
+        panic!("expected an httpRequest node");
+    };
+    assert_eq!(http.data.config.method, "GET");
+}
+
+#[test]
+fn parse_rejects_unknown_node_type() {
+    let mut workflow = linear_with_http_third();
+    workflow["nodes"][2]["type"] = "futureNode".into();
+    workflow["nodes"][0]["type"] = "futureTrigger".into();
+
+    let errors = parse::parse(&workflow.to_string()).unwrap_err();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert_eq!(errors[1].code, "P003");
+    assert_eq!(errors[1].node_id.as_deref(), Some("h1"));
+    assert!(
+        errors[1].message.starts_with(
+            "Node 'h1' has type 'futureNode', which this compiler version does not support"
+        ),
+        "{}",
+        errors[1]
+    );
+    assert_eq!(errors[1].to_json()["path"], "nodes[2].type");
+    assert_eq!(errors[0].to_json()["path"], "nodes[0].type");
+}
+
+#[test]
+fn parse_defaults_omitted_empty_lists() {
+    let mut workflow = linear_with_http_third();
+    let global_config = workflow["globalConfig"].as_object_mut().unwrap();
+    global_config.remove("secrets");
+    global_config.remove("rpcs");
+
+    let workflow = parse::parse(&workflow.to_string()).expect("Should parse");
+    assert!(workflow.global_config.secrets.is_empty());
+    assert!(workflow.global_config.rpcs.is_empty());
+}
+
 #[test]
 fn parse_node_types_correct() {
     let json = include_str!("fixtures/example_workflow.json");
@@ -61,3 +139,197 @@ fn build_graph_from_example() {
     assert_eq!(graph.outgoing_count("return-1"), 0);
     assert_eq!(graph.outgoing_count("return-2"), 0);
 }
+
+#[test]
+fn build_graph_drops_duplicate_edges_with_warning() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).expect("Should parse");
+    let mut duplicate = workflow.edges[0].clone();
+    duplicate.id = "e1-copy".into();
+    workflow.edges.push(duplicate);
+
+    let graph = parse::WorkflowGraph::build(&workflow).expect("Should build graph");
+    assert!(
+        graph
+            .warnings
+            .iter()
+            .any(|w| w.code == "W003" && w.message.contains("e1-copy")),
+        "Expected W003 for duplicate edge, got: {:?}",
+        graph.warnings
+    );
+    assert_eq!(graph.predecessors("h1"), vec!["t1"]);
+    assert_eq!(graph.outgoing_count("t1"), 1);
+}
+
+#[test]
+fn single_entry_holds_for_connected_graph() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let workflow = parse::parse(json).expect("Should parse");
+    let graph = parse::WorkflowGraph::build(&workflow).expect("Should build graph");
+    assert!(graph.validate_single_entry(&workflow).is_empty());
+}
+
+#[test]
+fn single_entry_reports_orphaned_node_pair_once() {
+    let mut workflow: serde_json::Value =
+        serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+    // Copies of the HTTP and Return nodes, wired to each other only.
+    let mut http = workflow["nodes"][1].clone();
+    http["id"] = "h2".into();
+    let mut ret = workflow["nodes"][2].clone();
+    ret["id"] = "r2".into();
+    workflow["nodes"]
+        .as_array_mut()
+        .unwrap()
+        .extend([http, ret]);
+    workflow["edges"]
+        .as_array_mut()
+        .unwrap()
+        .push(serde_json::json!({ "id": "e3", "source": "h2", "target": "r2" }));
+    let workflow = parse::parse(&workflow.to_string()).expect("Should parse");
+    let graph = parse::WorkflowGraph::build(&workflow).expect("Should build graph");
+
+    let warnings = graph.validate_single_entry(&workflow);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].code, "W007");
+    assert!(
+        warnings[0].message.contains("'h2', 'r2'"),
+        "{}",
+        warnings[0]
+    );
+    assert_eq!(warnings[0].node_id.as_deref(), Some("h2"));
+    assert_eq!(warnings[0].related[0].node_ids, vec!["h2", "r2"]);
+    assert_eq!(warnings[0].to_json()["path"], "nodes[3]");
+}
+
+#[test]
+fn parse_migrates_schema_1_workflow_to_current_types() {
+    let json = include_str!("fixtures/migrations/v1_workflow.json");
+    let (workflow, infos) =
+        parse::parse_with_migrations(json).expect("Should parse after migration");
+    assert_eq!(
+        workflow.schema_version,
+        parse::migrate::CURRENT_SCHEMA_VERSION
+    );
+
+    let parse::WorkflowNode::HttpRequest(h1) = &workflow.nodes[1] else {
+        panic!("expected h1 to be an HTTP request");
+    };
+    assert_eq!(h1.data.config.cache_max_age, Some(60));
+    assert!(matches!(
+        &h1.data.config.authentication,
+        Some(parse::HttpAuthConfig::BearerToken { token_secret }) if token_secret == "API_KEY"
+    ));
+    let parse::WorkflowNode::Filter(f1) = &workflow.nodes[2] else {
+        panic!("expected f1 to be a filter");
+    };
+    assert_eq!(f1.data.config.combine_with, "and");
+    assert!(matches!(
+        &f1.data.config.conditions[1],
+        parse::ConditionNode::Group(group) if group.combine_with == "or"
+    ));
+    let parse::WorkflowNode::HttpRequest(h2) = &workflow.nodes[3] else {
+        panic!("expected h2 to be an HTTP request");
+    };
+    assert!(matches!(
+        h2.data.config.authentication,
+        Some(parse::HttpAuthConfig::None)
+    ));
+    let body = h2.data.config.body.as_ref().unwrap();
+    assert_eq!(body.content_type, "json");
+    assert_eq!(body.data, r#"{ "source": "price-feed" }"#);
+
+    // One I001 per node each migration rewrote, in migration order
+    assert!(
+        infos.iter().all(|i| i.code == "I001" && i.is_info()),
+        "{:?}",
+        infos
+    );
+    let migrated: Vec<(String, String)> = infos
+        .iter()
+        .map(|i| {
+            let json = i.to_json();
+            (
+                json["node_id"].as_str().unwrap().into(),
+                json["path"].as_str().unwrap().into(),
+            )
+        })
+        .collect();
+    let expected = [
+        ("h1", "nodes[1].data.config"),
+        ("f1", "nodes[2].data.config"),
+        ("h1", "nodes[1].data.config"),
+        ("h2", "nodes[3].data.config"),
+        ("h2", "nodes[3].data.config"),
+    ];
+    assert_eq!(
+        migrated,
+        expected.map(|(id, path)| (id.to_string(), path.to_string()))
+    );
+    assert!(
+        infos[0].message.contains("cacheTtl"),
+        "{}",
+        infos[0].message
+    );
+
+    // A current document needs no migration
+    let json = include_str!("fixtures/linear_workflow.json");
+    let (_, infos) = parse::parse_with_migrations(json).unwrap();
+    assert!(infos.is_empty(), "{:?}", infos);
+}
+
+#[test]
+fn parse_applies_only_migrations_after_the_saved_schema_version() {
+    let json = include_str!("fixtures/migrations/v3_workflow.json");
+    let (workflow, infos) =
+        parse::parse_with_migrations(json).expect("Should parse after migration");
+    let parse::WorkflowNode::HttpRequest(h1) = &workflow.nodes[1] else {
+        panic!("expected h1 to be an HTTP request");
+    };
+    assert!(matches!(
+        &h1.data.config.authentication,
+        Some(parse::HttpAuthConfig::BearerToken { token_secret }) if token_secret == "API_KEY"
+    ));
+    assert_eq!(h1.data.config.body.as_ref().unwrap().content_type, "json");
+
+    let versions: Vec<&str> = infos
+        .iter()
+        .map(|i| i.message.split(':').next().unwrap())
+        .collect();
+    assert_eq!(
+        versions,
+        [
+            "Migrated from schemaVersion 3",
+            "Migrated from schemaVersion 4"
+        ]
+    );
+}
+
+#[test]
+fn parse_rejects_unknown_schema_version() {
+    let with_version = |version: serde_json::Value| {
+        let mut workflow: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+        workflow["schemaVersion"] = version;
+        parse::parse(&workflow.to_string())
+    };
+    let current = parse::migrate::CURRENT_SCHEMA_VERSION;
+    assert!(with_version(current.into()).is_ok());
+
+    for version in [
+        serde_json::json!(current + 1),
+        serde_json::json!("2"),
+        serde_json::json!(0),
+    ] {
+        let errors = with_version(version.clone()).unwrap_err();
+        assert_eq!(errors.len(), 1, "{}: {:?}", version, errors);
+        assert_eq!(errors[0].code, "P004");
+        assert_eq!(errors[0].to_json()["path"], "schemaVersion");
+    }
+    let errors = with_version((current + 1).into()).unwrap_err();
+    assert!(
+        errors[0].message.contains("supports up to"),
+        "{}",
+        errors[0].message
+    );
+}
@@ -0,0 +1,166 @@
+//! Several workflows compiled into one CRE project (J001–J003).
+
+use compiler::project::{ProjectOptions, ProjectOutput, compile_project};
+
+const LINEAR: &str = include_str!("fixtures/linear_workflow.json");
+
+/// The linear workflow with id `id` and each `(from, to)` replacement applied.
+fn linear_variant(id: &str, replacements: &[(&str, &str)]) -> String {
+    replacements.iter().fold(
+        LINEAR.replace(r#""id": "linear-test""#, &format!(r#""id": "{}""#, id)),
+        |json, (from, to)| {
+            assert!(json.contains(from), "{from}");
+            json.replace(from, to)
+        },
+    )
+}
+
+fn file<'a>(project: &'a ProjectOutput, path: &str) -> Option<&'a str> {
+    project
+        .files
+        .iter()
+        .find(|f| f.path == path)
+        .map(|f| &*f.content)
+}
+
+fn codes(project: &ProjectOutput, id: &str) -> Vec<String> {
+    let workflow = project.workflows.iter().find(|w| w.id == id).unwrap();
+    match &workflow.result {
+        Ok(_) => vec![],
+        Err(errors) => errors.iter().map(|e| e.code.clone()).collect(),
+    }
+}
+
+#[test]
+fn workflows_sharing_a_chain_merge_into_one_project() {
+    let alerts = linear_variant("alerts", &[]);
+    let project = compile_project(&[LINEAR, &alerts], &ProjectOptions::default());
+
+    assert!(project.workflows.iter().all(|w| w.result.is_ok()));
+    let project_yaml = file(&project, "project.yaml").unwrap();
+    for chain in ["ethereum-mainnet", "ethereum-testnet-sepolia"] {
+        // Listed once per environment.
+        assert_eq!(
+            project_yaml
+                .matches(&format!("chain-name: {chain}\n"))
+                .count(),
+            2,
+            "{project_yaml}"
+        );
+    }
+    assert!(project_yaml.contains("url: https://rpc.example.com/ethereum-testnet-sepolia"));
+
+    for id in ["linear-test", "alerts"] {
+        let main_ts = file(&project, &format!("{id}/main.ts")).unwrap();
+        assert!(main_ts.contains("api.example.com"), "{main_ts}");
+        assert!(file(&project, &format!("{id}/workflow.yaml")).is_some());
+        assert!(file(&project, &format!("{id}/project.yaml")).is_none());
+        assert!(file(&project, &format!("{id}/secrets.yaml")).is_none());
+    }
+
+    let package_json = file(&project, "package.json").unwrap();
+    assert!(package_json.contains(r#""name": "cre-project""#));
+    assert_eq!(package_json.matches("@chainlink/cre-sdk").count(), 1);
+}
+
+#[test]
+fn conflicting_rpc_urls_leave_the_later_workflow_out() {
+    let alerts = linear_variant(
+        "alerts",
+        &[(
+            "https://rpc.example.com/ethereum-testnet-sepolia",
+            "https://other.example.com/sepolia",
+        )],
+    );
+    let project = compile_project(&[LINEAR, &alerts], &ProjectOptions::default());
+
+    assert_eq!(codes(&project, "linear-test"), Vec::<String>::new());
+    assert_eq!(codes(&project, "alerts"), ["J001"]);
+    let Err(errors) = &project.workflows[1].result else {
+        panic!("alerts should fail");
+    };
+    assert_eq!(errors[0].to_json()["path"], "globalConfig.rpcs[1].url");
+    assert!(errors[0].message.contains("linear-test"), "{}", errors[0]);
+
+    assert!(file(&project, "linear-test/main.ts").is_some());
+    assert!(file(&project, "alerts/main.ts").is_none());
+    let project_yaml = file(&project, "project.yaml").unwrap();
+    assert!(
+        !project_yaml.contains("other.example.com"),
+        "{project_yaml}"
+    );
+}
+
+#[test]
+fn secrets_are_merged_by_name() {
+    let alerts = linear_variant(
+        "alerts",
+        &[
+            (r#""name": "API_KEY""#, r#""name": "ALERT_KEY""#),
+            (
+                r#""envVariable": "API_KEY_VAR""#,
+                r#""envVariable": "ALERT_KEY_VAR""#,
+            ),
+            (
+                r#""tokenSecret": "API_KEY""#,
+                r#""tokenSecret": "ALERT_KEY""#,
+            ),
+        ],
+    );
+    let same_key = linear_variant("settlement", &[]);
+    let project = compile_project(&[LINEAR, &alerts, &same_key], &ProjectOptions::default());
+
+    assert!(project.workflows.iter().all(|w| w.result.is_ok()));
+    assert_eq!(
+        file(&project, "secrets.yaml").unwrap(),
+        "secretsNames:\n  API_KEY:\n    - API_KEY_VAR\n  ALERT_KEY:\n    - ALERT_KEY_VAR\n"
+    );
+
+    // The same secret name read from another variable cannot be merged.
+    let renamed = linear_variant(
+        "renamed",
+        &[(
+            r#""envVariable": "API_KEY_VAR""#,
+            r#""envVariable": "OTHER_VAR""#,
+        )],
+    );
+    let project = compile_project(&[LINEAR, &renamed], &ProjectOptions::default());
+    assert_eq!(codes(&project, "renamed"), ["J002"]);
+    assert_eq!(
+        file(&project, "secrets.yaml").unwrap(),
+        "secretsNames:\n  API_KEY:\n    - API_KEY_VAR\n"
+    );
+}
+
+#[test]
+fn failing_workflows_are_reported_by_id_without_stopping_the_others() {
+    let broken = linear_variant("broken", &[(r#""source": "h1""#, r#""source": "nope""#)]);
+    let project = compile_project(
+        &[&broken, "not json", LINEAR, LINEAR],
+        &ProjectOptions {
+            name: Some("price-suite".into()),
+            ..Default::default()
+        },
+    );
+
+    let ids: Vec<_> = project.workflows.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        ["broken", "workflows[1]", "linear-test", "linear-test"]
+    );
+    assert!(!codes(&project, "broken").is_empty());
+    assert_eq!(codes(&project, "workflows[1]"), ["P001"]);
+    assert!(project.workflows[2].result.is_ok());
+    let Err(errors) = &project.workflows[3].result else {
+        panic!("the duplicate id should fail");
+    };
+    assert_eq!(errors[0].code, "J003");
+
+    assert!(file(&project, "linear-test/main.ts").is_some());
+    assert!(!project.files.iter().any(|f| f.path.starts_with("broken/")));
+    assert!(
+        file(&project, "package.json")
+            .unwrap()
+            .contains(r#""name": "price-suite""#)
+    );
+}
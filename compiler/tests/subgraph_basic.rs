@@ -0,0 +1,87 @@
+//! "Test this node": compiling a node with its upstream chain (S001–S003).
+
+use compiler::subgraph::compile_subgraph;
+
+const KYC: &str = include_str!("fixtures/example_workflow.json");
+
+const KYC_SAMPLES: &str = r#"{
+    "http-1": {
+        "statusCode": 200,
+        "body": { "isApproved": true, "walletAddress": "0xabc", "tokenAmount": "5" }
+    }
+}"#;
+
+#[test]
+fn target_after_http_request_stubs_it_with_the_sample_body() {
+    let compiled = compile_subgraph(KYC, "encode-1", KYC_SAMPLES).expect("slice compiles");
+    let main_ts = compiled
+        .output
+        .files
+        .iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap();
+    let content = &main_ts.content;
+
+    assert!(content.contains("[stub] Check KYC Status"), "{}", content);
+    assert!(
+        content.contains(
+            "Stub: returns the sample input for 'http-1' instead of calling HttpRequest."
+        ),
+        "{}",
+        content
+    );
+    assert!(
+        content.contains(
+            r#"return {"body":{"isApproved":true,"tokenAmount":"5","walletAddress":"0xabc"},"statusCode":200};"#
+        ),
+        "{}",
+        content
+    );
+    // The stub makes no request, and nothing downstream of the target is kept.
+    assert!(!content.contains("HTTPClient"), "{}", content);
+    assert!(!content.contains("step_write_1"), "{}", content);
+    assert!(
+        content.contains("__stringify(step_encode_1)"),
+        "{}",
+        content
+    );
+    // The `if` arm that does not lead to the target still returns.
+    assert!(
+        content.contains("return \"Not on the path to 'encode-1'\";"),
+        "{}",
+        content
+    );
+}
+
+#[test]
+fn missing_sample_and_unknown_target_are_reported() {
+    let errors = compile_subgraph(KYC, "encode-1", "{}").unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "S003");
+    assert_eq!(errors[0].node_id.as_deref(), Some("http-1"));
+    assert_eq!(errors[0].to_json()["path"], "nodes[1]");
+
+    let errors = compile_subgraph(KYC, "nope", KYC_SAMPLES).unwrap_err();
+    assert_eq!(errors[0].code, "S001");
+    let errors = compile_subgraph(KYC, "trigger-1", KYC_SAMPLES).unwrap_err();
+    assert_eq!(errors[0].code, "S001");
+    let errors = compile_subgraph(KYC, "encode-1", "[]").unwrap_err();
+    assert_eq!(errors[0].code, "S002");
+}
+
+#[test]
+fn target_that_is_a_capability_call_runs_for_real() {
+    let compiled = compile_subgraph(KYC, "http-1", "{}").expect("slice compiles");
+    let main_ts = compiled
+        .output
+        .files
+        .iter()
+        .find(|f| f.path == "main.ts")
+        .unwrap();
+    assert!(!main_ts.content.contains("[stub]"), "{}", main_ts.content);
+    assert!(
+        main_ts.content.contains("HTTPClient"),
+        "{}",
+        main_ts.content
+    );
+}
@@ -0,0 +1,74 @@
+//! Integration tests for workflow template instantiation.
+
+use std::collections::HashMap;
+
+use compiler::ir::validate_ir;
+use compiler::parse::types::{Workflow, WorkflowNode};
+use compiler::{lower, parse, template, validate};
+
+fn template_workflow() -> Workflow {
+    parse::parse(include_str!("fixtures/template_workflow.json")).expect("Should parse")
+}
+
+fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn instantiate_substitutes_params_and_defaults() {
+    let workflow = template::instantiate(&template_workflow(), &params(&[("WALLET", "0xabc")]))
+        .expect("Should instantiate");
+
+    let WorkflowNode::CronTrigger(trigger) = &workflow.nodes[0] else {
+        panic!("expected cron trigger");
+    };
+    assert_eq!(trigger.data.config.schedule, "0 */10 * * * *");
+    let WorkflowNode::HttpRequest(http) = &workflow.nodes[1] else {
+        panic!("expected HTTP request");
+    };
+    assert_eq!(
+        http.data.config.url,
+        "https://api.example.com/balance/0xabc"
+    );
+    assert_eq!(http.id, "h1");
+    assert!(workflow.global_config.template_params.is_none());
+}
+
+#[test]
+fn missing_required_and_unknown_params_are_errors() {
+    let errors = template::instantiate(&template_workflow(), &params(&[("WALET", "0xabc")]))
+        .expect_err("Should fail");
+
+    let codes: Vec<&str> = errors.iter().map(|e| e.code.as_str()).collect();
+    assert_eq!(codes, vec!["T002", "T001"], "{:?}", errors);
+    assert!(errors[0].message.contains("'WALET'"));
+    assert!(errors[1].message.contains("'WALLET'"));
+}
+
+#[test]
+fn id_prefix_keeps_references_valid_through_lowering() {
+    let workflow = template::instantiate_with_id_prefix(
+        &template_workflow(),
+        &params(&[("WALLET", "0xabc"), ("SCHEDULE", "0 0 * * * *")]),
+        "balance-",
+    )
+    .expect("Should instantiate");
+
+    let ids: Vec<&str> = workflow.nodes.iter().map(|n| n.id()).collect();
+    assert_eq!(ids, vec!["balance-t1", "balance-h1", "balance-r1"]);
+    assert_eq!(workflow.edges[1].id, "balance-e2");
+    assert_eq!(workflow.edges[1].source, "balance-h1");
+    let WorkflowNode::Return(ret) = &workflow.nodes[2] else {
+        panic!("expected return");
+    };
+    assert_eq!(ret.data.config.return_expression, "{{balance-h1.body}}");
+
+    let graph = parse::WorkflowGraph::build(&workflow).expect("Should build graph");
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let ir = lower::lower(&workflow, &graph).expect("Should lower");
+    assert!(validate_ir(&ir).is_empty(), "{:?}", validate_ir(&ir));
+}
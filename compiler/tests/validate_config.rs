@@ -0,0 +1,147 @@
+//! Integration tests for config document checks (C001–C005, W014).
+
+use compiler::ir::types::{ConfigField, WorkflowIR, ZodType};
+use compiler::lower;
+use compiler::parse;
+use compiler::validate::config::check_config_values;
+
+/// The linear workflow (cron trigger, `schedule` field with a default) plus
+/// the given fields.
+fn ir_with_fields(fields: Vec<(&str, ZodType)>) -> WorkflowIR {
+    let workflow = parse::parse(include_str!("fixtures/linear_workflow.json")).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let mut ir = lower::lower(&workflow, &graph).unwrap();
+    for (name, zod_type) in fields {
+        ir.config_schema.push(ConfigField {
+            name: name.into(),
+            zod_type,
+            default_value: None,
+            description: None,
+            referenced_by: vec![],
+            optional_in_config: false,
+        });
+    }
+    ir
+}
+
+fn codes(ir: &WorkflowIR, config: &str) -> Vec<&'static str> {
+    check_config_values(ir, config)
+        .into_iter()
+        .map(|e| e.code)
+        .collect()
+}
+
+#[test]
+fn matching_config_passes() {
+    let ir = ir_with_fields(vec![
+        ("apiUrl", ZodType::String),
+        ("threshold", ZodType::Number),
+        ("mode", ZodType::Raw(r#"z.enum(["fast", "safe"])"#.into())),
+        ("tags", ZodType::Raw("z.array(z.string())".into())),
+    ]);
+    let config = r#"{
+        "schedule": "0 */5 * * * *",
+        "apiUrl": "https://api.example.com",
+        "threshold": 10,
+        "mode": "safe",
+        "tags": ["a"]
+    }"#;
+    assert!(check_config_values(&ir, config).is_empty());
+}
+
+#[test]
+fn c002_missing_required_field() {
+    let ir = ir_with_fields(vec![("apiUrl", ZodType::String)]);
+    // `schedule` has a default, so leaving it out is fine.
+    let errors = check_config_values(&ir, "{}");
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "C002");
+    assert!(
+        errors[0].message.contains("'apiUrl'"),
+        "{}",
+        errors[0].message
+    );
+}
+
+#[test]
+fn c003_wrong_type() {
+    let ir = ir_with_fields(vec![
+        ("threshold", ZodType::Number),
+        ("mode", ZodType::Raw(r#"z.enum(["fast", "safe"])"#.into())),
+        (
+            "limits",
+            ZodType::Raw("z.object({ max: z.number() })".into()),
+        ),
+    ]);
+    let errors = check_config_values(
+        &ir,
+        r#"{ "threshold": "10", "mode": "slow", "limits": [1] }"#,
+    );
+    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert!(errors.iter().all(|e| e.code == "C003"), "{:?}", errors);
+    assert_eq!(
+        messages,
+        [
+            "Config field 'threshold' must be a number, got a string",
+            "Config field 'mode' must be one of fast, safe, got \"slow\"",
+            "Config field 'limits' must be an object, got an array",
+        ]
+    );
+}
+
+#[test]
+fn c004_extra_key() {
+    let ir = ir_with_fields(vec![]);
+    let errors = check_config_values(&ir, r#"{ "apiUrl": "https://api.example.com" }"#);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "C004");
+    assert!(errors[0].message.contains("'apiUrl'"));
+}
+
+#[test]
+fn c005_cron_schedule_syntax() {
+    let ir = ir_with_fields(vec![]);
+    for schedule in [
+        "0 */5 * * * *",
+        "*/10 * * * *",
+        "TZ=Europe/Paris 0 9 * * MON-FRI",
+    ] {
+        let config = format!(r#"{{ "schedule": "{}" }}"#, schedule);
+        assert!(codes(&ir, &config).is_empty(), "{}", schedule);
+    }
+    for schedule in [
+        "every 5 minutes",
+        "0 61 * * *",
+        "* * * * * * *",
+        "0 */0 * * *",
+    ] {
+        let config = format!(r#"{{ "schedule": "{}" }}"#, schedule);
+        assert_eq!(codes(&ir, &config), ["C005"], "{}", schedule);
+    }
+}
+
+#[test]
+fn w014_raw_field_is_reported_unchecked() {
+    let ir = ir_with_fields(vec![(
+        "window",
+        ZodType::Raw("z.union([z.string(), z.number()])".into()),
+    )]);
+    let errors = check_config_values(&ir, r#"{ "window": true }"#);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "W014");
+    assert!(errors[0].message.contains("not checked"));
+    // A nullable custom type may be null or left out.
+    let ir = ir_with_fields(vec![(
+        "limits",
+        ZodType::Raw("z.object({}).nullable()".into()),
+    )]);
+    assert!(codes(&ir, r#"{ "limits": null }"#).is_empty());
+    assert!(codes(&ir, "{}").is_empty());
+}
+
+#[test]
+fn c001_config_must_be_a_json_object() {
+    let ir = ir_with_fields(vec![]);
+    assert_eq!(codes(&ir, "[1, 2]"), ["C001"]);
+    assert_eq!(codes(&ir, "{ \"schedule\": "), ["C001"]);
+}
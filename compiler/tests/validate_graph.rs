@@ -1,4 +1,4 @@
-//! Integration tests for graph-level validation rules (V001–V010).
+//! Integration tests for graph-level validation rules (V001–V014, G001).
 
 use compiler::parse;
 use compiler::validate;
@@ -68,6 +68,49 @@ fn v005_unreachable_node() {
     );
 }
 
+#[test]
+fn v006_edge_into_trigger_is_named() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+
+    // A second root, wired into the trigger.
+    workflow.nodes.push(
+        serde_json::from_value(serde_json::json!({
+            "id": "x1",
+            "type": "return",
+            "position": { "x": 0, "y": 0 },
+            "data": { "label": "Stray", "config": { "returnExpression": "\"ok\"" } }
+        }))
+        .unwrap(),
+    );
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e-into-trigger".into(),
+        source: "x1".into(),
+        target: "t1".into(),
+        source_handle: None,
+        target_handle: None,
+        label: None,
+    });
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+
+    let v006: Vec<_> = errors.iter().filter(|e| e.code == "V006").collect();
+    assert_eq!(v006.len(), 1, "{errors:?}");
+    assert!(v006[0].message.contains("'e-into-trigger'"), "{:?}", v006);
+    assert_eq!(
+        compiler::error::format_json_path(&v006[0].path),
+        "edges[2].target"
+    );
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.code == "V005" && e.message.contains("'x1' has no incoming edges")),
+        "{errors:?}"
+    );
+}
+
 #[test]
 fn v008_if_missing_handle() {
     let json = include_str!("fixtures/if_missing_handle.json");
@@ -93,3 +136,180 @@ fn v010_self_loop() {
         errors
     );
 }
+
+#[test]
+fn v003_exact_duplicate_edge() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let mut duplicate = workflow.edges[0].clone();
+    duplicate.id = "e1-copy".into();
+    workflow.edges.push(duplicate);
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(
+        errors.iter().any(|e| e.code == "V003"),
+        "Should flag duplicate edge: {:?}",
+        errors
+    );
+}
+
+#[test]
+fn v011_source_handle_not_exposed_by_node() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    // h1 is an httpRequest — it only has an "output" handle
+    workflow.edges[1].source_handle = Some("true".into());
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.code == "V011" && e.node_id.as_deref() == Some("h1")),
+        "Should flag unknown source handle: {:?}",
+        errors
+    );
+}
+
+#[test]
+fn v012_if_handle_with_two_targets() {
+    let json = include_str!("fixtures/example_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow.edges.push(parse::types::WorkflowEdge {
+        id: "e8".into(),
+        source: "condition-1".into(),
+        target: "return-1".into(),
+        source_handle: Some("true".into()),
+        target_handle: None,
+        label: None,
+    });
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.code == "V012" && e.node_id.as_deref() == Some("condition-1")),
+        "Should flag conflicting If handle: {:?}",
+        errors
+    );
+}
+
+#[test]
+fn v013_too_many_distinct_kv_keys() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    let kv_get = |i: usize, key: String| -> parse::types::WorkflowNode {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("kv-{i}"),
+            "type": "kvGet",
+            "position": { "x": 0, "y": 0 },
+            "data": { "label": "State", "config": { "key": key } }
+        }))
+        .unwrap()
+    };
+    // 16 distinct keys, one of them read twice.
+    for i in 0..16 {
+        workflow.nodes.push(kv_get(i, format!("key-{i}")));
+    }
+    workflow.nodes.push(kv_get(16, "key-0".into()));
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(!errors.iter().any(|e| e.code == "V013"), "{:?}", errors);
+
+    workflow.nodes.push(kv_get(17, "key-16".into()));
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    let v013: Vec<_> = errors.iter().filter(|e| e.code == "V013").collect();
+    assert_eq!(v013.len(), 1, "{:?}", errors);
+    assert_eq!(v013[0].node_id.as_deref(), Some("kv-17"));
+    assert!(
+        v013[0].message.contains("17 distinct KV keys"),
+        "{}",
+        v013[0].message
+    );
+}
+
+#[test]
+fn v014_node_id_with_step_separator() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let workflow = parse::parse(json).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    assert!(!errors.iter().any(|e| e.code == "V014"), "{:?}", errors);
+
+    let mut json: serde_json::Value = serde_json::from_str(json).unwrap();
+    let old_id = json["nodes"][1]["id"].as_str().unwrap().to_string();
+    let new_id = format!("{old_id}___encode");
+    json["nodes"][1]["id"] = new_id.clone().into();
+    for edge in json["edges"].as_array_mut().unwrap() {
+        for end in ["source", "target"] {
+            if edge[end] == old_id.as_str() {
+                edge[end] = new_id.clone().into();
+            }
+        }
+    }
+    let workflow = parse::parse(&json.to_string()).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+    let v014: Vec<_> = errors.iter().filter(|e| e.code == "V014").collect();
+    assert_eq!(v014.len(), 1, "{:?}", errors);
+    assert_eq!(v014[0].node_id.as_deref(), Some(new_id.as_str()));
+    assert_eq!(
+        compiler::error::format_json_path(&v014[0].path),
+        "nodes[1].id"
+    );
+}
+
+#[test]
+fn node_rule_error_path_starts_at_workflow_root() {
+    let mut json: serde_json::Value =
+        serde_json::from_str(include_str!("fixtures/linear_workflow.json")).unwrap();
+    json["nodes"].as_array_mut().unwrap().swap(1, 2);
+    json["nodes"][2]["data"]["config"]["url"] = "".into();
+    let workflow = parse::parse(&json.to_string()).unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+    let errors = validate::validate_graph(&workflow, &graph);
+
+    let n004 = errors.iter().find(|e| e.code == "N004").expect("N004");
+    assert_eq!(
+        compiler::error::format_json_path(&n004.path),
+        "nodes[2].data.config.url"
+    );
+    // Single-node validation has no index to anchor to.
+    let node_errors = validate::validate_node(&workflow.nodes[2], &workflow.global_config);
+    assert_eq!(node_errors[0].to_json()["path"], "data.config.url");
+}
+
+#[test]
+fn g001_secrets_sharing_an_env_variable() {
+    let json = include_str!("fixtures/linear_workflow.json");
+    let mut workflow = parse::parse(json).unwrap();
+    workflow.global_config.secrets = serde_json::from_value(serde_json::json!([
+        {
+            "name": "KYC_KEY",
+            "envVariable": "KYC_KEY_VAR",
+            "envVariableStaging": "KYC_KEY_STAGING",
+            "envVariableProduction": "KYC_KEY_PROD"
+        },
+        { "name": "AUDIT_KEY", "envVariable": "AUDIT_KEY_VAR", "envVariableStaging": "KYC_KEY_STAGING" },
+        { "name": "API_KEY", "envVariable": "API_KEY_VAR" }
+    ]))
+    .unwrap();
+    let graph = parse::WorkflowGraph::build(&workflow).unwrap();
+
+    let errors = validate::validate_graph(&workflow, &graph);
+    let g001: Vec<_> = errors.iter().filter(|e| e.code == "G001").collect();
+    assert_eq!(g001.len(), 1, "{:?}", errors);
+    assert_eq!(
+        g001[0].message,
+        "Secrets 'KYC_KEY' and 'AUDIT_KEY' both read environment variable 'KYC_KEY_STAGING' in staging"
+    );
+    assert_eq!(
+        g001[0].to_json()["path"],
+        "globalConfig.secrets[1].envVariableStaging"
+    );
+
+    // Production names may repeat another secret's staging name.
+    workflow.global_config.secrets[1].env_variable_staging = None;
+    workflow.global_config.secrets[1].env_variable_production = Some("KYC_KEY_STAGING".into());
+    assert!(validate::validate_graph(&workflow, &graph).is_empty());
+}
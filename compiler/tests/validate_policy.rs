@@ -0,0 +1,149 @@
+//! Integration tests for organization policy checks (R001–R005).
+
+#[allow(dead_code)]
+mod helpers;
+
+use compiler::ir::*;
+use compiler::validate::policy::{PolicyRuleset, apply_policy};
+use helpers::*;
+
+fn ruleset(json: &str) -> PolicyRuleset {
+    serde_json::from_str(json).expect("valid ruleset")
+}
+
+/// An HTTP GET whose URL is `https://<host_prefix>{{config.path}}`.
+fn templated_get(prefix: &str) -> Operation {
+    let mut op = http_get("");
+    if let Operation::HttpRequest(http) = &mut op {
+        http.url = ValueExpr::Template {
+            parts: vec![
+                TemplatePart::Lit {
+                    value: prefix.into(),
+                },
+                TemplatePart::Expr {
+                    value: ValueExpr::config("path"),
+                },
+            ],
+        };
+    }
+    op
+}
+
+#[test]
+fn deny_mainnet_writes_fires_on_a_mainnet_evm_write() {
+    let ir = ir_with_steps_and_deps(
+        vec![
+            make_step(
+                "write-test",
+                evm_write_op("evm_sepolia", "0xabc", ValueExpr::string("0x")),
+            ),
+            make_step(
+                "write-main",
+                evm_write_op("evm_mainnet", "0xabc", ValueExpr::string("0x")),
+            ),
+        ],
+        vec![],
+        vec![
+            ("ethereum-testnet-sepolia", "evm_sepolia", false),
+            ("ethereum-mainnet", "evm_mainnet", false),
+        ],
+    );
+    let errors = apply_policy(&ir, &ruleset(r#"{ "evm": { "denyMainnetWrites": true } }"#));
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "R002");
+    assert_eq!(errors[0].step_id.as_deref(), Some("write-main"));
+    assert_eq!(
+        errors[0].message,
+        "Policy rule 'evm.denyMainnetWrites': step 'write-main' writes to mainnet chain 'ethereum-mainnet'"
+    );
+}
+
+#[test]
+fn host_allowlist_checks_the_literal_prefix_of_a_templated_url() {
+    let policy = ruleset(r#"{ "http": { "allowHosts": ["api.example.com", "*.trusted.io"] } }"#);
+
+    let ir = ir_with_steps(vec![
+        make_step("allowed", templated_get("https://api.example.com/v1/")),
+        make_step("wildcard", templated_get("https://data.trusted.io/")),
+        make_step("blocked", templated_get("https://evil.example.net/")),
+    ]);
+    let errors = apply_policy(&ir, &policy);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "R001");
+    assert_eq!(errors[0].step_id.as_deref(), Some("blocked"));
+    assert!(
+        errors[0]
+            .message
+            .starts_with("Policy rule 'http.allowHosts'")
+            && errors[0].message.contains("'evil.example.net'"),
+        "{}",
+        errors[0].message
+    );
+
+    // The host itself is templated, so it cannot be checked and is blocked.
+    let ir = ir_with_steps(vec![make_step("dynamic", templated_get("https://api."))]);
+    let errors = apply_policy(&ir, &policy);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0]
+            .message
+            .contains("not known before the workflow runs"),
+        "{}",
+        errors[0].message
+    );
+}
+
+#[test]
+fn provider_restriction_rejects_other_providers() {
+    let ir = ir_with_steps(vec![
+        make_step("ask-claude", ai_call_op("claude", "AI_KEY")),
+        make_step("ask-gpt", ai_call_op("openai", "AI_KEY")),
+    ]);
+    let policy = ruleset(
+        r#"{
+            "ai": { "allowProviders": ["anthropic"] },
+            "http": { "allowHosts": ["api.openai.com"] }
+        }"#,
+    );
+    let errors = apply_policy(&ir, &policy);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, "R004");
+    assert_eq!(errors[0].step_id.as_deref(), Some("ask-gpt"));
+    assert_eq!(
+        errors[0].message,
+        "Policy rule 'ai.allowProviders': step 'ask-gpt' uses AI provider 'openai', which is not allowed"
+    );
+}
+
+#[test]
+fn workflow_within_the_ruleset_passes() {
+    let ir = ir_with_steps_and_deps(
+        vec![
+            make_step("fetch", http_get("https://api.example.com/price")),
+            make_step("read", evm_read_op("evm_sepolia", "0xabc", "balanceOf")),
+            make_step(
+                "write",
+                evm_write_op("evm_sepolia", "0xabc", ValueExpr::string("0x")),
+            ),
+            make_step("ask", ai_call_op("openai", "AI_KEY")),
+        ],
+        vec![],
+        vec![("ethereum-testnet-sepolia", "evm_sepolia", false)],
+    );
+    let policy = ruleset(
+        r#"{
+            "http": { "allowHosts": ["api.example.com", "api.openai.com"], "denyHosts": ["evil.io"] },
+            "evm": {
+                "allowChains": ["ethereum-testnet-sepolia"],
+                "denyMainnetWrites": true,
+                "maxGasLimit": 1000000
+            },
+            "operations": { "deny": ["KvWrite"] },
+            "ai": { "denyProviders": ["google"], "maxTokens": 1024 },
+            "budgets": { "maxHttpCalls": 2, "maxEvmWrites": 1 }
+        }"#,
+    );
+    let errors = apply_policy(&ir, &policy);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert!(apply_policy(&ir, &PolicyRuleset::default()).is_empty());
+}
@@ -7,9 +7,18 @@ export interface GeneratedFile {
 /** A compiler error returned from validation or compilation. */
 export interface CompilerError {
   code: string;
+  severity: "error" | "warning" | "info"; // Follows the code: W-codes warn, I-codes inform
   phase: string;
   message: string;
   node_id: string | null;
+  related: RelatedDiagnostic[];
+  path: string | null; // e.g. "nodes[2].data.config.url"; node-relative from validate_node()
+}
+
+/** A follow-up note on a compiler error, e.g. which HTTP calls could be batched under E009. */
+export interface RelatedDiagnostic {
+  message: string;
+  node_ids: string[];
 }
 
 /** Successful compilation result. */
@@ -51,7 +51,7 @@ export interface NodeSettings {
     waitBetweenTries: number; // ms, default 1000
   };
   onError?: OnErrorBehavior; // default 'stop'
-  notes?: string; // User-facing documentation note
+  notes?: string; // User-facing documentation note; emitted as a doc comment and in audit.json
   executeOnce?: boolean; // Only process first item (for batch scenarios)
 }
 
@@ -111,7 +111,8 @@ export interface WorkflowEdge {
   source: string; // Source node ID
   target: string; // Target node ID
   sourceHandle?: string; // Output port name (for multi-output nodes like IfElse)
-  targetHandle?: string; // Input port name (for multi-input nodes like Merge)
+  targetHandle?: string; // Input port name (for multi-input nodes like Merge); "data" marks the edge an ABI Decode reads from
+  label?: string; // Text shown on the edge; on an If node's outgoing edge it names the arm in generated code
 }
 
 /** A user-defined RPC endpoint for a specific blockchain */
@@ -125,12 +126,34 @@ export interface GlobalConfig {
   isTestnet: boolean;
   secrets: SecretReference[];
   rpcs: RpcEntry[];
+  defaultChainSelector?: string; // Fallback chain for EVM nodes without chainSelectorName
+  autoReturn?: AutoReturnMode; // Paths ending without Return/Error (default "autoString")
+  defaultCacheMaxAge?: number; // cacheMaxAge for GET/HEAD requests that leave it unset
+  templateParams?: TemplateParam[]; // Parameters of a workflow template, used as ${param:NAME}
+  maxKvOperations?: number; // KV reads + writes allowed per execution (default 10)
 }
 
+/** A value supplied when instantiating a workflow template */
+export interface TemplateParam {
+  name: string;
+  description?: string;
+  required?: boolean;
+  default?: string;
+}
+
+/**
+ * "error": compile error naming the node each open path ends on
+ * "autoString": append `return "Workflow completed"`
+ * "autoSummary": return the JSON of the last step's output on each open path
+ */
+export type AutoReturnMode = "error" | "autoString" | "autoSummary";
+
 /** Reference to a secret in secrets.yaml */
 export interface SecretReference {
   name: string; // Logical name used in code
   envVariable: string; // Environment variable name in .env
+  envVariableStaging?: string; // Overrides envVariable for staging
+  envVariableProduction?: string; // Overrides envVariable for production
 }
 
 /** Complete workflow definition */
@@ -139,6 +162,7 @@ export interface Workflow {
   name: string;
   description?: string;
   version: string;
+  schemaVersion?: number; // Saved config shape; the compiler migrates older documents (1 when absent)
   nodes: WorkflowNode[];
   edges: WorkflowEdge[];
   globalConfig: GlobalConfig;
@@ -263,10 +287,10 @@ export type HttpAuthConfig =
 
 export interface HttpRequestConfig {
   method: HttpMethod;
-  url: string; // Supports {{variable}} interpolation
+  url: string; // Supports {{variable}} and {{secrets.NAME}} interpolation
   authentication?: HttpAuthConfig;
-  headers?: Record<string, string>;
-  queryParameters?: Record<string, string>;
+  headers?: Record<string, string>; // Values may read a secret: {{secrets.NAME}}
+  queryParameters?: Record<string, string>; // No {{secrets.NAME}} here
   body?: {
     contentType: "json" | "formUrlEncoded" | "raw";
     data: string; // Template with {{variables}}
@@ -275,8 +299,8 @@ export interface HttpRequestConfig {
   timeout?: number; // ms (max 10000 per CRE)
   expectedStatusCodes?: number[]; // Default [200]
   responseFormat?: "json" | "text" | "binary";
-  followRedirects?: boolean; // Default true
-  ignoreSSL?: boolean; // For dev/testing
+  followRedirects?: boolean; // Default true; false is rejected (N021), CRE always follows redirects
+  ignoreSSL?: boolean; // No effect in CRE (TLS is always verified); rejected on mainnet (N025), warning W008 on testnet
 }
 
 export type HttpRequestNode = BaseNode<"httpRequest", HttpRequestConfig>;
@@ -290,10 +314,10 @@ export interface HttpRequestOutput {
 // -----------------------------------------------------------------------------
 
 /** EVM Read - read from smart contract (view/pure functions) */
-export type BlockNumber = "latest" | "finalized" | string; // string for custom bigint
+export type BlockNumber = "latest" | "finalized" | string; // any case; string for a decimal block number or a {{node.field}} reference (N033)
 
 export interface EvmReadConfig {
-  chainSelectorName: ChainSelectorName;
+  chainSelectorName?: ChainSelectorName; // Omitted: the workflow's defaultChainSelector; never empty (N005)
   contractAddress: string;
   abi: AbiFunction;
   functionName: string;
@@ -316,7 +340,7 @@ export type EvmReadOutput = Record<string, unknown>; // Decoded return values
 
 /** EVM Write - write to blockchain via CRE Forwarder */
 export interface EvmWriteConfig {
-  chainSelectorName: ChainSelectorName;
+  chainSelectorName?: ChainSelectorName; // Omitted: the workflow's defaultChainSelector; never empty (N006)
   receiverAddress: string; // Consumer contract address (must implement IReceiver)
   gasLimit: string; // Max "5000000" per CRE
   encodedData: string; // "{{abiEncode_1.encoded}}" reference from upstream AbiEncode node
@@ -332,6 +356,49 @@ export interface EvmWriteOutput {
 
 // -----------------------------------------------------------------------------
 
+/** Broadcast Write - encode once, write the same report to several chains */
+export interface BroadcastWriteConfig {
+  chainSelectorNames: ChainSelectorName[]; // One EVM write per chain, in this order
+  receiverAddress?: string; // Receiver on chains without a receiverAddresses entry
+  receiverAddresses?: Record<string, string>; // Per-chain receiver, keyed by chain selector name
+  gasLimit: string; // Max "5000000" per CRE, applied to every write
+  abiParams: AbiParameter[];
+  dataMapping: {
+    paramName: string;
+    source: string; // "{{previousNode.fieldName}}"
+  }[];
+  functionName?: string; // As in AbiEncodeConfig
+}
+
+export type BroadcastWriteNode = BaseNode<"broadcastWrite", BroadcastWriteConfig>;
+
+export interface BroadcastWriteOutput {
+  txHashes: string[]; // Hex, in chainSelectorNames order
+}
+
+// -----------------------------------------------------------------------------
+
+/** KV Get - read a value stored by an earlier execution */
+export interface KvGetConfig {
+  key: string; // e.g. "lastBlock" or "price-{{config.asset}}"
+}
+
+export type KvGetNode = BaseNode<"kvGet", KvGetConfig>;
+
+export type KvGetOutput = string | undefined; // undefined until a kvSet stores the key
+
+// -----------------------------------------------------------------------------
+
+/** KV Set - store a value for later executions */
+export interface KvSetConfig {
+  key: string; // At most 16 distinct keys per workflow
+  value: string; // Stored as a string, e.g. "{{read-1.blockNumber}}"
+}
+
+export type KvSetNode = BaseNode<"kvSet", KvSetConfig>;
+
+// -----------------------------------------------------------------------------
+
 // =============================================================================
 // TRANSFORM NODES (Data Processing)
 // =============================================================================
@@ -344,6 +411,7 @@ export interface CodeNodeConfig {
   language?: "typescript"; // Explicit, future-proof (default 'typescript')
   executionMode: CodeExecutionMode;
   inputVariables: string[];
+  iterateOver?: string; // Input variable mapped over in runOnceForEach mode
   timeout?: number; // Max execution time (ms)
   outputFields?: { key: string; type: DataSchemaType }[]; // Manually declared output fields
 }
@@ -361,6 +429,7 @@ export interface AbiEncodeConfig {
     paramName: string;
     source: string; // "{{previousNode.fieldName}}"
   }[];
+  functionName?: string; // If set, encodeFunctionData (with selector) instead of encodeAbiParameters
 }
 
 export type AbiEncodeNode = BaseNode<"abiEncode", AbiEncodeConfig>;
@@ -402,12 +471,22 @@ export type MergeStrategy =
 
 export interface MergeConfig {
   strategy: MergeStrategy;
-  numberOfInputs?: number; // Default 2, max 5
+  numberOfInputs?: number; // Default 2, max 5; a merge joining independent chains must have exactly this many incoming edges
   clashHandling?: "preferInput1" | "preferInput2" | "addSuffix";
 }
 
 export type MergeNode = BaseNode<"merge", MergeConfig>;
 
+// -----------------------------------------------------------------------------
+
+/** Math - arithmetic over previous outputs without a Code Node */
+export interface MathConfig {
+  expression: string; // e.g. "{{read-1.value}} * 1e18 / 2" — supports + - * / % and parentheses
+  mode?: "auto" | "number" | "bigint"; // Default "auto": bigint when an operand is an EVM int output
+}
+
+export type MathNode = BaseNode<"math", MathConfig>;
+
 // =============================================================================
 // CONTROL FLOW NODES
 // =============================================================================
@@ -437,10 +516,18 @@ export interface Condition {
   value?: string; // Not needed for exists/isEmpty operators
 }
 
+/** Nested group of conditions with its own combinator, e.g. (a AND b) inside an OR list */
+export interface ConditionGroup {
+  conditions: (Condition | ConditionGroup)[]; // At least one; at most 4 levels deep
+  combineWith: "and" | "or";
+}
+
 /** Filter - remove items matching a condition */
 export interface FilterConfig {
-  conditions: Condition[];
+  conditions: (Condition | ConditionGroup)[];
   combineWith: "and" | "or";
+  nonMatchBehavior?: "earlyReturn" | "skip"; // On a failed condition: return nonMatchMessage (default) or skip the rest of the block
+  nonMatchMessage?: string; // Returned in earlyReturn mode; supports {{...}} references (default "Filter condition not met")
 }
 
 export type FilterNode = BaseNode<"filter", FilterConfig>;
@@ -451,7 +538,7 @@ export type FilterNode = BaseNode<"filter", FilterConfig>;
 
 /** If - route to different branches based on a true/false condition */
 export interface IfConfig {
-  conditions: Condition[];
+  conditions: (Condition | ConditionGroup)[];
   combineWith: "and" | "or";
 }
 
@@ -465,17 +552,20 @@ export type IfNode = BaseNode<"if", IfConfig>;
 
 /** AI Node - call an AI model for inference */
 export interface AINodeConfig {
-  provider: string; // e.g. "openai" | "anthropic" | "google"
+  provider: string; // "openai" | "anthropic" | "google" | "openai-compatible" (case-insensitive; "gpt"/"claude"/"gemini" aliases)
+  // baseUrl / model may be left empty for openai, anthropic and google to use the provider default
   baseUrl: string;
   model: string;
   apiKeySecret: string; // References secret name
   systemPrompt: string;
   userPrompt: string; // Template with {{variables}}
-  temperature?: number; // 0-2 (default 0.7)
-  maxTokens?: number; // Max output tokens
+  temperature?: number; // 0-2, 0-1 for Anthropic (default 0.7)
+  maxTokens?: number; // Max output tokens, 1-128000 (required for Anthropic)
   responseFormat?: "text" | "json";
   timeout?: number; // ms
-  maxRetries?: number; // Default 3
+  maxRetries?: number; // Deprecated (W018): no effect, removed in compiler 0.3.0
+  consensus?: "content" | "identical" | "custom"; // Default "content": nodes agree on the generated content, not ids/timestamps/usage
+  consensusExpression?: string; // Required in "custom" mode (N032), e.g. "consensusIdenticalAggregation()"
 }
 
 export type AINode = BaseNode<"ai", AINodeConfig>;
@@ -514,11 +604,15 @@ export type NodeType =
   | "httpRequest"
   | "evmRead"
   | "evmWrite"
+  | "broadcastWrite"
+  | "kvGet"
+  | "kvSet"
   // Transforms
   | "codeNode"
   | "abiEncode"
   | "abiDecode"
   | "merge"
+  | "math"
   // Control Flow
   | "filter"
   | "if"
@@ -547,11 +641,15 @@ export const NODE_TYPE_TO_CATEGORY: Record<NodeType, NodeCategory> = {
   httpRequest: "action",
   evmRead: "action",
   evmWrite: "action",
+  broadcastWrite: "action",
+  kvGet: "action",
+  kvSet: "action",
   // Transforms
   codeNode: "transform",
   abiEncode: "transform",
   abiDecode: "transform",
   merge: "transform",
+  math: "transform",
   // Control Flow
   filter: "controlFlow",
   if: "controlFlow",
@@ -577,11 +675,15 @@ export type WorkflowNode =
   | HttpRequestNode
   | EvmReadNode
   | EvmWriteNode
+  | BroadcastWriteNode
+  | KvGetNode
+  | KvSetNode
   // Transforms
   | CodeNodeNode
   | AbiEncodeNode
   | AbiDecodeNode
   | MergeNode
+  | MathNode
   // Control Flow
   | FilterNode
   | IfNode
@@ -604,12 +706,12 @@ export function isTriggerNode(
 
 /** Check if a node is an action node (capability) */
 export function isActionNode(node: WorkflowNode): boolean {
-  return ["httpRequest", "evmRead", "evmWrite"].includes(node.type);
+  return ["httpRequest", "evmRead", "evmWrite", "broadcastWrite", "kvGet", "kvSet"].includes(node.type);
 }
 
 /** Check if a node is a transform node */
 export function isTransformNode(node: WorkflowNode): boolean {
-  return ["codeNode", "abiEncode", "abiDecode", "merge"].includes(node.type);
+  return ["codeNode", "abiEncode", "abiDecode", "merge", "math"].includes(node.type);
 }
 
 /** Check if a node is a control flow node */